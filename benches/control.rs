@@ -29,9 +29,11 @@ Description: GNU core utilities
 ";
 
 pub fn parse_benchmark(c: &mut Criterion) {
-    c.bench_function("parse test", |b| b.iter(||{
-        ControlFile::parse(black_box(TEXT)).unwrap();
-    }));
+    c.bench_function("parse test", |b| {
+        b.iter(|| {
+            ControlFile::parse(black_box(TEXT)).unwrap();
+        })
+    });
 }
 
 criterion_group!(benches, parse_benchmark);
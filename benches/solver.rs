@@ -43,10 +43,11 @@ pub fn parse_benchmark(c: &mut Criterion) {
         b.iter(|| {
             let packages = vec![Packages::new(debrepo::null_provider(), data.clone())
                 .expect("failed to parse packages")];
-            let mut uni = Universe::new("amd64", packages.into_iter()).expect("universe");
+            let mut uni = Universe::new("amd64", packages).expect("universe");
             let problem = uni.problem(
                 vec![Dependency::try_from("task-gnome-desktop | task-kde-desktop").unwrap()],
                 vec![],
+                vec![],
             );
             let _ = match uni.solve(problem) {
                 Ok(solution) => solution,
@@ -0,0 +1,82 @@
+//! The regression baseline: parsing, building the universe, solving the
+//! standard problems and sorting their solutions over bookworm main.
+//!
+//! The fixtures are fetched on the first run, see `debrepo::bench`.
+
+use async_std::task;
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use debrepo::{bench, null_provider, ControlParser, Package, Packages, Universe, UniverseBuilder};
+use std::sync::Arc;
+
+fn universe(data: &Arc<str>) -> Universe<Arc<str>> {
+    let packages = Packages::new(null_provider(), data.clone()).expect("failed to parse packages");
+    Universe::new("amd64", [packages]).expect("universe")
+}
+
+pub fn suite(c: &mut Criterion) {
+    let data = task::block_on(bench::BOOKWORM_MAIN.load()).expect("fixture");
+
+    c.bench_function("parse", |b| {
+        b.iter(|| {
+            let mut parser = ControlParser::new(&data);
+            let mut count = 0;
+            while Package::try_parse_from(&mut parser)
+                .expect("failed to parse package")
+                .is_some()
+            {
+                count += 1;
+            }
+            black_box(count);
+        })
+    });
+
+    // the indexes sized by the number of packages, against growing them
+    let mut g = c.benchmark_group("universe");
+    for (name, capacity) in [("pre-sized", None), ("growing", Some(0))] {
+        g.bench_function(name, |b| {
+            b.iter(|| {
+                let packages =
+                    Packages::new(null_provider(), data.clone()).expect("failed to parse packages");
+                let mut builder = UniverseBuilder::new("amd64").packages([packages]);
+                if let Some(capacity) = capacity {
+                    builder = builder.capacity(capacity);
+                }
+                black_box(builder.build().expect("universe"))
+            })
+        });
+    }
+    g.finish();
+
+    let mut g = c.benchmark_group("solve");
+    g.measurement_time(std::time::Duration::from_secs(10));
+    for problem in bench::PROBLEMS {
+        g.bench_function(problem.name, |b| {
+            b.iter_batched(
+                || universe(&data),
+                |mut uni| {
+                    let problem = uni.problem(problem.requirements(), vec![], vec![]);
+                    black_box(uni.solve(problem).expect("solution"))
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    g.finish();
+
+    let mut g = c.benchmark_group("sort_solution");
+    for problem in bench::PROBLEMS {
+        let mut uni = universe(&data);
+        let requirements = uni.problem(problem.requirements(), vec![], vec![]);
+        let solution = uni.solve(requirements).expect("solution");
+        g.bench_function(problem.name, |b| {
+            b.iter(|| {
+                let mut solution = solution.clone();
+                black_box(uni.sort_solution(&mut solution).count())
+            })
+        });
+    }
+    g.finish();
+}
+
+criterion_group!(benches, suite);
+criterion_main!(benches);
@@ -2,19 +2,26 @@ use {
     anyhow::{anyhow, Result},
     async_std::{
         fs,
-        io::{self, prelude::*},
+        io::prelude::*,
         path::{Path, PathBuf},
     },
-    clap::{Parser, Subcommand},
+    clap::{Args, Parser, Subcommand},
     debrepo::{
-        DebRepo, Dependency, DeploymentFileSystem, HttpDebRepo, MutableControlStanza, Universe,
-        Version,
+        AuthConf, DebRepo, Dependency, DeploymentFileSystem, FSDebRepo, HttpDebRepo,
+        MutableControlStanza, NoProgress, Progress, Relations, SolvableId, Universe, Version,
     },
     futures::{
         future::join_all,
         stream::{FuturesUnordered, StreamExt},
     },
-    std::process::ExitCode,
+    std::{
+        collections::{hash_map::Entry, HashMap, VecDeque},
+        process::ExitCode,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    },
 };
 
 fn arch<'a>(a: &'a str) -> &'a str {
@@ -36,6 +43,83 @@ struct Cli {
     cmd: Commands,
 }
 
+/// The repository and the indexes to load
+#[derive(Args, Debug, Clone)]
+struct Source {
+    /// Architecture
+    #[arg(short, long, value_name = "ARCH", default_value = arch(std::env::consts::ARCH))]
+    arch: String,
+    /// Origin repository URL, or the directory of a local repository
+    #[arg(
+        short = 'u',
+        long = "url",
+        value_name = "URL",
+        default_value = "https://ftp.debian.org/debian/"
+    )]
+    origin: String,
+    /// Distribution name
+    #[arg(
+        short = 'd',
+        long = "distr",
+        value_name = "DISTR",
+        default_value = "sid"
+    )]
+    distr: String,
+    /// Component
+    #[arg(
+        short = 'c',
+        long = "component",
+        value_name = "COMPONENT",
+        default_value = "all"
+    )]
+    comp: String,
+    /// Keyring to verify the Release file with, instead of the Debian archive keyring
+    #[arg(short = 'k', long = "keyring", value_name = "FILE")]
+    keyring: Option<PathBuf>,
+    /// Do not verify the signature of the Release file
+    #[arg(long = "insecure", action, conflicts_with = "keyring")]
+    insecure: bool,
+    /// Report the downloads on stderr
+    #[arg(short = 'P', long = "progress", action)]
+    progress: bool,
+}
+
+impl Source {
+    fn progress(&self) -> Arc<dyn Progress> {
+        match self.progress {
+            true => Arc::new(Reporter::default()),
+            false => Arc::new(NoProgress),
+        }
+    }
+}
+
+// Reports the downloads the way apt does, e.g. `Get:1 pool/.../hello.deb [53 kB]`.
+#[derive(Default)]
+struct Reporter {
+    files: AtomicUsize,
+}
+
+impl Reporter {
+    fn get(&self, what: &str, size: u64) {
+        let n = self.files.fetch_add(1, Ordering::Relaxed) + 1;
+        eprintln!("Get:{} {} [{} B]", n, what, size);
+    }
+}
+
+impl Progress for Reporter {
+    fn on_index_fetch(&self, path: &str, size: u64) {
+        self.get(path, size)
+    }
+    fn on_package_fetch(&self, package: &str, _path: &str, size: u64) {
+        self.get(package, size)
+    }
+    fn on_solve_step(&self, step: usize, total: Option<usize>) {
+        if let Some(total) = total {
+            eprintln!("Reading package lists... {}/{}", step, total);
+        }
+    }
+}
+
 #[derive(Subcommand, Debug, Clone)]
 enum Commands {
     #[command(name = "fetch")]
@@ -61,89 +145,156 @@ enum Commands {
         #[arg(value_name = "COMPONENT", default_value = "main")]
         comp: String,
     },
+    /// Download the Release file and the Packages indexes into a local
+    /// repository usable with --url
+    #[command(name = "update")]
+    Update {
+        #[command(flatten)]
+        source: Source,
+        /// Target directory
+        #[arg(short = 't', long = "target", value_name = "DIR", default_value = ".")]
+        target: PathBuf,
+    },
     #[command(name = "solve")]
     Solve {
-        /// Architecture
-        #[arg(short, long, value_name = "ARCH", default_value = arch(std::env::consts::ARCH))]
-        arch: String,
-        /// Origin repository URL
-        #[arg(
-            short = 'u',
-            long = "url",
-            value_name = "URL",
-            default_value = "https://ftp.debian.org/debian/"
-        )]
-        origin: String,
-        /// Distribution name
-        #[arg(
-            short = 'd',
-            long = "distr",
-            value_name = "DISTR",
-            default_value = "sid"
-        )]
-        distr: String,
-        /// Component
-        #[arg(
-            short = 'c',
-            long = "component",
-            value_name = "COMPONENT",
-            default_value = "all"
-        )]
-        comp: String,
-        /// Fetch packages
+        #[command(flatten)]
+        source: Source,
+        /// Print the dependency graph
         #[arg(short = 'g', long = "print-graph", value_name = "dot|text")]
         print_graph: Option<String>,
+        /// Print the solution as apt-get --simulate does
+        #[arg(short = 's', long = "simulate", action)]
+        simulate: bool,
+        /// Requirements
+        #[arg(value_name = "REQUIREMENT")]
+        reqs: Vec<String>,
+    },
+    /// Solve and download the packages
+    #[command(name = "download")]
+    Download {
+        #[command(flatten)]
+        source: Source,
+        /// Number of concurrent downloads
+        #[arg(short = 'l', long = "limit", value_name = "NUM", default_value = "5")]
+        limit: usize,
+        /// Target directory
+        #[arg(short = 't', long = "target", value_name = "DIR", default_value = ".")]
+        target: PathBuf,
+        /// Requirements
+        #[arg(value_name = "REQUIREMENT")]
+        reqs: Vec<String>,
+    },
+    /// Solve and extract the packages into a directory
+    #[command(name = "extract")]
+    Extract {
+        #[command(flatten)]
+        source: Source,
         /// Number of concurrent downloads
         #[arg(short = 'l', long = "limit", value_name = "NUM", default_value = "5")]
         limit: usize,
-        /// Fetch packages
-        #[arg(short = 'f', long = "fetch-packages", action)]
-        fetch: bool,
-        /// Extract packages into target directory
-        #[arg(short = 'e', long = "extract-packages", action)]
-        extract: bool,
         /// Target directory
         #[arg(short = 't', long = "target", value_name = "DIR", default_value = ".")]
         target: PathBuf,
+        /// Also extract the base system of the variant: minbase, buildd,
+        /// important or standard
+        #[arg(long = "variant", value_name = "VARIANT")]
+        variant: Option<debrepo::Variant>,
+        /// Do not extract the packages whose name matches the glob, even if
+        /// others depend on them
+        #[arg(long = "exclude", value_name = "GLOB")]
+        exclude: Vec<String>,
+        /// Do not extract the files matching the glob, e.g.
+        /// '/usr/share/doc/*'
+        #[arg(long = "path-exclude", value_name = "GLOB")]
+        path_exclude: Vec<String>,
+        /// Extract the files matching the glob even if excluded, e.g.
+        /// '/usr/share/doc/*/copyright'
+        #[arg(long = "path-include", value_name = "GLOB")]
+        path_include: Vec<String>,
+        /// Also pack the extracted tree into a tar archive
+        #[arg(long = "tar", value_name = "FILE")]
+        tar: Option<PathBuf>,
+        /// Make the tar archive reproducible, clamping the modification
+        /// times to SOURCE_DATE_EPOCH and owning the files by root
+        #[arg(long = "reproducible", action, requires = "tar")]
+        reproducible: bool,
         /// Requirements
         #[arg(value_name = "REQUIREMENT")]
         reqs: Vec<String>,
     },
     #[command(name = "search")]
     Search {
-        /// Architecture
-        #[arg(short, long, value_name = "ARCH", default_value = arch(std::env::consts::ARCH))]
-        arch: String,
-        /// Origin repository URL
-        #[arg(
-            short = 'u',
-            long = "url",
-            value_name = "URL",
-            default_value = "https://ftp.debian.org/debian/"
-        )]
-        origin: String,
-        /// Distribution name
-        #[arg(
-            short = 'd',
-            long = "distr",
-            value_name = "DISTR",
-            default_value = "sid"
-        )]
-        distr: String,
-        /// Component
-        #[arg(
-            short = 'c',
-            long = "component",
-            value_name = "COMPONENT",
-            default_value = "all"
-        )]
-        comp: String,
+        #[command(flatten)]
+        source: Source,
         /// name
         #[arg(value_name = "NAME")]
         name: String,
     },
+    /// Print the control stanzas of a package
+    #[command(name = "show")]
+    Show {
+        #[command(flatten)]
+        source: Source,
+        /// Package name
+        #[arg(value_name = "NAME")]
+        name: String,
+    },
+    /// Print the relationships of a package
+    #[command(name = "depends")]
+    Depends {
+        #[command(flatten)]
+        source: Source,
+        /// Package name
+        #[arg(value_name = "NAME")]
+        name: String,
+    },
+    /// Print the packages depending on a package
+    #[command(name = "rdepends")]
+    Rdepends {
+        #[command(flatten)]
+        source: Source,
+        /// Package name
+        #[arg(value_name = "NAME")]
+        name: String,
+    },
+    /// Explain why a package is part of the solution of the requirements
+    #[command(name = "why")]
+    Why {
+        #[command(flatten)]
+        source: Source,
+        /// Package name
+        #[arg(value_name = "NAME")]
+        name: String,
+        /// Requirements
+        #[arg(value_name = "REQUIREMENT", required = true)]
+        reqs: Vec<String>,
+    },
+    /// Compare two versions as dpkg --compare-versions does, exiting with 0
+    /// if the relation holds, 1 if not and 2 on a bad operator
+    #[command(name = "compare-versions")]
+    CompareVersions {
+        #[arg(value_name = "VERSION", allow_hyphen_values = true)]
+        a: String,
+        /// lt, le, eq, ne, ge, gt or their -nl variants
+        #[arg(value_name = "OP")]
+        op: String,
+        #[arg(value_name = "VERSION", allow_hyphen_values = true)]
+        b: String,
+    },
 }
 
+const RELATIONSHIPS: &[&str] = &[
+    "Pre-Depends",
+    "Depends",
+    "Recommends",
+    "Suggests",
+    "Enhances",
+    "Breaks",
+    "Conflicts",
+    "Replaces",
+    "Provides",
+];
+
 struct Package<'a> {
     name: &'a str,
     arch: &'a str,
@@ -215,14 +366,122 @@ async fn extract_repo_package<'a, S: AsRef<str>, F: DeploymentFileSystem>(
     universe: &'a Universe<S>,
     id: debrepo::SolvableId,
     target: F,
+    custom: &debrepo::Customization,
 ) -> Result<MutableControlStanza> {
     let reader = universe.deb_reader(id).await?;
-    let desc = reader.extract_to(target).await?;
+    let name = universe.package(id).name();
+    let desc = custom.extract(name, reader, target).await?;
     Ok(desc)
 }
 
+async fn open_repo(origin: &str) -> Result<DebRepo> {
+    if origin.starts_with("http:") || origin.starts_with("https:") {
+        let auth = AuthConf::load_system().await?;
+        Ok(HttpDebRepo::new(origin).await?.auth_conf(&auth).into())
+    } else {
+        Ok(FSDebRepo::from_url(origin).await?.into())
+    }
+}
+
+// Fetches the Release file of the source, verified unless insecure.
+async fn fetch_release(repo: &DebRepo, source: &Source) -> Result<debrepo::Release> {
+    let release = match &source.keyring {
+        _ if source.insecure => repo.fetch_release(&source.distr).await?,
+        Some(keyring) => {
+            let key = fs::read(keyring).await?;
+            repo.fetch_verify_release_with_keys(&source.distr, [key])
+                .await?
+        }
+        None => {
+            repo.fetch_verify_release_with_keys(&source.distr, [debrepo::DEBIAN_KEYRING])
+                .await?
+        }
+    };
+    Ok(release)
+}
+
+async fn load_universe(source: &Source) -> Result<Universe<Box<str>>> {
+    let progress = source.progress();
+    let repo = open_repo(&source.origin)
+        .await?
+        .with_progress(Arc::clone(&progress));
+    let release = fetch_release(&repo, source).await?;
+    let components = if source.comp == "all" {
+        release.components().collect::<Vec<&'_ str>>()
+    } else {
+        source
+            .comp
+            .split(',')
+            .map(|s| s.trim())
+            .collect::<Vec<&'_ str>>()
+    };
+    let packages = join_all(
+        components
+            .iter()
+            .map(|comp| release.fetch_packages(comp, &source.arch)),
+    )
+    .await
+    .into_iter()
+    .collect::<Result<Vec<_>, debrepo::Error>>()?;
+    Ok(Universe::new_with_progress(
+        &source.arch,
+        packages,
+        progress,
+    )?)
+}
+
+fn solve<S: AsRef<str>>(universe: &mut Universe<S>, reqs: &[String]) -> Result<Vec<SolvableId>> {
+    let requirements = reqs
+        .iter()
+        .map(|s| Dependency::try_from(s.as_ref()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let problem = universe.problem(requirements, std::iter::empty(), std::iter::empty());
+    universe
+        .solve(problem)
+        .map_err(|err| universe.solver_error(err).into())
+}
+
+// Runs `task` on the packages of the solution, `limit` at a time.
+async fn for_each_package<T, F>(
+    solution: Vec<SolvableId>,
+    limit: usize,
+    task: impl Fn(SolvableId) -> F,
+) -> Vec<Result<T>>
+where
+    F: std::future::Future<Output = Result<T>>,
+{
+    let mut results = vec![];
+    let mut stream = FuturesUnordered::new();
+    let mut pending = solution.into_iter();
+    stream.extend(pending.by_ref().take(limit).map(&task));
+    while let Some(result) = stream.next().await {
+        results.push(result);
+        stream.extend(pending.next().map(&task));
+    }
+    results
+}
+
+// The versions of the package `name`.
+fn find_packages<'a, S: AsRef<str>>(
+    universe: &'a Universe<S>,
+    name: &'a str,
+) -> impl Iterator<Item = &'a debrepo::Package<'a>> {
+    universe.packages().filter(move |p| p.name() == name)
+}
+
 async fn cmd(cli: Cli) -> Result<ExitCode> {
     match cli.cmd {
+        Commands::CompareVersions { a, op, b } => {
+            // dpkg exits with 2 on a bad operator
+            match debrepo::compare_versions(&a, &op, &b) {
+                Ok(true) => Ok(ExitCode::SUCCESS),
+                Ok(false) => Ok(ExitCode::FAILURE),
+                Err(err) => {
+                    eprintln!("{}", err);
+                    Ok(ExitCode::from(2))
+                }
+            }
+        }
         Commands::Fetch {
             arch: a,
             origin,
@@ -231,7 +490,7 @@ async fn cmd(cli: Cli) -> Result<ExitCode> {
             comp,
         } => {
             let start = std::time::Instant::now();
-            let repo: DebRepo = HttpDebRepo::new(&origin).await?.into();
+            let repo = open_repo(&origin).await?;
             let release = repo.fetch_release(&distr).await?;
             let (path, size, hash) = release
                 .packages_file(&comp, arch(&a))
@@ -249,30 +508,53 @@ async fn cmd(cli: Cli) -> Result<ExitCode> {
             println!("fetched in {:?}", start.elapsed());
             Ok(ExitCode::SUCCESS)
         }
-        Commands::Search {
-            arch,
-            origin,
-            distr,
-            comp,
-            name,
-        } => {
+        Commands::Update { source, target } => {
             let start = std::time::Instant::now();
-            let repo: DebRepo = HttpDebRepo::new(&origin).await?.into();
-            let release = repo.fetch_verify_release_with_keys(&distr, [debrepo::DEBIAN_KEYRING]).await?;
-            let components = if &comp == "all" {
+            let repo = open_repo(&source.origin)
+                .await?
+                .with_progress(source.progress());
+            let base = format!("dists/{}", &source.distr);
+            let release = fetch_release(&repo, &source).await?;
+            let components = if source.comp == "all" {
                 release.components().collect::<Vec<&'_ str>>()
             } else {
-                comp.split(',').map(|s| s.trim()).collect::<Vec<&'_ str>>()
+                source
+                    .comp
+                    .split(',')
+                    .map(|s| s.trim())
+                    .collect::<Vec<&'_ str>>()
             };
-            let packages = join_all(
-                components
-                    .iter()
-                    .map(|comp| release.fetch_packages(comp, &arch)),
-            )
-            .await
-            .into_iter()
-            .collect::<io::Result<Vec<_>>>()?;
-            let universe = Universe::new(&arch, packages)?;
+            let mut files = vec![];
+            for comp in components {
+                let (path, size, hash) =
+                    release.packages_file(comp, &source.arch).ok_or_else(|| {
+                        anyhow!("Packages file for {} {} not found", &source.arch, comp)
+                    })?;
+                repo.progress().on_index_fetch(&path, size as u64);
+                files.push((path.clone(), repo.fetch_verify(&path, size, hash).await?));
+            }
+            for name in ["Release", "InRelease", "Release.gpg"] {
+                let path = format!("{}/{}", &base, name);
+                match repo.fetch(&path).await {
+                    Ok(data) => files.push((path, data)),
+                    Err(debrepo::Error::NotFound(_)) => {}
+                    Err(err) => return Err(err.into()),
+                }
+            }
+            for (path, data) in files {
+                let path = target.join(&path);
+                if let Some(dir) = path.parent() {
+                    fs::create_dir_all(dir).await?;
+                }
+                fs::write(&path, data).await?;
+                println!("{}", path.display());
+            }
+            println!("updated in {:?}", start.elapsed());
+            Ok(ExitCode::SUCCESS)
+        }
+        Commands::Search { source, name } => {
+            let start = std::time::Instant::now();
+            let universe = load_universe(&source).await?;
             let re = regex::RegexBuilder::new(&name)
                 .case_insensitive(true)
                 .build()?;
@@ -295,162 +577,291 @@ async fn cmd(cli: Cli) -> Result<ExitCode> {
                 }
             }
         }
-        Commands::Solve {
-            arch,
-            origin,
-            distr,
-            comp,
-            print_graph,
-            fetch,
-            extract,
-            target,
+        Commands::Show { source, name } => {
+            let universe = load_universe(&source).await?;
+            let mut found = false;
+            for package in find_packages(&universe, &name) {
+                println!("{}", package.control()?);
+                found = true;
+            }
+            if !found {
+                return Err(anyhow!("package {} not found", name));
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        Commands::Depends { source, name } => {
+            let universe = load_universe(&source).await?;
+            let mut found = false;
+            for package in find_packages(&universe, &name) {
+                println!("{}", package.full_name());
+                for field in RELATIONSHIPS {
+                    let Some(value) = package.field(field) else {
+                        continue;
+                    };
+                    let relations = match Relations::parse(value) {
+                        Ok(relations) => relations,
+                        Err(err) => {
+                            eprintln!("{}: invalid {} field: {}", package.full_name(), field, err);
+                            continue;
+                        }
+                    };
+                    for group in relations.groups {
+                        let group = Relations {
+                            groups: vec![group],
+                        };
+                        println!("  {}: {}", field, group);
+                    }
+                }
+                found = true;
+            }
+            if !found {
+                return Err(anyhow!("package {} not found", name));
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        Commands::Rdepends { source, name } => {
+            let universe = load_universe(&source).await?;
+            println!("{}", name);
+            println!("Reverse Depends:");
+            for package in universe.packages() {
+                for field in &RELATIONSHIPS[..4] {
+                    let Some(value) = package.field(field) else {
+                        continue;
+                    };
+                    match Relations::parse(value) {
+                        Ok(relations) if relations.find(&name).next().is_some() => {
+                            println!("  {}: {} {}", field, package.name(), package.version());
+                        }
+                        Ok(_) => {}
+                        Err(err) => {
+                            eprintln!("{}: invalid {} field: {}", package.full_name(), field, err);
+                        }
+                    }
+                }
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        Commands::Why { source, name, reqs } => {
+            let mut universe = load_universe(&source).await?;
+            let mut solution = solve(&mut universe, &reqs)?;
+            let graph = universe.dependency_graph(&mut solution);
+            let names: Vec<String> = reqs
+                .iter()
+                .filter_map(|r| Dependency::try_from(r.as_str()).ok())
+                .flat_map(|dep| dep.iter().map(|c| c.name().to_string()).collect::<Vec<_>>())
+                .collect();
+            // breadth first from the required packages, for the shortest chain
+            let mut parent: HashMap<SolvableId, Option<SolvableId>> = HashMap::new();
+            let mut queue: VecDeque<SolvableId> = solution
+                .iter()
+                .copied()
+                .filter(|id| names.iter().any(|n| universe.package(*id).name() == n))
+                .collect();
+            parent.extend(queue.iter().map(|id| (*id, None)));
+            while let Some(id) = queue.pop_front() {
+                if universe.package(id).name() == name {
+                    let mut chain = vec![id];
+                    while let Some(Some(up)) = parent.get(chain.last().unwrap()) {
+                        chain.push(*up);
+                    }
+                    let chain: Vec<String> = chain
+                        .iter()
+                        .rev()
+                        .map(|id| universe.display_solvable(*id).to_string())
+                        .collect();
+                    println!("{}", chain.join(" -> "));
+                    return Ok(ExitCode::SUCCESS);
+                }
+                for dep in graph.neighbors(id) {
+                    if let Entry::Vacant(entry) = parent.entry(dep) {
+                        entry.insert(Some(id));
+                        queue.push_back(dep);
+                    }
+                }
+            }
+            println!("{} is not required by {}", name, reqs.join(", "));
+            Ok(ExitCode::FAILURE)
+        }
+        Commands::Download {
+            source,
             limit,
+            target,
             reqs,
         } => {
             let start = std::time::Instant::now();
-            let requirements: Result<Vec<_>> = reqs
-                .iter()
-                .map(|s| Dependency::try_from(s.as_ref()).map_err(|err| err.into()))
-                .collect();
-            let repo: DebRepo = HttpDebRepo::new(&origin).await?.into();
-            let release = repo.fetch_release(&distr).await?;
-            let components = if &comp == "all" {
-                release.components().collect::<Vec<&'_ str>>()
+            let mut universe = load_universe(&source).await?;
+            let solution = solve(&mut universe, &reqs)?;
+            let universe = &universe;
+            let target = &target;
+            let mut failed = false;
+            for result in for_each_package(solution, limit, |id| {
+                copy_repo_package(universe, id, target)
+            })
+            .await
+            {
+                match result {
+                    Ok((_, name, size)) => println!("{} {}", name, size),
+                    Err(err) => {
+                        println!("Failed to download: {}", err);
+                        failed = true;
+                    }
+                }
+            }
+            println!("solved and fetched in {:?}", start.elapsed());
+            Ok(if failed {
+                ExitCode::FAILURE
             } else {
-                comp.split(',').map(|s| s.trim()).collect::<Vec<&'_ str>>()
+                ExitCode::SUCCESS
+            })
+        }
+        Commands::Extract {
+            source,
+            limit,
+            target,
+            variant,
+            exclude,
+            path_exclude,
+            path_include,
+            tar,
+            reproducible,
+            reqs,
+        } => {
+            let start = std::time::Instant::now();
+            let mut universe = load_universe(&source).await?;
+            let solution = match variant {
+                Some(variant) => {
+                    let mut requirements = universe.variant_requirements(variant)?;
+                    for req in &reqs {
+                        requirements.push(Dependency::try_from(req.as_str())?);
+                    }
+                    let problem =
+                        universe.problem(requirements, std::iter::empty(), std::iter::empty());
+                    universe
+                        .solve(problem)
+                        .map_err(|err| universe.solver_error(err))?
+                }
+                None => solve(&mut universe, &reqs)?,
             };
-            let packages = join_all(
-                components
-                    .iter()
-                    .map(|comp| release.fetch_packages(comp, &arch)),
-            )
+            let fs =
+                debrepo::LocalFileSystem::new(&target, nix::unistd::Uid::effective().is_root())
+                    .await?;
+            let mut custom = debrepo::Customization::new();
+            for glob in exclude {
+                custom = custom.exclude_packages(debrepo::PackageFilter::name(glob));
+            }
+            // excluded first, so that the includes win
+            for glob in path_exclude {
+                custom = custom.exclude_path(glob);
+            }
+            for glob in path_include {
+                custom = custom.include_path(glob);
+            }
+            let solution = custom.packages(&universe, &solution);
+            let (universe, fs, custom) = (&universe, &fs, &custom);
+            let mut control_file = for_each_package(solution, limit, |id| {
+                extract_repo_package(universe, id, fs, custom)
+            })
             .await
             .into_iter()
-            .collect::<io::Result<Vec<_>>>()?;
-            let mut universe = Universe::new(&arch, packages)?;
-            let problem = universe.problem(requirements?, std::iter::empty());
-            match universe.solve(problem) {
-                Ok(mut solution) => {
-                    if extract {
-                        let fs = debrepo::LocalFileSystem::new(
-                            &target,
-                            nix::unistd::Uid::effective().is_root(),
+            .map(|stanza| {
+                let mut stanza = stanza?;
+                stanza.set("Status", "install ok unpacked");
+                stanza.sort_fields_deb_order();
+                Ok(stanza)
+            })
+            .collect::<Result<Vec<_>>>()?;
+            control_file
+                .sort_by(|a, b| a.field("Package").unwrap().cmp(b.field("Package").unwrap()));
+            let mut status = PathBuf::from(&target);
+            status.push("var/lib/dpkg");
+            fs::create_dir_all(&status).await?;
+            status.push("status");
+            let mut out = fs::File::create(status).await?;
+            for i in control_file.into_iter() {
+                out.write_all(format!("{}", &i).as_bytes()).await?;
+                out.write_all(b"\n").await?;
+            }
+            out.flush().await?;
+            if let Some(tar) = tar {
+                let options = match reproducible {
+                    true => debrepo::TarOptions::reproducible(),
+                    false => debrepo::TarOptions::default(),
+                };
+                let out = fs::File::create(tar).await?;
+                debrepo::write_tarball(&target, out, &options)
+                    .await?
+                    .flush()
+                    .await?;
+            }
+            println!("solved and extracted in {:?}", start.elapsed());
+            Ok(ExitCode::SUCCESS)
+        }
+        Commands::Solve {
+            source,
+            print_graph,
+            simulate,
+            reqs,
+        } => {
+            let start = std::time::Instant::now();
+            let mut universe = load_universe(&source).await?;
+            let mut solution = match solve(&mut universe, &reqs) {
+                Ok(solution) => solution,
+                Err(err) => {
+                    println!("{}", err);
+                    return Ok(ExitCode::FAILURE);
+                }
+            };
+            use petgraph::dot::{Config, Dot};
+            let mut out = std::io::stdout().lock();
+            if simulate {
+                let requested: Vec<&str> = reqs.iter().map(String::as_str).collect();
+                print!(
+                    "{}",
+                    universe.plan(&solution, &requested)?.render_apt_style()
+                );
+            } else if let Some(format) = print_graph {
+                let graph = universe.dependency_graph(&mut solution);
+                if format.eq_ignore_ascii_case("dot") {
+                    println!(
+                        "{:?}",
+                        Dot::with_attr_getters(
+                            &graph,
+                            &[Config::EdgeNoLabel, Config::NodeNoLabel],
+                            &|_, _| "".to_owned(),
+                            &|_, id| format!("label = \"{}\"", universe.display_solvable(id.0))
                         )
-                        .await?;
-                        let mut control_file: Vec<MutableControlStanza> = vec![];
-                        let mut stream = FuturesUnordered::new();
-                        let mut pending = solution.into_iter();
-                        for _ in 0..limit {
-                            if let Some(id) = pending.next() {
-                                stream.push(extract_repo_package(&universe, id, &fs));
-                            }
-                        }
-                        while let Some(result) = stream.next().await {
-                            match result {
-                                Ok(mut stanza) => {
-                                    stanza.set("Status", "install ok unpacked");
-                                    stanza.sort_fields_deb_order();
-                                    control_file.push(stanza)
-                                }
-                                Err(err) => return Err(err),
-                            }
-                            if let Some(id) = pending.next() {
-                                stream.push(extract_repo_package(&universe, id, &fs));
-                            }
-                        }
-                        control_file.sort_by(|a, b| {
-                            a.field("Package").unwrap().cmp(b.field("Package").unwrap())
-                        });
-                        {
-                            let mut target = PathBuf::from(&target);
-                            target.push("var/lib/dpkg");
-                            fs::create_dir_all(&target).await?;
-                            target.push("status");
-                            let mut out = fs::File::create(target).await?;
-                            for i in control_file.into_iter() {
-                                out.write_all(format!("{}", &i).as_bytes()).await?;
-                                out.write_all(&[b'\n']).await?;
-                            }
-                        };
-
-                        println!("solved and fetched in {:?}", start.elapsed());
-                    } else if fetch {
-                        let mut stream = FuturesUnordered::new();
-                        let mut pending = solution.into_iter();
-                        for _ in 0..limit {
-                            if let Some(id) = pending.next() {
-                                stream.push(copy_repo_package(&universe, id, &target));
-                            }
-                        }
-                        while let Some(result) = stream.next().await {
-                            match result {
-                                Ok((_, name, size)) => println!("{} {}", name, size),
-                                Err(err) => println!("Failed to download: {}", err),
-                            }
-                            if let Some(id) = pending.next() {
-                                stream.push(copy_repo_package(&universe, id, &target));
-                            }
-                        }
-                        println!("solved and fetched in {:?}", start.elapsed());
-                    } else {
-                        use petgraph::dot::{Config, Dot};
-                        let mut out = std::io::stdout().lock();
-                        if let Some(format) = print_graph {
-                            let graph = universe.dependency_graph(&mut solution);
-                            if format.eq_ignore_ascii_case("dot") {
-                                println!(
-                                    "{:?}",
-                                    Dot::with_attr_getters(
-                                        &graph,
-                                        &[Config::EdgeNoLabel, Config::NodeNoLabel],
-                                        &|_, _| "".to_owned(),
-                                        &|_, id| format!(
-                                            "label = \"{}\"",
-                                            universe.display_solvable(id.0)
-                                        )
-                                    )
-                                );
+                    );
+                } else {
+                    let ordered = petgraph::algo::kosaraju_scc(&graph)
+                        .into_iter()
+                        .flat_map(|g| g.into_iter());
+                    for id in ordered {
+                        println!("{}", universe.display_solvable(id));
+                        let mut has_deps = false;
+                        for (i, dep) in graph.neighbors(id).enumerate() {
+                            if i == 0 {
+                                print!(" {}", universe.display_solvable(dep));
+                                has_deps = true;
                             } else {
-                                let ordered = petgraph::algo::kosaraju_scc(&graph)
-                                    .into_iter()
-                                    .flat_map(|g| g.into_iter());
-                                for id in ordered {
-                                    println!("{}", universe.display_solvable(id));
-                                    let mut has_deps = false;
-                                    for (i, dep) in graph.neighbors(id).enumerate() {
-                                        if i == 0 {
-                                            print!(" {}", universe.display_solvable(dep));
-                                            has_deps = true;
-                                        } else {
-                                            print!(", {}", universe.display_solvable(dep));
-                                        }
-                                    }
-                                    if has_deps {
-                                        println!("");
-                                    }
-                                }
+                                print!(", {}", universe.display_solvable(dep));
                             }
-                        } else {
-                            pretty_print_packages(
-                                &mut out,
-                                universe
-                                    .sort_solution(&mut solution)
-                                    .map(|s| -> Package { universe.package(s).into() }),
-                                false,
-                            )?;
-                            println!("solved in {:?}", start.elapsed());
                         }
-                    };
-                    Ok(ExitCode::SUCCESS)
-                }
-                Err(unsolvable) => match unsolvable {
-                    resolvo::UnsolvableOrCancelled::Unsolvable(conflict) => {
-                        println!("{}", universe.display_conflict(conflict));
-                        Ok(ExitCode::FAILURE)
+                        if has_deps {
+                            println!();
+                        }
                     }
-                    resolvo::UnsolvableOrCancelled::Cancelled(_) => unreachable!(),
-                },
+                }
+            } else {
+                pretty_print_packages(
+                    &mut out,
+                    universe
+                        .sort_solution(&mut solution)
+                        .map(|s| -> Package { universe.package(s).into() }),
+                    false,
+                )?;
+                println!("solved in {:?}", start.elapsed());
             }
+            Ok(ExitCode::SUCCESS)
         }
     }
 }
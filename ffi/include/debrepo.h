@@ -0,0 +1,68 @@
+/*
+ * C API of the debrepo resolver, implemented by libdebrepo_ffi.
+ *
+ * Strings are NUL terminated UTF-8. On failure, the functions returning a
+ * pointer return NULL and the ones returning an int return -1;
+ * debrepo_last_error() then describes the failure.
+ *
+ *     const char *index = ...; // the contents of a Packages file
+ *     const char *reqs[] = { "hello (>= 2.10)" };
+ *     debrepo_universe *u = debrepo_universe_new(
+ *         "https://deb.debian.org/debian", "amd64", &index, 1);
+ *     debrepo_solution *s = debrepo_solve(u, reqs, 1);
+ *     for (size_t i = 0; s && i < debrepo_solution_len(s); i++)
+ *         printf("%s %s\n", debrepo_solution_name(s, i),
+ *                debrepo_solution_version(s, i));
+ *     debrepo_solution_free(s);
+ *     debrepo_universe_free(u);
+ */
+
+#ifndef DEBREPO_H
+#define DEBREPO_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct DebrepoUniverse debrepo_universe;
+typedef struct DebrepoSolution debrepo_solution;
+
+/* The last failure on the calling thread, valid until the next one. */
+const char *debrepo_last_error(void);
+
+/* Creates a universe for arch from the contents of count Packages indexes.
+ * The packages are fetched from url, an http(s) URL or a local directory;
+ * url may be NULL when nothing is fetched. */
+debrepo_universe *debrepo_universe_new(const char *url, const char *arch,
+                                       const char *const *indexes,
+                                       size_t count);
+void debrepo_universe_free(debrepo_universe *universe);
+
+/* Solves count requirements, e.g. "hello (>= 2.10)". */
+debrepo_solution *debrepo_solve(debrepo_universe *universe,
+                                const char *const *requirements,
+                                size_t count);
+void debrepo_solution_free(debrepo_solution *solution);
+
+/* Iterates the packages of a solution. The strings are owned by it. */
+size_t debrepo_solution_len(const debrepo_solution *solution);
+const char *debrepo_solution_name(const debrepo_solution *solution,
+                                  size_t index);
+const char *debrepo_solution_version(const debrepo_solution *solution,
+                                     size_t index);
+const char *debrepo_solution_arch(const debrepo_solution *solution,
+                                  size_t index);
+
+/* Downloads the .deb of a package of the solution to path, verifying its
+ * hash. Returns 0 on success. */
+int debrepo_fetch_deb(const debrepo_universe *universe,
+                      const debrepo_solution *solution, size_t index,
+                      const char *path);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,334 @@
+//! C API of the debrepo resolver
+//!
+//! Builds `libdebrepo_ffi`, declared in `include/debrepo.h`. Strings are NUL
+//! terminated UTF-8. On failure, the functions returning a pointer return
+//! NULL and the ones returning an int return -1; [`debrepo_last_error`] then
+//! describes the failure.
+
+use {
+    async_std::task::block_on,
+    debrepo::{
+        null_provider, DebRepo, Dependency, FSDebRepo, HttpDebRepo, Packages, SolvableId, Universe,
+    },
+    std::{
+        cell::RefCell,
+        ffi::{c_char, c_int, CStr, CString},
+        fmt::Display,
+        panic::{catch_unwind, AssertUnwindSafe},
+        ptr, slice,
+    },
+};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error(err: impl Display) {
+    let msg = CString::new(err.to_string().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(msg));
+}
+
+// Runs `f`, recording its error or panic and returning `failed` then.
+fn guard<T>(failed: T, f: impl FnOnce() -> Result<T, String>) -> T {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(value)) => value,
+        Ok(Err(err)) => {
+            set_error(err);
+            failed
+        }
+        Err(_) => {
+            set_error("internal error");
+            failed
+        }
+    }
+}
+
+unsafe fn str_arg<'a>(s: *const c_char, what: &str) -> Result<&'a str, String> {
+    if s.is_null() {
+        return Err(format!("{} is NULL", what));
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|err| format!("{}: {}", what, err))
+}
+
+unsafe fn str_args<'a>(s: *const *const c_char, count: usize) -> Result<Vec<&'a str>, String> {
+    match count {
+        0 => Ok(vec![]),
+        _ if s.is_null() => Err("NULL array".to_string()),
+        _ => slice::from_raw_parts(s, count)
+            .iter()
+            .map(|s| str_arg(*s, "array item"))
+            .collect(),
+    }
+}
+
+async fn open_repo(url: &str) -> std::io::Result<DebRepo> {
+    if url.starts_with("http:") || url.starts_with("https:") {
+        Ok(HttpDebRepo::new(url).await?.into())
+    } else {
+        Ok(FSDebRepo::from_url(url).await?.into())
+    }
+}
+
+/// A universe of packages, `debrepo_universe` in C.
+pub struct DebrepoUniverse(Universe<Box<str>>);
+
+/// A solution, `debrepo_solution` in C.
+pub struct DebrepoSolution {
+    ids: Vec<SolvableId>,
+    names: Vec<CString>,
+    versions: Vec<CString>,
+    archs: Vec<CString>,
+}
+
+/// Returns the description of the last failure on the calling thread, or
+/// NULL. The string is valid until the next failure on the thread.
+#[no_mangle]
+pub extern "C" fn debrepo_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |msg| msg.as_ptr())
+    })
+}
+
+/// Creates a universe for `arch` from the contents of `count` Packages
+/// indexes. The packages are fetched from the repository at `url`, an
+/// http(s) URL or a local directory; with a NULL `url` they cannot be.
+///
+/// # Safety
+///
+/// The strings must be NUL terminated and `indexes` must point to `count`
+/// of them.
+#[no_mangle]
+pub unsafe extern "C" fn debrepo_universe_new(
+    url: *const c_char,
+    arch: *const c_char,
+    indexes: *const *const c_char,
+    count: usize,
+) -> *mut DebrepoUniverse {
+    guard(ptr::null_mut(), || {
+        let repo = match url.is_null() {
+            true => null_provider(),
+            false => block_on(open_repo(str_arg(url, "url")?)).map_err(|err| err.to_string())?,
+        };
+        let packages = str_args(indexes, count)?
+            .into_iter()
+            .map(|index| Packages::new(repo.clone(), index.into()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| err.to_string())?;
+        let universe =
+            Universe::new(str_arg(arch, "arch")?, packages).map_err(|err| err.to_string())?;
+        Ok(Box::into_raw(Box::new(DebrepoUniverse(universe))))
+    })
+}
+
+/// # Safety
+///
+/// `universe` must come from [`debrepo_universe_new`] and not be used
+/// afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn debrepo_universe_free(universe: *mut DebrepoUniverse) {
+    if !universe.is_null() {
+        drop(Box::from_raw(universe));
+    }
+}
+
+/// Solves the `count` requirements, e.g. `"hello (>= 2.10)"`.
+///
+/// # Safety
+///
+/// `universe` must be valid and `requirements` must point to `count` NUL
+/// terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn debrepo_solve(
+    universe: *mut DebrepoUniverse,
+    requirements: *const *const c_char,
+    count: usize,
+) -> *mut DebrepoSolution {
+    guard(ptr::null_mut(), || {
+        let universe = &mut universe.as_mut().ok_or("universe is NULL")?.0;
+        let requirements = str_args(requirements, count)?
+            .into_iter()
+            .map(Dependency::try_from)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| err.to_string())?;
+        let problem = universe.problem(requirements, vec![], vec![]);
+        let ids = universe
+            .solve(problem)
+            .map_err(|err| universe.solver_error(err).to_string())?;
+        let strings = |f: &dyn Fn(&debrepo::Package<'_>) -> String| {
+            ids.iter()
+                .map(|id| CString::new(f(universe.package(*id))).unwrap_or_default())
+                .collect()
+        };
+        Ok(Box::into_raw(Box::new(DebrepoSolution {
+            names: strings(&|p| p.name().to_string()),
+            versions: strings(&|p| p.version().to_string()),
+            archs: strings(&|p| p.arch().to_string()),
+            ids,
+        })))
+    })
+}
+
+/// # Safety
+///
+/// `solution` must come from [`debrepo_solve`] and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn debrepo_solution_free(solution: *mut DebrepoSolution) {
+    if !solution.is_null() {
+        drop(Box::from_raw(solution));
+    }
+}
+
+/// The number of packages of the solution.
+///
+/// # Safety
+///
+/// `solution` must be valid.
+#[no_mangle]
+pub unsafe extern "C" fn debrepo_solution_len(solution: *const DebrepoSolution) -> usize {
+    solution.as_ref().map_or(0, |s| s.ids.len())
+}
+
+unsafe fn solution_str(
+    solution: *const DebrepoSolution,
+    index: usize,
+    strings: fn(&DebrepoSolution) -> &Vec<CString>,
+) -> *const c_char {
+    guard(ptr::null(), || {
+        let solution = solution.as_ref().ok_or("solution is NULL")?;
+        let s = strings(solution)
+            .get(index)
+            .ok_or_else(|| format!("no package {} in the solution", index))?;
+        Ok(s.as_ptr())
+    })
+}
+
+/// The name of the package at `index` in the solution, owned by it.
+///
+/// # Safety
+///
+/// `solution` must be valid.
+#[no_mangle]
+pub unsafe extern "C" fn debrepo_solution_name(
+    solution: *const DebrepoSolution,
+    index: usize,
+) -> *const c_char {
+    solution_str(solution, index, |s| &s.names)
+}
+
+/// The version of the package at `index` in the solution, owned by it.
+///
+/// # Safety
+///
+/// `solution` must be valid.
+#[no_mangle]
+pub unsafe extern "C" fn debrepo_solution_version(
+    solution: *const DebrepoSolution,
+    index: usize,
+) -> *const c_char {
+    solution_str(solution, index, |s| &s.versions)
+}
+
+/// The architecture of the package at `index` in the solution, owned by it.
+///
+/// # Safety
+///
+/// `solution` must be valid.
+#[no_mangle]
+pub unsafe extern "C" fn debrepo_solution_arch(
+    solution: *const DebrepoSolution,
+    index: usize,
+) -> *const c_char {
+    solution_str(solution, index, |s| &s.archs)
+}
+
+/// Downloads the .deb of the package at `index` in the solution to `path`,
+/// verifying its hash. Returns 0 on success.
+///
+/// # Safety
+///
+/// `universe` must be the one `solution` comes from and `path` a NUL
+/// terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn debrepo_fetch_deb(
+    universe: *const DebrepoUniverse,
+    solution: *const DebrepoSolution,
+    index: usize,
+    path: *const c_char,
+) -> c_int {
+    guard(-1, || {
+        let universe = &universe.as_ref().ok_or("universe is NULL")?.0;
+        let solution = solution.as_ref().ok_or("solution is NULL")?;
+        let id = *solution
+            .ids
+            .get(index)
+            .ok_or_else(|| format!("no package {} in the solution", index))?;
+        let path = str_arg(path, "path")?;
+        block_on(async {
+            let file = async_std::fs::File::create(path).await?;
+            universe.copy_deb_file(file, id).await
+        })
+        .map_err(|err| format!("{}: {}", path, err))?;
+        Ok(0)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PACKAGES: &str = "\
+Package: hello
+Version: 2.10-3
+Architecture: amd64
+Depends: libfoo1 (>= 1.0)
+
+Package: libfoo1
+Version: 1.0
+Architecture: amd64
+";
+
+    fn last_error() -> String {
+        unsafe { CStr::from_ptr(debrepo_last_error()) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn test_solve() {
+        let arch = CString::new("amd64").unwrap();
+        let index = CString::new(PACKAGES).unwrap();
+        let universe =
+            unsafe { debrepo_universe_new(ptr::null(), arch.as_ptr(), &index.as_ptr(), 1) };
+        assert!(!universe.is_null());
+        let req = CString::new("hello").unwrap();
+        let solution = unsafe { debrepo_solve(universe, &req.as_ptr(), 1) };
+        assert!(!solution.is_null());
+        let mut names: Vec<_> = (0..unsafe { debrepo_solution_len(solution) })
+            .map(|i| {
+                let name = unsafe { CStr::from_ptr(debrepo_solution_name(solution, i)) };
+                let version = unsafe { CStr::from_ptr(debrepo_solution_version(solution, i)) };
+                format!("{}={}", name.to_str().unwrap(), version.to_str().unwrap())
+            })
+            .collect();
+        names.sort();
+        assert_eq!(names, ["hello=2.10-3", "libfoo1=1.0"]);
+        assert!(unsafe { debrepo_solution_name(solution, 2) }.is_null());
+        assert_eq!(last_error(), "no package 2 in the solution");
+        let path = CString::new("/nonexistent/hello.deb").unwrap();
+        assert_eq!(
+            unsafe { debrepo_fetch_deb(universe, solution, 0, path.as_ptr()) },
+            -1
+        );
+        let missing = CString::new("missing").unwrap();
+        assert!(unsafe { debrepo_solve(universe, &missing.as_ptr(), 1) }.is_null());
+        assert!(last_error().contains("missing"));
+        unsafe {
+            debrepo_solution_free(solution);
+            debrepo_universe_free(universe);
+        }
+    }
+}
@@ -0,0 +1,35 @@
+//! Alternatives selected by the solver
+//!
+//! [`crate::Universe::alternatives_report`] tells which alternative of each
+//! `a | b` dependency of a solution was selected, and why the alternatives
+//! tried before it were passed over.
+
+use resolvo::SolvableId;
+
+/// Why an alternative was selected.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ChoiceReason {
+    /// It is the preferred alternative
+    FirstPreference,
+    /// No package satisfies the alternatives preferred to it
+    OnlyInstallable,
+    /// The alternatives preferred to it were ruled out by these packages of
+    /// the solution, through conflicts or by being another version of them.
+    /// Empty if they were ruled out otherwise, e.g. by their dependencies.
+    Constrained(Vec<SolvableId>),
+}
+
+/// The alternative selected for a dependency of a package of a solution.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AlternativeChoice {
+    /// The package with the dependency
+    pub package: SolvableId,
+    /// The dependency with the alternatives in the order they were tried,
+    /// e.g. `gawk | mawk`
+    pub dependency: String,
+    /// The position of the selected alternative in `dependency`
+    pub alternative: usize,
+    /// The package of the solution satisfying it
+    pub selected: SolvableId,
+    pub reason: ChoiceReason,
+}
@@ -0,0 +1,262 @@
+//! Debian architecture names and wildcards
+//!
+//! Every Debian architecture maps to a tuple `abi-libc-os-cpu`, e.g. `armhf`
+//! is `eabihf-gnu-linux-arm` and `kfreebsd-amd64` is
+//! `base-gnu-kfreebsd-amd64`. Wildcards such as `linux-any`, `any-arm64` or
+//! `gnu-any-any` are tuples with some components set to `any`. The tables
+//! and the matching rules follow dpkg-architecture(1).
+
+const CPUS: &[&str] = &[
+    "i386",
+    "ia64",
+    "alpha",
+    "amd64",
+    "arc",
+    "armeb",
+    "arm",
+    "arm64",
+    "avr32",
+    "hppa",
+    "loong64",
+    "m32r",
+    "m68k",
+    "mips",
+    "mipsel",
+    "mipsr6",
+    "mipsr6el",
+    "mips64",
+    "mips64el",
+    "mips64r6",
+    "mips64r6el",
+    "nios2",
+    "or1k",
+    "powerpc",
+    "powerpcel",
+    "ppc64",
+    "ppc64el",
+    "riscv64",
+    "s390",
+    "s390x",
+    "sh3",
+    "sh3eb",
+    "sh4",
+    "sh4eb",
+    "sparc",
+    "sparc64",
+    "tilegx",
+];
+
+// (tuple, architecture) in dpkg's tupletable order; the first match wins.
+const TUPLES: &[(&str, &str)] = &[
+    ("eabihf-musl-linux-arm", "musl-linux-armhf"),
+    ("base-musl-linux-<cpu>", "musl-linux-<cpu>"),
+    ("ilp32-gnu-linux-arm64", "arm64ilp32"),
+    ("eabihf-gnu-linux-arm", "armhf"),
+    ("eabi-gnu-linux-arm", "armel"),
+    ("abin32-gnu-linux-mips64r6el", "mipsn32r6el"),
+    ("abin32-gnu-linux-mips64r6", "mipsn32r6"),
+    ("abin32-gnu-linux-mips64el", "mipsn32el"),
+    ("abin32-gnu-linux-mips64", "mipsn32"),
+    ("abi64-gnu-linux-mips64r6el", "mips64r6el"),
+    ("abi64-gnu-linux-mips64r6", "mips64r6"),
+    ("abi64-gnu-linux-mips64el", "mips64el"),
+    ("abi64-gnu-linux-mips64", "mips64"),
+    ("spe-gnu-linux-powerpc", "powerpcspe"),
+    ("x32-gnu-linux-amd64", "x32"),
+    ("base-gnu-linux-<cpu>", "<cpu>"),
+    ("eabihf-gnu-kfreebsd-arm", "kfreebsd-armhf"),
+    ("base-gnu-kfreebsd-<cpu>", "kfreebsd-<cpu>"),
+    ("base-gnu-knetbsd-<cpu>", "knetbsd-<cpu>"),
+    ("base-gnu-kopensolaris-<cpu>", "kopensolaris-<cpu>"),
+    ("base-gnu-hurd-<cpu>", "hurd-<cpu>"),
+    ("base-bsd-dragonflybsd-<cpu>", "dragonflybsd-<cpu>"),
+    ("base-bsd-freebsd-<cpu>", "freebsd-<cpu>"),
+    ("base-bsd-openbsd-<cpu>", "openbsd-<cpu>"),
+    ("base-bsd-netbsd-<cpu>", "netbsd-<cpu>"),
+    ("base-bsd-darwin-<cpu>", "darwin-<cpu>"),
+    ("base-sysv-aix-<cpu>", "aix-<cpu>"),
+    ("base-sysv-solaris-<cpu>", "solaris-<cpu>"),
+    ("eabihf-uclibc-linux-arm", "uclibc-linux-armhf"),
+    ("eabi-uclibc-linux-arm", "uclibc-linux-armel"),
+    ("base-uclibc-linux-<cpu>", "uclibc-linux-<cpu>"),
+];
+
+/// The `abi-libc-os-cpu` tuple of an architecture or a wildcard.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ArchTuple<'a> {
+    pub abi: &'a str,
+    pub libc: &'a str,
+    pub os: &'a str,
+    pub cpu: &'a str,
+}
+
+impl<'a> ArchTuple<'a> {
+    fn split(tuple: &'a str) -> Self {
+        let mut parts = tuple.splitn(4, '-');
+        let mut next = || parts.next().unwrap_or("any");
+        Self {
+            abi: next(),
+            libc: next(),
+            os: next(),
+            cpu: next(),
+        }
+    }
+    /// Returns the tuple of a Debian architecture name.
+    pub fn from_arch(arch: &'a str) -> Option<ArchTuple<'a>> {
+        let arch = match arch.strip_prefix("linux-") {
+            Some(cpu) if !cpu.contains('-') => cpu,
+            _ => arch,
+        };
+        TUPLES
+            .iter()
+            .find_map(|(tuple, name)| match name.split_once("<cpu>") {
+                None => (*name == arch).then(|| Self::split(tuple)),
+                Some((prefix, _)) => arch
+                    .strip_prefix(prefix)
+                    .and_then(|cpu| CPUS.iter().find(|c| **c == cpu))
+                    .map(|cpu| {
+                        let mut t = Self::split(tuple);
+                        t.cpu = cpu;
+                        t
+                    }),
+            })
+    }
+    /// Returns the tuple of a wildcard or an architecture name.
+    pub fn from_wildcard(wildcard: &'a str) -> Option<ArchTuple<'a>> {
+        if !is_wildcard(wildcard) {
+            return Self::from_arch(wildcard);
+        }
+        let parts: Vec<&str> = wildcard.splitn(4, '-').collect();
+        let any = "any";
+        Some(match parts[..] {
+            [abi, libc, os, cpu] => Self { abi, libc, os, cpu },
+            [libc, os, cpu] => Self {
+                abi: any,
+                libc,
+                os,
+                cpu,
+            },
+            [os, cpu] => Self {
+                abi: any,
+                libc: any,
+                os,
+                cpu,
+            },
+            _ => Self {
+                abi: any,
+                libc: any,
+                os: any,
+                cpu: any,
+            },
+        })
+    }
+}
+
+/// Returns true if `arch` contains an `any` component.
+pub fn is_wildcard(arch: &str) -> bool {
+    arch.split('-').any(|part| part == "any")
+}
+
+/// Returns true if the architecture `arch` matches `wildcard`, which is
+/// either an architecture name or a wildcard like `linux-any` or `any-arm64`.
+pub fn matches(arch: &str, wildcard: &str) -> bool {
+    if arch == wildcard || wildcard == "any" {
+        return true;
+    }
+    match (
+        ArchTuple::from_arch(arch),
+        ArchTuple::from_wildcard(wildcard),
+    ) {
+        (Some(real), Some(alias)) => {
+            let eq = |real: &str, alias: &str| alias == "any" || real == alias;
+            eq(real.abi, alias.abi)
+                && eq(real.libc, alias.libc)
+                && eq(real.os, alias.os)
+                && eq(real.cpu, alias.cpu)
+        }
+        _ => false,
+    }
+}
+
+/// Returns the name qemu-user gives the CPU of the Linux architecture
+/// `arch`, e.g. `aarch64` for `arm64`, as in `qemu-aarch64-static`.
+pub fn qemu_cpu(arch: &str) -> Option<&'static str> {
+    let tuple = ArchTuple::from_arch(arch).filter(|t| t.os == "linux")?;
+    Some(match (tuple.abi, tuple.cpu) {
+        ("abin32", "mips64") => "mipsn32",
+        ("abin32", "mips64el") => "mipsn32el",
+        ("x32", _) | ("ilp32", _) => return None,
+        (_, "amd64") => "x86_64",
+        (_, "i386") => "i386",
+        (_, "arm64") => "aarch64",
+        (_, "arm") => "arm",
+        (_, "armeb") => "armeb",
+        (_, "powerpc") => "ppc",
+        (_, "ppc64") => "ppc64",
+        (_, "ppc64el") => "ppc64le",
+        (_, "loong64") => "loongarch64",
+        (_, "mips") => "mips",
+        (_, "mipsel") => "mipsel",
+        (_, "mips64") => "mips64",
+        (_, "mips64el") => "mips64el",
+        (_, "riscv64") => "riscv64",
+        (_, "s390x") => "s390x",
+        (_, "sparc64") => "sparc64",
+        (_, "m68k") => "m68k",
+        (_, "alpha") => "alpha",
+        (_, "hppa") => "hppa",
+        (_, "sh4") => "sh4",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tuples() {
+        let t = |a| ArchTuple::from_arch(a).map(|t| [t.abi, t.libc, t.os, t.cpu]);
+        assert_eq!(t("amd64"), Some(["base", "gnu", "linux", "amd64"]));
+        assert_eq!(t("armhf"), Some(["eabihf", "gnu", "linux", "arm"]));
+        assert_eq!(t("mips64el"), Some(["abi64", "gnu", "linux", "mips64el"]));
+        assert_eq!(
+            t("kfreebsd-amd64"),
+            Some(["base", "gnu", "kfreebsd", "amd64"])
+        );
+        assert_eq!(t("hurd-i386"), Some(["base", "gnu", "hurd", "i386"]));
+        assert_eq!(
+            t("musl-linux-arm64"),
+            Some(["base", "musl", "linux", "arm64"])
+        );
+        assert_eq!(t("nonsense"), None);
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("amd64", "any"));
+        assert!(matches("amd64", "amd64"));
+        assert!(matches("amd64", "linux-any"));
+        assert!(matches("arm64", "any-arm64"));
+        assert!(matches("armhf", "any-arm"));
+        assert!(!matches("armel", "musl-linux-any"));
+        assert!(matches("kfreebsd-amd64", "kfreebsd-any"));
+        assert!(matches("kfreebsd-amd64", "any-amd64"));
+        assert!(matches("hurd-i386", "gnu-any-any"));
+        assert!(matches("x32", "x32-any-any-any"));
+        assert!(!matches("amd64", "kfreebsd-any"));
+        assert!(!matches("hurd-i386", "linux-any"));
+        assert!(!matches("arm64", "amd64"));
+        assert!(!matches("amd64", "musl-any-any"));
+    }
+
+    #[test]
+    fn test_qemu_cpu() {
+        assert_eq!(qemu_cpu("arm64"), Some("aarch64"));
+        assert_eq!(qemu_cpu("armhf"), Some("arm"));
+        assert_eq!(qemu_cpu("ppc64el"), Some("ppc64le"));
+        assert_eq!(qemu_cpu("mipsn32el"), Some("mipsn32el"));
+        assert_eq!(qemu_cpu("hurd-i386"), None);
+        assert_eq!(qemu_cpu("x32"), None);
+    }
+}
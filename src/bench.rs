@@ -0,0 +1,214 @@
+//! Benchmark fixtures
+//!
+//! The benchmarks in `benches/suite.rs` run on real indexes, fetched once
+//! from a snapshot.debian.org timestamp so that they never change, and kept
+//! in the fixture directory, `target/bench-fixtures` or
+//! `$DEBREPO_BENCH_FIXTURES`. Each fixture is stored unpacked along with
+//! its SHA256 digest, and fetched again if it no longer matches:
+//!
+//! ```ignore
+//! let data = bench::BOOKWORM_MAIN.load().await?;
+//! let packages = Packages::new(debrepo::null_provider(), data)?;
+//! let mut universe = Universe::new("amd64", [packages])?;
+//! for problem in bench::PROBLEMS {
+//!     let problem = universe.problem(problem.requirements(), vec![], vec![]);
+//!     universe.solve(problem)?;
+//! }
+//! ```
+//!
+//! Run the suite with `cargo bench --features bench --bench suite`.
+
+use {
+    crate::{
+        error::{Error, Result},
+        fsrepo::FSDebRepo,
+        httprepo::HttpDebRepo,
+        repo::DebRepo,
+        version::{Dependency, Version},
+    },
+    async_std::{
+        fs,
+        path::{Path, PathBuf},
+    },
+    sha2::{Digest, Sha256},
+    std::{io, sync::Arc},
+};
+
+/// The environment variable overriding the fixture directory.
+pub const FIXTURES_ENV: &str = "DEBREPO_BENCH_FIXTURES";
+
+/// A Packages index used by the benchmarks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Fixture {
+    /// The name of the fixture file
+    pub name: &'static str,
+    /// The URL of the repository
+    pub repo: &'static str,
+    pub suite: &'static str,
+    pub component: &'static str,
+    pub arch: &'static str,
+}
+
+/// Debian bookworm main for amd64, as of 2024-12-01.
+pub const BOOKWORM_MAIN: Fixture = Fixture {
+    name: "bookworm-main-amd64",
+    repo: "https://snapshot.debian.org/archive/debian/20241201T025825Z/",
+    suite: "bookworm",
+    component: "main",
+    arch: "amd64",
+};
+
+/// A set of requirements solved by the benchmarks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Problem {
+    pub name: &'static str,
+    pub requirements: &'static [&'static str],
+}
+
+impl Problem {
+    /// Returns the parsed requirements.
+    pub fn requirements(
+        &self,
+    ) -> Vec<Dependency<Option<&'static str>, &'static str, Version<&'static str>>> {
+        self.requirements
+            .iter()
+            .map(|req| Dependency::try_from(*req).expect("valid benchmark requirement"))
+            .collect()
+    }
+}
+
+/// The problems solved over [`BOOKWORM_MAIN`].
+pub const PROBLEMS: &[Problem] = &[
+    Problem {
+        name: "minimal",
+        requirements: &["base-files", "apt"],
+    },
+    Problem {
+        name: "build-essential",
+        requirements: &["build-essential", "debhelper"],
+    },
+    Problem {
+        name: "server",
+        requirements: &["openssh-server", "nginx", "postgresql"],
+    },
+    Problem {
+        name: "desktop",
+        requirements: &["task-gnome-desktop | task-kde-desktop"],
+    },
+];
+
+/// Returns the fixture directory.
+pub fn fixture_dir() -> PathBuf {
+    match std::env::var_os(FIXTURES_ENV) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/target/bench-fixtures"
+        )),
+    }
+}
+
+impl Fixture {
+    /// Returns the path of the fixture file.
+    pub fn path(&self) -> PathBuf {
+        self.path_in(&fixture_dir())
+    }
+    fn path_in(&self, dir: &Path) -> PathBuf {
+        dir.join(format!("{}.Packages", self.name))
+    }
+    /// Returns the fixture, fetching it first unless it is stored already.
+    pub async fn load(&self) -> Result<Arc<str>> {
+        self.load_with(&fixture_dir(), self.repo).await
+    }
+    // Loads the fixture stored in `dir`, fetching it from `repo` first
+    // unless it is stored already.
+    async fn load_with(&self, dir: &Path, repo: &str) -> Result<Arc<str>> {
+        let path = self.path_in(dir);
+        let digest = path.with_extension("Packages.sha256");
+        let stored = fs::read(&path).await;
+        if let (Ok(data), Ok(expected)) = (stored, fs::read_to_string(&digest).await) {
+            if hex::encode(Sha256::digest(&data)) == expected.trim() {
+                if let Ok(text) = String::from_utf8(data) {
+                    return Ok(text.into());
+                }
+            }
+            tracing::debug!("fixture {} is corrupt, fetching it again", self.name);
+        }
+        let repo: DebRepo = match repo.starts_with("http:") || repo.starts_with("https:") {
+            true => HttpDebRepo::new(repo).await?.into(),
+            false => FSDebRepo::from_url(repo).await?.into(),
+        };
+        let data = self.fetch(&repo).await?;
+        let text = String::from_utf8(data)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        fs::create_dir_all(path.parent().unwrap()).await?;
+        write(&path, text.as_bytes()).await?;
+        write(&digest, hex::encode(Sha256::digest(&text)).as_bytes()).await?;
+        Ok(text.into())
+    }
+    // Fetches the index from `repo`, verified against its Release file.
+    async fn fetch(&self, repo: &DebRepo) -> Result<Vec<u8>> {
+        let release = repo.fetch_release(self.suite).await?;
+        let (path, size, digests) = release
+            .packages_file(self.component, self.arch)
+            .ok_or_else(|| {
+                Error::NotFound(format!(
+                    "{}/binary-{} in {}",
+                    self.component, self.arch, self.suite
+                ))
+            })?;
+        repo.fetch_verify_unpack(&path, size, digests).await
+    }
+}
+
+// Writes `data` to `path` atomically.
+async fn write(path: &Path, data: &[u8]) -> io::Result<()> {
+    let tmp = tempfile::NamedTempFile::new_in(path.parent().unwrap())?;
+    fs::write(tmp.path(), data).await?;
+    tmp.persist(path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        let index = "Package: hello\nVersion: 1.0\nArchitecture: amd64\n";
+        let packages = repo.join("dists/test/main/binary-amd64/Packages");
+        std::fs::create_dir_all(packages.parent().unwrap()).unwrap();
+        std::fs::write(&packages, index).unwrap();
+        std::fs::write(
+            repo.join("dists/test/Release"),
+            format!(
+                "Suite: test\nComponents: main\nArchitectures: amd64\nSHA256:\n \
+                 {} {} main/binary-amd64/Packages\n",
+                hex::encode(Sha256::digest(index)),
+                index.len()
+            ),
+        )
+        .unwrap();
+        let url = format!("file://{}", repo.display());
+        let fixture = Fixture {
+            name: "test",
+            repo: "",
+            suite: "test",
+            component: "main",
+            arch: "amd64",
+        };
+        let fixtures = PathBuf::from(dir.path().join("fixtures"));
+        let load = || fixture.load_with(&fixtures, &url);
+        assert_eq!(&*load().await.unwrap(), index);
+        let stored = fixture.path_in(&fixtures);
+        assert_eq!(std::fs::read_to_string(&stored).unwrap(), index);
+
+        // stored fixtures are used as long as they match their digest
+        std::fs::remove_dir_all(&repo).unwrap();
+        assert_eq!(&*load().await.unwrap(), index);
+        std::fs::write(&stored, "Package: corrupt\n").unwrap();
+        assert!(load().await.is_err());
+    }
+}
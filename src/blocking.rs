@@ -0,0 +1,226 @@
+//! Synchronous API
+//!
+//! Wrappers running the download functions to completion on the calling
+//! thread, for programs that do not use an async runtime otherwise:
+//!
+//! ```ignore
+//! let repo = blocking::DebRepo::new("https://deb.debian.org/debian/")?;
+//! let release = repo.fetch_verify_release_with_keys("bookworm", [DEBIAN_KEYRING])?;
+//! let packages = release.fetch_packages("main", "amd64")?;
+//! let mut universe = blocking::Universe::new("amd64", [packages])?;
+//! let problem = universe.problem(requirements, vec![], vec![]);
+//! for id in universe.solve(problem).map_err(|err| universe.solver_error(err))? {
+//!     universe.download_deb(id, "debs")?;
+//! }
+//! ```
+//!
+//! The wrappers dereference to the async types for everything else.
+
+use {
+    crate::{
+        changelog::Changelog,
+        control::ParseError,
+        dep11::Components,
+        digest::Digests,
+        error::Result,
+        fsrepo::FSDebRepo,
+        httprepo::HttpDebRepo,
+        packages::{Package, PackageKind, Packages},
+    },
+    async_std::task::block_on,
+    futures::io::AllowStdIo,
+    resolvo::SolvableId,
+    std::{
+        ops::{Deref, DerefMut},
+        path::Path,
+    },
+};
+
+/// A [`crate::DebRepo`] with synchronous downloads.
+#[derive(Clone)]
+pub struct DebRepo(pub crate::DebRepo);
+
+impl DebRepo {
+    /// Opens the repository at an http(s) URL, or a `file:` URL or path.
+    pub fn new(url: &str) -> Result<Self> {
+        block_on(async {
+            Ok(Self(
+                match url.starts_with("http:") || url.starts_with("https:") {
+                    true => HttpDebRepo::new(url).await?.into(),
+                    false => FSDebRepo::from_url(url).await?.into(),
+                },
+            ))
+        })
+    }
+    pub fn fetch_release(&self, distr: &str) -> Result<Release> {
+        block_on(self.0.fetch_release(distr)).map(Release)
+    }
+    pub fn fetch_verify_release(&self, distr: &str) -> Result<Release> {
+        block_on(self.0.fetch_verify_release(distr)).map(Release)
+    }
+    pub fn fetch_verify_release_with_keys<K: IntoIterator<Item = impl AsRef<[u8]>>>(
+        &self,
+        distr: &str,
+        keys: K,
+    ) -> Result<Release> {
+        block_on(self.0.fetch_verify_release_with_keys(distr, keys)).map(Release)
+    }
+    pub fn fetch(&self, path: &str) -> Result<Vec<u8>> {
+        block_on(self.0.fetch(path))
+    }
+    pub fn fetch_unpack(&self, path: &str) -> Result<Vec<u8>> {
+        block_on(self.0.fetch_unpack(path))
+    }
+    pub fn fetch_verify(
+        &self,
+        path: &str,
+        size: usize,
+        digest: impl Into<Digests>,
+    ) -> Result<Vec<u8>> {
+        block_on(self.0.fetch_verify(path, size, digest))
+    }
+    pub fn fetch_verify_unpack(
+        &self,
+        path: &str,
+        size: usize,
+        digest: impl Into<Digests>,
+    ) -> Result<Vec<u8>> {
+        block_on(self.0.fetch_verify_unpack(path, size, digest))
+    }
+    /// Copies the file at `path` to `w`, verifying it.
+    pub fn copy_verify<W: std::io::Write + Send>(
+        &self,
+        w: W,
+        path: &str,
+        size: usize,
+        digest: impl Into<Digests>,
+    ) -> Result<u64> {
+        block_on(self.0.copy_verify(AllowStdIo::new(w), path, size, digest))
+    }
+    pub fn changelog(
+        &self,
+        package: &Package<'_>,
+        metadata: Option<&DebRepo>,
+    ) -> Result<Changelog> {
+        block_on(self.0.changelog(package, metadata.map(|m| &m.0)))
+    }
+    pub fn copyright(&self, package: &Package<'_>, metadata: Option<&DebRepo>) -> Result<String> {
+        block_on(self.0.copyright(package, metadata.map(|m| &m.0)))
+    }
+}
+
+impl Deref for DebRepo {
+    type Target = crate::DebRepo;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// A [`crate::Release`] with synchronous downloads.
+pub struct Release(pub crate::Release);
+
+impl Release {
+    pub fn fetch_packages(&self, component: &str, arch: &str) -> Result<Packages<Box<str>>> {
+        block_on(self.0.fetch_packages(component, arch))
+    }
+    pub fn fetch_index(
+        &self,
+        kind: PackageKind,
+        component: &str,
+        arch: &str,
+    ) -> Result<Packages<Box<str>>> {
+        block_on(self.0.fetch_index(kind, component, arch))
+    }
+    pub fn fetch_components(&self, component: &str, arch: &str) -> Result<Components> {
+        block_on(self.0.fetch_components(component, arch))
+    }
+}
+
+impl Deref for Release {
+    type Target = crate::Release;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// A [`crate::Universe`] with synchronous downloads.
+pub struct Universe<S: AsRef<str> + 'static>(pub crate::Universe<S>);
+
+impl<S: AsRef<str> + 'static> Universe<S> {
+    pub fn new(
+        arch: impl AsRef<str>,
+        from: impl IntoIterator<Item = Packages<S>>,
+    ) -> std::result::Result<Self, ParseError> {
+        crate::Universe::new(arch, from).map(Self)
+    }
+    /// Copies the .deb of the package to `w`, verifying it.
+    pub fn copy_deb_file<W: std::io::Write + Send>(&self, w: W, id: SolvableId) -> Result<u64> {
+        block_on(self.0.copy_deb_file(AllowStdIo::new(w), id))
+    }
+    /// Downloads the .deb of the package into the directory `dir`, under its
+    /// file name in the pool. Returns the path of the file.
+    pub fn download_deb(
+        &self,
+        id: SolvableId,
+        dir: impl AsRef<Path>,
+    ) -> Result<std::path::PathBuf> {
+        let (path, _, _) = self.0.package(id).repo_file()?;
+        let path = dir.as_ref().join(path.rsplit('/').next().unwrap_or(path));
+        self.copy_deb_file(std::fs::File::create(&path)?, id)?;
+        Ok(path)
+    }
+}
+
+impl<S: AsRef<str> + 'static> Deref for Universe<S> {
+    type Target = crate::Universe<S>;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<S: AsRef<str> + 'static> DerefMut for Universe<S> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blocking() {
+        let dir = tempfile::tempdir().unwrap();
+        let deb = b"!<arch>\n".to_vec();
+        let pool = dir.path().join("pool/main/h/hello");
+        std::fs::create_dir_all(&pool).unwrap();
+        std::fs::write(pool.join("hello_1.0_amd64.deb"), &deb).unwrap();
+        let index = format!(
+            "Package: hello\nVersion: 1.0\nArchitecture: amd64\n\
+             Filename: pool/main/h/hello/hello_1.0_amd64.deb\nSize: {}\nSHA256: {}\n",
+            deb.len(),
+            hex::encode(<sha2::Sha256 as sha2::Digest>::digest(&deb)),
+        );
+        let packages = "dists/test/main/binary-amd64/Packages";
+        std::fs::create_dir_all(dir.path().join(packages).parent().unwrap()).unwrap();
+        std::fs::write(dir.path().join(packages), &index).unwrap();
+        std::fs::write(
+            dir.path().join("dists/test/Release"),
+            format!(
+                "Suite: test\nComponents: main\nArchitectures: amd64\nSHA256:\n {} {} main/binary-amd64/Packages\n",
+                hex::encode(<sha2::Sha256 as sha2::Digest>::digest(index.as_bytes())),
+                index.len()
+            ),
+        )
+        .unwrap();
+        let repo = DebRepo::new(dir.path().to_str().unwrap()).unwrap();
+        let release = repo.fetch_release("test").unwrap();
+        assert_eq!(release.components().collect::<Vec<_>>(), ["main"]);
+        let packages = release.fetch_packages("main", "amd64").unwrap();
+        let mut universe = Universe::new("amd64", [packages]).unwrap();
+        let solution = universe.check_coinstallable(&["hello"]).unwrap();
+        let path = universe.download_deb(solution[0], dir.path()).unwrap();
+        assert_eq!(std::fs::read(path).unwrap(), deb);
+        assert!(repo.fetch_release("missing").is_err());
+    }
+}
@@ -0,0 +1,147 @@
+//! Build information (`.buildinfo`) files
+//!
+//! A `.buildinfo` file records the environment a package was built in: the
+//! exact versions of the packages installed during the build, the relevant
+//! environment variables and the checksums of the build artifacts. To
+//! rebuild in the recorded environment, pass
+//! [`Buildinfo::installed_build_depends`] to [`crate::Universe::problem`],
+//! or use [`crate::Universe::buildinfo_problem`].
+
+use crate::{
+    control::{MutableControlStanza, ParseError},
+    dsc::{parse_checksums, strip_signature, DscFile},
+    version::{Dependency, ParsedDependencyIterator, Version, UNRESTRICTED},
+};
+
+/// A parsed `.buildinfo` file.
+pub struct Buildinfo {
+    control: MutableControlStanza,
+    checksums: Vec<DscFile>,
+}
+
+impl Buildinfo {
+    /// Parses a `.buildinfo` file. An OpenPGP clearsign armor is stripped
+    /// without verifying the signature.
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let control = MutableControlStanza::parse(strip_signature(text)?.into_owned())?;
+        for field in ["Source", "Version"] {
+            if control.field(field).is_none() {
+                return Err(format!("Field {} not found", field).into());
+            }
+        }
+        let checksums = parse_checksums(&control)?;
+        let buildinfo = Self { control, checksums };
+        // Fail early on malformed dependencies
+        for dep in buildinfo.installed_build_depends() {
+            dep?;
+        }
+        Ok(buildinfo)
+    }
+    pub fn source(&self) -> &str {
+        self.field("Source").unwrap_or_default()
+    }
+    pub fn version(&self) -> &str {
+        self.field("Version").unwrap_or_default()
+    }
+    pub fn build_architecture(&self) -> Option<&str> {
+        self.field("Build-Architecture")
+    }
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.control.field(name).map(str::trim)
+    }
+    /// The build artifacts with their sizes and digests.
+    pub fn checksums(&self) -> &[DscFile] {
+        &self.checksums
+    }
+    /// The packages installed during the build, with their exact versions.
+    pub fn installed_build_depends(
+        &self,
+    ) -> impl Iterator<Item = Result<Dependency<Option<&str>, &str, Version<&str>>, ParseError>>
+    {
+        ParsedDependencyIterator::new(
+            self.field("Installed-Build-Depends").unwrap_or_default(),
+            &UNRESTRICTED,
+        )
+    }
+    /// The recorded environment variables, e.g. `("LANG", "C.UTF-8")`.
+    pub fn environment(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.field("Environment")
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| line.trim().split_once('='))
+            .map(|(name, value)| (name, value.trim_matches('"')))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::packages::Packages, crate::universe::Universe};
+
+    const BUILDINFO: &str = "\
+Format: 1.0
+Source: hello
+Binary: hello
+Architecture: amd64
+Version: 2.10-3
+Checksums-Md5:
+ a7cf6ec23d5ad2ae3e1f9b0d4d8e4c5f 53172 hello_2.10-3_amd64.deb
+Checksums-Sha256:
+ 1d5e4bb7b8c4f4a7d6c0cde4eb2cd0d0c9c6a4c1c2a4f0b8c1d9f0a3d0e6b7c2 53172 hello_2.10-3_amd64.deb
+Build-Origin: Debian
+Build-Architecture: amd64
+Installed-Build-Depends:
+ base-files (= 12.4+deb12u5),
+ gcc-12 (= 12.2.0-14),
+ libc6 (= 2.36-9+deb12u4)
+Environment:
+ DEB_BUILD_OPTIONS=\"parallel=4\"
+ LANG=\"C.UTF-8\"
+ SOURCE_DATE_EPOCH=\"1675590390\"
+";
+
+    #[test]
+    fn test_parse_buildinfo() {
+        let info = Buildinfo::parse(BUILDINFO).unwrap();
+        assert_eq!(info.source(), "hello");
+        assert_eq!(info.build_architecture(), Some("amd64"));
+        assert_eq!(info.checksums().len(), 1);
+        assert!(info.checksums()[0].digests.sha256().is_some());
+        let env: Vec<_> = info.environment().collect();
+        assert_eq!(env[1], ("LANG", "C.UTF-8"));
+        assert_eq!(info.installed_build_depends().count(), 3);
+    }
+
+    #[test]
+    fn test_buildinfo_problem() {
+        let mut packages = String::new();
+        for (name, versions) in [
+            ("base-files", ["12.4+deb12u5", "12.4+deb12u8"]),
+            ("gcc-12", ["12.2.0-14", "12.2.0-14+deb12u1"]),
+            ("libc6", ["2.36-9+deb12u4", "2.36-9+deb12u9"]),
+        ] {
+            for version in versions {
+                packages += &format!(
+                    "Package: {}\nVersion: {}\nArchitecture: amd64\n\n",
+                    name, version
+                );
+            }
+        }
+        let info = Buildinfo::parse(BUILDINFO).unwrap();
+        let mut uni = Universe::new("amd64", vec![Packages::new_test(packages).unwrap()]).unwrap();
+        let problem = uni.buildinfo_problem(&info).unwrap();
+        let solution = uni.solve(problem).unwrap();
+        let mut installed: Vec<_> = solution
+            .iter()
+            .map(|id| uni.display_solvable(*id).to_string())
+            .collect();
+        installed.sort();
+        assert_eq!(
+            installed,
+            [
+                "base-files:amd64=12.4+deb12u5",
+                "gcc-12:amd64=12.2.0-14",
+                "libc6:amd64=2.36-9+deb12u4"
+            ]
+        );
+    }
+}
@@ -0,0 +1,191 @@
+//! Download cache and offline mode
+//!
+//! [`Cached`] wraps a transport and keeps the files it fetches in a local
+//! directory laid out as the repository. Pool files and `by-hash` indexes
+//! never change and are served from the cache once there; the other files,
+//! e.g. Release files, are fetched again as long as the network is used.
+//!
+//! [`OfflineMode::Offline`] turns the network off for the whole process:
+//! the cached transports serve their cache only, and the HTTP ones refuse to
+//! connect, so that a reproducible build can prove it did not use the
+//! network. The files missing from the cache fail with
+//! [`Error::NotCached`](crate::Error::NotCached):
+//!
+//! ```ignore
+//! OfflineMode::Offline.set();
+//! let repo: DebRepo = Cached::new(HttpDebRepo::new(url).await?, "/var/cache/debrepo").into();
+//! let release = repo.fetch_release("bookworm").await?;
+//! ```
+//!
+//! A single cache can also be taken offline with [`Cached::offline_mode`],
+//! leaving the rest of the process online.
+
+use {
+    crate::{
+        error::Error,
+        fsrepo::FSDebRepo,
+        pool,
+        transport::{Transport, TransportResponse},
+    },
+    async_std::{fs, io, path::PathBuf},
+    async_trait::async_trait,
+    std::{
+        ops::Range,
+        sync::atomic::{AtomicBool, Ordering},
+    },
+};
+
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+/// Whether the process may use the network.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OfflineMode {
+    #[default]
+    Online,
+    /// Files are served from the download cache only
+    Offline,
+}
+
+impl OfflineMode {
+    /// Sets the mode of the process.
+    pub fn set(self) {
+        OFFLINE.store(self == OfflineMode::Offline, Ordering::SeqCst);
+    }
+    /// Returns the mode of the process.
+    pub fn current() -> Self {
+        if OFFLINE.load(Ordering::SeqCst) {
+            OfflineMode::Offline
+        } else {
+            OfflineMode::Online
+        }
+    }
+    // Fails with `Error::NotCached` when the process is offline.
+    pub(crate) fn check(path: &str) -> io::Result<()> {
+        Self::current().check_path(path)
+    }
+    fn check_path(self, path: &str) -> io::Result<()> {
+        match self {
+            OfflineMode::Online => Ok(()),
+            OfflineMode::Offline => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                Error::NotCached(path.to_string()),
+            )),
+        }
+    }
+}
+
+/// A transport keeping the files it fetches in a download cache.
+pub struct Cached<T: Transport> {
+    inner: T,
+    dir: PathBuf,
+    mode: Option<OfflineMode>,
+}
+
+impl<T: Transport> Cached<T> {
+    /// Wraps `inner`, keeping the files in the directory `dir`.
+    pub fn new(inner: T, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            inner,
+            dir: dir.into(),
+            mode: None,
+        }
+    }
+    /// Sets the mode of this cache, overriding the mode of the process.
+    pub fn offline_mode(mut self, mode: OfflineMode) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+    // Fetches the file at `path` into the cache.
+    async fn store(&self, path: &str) -> io::Result<()> {
+        let dst = self.dir.join(path);
+        let parent = dst.parent().unwrap_or(&self.dir);
+        fs::create_dir_all(parent).await?;
+        let rsp = self.inner.get(path, None).await?;
+        let tmp = tempfile::NamedTempFile::new_in(parent)?;
+        let mut file = fs::File::from(tmp.reopen()?);
+        io::copy(rsp.reader, &mut file).await?;
+        file.sync_all().await?;
+        tmp.persist(&dst)?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<T: Transport> Transport for Cached<T> {
+    async fn get(&self, path: &str, range: Option<Range<u64>>) -> io::Result<TransportResponse> {
+        pool::validate_filename(path)?;
+        let immutable = path.starts_with("pool/") || path.contains("/by-hash/");
+        let cached = self.dir.join(path).is_file().await;
+        if !(cached && immutable) {
+            match self.mode.unwrap_or_else(OfflineMode::current) {
+                OfflineMode::Online => self.store(path).await?,
+                OfflineMode::Offline if cached => {}
+                mode => mode.check_path(path)?,
+            }
+        }
+        FSDebRepo::new(&self.dir).await?.get(path, range).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{httprepo::HttpDebRepo, repo::DebRepo},
+    };
+
+    #[async_std::test]
+    async fn test_cached() {
+        let dir = tempfile::tempdir().unwrap();
+        let remote = dir.path().join("remote");
+        let write = |path: &str, data: &str| {
+            let path = remote.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, data).unwrap();
+        };
+        write("dists/stable/Release", "old");
+        write("pool/main/a.deb", "deb");
+        let cache = dir.path().join("cache");
+        let repo: DebRepo = Cached::new(FSDebRepo::new(&remote).await.unwrap(), &cache).into();
+        assert_eq!(repo.fetch("dists/stable/Release").await.unwrap(), b"old");
+        assert_eq!(repo.fetch("pool/main/a.deb").await.unwrap(), b"deb");
+        write("dists/stable/Release", "new");
+        write("pool/main/a.deb", "changed");
+        assert_eq!(repo.fetch("dists/stable/Release").await.unwrap(), b"new");
+        assert_eq!(repo.fetch("pool/main/a.deb").await.unwrap(), b"deb");
+        assert!(repo.fetch("../remote/pool/main/a.deb").await.is_err());
+
+        let repo: DebRepo = Cached::new(FSDebRepo::new(&remote).await.unwrap(), &cache)
+            .offline_mode(OfflineMode::Offline)
+            .into();
+        write("dists/stable/Release", "newer");
+        write("dists/stable/InRelease", "new");
+        assert_eq!(repo.fetch("dists/stable/Release").await.unwrap(), b"new");
+        assert!(matches!(
+            repo.fetch("dists/stable/InRelease").await,
+            Err(Error::NotCached(_))
+        ));
+    }
+
+    // Restores the online mode of the process when dropped.
+    struct OnlineGuard;
+
+    impl Drop for OnlineGuard {
+        fn drop(&mut self) {
+            OfflineMode::Online.set();
+        }
+    }
+
+    #[async_std::test]
+    async fn test_offline_process() {
+        // the only test changing the mode of the process
+        let _guard = OnlineGuard;
+        OfflineMode::Offline.set();
+        let http: DebRepo = HttpDebRepo::new("http://127.0.0.1:9/")
+            .await
+            .unwrap()
+            .into();
+        let http = http.fetch("dists/stable/Release").await;
+        assert!(matches!(http, Err(Error::NotCached(_))));
+    }
+}
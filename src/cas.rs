@@ -0,0 +1,246 @@
+//! Content-addressable package store
+//!
+//! A [`Cas`] keeps package files by their SHA256 digest, e.g.
+//! `sha256/3a/3a7bd3e2...`, so that the projects sharing a machine, and the
+//! versions of a package shared by their solutions, are downloaded and
+//! stored once. [`export`] lays the packages of a solution out in a
+//! directory as hard links into the store:
+//!
+//! ```ignore
+//! let cas = Cas::new("/var/cache/debrepo/cas");
+//! let solution = universe.solve(problem)?;
+//! cas::export(&cas, &universe, &solution, "build/debs", ExportLayout::Flat).await?;
+//! ```
+//!
+//! When the target is on another filesystem, the files are cloned instead
+//! on Linux filesystems sharing blocks between files (btrfs, XFS, ...), as
+//! `cp --reflink` does, and copied otherwise.
+
+use {
+    crate::{
+        digest::HashAlgorithm,
+        error::{Error, Result},
+        metrics::{self, Counter},
+        pool,
+        universe::Universe,
+    },
+    async_std::{
+        fs,
+        path::{Path, PathBuf},
+    },
+    resolvo::SolvableId,
+    std::io,
+};
+
+/// A content-addressable store of package files.
+#[derive(Clone, Debug)]
+pub struct Cas {
+    dir: PathBuf,
+}
+
+/// The layout of the packages exported from a [`Cas`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExportLayout {
+    /// The apt archive names in a single directory, e.g.
+    /// `hello_2.10-3_amd64.deb`
+    #[default]
+    Flat,
+    /// The `Filename` of the packages, e.g.
+    /// `pool/main/h/hello/hello_2.10-3_amd64.deb`
+    Pool,
+}
+
+impl Cas {
+    /// Opens the store in the directory `dir`.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+    /// Returns the path of the file with the hex-encoded SHA256 `digest`.
+    pub fn path(&self, digest: &str) -> PathBuf {
+        let prefix = digest.get(..2).unwrap_or_default();
+        self.dir.join("sha256").join(prefix).join(digest)
+    }
+    /// Returns the path in the store of the package file of `id`, fetching
+    /// and verifying it first unless already there.
+    pub async fn fetch<S: AsRef<str> + 'static>(
+        &self,
+        universe: &Universe<S>,
+        id: SolvableId,
+    ) -> Result<PathBuf> {
+        let package = universe.package(id);
+        let (_, _, digests) = package.repo_file()?;
+        let digest = digests
+            .get(HashAlgorithm::Sha256)
+            .map(hex::encode)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("package {} lacks a SHA256 digest", package),
+                )
+            })?;
+        let path = self.path(&digest);
+        if path.is_file().await {
+            metrics::increment(Counter::CacheHits, 1);
+            return Ok(path);
+        }
+        let dir = path.parent().unwrap();
+        fs::create_dir_all(dir).await?;
+        let tmp = tempfile::NamedTempFile::new_in(dir)?;
+        universe
+            .copy_deb_file(fs::File::from(tmp.reopen()?), id)
+            .await?;
+        tmp.persist(&path).map_err(|err| Error::Io(err.error))?;
+        Ok(path)
+    }
+}
+
+/// Fetches the packages of `solution` into `cas` and links them into `dir`
+/// laid out as `layout`. Returns the exported paths, in the order of the
+/// solution.
+pub async fn export<S: AsRef<str> + 'static>(
+    cas: &Cas,
+    universe: &Universe<S>,
+    solution: &[SolvableId],
+    dir: impl AsRef<Path>,
+    layout: ExportLayout,
+) -> Result<Vec<PathBuf>> {
+    let dir = dir.as_ref();
+    let mut exported = Vec::with_capacity(solution.len());
+    for &id in solution {
+        let src = cas.fetch(universe, id).await?;
+        let package = universe.package(id);
+        let name = match layout {
+            ExportLayout::Flat => format!(
+                "{}_{}_{}.deb",
+                package.name(),
+                package.version().as_ref().replace(':', "%3a"),
+                package.architecture()
+            ),
+            ExportLayout::Pool => {
+                let (path, _, _) = package.repo_file()?;
+                pool::validate_filename(path)?;
+                path.trim_start_matches("./").to_string()
+            }
+        };
+        let dst = dir.join(name);
+        fs::create_dir_all(dst.parent().unwrap()).await?;
+        match fs::remove_file(&dst).await {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err.into()),
+            _ => {}
+        }
+        if fs::hard_link(&src, &dst).await.is_err() && reflink(&src, &dst).await.is_err() {
+            fs::copy(&src, &dst).await?;
+        }
+        exported.push(dst);
+    }
+    Ok(exported)
+}
+
+// Clones `src` into `dst`, a new file sharing the blocks of `src`.
+#[cfg(target_os = "linux")]
+async fn reflink(src: &Path, dst: &Path) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+    // FICLONE from linux/fs.h
+    nix::ioctl_write_int!(ficlone, 0x94, 9);
+    let (src, dst) = (src.to_path_buf(), dst.to_path_buf());
+    async_std::task::spawn_blocking(move || {
+        let src = std::fs::File::open(src)?;
+        let file = std::fs::File::create(&dst)?;
+        // SAFETY: both descriptors are open for the duration of the call
+        match unsafe { ficlone(file.as_raw_fd(), src.as_raw_fd() as _) } {
+            Ok(_) => Ok(()),
+            Err(err) => {
+                drop(file);
+                let _ = std::fs::remove_file(&dst);
+                Err(err.into())
+            }
+        }
+    })
+    .await
+}
+
+#[cfg(not(target_os = "linux"))]
+async fn reflink(_src: &Path, _dst: &Path) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{fsrepo::FSDebRepo, packages::Packages, repo::DebRepo, version::Dependency},
+        digest::Digest,
+        std::os::unix::fs::MetadataExt,
+    };
+
+    #[async_std::test]
+    async fn test_export() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let mut index = String::new();
+        for (name, depends) in [("alpha", "beta"), ("beta", "")] {
+            let path = format!("pool/main/{}_1:1.0_amd64.deb", name);
+            std::fs::create_dir_all(root.join("repo/pool/main")).unwrap();
+            std::fs::write(root.join("repo").join(&path), name).unwrap();
+            index.push_str(&format!(
+                "Package: {}\nVersion: 1:1.0\nArchitecture: amd64\nDepends: {}\n\
+                 Filename: {}\nSize: {}\nSHA256: {}\n\n",
+                name,
+                depends,
+                path,
+                name.len(),
+                hex::encode(sha2::Sha256::digest(name))
+            ));
+        }
+        let repo: DebRepo = FSDebRepo::new(root.join("repo")).await.unwrap().into();
+        let mut universe =
+            Universe::new("amd64", vec![Packages::new(repo, index).unwrap()]).unwrap();
+        let problem =
+            universe.problem(vec![Dependency::try_from("alpha").unwrap()], vec![], vec![]);
+        let solution = universe.solve(problem).unwrap();
+
+        let cas = Cas::new(root.join("cas"));
+        let flat = export(
+            &cas,
+            &universe,
+            &solution,
+            root.join("flat"),
+            ExportLayout::Flat,
+        )
+        .await
+        .unwrap();
+        let mut names: Vec<_> = flat
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["alpha_1%3a1.0_amd64.deb", "beta_1%3a1.0_amd64.deb"]);
+        // the repository is not needed any more
+        std::fs::remove_dir_all(root.join("repo")).unwrap();
+        let pool = export(
+            &cas,
+            &universe,
+            &solution,
+            root.join("tree"),
+            ExportLayout::Pool,
+        )
+        .await
+        .unwrap();
+        let alpha = root.join("tree/pool/main/alpha_1:1.0_amd64.deb");
+        assert!(pool.contains(&alpha.clone().into()));
+        assert_eq!(std::fs::read(&alpha).unwrap(), b"alpha");
+        assert_eq!(std::fs::metadata(&alpha).unwrap().nlink(), 3);
+    }
+
+    #[async_std::test]
+    async fn test_reflink() {
+        let dir = tempfile::tempdir().unwrap();
+        let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+        std::fs::write(&src, "package").unwrap();
+        // cloned where the filesystem supports it, left alone otherwise
+        match reflink(Path::new(&src), Path::new(&dst)).await {
+            Ok(()) => assert_eq!(std::fs::read(&dst).unwrap(), b"package"),
+            Err(_) => assert!(!dst.exists()),
+        }
+    }
+}
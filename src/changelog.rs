@@ -0,0 +1,300 @@
+//! Debian changelogs
+//!
+//! A `debian/changelog` file lists the uploads of a package, newest first.
+//! Each entry has a header line, the change details and a trailer line:
+//!
+//! ```text
+//! hello (2.10-3) unstable; urgency=medium
+//!
+//!   * Fix the build with GCC 14.
+//!
+//!  -- Santiago Vila <sanvila@debian.org>  Sat, 27 Jul 2024 12:00:00 +0200
+//! ```
+//!
+//! The change details are also parsed into [`ChangelogItem`]s, following
+//! the `*`, `-` and `+` bullets and the `[ Name ]` lines of team uploads.
+//!
+//! [`crate::DebRepo::changelog`] fetches the changelog of a binary package.
+
+use {
+    crate::{control::ParseError, version::Version},
+    chrono::{DateTime, FixedOffset},
+};
+
+/// A bulleted change of a changelog entry.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChangelogItem {
+    /// The name from the `[ Name ]` line the item follows, if any
+    pub author: Option<String>,
+    /// The text, with the continuation lines joined
+    pub text: String,
+    /// The items nested under this one
+    pub items: Vec<ChangelogItem>,
+}
+
+/// An entry of a changelog.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChangelogEntry {
+    pub package: String,
+    pub version: Version<String>,
+    /// The target distributions, e.g. `unstable`
+    pub distributions: Vec<String>,
+    pub urgency: String,
+    /// The lines between the header and the trailer, blank lines around
+    /// them dropped
+    pub changes: Vec<String>,
+    /// The maintainer from the trailer, e.g. `Jane Doe <jane@example.org>`
+    pub maintainer: String,
+    /// The date from the trailer, in RFC 2822 format
+    pub date: String,
+    /// The parsed date, None if malformed
+    pub timestamp: Option<DateTime<FixedOffset>>,
+    /// The bulleted changes
+    pub items: Vec<ChangelogItem>,
+}
+
+impl ChangelogEntry {
+    /// The maintainer name, without the email address.
+    pub fn maintainer_name(&self) -> &str {
+        self.maintainer
+            .split_once('<')
+            .map_or(self.maintainer.as_str(), |(name, _)| name)
+            .trim()
+    }
+    pub fn maintainer_email(&self) -> Option<&str> {
+        let (_, email) = self.maintainer.split_once('<')?;
+        email.split_once('>').map(|(email, _)| email.trim())
+    }
+}
+
+/// A parsed changelog.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Changelog {
+    /// The entries, newest first
+    pub entries: Vec<ChangelogEntry>,
+}
+
+impl Changelog {
+    /// Parses a changelog. Parsing stops at the first line that is neither
+    /// part of an entry nor blank, such as `Local variables:` or `Old
+    /// Changelog:`.
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut entries = vec![];
+        let mut lines = text.lines().enumerate();
+        while let Some((n, line)) = lines.next() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let Some(mut entry) = parse_header(line) else {
+                if entries.is_empty() {
+                    return Err(format!("line {}: invalid changelog header", n + 1).into());
+                }
+                break;
+            };
+            loop {
+                let Some((n, line)) = lines.next() else {
+                    return Err(format!("{}: missing trailer line", entry.version).into());
+                };
+                if let Some(trailer) = line.strip_prefix(" -- ") {
+                    let (maintainer, date) = trailer.split_once("  ").ok_or_else(|| {
+                        ParseError::from(format!("line {}: invalid trailer line", n + 1))
+                    })?;
+                    entry.maintainer = maintainer.trim().to_string();
+                    entry.date = date.trim().to_string();
+                    entry.timestamp = DateTime::parse_from_rfc2822(&entry.date).ok();
+                    break;
+                } else if line.starts_with(|c: char| !c.is_whitespace()) {
+                    return Err(format!("line {}: missing trailer line", n + 1).into());
+                }
+                entry.changes.push(line.trim_end().to_string());
+            }
+            while entry.changes.last().is_some_and(|l| l.is_empty()) {
+                entry.changes.pop();
+            }
+            let leading = entry.changes.iter().take_while(|l| l.is_empty()).count();
+            entry.changes.drain(..leading);
+            entry.items = parse_items(&entry.changes);
+            entries.push(entry);
+        }
+        Ok(Self { entries })
+    }
+}
+
+// `package (version) distributions; urgency=value, key=value`
+fn parse_header(line: &str) -> Option<ChangelogEntry> {
+    let (package, rest) = line.split_once(" (")?;
+    let (version, rest) = rest.split_once(')')?;
+    let (distributions, fields) = rest.split_once(';')?;
+    let version = Version::from(version.to_string());
+    if package.is_empty() || package.contains(char::is_whitespace) || !version.is_valid() {
+        return None;
+    }
+    let urgency = fields
+        .split(',')
+        .filter_map(|field| field.trim().split_once('='))
+        .find(|(key, _)| key.eq_ignore_ascii_case("urgency"))
+        .map_or("low", |(_, value)| value.trim());
+    Some(ChangelogEntry {
+        package: package.to_string(),
+        version,
+        distributions: distributions.split_whitespace().map(String::from).collect(),
+        urgency: urgency.to_string(),
+        changes: vec![],
+        maintainer: String::new(),
+        date: String::new(),
+        timestamp: None,
+        items: vec![],
+    })
+}
+
+// Builds the item tree from the change lines. An item nests under the
+// closest item before it with a lesser indentation.
+fn parse_items(lines: &[String]) -> Vec<ChangelogItem> {
+    let mut items = vec![];
+    // the path to the last item with the indentations of its bullets
+    let mut open: Vec<usize> = vec![];
+    let mut author = None;
+    for line in lines {
+        let text = line.trim_start();
+        let indent = line.len() - text.len();
+        if text.is_empty() {
+            continue;
+        }
+        if let Some(name) = text.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+            author = Some(name.trim().to_string());
+            open.clear();
+            continue;
+        }
+        let bullet = ["* ", "- ", "+ "]
+            .iter()
+            .find_map(|b| text.strip_prefix(b))
+            .or_else(|| (text.len() == 1 && "*-+".contains(text)).then_some(""));
+        match bullet {
+            Some(text) => {
+                while open.last().is_some_and(|i| *i >= indent) {
+                    open.pop();
+                }
+                let item = ChangelogItem {
+                    author: author.clone(),
+                    text: text.trim().to_string(),
+                    items: vec![],
+                };
+                last_items(&mut items, open.len()).push(item);
+                open.push(indent);
+            }
+            None => match open.len() {
+                // not part of an item
+                0 => items.push(ChangelogItem {
+                    author: author.clone(),
+                    text: text.to_string(),
+                    items: vec![],
+                }),
+                depth => {
+                    let item = last_items(&mut items, depth - 1).last_mut().unwrap();
+                    if !item.text.is_empty() {
+                        item.text.push(' ');
+                    }
+                    item.text.push_str(text);
+                }
+            },
+        }
+    }
+    items
+}
+
+// The list of the items nested `depth` levels down along the last items.
+fn last_items(items: &mut Vec<ChangelogItem>, depth: usize) -> &mut Vec<ChangelogItem> {
+    match depth {
+        0 => items,
+        _ => last_items(&mut items.last_mut().unwrap().items, depth - 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_changelog() {
+        let text = "\
+hello (2.10-3) unstable; urgency=medium
+
+  * Fix the build with GCC 14.
+    Closes: #1075000
+
+ -- Jane Doe <jane@example.org>  Sat, 27 Jul 2024 12:00:00 +0200
+
+hello (2.10-2) unstable; urgency=low
+
+  * Initial upload.
+
+ -- Jane Doe <jane@example.org>  Mon, 01 Jan 2024 10:00:00 +0000
+
+Local variables:
+mode: debian-changelog
+";
+        let changelog = Changelog::parse(text).unwrap();
+        assert_eq!(changelog.entries.len(), 2);
+        let entry = &changelog.entries[0];
+        assert_eq!(entry.package, "hello");
+        assert_eq!(entry.version, "2.10-3");
+        assert_eq!(entry.distributions, ["unstable"]);
+        assert_eq!(entry.urgency, "medium");
+        assert_eq!(
+            entry.changes,
+            ["  * Fix the build with GCC 14.", "    Closes: #1075000"]
+        );
+        assert_eq!(entry.maintainer, "Jane Doe <jane@example.org>");
+        assert_eq!(entry.date, "Sat, 27 Jul 2024 12:00:00 +0200");
+        assert_eq!(entry.maintainer_name(), "Jane Doe");
+        assert_eq!(entry.maintainer_email(), Some("jane@example.org"));
+        let timestamp = entry.timestamp.unwrap();
+        assert_eq!(timestamp.to_rfc3339(), "2024-07-27T12:00:00+02:00");
+        assert_eq!(entry.items.len(), 1);
+        assert_eq!(
+            entry.items[0].text,
+            "Fix the build with GCC 14. Closes: #1075000"
+        );
+        assert!(Changelog::parse("not a changelog").is_err());
+        assert!(Changelog::parse("hello (1.0) unstable; urgency=low\n\n  * x\n").is_err());
+    }
+
+    #[test]
+    fn test_items() {
+        let text = "\
+hello (2.10-4) unstable bookworm-backports; urgency=high
+
+  [ Jane Doe ]
+  * New upstream release.
+    - Drop patches applied upstream:
+      + fix-gcc14.patch
+    - Refresh the other ones.
+  * Bump Standards-Version.
+
+  [ John Roe ]
+  * Team upload.
+
+ -- John Roe <john@example.org>  Tue, 10 Sep 2024 08:00:00 +0000
+";
+        let entry = &Changelog::parse(text).unwrap().entries[0];
+        assert_eq!(entry.distributions, ["unstable", "bookworm-backports"]);
+        assert_eq!(entry.urgency, "high");
+        let summary: Vec<_> = entry
+            .items
+            .iter()
+            .map(|item| (item.author.as_deref(), item.text.as_str(), item.items.len()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (Some("Jane Doe"), "New upstream release.", 2),
+                (Some("Jane Doe"), "Bump Standards-Version.", 0),
+                (Some("John Roe"), "Team upload.", 0),
+            ]
+        );
+        let nested = &entry.items[0].items;
+        assert_eq!(nested[0].text, "Drop patches applied upstream:");
+        assert_eq!(nested[0].items[0].text, "fix-gcc14.patch");
+        assert_eq!(nested[1].text, "Refresh the other ones.");
+    }
+}
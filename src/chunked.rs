@@ -0,0 +1,351 @@
+//! Chunked transfers (experimental)
+//!
+//! A repository can publish, next to a large file, a manifest `<file>.chunks`
+//! listing the SHA256 digests and sizes of the content-defined chunks of the
+//! file. As the chunk boundaries follow the content, an update of the file
+//! changes only the chunks around the edits. [`Chunked`] wraps a transport
+//! and keeps the chunks it fetches in a local store: when a file has a
+//! manifest, only the chunks missing from the store are fetched, as ranges
+//! of the file itself, so repeated syncs of a mirror or a cache download
+//! little more than what changed. Files without a manifest are fetched as
+//! usual.
+//!
+//! The manifests are made with [`ChunkManifest::new`]:
+//!
+//! ```ignore
+//! let data = std::fs::read("dists/bookworm/main/binary-amd64/Packages")?;
+//! std::fs::write(
+//!     "dists/bookworm/main/binary-amd64/Packages.chunks",
+//!     ChunkManifest::new(&data).to_string(),
+//! )?;
+//! ```
+
+use {
+    crate::transport::{Transport, TransportResponse},
+    async_std::{
+        fs,
+        io::{self, prelude::*, Cursor},
+        path::PathBuf,
+    },
+    async_trait::async_trait,
+    sha2::{Digest, Sha256},
+    std::{fmt, ops::Range},
+};
+
+/// The suffix of the manifests of chunked files.
+pub const CHUNK_MANIFEST_SUFFIX: &str = ".chunks";
+
+const MIN_CHUNK: usize = 16 << 10;
+const MAX_CHUNK: usize = 256 << 10;
+// Cuts on average every 64 KiB past the minimum size.
+const CHUNK_MASK: u64 = (1 << 16) - 1;
+
+// The random values of the gear rolling hash, from a splitmix64 sequence.
+const GEAR: [u64; 256] = {
+    let mut gear = [0u64; 256];
+    let mut state = 0x9e37_79b9_7f4a_7c15u64;
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        gear[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    gear
+};
+
+// Returns the length of the chunk at the start of `data`.
+fn cut(data: &[u8]) -> usize {
+    if data.len() <= MIN_CHUNK {
+        return data.len();
+    }
+    let mut hash = 0u64;
+    for (i, &byte) in data.iter().enumerate().take(MAX_CHUNK).skip(MIN_CHUNK) {
+        hash = (hash << 1).wrapping_add(GEAR[byte as usize]);
+        if hash & CHUNK_MASK == 0 {
+            return i + 1;
+        }
+    }
+    data.len().min(MAX_CHUNK)
+}
+
+/// A chunk of a file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Chunk {
+    /// The hex-encoded SHA256 digest of the chunk
+    pub digest: String,
+    pub size: u64,
+}
+
+/// The list of the chunks of a file, written one per line as
+/// `<sha256> <size>`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChunkManifest {
+    pub chunks: Vec<Chunk>,
+}
+
+impl ChunkManifest {
+    /// Splits `data` into content-defined chunks.
+    pub fn new(mut data: &[u8]) -> Self {
+        let mut chunks = vec![];
+        while !data.is_empty() {
+            let (chunk, rest) = data.split_at(cut(data));
+            chunks.push(Chunk {
+                digest: hex::encode(Sha256::digest(chunk)),
+                size: chunk.len() as u64,
+            });
+            data = rest;
+        }
+        Self { chunks }
+    }
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let chunks = text
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                let chunk = line.split_once(' ').and_then(|(digest, size)| {
+                    let valid = digest.len() == 64
+                        && digest
+                            .bytes()
+                            .all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'));
+                    Some(Chunk {
+                        digest: valid.then(|| digest.to_string())?,
+                        size: size.trim().parse().ok()?,
+                    })
+                });
+                chunk.ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("invalid chunk manifest line {:?}", line),
+                    )
+                })
+            })
+            .collect::<io::Result<_>>()?;
+        Ok(Self { chunks })
+    }
+    /// Returns the size of the file.
+    pub fn size(&self) -> u64 {
+        self.chunks.iter().map(|chunk| chunk.size).sum()
+    }
+}
+
+impl fmt::Display for ChunkManifest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for chunk in &self.chunks {
+            writeln!(f, "{} {}", chunk.digest, chunk.size)?;
+        }
+        Ok(())
+    }
+}
+
+/// A transport fetching the files with a chunk manifest chunk by chunk,
+/// through a local chunk store.
+pub struct Chunked<T: Transport> {
+    inner: T,
+    store: PathBuf,
+}
+
+impl<T: Transport> Chunked<T> {
+    /// Wraps `inner`, keeping the chunks in the directory `store`.
+    pub fn new(inner: T, store: impl Into<PathBuf>) -> Self {
+        Self {
+            inner,
+            store: store.into(),
+        }
+    }
+    fn chunk_path(&self, chunk: &Chunk) -> PathBuf {
+        self.store.join(&chunk.digest[..2]).join(&chunk.digest)
+    }
+    async fn manifest(&self, path: &str) -> io::Result<Option<ChunkManifest>> {
+        let manifest = format!("{}{}", path, CHUNK_MANIFEST_SUFFIX);
+        let mut rsp = match self.inner.get(&manifest, None).await {
+            Ok(rsp) => rsp,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let mut text = String::new();
+        rsp.reader.read_to_string(&mut text).await?;
+        ChunkManifest::parse(&text).map(Some)
+    }
+    // Assembles the file from the stored chunks and the missing ones,
+    // fetching each run of consecutive missing chunks as a single range.
+    async fn assemble(&self, path: &str, manifest: &ChunkManifest) -> io::Result<Vec<u8>> {
+        let mut data = Vec::with_capacity(manifest.size() as usize);
+        let mut missing: Vec<&Chunk> = vec![];
+        let mut offset = 0u64;
+        for chunk in &manifest.chunks {
+            match fs::read(self.chunk_path(chunk)).await {
+                Ok(stored) if hex::encode(Sha256::digest(&stored)) == chunk.digest => {
+                    self.fetch(path, offset, &missing, &mut data).await?;
+                    offset += missing.iter().map(|chunk| chunk.size).sum::<u64>();
+                    missing.clear();
+                    data.extend_from_slice(&stored);
+                    offset += chunk.size;
+                }
+                _ => missing.push(chunk),
+            }
+        }
+        self.fetch(path, offset, &missing, &mut data).await?;
+        Ok(data)
+    }
+    async fn fetch(
+        &self,
+        path: &str,
+        offset: u64,
+        chunks: &[&Chunk],
+        data: &mut Vec<u8>,
+    ) -> io::Result<()> {
+        if chunks.is_empty() {
+            return Ok(());
+        }
+        let size: u64 = chunks.iter().map(|chunk| chunk.size).sum();
+        let mut rsp = self.inner.get(path, Some(offset..offset + size)).await?;
+        let mut buf = Vec::with_capacity(size as usize);
+        rsp.reader.read_to_end(&mut buf).await?;
+        let mut rest = &buf[..];
+        for chunk in chunks {
+            if (rest.len() as u64) < chunk.size {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("{} is shorter than its chunk manifest", path),
+                ));
+            }
+            let (bytes, tail) = rest.split_at(chunk.size as usize);
+            if hex::encode(Sha256::digest(bytes)) != chunk.digest {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{} does not match its chunk manifest", path),
+                ));
+            }
+            let dst = self.chunk_path(chunk);
+            fs::create_dir_all(dst.parent().unwrap()).await?;
+            let tmp = dst.with_extension("tmp");
+            fs::write(&tmp, bytes).await?;
+            fs::rename(&tmp, &dst).await?;
+            data.extend_from_slice(bytes);
+            rest = tail;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<T: Transport> Transport for Chunked<T> {
+    async fn get(&self, path: &str, range: Option<Range<u64>>) -> io::Result<TransportResponse> {
+        let Some(manifest) = self.manifest(path).await? else {
+            return self.inner.get(path, range).await;
+        };
+        let mut data = self.assemble(path, &manifest).await?;
+        if let Some(range) = range {
+            let end = (range.end as usize).min(data.len());
+            let start = (range.start as usize).min(end);
+            data.truncate(end);
+            data.drain(..start);
+        }
+        let length = data.len() as u64;
+        Ok(TransportResponse::new(
+            Box::pin(Cursor::new(data)),
+            Some(length),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::fsrepo::FSDebRepo,
+        std::sync::{Arc, Mutex},
+    };
+
+    // Records the ranges fetched from the repository.
+    struct Recording {
+        inner: FSDebRepo,
+        ranges: Arc<Mutex<Vec<Range<u64>>>>,
+    }
+
+    #[async_trait]
+    impl Transport for Recording {
+        async fn get(
+            &self,
+            path: &str,
+            range: Option<Range<u64>>,
+        ) -> io::Result<TransportResponse> {
+            if let Some(range) = &range {
+                self.ranges.lock().unwrap().push(range.clone());
+            }
+            self.inner.get(path, range).await
+        }
+    }
+
+    fn random(len: usize, mut seed: u64) -> Vec<u8> {
+        (0..len)
+            .map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                seed as u8
+            })
+            .collect()
+    }
+
+    #[async_std::test]
+    async fn test_chunked() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        std::fs::create_dir(&repo).unwrap();
+        let publish = |data: &[u8]| {
+            let manifest = ChunkManifest::new(data);
+            std::fs::write(repo.join("Packages"), data).unwrap();
+            std::fs::write(repo.join("Packages.chunks"), manifest.to_string()).unwrap();
+            manifest
+        };
+        let mut data = random(1 << 20, 1);
+        let manifest = publish(&data);
+        assert!(manifest.chunks.len() > 4);
+        assert_eq!(manifest.size(), data.len() as u64);
+        assert_eq!(
+            ChunkManifest::parse(&manifest.to_string()).unwrap(),
+            manifest
+        );
+        assert!(ChunkManifest::parse("abc 12\n").is_err());
+
+        let ranges = Arc::new(Mutex::new(vec![]));
+        let transport = Chunked::new(
+            Recording {
+                inner: FSDebRepo::new(&repo).await.unwrap(),
+                ranges: Arc::clone(&ranges),
+            },
+            dir.path().join("store"),
+        );
+        let fetch = |range| {
+            let transport = &transport;
+            async move {
+                let mut buf = vec![];
+                let mut rsp = transport.get("Packages", range).await.unwrap();
+                rsp.reader.read_to_end(&mut buf).await.unwrap();
+                buf
+            }
+        };
+        assert_eq!(fetch(None).await, data);
+        let whole = 0..data.len() as u64;
+        assert_eq!(*ranges.lock().unwrap(), vec![whole]);
+
+        // an edit in the middle changes the chunks around it only
+        ranges.lock().unwrap().clear();
+        data.splice(500_000..500_010, *b"0123456789ab");
+        publish(&data);
+        assert_eq!(fetch(Some(10..20)).await, &data[10..20]);
+        let fetched: u64 = ranges.lock().unwrap().iter().map(|r| r.end - r.start).sum();
+        assert!(
+            fetched > 0 && fetched <= 2 * MAX_CHUNK as u64,
+            "{}",
+            fetched
+        );
+        ranges.lock().unwrap().clear();
+        assert_eq!(fetch(None).await, data);
+        assert!(ranges.lock().unwrap().is_empty());
+    }
+}
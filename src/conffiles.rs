@@ -0,0 +1,176 @@
+//! Configuration files
+//!
+//! dpkg records the md5 of every conffile as shipped in the `Conffiles`
+//! field of the status file. On upgrade, the hash of the file on disk tells
+//! whether the administrator modified it and the hash of the new version
+//! whether the package did: [`ConffileAction::decide`] makes dpkg's keep or
+//! replace decision from the three.
+
+/// A conffile of a package, with the md5 of the version shipped.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Conffile {
+    pub path: String,
+    /// Not known yet for the `conffiles` member of control.tar
+    pub hash: Option<String>,
+    /// No longer shipped by the package
+    pub obsolete: bool,
+}
+
+impl Conffile {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            hash: None,
+            obsolete: false,
+        }
+    }
+    /// Parses the `conffiles` member of control.tar. The files flagged
+    /// `remove-on-upgrade` are not shipped and are skipped.
+    pub fn parse_list(src: &str) -> Vec<Conffile> {
+        src.lines()
+            .map(str::trim)
+            .filter(|line| line.starts_with('/'))
+            .map(Conffile::new)
+            .collect()
+    }
+    /// Parses the `Conffiles` field of the dpkg status file.
+    pub fn parse_field(src: &str) -> Vec<Conffile> {
+        src.lines()
+            .filter_map(|line| {
+                let mut words = line.split_whitespace();
+                let path = words.next()?;
+                let hash = words.next().filter(|hash| *hash != "newconffile");
+                let obsolete = words.any(|flag| flag == "obsolete");
+                Some(Conffile {
+                    path: path.to_string(),
+                    hash: hash.map(str::to_string),
+                    obsolete,
+                })
+            })
+            .collect()
+    }
+    /// Formats the `Conffiles` field of the dpkg status file, leaving out
+    /// the conffiles without a hash.
+    pub fn format_field(conffiles: &[Conffile]) -> String {
+        let mut field = String::new();
+        for conffile in conffiles {
+            if let Some(hash) = &conffile.hash {
+                field.push_str("\n ");
+                field.push_str(&conffile.path);
+                field.push(' ');
+                field.push_str(hash);
+                if conffile.obsolete {
+                    field.push_str(" obsolete");
+                }
+            }
+        }
+        field
+    }
+}
+
+/// What to do with a conffile modified both locally and by the package,
+/// like dpkg's `--force-confold` and `--force-confnew`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ConffilePolicy {
+    /// Keep the local version, installing the new one as `.dpkg-dist`
+    #[default]
+    KeepLocal,
+    /// Install the new version, saving the local one as `.dpkg-old`
+    Replace,
+}
+
+/// The fate of a conffile on installation or upgrade.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConffileAction {
+    /// Install the new version: the file is new or was not modified locally
+    Install,
+    /// The file on disk already is the new version
+    Unchanged,
+    /// Keep the locally modified file, which the package did not change
+    Keep,
+    /// Keep the locally modified file and install the new version next to
+    /// it as `.dpkg-dist`
+    KeepDist,
+    /// Install the new version, saving the locally modified file as
+    /// `.dpkg-old`
+    ReplaceOld,
+    /// The file was deleted locally and is not reinstalled
+    Skip,
+}
+
+impl ConffileAction {
+    /// Decides the fate of a conffile from the md5 of the version
+    /// `shipped` with the installed package, if any, of the `current` file
+    /// on disk, if any, and of the `new` version.
+    pub fn decide(
+        shipped: Option<&str>,
+        current: Option<&str>,
+        new: &str,
+        policy: ConffilePolicy,
+    ) -> Self {
+        let same = |a: &str, b: &str| a.eq_ignore_ascii_case(b);
+        let conflict = match policy {
+            ConffilePolicy::KeepLocal => ConffileAction::KeepDist,
+            ConffilePolicy::Replace => ConffileAction::ReplaceOld,
+        };
+        match (shipped, current) {
+            (_, Some(current)) if same(current, new) => ConffileAction::Unchanged,
+            (None, None) => ConffileAction::Install,
+            (Some(_), None) => ConffileAction::Skip,
+            (Some(shipped), Some(current)) if same(shipped, current) => ConffileAction::Install,
+            (Some(shipped), Some(_)) if same(shipped, new) => ConffileAction::Keep,
+            (_, Some(_)) => conflict,
+        }
+    }
+    /// Returns the suffix of the path the new version is written to, if it
+    /// is written at all.
+    pub fn new_suffix(&self) -> Option<&'static str> {
+        match self {
+            ConffileAction::Install | ConffileAction::ReplaceOld => Some(""),
+            ConffileAction::KeepDist => Some(".dpkg-dist"),
+            ConffileAction::Unchanged | ConffileAction::Keep | ConffileAction::Skip => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conffiles() {
+        let list = Conffile::parse_list("/etc/hello.conf\nremove-on-upgrade /etc/old.conf\n");
+        assert_eq!(list, [Conffile::new("/etc/hello.conf")]);
+        let field = "\n /etc/hello.conf 0cc175b9c0f1b6a831c399e269772661\n \
+                     /etc/gone.conf 92eb5ffee6ae2fec3ad71c777531578f obsolete\n \
+                     /etc/fresh.conf newconffile";
+        let conffiles = Conffile::parse_field(field);
+        assert_eq!(conffiles.len(), 3);
+        assert!(conffiles[1].obsolete);
+        assert_eq!(conffiles[2].hash, None);
+        assert_eq!(
+            Conffile::format_field(&conffiles),
+            "\n /etc/hello.conf 0cc175b9c0f1b6a831c399e269772661\n \
+             /etc/gone.conf 92eb5ffee6ae2fec3ad71c777531578f obsolete"
+        );
+    }
+
+    #[test]
+    fn test_decide() {
+        use ConffileAction::*;
+        let decide =
+            |shipped, current, policy| ConffileAction::decide(shipped, current, "new", policy);
+        let keep = ConffilePolicy::KeepLocal;
+        assert_eq!(decide(None, None, keep), Install);
+        assert_eq!(decide(None, Some("new"), keep), Unchanged);
+        assert_eq!(decide(None, Some("local"), keep), KeepDist);
+        assert_eq!(decide(Some("old"), None, keep), Skip);
+        assert_eq!(decide(Some("old"), Some("old"), keep), Install);
+        assert_eq!(decide(Some("new"), Some("local"), keep), Keep);
+        assert_eq!(decide(Some("old"), Some("local"), keep), KeepDist);
+        assert_eq!(
+            decide(Some("old"), Some("local"), ConffilePolicy::Replace),
+            ReplaceOld
+        );
+    }
+}
@@ -36,7 +36,7 @@ impl std::fmt::Display for ParseError {
 
 impl From<ParseError> for std::io::Error {
     fn from(err: ParseError) -> Self {
-        std::io::Error::new(std::io::ErrorKind::InvalidData, err.msg.into_owned())
+        std::io::Error::new(std::io::ErrorKind::InvalidData, err)
     }
 }
 
@@ -51,12 +51,34 @@ pub struct ControlField<'a> {
 
 impl<'a> std::fmt::Display for ControlField<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if self.value.as_bytes().iter().next() == Some(&b'\n') {
-            writeln!(f, "{}:{}", self.name, self.value)
+        write_field(f, self.name, self.value)
+    }
+}
+
+// Writes a field in the canonical form: `Name: value` for single line
+// values, with the continuation lines indented by a space unless already
+// indented and the empty ones written as ` .`, and `Name:` alone on the
+// first line of the values starting with a line break, as multiline
+// descriptions do. The values keep the continuation lines as written, so
+// only those already in this form, as the parsed ones are, parse back
+// unchanged. The others parse back to their canonical form, which is
+// written as is: writing is idempotent after one normalization.
+fn write_field(f: &mut std::fmt::Formatter<'_>, name: &str, value: &str) -> std::fmt::Result {
+    let mut lines = value.split('\n');
+    match lines.next().unwrap_or_default() {
+        "" => write!(f, "{}:", name)?,
+        first => write!(f, "{}: {}", name, first)?,
+    }
+    for line in lines {
+        if line.bytes().all(|b| is_ws(&b)) {
+            f.write_str("\n .")?;
+        } else if line.starts_with([' ', '\t']) {
+            write!(f, "\n{}", line)?;
         } else {
-            writeln!(f, "{}: {}", &self.name, &self.value)
+            write!(f, "\n {}", line)?;
         }
     }
+    f.write_str("\n")
 }
 
 impl<'a> ControlField<'a> {
@@ -83,11 +105,7 @@ pub struct MutableControlField<'a> {
 
 impl<'a> std::fmt::Display for MutableControlField<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if self.value.as_bytes().iter().next() == Some(&b'\n') {
-            writeln!(f, "{}:{}", self.name, self.value)
-        } else {
-            writeln!(f, "{}: {}", &self.name, &self.value)
-        }
+        write_field(f, &self.name, &self.value)
     }
 }
 
@@ -365,8 +383,9 @@ impl std::fmt::Display for MutableControlFile {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
         for stanza in &self.stanzas {
             write!(f, "{}", stanza)?;
+            f.write_str("\n")?;
         }
-        write!(f, "\n")
+        Ok(())
     }
 }
 
@@ -400,13 +419,14 @@ impl<'a> std::fmt::Display for ControlFile<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
         for stanza in &self.stanzas {
             write!(f, "{}", stanza)?;
+            f.write_str("\n")?;
         }
-        write!(f, "\n")
+        Ok(())
     }
 }
 
 impl<'a> ControlFile<'a> {
-    /// Parses a string into ControlFile 
+    /// Parses a string into ControlFile
     pub fn parse<S: ?Sized + AsRef<str>>(src: &'a S) -> Result<Self, ParseError> {
         let mut parser = ControlParser::new(src.as_ref());
         let mut stanzas: Vec<ControlStanza<'a>> = vec![];
@@ -584,6 +604,7 @@ impl<'a> Iterator for ControlParser<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testutil::XorShift;
 
     #[test]
     fn test_valid_field_name() {
@@ -657,6 +678,60 @@ Multi-Line:
             .is_a("a"))
     }
 
+    #[test]
+    fn test_round_trip() {
+        let mut rng = XorShift::new(0x9e3779b97f4a7c15);
+        let mut next = move |n: usize| rng.below(n);
+        const NAME: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-";
+        const TEXT: &[u8] = b"abc xyz.,:;-+~()<>|0123456789";
+        let word = |next: &mut dyn FnMut(usize) -> usize, chars: &[u8]| {
+            let mut word = String::from("x");
+            for _ in 0..next(12) {
+                word.push(chars[next(chars.len())] as char);
+            }
+            word.trim_end().to_string()
+        };
+        for _ in 0..200 {
+            let mut text = String::new();
+            for _ in 0..1 + next(3) {
+                for i in 0..1 + next(6) {
+                    text += &format!("{}{}:", word(&mut next, NAME), i);
+                    let (a, b) = (word(&mut next, TEXT), word(&mut next, TEXT));
+                    match next(3) {
+                        0 => text += &format!(" {}\n", a),
+                        1 => text += &format!(" {}\n {}\n", a, b),
+                        _ => text += &format!("\n {}\n .\n  {}\n", a, b),
+                    }
+                }
+                text.push('\n');
+            }
+            let file = ControlFile::parse(&text).unwrap();
+            assert_eq!(file.to_string(), text);
+            let mut mutable = MutableControlFile::new();
+            for stanza in file.stanzas() {
+                mutable.add(MutableControlStanza::from(stanza));
+            }
+            assert_eq!(mutable.to_string(), text);
+        }
+    }
+
+    #[test]
+    fn test_write_canonical() {
+        let mut stanza = MutableControlStanza::new();
+        stanza
+            .set("Empty", "")
+            .set("Folded", "one\ntwo\n\n three")
+            .set("Multi", "\nfirst\n  second");
+        let text = stanza.to_string();
+        assert_eq!(
+            text,
+            "Empty:\nFolded: one\n two\n .\n three\nMulti:\n first\n  second\n"
+        );
+        let parsed = MutableControlStanza::parse(text.clone()).unwrap();
+        assert_eq!(parsed.field("Folded"), Some("one\n two\n .\n three"));
+        assert_eq!(parsed.to_string(), text);
+    }
+
     #[test]
     fn test_add_stanza() {
         let mut cf = MutableControlFile::new();
@@ -0,0 +1,238 @@
+//! Root file system customization
+//!
+//! A [`Customization`] trims a root file system while it is extracted
+//! instead of after: it drops packages from the solution, skips paths
+//! such as the documentation and the locales, and calls [`UnpackHook`]s
+//! before and after each package is extracted:
+//!
+//! ```ignore
+//! let custom = Customization::new()
+//!     .exclude_packages(Priority::Optional)
+//!     .include_packages(PackageFilter::name("ca-certificates"))
+//!     .exclude_packages(PackageFilter::name("*-doc"))
+//!     .exclude_path("/usr/share/doc/*")
+//!     .include_path("/usr/share/doc/*/copyright")
+//!     .exclude_path("/usr/share/locale/*");
+//! for id in custom.packages(&universe, &solution) {
+//!     let name = universe.package(id).name();
+//!     custom.extract(name, universe.deb_reader(id).await?, &fs).await?;
+//! }
+//! ```
+
+use {
+    crate::{
+        control::MutableControlStanza, deb::DebReader, filter::PackageFilter, policy::glob,
+        universe::Universe, DeploymentFileSystem,
+    },
+    async_std::io::{self, Read},
+    async_trait::async_trait,
+    resolvo::SolvableId,
+    std::sync::Arc,
+};
+
+/// The paths extracted from the packages, as dpkg's `--path-exclude` and
+/// `--path-include` select them: the last glob matching a path decides,
+/// and paths matching none are extracted. The globs match the absolute
+/// path, `*` matching `/` too.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PathFilter {
+    // (glob, included)
+    rules: Vec<(String, bool)>,
+}
+
+impl PathFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Excludes the paths matching `glob`, e.g. `/usr/share/doc/*`.
+    pub fn exclude(mut self, glob: impl Into<String>) -> Self {
+        self.rules.push((glob.into(), false));
+        self
+    }
+    /// Includes the paths matching `glob` again, e.g.
+    /// `/usr/share/doc/*/copyright`.
+    pub fn include(mut self, glob: impl Into<String>) -> Self {
+        self.rules.push((glob.into(), true));
+        self
+    }
+    /// Returns true if the path is not extracted. Directories may end
+    /// with `/`.
+    pub fn is_excluded(&self, path: &str) -> bool {
+        let path = match path.trim_end_matches('/') {
+            "" => "/",
+            path => path,
+        };
+        self.rules
+            .iter()
+            .rev()
+            .find(|(pattern, _)| glob(pattern, path))
+            .is_some_and(|(_, included)| !included)
+    }
+}
+
+/// Called around the extraction of each package by
+/// [`Customization::extract`].
+#[async_trait]
+pub trait UnpackHook: Send + Sync {
+    /// Called before `package` is extracted.
+    async fn pre_unpack(&self, package: &str) -> io::Result<()> {
+        let _ = package;
+        Ok(())
+    }
+    /// Called once `package` is extracted, with its control stanza.
+    async fn post_unpack(&self, package: &str, control: &MutableControlStanza) -> io::Result<()> {
+        let _ = (package, control);
+        Ok(())
+    }
+}
+
+/// The packages, paths and hooks of a customized root file system.
+#[derive(Clone, Default)]
+pub struct Customization {
+    // (filter, included)
+    packages: Vec<(PackageFilter, bool)>,
+    paths: PathFilter,
+    hooks: Vec<Arc<dyn UnpackHook>>,
+}
+
+impl Customization {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Drops the packages matching `filter` from the solution.
+    pub fn exclude_packages(mut self, filter: impl Into<PackageFilter>) -> Self {
+        self.packages.push((filter.into(), false));
+        self
+    }
+    /// Keeps the packages matching `filter` again, e.g. one package of a
+    /// priority excluded before. Like with paths, the last filter matching
+    /// a package decides.
+    pub fn include_packages(mut self, filter: impl Into<PackageFilter>) -> Self {
+        self.packages.push((filter.into(), true));
+        self
+    }
+    /// See [`PathFilter::exclude`].
+    pub fn exclude_path(mut self, glob: impl Into<String>) -> Self {
+        self.paths = self.paths.exclude(glob);
+        self
+    }
+    /// See [`PathFilter::include`].
+    pub fn include_path(mut self, glob: impl Into<String>) -> Self {
+        self.paths = self.paths.include(glob);
+        self
+    }
+    /// Adds a hook, called after the ones added before.
+    pub fn hook(mut self, hook: impl UnpackHook + 'static) -> Self {
+        self.hooks.push(Arc::new(hook));
+        self
+    }
+    pub fn paths(&self) -> &PathFilter {
+        &self.paths
+    }
+    /// Returns the packages of `solution` not excluded. The packages
+    /// depending on an excluded one are kept, as debootstrap's `--exclude`
+    /// does, so the root may be inconsistent.
+    pub fn packages<S: AsRef<str> + 'static>(
+        &self,
+        universe: &Universe<S>,
+        solution: &[SolvableId],
+    ) -> Vec<SolvableId> {
+        solution
+            .iter()
+            .copied()
+            .filter(|&id| {
+                let package = universe.package(id);
+                self.packages
+                    .iter()
+                    .rev()
+                    .find(|(filter, _)| filter.matches(package))
+                    .is_none_or(|(_, included)| *included)
+            })
+            .collect()
+    }
+    /// Extracts `package` from `reader` into `fs`, skipping the excluded
+    /// paths, between the calls of the hooks.
+    pub async fn extract<'a, R, FS>(
+        &self,
+        package: &str,
+        reader: DebReader<'a, R>,
+        fs: FS,
+    ) -> io::Result<MutableControlStanza>
+    where
+        R: Read + Unpin + Send + 'a,
+        FS: DeploymentFileSystem,
+    {
+        for hook in &self.hooks {
+            hook.pre_unpack(package).await?;
+        }
+        let control = reader.extract_filtered_to(fs, &self.paths).await?;
+        for hook in &self.hooks {
+            hook.post_unpack(package, &control).await?;
+        }
+        Ok(control)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::packages::{Packages, Priority},
+    };
+
+    #[test]
+    fn test_path_filter() {
+        let paths = PathFilter::new()
+            .exclude("/usr/share/doc/*")
+            .include("/usr/share/doc/*/copyright")
+            .exclude("/usr/share/locale/*");
+        assert!(paths.is_excluded("/usr/share/doc/hello/"));
+        assert!(paths.is_excluded("/usr/share/doc/hello/README.gz"));
+        assert!(!paths.is_excluded("/usr/share/doc/hello/copyright"));
+        assert!(!paths.is_excluded("/usr/share/doc/"));
+        assert!(paths.is_excluded("/usr/share/locale/de/LC_MESSAGES/hello.mo"));
+        assert!(!paths.is_excluded("/usr/bin/hello"));
+        assert!(!paths.is_excluded("/"));
+    }
+
+    #[test]
+    fn test_customization() {
+        let src = "\
+Package: hello
+Version: 2.10
+Architecture: amd64
+Priority: optional
+
+Package: hello-doc
+Version: 2.10
+Architecture: all
+Priority: optional
+
+Package: locales
+Version: 2.36
+Architecture: all
+Priority: standard
+";
+        let uni = Universe::new("amd64", [Packages::new_test(src).unwrap()]).unwrap();
+        let all: Vec<SolvableId> = uni
+            .filter(Priority::Optional | Priority::Standard)
+            .collect();
+        let custom = Customization::new()
+            .exclude_packages(PackageFilter::name("*-doc"))
+            .exclude_packages(Priority::Standard)
+            .exclude_path("/usr/share/doc/*");
+        let kept = |custom: &Customization| -> Vec<_> {
+            custom
+                .packages(&uni, &all)
+                .into_iter()
+                .map(|id| uni.package(id).name())
+                .collect()
+        };
+        assert_eq!(kept(&custom), ["hello"]);
+        let custom = custom.include_packages(PackageFilter::name("locales"));
+        assert_eq!(kept(&custom), ["hello", "locales"]);
+        assert!(custom
+            .paths()
+            .is_excluded("/usr/share/doc/hello/changelog.gz"));
+    }
+}
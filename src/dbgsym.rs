@@ -0,0 +1,119 @@
+//! Debug symbol packages
+//!
+//! The `-dbgsym` packages holding the detached debug symbols of the Debian
+//! packages are published in a separate archive, e.g. `bookworm-debug` of
+//! `https://deb.debian.org/debian-debug/`. [`Universe::debug_symbols`]
+//! finds the ones of a solution in a universe of that archive, so that
+//! they can be downloaded along with the packages:
+//!
+//! ```ignore
+//! let debug = Universe::new("amd64", debug_packages)?;
+//! let symbols = universe.debug_symbols(&solution, &debug);
+//! cas::export(&cas, &debug, &symbols.packages, "debs", ExportLayout::Flat).await?;
+//! ```
+
+use {
+    crate::{idmap::IntoId, universe::Universe},
+    resolvo::SolvableId,
+    std::collections::HashMap,
+};
+
+/// The debug symbol packages of a solution.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DebugSymbols {
+    /// The `-dbgsym` packages, in the debug universe
+    pub packages: Vec<SolvableId>,
+    /// The architecture-specific packages of the solution without one
+    pub missing: Vec<SolvableId>,
+}
+
+impl<S: AsRef<str> + 'static> Universe<S> {
+    /// Finds the `-dbgsym` packages of `solution` in `debug`: those named
+    /// after a package of the solution, for its architecture and of its
+    /// exact version. `debug` is usually the universe of the debug archive,
+    /// or the universe itself when it includes the debug indexes.
+    /// Architecture-independent packages have no debug symbols.
+    pub fn debug_symbols<T: AsRef<str> + 'static>(
+        &self,
+        solution: &[SolvableId],
+        debug: &Universe<T>,
+    ) -> DebugSymbols {
+        let dbgsym: HashMap<(&str, &str, String), SolvableId> = debug
+            .packages()
+            .enumerate()
+            .filter_map(|(id, p)| {
+                let name = p.name().strip_suffix("-dbgsym")?;
+                let key = (name, p.architecture(), p.version().to_string());
+                Some((key, id.into_id()))
+            })
+            .collect();
+        let mut symbols = DebugSymbols::default();
+        for &id in solution {
+            let package = self.package(id);
+            if package.architecture() == "all" {
+                continue;
+            }
+            let key = (
+                package.name(),
+                package.architecture(),
+                package.version().to_string(),
+            );
+            match dbgsym.get(&key) {
+                Some(&found) => symbols.packages.push(found),
+                None => symbols.missing.push(id),
+            }
+        }
+        symbols
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{packages::Packages, version::Dependency},
+    };
+
+    #[test]
+    fn test_debug_symbols() {
+        let src = "\
+Package: hello
+Version: 2.10-3
+Architecture: amd64
+Depends: libc6
+
+Package: libc6
+Version: 2.36-9
+Architecture: amd64
+Depends: tzdata
+
+Package: tzdata
+Version: 2024a-0
+Architecture: all
+";
+        let debug = "\
+Package: hello-dbgsym
+Version: 2.10-3
+Architecture: amd64
+Auto-Built-Package: debug-symbols
+
+Package: libc6-dbgsym
+Version: 2.36-8
+Architecture: amd64
+Auto-Built-Package: debug-symbols
+";
+        let mut uni = Universe::new("amd64", [Packages::new_test(src).unwrap()]).unwrap();
+        let problem = uni.problem(vec![Dependency::try_from("hello").unwrap()], vec![], vec![]);
+        let solution = uni.solve(problem).unwrap();
+        let debug = Universe::new("amd64", [Packages::new_test(debug).unwrap()]).unwrap();
+        let symbols = uni.debug_symbols(&solution, &debug);
+        let names = |uni: &Universe<_>, ids: &[SolvableId]| -> Vec<String> {
+            ids.iter().map(|&id| uni.package(id).to_string()).collect()
+        };
+        assert_eq!(
+            names(&debug, &symbols.packages),
+            ["hello-dbgsym:amd64=2.10-3"]
+        );
+        assert_eq!(names(&uni, &symbols.missing), ["libc6:amd64=2.36-9"]);
+    }
+}
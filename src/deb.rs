@@ -3,18 +3,28 @@ pub use async_tar::{
     EntryType as TarballEntryType,
 };
 use {
-    crate::{control::MutableControlStanza, parse_size},
+    crate::{
+        conffiles::{Conffile, ConffileAction, ConffilePolicy},
+        control::{ControlFile, MutableControlStanza},
+        customize::PathFilter,
+        diagnostic::Diagnostic,
+        digest::DigestingReader,
+        packages::PackageKind,
+        parse_size, pool,
+        version::{ParsedConstraintIterator, Version, UNRESTRICTED},
+    },
     async_compression::futures::bufread::{
         BzDecoder, GzipDecoder, LzmaDecoder, XzDecoder, ZstdDecoder,
     },
     async_std::{
         io::{self, prelude::*, BufReader, Result},
-        path::PathBuf,
+        path::{Path, PathBuf},
         stream::{Stream, StreamExt},
         task::{self, Context, Poll},
     },
     pin_project::pin_project,
     std::{
+        collections::{HashMap, HashSet},
         ops::Range,
         pin::{pin, Pin},
         sync::{Arc, Mutex},
@@ -52,6 +62,73 @@ enum State {
     Content,
 }
 
+/// Verification of the files extracted by [`DebReader::extract_to_with_mode`]
+/// against the `md5sums` member of control.tar.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VerifyExtract {
+    /// Do not verify the files
+    #[default]
+    Off,
+    /// Report mismatching, missing and unlisted files
+    Report,
+    /// Fail on mismatching, missing or unlisted files, or a missing md5sums
+    Strict,
+}
+
+// Checks the md5 `digests` of the regular files of data.tar, by path
+// relative to the root, against the `md5sums` member. Conffiles are not
+// listed in md5sums.
+fn verify_md5sums(
+    md5sums: Option<&str>,
+    digests: &HashMap<String, String>,
+    conf_files: &[Conffile],
+) -> Vec<Diagnostic> {
+    let Some(md5sums) = md5sums else {
+        return vec![Diagnostic::new("no md5sums in control.tar")];
+    };
+    let mut diagnostics = vec![];
+    let mut listed = HashMap::new();
+    for line in md5sums.lines().filter(|line| !line.trim().is_empty()) {
+        match line.split_once(char::is_whitespace) {
+            Some((hash, path)) => {
+                let path = path
+                    .trim_start()
+                    .trim_start_matches("./")
+                    .trim_start_matches('/');
+                listed.insert(path, hash);
+            }
+            None => diagnostics.push(Diagnostic::new(format!("malformed md5sums line {}", line))),
+        }
+    }
+    let mut paths: Vec<_> = digests.keys().collect();
+    paths.sort();
+    for path in paths {
+        match listed.remove(path.as_str()) {
+            Some(hash) if !hash.eq_ignore_ascii_case(&digests[path]) => {
+                diagnostics.push(Diagnostic::new(format!("/{}: md5sum mismatch", path)))
+            }
+            Some(_) => {}
+            None if conf_files
+                .iter()
+                .any(|c| c.path.trim_start_matches('/') == path) => {}
+            None => diagnostics.push(Diagnostic::new(format!("/{}: not in md5sums", path))),
+        }
+    }
+    let mut missing: Vec<_> = listed.into_keys().collect();
+    missing.sort();
+    for path in missing {
+        diagnostics.push(Diagnostic::new(format!("/{}: missing from data.tar", path)));
+    }
+    diagnostics
+}
+
+// The path with `suffix` appended to the file name, e.g. `.dpkg-new`.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    path.into()
+}
+
 #[derive(PartialEq)]
 enum EntryKind {
     Control,
@@ -344,14 +421,172 @@ impl<'a, R: Read + Unpin + Send + 'a> DebReader<'a, R> {
             _ => Box::pin(r),
         })
     }
+    /// Reads the package control file from the control.tar member, skipping the rest.
+    pub async fn control(mut self) -> Result<MutableControlStanza> {
+        let mut control_entries = self
+            .next()
+            .await
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "no control.tar entry".to_owned(),
+                )
+            })?
+            .and_then(|f| match f {
+                DebEntry::Control(f) => Ok(f),
+                _ => Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "unexpected entry",
+                )),
+            })?
+            .entries()?;
+        while let Some(entry) = control_entries.next().await {
+            let mut entry = entry?;
+            if entry.header().entry_type() == TarballEntryType::Regular
+                && entry
+                    .header()
+                    .path()?
+                    .file_name()
+                    .is_some_and(|f| f == "control")
+            {
+                let mut buf = String::new();
+                entry.read_to_string(&mut buf).await?;
+                return MutableControlStanza::parse(buf).map_err(|err| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("error parsing control file: {}", err),
+                    )
+                });
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "no control file",
+        ))
+    }
+    /// Reads the first of `paths` found in the data.tar member, e.g.
+    /// `usr/share/doc/hello/changelog.Debian.gz` before
+    /// `usr/share/doc/hello/changelog.gz`, scanning the member once.
+    /// Returns the position of the file read in `paths` with its contents,
+    /// or None if there is no such regular file.
+    pub async fn data_file(mut self, paths: &[&str]) -> Result<Option<(usize, Vec<u8>)>> {
+        let paths: Vec<&str> = paths
+            .iter()
+            .map(|path| path.trim_start_matches("./").trim_start_matches('/'))
+            .collect();
+        while let Some(entry) = self.next().await {
+            let DebEntry::Data(tarball) = entry? else {
+                continue;
+            };
+            let mut found = None;
+            let mut entries = tarball.entries()?;
+            while let Some(entry) = entries.next().await {
+                let mut entry = entry?;
+                if entry.header().entry_type() != TarballEntryType::Regular {
+                    continue;
+                }
+                let path = entry.header().path()?;
+                let Some(path) = path.to_str().map(|p| p.trim_start_matches("./")) else {
+                    continue;
+                };
+                // only the files preferred to the one found so far are read
+                let preferred = found.as_ref().map_or(paths.len(), |(pos, _)| *pos);
+                let Some(pos) = paths[..preferred].iter().position(|p| *p == path) else {
+                    continue;
+                };
+                let mut buf = vec![];
+                entry.read_to_end(&mut buf).await?;
+                if pos == 0 {
+                    return Ok(Some((pos, buf)));
+                }
+                found = Some((pos, buf));
+            }
+            return Ok(found);
+        }
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "no data.tar entry",
+        ))
+    }
+    /// Extracts the package into `fs`, installing its control files to
+    /// `/var/lib/dpkg/info`. The files of the installed packages it
+    /// `Replaces` are overwritten and dropped from their `.list` files, if
+    /// the version of the package in `/var/lib/dpkg/status` is in the
+    /// range replaced. The packages missing from the status, e.g. unpacked
+    /// before it is written, are replaced whatever their version.
     pub async fn extract_to<FS: crate::DeploymentFileSystem>(
-        mut self,
+        self,
+        fs: FS,
+    ) -> Result<MutableControlStanza> {
+        self.extract_to_with_mode(fs, VerifyExtract::Off)
+            .await
+            .map(|(ctrl, _)| ctrl)
+    }
+    /// Extracts the package like [`DebReader::extract_to`], verifying the
+    /// extracted files in the given mode. Returns the control stanza along
+    /// with the mismatching, missing and unlisted files. In strict mode these
+    /// are an error instead, returned once the files are extracted.
+    pub async fn extract_to_with_mode<FS: crate::DeploymentFileSystem>(
+        self,
+        fs: FS,
+        mode: VerifyExtract,
+    ) -> Result<(MutableControlStanza, Vec<Diagnostic>)> {
+        self.extract(fs, mode, None, None)
+            .await
+            .map(|(ctrl, diagnostics, _)| (ctrl, diagnostics))
+    }
+    /// Extracts the package like [`DebReader::extract_to`], skipping the
+    /// files excluded by `paths`, as dpkg's `--path-exclude` does. The
+    /// skipped files are not listed in the `.list` file.
+    pub async fn extract_filtered_to<FS: crate::DeploymentFileSystem>(
+        self,
         fs: FS,
+        paths: &PathFilter,
     ) -> Result<MutableControlStanza> {
+        self.extract(fs, VerifyExtract::Off, None, Some(paths))
+            .await
+            .map(|(ctrl, _, _)| ctrl)
+    }
+    /// Extracts the package over an installed version of it, whose
+    /// conffiles are `installed`, e.g. parsed from the `Conffiles` field of
+    /// its status with [`Conffile::parse_field`]. Files are replaced through
+    /// a `.dpkg-new` file, and each conffile is installed, kept or set aside
+    /// as decided by [`ConffileAction::decide`]. Returns the control stanza
+    /// along with the actions taken on the conffiles.
+    pub async fn upgrade_to<FS: crate::DeploymentFileSystem>(
+        self,
+        fs: FS,
+        installed: &[Conffile],
+        policy: ConffilePolicy,
+    ) -> Result<(MutableControlStanza, Vec<(String, ConffileAction)>)> {
+        self.extract(fs, VerifyExtract::Off, Some((installed, policy)), None)
+            .await
+            .map(|(ctrl, _, actions)| (ctrl, actions))
+    }
+    async fn extract<FS: crate::DeploymentFileSystem>(
+        mut self,
+        fs: FS,
+        mode: VerifyExtract,
+        upgrade: Option<(&[Conffile], ConffilePolicy)>,
+        paths: Option<&PathFilter>,
+    ) -> Result<(
+        MutableControlStanza,
+        Vec<Diagnostic>,
+        Vec<(String, ConffileAction)>,
+    )> {
         let mut installed_files: Vec<String> = vec![];
+        let mut conffile_actions: Vec<(String, ConffileAction)> = vec![];
+        let mut md5sums: Option<String> = None;
+        let mut digests: HashMap<String, String> = HashMap::new();
         let mut ctrl: MutableControlStanza;
         let mut ctrl_files: Vec<(PathBuf, PathBuf)> = vec![];
-        let mut conf_files: Vec<(String, Option<String>)> = vec![];
+        let mut conf_files: Vec<Conffile> = vec![];
+        let mut replaced: HashSet<String> = HashSet::new();
+        let mut replaced_lists: Vec<(PathBuf, Vec<String>)> = vec![];
+        let mut taken_over: HashSet<String> = HashSet::new();
+        // the excluded directories, with their names in the `.list` file,
+        // created and listed if a file in them is included
+        let mut skipped_dirs: HashMap<PathBuf, String> = HashMap::new();
         let multiarch: Option<&str>;
         let pkg: &str;
         let ctrl_base = PathBuf::from("var/lib/dpkg/info");
@@ -405,13 +640,22 @@ impl<'a, R: Read + Unpin + Send + 'a> DebReader<'a, R> {
                         } else if filename.eq("conffiles") {
                             let mut buf = String::with_capacity(entry.header().size()? as usize);
                             entry.read_to_string(&mut buf).await?;
-                            conf_files.extend(buf.lines().map(|l| (l.to_owned(), None)));
+                            conf_files.extend(Conffile::parse_list(&buf));
                             let (tmpname, mut file) = fs
                                 .create_tmp_file("/var/lib/dpkg/info", Some(entry.header().mode()?))
                                 .await?;
                             fs.fallocate(&mut file, entry.header().size()?).await.ok();
                             file.write_all(buf.as_bytes()).await?;
                             ctrl_files.push((filename.into(), tmpname));
+                        } else if filename.eq("md5sums") && mode != VerifyExtract::Off {
+                            let mut buf = String::with_capacity(entry.header().size()? as usize);
+                            entry.read_to_string(&mut buf).await?;
+                            let (tmpname, mut file) = fs
+                                .create_tmp_file("/var/lib/dpkg/info", Some(entry.header().mode()?))
+                                .await?;
+                            file.write_all(buf.as_bytes()).await?;
+                            ctrl_files.push((filename.into(), tmpname));
+                            md5sums = Some(buf);
                         } else {
                             let (tmpname, mut file) = fs
                                 .create_tmp_file("/var/lib/dpkg/info", Some(entry.header().mode()?))
@@ -456,6 +700,59 @@ impl<'a, R: Read + Unpin + Send + 'a> DebReader<'a, R> {
                 target_name.push(name);
                 fs.rename(tmpname, ctrl_base.join(target_name)).await?;
             }
+            // the files of the replaced packages may be overwritten, if the
+            // installed version is in the range replaced
+            let replaces: Vec<_> = ctrl
+                .field("Replaces")
+                .into_iter()
+                .flat_map(|src| ParsedConstraintIterator::new(src, true, &UNRESTRICTED))
+                .filter_map(|r| r.ok())
+                .filter(|r| *r.name() != pkg)
+                .collect();
+            let status = match replaces.is_empty() {
+                true => String::new(),
+                false => match fs.read_file("var/lib/dpkg/status").await {
+                    Ok(data) => String::from_utf8_lossy(&data).into_owned(),
+                    Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+                    Err(err) => return Err(err),
+                },
+            };
+            let status = ControlFile::parse(&status).map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("error parsing /var/lib/dpkg/status: {}", err),
+                )
+            })?;
+            let arch = ctrl.field("Architecture").unwrap_or_default();
+            for replaced_pkg in replaces {
+                let name = *replaced_pkg.name();
+                let installed = status.stanzas().find(|stanza| {
+                    stanza.field("Package") == Some(name)
+                        && stanza
+                            .field("Architecture")
+                            .is_none_or(|a| a == arch || a == "all" || arch == "all")
+                });
+                if let Some(version) = installed.and_then(|stanza| stanza.field("Version")) {
+                    if !replaced_pkg.range().contains(&Version::from(version)) {
+                        continue;
+                    }
+                }
+                for list in [format!("{}.list", name), format!("{}:{}.list", name, arch)] {
+                    let list = ctrl_base.join(list);
+                    match fs.read_file(&list).await {
+                        Ok(data) => {
+                            let files: Vec<String> = String::from_utf8_lossy(&data)
+                                .lines()
+                                .map(String::from)
+                                .collect();
+                            replaced.extend(files.iter().cloned());
+                            replaced_lists.push((list, files));
+                        }
+                        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                        Err(err) => return Err(err),
+                    }
+                }
+            }
         }
         {
             let mut data_entries = self
@@ -483,8 +780,39 @@ impl<'a, R: Read + Unpin + Send + 'a> DebReader<'a, R> {
                             format!("package file name {:?} is not a valid UTF-8", path),
                         )
                     })
-                    .map(|p| if p.starts_with('.') { &p[1..] } else { p })?;
+                    .map(|p| {
+                        let p = p.strip_prefix('.').unwrap_or(p);
+                        format!("/{}", p.trim_start_matches('/'))
+                    })?;
+                let path_str = path_str.as_str();
+                let is_dir = entry.header().entry_type() == TarballEntryType::Directory;
+                if paths.is_some_and(|paths| paths.is_excluded(path_str)) {
+                    if is_dir {
+                        skipped_dirs.insert(path.clone(), path_str.to_owned());
+                    }
+                    continue;
+                }
+                if let Some(parent) = path.parent().filter(|p| skipped_dirs.contains_key(*p)) {
+                    fs.create_dir_all(parent, None).await?;
+                    let mut created: Vec<String> = vec![];
+                    skipped_dirs.retain(|dir, name| {
+                        let keep = !parent.starts_with(dir);
+                        if !keep {
+                            created.push(std::mem::take(name));
+                        }
+                        keep
+                    });
+                    created.sort();
+                    installed_files.extend(created);
+                }
                 installed_files.push(path_str.to_owned());
+                let takeover = entry.header().entry_type() != TarballEntryType::Directory
+                    && replaced.contains(path_str);
+                if takeover {
+                    taken_over.insert(path_str.to_owned());
+                }
+                // on upgrade or takeover, files are replaced through a new one
+                let replace = upgrade.is_some() || takeover;
                 match entry.header().entry_type() {
                     TarballEntryType::Directory => {
                         fs.create_dir_all(&path, Some(entry.header().mode()?))
@@ -497,53 +825,124 @@ impl<'a, R: Read + Unpin + Send + 'a> DebReader<'a, R> {
                     }
                     TarballEntryType::Regular => {
                         let mtime = UNIX_EPOCH + Duration::from_secs(entry.header().mtime()?);
-                        let mut sink = fs.create_file(&path, Some(entry.header().mode()?)).await?;
-                        // ignore fallocate error as the target underlaying filesystem may not
-                        // support it
-                        fs.fallocate(&mut sink, entry.header().size()?).await.ok();
+                        let file_mode = entry.header().mode()?;
+                        let size = entry.header().size()?;
                         let uid = entry.header().uid()?.as_uid();
                         let gid = entry.header().gid()?.as_uid();
-                        if uid.is_some() || gid.is_some() {
-                            fs.fchown(&mut sink, uid, gid).await?;
+                        let conf_file = conf_files.iter_mut().find(|c| c.path == path_str);
+                        let mut target = Some(path.clone());
+                        let mut reader: Pin<Box<dyn Read + Unpin + Send + '_>> = Box::pin(entry);
+                        if let (Some(_), Some((installed, policy))) = (&conf_file, upgrade) {
+                            // the conffile is read whole to decide where it goes
+                            let mut data = Vec::with_capacity(size as usize);
+                            reader.read_to_end(&mut data).await?;
+                            let md5 =
+                                |data: &[u8]| hex::encode(<md5::Md5 as md5::Digest>::digest(data));
+                            let current = match fs.read_file(&path).await {
+                                Ok(current) => Some(md5(&current)),
+                                Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+                                Err(err) => return Err(err),
+                            };
+                            let shipped = installed
+                                .iter()
+                                .find(|c| c.path == path_str)
+                                .and_then(|c| c.hash.as_deref());
+                            let action = ConffileAction::decide(
+                                shipped,
+                                current.as_deref(),
+                                &md5(&data),
+                                policy,
+                            );
+                            if action == ConffileAction::ReplaceOld {
+                                fs.rename(&path, with_suffix(&path, ".dpkg-old")).await?;
+                            }
+                            target = action.new_suffix().map(|suffix| with_suffix(&path, suffix));
+                            conffile_actions.push((path_str.to_owned(), action));
+                            reader = Box::pin(io::Cursor::new(data));
                         }
-                        match conf_files.iter_mut().find(|(name, _)| name == path_str) {
-                            None => {
-                                io::copy(entry, &mut sink).await?;
+                        let staged = target.as_ref().map(|target| match replace {
+                            true => with_suffix(target, ".dpkg-new"),
+                            false => target.clone(),
+                        });
+                        let mut sink = match &staged {
+                            Some(staged) => {
+                                let mut sink = fs.create_file(staged, Some(file_mode)).await?;
+                                // ignore fallocate error as the target underlaying filesystem
+                                // may not support it
+                                fs.fallocate(&mut sink, size).await.ok();
+                                if uid.is_some() || gid.is_some() {
+                                    fs.fchown(&mut sink, uid, gid).await?;
+                                }
+                                Some(sink)
+                            }
+                            None => None,
+                        };
+                        let hash = if conf_file.is_none() && mode == VerifyExtract::Off {
+                            if let Some(sink) = &mut sink {
+                                io::copy(reader, sink).await?;
+                            }
+                            None
+                        } else {
+                            let mut digester =
+                                crate::digest::DigestingReader::<md5::Md5, _>::new(reader);
+                            match &mut sink {
+                                Some(sink) => io::copy(&mut digester, sink).await?,
+                                None => io::copy(&mut digester, &mut io::sink()).await?,
+                            };
+                            Some(String::from(digester.finalize()))
+                        };
+                        if let Some(hash) = hash {
+                            if mode != VerifyExtract::Off {
+                                let path = path_str.trim_start_matches('/');
+                                digests.insert(path.into(), hash.clone());
                             }
-                            Some((_, sum)) => {
-                                let mut digester =
-                                    crate::digest::DigestingReader::<md5::Md5, _>::new(entry);
-                                io::copy(&mut digester, &mut sink).await?;
-                                let hash = digester.finalize();
-                                sum.replace(hash.into());
+                            if let Some(conf_file) = conf_file {
+                                conf_file.hash = Some(hash);
+                            }
+                        }
+                        if let (Some(mut sink), Some(staged)) = (sink, staged) {
+                            sink.flush().await?;
+                            fs.set_mtime(&staged, mtime).await?;
+                            if let Some(target) = target.filter(|target| *target != staged) {
+                                fs.rename(staged, target).await?;
                             }
                         }
-                        sink.flush().await?;
-                        fs.set_mtime(&path, mtime).await?;
                     }
                     TarballEntryType::Link => {
-                        fs.hardlink(
-                            entry.header().link_name()?.ok_or_else(|| {
-                                io::Error::new(
-                                    io::ErrorKind::InvalidData,
-                                    format!("invalid link entry in data.tar: {:?}", &entry),
-                                )
-                            })?,
-                            &path,
-                        )
-                        .await?;
+                        let target = entry.header().link_name()?.ok_or_else(|| {
+                            io::Error::new(
+                                io::ErrorKind::InvalidData,
+                                format!("invalid link entry in data.tar: {:?}", &entry),
+                            )
+                        })?;
+                        // a hard link is a regular file as far as md5sums is concerned
+                        let target_str = target.to_string_lossy();
+                        let target_str = target_str.trim_start_matches('.').trim_start_matches('/');
+                        if let Some(hash) = digests.get(target_str).cloned() {
+                            digests.insert(path_str.trim_start_matches('/').into(), hash);
+                        }
+                        if replace {
+                            let staged = with_suffix(&path, ".dpkg-new");
+                            fs.hardlink(target, &staged).await?;
+                            fs.rename(staged, &path).await?;
+                        } else {
+                            fs.hardlink(target, &path).await?;
+                        }
                     }
                     TarballEntryType::Symlink => {
-                        fs.symlink(
-                            entry.header().link_name()?.ok_or_else(|| {
-                                io::Error::new(
-                                    io::ErrorKind::InvalidData,
-                                    format!("invalid symlink entry in data.tar: {:?}", &entry),
-                                )
-                            })?,
-                            &path,
-                        )
-                        .await?;
+                        let target = entry.header().link_name()?.ok_or_else(|| {
+                            io::Error::new(
+                                io::ErrorKind::InvalidData,
+                                format!("invalid symlink entry in data.tar: {:?}", &entry),
+                            )
+                        })?;
+                        if replace {
+                            let staged = with_suffix(&path, ".dpkg-new");
+                            fs.symlink(target, &staged).await?;
+                            fs.rename(staged, &path).await?;
+                        } else {
+                            fs.symlink(target, &path).await?;
+                        }
                         let uid = entry.header().uid()?.as_uid();
                         let gid = entry.header().gid()?.as_uid();
                         if uid.is_some() || gid.is_some() {
@@ -570,29 +969,50 @@ impl<'a, R: Read + Unpin + Send + 'a> DebReader<'a, R> {
                 target_name.push(arch);
             }
             target_name.push(".list");
-            let mut out = fs
-                .create_file(ctrl_base.join(target_name), Some(0o644u32))
+            let (tmpname, mut out) = fs
+                .create_tmp_file("/var/lib/dpkg/info", Some(0o644u32))
                 .await?;
             for i in installed_files.into_iter() {
                 out.write_all(i.as_bytes()).await?;
                 out.write_all(&[b'\n']).await?;
             }
+            out.flush().await?;
+            fs.rename(tmpname, ctrl_base.join(target_name)).await?;
         }
-        if conf_files.len() > 0 {
-            let mut buf = String::new();
-            for (name, hash) in conf_files.into_iter() {
-                if let Some(hash) = hash {
-                    buf.push_str("\n ");
-                    buf.push_str(&name);
-                    buf.push(' ');
-                    buf.push_str(&hash);
-                }
+        // the files taken over no longer belong to the replaced packages
+        for (list, files) in replaced_lists {
+            if !files.iter().any(|file| taken_over.contains(file)) {
+                continue;
             }
-            if buf.len() > 0 {
-                ctrl.set("Conffiles", buf);
+            let (tmpname, mut out) = fs
+                .create_tmp_file("/var/lib/dpkg/info", Some(0o644u32))
+                .await?;
+            for file in files.iter().filter(|file| !taken_over.contains(*file)) {
+                out.write_all(file.as_bytes()).await?;
+                out.write_all(b"\n").await?;
             }
+            out.flush().await?;
+            fs.rename(tmpname, list).await?;
         }
-        Ok(ctrl)
+        let mut diagnostics = vec![];
+        if mode != VerifyExtract::Off {
+            diagnostics = verify_md5sums(md5sums.as_deref(), &digests, &conf_files);
+            for d in diagnostics.iter_mut() {
+                d.package = Some(pkg.to_string());
+            }
+        }
+        let conffiles = Conffile::format_field(&conf_files);
+        if !conffiles.is_empty() {
+            ctrl.set("Conffiles", conffiles);
+        }
+        if mode == VerifyExtract::Strict && !diagnostics.is_empty() {
+            let messages: Vec<_> = diagnostics.iter().map(|d| d.to_string()).collect();
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                messages.join("; "),
+            ));
+        }
+        Ok((ctrl, diagnostics, conffile_actions))
     }
 }
 
@@ -622,6 +1042,63 @@ impl<'a, R: Read + Unpin + Send + 'a> Stream for DebReader<'a, R> {
     }
 }
 
+/// How [`scan_deb`] sets the `Filename` of a package.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PoolLayout {
+    /// This path, relative to the repository root
+    Path(String),
+    /// The canonical path in the pool of this component, see
+    /// [`crate::pool::deb_path`]
+    Component(String),
+}
+
+/// Reads the control file of the .deb or .udeb file at `path` and completes
+/// it into the stanza of the package in a Packages index: with the
+/// `Filename` given by `layout`, the `Size` and the `MD5sum` and `SHA256`
+/// digests of the file.
+pub async fn scan_deb(path: impl AsRef<Path>, layout: &PoolLayout) -> Result<MutableControlStanza> {
+    let path = path.as_ref();
+    let file = async_std::fs::File::open(path).await?;
+    let size = file.metadata().await?.len();
+    // The file is hashed while its control member is read, then read
+    // through to the end.
+    let mut reader =
+        DigestingReader::<md5::Md5, _>::new(DigestingReader::<sha2::Sha256, _>::new(file));
+    let mut stanza = DebReader::new(&mut reader).await?.control().await?;
+    io::copy(&mut reader, &mut io::sink()).await?;
+    let (md5, reader) = reader.into_parts();
+    let (sha256, _) = reader.into_parts();
+    let filename = match layout {
+        PoolLayout::Path(filename) => {
+            pool::validate_filename(filename)?;
+            filename.clone()
+        }
+        PoolLayout::Component(component) => {
+            let field = |name| stanza.field(name).map(str::trim).unwrap_or_default();
+            let kind = match path.extension().is_some_and(|ext| ext == "udeb") {
+                true => PackageKind::Udeb,
+                false => PackageKind::Deb,
+            };
+            pool::deb_path(
+                component,
+                field("Source")
+                    .split_whitespace()
+                    .next()
+                    .unwrap_or(field("Package")),
+                field("Package"),
+                field("Version"),
+                field("Architecture"),
+                kind,
+            )
+        }
+    };
+    stanza.set("Filename", filename);
+    stanza.set("Size", size.to_string());
+    stanza.set("MD5sum", format!("{:x}", md5));
+    stanza.set("SHA256", format!("{:x}", sha256));
+    Ok(stanza)
+}
+
 pub struct DebEntryReader {
     inner: Pin<Box<dyn Read + Unpin + Send>>,
 }
@@ -653,3 +1130,228 @@ impl<'a> DebEntry<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::testutil::deb, digest::Digest};
+
+    #[test]
+    fn test_verify_md5sums() {
+        let md5sums = "\
+d41d8cd98f00b204e9800998ecf8427e  usr/bin/hello
+0cc175b9c0f1b6a831c399e269772661  usr/share/doc/hello/README
+92eb5ffee6ae2fec3ad71c777531578f  usr/share/doc/hello/gone
+";
+        let digests: HashMap<String, String> = [
+            ("usr/bin/hello", "d41d8cd98f00b204e9800998ecf8427e"),
+            (
+                "usr/share/doc/hello/README",
+                "00000000000000000000000000000000",
+            ),
+            (
+                "usr/share/doc/hello/extra",
+                "d41d8cd98f00b204e9800998ecf8427e",
+            ),
+            ("etc/hello.conf", "d41d8cd98f00b204e9800998ecf8427e"),
+        ]
+        .into_iter()
+        .map(|(path, hash)| (path.to_string(), hash.to_string()))
+        .collect();
+        let conf_files = [Conffile::new("/etc/hello.conf")];
+        let messages: Vec<_> = verify_md5sums(Some(md5sums), &digests, &conf_files)
+            .into_iter()
+            .map(|d| d.message)
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "/usr/share/doc/hello/README: md5sum mismatch",
+                "/usr/share/doc/hello/extra: not in md5sums",
+                "/usr/share/doc/hello/gone: missing from data.tar",
+            ]
+        );
+        assert_eq!(verify_md5sums(None, &digests, &conf_files).len(), 1);
+    }
+
+    const HELLO: &[u8] = b"Package: hello\nVersion: 1.0\n";
+
+    #[async_std::test]
+    async fn test_scan_deb() {
+        let dir = tempfile::tempdir().unwrap();
+        let control = b"Package: libhello1\nSource: hello (2.10-3)\nVersion: 1:2.10-3+b1\n\
+Architecture: amd64\n";
+        let data = deb(control, &[("./usr/", None)]).await;
+        let path = dir.path().join("libhello1.deb");
+        std::fs::write(&path, &data).unwrap();
+        let layout = PoolLayout::Component("main".to_string());
+        let stanza = scan_deb(&path, &layout).await.unwrap();
+        assert_eq!(
+            stanza.field("Filename"),
+            Some("pool/main/h/hello/libhello1_2.10-3+b1_amd64.deb")
+        );
+        assert_eq!(stanza.field("Size"), Some(data.len().to_string().as_str()));
+        assert_eq!(
+            stanza.field("SHA256"),
+            Some(hex::encode(sha2::Sha256::digest(&data)).as_str())
+        );
+        let layout = PoolLayout::Path("../libhello1.deb".to_string());
+        assert!(scan_deb(&path, &layout).await.is_err());
+    }
+
+    #[async_std::test]
+    async fn test_upgrade() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = crate::LocalFileSystem::new(dir.path(), false)
+            .await
+            .unwrap();
+        let files = |conf: &'static [u8], bin: &'static [u8]| {
+            [
+                ("./etc/", None),
+                ("./etc/hello.conf", Some(conf)),
+                ("./usr/", None),
+                ("./usr/bin/", None),
+                ("./usr/bin/hello", Some(bin)),
+            ]
+        };
+        let v1 = deb(HELLO, &files(b"greeting=hello\n", b"v1")).await;
+        let ctrl = DebReader::new(&v1[..])
+            .await
+            .unwrap()
+            .extract_to(&root)
+            .await
+            .unwrap();
+        let installed = Conffile::parse_field(ctrl.field("Conffiles").unwrap());
+        assert_eq!(installed.len(), 1);
+
+        let conf = dir.path().join("etc/hello.conf");
+        std::fs::write(&conf, "greeting=hi\n").unwrap();
+        let v2 = deb(HELLO, &files(b"greeting=hello, world\n", b"v2")).await;
+        let (_, actions) = DebReader::new(&v2[..])
+            .await
+            .unwrap()
+            .upgrade_to(&root, &installed, ConffilePolicy::KeepLocal)
+            .await
+            .unwrap();
+        assert_eq!(
+            actions,
+            [("/etc/hello.conf".to_string(), ConffileAction::KeepDist)]
+        );
+        let read = |path: &str| std::fs::read_to_string(dir.path().join(path)).unwrap();
+        assert_eq!(read("etc/hello.conf"), "greeting=hi\n");
+        assert_eq!(read("etc/hello.conf.dpkg-dist"), "greeting=hello, world\n");
+        assert_eq!(read("usr/bin/hello"), "v2");
+        assert!(!dir.path().join("usr/bin/hello.dpkg-new").exists());
+    }
+
+    #[async_std::test]
+    async fn test_takeover() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = crate::LocalFileSystem::new(dir.path(), false)
+            .await
+            .unwrap();
+        let files = [
+            ("./usr/", None),
+            ("./usr/bin/", None),
+            ("./usr/bin/hello", Some(&b"hello"[..])),
+        ];
+        let hello = deb(HELLO, &files).await;
+        DebReader::new(&hello[..])
+            .await
+            .unwrap()
+            .extract_to(&root)
+            .await
+            .unwrap();
+        let clash = deb(b"Package: hi\nVersion: 1.0\n", &files).await;
+        assert!(DebReader::new(&clash[..])
+            .await
+            .unwrap()
+            .extract_to(&root)
+            .await
+            .is_err());
+        let control = b"Package: hello-ng\nVersion: 1.0\nReplaces: hello (<< 2)\n";
+        let hello_ng = deb(control, &files).await;
+        DebReader::new(&hello_ng[..])
+            .await
+            .unwrap()
+            .extract_to(&root)
+            .await
+            .unwrap();
+        let read = |path: &str| std::fs::read_to_string(dir.path().join(path)).unwrap();
+        assert_eq!(read("var/lib/dpkg/info/hello.list"), "/usr/\n/usr/bin/\n");
+        assert!(read("var/lib/dpkg/info/hello-ng.list").contains("/usr/bin/hello\n"));
+    }
+
+    #[async_std::test]
+    async fn test_takeover_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = crate::LocalFileSystem::new(dir.path(), false)
+            .await
+            .unwrap();
+        let files = [
+            ("./usr/", None),
+            ("./usr/bin/", None),
+            ("./usr/bin/hello", Some(&b"hello"[..])),
+        ];
+        let hello = deb(HELLO, &files).await;
+        DebReader::new(&hello[..])
+            .await
+            .unwrap()
+            .extract_to(&root)
+            .await
+            .unwrap();
+        let status = dir.path().join("var/lib/dpkg/status");
+        std::fs::write(&status, "Package: hello\nVersion: 2.1\n").unwrap();
+        let control = b"Package: hello-ng\nVersion: 1.0\nReplaces: hello (<< 2)\n";
+        let hello_ng = deb(control, &files).await;
+        assert!(DebReader::new(&hello_ng[..])
+            .await
+            .unwrap()
+            .extract_to(&root)
+            .await
+            .is_err());
+        std::fs::write(&status, "Package: hello\nVersion: 1.9\n").unwrap();
+        DebReader::new(&hello_ng[..])
+            .await
+            .unwrap()
+            .extract_to(&root)
+            .await
+            .unwrap();
+    }
+
+    #[async_std::test]
+    async fn test_extract_filtered() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = crate::LocalFileSystem::new(dir.path(), false)
+            .await
+            .unwrap();
+        let files = [
+            ("./usr/", None),
+            ("./usr/bin/", None),
+            ("./usr/bin/hello", Some(&b"hello"[..])),
+            ("./usr/share/", None),
+            ("./usr/share/doc/", None),
+            ("./usr/share/doc/hello/", None),
+            ("./usr/share/doc/hello/changelog.gz", Some(&b"changes"[..])),
+            ("./usr/share/doc/hello/copyright", Some(&b"GPL"[..])),
+        ];
+        let hello = deb(HELLO, &files).await;
+        let paths = PathFilter::new()
+            .exclude("/usr/share/doc/*")
+            .include("/usr/share/doc/*/copyright");
+        DebReader::new(&hello[..])
+            .await
+            .unwrap()
+            .extract_filtered_to(&root, &paths)
+            .await
+            .unwrap();
+        let path = |path: &str| dir.path().join(path);
+        assert!(path("usr/bin/hello").exists());
+        assert!(path("usr/share/doc/hello/copyright").exists());
+        assert!(!path("usr/share/doc/hello/changelog.gz").exists());
+        let list = std::fs::read_to_string(path("var/lib/dpkg/info/hello.list")).unwrap();
+        assert!(!list.contains("changelog.gz"));
+        // the directories recreated for the included file are listed too
+        assert!(list.contains("/usr/share/doc/\n/usr/share/doc/hello/\n"));
+        assert!(list.contains("/usr/share/doc/hello/copyright\n"));
+    }
+}
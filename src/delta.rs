@@ -0,0 +1,258 @@
+//! Delta downloads
+//!
+//! A debdelta server publishes, next to the pool of an archive, binary
+//! patches from one version of a package to the next, e.g.
+//! `pool/main/h/hello/hello_2.10-2_2.10-3_amd64.debdelta`. When a previous
+//! version of a package is in the download cache, [`DeltaFetcher`] fetches
+//! the much smaller patch instead of the package, rebuilds the package with
+//! `debpatch` and checks it against the digests of its index, and falls back
+//! to the full download if any of these steps fails:
+//!
+//! ```ignore
+//! let deltas: DebRepo = HttpDebRepo::new("http://debdeltas.debian.net/debian-deltas")
+//!     .await?
+//!     .into();
+//! let fetcher = DeltaFetcher::new(deltas, "/var/cache/apt/archives");
+//! fetcher.fetch(&repo, &package, "/var/cache/apt/archives/partial/hello.deb").await?;
+//! ```
+//!
+//! The cached packages are looked up by their apt archive names,
+//! `<name>_<version>_<arch>.deb` with the epoch colon written as `%3a`.
+
+use {
+    crate::{
+        digest::MultiVerifyingReader,
+        error::{Error, Result},
+        packages::Package,
+        repo::DebRepo,
+        version::Version,
+    },
+    async_std::{
+        fs,
+        path::{Path, PathBuf},
+        stream::StreamExt,
+        task,
+    },
+    std::io,
+};
+
+/// How [`DeltaFetcher::fetch`] got a package.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Fetched {
+    /// Rebuilt from the cached package `base` and a delta of `size` bytes
+    Delta { base: PathBuf, size: u64 },
+    /// Downloaded in full
+    Full,
+}
+
+/// Fetches packages as deltas against the cached previous versions.
+pub struct DeltaFetcher {
+    deltas: DebRepo,
+    cache: PathBuf,
+    debpatch: Vec<String>,
+}
+
+impl DeltaFetcher {
+    /// Creates a fetcher getting the deltas from `deltas` and the previous
+    /// versions from the directory `cache`.
+    pub fn new(deltas: DebRepo, cache: impl Into<PathBuf>) -> Self {
+        Self {
+            deltas,
+            cache: cache.into(),
+            debpatch: vec!["debpatch".to_string()],
+        }
+    }
+    /// Sets the command applying a delta, `debpatch` by default. It is run
+    /// with the paths of the delta, the previous version and the result.
+    pub fn debpatch<I: IntoIterator<Item = S>, S: Into<String>>(mut self, argv: I) -> Self {
+        self.debpatch = argv.into_iter().map(Into::into).collect();
+        self
+    }
+    /// Writes `package` from `repo` to `dst`, rebuilt from a delta if
+    /// possible. The result is verified either way.
+    pub async fn fetch(
+        &self,
+        repo: &DebRepo,
+        package: &Package<'_>,
+        dst: impl AsRef<Path>,
+    ) -> Result<Fetched> {
+        let dst = dst.as_ref();
+        let (path, size, digests) = package.repo_file()?;
+        match self.fetch_delta(package, dst).await {
+            Ok(Some(fetched)) => return Ok(fetched),
+            Ok(None) => {}
+            Err(err) => {
+                tracing::debug!("delta of {} failed, fetching in full: {}", package, err);
+            }
+        }
+        let file = fs::File::create(dst).await?;
+        repo.copy_verify(file, path, size, digests).await?;
+        Ok(Fetched::Full)
+    }
+    async fn fetch_delta(&self, package: &Package<'_>, dst: &Path) -> Result<Option<Fetched>> {
+        let (path, size, digests) = package.repo_file()?;
+        let Some((base, from)) = self.base(package).await? else {
+            return Ok(None);
+        };
+        let dir = path.rsplit_once('/').map_or("", |(dir, _)| dir);
+        let delta_path = format!(
+            "{}/{}_{}_{}_{}.debdelta",
+            dir,
+            package.name(),
+            escape(&from),
+            escape(package.version().as_ref()),
+            package.arch()
+        )
+        .trim_start_matches('/')
+        .to_string();
+        let tmp = tempfile::tempdir()?;
+        let delta = tmp.path().join("delta");
+        let delta_size = self
+            .deltas
+            .copy(&delta_path, fs::File::create(&delta).await?)
+            .await?;
+        let out = tmp.path().join("deb");
+        let mut argv = self.debpatch.clone();
+        for arg in [delta.as_os_str(), base.as_os_str(), out.as_os_str()] {
+            argv.push(arg.to_string_lossy().into_owned());
+        }
+        let status = task::spawn_blocking(move || {
+            std::process::Command::new(&argv[0])
+                .args(&argv[1..])
+                .status()
+        })
+        .await?;
+        if !status.success() {
+            return Err(Error::Io(io::Error::other(format!(
+                "applying {} failed: {}",
+                delta_path, status
+            ))));
+        }
+        let reader = fs::File::open(&out).await?;
+        futures::io::copy(
+            &mut MultiVerifyingReader::new(reader, size, digests),
+            &mut futures::io::sink(),
+        )
+        .await?;
+        fs::copy(&out, dst).await?;
+        Ok(Some(Fetched::Delta {
+            base,
+            size: delta_size,
+        }))
+    }
+    // Returns the newest cached version of the package older than its own,
+    // along with the version.
+    async fn base(&self, package: &Package<'_>) -> io::Result<Option<(PathBuf, String)>> {
+        let mut entries = match fs::read_dir(&self.cache).await {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let mut base: Option<(PathBuf, String)> = None;
+        while let Some(entry) = entries.next().await {
+            let entry = entry?;
+            let name = entry.file_name();
+            let Some(version) = name
+                .to_str()
+                .and_then(|name| name.strip_suffix(".deb"))
+                .and_then(|name| name.strip_prefix(package.name()))
+                .and_then(|name| name.strip_prefix('_'))
+                .and_then(|name| name.strip_suffix(package.arch()))
+                .and_then(|name| name.strip_suffix('_'))
+            else {
+                continue;
+            };
+            let version = version.replace("%3a", ":");
+            let older = Version::from(version.as_str());
+            if version.contains('_') || older >= package.version() {
+                continue;
+            }
+            if let Some((_, newest)) = &base {
+                let newest = Version::from(newest.as_str());
+                if newest >= older {
+                    continue;
+                }
+            }
+            base = Some((entry.path(), version));
+        }
+        Ok(base)
+    }
+}
+
+// Writes the epoch colon of `version` the way debdelta and apt do in file
+// names.
+fn escape(version: &str) -> String {
+    version.replace(':', "%3a")
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{fsrepo::FSDebRepo, packages::Packages},
+        digest::Digest,
+    };
+
+    #[async_std::test]
+    async fn test_fetch() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for (path, data) in [
+            ("repo/pool/main/h/hello/hello_1:2.0_all.deb", "hello world"),
+            (
+                "deltas/pool/main/h/hello/hello_1%3a1.0_1%3a2.0_all.debdelta",
+                " world",
+            ),
+            ("cache/hello_1%3a0.5_all.deb", "bye"),
+            ("cache/hello_1%3a1.0_all.deb", "hello"),
+            ("cache/hello-doc_1%3a1.0_all.deb", "doc"),
+        ] {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, data).unwrap();
+        }
+        let index = |digest: &[u8]| {
+            format!(
+                "Package: hello\nVersion: 1:2.0\nArchitecture: all\n\
+                 Filename: pool/main/h/hello/hello_1:2.0_all.deb\nSize: 11\nSHA256: {}\n",
+                hex::encode(digest)
+            )
+        };
+        let repo: DebRepo = FSDebRepo::new(root.join("repo")).await.unwrap().into();
+        let deltas: DebRepo = FSDebRepo::new(root.join("deltas")).await.unwrap().into();
+        // the fake debpatch concatenates the base and the delta
+        let fetcher = DeltaFetcher::new(deltas, root.join("cache")).debpatch([
+            "sh",
+            "-c",
+            "cat \"$2\" \"$1\" > \"$3\"",
+            "debpatch",
+        ]);
+        let dst = root.join("hello.deb");
+
+        let packages = Packages::new_test(index(&sha2::Sha256::digest(b"hello world"))).unwrap();
+        let package = packages.packages().next().unwrap();
+        let fetched = fetcher.fetch(&repo, package, &dst).await.unwrap();
+        assert_eq!(
+            fetched,
+            Fetched::Delta {
+                base: root.join("cache/hello_1%3a1.0_all.deb").into(),
+                size: 6
+            }
+        );
+        assert_eq!(std::fs::read_to_string(&dst).unwrap(), "hello world");
+
+        // a rebuilt package not matching its digest is fetched in full
+        std::fs::write(root.join("cache/hello_1%3a1.0_all.deb"), "jello").unwrap();
+        let fetched = fetcher.fetch(&repo, package, &dst).await.unwrap();
+        assert_eq!(fetched, Fetched::Full);
+        assert_eq!(std::fs::read_to_string(&dst).unwrap(), "hello world");
+
+        // as is one with no previous version cached
+        std::fs::remove_dir_all(root.join("cache")).unwrap();
+        let fetched = fetcher.fetch(&repo, package, &dst).await.unwrap();
+        assert_eq!(fetched, Fetched::Full);
+        let packages = Packages::new_test(index(&sha2::Sha256::digest(b"bye"))).unwrap();
+        let package = packages.packages().next().unwrap();
+        assert!(fetcher.fetch(&repo, package, &dst).await.is_err());
+    }
+}
@@ -0,0 +1,594 @@
+//! AppStream metadata (DEP-11)
+//!
+//! Archives ship AppStream component metadata in
+//! `dists/<suite>/<component>/dep11/Components-<arch>.yml.gz`: one YAML
+//! document per application, font, codec and so on, naming the package
+//! that provides it. [`crate::Release::fetch_components`] fetches it and
+//! [`Components::for_package`] joins it to the packages.
+//!
+//! Only the subset of YAML the archive generators produce is understood:
+//! block mappings and sequences, plain, quoted and block scalars, and empty
+//! flow collections.
+
+use {
+    crate::{control::ParseError, packages::Package},
+    std::collections::HashMap,
+};
+
+/// A text translated to several languages, keyed by locale, e.g. `de_AT`.
+/// The untranslated text has the locale `C`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Localized(pub HashMap<String, String>);
+
+impl Localized {
+    /// Returns the text for `locale`, falling back to its language and then
+    /// to the untranslated text.
+    pub fn get(&self, locale: &str) -> Option<&str> {
+        let language = locale.split(['_', '.', '@']).next().unwrap_or(locale);
+        [locale, language, "C"]
+            .iter()
+            .find_map(|l| self.0.get(*l))
+            .map(String::as_str)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IconKind {
+    /// A themed icon name
+    Stock,
+    /// An icon in the archive icon tarballs, `icons-<size>.tar.gz`
+    Cached,
+    /// An icon at an URL relative to the media base URL
+    Remote,
+    /// An icon installed by the package
+    Local,
+}
+
+/// An icon of a component.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Icon {
+    pub kind: IconKind,
+    /// The icon name, file name, path or URL, depending on the kind
+    pub name: String,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+/// An AppStream component.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Component {
+    /// The component type, e.g. `desktop-application`
+    pub kind: String,
+    /// The component id, e.g. `org.gnome.Calculator`
+    pub id: String,
+    /// The binary package providing the component
+    pub package: Option<String>,
+    pub name: Localized,
+    pub summary: Localized,
+    /// The description, in AppStream markup
+    pub description: Localized,
+    pub categories: Vec<String>,
+    /// The untranslated keywords
+    pub keywords: Vec<String>,
+    pub icons: Vec<Icon>,
+    pub homepage: Option<String>,
+}
+
+/// A parsed `Components-<arch>.yml` file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Components {
+    /// The origin from the header document, e.g. `debian-bookworm-main`
+    pub origin: Option<String>,
+    /// The base URL of the remote icons and screenshots
+    pub media_base_url: Option<String>,
+    pub components: Vec<Component>,
+}
+
+impl Components {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut components = Self::default();
+        for (n, doc) in yaml::documents(text)?.into_iter().enumerate() {
+            if n == 0 && doc.get("File").and_then(Node::as_str) == Some("DEP-11") {
+                components.origin = doc.get("Origin").and_then(Node::as_str).map(String::from);
+                components.media_base_url = doc
+                    .get("MediaBaseUrl")
+                    .and_then(Node::as_str)
+                    .map(String::from);
+                continue;
+            }
+            let Some(id) = doc.get("ID").and_then(Node::as_str) else {
+                return Err(format!("document {}: component without ID", n + 1).into());
+            };
+            let string = |key| doc.get(key).and_then(Node::as_str).map(String::from);
+            components.components.push(Component {
+                kind: string("Type").unwrap_or_default(),
+                id: id.to_string(),
+                package: string("Package"),
+                name: localized(doc.get("Name")),
+                summary: localized(doc.get("Summary")),
+                description: localized(doc.get("Description")),
+                categories: strings(doc.get("Categories")),
+                keywords: strings(doc.get("Keywords").and_then(|k| k.get("C"))),
+                icons: icons(doc.get("Icon")),
+                homepage: doc
+                    .get("Url")
+                    .and_then(|url| url.get("homepage"))
+                    .and_then(Node::as_str)
+                    .map(String::from),
+            });
+        }
+        Ok(components)
+    }
+    /// Returns the components provided by the package `name`.
+    pub fn by_package<'s>(&'s self, name: &'s str) -> impl Iterator<Item = &'s Component> {
+        self.components
+            .iter()
+            .filter(move |c| c.package.as_deref() == Some(name))
+    }
+    /// Returns the components provided by `package`.
+    pub fn for_package<'s>(
+        &'s self,
+        package: &'s Package<'_>,
+    ) -> impl Iterator<Item = &'s Component> {
+        self.by_package(package.name())
+    }
+    /// The URL of a remote icon.
+    pub fn icon_url(&self, icon: &Icon) -> Option<String> {
+        match (icon.kind, &self.media_base_url) {
+            (IconKind::Remote, _) if icon.name.contains("://") => Some(icon.name.clone()),
+            (IconKind::Remote, Some(base)) => Some(format!(
+                "{}/{}",
+                base.trim_end_matches('/'),
+                icon.name.trim_start_matches('/')
+            )),
+            _ => None,
+        }
+    }
+}
+
+fn localized(node: Option<&Node>) -> Localized {
+    Localized(
+        node.and_then(Node::as_map)
+            .into_iter()
+            .flatten()
+            .filter_map(|(locale, text)| Some((locale.clone(), text.as_str()?.to_string())))
+            .collect(),
+    )
+}
+
+fn strings(node: Option<&Node>) -> Vec<String> {
+    node.and_then(Node::as_list)
+        .into_iter()
+        .flatten()
+        .filter_map(|item| item.as_str().map(String::from))
+        .collect()
+}
+
+fn icons(node: Option<&Node>) -> Vec<Icon> {
+    let mut icons = vec![];
+    for (kind, node) in node.and_then(Node::as_map).into_iter().flatten() {
+        let kind = match kind.as_str() {
+            "stock" => IconKind::Stock,
+            "cached" => IconKind::Cached,
+            "remote" => IconKind::Remote,
+            "local" => IconKind::Local,
+            _ => continue,
+        };
+        if let Some(name) = node.as_str() {
+            icons.push(Icon {
+                kind,
+                name: name.to_string(),
+                width: None,
+                height: None,
+            });
+            continue;
+        }
+        for icon in node.as_list().into_iter().flatten() {
+            let size = |key| icon.get(key).and_then(Node::as_str)?.parse().ok();
+            let Some(name) = icon.get("name").or(icon.get("url")).and_then(Node::as_str) else {
+                continue;
+            };
+            icons.push(Icon {
+                kind,
+                name: name.to_string(),
+                width: size("width"),
+                height: size("height"),
+            });
+        }
+    }
+    icons
+}
+
+use yaml::Node;
+
+mod yaml {
+    use crate::control::ParseError;
+
+    #[derive(Debug, PartialEq, Eq)]
+    pub(super) enum Node {
+        Scalar(String),
+        List(Vec<Node>),
+        Map(Vec<(String, Node)>),
+    }
+
+    impl Node {
+        pub(super) fn get(&self, key: &str) -> Option<&Node> {
+            self.as_map()?
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v)
+        }
+        pub(super) fn as_str(&self) -> Option<&str> {
+            match self {
+                Self::Scalar(s) => Some(s),
+                _ => None,
+            }
+        }
+        pub(super) fn as_list(&self) -> Option<&[Node]> {
+            match self {
+                Self::List(items) => Some(items),
+                _ => None,
+            }
+        }
+        pub(super) fn as_map(&self) -> Option<&[(String, Node)]> {
+            match self {
+                Self::Map(entries) => Some(entries),
+                _ => None,
+            }
+        }
+    }
+
+    // A line with its indentation, the content starting past it.
+    struct Line<'a> {
+        num: usize,
+        indent: usize,
+        text: &'a str,
+    }
+
+    struct Parser<'a> {
+        lines: Vec<Line<'a>>,
+        pos: usize,
+    }
+
+    /// Parses the documents of a YAML stream.
+    pub(super) fn documents(text: &str) -> Result<Vec<Node>, ParseError> {
+        let mut docs = vec![];
+        let mut lines = vec![];
+        let mut flush = |lines: &mut Vec<Line<'_>>| -> Result<(), ParseError> {
+            if lines.iter().any(|l| !is_blank(l.text)) {
+                let mut parser = Parser {
+                    lines: std::mem::take(lines),
+                    pos: 0,
+                };
+                let indent = parser.peek().map_or(0, |l| l.indent);
+                docs.push(parser.node(indent)?);
+                if let Some(line) = parser.peek() {
+                    return Err(format!("line {}: unexpected content", line.num).into());
+                }
+            }
+            lines.clear();
+            Ok(())
+        };
+        for (n, line) in text.lines().enumerate() {
+            if line == "---" || line.starts_with("--- ") || line == "..." {
+                flush(&mut lines)?;
+            } else if !line.starts_with('%') {
+                let text = line.trim_start_matches(' ');
+                lines.push(Line {
+                    num: n + 1,
+                    indent: line.len() - text.len(),
+                    text,
+                });
+            }
+        }
+        flush(&mut lines)?;
+        Ok(docs)
+    }
+
+    fn is_blank(text: &str) -> bool {
+        text.trim().is_empty() || text.starts_with('#')
+    }
+
+    // Splits `key: value` or `key:`, the key possibly quoted.
+    fn split_key(text: &str) -> Option<(String, &str)> {
+        if text.starts_with(['\'', '"']) {
+            let (key, len) = quoted(text)?;
+            let rest = text[len..].strip_prefix(':')?;
+            return (rest.is_empty() || rest.starts_with(' ')).then(|| (key, rest.trim()));
+        }
+        let end = text
+            .find(": ")
+            .or_else(|| text.ends_with(':').then(|| text.len() - 1))?;
+        Some((text[..end].trim_end().to_string(), text[end + 1..].trim()))
+    }
+
+    // Parses a quoted scalar at the start of `text`, returning it and the
+    // length of its source.
+    fn quoted(text: &str) -> Option<(String, usize)> {
+        let quote = text.chars().next()?;
+        let mut value = String::new();
+        let mut chars = text.char_indices().skip(1);
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\'' if quote == '\'' => match text[i + 1..].starts_with('\'') {
+                    true => {
+                        chars.next();
+                        value.push('\'');
+                    }
+                    false => return Some((value, i + 1)),
+                },
+                '"' if quote == '"' => return Some((value, i + 1)),
+                '\\' if quote == '"' => match chars.next()?.1 {
+                    'n' => value.push('\n'),
+                    't' => value.push('\t'),
+                    'u' => {
+                        let hex: String =
+                            (0..4).filter_map(|_| chars.next()).map(|c| c.1).collect();
+                        value.push(char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?);
+                    }
+                    c => value.push(c),
+                },
+                c => value.push(c),
+            }
+        }
+        None
+    }
+
+    fn scalar(text: &str) -> Node {
+        let text = match text.find(" #") {
+            Some(n) if !text.starts_with(['\'', '"']) => &text[..n],
+            _ => text,
+        };
+        match text {
+            "[]" => Node::List(vec![]),
+            "{}" => Node::Map(vec![]),
+            "~" | "null" => Node::Scalar(String::new()),
+            _ if text.starts_with('[') && text.ends_with(']') => Node::List(
+                text[1..text.len() - 1]
+                    .split(',')
+                    .map(|item| scalar(item.trim()))
+                    .collect(),
+            ),
+            _ => Node::Scalar(match quoted(text) {
+                Some((value, len)) if len == text.len() => value,
+                _ => text.trim().to_string(),
+            }),
+        }
+    }
+
+    impl<'a> Parser<'a> {
+        // The next non-blank line.
+        fn peek(&mut self) -> Option<&Line<'a>> {
+            while self.lines.get(self.pos).is_some_and(|l| is_blank(l.text)) {
+                self.pos += 1;
+            }
+            self.lines.get(self.pos)
+        }
+        // Parses the node starting at the next line, indented by `indent`.
+        fn node(&mut self, indent: usize) -> Result<Node, ParseError> {
+            let Some(line) = self.peek() else {
+                return Ok(Node::Scalar(String::new()));
+            };
+            if line.text == "-" || line.text.starts_with("- ") {
+                self.list(indent)
+            } else if split_key(line.text).is_some() {
+                self.map(indent)
+            } else {
+                let text = line.text;
+                self.pos += 1;
+                Ok(self.continued(scalar(text), indent))
+            }
+        }
+        fn map(&mut self, indent: usize) -> Result<Node, ParseError> {
+            let mut entries = vec![];
+            while let Some(line) = self.peek() {
+                if line.indent < indent {
+                    break;
+                }
+                let (num, text) = (line.num, line.text);
+                let Some((key, value)) = (line.indent == indent).then(|| split_key(text)).flatten()
+                else {
+                    return Err(format!("line {}: expected a mapping entry", num).into());
+                };
+                self.pos += 1;
+                let value = match value {
+                    "" => match self.peek() {
+                        Some(next) if next.indent > indent => {
+                            let indent = next.indent;
+                            self.node(indent)?
+                        }
+                        // a sequence may be indented as its key
+                        Some(next)
+                            if next.indent == indent
+                                && (next.text == "-" || next.text.starts_with("- ")) =>
+                        {
+                            self.list(indent)?
+                        }
+                        _ => Node::Scalar(String::new()),
+                    },
+                    _ if value.starts_with(['|', '>']) => self.block(value, indent),
+                    _ => self.continued(scalar(value), indent),
+                };
+                entries.push((key, value));
+            }
+            Ok(Node::Map(entries))
+        }
+        fn list(&mut self, indent: usize) -> Result<Node, ParseError> {
+            let mut items = vec![];
+            while let Some(line) = self.peek() {
+                if line.indent != indent || !(line.text == "-" || line.text.starts_with("- ")) {
+                    break;
+                }
+                let item = line.text[1..].trim_start();
+                if item.is_empty() {
+                    self.pos += 1;
+                    items.push(match self.peek() {
+                        Some(next) if next.indent > indent => {
+                            let indent = next.indent;
+                            self.node(indent)?
+                        }
+                        _ => Node::Scalar(String::new()),
+                    });
+                    continue;
+                }
+                // the item content starts a nested block: `- key: value`
+                let offset = line.text.len() - item.len();
+                let line = &mut self.lines[self.pos];
+                line.indent += offset;
+                line.text = item;
+                items.push(self.node(indent + offset)?);
+            }
+            Ok(Node::List(items))
+        }
+        // Reads a `|` or `>` block scalar following the line indented by
+        // `indent`.
+        fn block(&mut self, header: &str, indent: usize) -> Node {
+            let mut lines: Vec<String> = vec![];
+            let mut block_indent = None;
+            while let Some(line) = self.lines.get(self.pos) {
+                if line.text.trim().is_empty() {
+                    lines.push(String::new());
+                } else if line.indent > indent && block_indent.is_none_or(|i| line.indent >= i) {
+                    // more indented lines keep the extra indentation
+                    let extra = line.indent - *block_indent.get_or_insert(line.indent);
+                    lines.push(format!("{}{}", " ".repeat(extra), line.text.trim_end()));
+                } else {
+                    break;
+                }
+                self.pos += 1;
+            }
+            while lines.last().is_some_and(String::is_empty) {
+                lines.pop();
+            }
+            let mut text = String::new();
+            if header.starts_with('|') {
+                text = lines.join("\n");
+            } else {
+                // a line break folds to a space, blank lines are kept
+                for (n, line) in lines.iter().enumerate() {
+                    if line.is_empty() {
+                        text.push('\n');
+                    } else if n > 0 && !lines[n - 1].is_empty() {
+                        text.push(' ');
+                    }
+                    text.push_str(line);
+                }
+            }
+            if !header.contains('-') {
+                text.push('\n');
+            }
+            Node::Scalar(text)
+        }
+        // Folds the continuation lines of a multi-line plain scalar.
+        fn continued(&mut self, node: Node, indent: usize) -> Node {
+            let Node::Scalar(mut text) = node else {
+                return node;
+            };
+            while let Some(line) = self.peek() {
+                if line.indent <= indent || split_key(line.text).is_some() {
+                    break;
+                }
+                text.push(' ');
+                text.push_str(line.text.trim());
+                self.pos += 1;
+            }
+            Node::Scalar(text)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMPONENTS: &str = "\
+---
+File: DEP-11
+Version: '0.16'
+Origin: debian-bookworm-main
+MediaBaseUrl: https://appstream.debian.org/media/pool
+---
+Type: desktop-application
+ID: org.gnome.Calculator
+Package: gnome-calculator
+Name:
+  C: Calculator
+  de: Rechner
+Summary:
+  C: Perform arithmetic, scientific or financial calculations
+Description:
+  C: >-
+    <p>Calculator is an application that solves
+    mathematical equations.</p>
+
+    <p>It is easy to use.</p>
+Categories:
+- GNOME
+- Utility
+Keywords:
+  C:
+  - calculation
+  - 'arithmetic''s'
+Icon:
+  cached:
+  - name: gnome-calculator_org.gnome.Calculator.png
+    width: 64
+    height: 64
+  remote:
+  - url: g/gn/gnome-calculator/icons/128x128/org.gnome.Calculator.png
+    width: 128
+    height: 128
+  stock: accessories-calculator
+Url:
+  homepage: \"https://wiki.gnome.org/Apps/Calculator\"
+Provides: {}
+---
+Type: font
+ID: fonts-dejavu-core
+Package: fonts-dejavu-core
+Name:
+  C: DejaVu # a comment
+";
+
+    #[test]
+    fn test_parse_components() {
+        let components = Components::parse(COMPONENTS).unwrap();
+        assert_eq!(components.origin.as_deref(), Some("debian-bookworm-main"));
+        assert_eq!(components.components.len(), 2);
+        let calculator = components.by_package("gnome-calculator").next().unwrap();
+        assert_eq!(calculator.kind, "desktop-application");
+        assert_eq!(calculator.id, "org.gnome.Calculator");
+        assert_eq!(calculator.name.get("de_AT.UTF-8"), Some("Rechner"));
+        assert_eq!(calculator.name.get("fr"), Some("Calculator"));
+        assert_eq!(
+            calculator.description.get("C"),
+            Some("<p>Calculator is an application that solves mathematical equations.</p>\n<p>It is easy to use.</p>")
+        );
+        assert_eq!(calculator.categories, ["GNOME", "Utility"]);
+        assert_eq!(calculator.keywords, ["calculation", "arithmetic's"]);
+        assert_eq!(
+            calculator.homepage.as_deref(),
+            Some("https://wiki.gnome.org/Apps/Calculator")
+        );
+        let kinds: Vec<_> = calculator.icons.iter().map(|i| (i.kind, i.width)).collect();
+        assert_eq!(
+            kinds,
+            [
+                (IconKind::Cached, Some(64)),
+                (IconKind::Remote, Some(128)),
+                (IconKind::Stock, None)
+            ]
+        );
+        assert_eq!(
+            components.icon_url(&calculator.icons[1]).as_deref(),
+            Some("https://appstream.debian.org/media/pool/g/gn/gnome-calculator/icons/128x128/org.gnome.Calculator.png")
+        );
+        assert_eq!(components.icon_url(&calculator.icons[0]), None);
+        let font = &components.components[1];
+        assert_eq!(font.name.get("C"), Some("DejaVu"));
+        assert!(font.icons.is_empty());
+        assert_eq!(components.by_package("hello").count(), 0);
+        assert!(Components::parse("---\nType: generic\n").is_err());
+        assert!(Components::parse("---\nID: a\n  Name: b\n").is_err());
+    }
+}
@@ -57,6 +57,10 @@ pub trait DeploymentFileSystem {
     async fn set_mtime<P>(&self, path: P, time: std::time::SystemTime) -> io::Result<()>
     where
         P: AsRef<Path> + Send;
+    /// Read the file at `path`, e.g. a conffile to be upgraded
+    async fn read_file<P>(&self, path: P) -> io::Result<Vec<u8>>
+    where
+        P: AsRef<Path> + Send;
 }
 
 #[derive(Clone, Debug)]
@@ -142,6 +146,9 @@ impl DeploymentFileSystem for &LocalFileSystem {
         let to = self.target_path(to.as_ref())?;
         fs::hard_link(from, to).await
     }
+    async fn read_file<P: AsRef<Path> + Send>(&self, path: P) -> io::Result<Vec<u8>> {
+        fs::read(self.target_path(path.as_ref())?).await
+    }
     async fn create_file<P: AsRef<Path> + Send>(
         &self,
         path: P,
@@ -0,0 +1,55 @@
+//! Diagnostics for recoverable problems
+//!
+//! Lenient parsing and dependency resolution carry on past malformed data;
+//! what was skipped or fixed up is reported as a [`Diagnostic`].
+
+use std::fmt;
+
+/// A problem in repository data that did not stop processing, e.g. a
+/// skipped malformed stanza.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// Line of the index where the stanza starts, counting from 1
+    pub line: Option<usize>,
+    pub package: Option<String>,
+    pub field: Option<String>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            line: None,
+            package: None,
+            field: None,
+            message: message.into(),
+        }
+    }
+    pub fn line(mut self, line: usize) -> Self {
+        self.line = Some(line);
+        self
+    }
+    pub fn package(mut self, package: impl Into<String>) -> Self {
+        self.package = Some(package.into());
+        self
+    }
+    pub fn field(mut self, field: impl Into<String>) -> Self {
+        self.field = Some(field.into());
+        self
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(line) = self.line {
+            write!(f, "line {}: ", line)?;
+        }
+        if let Some(package) = &self.package {
+            write!(f, "package {}: ", package)?;
+        }
+        if let Some(field) = &self.field {
+            write!(f, "field {}: ", field)?;
+        }
+        f.write_str(&self.message)
+    }
+}
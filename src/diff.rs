@@ -0,0 +1,191 @@
+//! Package index comparison
+//!
+//! [`diff`] compares two snapshots of a Packages index, e.g. of a suite
+//! before and after a dinstall run, and tells which packages appeared,
+//! disappeared, or changed version. Packages are told apart by name and
+//! architecture; where an index lists several versions, the newest one
+//! counts. The diff displays as a report:
+//!
+//! ```text
+//! NEW  hello 2.10-3 [amd64]
+//! RM   hello-traditional 2.10-5 [amd64]
+//! UPG  libc6 2.36-8 -> 2.36-9 [amd64]
+//! 1 new, 1 removed, 1 upgraded, 0 downgraded
+//! ```
+
+use {
+    crate::{packages::Packages, version::Version},
+    std::{collections::BTreeMap, fmt},
+};
+
+/// A package added, removed or changed between two indexes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PackageChange {
+    pub name: String,
+    pub arch: String,
+    /// The version in the old index, if listed there
+    pub old: Option<String>,
+    /// The version in the new index, if listed there
+    pub new: Option<String>,
+}
+
+/// The changes between two package indexes, each sorted by name and
+/// architecture.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IndexDiff {
+    pub added: Vec<PackageChange>,
+    pub removed: Vec<PackageChange>,
+    pub upgraded: Vec<PackageChange>,
+    pub downgraded: Vec<PackageChange>,
+}
+
+impl IndexDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.upgraded.is_empty()
+            && self.downgraded.is_empty()
+    }
+    /// The counts of the changes, e.g.
+    /// `1 new, 1 removed, 1 upgraded, 0 downgraded`.
+    pub fn summary(&self) -> String {
+        format!(
+            "{} new, {} removed, {} upgraded, {} downgraded",
+            self.added.len(),
+            self.removed.len(),
+            self.upgraded.len(),
+            self.downgraded.len()
+        )
+    }
+}
+
+impl fmt::Display for IndexDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sections = [
+            ("NEW", &self.added),
+            ("RM", &self.removed),
+            ("UPG", &self.upgraded),
+            ("DOWN", &self.downgraded),
+        ];
+        for (tag, changes) in sections {
+            for change in changes {
+                write!(f, "{:<4} {} ", tag, change.name)?;
+                match (&change.old, &change.new) {
+                    (Some(old), Some(new)) => write!(f, "{} -> {}", old, new)?,
+                    (Some(version), None) | (None, Some(version)) => write!(f, "{}", version)?,
+                    (None, None) => {}
+                }
+                writeln!(f, " [{}]", change.arch)?;
+            }
+        }
+        writeln!(f, "{}", self.summary())
+    }
+}
+
+// The newest version of each package, by name and architecture.
+fn newest<S: AsRef<str>>(index: &Packages<S>) -> BTreeMap<(&str, &str), Version<&str>> {
+    let mut versions = BTreeMap::new();
+    for package in index.packages() {
+        let version = package.version();
+        versions
+            .entry((package.name(), package.arch()))
+            .and_modify(|v: &mut Version<&str>| {
+                if version > *v {
+                    *v = version.clone();
+                }
+            })
+            .or_insert(version);
+    }
+    versions
+}
+
+fn change(
+    (name, arch): (&str, &str),
+    old: Option<&Version<&str>>,
+    new: Option<&Version<&str>>,
+) -> PackageChange {
+    PackageChange {
+        name: name.to_string(),
+        arch: arch.to_string(),
+        old: old.map(|v| v.to_string()),
+        new: new.map(|v| v.to_string()),
+    }
+}
+
+/// Compares the package index `new` with `old`.
+pub fn diff<S: AsRef<str>, T: AsRef<str>>(old: &Packages<S>, new: &Packages<T>) -> IndexDiff {
+    let (old, new) = (newest(old), newest(new));
+    let mut diff = IndexDiff::default();
+    for (key, version) in &old {
+        let list = match new.get(key) {
+            None => &mut diff.removed,
+            Some(other) if other > version => &mut diff.upgraded,
+            Some(other) if other < version => &mut diff.downgraded,
+            Some(_) => continue,
+        };
+        list.push(change(*key, Some(version), new.get(key)));
+    }
+    for (key, version) in &new {
+        if !old.contains_key(key) {
+            diff.added.push(change(*key, None, Some(version)));
+        }
+    }
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff() {
+        let old = Packages::new_test(
+            "\
+Package: hello-traditional
+Version: 2.10-5
+Architecture: amd64
+
+Package: libc6
+Version: 2.36-8
+Architecture: amd64
+
+Package: libc6
+Version: 2.36-7
+Architecture: amd64
+
+Package: bash
+Version: 5.2-2
+Architecture: amd64
+",
+        )
+        .unwrap();
+        let new = Packages::new_test(
+            "\
+Package: hello
+Version: 2.10-3
+Architecture: amd64
+
+Package: libc6
+Version: 2.36-9
+Architecture: amd64
+
+Package: bash
+Version: 5.2-2
+Architecture: amd64
+",
+        )
+        .unwrap();
+        let diff = diff(&old, &new);
+        assert_eq!(diff.upgraded[0].old.as_deref(), Some("2.36-8"));
+        assert_eq!(
+            diff.to_string(),
+            "\
+NEW  hello 2.10-3 [amd64]
+RM   hello-traditional 2.10-5 [amd64]
+UPG  libc6 2.36-8 -> 2.36-9 [amd64]
+1 new, 1 removed, 1 upgraded, 0 downgraded
+"
+        );
+        assert!(super::diff(&new, &new).is_empty());
+    }
+}
@@ -1,7 +1,9 @@
 //! Digest verification
 
 pub use digest::{FixedOutputReset as Digester, Output as DigesterOutput};
+#[cfg(feature = "io")]
 use {
+    crate::metrics::{self, Counter},
     async_std::{
         io::prelude::*,
         task::{ready, Context, Poll},
@@ -105,12 +107,14 @@ impl<D: Digester + Send> std::fmt::LowerHex for Digest<D> {
     }
 }
 
+#[cfg(feature = "io")]
 #[pin_project]
 pub struct DigestingReader<D: Digester + Send, R: Read + Unpin + Send> {
     digester: D,
     #[pin]
     inner: R,
 }
+#[cfg(feature = "io")]
 impl<D: Digester + Default + Send, R: Read + Unpin + Send> DigestingReader<D, R> {
     pub fn new(reader: R) -> Self {
         Self {
@@ -123,8 +127,16 @@ impl<D: Digester + Default + Send, R: Read + Unpin + Send> DigestingReader<D, R>
             inner: self.digester.finalize_fixed(),
         }
     }
+    /// Returns the digest of the data read and the inner reader.
+    pub fn into_parts(self) -> (Digest<D>, R) {
+        let digest = Digest {
+            inner: self.digester.finalize_fixed(),
+        };
+        (digest, self.inner)
+    }
 }
 
+#[cfg(feature = "io")]
 impl<D: Digester + Send, R: Read + Unpin + Send> Read for DigestingReader<D, R> {
     fn poll_read(
         self: Pin<&mut Self>,
@@ -143,6 +155,59 @@ impl<D: Digester + Send, R: Read + Unpin + Send> Read for DigestingReader<D, R>
     }
 }
 
+/// Digests the data written through it.
+#[cfg(feature = "io")]
+#[pin_project]
+pub struct DigestingWriter<D: Digester + Send, W: Write + Unpin + Send> {
+    digester: D,
+    size: u64,
+    #[pin]
+    inner: W,
+}
+#[cfg(feature = "io")]
+impl<D: Digester + Default + Send, W: Write + Unpin + Send> DigestingWriter<D, W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            digester: D::default(),
+            size: 0,
+            inner: writer,
+        }
+    }
+    /// Returns the number of bytes written.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+    /// Returns the digest of the data written and the inner writer.
+    pub fn into_parts(self) -> (Digest<D>, W) {
+        let digest = Digest {
+            inner: self.digester.finalize_fixed(),
+        };
+        (digest, self.inner)
+    }
+}
+
+#[cfg(feature = "io")]
+impl<D: Digester + Send, W: Write + Unpin + Send> Write for DigestingWriter<D, W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let mut this = self.project();
+        let size = ready!(this.inner.as_mut().poll_write(cx, buf))?;
+        this.digester.update(&buf[..size]);
+        *this.size += size as u64;
+        Poll::Ready(Ok(size))
+    }
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.project().inner.poll_flush(cx)
+    }
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.project().inner.poll_close(cx)
+    }
+}
+
+#[cfg(feature = "io")]
 #[pin_project]
 pub struct VerifyingReader<D: Digester + Default + Send, R: Read + Unpin + Send> {
     digester: D,
@@ -153,6 +218,7 @@ pub struct VerifyingReader<D: Digester + Default + Send, R: Read + Unpin + Send>
     inner: R,
 }
 
+#[cfg(feature = "io")]
 impl<D: Digester + Default + Send, R: Read + Unpin + Send> VerifyingReader<D, R> {
     pub fn new(reader: R, size: usize, digest: Digest<D>) -> Self {
         Self {
@@ -165,6 +231,7 @@ impl<D: Digester + Default + Send, R: Read + Unpin + Send> VerifyingReader<D, R>
     }
 }
 
+#[cfg(feature = "io")]
 impl<D: Digester + Default + Send, R: Read + Unpin + Send> Read for VerifyingReader<D, R> {
     fn poll_read(
         self: Pin<&mut Self>,
@@ -203,6 +270,7 @@ impl<D: Digester + Default + Send, R: Read + Unpin + Send> Read for VerifyingRea
                 if this.digest == &digest {
                     Ok(0)
                 } else {
+                    metrics::increment(Counter::HashFailures, 1);
                     Err(std::io::Error::new(
                         std::io::ErrorKind::Other,
                         format!(
@@ -221,7 +289,325 @@ impl<D: Digester + Default + Send, R: Read + Unpin + Send> Read for VerifyingRea
     }
 }
 
-#[cfg(test)]
+/// Hash algorithms used in repository indexes, weakest first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HashAlgorithm {
+    Md5,
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl HashAlgorithm {
+    /// All algorithms, strongest first.
+    pub const ALL: [HashAlgorithm; 4] = [
+        HashAlgorithm::Sha512,
+        HashAlgorithm::Sha256,
+        HashAlgorithm::Sha1,
+        HashAlgorithm::Md5,
+    ];
+    /// Name of the checksum field in Release files.
+    pub fn release_field(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Md5 => "MD5Sum",
+            HashAlgorithm::Sha1 => "SHA1",
+            HashAlgorithm::Sha256 => "SHA256",
+            HashAlgorithm::Sha512 => "SHA512",
+        }
+    }
+    /// Name of the checksum field in Packages files.
+    pub fn packages_field(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Md5 => "MD5sum",
+            _ => self.release_field(),
+        }
+    }
+    /// Name of the checksum field in .dsc and Sources files.
+    pub fn sources_field(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Md5 => "Files",
+            HashAlgorithm::Sha1 => "Checksums-Sha1",
+            HashAlgorithm::Sha256 => "Checksums-Sha256",
+            HashAlgorithm::Sha512 => "Checksums-Sha512",
+        }
+    }
+    /// MD5 and SHA1 are only trusted when nothing stronger is available.
+    pub fn is_weak(&self) -> bool {
+        *self < HashAlgorithm::Sha256
+    }
+    fn output_size(&self) -> usize {
+        match self {
+            HashAlgorithm::Md5 => 16,
+            HashAlgorithm::Sha1 => 20,
+            HashAlgorithm::Sha256 => 32,
+            HashAlgorithm::Sha512 => 64,
+        }
+    }
+    #[cfg(feature = "io")]
+    fn hasher(&self) -> Hasher {
+        match self {
+            HashAlgorithm::Md5 => Hasher::Md5(Default::default()),
+            HashAlgorithm::Sha1 => Hasher::Sha1(Default::default()),
+            HashAlgorithm::Sha256 => Hasher::Sha256(Default::default()),
+            HashAlgorithm::Sha512 => Hasher::Sha512(Default::default()),
+        }
+    }
+}
+
+impl std::fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            HashAlgorithm::Md5 => "MD5",
+            _ => self.release_field(),
+        })
+    }
+}
+
+#[cfg(feature = "io")]
+enum Hasher {
+    Md5(md5::Md5),
+    Sha1(sha1::Sha1),
+    Sha256(sha2::Sha256),
+    Sha512(sha2::Sha512),
+}
+
+#[cfg(feature = "io")]
+impl Hasher {
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Md5(d) => digest::Update::update(d, data),
+            Hasher::Sha1(d) => digest::Update::update(d, data),
+            Hasher::Sha256(d) => digest::Update::update(d, data),
+            Hasher::Sha512(d) => digest::Update::update(d, data),
+        }
+    }
+    fn finalize(&mut self) -> Vec<u8> {
+        match self {
+            Hasher::Md5(d) => d.finalize_fixed_reset().to_vec(),
+            Hasher::Sha1(d) => d.finalize_fixed_reset().to_vec(),
+            Hasher::Sha256(d) => d.finalize_fixed_reset().to_vec(),
+            Hasher::Sha512(d) => d.finalize_fixed_reset().to_vec(),
+        }
+    }
+}
+
+/// The set of digests a file is expected to have, strongest first.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Digests {
+    inner: Vec<(HashAlgorithm, Box<[u8]>)>,
+}
+
+impl Digests {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Adds or replaces the hex-encoded digest for `algorithm`.
+    pub fn insert(&mut self, algorithm: HashAlgorithm, hex: &str) -> std::io::Result<()> {
+        let mut digest = vec![0u8; algorithm.output_size()];
+        hex::decode_to_slice(hex, &mut digest).map_err(|err| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Invalid {} digest: {}", algorithm, err),
+            )
+        })?;
+        self.inner.retain(|(a, _)| *a != algorithm);
+        self.inner.push((algorithm, digest.into_boxed_slice()));
+        self.inner.sort_by_key(|(a, _)| std::cmp::Reverse(*a));
+        Ok(())
+    }
+    pub fn get(&self, algorithm: HashAlgorithm) -> Option<&[u8]> {
+        self.inner
+            .iter()
+            .find(|(a, _)| *a == algorithm)
+            .map(|(_, d)| d.as_ref())
+    }
+    pub fn strongest(&self) -> Option<(HashAlgorithm, &[u8])> {
+        self.inner.first().map(|(a, d)| (*a, d.as_ref()))
+    }
+    pub fn sha256(&self) -> Option<Sha256> {
+        self.get(HashAlgorithm::Sha256)
+            .and_then(|d| d.try_into().ok())
+    }
+    pub fn iter(&self) -> impl Iterator<Item = (HashAlgorithm, &[u8])> {
+        self.inner.iter().map(|(a, d)| (*a, d.as_ref()))
+    }
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+    // The digests worth checking: weak ones only if nothing stronger is known.
+    #[cfg(feature = "io")]
+    fn verified(&self) -> impl Iterator<Item = (HashAlgorithm, &[u8])> {
+        let strong = self.iter().any(|(a, _)| !a.is_weak());
+        self.iter().filter(move |(a, _)| !strong || !a.is_weak())
+    }
+}
+
+impl std::fmt::Debug for Digests {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map()
+            .entries(self.iter().map(|(a, d)| (a, hex::encode(d))))
+            .finish()
+    }
+}
+
+macro_rules! digests_from {
+    ($digester:ty, $algorithm:ident) => {
+        impl From<Digest<$digester>> for Digests {
+            fn from(value: Digest<$digester>) -> Self {
+                Self {
+                    inner: vec![(
+                        HashAlgorithm::$algorithm,
+                        value.inner.to_vec().into_boxed_slice(),
+                    )],
+                }
+            }
+        }
+    };
+}
+
+digests_from!(md5::Md5, Md5);
+digests_from!(sha1::Sha1, Sha1);
+digests_from!(sha2::Sha256, Sha256);
+digests_from!(sha2::Sha512, Sha512);
+
+/// The error reported when a stream does not match one of the expected
+/// digests.
+#[derive(Debug)]
+pub struct DigestMismatch {
+    pub algorithm: HashAlgorithm,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl std::fmt::Display for DigestMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "unexpected stream {} digest `{}` (expected `{}`)",
+            self.algorithm, self.actual, self.expected
+        )
+    }
+}
+
+impl std::error::Error for DigestMismatch {}
+
+impl From<DigestMismatch> for std::io::Error {
+    fn from(err: DigestMismatch) -> Self {
+        std::io::Error::other(err)
+    }
+}
+
+/// The error reported when a stream is shorter or longer than expected.
+#[derive(Debug)]
+pub struct SizeMismatch {
+    pub expected: usize,
+    pub actual: usize,
+}
+
+impl std::fmt::Display for SizeMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "unexpected stream size {} (expected {})",
+            self.actual, self.expected
+        )
+    }
+}
+
+impl std::error::Error for SizeMismatch {}
+
+impl From<SizeMismatch> for std::io::Error {
+    fn from(err: SizeMismatch) -> Self {
+        std::io::Error::other(err)
+    }
+}
+
+/// Verifies a stream against its size and a set of [`Digests`], computing
+/// all of them in a single pass. MD5 and SHA1 are ignored when a stronger
+/// digest is available. A mismatch is reported as a [`DigestMismatch`].
+#[cfg(feature = "io")]
+#[pin_project]
+pub struct MultiVerifyingReader<R: Read + Unpin + Send> {
+    hashers: Vec<(HashAlgorithm, Hasher)>,
+    digests: Digests,
+    size: usize,
+    read: usize,
+    #[pin]
+    inner: R,
+}
+
+#[cfg(feature = "io")]
+impl<R: Read + Unpin + Send> MultiVerifyingReader<R> {
+    pub fn new(reader: R, size: usize, digests: impl Into<Digests>) -> Self {
+        let digests = digests.into();
+        Self {
+            hashers: digests.verified().map(|(a, _)| (a, a.hasher())).collect(),
+            digests,
+            size,
+            read: 0,
+            inner: reader,
+        }
+    }
+}
+
+#[cfg(feature = "io")]
+impl<R: Read + Unpin + Send> Read for MultiVerifyingReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let mut this = self.project();
+        let size = ready!(this.inner.as_mut().poll_read(cx, buf))?;
+        Poll::Ready(if size > 0 {
+            for (_, hasher) in this.hashers.iter_mut() {
+                hasher.update(&buf[0..size]);
+            }
+            *this.read += size;
+            if this.read > this.size {
+                Err(SizeMismatch {
+                    expected: *this.size,
+                    actual: *this.read,
+                }
+                .into())
+            } else {
+                Ok(size)
+            }
+        } else if this.read < this.size {
+            Err(SizeMismatch {
+                expected: *this.size,
+                actual: *this.read,
+            }
+            .into())
+        } else if this.read == this.size {
+            *this.read += 1;
+            if this.hashers.is_empty() {
+                return Poll::Ready(Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "no digest to verify the stream against",
+                )));
+            }
+            for (algorithm, hasher) in this.hashers.iter_mut() {
+                let actual = hasher.finalize();
+                let expected = this.digests.get(*algorithm).unwrap_or_default();
+                if actual != expected {
+                    metrics::increment(Counter::HashFailures, 1);
+                    return Poll::Ready(Err(DigestMismatch {
+                        algorithm: *algorithm,
+                        expected: hex::encode(expected),
+                        actual: hex::encode(actual),
+                    }
+                    .into()));
+                }
+            }
+            Ok(0)
+        } else {
+            Ok(0)
+        })
+    }
+}
+
+#[cfg(all(test, feature = "io"))]
 mod tests {
     use super::*;
     use async_std::io::{Cursor, ReadExt};
@@ -305,4 +691,44 @@ mod tests {
         assert_eq!(err.kind(), std::io::ErrorKind::Other);
         assert!(err.to_string().contains("unexpected stream size"));
     }
+
+    #[async_std::test]
+    async fn test_multi_verifying_reader() {
+        let data = b"hello world";
+        let mut digests = Digests::new();
+        digests
+            .insert(
+                HashAlgorithm::Sha512,
+                &hex::encode(sha2::Sha512::digest(data)),
+            )
+            .unwrap();
+        digests
+            .insert(HashAlgorithm::Md5, "00000000000000000000000000000000")
+            .unwrap();
+        assert_eq!(digests.strongest().unwrap().0, HashAlgorithm::Sha512);
+
+        // The weak MD5 is ignored in presence of SHA512
+        let mut buf = vec![];
+        MultiVerifyingReader::new(Cursor::new(data), data.len(), digests.clone())
+            .read_to_end(&mut buf)
+            .await
+            .unwrap();
+        assert_eq!(&buf, data);
+
+        digests
+            .insert(
+                HashAlgorithm::Sha256,
+                &hex::encode(Sha256::digest(b"incorrect")),
+            )
+            .unwrap();
+        let err = MultiVerifyingReader::new(Cursor::new(data), data.len(), digests)
+            .read_to_end(&mut buf)
+            .await
+            .unwrap_err();
+        let mismatch = err
+            .get_ref()
+            .and_then(|err| err.downcast_ref::<DigestMismatch>())
+            .unwrap();
+        assert_eq!(mismatch.algorithm, HashAlgorithm::Sha256);
+    }
 }
@@ -0,0 +1,130 @@
+//! dpkg diversions
+//!
+//! A diversion makes dpkg install a file shipped by packages to another
+//! path, e.g. `/usr/bin/ls` of coreutils to `/usr/bin/ls.distrib` while the
+//! diverting package ships its own `/usr/bin/ls`. dpkg keeps them in
+//! `/var/lib/dpkg/diversions`, three lines per diversion: the diverted path,
+//! the path it is diverted to and the diverting package, `:` for a local
+//! diversion.
+
+use {crate::control::ParseError, std::fmt};
+
+/// A diversion of a path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diversion {
+    /// The diverted path
+    pub from: String,
+    /// The path the file of the other packages is installed to
+    pub to: String,
+    /// The diverting package, or None for a local diversion
+    pub package: Option<String>,
+}
+
+impl Diversion {
+    /// Returns true if the diversion applies to the file `from` shipped by
+    /// `package`: every package but the diverting one is diverted.
+    pub fn applies_to(&self, package: &str) -> bool {
+        self.package.as_deref() != Some(package)
+    }
+}
+
+/// The diversions of a system.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Diversions {
+    pub diversions: Vec<Diversion>,
+}
+
+impl Diversions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Parses `/var/lib/dpkg/diversions`.
+    pub fn parse(src: &str) -> Result<Self, ParseError> {
+        let lines: Vec<&str> = src.lines().collect();
+        let chunks = lines.chunks(3);
+        if chunks.len() * 3 != lines.len() {
+            return Err("truncated diversions file".into());
+        }
+        let diversions = chunks
+            .map(|chunk| Diversion {
+                from: chunk[0].to_string(),
+                to: chunk[1].to_string(),
+                package: Some(chunk[2]).filter(|p| *p != ":").map(str::to_string),
+            })
+            .collect();
+        Ok(Self { diversions })
+    }
+    /// Adds a diversion of `from` to `to` by `package`, None for a local one.
+    pub fn divert(
+        mut self,
+        from: impl Into<String>,
+        to: impl Into<String>,
+        package: Option<&str>,
+    ) -> Self {
+        self.diversions.push(Diversion {
+            from: from.into(),
+            to: to.into(),
+            package: package.map(str::to_string),
+        });
+        self
+    }
+    /// The diversion of `path`, if any.
+    pub fn get(&self, path: &str) -> Option<&Diversion> {
+        self.diversions.iter().find(|d| d.from == path)
+    }
+    /// The path the file `path` of `package` is installed to.
+    pub fn target<'a>(&'a self, path: &'a str, package: &str) -> &'a str {
+        match self.get(path) {
+            Some(diversion) if diversion.applies_to(package) => &diversion.to,
+            _ => path,
+        }
+    }
+    /// Returns true if both `owner` and `package` may ship `path` because
+    /// a diversion sends the file of one of them elsewhere.
+    pub fn allows_shared(&self, path: &str, owner: &str, package: &str) -> bool {
+        self.target(path, owner) != self.target(path, package)
+    }
+}
+
+impl fmt::Display for Diversions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for d in &self.diversions {
+            writeln!(
+                f,
+                "{}\n{}\n{}",
+                d.from,
+                d.to,
+                d.package.as_deref().unwrap_or(":")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diversions() {
+        let src = "/usr/bin/ls\n/usr/bin/ls.distrib\nbusybox-ls\n/etc/issue\n/etc/issue.orig\n:\n";
+        let diversions = Diversions::parse(src).unwrap();
+        assert_eq!(diversions.get("/etc/issue").unwrap().package, None);
+        assert_eq!(
+            diversions.target("/usr/bin/ls", "coreutils"),
+            "/usr/bin/ls.distrib"
+        );
+        assert_eq!(
+            diversions.target("/usr/bin/ls", "busybox-ls"),
+            "/usr/bin/ls"
+        );
+        assert_eq!(
+            diversions.target("/usr/bin/cat", "coreutils"),
+            "/usr/bin/cat"
+        );
+        assert!(diversions.allows_shared("/usr/bin/ls", "coreutils", "busybox-ls"));
+        assert!(!diversions.allows_shared("/etc/issue", "base-files", "other"));
+        assert_eq!(diversions.to_string(), src);
+        assert!(Diversions::parse("/usr/bin/ls\n").is_err());
+    }
+}
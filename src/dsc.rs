@@ -0,0 +1,212 @@
+//! Debian source control (`.dsc`) files
+//!
+//! A `.dsc` file describes a source package and lists the files making it
+//! up (the orig tarballs and the debian tarball or diff) together with their
+//! sizes and digests. [`crate::DebRepo::fetch_source`] downloads and verifies
+//! them.
+
+use {
+    crate::{
+        control::{MutableControlStanza, ParseError},
+        digest::{Digests, HashAlgorithm},
+    },
+    std::borrow::Cow,
+};
+
+/// A file listed with its size and digests, e.g. in a `.dsc` or
+/// `.buildinfo` file.
+#[derive(Clone, Debug)]
+pub struct DscFile {
+    pub name: String,
+    pub size: usize,
+    pub digests: Digests,
+}
+
+/// A parsed `.dsc` file.
+pub struct Dsc {
+    control: MutableControlStanza,
+    files: Vec<DscFile>,
+}
+
+impl Dsc {
+    /// Parses a `.dsc` file. An OpenPGP clearsign armor is stripped without
+    /// verifying the signature.
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let control = MutableControlStanza::parse(strip_signature(text)?.into_owned())?;
+        for field in ["Source", "Version"] {
+            if control.field(field).is_none() {
+                return Err(format!("Field {} not found", field).into());
+            }
+        }
+        let files = parse_checksums(&control)?;
+        if files.is_empty() {
+            return Err("no files listed in the dsc".into());
+        }
+        Ok(Self { control, files })
+    }
+    pub fn source(&self) -> &str {
+        self.field("Source").unwrap_or_default()
+    }
+    pub fn version(&self) -> &str {
+        self.field("Version").unwrap_or_default()
+    }
+    pub fn format(&self) -> Option<&str> {
+        self.field("Format")
+    }
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.control.field(name).map(str::trim)
+    }
+    pub fn files(&self) -> &[DscFile] {
+        &self.files
+    }
+}
+
+// Collects the files listed in the Files and Checksums-* fields.
+pub(crate) fn parse_checksums(control: &MutableControlStanza) -> Result<Vec<DscFile>, ParseError> {
+    let mut files: Vec<DscFile> = vec![];
+    for algorithm in HashAlgorithm::ALL {
+        // .buildinfo files name the MD5 field Checksums-Md5
+        let list = match algorithm {
+            HashAlgorithm::Md5 => control.field("Files").or(control.field("Checksums-Md5")),
+            _ => control.field(algorithm.sources_field()),
+        };
+        let Some(list) = list else {
+            continue;
+        };
+        for line in list.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            let mut entry = line.split_ascii_whitespace();
+            let (Some(digest), Some(size), Some(name), None) =
+                (entry.next(), entry.next(), entry.next(), entry.next())
+            else {
+                let field = algorithm.sources_field();
+                return Err(format!("invalid {} entry {:?}", field, line).into());
+            };
+            let size = crate::parse_size(size.as_bytes())
+                .map_err(|err| ParseError::from(format!("{}: {}", name, err)))?;
+            let pos = match files.iter().position(|f| f.name == name) {
+                Some(pos) => pos,
+                None => {
+                    files.push(DscFile {
+                        name: name.to_string(),
+                        size,
+                        digests: Digests::new(),
+                    });
+                    files.len() - 1
+                }
+            };
+            let file = &mut files[pos];
+            if file.size != size {
+                return Err(format!("conflicting sizes for {}", name).into());
+            }
+            file.digests
+                .insert(algorithm, digest)
+                .map_err(|err| ParseError::from(format!("{}: {}", name, err)))?;
+        }
+    }
+    Ok(files)
+}
+
+// Returns the signed text of an OpenPGP clearsigned message, or `text` as is
+// if it is not signed.
+pub(crate) fn strip_signature(text: &str) -> Result<Cow<'_, str>, ParseError> {
+    let mut lines = text.lines();
+    if lines.next().map(str::trim_end) != Some("-----BEGIN PGP SIGNED MESSAGE-----") {
+        return Ok(Cow::Borrowed(text));
+    }
+    // Armor headers such as "Hash: SHA512" end with an empty line.
+    lines.by_ref().find(|line| line.trim_end().is_empty());
+    let mut body = String::with_capacity(text.len());
+    for line in lines {
+        if line.trim_end() == "-----BEGIN PGP SIGNATURE-----" {
+            return Ok(Cow::Owned(body));
+        }
+        body.push_str(line.strip_prefix("- ").unwrap_or(line));
+        body.push('\n');
+    }
+    Err("unterminated clearsigned message".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_dsc() {
+        let dsc = Dsc::parse(
+            "-----BEGIN PGP SIGNED MESSAGE-----
+Hash: SHA512
+
+Format: 3.0 (quilt)
+Source: hello
+Binary: hello
+Version: 2.10-3
+Checksums-Sha256:
+ 31e066137a962676e89f69d1b65382de95a7ef7d914b8cb956f41ea72e0f516b 725946 hello_2.10.orig.tar.gz
+ d9d3e7a6d4dd2b3d3a35a4e4c5b9a8ba1e7d04c5e9e16fcb08f1f2b4b1a1d3e2 12688 hello_2.10-3.debian.tar.xz
+Files:
+ 6cd0ffea3884a4e79330338dcc2987d6 725946 hello_2.10.orig.tar.gz
+ 28a0b7c6d3d45c1c3a24eaaeb8b8b0c0 12688 hello_2.10-3.debian.tar.xz
+
+-----BEGIN PGP SIGNATURE-----
+
+iQIzBAEBCgAdFiEE
+-----END PGP SIGNATURE-----
+",
+        )
+        .unwrap();
+        assert_eq!(dsc.source(), "hello");
+        assert_eq!(dsc.version(), "2.10-3");
+        assert_eq!(dsc.format(), Some("3.0 (quilt)"));
+        let names: Vec<_> = dsc.files().iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["hello_2.10.orig.tar.gz", "hello_2.10-3.debian.tar.xz"]
+        );
+        assert_eq!(dsc.files()[1].size, 12688);
+        let digests = &dsc.files()[0].digests;
+        assert!(digests.sha256().is_some());
+        assert!(digests.get(HashAlgorithm::Md5).is_some());
+        assert!(Dsc::parse("Source: hello\nVersion: 1\n").is_err());
+    }
+
+    #[cfg(feature = "io")]
+    #[async_std::test]
+    async fn test_fetch_source() {
+        use sha2::{Digest, Sha256};
+        let dir = tempfile::tempdir().unwrap();
+        let pool = dir.path().join("repo/pool/main/h/hello");
+        std::fs::create_dir_all(&pool).unwrap();
+        std::fs::create_dir_all(dir.path().join("out")).unwrap();
+        std::fs::write(pool.join("hello_1.0.tar.xz"), b"tarball").unwrap();
+        let dsc = |content: &[u8]| {
+            Dsc::parse(&format!(
+                "Source: hello\nVersion: 1.0\nChecksums-Sha256:\n {} {} hello_1.0.tar.xz\n",
+                hex::encode(Sha256::digest(content)),
+                content.len()
+            ))
+            .unwrap()
+        };
+        let repo: crate::DebRepo = crate::FSDebRepo::new(dir.path().join("repo"))
+            .await
+            .unwrap()
+            .into();
+        let out = dir.path().join("out");
+        let paths = repo
+            .fetch_source("pool/main/h/hello", &dsc(b"tarball"), &out)
+            .await
+            .unwrap();
+        assert_eq!(paths.len(), 1);
+        assert_eq!(
+            std::fs::read(out.join("hello_1.0.tar.xz")).unwrap(),
+            b"tarball"
+        );
+
+        std::fs::remove_file(out.join("hello_1.0.tar.xz")).unwrap();
+        let err = repo
+            .fetch_source("pool/main/h/hello", &dsc(b"tarbal!"), &out)
+            .await
+            .unwrap_err();
+        assert!(err.is_verification(), "{}", err);
+        assert_eq!(std::fs::read_dir(&out).unwrap().count(), 0);
+    }
+}
@@ -0,0 +1,208 @@
+//! Crate-wide error type
+//!
+//! Streams keep reporting [`io::Error`], with the typed cause attached as
+//! the inner error. [`Error::from`] recovers the cause, so a caller can tell
+//! a missing file from a digest mismatch or a bad signature.
+
+use {
+    crate::{
+        control::ParseError,
+        digest::{DigestMismatch, SizeMismatch},
+    },
+    std::{fmt, io},
+};
+
+#[cfg(feature = "io")]
+use crate::{failover::FailoverError, httprepo::HttpStatusError, release::StaleRelease};
+
+/// Errors returned by the repository APIs.
+#[derive(Debug)]
+pub enum Error {
+    /// The file does not exist in the repository
+    NotFound(String),
+    /// The file is not in the download cache and the network is off, see
+    /// `OfflineMode`
+    NotCached(String),
+    /// The file could not be fetched (connection failure, HTTP error, ...)
+    Transport(io::Error),
+    /// The file does not match the digest from the index
+    Digest(DigestMismatch),
+    /// The file does not match the size from the index
+    Size(SizeMismatch),
+    /// The Release signature could not be verified
+    Signature(String),
+    /// The Release file is expired or dated in the future
+    #[cfg(feature = "io")]
+    Stale(StaleRelease),
+    /// Malformed control data
+    Parse(ParseError),
+    /// The requirements could not be satisfied
+    Solver(String),
+    /// The plan removes these essential or protected packages, see
+    /// `Universe::set_force_remove_essential`
+    EssentialRemoval(Vec<String>),
+    /// Any other I/O error
+    Io(io::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NotFound(path) => write!(f, "{} not found", path),
+            Error::NotCached(path) => write!(f, "{} not cached in offline mode", path),
+            Error::Transport(err) => write!(f, "transport error: {}", err),
+            Error::Digest(err) => err.fmt(f),
+            Error::Size(err) => err.fmt(f),
+            Error::Signature(msg) => write!(f, "signature verification failed: {}", msg),
+            #[cfg(feature = "io")]
+            Error::Stale(err) => err.fmt(f),
+            Error::Parse(err) => err.fmt(f),
+            Error::Solver(msg) => write!(f, "no solution: {}", msg),
+            Error::EssentialRemoval(names) => write!(
+                f,
+                "refusing to remove essential packages: {}",
+                names.join(", ")
+            ),
+            Error::Io(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Transport(err) | Error::Io(err) => Some(err),
+            Error::Digest(err) => Some(err),
+            Error::Size(err) => Some(err),
+            #[cfg(feature = "io")]
+            Error::Stale(err) => Some(err),
+            Error::Parse(err) => Some(err),
+            Error::NotFound(_)
+            | Error::NotCached(_)
+            | Error::Signature(_)
+            | Error::Solver(_)
+            | Error::EssentialRemoval(_) => None,
+        }
+    }
+}
+
+impl Error {
+    /// True for errors caused by a file not matching the repository indexes.
+    pub fn is_verification(&self) -> bool {
+        matches!(
+            self,
+            Error::Digest(_) | Error::Size(_) | Error::Signature(_)
+        )
+    }
+}
+
+macro_rules! downcast {
+    ($inner:ident, $ty:ty, $variant:path) => {
+        let $inner = match $inner.downcast::<$ty>() {
+            Ok(err) => return $variant(*err),
+            Err(inner) => inner,
+        };
+    };
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        let kind = err.kind();
+        let err = match err.into_inner() {
+            Some(inner) => {
+                let inner = match inner.downcast::<Error>() {
+                    Ok(err) => return *err,
+                    Err(inner) => inner,
+                };
+                downcast!(inner, DigestMismatch, Error::Digest);
+                downcast!(inner, SizeMismatch, Error::Size);
+                #[cfg(feature = "io")]
+                downcast!(inner, StaleRelease, Error::Stale);
+                downcast!(inner, ParseError, Error::Parse);
+                #[cfg(feature = "io")]
+                if let Some(path) = inner
+                    .downcast_ref::<FailoverError>()
+                    .and_then(|err| err.attempts.last())
+                    .and_then(|attempt| attempt.error.get_ref())
+                    .and_then(|err| match err.downcast_ref::<Error>() {
+                        Some(Error::NotCached(path)) => Some(path),
+                        _ => None,
+                    })
+                {
+                    return Error::NotCached(path.clone());
+                }
+                #[cfg(feature = "io")]
+                if inner.is::<HttpStatusError>() || inner.is::<FailoverError>() {
+                    if kind == io::ErrorKind::NotFound {
+                        return Error::NotFound(inner.to_string());
+                    }
+                    return Error::Transport(io::Error::new(kind, inner));
+                }
+                io::Error::new(kind, inner)
+            }
+            None => io::Error::from(kind),
+        };
+        use io::ErrorKind::*;
+        match kind {
+            NotFound => Error::NotFound(err.to_string()),
+            TimedOut | ConnectionRefused | ConnectionReset | ConnectionAborted | NotConnected
+            | PermissionDenied | BrokenPipe => Error::Transport(err),
+            _ => Error::Io(err),
+        }
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::Transport(err) | Error::Io(err) => err,
+            Error::NotFound(_) | Error::NotCached(_) => {
+                io::Error::new(io::ErrorKind::NotFound, err)
+            }
+            err => io::Error::new(io::ErrorKind::InvalidData, err),
+        }
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(err: ParseError) -> Self {
+        Error::Parse(err)
+    }
+}
+
+#[cfg(feature = "io")]
+impl From<StaleRelease> for Error {
+    fn from(err: StaleRelease) -> Self {
+        Error::Stale(err)
+    }
+}
+
+#[cfg(feature = "io")]
+impl From<gpgme::Error> for Error {
+    fn from(err: gpgme::Error) -> Self {
+        Error::Signature(err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let err: io::Error = DigestMismatch {
+            algorithm: crate::digest::HashAlgorithm::Sha256,
+            expected: "00".into(),
+            actual: "01".into(),
+        }
+        .into();
+        assert!(matches!(Error::from(err), Error::Digest(_)));
+        let err = io::Error::new(io::ErrorKind::NotFound, "pool/main/a.deb");
+        assert!(matches!(Error::from(err), Error::NotFound(_)));
+        // Round trip through io::Error keeps the variant
+        let err: io::Error = Error::Signature("bad key".into()).into();
+        assert!(matches!(Error::from(err), Error::Signature(_)));
+    }
+}
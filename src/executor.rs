@@ -0,0 +1,423 @@
+//! Running plans with dpkg
+//!
+//! An [`Executor`] carries out the [`PlanPhase`]s of a plan by running
+//! `dpkg --remove`, `--unpack` and `--configure` in a target root, through
+//! a [`Target`] such as [`Chroot`] or [`Nspawn`]. The packages are staged
+//! in the root first. Each step completed is recorded in a state file in
+//! the root, so that after a failure, e.g. of a maintainer script, running
+//! the same plan again resumes at the step that failed:
+//!
+//! ```ignore
+//! let plan = universe.plan(&solution, &requested)?;
+//! // the downloaded .deb files, by package name
+//! let debs: HashMap<String, PathBuf> = download(&universe, &solution).await?;
+//! Executor::new(Chroot::new("/srv/rootfs")).execute(&plan, &debs).await?;
+//! ```
+
+use {
+    crate::{
+        error::{Error, Result},
+        foreign::Emulator,
+        plan::{Plan, PlanPhase},
+    },
+    async_std::{
+        fs, io,
+        path::{Path, PathBuf},
+        task,
+    },
+    async_trait::async_trait,
+    sha2::{Digest, Sha256},
+    std::collections::HashMap,
+};
+
+/// The directory of the target root the packages are staged in.
+pub const STAGING_DIR: &str = "var/cache/debrepo/archives";
+
+/// The state file of the executor in the target root.
+pub const STATE_FILE: &str = "var/lib/debrepo/executor.state";
+
+/// Runs commands in a target root.
+#[async_trait]
+pub trait Target: Send + Sync {
+    /// The target root, as seen from the host.
+    fn root(&self) -> &Path;
+    /// Runs `argv` in the target root.
+    async fn run(&self, argv: &[String]) -> io::Result<()>;
+}
+
+// Runs `argv` on the host, noninteractively.
+async fn spawn(argv: Vec<String>) -> io::Result<()> {
+    let command = argv.join(" ");
+    let status = task::spawn_blocking(move || {
+        std::process::Command::new(&argv[0])
+            .args(&argv[1..])
+            .env("DEBIAN_FRONTEND", "noninteractive")
+            .status()
+    })
+    .await?;
+    match status.success() {
+        true => Ok(()),
+        false => Err(io::Error::other(format!("{} failed: {}", command, status))),
+    }
+}
+
+/// A [`Target`] running the commands chrooted into the root, optionally
+/// through a wrapper such as `fakechroot fakeroot`.
+#[derive(Clone, Debug)]
+pub struct Chroot {
+    root: PathBuf,
+    wrapper: Vec<String>,
+}
+
+impl Chroot {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            wrapper: vec![],
+        }
+    }
+    /// Runs `chroot` through the command `wrapper`, e.g.
+    /// `["fakechroot", "fakeroot"]`.
+    pub fn wrapper<I: IntoIterator<Item = impl Into<String>>>(mut self, wrapper: I) -> Self {
+        self.wrapper = wrapper.into_iter().map(|w| w.into()).collect();
+        self
+    }
+}
+
+#[async_trait]
+impl Target for Chroot {
+    fn root(&self) -> &Path {
+        &self.root
+    }
+    async fn run(&self, argv: &[String]) -> io::Result<()> {
+        let mut command = self.wrapper.clone();
+        command.push("chroot".to_string());
+        command.push(self.root.to_string_lossy().into_owned());
+        command.extend(argv.iter().cloned());
+        spawn(command).await
+    }
+}
+
+/// A [`Target`] running the commands in a `systemd-nspawn` container of
+/// the root.
+#[derive(Clone, Debug)]
+pub struct Nspawn {
+    root: PathBuf,
+}
+
+impl Nspawn {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+#[async_trait]
+impl Target for Nspawn {
+    fn root(&self) -> &Path {
+        &self.root
+    }
+    async fn run(&self, argv: &[String]) -> io::Result<()> {
+        let mut command = vec![
+            "systemd-nspawn".to_string(),
+            "--quiet".to_string(),
+            "--directory".to_string(),
+            self.root.to_string_lossy().into_owned(),
+            "--setenv=DEBIAN_FRONTEND=noninteractive".to_string(),
+        ];
+        command.extend(argv.iter().cloned());
+        spawn(command).await
+    }
+}
+
+/// Carries out plans with dpkg in a [`Target`].
+pub struct Executor<T: Target> {
+    target: T,
+    dpkg: Vec<String>,
+    emulator: Option<(String, Box<dyn Emulator>)>,
+}
+
+// The dpkg steps of `phases`, with the package names.
+fn steps(phases: &[PlanPhase]) -> impl Iterator<Item = (&'static str, &[String])> {
+    phases.iter().map(|phase| match phase {
+        PlanPhase::Remove(names) => ("--remove", names.as_slice()),
+        PlanPhase::Unpack(names) => ("--unpack", names.as_slice()),
+        PlanPhase::Configure(names) => ("--configure", names.as_slice()),
+    })
+}
+
+// Identifies the plan in the state file, by its steps, the versions of the
+// packages and the files unpacked.
+fn fingerprint(plan: &Plan, debs: &HashMap<String, PathBuf>) -> String {
+    let versions: HashMap<&str, &str> = plan
+        .items
+        .iter()
+        .map(|item| (item.name.as_str(), item.version.as_str()))
+        .collect();
+    let mut hasher = Sha256::new();
+    for (action, names) in steps(&plan.phases) {
+        hasher.update(action);
+        for name in names {
+            let version = versions.get(name.as_str()).copied().unwrap_or_default();
+            let deb = match action {
+                "--unpack" => debs.get(name).map(|path| path.to_string_lossy()),
+                _ => None,
+            };
+            hasher.update(format!(" {}={} {}", name, version, deb.unwrap_or_default()));
+        }
+        hasher.update("\n");
+    }
+    hex::encode(hasher.finalize())
+}
+
+impl<T: Target> Executor<T> {
+    pub fn new(target: T) -> Self {
+        Self {
+            target,
+            dpkg: vec!["dpkg".to_string()],
+            emulator: None,
+        }
+    }
+    /// Runs `dpkg` as `command`, e.g. `["dpkg", "--force-confold"]`.
+    pub fn dpkg<I: IntoIterator<Item = impl Into<String>>>(mut self, command: I) -> Self {
+        self.dpkg = command.into_iter().map(|c| c.into()).collect();
+        self
+    }
+    /// Runs dpkg and the maintainer scripts of a root of the foreign
+    /// architecture `arch` through `emulator`, installed in the root while
+    /// the plan is executed.
+    pub fn emulator(mut self, arch: impl Into<String>, emulator: impl Emulator + 'static) -> Self {
+        self.emulator = Some((arch.into(), Box::new(emulator)));
+        self
+    }
+    // The steps of the plan `fingerprint` completed in an earlier run.
+    async fn completed(&self, fingerprint: &str) -> usize {
+        let state = fs::read_to_string(self.target.root().join(STATE_FILE))
+            .await
+            .unwrap_or_default();
+        match state.split_once('\n') {
+            Some((plan, done)) if plan == fingerprint => done.trim().parse().unwrap_or(0),
+            _ => 0,
+        }
+    }
+    async fn record(&self, fingerprint: &str, done: usize) -> io::Result<()> {
+        let path = self.target.root().join(STATE_FILE);
+        let dir = path.parent().unwrap();
+        fs::create_dir_all(dir).await?;
+        let tmp = tempfile::NamedTempFile::new_in(dir)?;
+        fs::write(tmp.path(), format!("{}\n{}\n", fingerprint, done)).await?;
+        tmp.persist(&path)?;
+        Ok(())
+    }
+    // Copies the .deb files of `names` into the root, returning their paths
+    // in the root.
+    async fn stage(
+        &self,
+        names: &[String],
+        debs: &HashMap<String, PathBuf>,
+    ) -> Result<Vec<String>> {
+        let dir = self.target.root().join(STAGING_DIR);
+        fs::create_dir_all(&dir).await?;
+        let mut staged = Vec::with_capacity(names.len());
+        for name in names {
+            let src = debs
+                .get(name)
+                .ok_or_else(|| Error::NotFound(format!("package file of {}", name)))?;
+            let file = src.file_name().ok_or_else(|| {
+                io::Error::other(format!("invalid package file {}", src.display()))
+            })?;
+            let dst = dir.join(file);
+            if fs::hard_link(src, &dst).await.is_err() {
+                fs::copy(src, &dst).await?;
+            }
+            staged.push(format!("/{}/{}", STAGING_DIR, file.to_string_lossy()));
+        }
+        Ok(staged)
+    }
+    /// Runs the phases of `plan`, taking the package files from `debs`, by
+    /// plan item name. Resumes an earlier run of the same plan at the step
+    /// that failed. Returns the number of steps run.
+    pub async fn execute(&self, plan: &Plan, debs: &HashMap<String, PathBuf>) -> Result<usize> {
+        let fingerprint = fingerprint(plan, debs);
+        let done = self.completed(&fingerprint).await;
+        let total = plan.phases.len();
+        if done >= total {
+            return Ok(0);
+        } else if done > 0 {
+            tracing::info!("resuming the plan at step {}", done + 1);
+        }
+        if let Some((arch, emulator)) = &self.emulator {
+            emulator.install(self.target.root(), arch).await?;
+        }
+        let mut result = Ok(total - done);
+        for (step, (action, names)) in steps(&plan.phases).enumerate().skip(done) {
+            let packages = names.join(" ");
+            tracing::info!(packages, "step {} of {}: dpkg {}", step + 1, total, action);
+            if let Err(err) = self.run_step(action, names, debs).await {
+                tracing::error!("step {} of {} failed: {}", step + 1, total, err);
+                result = Err(err);
+                break;
+            }
+            if let Err(err) = self.record(&fingerprint, step + 1).await {
+                result = Err(err.into());
+                break;
+            }
+        }
+        if let Some((arch, emulator)) = &self.emulator {
+            // a failed cleanup must not hide the error of a step
+            match emulator.remove(self.target.root(), arch).await {
+                Err(err) if result.is_ok() => result = Err(err.into()),
+                Err(err) => tracing::warn!("failed to remove the {} emulator: {}", arch, err),
+                Ok(()) => {}
+            }
+        }
+        if result.is_ok() {
+            fs::remove_dir_all(self.target.root().join(STAGING_DIR))
+                .await
+                .ok();
+        }
+        result
+    }
+    async fn run_step(
+        &self,
+        action: &str,
+        names: &[String],
+        debs: &HashMap<String, PathBuf>,
+    ) -> Result<()> {
+        let mut argv = self.dpkg.clone();
+        argv.push(action.to_string());
+        match action {
+            "--unpack" => argv.extend(self.stage(names, debs).await?),
+            _ => argv.extend(names.iter().cloned()),
+        }
+        Ok(self.target.run(&argv).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::sync::Mutex};
+
+    // Records the commands, failing the first one containing `fail`.
+    struct Recorder {
+        root: PathBuf,
+        fail: Mutex<Option<String>>,
+        commands: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl Target for Recorder {
+        fn root(&self) -> &Path {
+            &self.root
+        }
+        async fn run(&self, argv: &[String]) -> io::Result<()> {
+            let command = argv.join(" ");
+            let mut fail = self.fail.lock().unwrap();
+            if fail
+                .as_ref()
+                .is_some_and(|fail| command.contains(fail.as_str()))
+            {
+                *fail = None;
+                return Err(io::Error::other("postinst failed"));
+            }
+            self.commands.lock().unwrap().push(command);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let item = |name: &str, version: &str| crate::PlanItem {
+            name: name.to_string(),
+            version: version.to_string(),
+            action: crate::PlanAction::Install,
+            requested: true,
+            size: 0,
+            installed_size: 0,
+            replaced_size: 0,
+        };
+        let mut plan = Plan {
+            items: vec![item("app", "1.0")],
+            phases: vec![
+                PlanPhase::Unpack(vec!["app".to_string()]),
+                PlanPhase::Configure(vec!["app".to_string()]),
+            ],
+            ..Plan::default()
+        };
+        let mut debs = HashMap::new();
+        debs.insert("app".to_string(), PathBuf::from("/tmp/app_1.0_amd64.deb"));
+        let first = fingerprint(&plan, &debs);
+        assert_eq!(fingerprint(&plan, &debs), first);
+        // another file of the same version is another plan
+        debs.insert(
+            "app".to_string(),
+            PathBuf::from("/tmp/app_1.0+b1_amd64.deb"),
+        );
+        let rebuilt = fingerprint(&plan, &debs);
+        assert_ne!(rebuilt, first);
+        plan.items = vec![item("app", "1.1")];
+        assert_ne!(fingerprint(&plan, &debs), rebuilt);
+    }
+
+    #[async_std::test]
+    async fn test_execute() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        let mut debs = HashMap::new();
+        for name in ["dpkg", "libapp", "app"] {
+            let path = dir.path().join(format!("{}_1.0_amd64.deb", name));
+            std::fs::write(&path, name).unwrap();
+            debs.insert(name.to_string(), PathBuf::from(path));
+        }
+        let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        let plan = Plan {
+            phases: vec![
+                PlanPhase::Remove(names(&["oldapp"])),
+                PlanPhase::Unpack(names(&["dpkg"])),
+                PlanPhase::Configure(names(&["dpkg"])),
+                PlanPhase::Unpack(names(&["libapp", "app"])),
+                PlanPhase::Configure(names(&["libapp", "app"])),
+            ],
+            ..Plan::default()
+        };
+        std::fs::write(dir.path().join("qemu-aarch64-static"), "qemu").unwrap();
+        let executor = Executor::new(Recorder {
+            root: root.clone().into(),
+            fail: Mutex::new(Some("--configure libapp".to_string())),
+            commands: Mutex::new(vec![]),
+        })
+        .emulator("arm64", crate::QemuUser::new(dir.path()));
+        assert!(executor.execute(&plan, &debs).await.is_err());
+        // the emulator is removed after a failure too
+        assert!(!root.join("usr/bin/qemu-aarch64-static").exists());
+        assert!(root.join(STAGING_DIR).join("app_1.0_amd64.deb").exists());
+        // the failed step is run again
+        assert_eq!(executor.execute(&plan, &debs).await.unwrap(), 1);
+        assert_eq!(executor.execute(&plan, &debs).await.unwrap(), 0);
+        let archives = format!("/{}", STAGING_DIR);
+        assert_eq!(
+            *executor.target.commands.lock().unwrap(),
+            [
+                "dpkg --remove oldapp".to_string(),
+                format!("dpkg --unpack {}/dpkg_1.0_amd64.deb", archives),
+                "dpkg --configure dpkg".to_string(),
+                format!(
+                    "dpkg --unpack {0}/libapp_1.0_amd64.deb {0}/app_1.0_amd64.deb",
+                    archives
+                ),
+                "dpkg --configure libapp app".to_string(),
+            ]
+        );
+        assert!(!root.join(STAGING_DIR).exists());
+
+        // a missing package file fails before running dpkg
+        debs.remove("app");
+        let other = Plan {
+            phases: vec![PlanPhase::Unpack(names(&["app"]))],
+            ..Plan::default()
+        };
+        assert!(matches!(
+            executor.execute(&other, &debs).await,
+            Err(Error::NotFound(_))
+        ));
+    }
+}
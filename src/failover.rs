@@ -0,0 +1,263 @@
+//! Retries and mirror failover
+//!
+//! [`Failover`] is a [`Transport`] over an ordered list of mirrors. Transient
+//! errors are retried on the same mirror with jittered exponential backoff;
+//! missing files and exhausted retries move on to the next mirror. When every
+//! mirror fails, the returned error carries a [`FailoverError`] describing all
+//! attempts, available through [`io::Error::get_ref`].
+
+use {
+    crate::{
+        httprepo::HttpStatusError,
+        transport::{Transport, TransportResponse},
+    },
+    async_std::{io, task},
+    async_trait::async_trait,
+    std::{
+        fmt,
+        hash::{BuildHasher, Hasher},
+        ops::Range,
+        sync::Arc,
+        time::Duration,
+    },
+};
+
+/// Controls how often and how fast failed requests are retried.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// Number of attempts per mirror, including the first one
+    pub attempts: u32,
+    /// Delay before the first retry
+    pub base_delay: Duration,
+    /// Upper bound for the delay between retries
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            base_delay: Duration::from_millis(250),
+            max_delay: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that never retries.
+    pub fn none() -> Self {
+        Self {
+            attempts: 1,
+            ..Self::default()
+        }
+    }
+    // Full jitter: a random delay up to base_delay * 2^retry, capped.
+    fn delay(&self, retry: u32) -> Duration {
+        let cap = self
+            .base_delay
+            .saturating_mul(1u32.checked_shl(retry).unwrap_or(u32::MAX))
+            .min(self.max_delay);
+        let random = std::collections::hash_map::RandomState::new()
+            .build_hasher()
+            .finish();
+        cap.mul_f64((random >> 11) as f64 / (1u64 << 53) as f64)
+    }
+}
+
+/// Returns true for errors that are worth retrying on the same mirror.
+pub fn is_transient(err: &io::Error) -> bool {
+    use io::ErrorKind::*;
+    matches!(
+        err.kind(),
+        TimedOut
+            | Interrupted
+            | ConnectionReset
+            | ConnectionAborted
+            | ConnectionRefused
+            | NotConnected
+            | BrokenPipe
+            | UnexpectedEof
+            | WouldBlock
+    ) || err
+        .get_ref()
+        .and_then(|err| err.downcast_ref::<HttpStatusError>())
+        .is_some_and(|err| err.is_transient())
+}
+
+/// A single failed attempt.
+#[derive(Debug)]
+pub struct Attempt {
+    /// Index of the mirror in the failover list
+    pub mirror: usize,
+    /// Attempt number on that mirror, starting with 1
+    pub attempt: u32,
+    pub error: io::Error,
+}
+
+/// Error returned when every mirror failed.
+#[derive(Debug)]
+pub struct FailoverError {
+    pub path: String,
+    pub attempts: Vec<Attempt>,
+}
+
+impl fmt::Display for FailoverError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "failed to fetch {} after {} attempts",
+            self.path,
+            self.attempts.len()
+        )?;
+        for a in &self.attempts {
+            write!(
+                f,
+                "; mirror {} attempt {}: {}",
+                a.mirror, a.attempt, a.error
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for FailoverError {}
+
+/// A transport trying an ordered list of mirrors.
+#[derive(Clone)]
+pub struct Failover {
+    mirrors: Vec<Arc<dyn Transport>>,
+    policy: RetryPolicy,
+}
+
+impl Failover {
+    pub fn new() -> Self {
+        Self {
+            mirrors: vec![],
+            policy: RetryPolicy::default(),
+        }
+    }
+    /// Appends a mirror to the list.
+    pub fn mirror<T: Transport + 'static>(mut self, mirror: T) -> Self {
+        self.mirrors.push(Arc::new(mirror));
+        self
+    }
+    pub fn policy(mut self, policy: RetryPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+}
+
+impl Default for Failover {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Transport for Failover {
+    async fn get(&self, path: &str, range: Option<Range<u64>>) -> io::Result<TransportResponse> {
+        let mut attempts = vec![];
+        for (mirror, transport) in self.mirrors.iter().enumerate() {
+            for attempt in 1..=self.policy.attempts.max(1) {
+                if attempt > 1 {
+                    task::sleep(self.policy.delay(attempt - 2)).await;
+                }
+                match transport.get(path, range.clone()).await {
+                    Ok(rsp) => return Ok(rsp),
+                    Err(error) => {
+                        let retry = is_transient(&error);
+                        attempts.push(Attempt {
+                            mirror,
+                            attempt,
+                            error,
+                        });
+                        if !retry {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        // Report the last error kind so that e.g. NotFound is still
+        // recognizable by the callers.
+        let kind = attempts
+            .last()
+            .map_or(io::ErrorKind::NotFound, |a| a.error.kind());
+        Err(io::Error::new(
+            kind,
+            FailoverError {
+                path: path.to_string(),
+                attempts,
+            },
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct Flaky {
+        failures: AtomicUsize,
+        kind: io::ErrorKind,
+    }
+
+    #[async_trait]
+    impl Transport for Flaky {
+        async fn get(&self, _: &str, _: Option<Range<u64>>) -> io::Result<TransportResponse> {
+            if self.failures.load(Ordering::SeqCst) > 0 {
+                self.failures.fetch_sub(1, Ordering::SeqCst);
+                Err(io::Error::new(self.kind, "flaky"))
+            } else {
+                Ok(TransportResponse::new(Box::pin(io::empty()), Some(0)))
+            }
+        }
+    }
+
+    fn flaky(failures: usize, kind: io::ErrorKind) -> Flaky {
+        Flaky {
+            failures: AtomicUsize::new(failures),
+            kind,
+        }
+    }
+
+    fn fast(attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            attempts,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(2),
+        }
+    }
+
+    #[async_std::test]
+    async fn test_retry() {
+        let transport = Failover::new()
+            .mirror(flaky(2, io::ErrorKind::TimedOut))
+            .policy(fast(3));
+        assert!(transport.get("file", None).await.is_ok());
+    }
+
+    #[async_std::test]
+    async fn test_failover() {
+        let transport = Failover::new()
+            .mirror(flaky(usize::MAX, io::ErrorKind::NotFound))
+            .mirror(flaky(1, io::ErrorKind::TimedOut))
+            .policy(fast(2));
+        assert!(transport.get("file", None).await.is_ok());
+
+        let transport = Failover::new()
+            .mirror(flaky(usize::MAX, io::ErrorKind::NotFound))
+            .mirror(flaky(usize::MAX, io::ErrorKind::TimedOut))
+            .policy(fast(2));
+        let err = transport.get("file", None).await.err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        let err = err
+            .get_ref()
+            .unwrap()
+            .downcast_ref::<FailoverError>()
+            .unwrap();
+        let attempts: Vec<_> = err.attempts.iter().map(|a| (a.mirror, a.attempt)).collect();
+        assert_eq!(attempts, [(0, 1), (1, 1), (1, 2)]);
+    }
+}
@@ -0,0 +1,215 @@
+//! Package queries
+//!
+//! A [`PackageFilter`] selects packages by name, priority, section or
+//! debtags and is combined with `|`, `&` and `!`:
+//!
+//! ```ignore
+//! let base = packages.filter(Priority::Required | Priority::Important);
+//! let libs = packages.filter(Section::starts_with("libs") & !Tag::contains("role::program"));
+//! ```
+
+use {
+    crate::{
+        packages::{Package, Priority},
+        policy::glob,
+    },
+    std::ops::{BitAnd, BitOr, Not},
+};
+
+/// A predicate over packages.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PackageFilter {
+    /// Packages with `Essential: yes`
+    Essential,
+    /// Packages with `Protected: yes` or `Important: yes`
+    Protected,
+    /// Packages whose name matches this glob, e.g. `*-doc`
+    Name(String),
+    /// Packages with exactly this priority
+    Priority(Priority),
+    /// Packages in this section
+    Section(String),
+    /// Packages in a section starting with this prefix
+    SectionPrefix(String),
+    /// Packages with this debtag
+    Tag(String),
+    /// Packages with a debtag of this facet, e.g. `role`
+    Facet(String),
+    Any(Vec<PackageFilter>),
+    All(Vec<PackageFilter>),
+    Not(Box<PackageFilter>),
+}
+
+impl PackageFilter {
+    /// Packages whose name matches the glob `pattern`, e.g. `*-doc`.
+    pub fn name(pattern: impl Into<String>) -> Self {
+        Self::Name(pattern.into())
+    }
+    /// Returns true if `package` is selected by the filter.
+    pub fn matches(&self, package: &Package<'_>) -> bool {
+        // The archive area prefix is not part of the section name.
+        let section = || {
+            package
+                .section()
+                .map(|s| s.rsplit_once('/').map_or(s, |(_, s)| s))
+        };
+        match self {
+            Self::Essential => package.essential(),
+            Self::Protected => package.protected(),
+            Self::Name(pattern) => glob(pattern, package.name()),
+            Self::Priority(priority) => package.priority() == *priority,
+            Self::Section(name) => section().is_some_and(|s| s == name),
+            Self::SectionPrefix(prefix) => {
+                section().is_some_and(|s| s.starts_with(prefix.as_str()))
+            }
+            Self::Tag(tag) => package.has_tag(tag),
+            Self::Facet(facet) => package
+                .tags()
+                .any(|tag| tag.split_once("::").is_some_and(|(f, _)| f == facet)),
+            Self::Any(filters) => filters.iter().any(|f| f.matches(package)),
+            Self::All(filters) => filters.iter().all(|f| f.matches(package)),
+            Self::Not(filter) => !filter.matches(package),
+        }
+    }
+}
+
+/// Section filters.
+pub struct Section;
+
+impl Section {
+    pub fn is(name: impl Into<String>) -> PackageFilter {
+        PackageFilter::Section(name.into())
+    }
+    pub fn starts_with(prefix: impl Into<String>) -> PackageFilter {
+        PackageFilter::SectionPrefix(prefix.into())
+    }
+}
+
+/// Debtags filters.
+pub struct Tag;
+
+impl Tag {
+    pub fn contains(tag: impl Into<String>) -> PackageFilter {
+        PackageFilter::Tag(tag.into())
+    }
+    pub fn facet(facet: impl Into<String>) -> PackageFilter {
+        PackageFilter::Facet(facet.into())
+    }
+}
+
+impl From<Priority> for PackageFilter {
+    fn from(priority: Priority) -> Self {
+        PackageFilter::Priority(priority)
+    }
+}
+
+impl<F: Into<PackageFilter>> BitOr<F> for PackageFilter {
+    type Output = PackageFilter;
+    fn bitor(self, rhs: F) -> PackageFilter {
+        match (self, rhs.into()) {
+            (PackageFilter::Any(mut lhs), PackageFilter::Any(rhs)) => {
+                lhs.extend(rhs);
+                PackageFilter::Any(lhs)
+            }
+            (PackageFilter::Any(mut lhs), rhs) => {
+                lhs.push(rhs);
+                PackageFilter::Any(lhs)
+            }
+            (lhs, rhs) => PackageFilter::Any(vec![lhs, rhs]),
+        }
+    }
+}
+
+impl<F: Into<PackageFilter>> BitAnd<F> for PackageFilter {
+    type Output = PackageFilter;
+    fn bitand(self, rhs: F) -> PackageFilter {
+        match (self, rhs.into()) {
+            (PackageFilter::All(mut lhs), PackageFilter::All(rhs)) => {
+                lhs.extend(rhs);
+                PackageFilter::All(lhs)
+            }
+            (PackageFilter::All(mut lhs), rhs) => {
+                lhs.push(rhs);
+                PackageFilter::All(lhs)
+            }
+            (lhs, rhs) => PackageFilter::All(vec![lhs, rhs]),
+        }
+    }
+}
+
+impl Not for PackageFilter {
+    type Output = PackageFilter;
+    fn not(self) -> PackageFilter {
+        match self {
+            PackageFilter::Not(filter) => *filter,
+            filter => PackageFilter::Not(Box::new(filter)),
+        }
+    }
+}
+
+impl<F: Into<PackageFilter>> BitOr<F> for Priority {
+    type Output = PackageFilter;
+    fn bitor(self, rhs: F) -> PackageFilter {
+        PackageFilter::from(self) | rhs
+    }
+}
+
+impl<F: Into<PackageFilter>> BitAnd<F> for Priority {
+    type Output = PackageFilter;
+    fn bitand(self, rhs: F) -> PackageFilter {
+        PackageFilter::from(self) & rhs
+    }
+}
+
+impl Not for Priority {
+    type Output = PackageFilter;
+    fn not(self) -> PackageFilter {
+        !PackageFilter::from(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::packages::Packages};
+
+    #[test]
+    fn test_filter() {
+        let packages = Packages::try_from(
+            "Package: base-files\nArchitecture: amd64\nVersion: 13\nEssential: yes\n\
+             Priority: required\nSection: admin\n\n\
+             Package: libc6\nArchitecture: amd64\nVersion: 2.36\nPriority: optional\n\
+             Section: libs\nTag: role::shared-lib\n\n\
+             Package: libfoo-dev\nArchitecture: amd64\nVersion: 1.0\nPriority: optional\n\
+             Section: contrib/libdevel\nTag: devel::library, role::devel-lib\n\n\
+             Package: less\nArchitecture: amd64\nVersion: 590\nPriority: important\n\
+             Section: text\nTag: role::program,\n use::viewing\n\n\
+             Package: init\nArchitecture: amd64\nVersion: 1.66\nImportant: yes\n",
+        )
+        .unwrap();
+        let names = |filter: PackageFilter| {
+            packages
+                .filter(filter)
+                .map(|p| p.name())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            names(Priority::Required | Priority::Important),
+            ["base-files", "less"]
+        );
+        assert_eq!(
+            names(Section::starts_with("lib") & !Tag::contains("role::shared-lib")),
+            ["libfoo-dev"]
+        );
+        assert_eq!(
+            names(Priority::Required | Section::is("libs") | Tag::contains("use::viewing")),
+            ["base-files", "libc6", "less"]
+        );
+        assert_eq!(names(Tag::facet("devel")), ["libfoo-dev"]);
+        assert_eq!(names(PackageFilter::Essential), ["base-files"]);
+        assert_eq!(names(PackageFilter::Protected), ["init"]);
+        assert_eq!(
+            names(PackageFilter::name("lib*") & !PackageFilter::name("*-dev")),
+            ["libc6"]
+        );
+    }
+}
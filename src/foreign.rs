@@ -0,0 +1,106 @@
+//! Foreign architecture roots
+//!
+//! A root file system for a foreign architecture, e.g. arm64 on an amd64
+//! host, is built in two stages, as `debootstrap --foreign` does. The
+//! packages are solved in a universe of the target architecture and
+//! extracted with [`crate::DebReader::extract_to`], which never runs the
+//! maintainer scripts. The packages are then configured in the root
+//! through an [`Emulator`], e.g. [`QemuUser`], registered with
+//! `Executor::emulator` or installed around the [`crate::ChrootScripts`]:
+//!
+//! ```ignore
+//! let mut universe = Universe::new("arm64", packages)?;
+//! // extract the base system into "rootfs", then
+//! let executor = Executor::new(Chroot::new("rootfs"))
+//!     .emulator("arm64", QemuUser::new("/usr/bin"));
+//! executor.execute(&plan, &debs).await?;
+//! ```
+
+use {
+    crate::arch::qemu_cpu,
+    async_std::{
+        fs, io,
+        path::{Path, PathBuf},
+    },
+    async_trait::async_trait,
+};
+
+/// Makes a root able to run the binaries of a foreign architecture.
+#[async_trait]
+pub trait Emulator: Send + Sync {
+    /// Prepares `root` to run the binaries of `arch`.
+    async fn install(&self, root: &Path, arch: &str) -> io::Result<()>;
+    /// Undoes [`Emulator::install`] once the packages are configured.
+    async fn remove(&self, root: &Path, arch: &str) -> io::Result<()>;
+}
+
+/// An [`Emulator`] copying the statically linked qemu-user interpreter of
+/// the architecture, e.g. `qemu-aarch64-static`, into `/usr/bin` of the
+/// root, where the binfmt_misc handlers registered by `qemu-user-static`
+/// expect it.
+#[derive(Clone, Debug)]
+pub struct QemuUser {
+    dir: PathBuf,
+}
+
+impl QemuUser {
+    /// Takes the interpreters from `dir`, e.g. `/usr/bin`.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+    fn cpu(arch: &str) -> io::Result<&'static str> {
+        qemu_cpu(arch)
+            .ok_or_else(|| io::Error::other(format!("no qemu-user emulation of {}", arch)))
+    }
+}
+
+#[async_trait]
+impl Emulator for QemuUser {
+    async fn install(&self, root: &Path, arch: &str) -> io::Result<()> {
+        let cpu = Self::cpu(arch)?;
+        let name = format!("qemu-{}-static", cpu);
+        let binfmt = Path::new("/proc/sys/fs/binfmt_misc").join(format!("qemu-{}", cpu));
+        if !binfmt.exists().await {
+            tracing::warn!(
+                "{} is not registered, foreign binaries may fail",
+                binfmt.display()
+            );
+        }
+        let dir = root.join("usr/bin");
+        fs::create_dir_all(&dir).await?;
+        fs::copy(self.dir.join(&name), dir.join(&name)).await?;
+        tracing::info!("installed {} into {}", name, root.display());
+        Ok(())
+    }
+    async fn remove(&self, root: &Path, arch: &str) -> io::Result<()> {
+        let name = format!("qemu-{}-static", Self::cpu(arch)?);
+        match fs::remove_file(root.join("usr/bin").join(name)).await {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_qemu_user() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = Path::new(dir.path());
+        fs::create_dir(dir.join("bin")).await.unwrap();
+        fs::write(dir.join("bin/qemu-aarch64-static"), "qemu")
+            .await
+            .unwrap();
+        let qemu = QemuUser::new(dir.join("bin"));
+        let root = dir.join("root");
+        qemu.install(&root, "arm64").await.unwrap();
+        assert!(root.join("usr/bin/qemu-aarch64-static").exists().await);
+        qemu.remove(&root, "arm64").await.unwrap();
+        assert!(!root.join("usr/bin/qemu-aarch64-static").exists().await);
+        assert!(qemu.remove(&root, "arm64").await.is_ok());
+        assert!(qemu.install(&root, "riscv64").await.is_err());
+        assert!(qemu.install(&root, "hurd-i386").await.is_err());
+    }
+}
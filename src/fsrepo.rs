@@ -1,15 +1,16 @@
 //! Debian repository client
 
 use {
+    crate::transport::{Transport, TransportResponse},
     async_std::{
-        io::{self, Read},
-        path::{Path, PathBuf},
+        io::{self, prelude::*, SeekFrom},
+        path::{Component, Path, PathBuf},
     },
     async_trait::async_trait,
-    std::pin::Pin,
-    crate::repo::DebRepoProvider,
+    std::ops::Range,
 };
 
+/// Local filesystem repository provider.
 #[derive(Clone)]
 pub struct FSDebRepo {
     base: PathBuf,
@@ -19,19 +20,103 @@ impl FSDebRepo {
     pub async fn new(path: impl AsRef<Path>) -> io::Result<Self> {
         let base = path.as_ref().to_path_buf();
         if base.is_dir().await {
-            Ok(FSDebRepo{base})
+            Ok(FSDebRepo { base })
         } else {
-            Err(io::Error::new(io::ErrorKind::NotADirectory, format!("{:#?}", path.as_ref())))
+            Err(io::Error::new(
+                io::ErrorKind::NotADirectory,
+                format!("{:#?}", path.as_ref()),
+            ))
         }
     }
+    /// Creates a provider from either a `file://` URL or a plain path.
+    pub async fn from_url(url: &str) -> io::Result<Self> {
+        if url.starts_with("file:") {
+            let path = url::Url::parse(url)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, format!("{}", err)))?
+                .to_file_path()
+                .map_err(|_| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("{} is not a local file URL", url),
+                    )
+                })?;
+            Self::new(path).await
+        } else {
+            Self::new(url).await
+        }
+    }
+    // Resolves `path` relative to the repository base, refusing to escape it.
+    fn target_path(&self, path: &str) -> io::Result<PathBuf> {
+        let rel = Path::new(path);
+        for c in rel.components() {
+            match c {
+                Component::Normal(_) | Component::CurDir => {}
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("invalid repository path {:?}", path),
+                    ))
+                }
+            }
+        }
+        Ok(self.base.join(rel))
+    }
 }
 
 #[async_trait]
-impl DebRepoProvider for FSDebRepo {
-    async fn reader(&self, path: &str) -> io::Result<Pin<Box<dyn Read + Send>>> {
-        let path = self.base.join(path);
-        Ok(Box::pin(async_std::fs::File::open(path).await?) as Pin<Box<dyn Read + Send>>)
-
+impl Transport for FSDebRepo {
+    async fn get(&self, path: &str, range: Option<Range<u64>>) -> io::Result<TransportResponse> {
+        let path = self.target_path(path)?;
+        let mut file = async_std::fs::File::open(path).await?;
+        let length = file.metadata().await?.len();
+        match range {
+            None => Ok(TransportResponse::new(Box::pin(file), Some(length))),
+            Some(range) => {
+                let end = std::cmp::min(range.end, length);
+                let start = std::cmp::min(range.start, end);
+                file.seek(SeekFrom::Start(start)).await?;
+                Ok(TransportResponse::new(
+                    Box::pin(file.take(end - start)),
+                    Some(end - start),
+                ))
+            }
+        }
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::DebRepo;
+
+    #[async_std::test]
+    async fn test_file_url() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("pool")).unwrap();
+        std::fs::write(dir.path().join("pool/file"), b"data").unwrap();
+        let url = url::Url::from_directory_path(dir.path()).unwrap();
+        let repo: DebRepo = FSDebRepo::from_url(url.as_str()).await.unwrap().into();
+        assert_eq!(repo.fetch("pool/file").await.unwrap(), b"data");
+        assert_eq!(repo.fetch("./pool/file").await.unwrap(), b"data");
+        let mut rsp = repo.get("pool/file", Some(1..3)).await.unwrap();
+        let mut buf = vec![];
+        rsp.reader.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(rsp.length, Some(2));
+        assert_eq!(buf, b"at");
+    }
+
+    #[async_std::test]
+    async fn test_path_traversal() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("repo/pool")).unwrap();
+        std::fs::write(dir.path().join("secret"), b"data").unwrap();
+        let repo: DebRepo = FSDebRepo::new(dir.path().join("repo"))
+            .await
+            .unwrap()
+            .into();
+        for path in ["../secret", "pool/../../secret", "/etc/passwd"] {
+            let err = io::Error::from(repo.fetch(path).await.unwrap_err());
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{}", path);
+        }
+    }
+}
@@ -1,50 +1,513 @@
 //! Debian repository client
 
 use {
-    crate::repo::DebRepoProvider,
+    crate::{
+        auth::{redact_url, AuthConf, Credentials},
+        cache::OfflineMode,
+        transport::{skip_to_range, Transport, TransportResponse},
+    },
     async_std::io::{self, Read},
     async_trait::async_trait,
-    isahc::{config::RedirectPolicy, prelude::*, HttpClient},
-    std::pin::Pin,
+    curl::easy::{Auth, Easy2, Handler, List, WriteError},
+    futures::TryStreamExt,
+    isahc::{
+        auth::Authentication,
+        config::{
+            CaCertificate, ClientCertificate, PrivateKey, RedirectPolicy, VersionNegotiation,
+        },
+        http::StatusCode,
+        prelude::*,
+        HttpClient,
+    },
+    std::{ops::Range, path::PathBuf, pin::Pin, sync::OnceLock},
 };
 
+/// Proxy settings of an [`HttpDebRepo`].
+#[derive(Clone, Debug, Default)]
+pub enum Proxy {
+    /// Use the proxy from the `http_proxy`, `https_proxy` and `no_proxy`
+    /// environment variables, if set.
+    #[default]
+    Env,
+    /// Connect directly, ignoring the environment.
+    Direct,
+    /// Send requests through the given HTTP proxy. Hosts listed in
+    /// `no_proxy` are still reached directly.
+    Http(url::Url),
+    /// Rewrite request URLs to go through an apt-cacher-ng style cache, e.g.
+    /// `http://deb.debian.org/debian/` becomes
+    /// `http://cache:3142/deb.debian.org/debian/` and https repositories
+    /// are requested as `http://cache:3142/HTTPS///host/path`.
+    Rewrite(url::Url),
+}
+
+impl Proxy {
+    fn rewrite(&self, url: url::Url) -> url::Url {
+        match self {
+            Proxy::Rewrite(prefix) => {
+                let host = match (url.host_str(), url.port()) {
+                    (Some(host), Some(port)) => format!("{}:{}", host, port),
+                    (Some(host), None) => host.to_string(),
+                    (None, _) => return url,
+                };
+                let mut path = prefix.path().trim_end_matches('/').to_string();
+                if url.scheme() == "https" {
+                    path.push_str("/HTTPS///");
+                } else {
+                    path.push('/');
+                }
+                path.push_str(&host);
+                path.push_str(url.path());
+                let mut rewritten = prefix.clone();
+                rewritten.set_path(&path);
+                rewritten.set_query(url.query());
+                rewritten
+            }
+            _ => url,
+        }
+    }
+}
+
+/// TLS settings of an [`HttpDebRepo`], e.g. for an internal mirror behind
+/// a private CA and mTLS.
+#[derive(Clone, Debug, Default)]
+pub struct Tls {
+    /// The PEM bundle of the CAs trusted instead of the system ones
+    pub ca_file: Option<PathBuf>,
+    /// The PEM client certificate and private key files
+    pub client_cert: Option<(PathBuf, PathBuf)>,
+    /// The password of the private key, if encrypted
+    pub key_password: Option<String>,
+    /// The public keys (SPKI) the server must present one of, as
+    /// `sha256//<base64>` hashes separated by `;`, or the file of the key,
+    /// as curl's `--pinnedpubkey`. The requests then bypass the shared
+    /// client and its connection cache.
+    pub pinned_public_key: Option<String>,
+}
+
+// Requests beyond this many to a host wait for a connection to be free,
+// or for the first one to settle on HTTP/2 and multiplex them.
+const MAX_CONNECTIONS_PER_HOST: usize = 6;
+
+// The client of all the repositories, so that they share its connection
+// cache and the fetches of many small files from the same host go over a few
+// kept-alive connections, or a single multiplexed HTTP/2 one.
+fn shared_client() -> io::Result<HttpClient> {
+    static CLIENT: OnceLock<HttpClient> = OnceLock::new();
+    if let Some(client) = CLIENT.get() {
+        return Ok(client.clone());
+    }
+    let client = HttpClient::builder()
+        .redirect_policy(RedirectPolicy::Limit(10))
+        .timeout(std::time::Duration::from_secs(30))
+        .version_negotiation(VersionNegotiation::latest_compatible())
+        .max_connections_per_host(MAX_CONNECTIONS_PER_HOST)
+        .build()?;
+    Ok(CLIENT.get_or_init(|| client).clone())
+}
+
 #[derive(Clone)]
 pub struct HttpDebRepo {
     base: url::Url,
     client: HttpClient,
+    credentials: Option<Credentials>,
+    proxy: Proxy,
+    tls: Tls,
 }
 
 impl HttpDebRepo {
     pub async fn new(url: &str) -> io::Result<Self> {
         Ok(Self {
-            base: url::Url::parse(url).map_err(|err| 
-                io::Error::new(io::ErrorKind::InvalidInput, format!("{}", err))
-            )?,
-            client: HttpClient::builder()
-                .redirect_policy(RedirectPolicy::Limit(10))
-                .timeout(std::time::Duration::from_secs(30))
-                .build()?,
+            base: url::Url::parse(url)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, format!("{}", err)))?,
+            client: shared_client()?,
+            credentials: None,
+            proxy: Proxy::Env,
+            tls: Tls::default(),
+        })
+    }
+    /// Sets credentials sent with every request to this repository.
+    pub fn credentials(mut self, credentials: Credentials) -> Self {
+        self.credentials = Some(credentials);
+        self
+    }
+    /// Sets the proxy used for this repository.
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.proxy = proxy;
+        self
+    }
+    /// Sets the TLS settings of the connections to this repository.
+    pub fn tls(mut self, tls: Tls) -> Self {
+        self.tls = tls;
+        self
+    }
+    /// Uses the credentials from `conf` matching the repository URL, if any.
+    pub fn auth_conf(mut self, conf: &AuthConf) -> Self {
+        if let Some(credentials) = conf.lookup(&self.base) {
+            self.credentials = Some(credentials.clone());
+        }
+        self
+    }
+}
+
+/// An unexpected HTTP response status.
+#[derive(Debug)]
+pub struct HttpStatusError {
+    pub url: String,
+    pub status: u16,
+}
+
+impl HttpStatusError {
+    /// Server errors, timeouts and throttling are worth retrying.
+    pub fn is_transient(&self) -> bool {
+        self.status >= 500 || self.status == 408 || self.status == 429
+    }
+}
+
+impl std::fmt::Display for HttpStatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: unexpected HTTP response {}", self.url, self.status)
+    }
+}
+
+impl std::error::Error for HttpStatusError {}
+
+// Checks the `no_proxy` environment variable for the host of `url`.
+fn no_proxy(url: &url::Url) -> bool {
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+    let list = std::env::var("no_proxy")
+        .or_else(|_| std::env::var("NO_PROXY"))
+        .unwrap_or_default();
+    list.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .any(|entry| {
+            entry == "*" || {
+                let entry = entry.trim_start_matches('.');
+                host == entry || host.ends_with(&format!(".{}", entry))
+            }
         })
+}
+
+// The status of a response, the length of its body if known, and its body.
+type Response = (StatusCode, Option<u64>, Pin<Box<dyn Read + Send>>);
+
+impl HttpDebRepo {
+    // Sends the request for `url` with the shared client.
+    async fn send(&self, url: &url::Url, range: Option<&Range<u64>>) -> io::Result<Response> {
+        let mut req = isahc::Request::get(url.as_str());
+        match &self.proxy {
+            Proxy::Direct => req = req.proxy(None),
+            Proxy::Http(proxy) => {
+                let uri = proxy
+                    .as_str()
+                    .parse()
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+                if !no_proxy(url) {
+                    req = req.proxy(Some(uri));
+                }
+            }
+            Proxy::Env | Proxy::Rewrite(_) => {}
+        }
+        match &self.credentials {
+            Some(Credentials::Basic { login, password }) => {
+                req = req.authentication(Authentication::basic()).credentials(
+                    isahc::auth::Credentials::new(login.as_str(), password.as_str()),
+                );
+            }
+            Some(Credentials::Bearer(token)) => {
+                req = req.header("Authorization", format!("Bearer {}", token));
+            }
+            None => {}
+        }
+        if let Some(ca_file) = &self.tls.ca_file {
+            req = req.ssl_ca_certificate(CaCertificate::file(ca_file));
+        }
+        if let Some((cert, key)) = &self.tls.client_cert {
+            let key = PrivateKey::pem_file(key, self.tls.key_password.clone());
+            req = req.ssl_client_certificate(ClientCertificate::pem_file(cert, key));
+        }
+        if let Some(range) = range {
+            req = req.header("Range", format!("bytes={}-{}", range.start, range.end - 1));
+        }
+        let req = req
+            .body(())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        let rsp = self.client.send_async(req).await.map_err(|err| {
+            let msg = format!("{}: {}", redact_url(url), err);
+            io::Error::new(io::Error::from(err).kind(), msg)
+        })?;
+        let length = rsp.body().len();
+        Ok((rsp.status(), length, Box::pin(rsp.into_body())))
+    }
+    // Sends the request for `url` with curl directly, as the shared client
+    // cannot pin the public key of the server. The transfer runs on a
+    // blocking thread, which streams the body through a channel.
+    async fn send_pinned(
+        &self,
+        url: &url::Url,
+        range: Option<&Range<u64>>,
+        pins: &str,
+    ) -> io::Result<Response> {
+        let (head_tx, head_rx) = futures::channel::oneshot::channel();
+        let (body_tx, body_rx) = async_std::channel::bounded(16);
+        let mut easy = Easy2::new(Streamer {
+            status: 0,
+            length: None,
+            head: Some(head_tx),
+            body: body_tx,
+        });
+        easy.url(url.as_str())?;
+        easy.pinned_public_key(pins)?;
+        easy.follow_location(true)?;
+        easy.max_redirections(10)?;
+        easy.timeout(std::time::Duration::from_secs(30))?;
+        match &self.proxy {
+            Proxy::Direct => easy.proxy("")?,
+            Proxy::Http(proxy) if !no_proxy(url) => easy.proxy(proxy.as_str())?,
+            Proxy::Http(_) | Proxy::Env | Proxy::Rewrite(_) => {}
+        }
+        let mut headers = List::new();
+        match &self.credentials {
+            Some(Credentials::Basic { login, password }) => {
+                let mut auth = Auth::new();
+                auth.basic(true);
+                easy.http_auth(&auth)?;
+                easy.username(login)?;
+                easy.password(password)?;
+            }
+            Some(Credentials::Bearer(token)) => {
+                headers.append(&format!("Authorization: Bearer {}", token))?;
+            }
+            None => {}
+        }
+        if let Some(ca_file) = &self.tls.ca_file {
+            easy.cainfo(ca_file)?;
+        }
+        if let Some((cert, key)) = &self.tls.client_cert {
+            easy.ssl_cert(cert)?;
+            easy.ssl_key(key)?;
+            if let Some(password) = &self.tls.key_password {
+                easy.key_password(password)?;
+            }
+        }
+        if let Some(range) = range {
+            headers.append(&format!("Range: bytes={}-{}", range.start, range.end - 1))?;
+        }
+        easy.http_headers(headers)?;
+        async_std::task::spawn_blocking(move || {
+            let result = easy.perform();
+            let streamer = easy.get_mut();
+            match result {
+                Ok(()) => streamer.send_head(),
+                Err(err) => match streamer.head.take() {
+                    Some(head) => {
+                        head.send(Err(err)).ok();
+                    }
+                    None => {
+                        streamer.body.send_blocking(Err(err.into())).ok();
+                    }
+                },
+            }
+        });
+        let head = head_rx.await.map_err(|_| {
+            io::Error::new(io::ErrorKind::BrokenPipe, "the transfer ended unexpectedly")
+        })?;
+        let (status, length) = head.map_err(|err| {
+            let msg = format!("{}: {}", redact_url(url), err);
+            io::Error::new(io::Error::from(err).kind(), msg)
+        })?;
+        let status = StatusCode::from_u16(status)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        Ok((status, length, Box::pin(body_rx.into_async_read())))
+    }
+}
+
+// The status and the length of a response of curl, or the error of the
+// transfer if it failed before.
+type Head = Result<(u16, Option<u64>), curl::Error>;
+
+// Collects the status and the length of a response of curl, then sends its
+// body through a channel.
+struct Streamer {
+    status: u16,
+    length: Option<u64>,
+    head: Option<futures::channel::oneshot::Sender<Head>>,
+    body: async_std::channel::Sender<io::Result<Vec<u8>>>,
+}
+
+impl Streamer {
+    fn send_head(&mut self) {
+        if let Some(head) = self.head.take() {
+            head.send(Ok((self.status, self.length))).ok();
+        }
+    }
+}
+
+impl Handler for Streamer {
+    fn header(&mut self, data: &[u8]) -> bool {
+        let line = String::from_utf8_lossy(data);
+        // every response of the redirects starts with its status line
+        if line.starts_with("HTTP/") {
+            self.status = line
+                .split_whitespace()
+                .nth(1)
+                .and_then(|code| code.parse().ok())
+                .unwrap_or_default();
+            self.length = None;
+        } else if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                self.length = value.trim().parse().ok();
+            }
+        }
+        true
+    }
+    fn write(&mut self, data: &[u8]) -> Result<usize, WriteError> {
+        self.send_head();
+        // a short write aborts the transfer once the body is dropped
+        match self.body.send_blocking(Ok(data.to_vec())) {
+            Ok(()) => Ok(data.len()),
+            Err(_) => Ok(0),
+        }
     }
 }
 
 #[async_trait]
-impl DebRepoProvider for HttpDebRepo {
-    async fn reader(&self, path: &str) -> io::Result<Pin<Box<dyn Read + Send>>> {
-        let uri = self
+impl Transport for HttpDebRepo {
+    async fn get(&self, path: &str, range: Option<Range<u64>>) -> io::Result<TransportResponse> {
+        OfflineMode::check(path)?;
+        let url = self
             .base
             .join(path)
-            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?
-            .to_string();
-        let rsp = self.client.get_async(&uri).await?;
-        use isahc::http::StatusCode;
-        match rsp.status() {
-            StatusCode::OK => Ok(Box::pin(rsp.into_body()) as Pin<Box<dyn Read + Send + Unpin>>),
-            StatusCode::NOT_FOUND => Err(io::Error::new(io::ErrorKind::NotFound, uri.clone())),
-            code => Err(io::Error::new(
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        let url = self.proxy.rewrite(url);
+        if range.as_ref().is_some_and(|range| range.is_empty()) {
+            return Ok(TransportResponse::new(Box::pin(io::empty()), Some(0)));
+        }
+        let (status, length, body) = match &self.tls.pinned_public_key {
+            Some(pins) => self.send_pinned(&url, range.as_ref(), pins).await?,
+            None => self.send(&url, range.as_ref()).await?,
+        };
+        match (status, range) {
+            (StatusCode::OK, None) | (StatusCode::PARTIAL_CONTENT, Some(_)) => {
+                Ok(TransportResponse::new(body, length))
+            }
+            // the server ignored the Range header and sent the whole file
+            (StatusCode::OK, Some(range)) => skip_to_range(path, body, length, range).await,
+            (StatusCode::NOT_FOUND, _) => {
+                Err(io::Error::new(io::ErrorKind::NotFound, redact_url(&url)))
+            }
+            (StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN, _) => Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("{}: access denied ({})", redact_url(&url), status),
+            )),
+            (code, _) => Err(io::Error::new(
                 io::ErrorKind::Other,
-                format!("unexpected HTTP response {}", code),
+                HttpStatusError {
+                    url: redact_url(&url),
+                    status: code.as_u16(),
+                },
             )),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rewrite_proxy() {
+        let proxy = Proxy::Rewrite(url::Url::parse("http://cache:3142/").unwrap());
+        let url = |s: &str| url::Url::parse(s).unwrap();
+        assert_eq!(
+            proxy
+                .rewrite(url("http://deb.debian.org/debian/dists/sid/Release"))
+                .as_str(),
+            "http://cache:3142/deb.debian.org/debian/dists/sid/Release"
+        );
+        assert_eq!(
+            proxy
+                .rewrite(url("https://example.org:8443/repo/"))
+                .as_str(),
+            "http://cache:3142/HTTPS///example.org:8443/repo/"
+        );
+        assert_eq!(
+            Proxy::Env.rewrite(url("https://example.org/")).as_str(),
+            "https://example.org/"
+        );
+    }
+
+    // Serves the file `contents` as a whole to every request, ignoring
+    // ranges, and returns the URL of the server.
+    fn serve_whole_file() -> String {
+        use std::io::{BufRead, Write};
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = std::io::BufReader::new(&stream);
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                stream
+                    .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 8\r\n\r\ncontents")
+                    .unwrap();
+            }
+        });
+        url
+    }
+
+    async fn read(rsp: TransportResponse) -> Vec<u8> {
+        let mut buf = vec![];
+        let mut reader = rsp.reader;
+        io::ReadExt::read_to_end(&mut reader, &mut buf)
+            .await
+            .unwrap();
+        buf
+    }
+
+    #[async_std::test]
+    async fn test_range_ignored() {
+        let url = serve_whole_file();
+        let repo = HttpDebRepo::new(&url).await.unwrap().proxy(Proxy::Direct);
+        let rsp = repo.get("file", Some(2..5)).await.unwrap();
+        assert_eq!(rsp.length, Some(3));
+        assert_eq!(read(rsp).await, b"nte");
+        let err = repo.get("file", Some(9..10)).await.err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[async_std::test]
+    async fn test_pinned_public_key() {
+        // pins only apply to TLS connections, the transfer goes through curl
+        let url = serve_whole_file();
+        let tls = Tls {
+            pinned_public_key: Some(
+                "sha256//YhKJKSzoTt2b5FP18fvpHo7fJYqQCjAa3HWY3tvRMwE=".to_string(),
+            ),
+            ..Tls::default()
+        };
+        let repo = HttpDebRepo::new(&url)
+            .await
+            .unwrap()
+            .proxy(Proxy::Direct)
+            .tls(tls);
+        let rsp = repo.get("file", None).await.unwrap();
+        assert_eq!(rsp.length, Some(8));
+        assert_eq!(read(rsp).await, b"contents");
+        let rsp = repo.get("file", Some(2..5)).await.unwrap();
+        assert_eq!(read(rsp).await, b"nte");
+        let repo = HttpDebRepo::new("http://127.0.0.1:9/")
+            .await
+            .unwrap()
+            .proxy(Proxy::Direct)
+            .tls(repo.tls.clone());
+        let err = repo.get("file", None).await.err().unwrap();
+        assert!(err.to_string().starts_with("http://127.0.0.1:9/file: "));
+    }
+}
@@ -2,8 +2,9 @@ use std::{
     borrow::Borrow,
     cell::UnsafeCell,
     collections::HashMap,
-    hash::{Hash, Hasher},
+    hash::{BuildHasher, Hash, Hasher, RandomState},
     ops::Index,
+    sync::{Mutex, PoisonError},
 };
 
 pub(crate) trait IntoId<Id> {
@@ -83,6 +84,24 @@ pub(crate) use id_type;
 
 const BLOCK_SIZE: usize = 4096;
 
+/// The number of entries of an interning map and an estimate of the bytes
+/// they take.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MapStats {
+    pub count: usize,
+    pub bytes: usize,
+}
+
+impl std::ops::Add for MapStats {
+    type Output = MapStats;
+    fn add(self, other: MapStats) -> MapStats {
+        MapStats {
+            count: self.count + other.count,
+            bytes: self.bytes + other.bytes,
+        }
+    }
+}
+
 pub(crate) struct HashRef<T: Hash + Eq + ?Sized> {
     ptr: *const T,
 }
@@ -124,11 +143,24 @@ impl<T: Hash + Eq + ?Sized> From<&T> for HashRef<T> {
     }
 }
 
+/// Interns values, giving each distinct one a dense id. The values are
+/// stored in blocks that never move, so that the references handed out
+/// stay valid while more values are inserted.
+///
+/// The map is not locked: each one belongs to a single universe, filled
+/// while the universe is built and then by its solver, both on the thread
+/// owning the universe. The `UnsafeCell`s only let the solver callbacks,
+/// which get shared references, insert. [`Interner`] shares maps between
+/// threads.
 pub(crate) struct IdMap<IdMap, Value: Hash + Eq> {
     arena: UnsafeCell<Vec<Box<Vec<Value>>>>,
     index: UnsafeCell<HashMap<HashRef<Value>, IdMap>>,
 }
 
+// SAFETY: the pointers of the index point into the arena blocks the map
+// owns, which do not move along with the map.
+unsafe impl<IdType: Send, Value: Hash + Eq + Send> Send for IdMap<IdType, Value> {}
+
 impl<IdType, Value: std::fmt::Debug + Hash + Eq> std::fmt::Debug for IdMap<IdType, Value> {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         unsafe {
@@ -176,6 +208,13 @@ where
             index: UnsafeCell::new(HashMap::new()),
         }
     }
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        let blocks = capacity.div_ceil(BLOCK_SIZE);
+        Self {
+            arena: UnsafeCell::new(Vec::with_capacity(blocks)),
+            index: UnsafeCell::new(HashMap::with_capacity(capacity)),
+        }
+    }
     pub(crate) fn get<K: Hash + Eq + ?Sized>(&self, item: &K) -> Option<IdType>
     where
         HashRef<Value>: Borrow<K>,
@@ -204,6 +243,34 @@ where
             id
         }
     }
+    pub(crate) fn len(&self) -> usize {
+        unsafe { (&*self.index.get()).len() }
+    }
+    /// Returns the number of entries and the bytes taken by the arena, the
+    /// index and, as reported by `heap`, the data the values own.
+    pub(crate) fn stats<F: Fn(&Value) -> usize>(&self, heap: F) -> MapStats {
+        unsafe {
+            let arena = &*self.arena.get();
+            let index = &*self.index.get();
+            let slots: usize = arena.iter().map(|block| block.capacity()).sum();
+            let owned: usize = arena.iter().flat_map(|block| block.iter()).map(heap).sum();
+            MapStats {
+                count: index.len(),
+                bytes: slots * std::mem::size_of::<Value>()
+                    + arena.capacity() * std::mem::size_of::<Box<Vec<Value>>>()
+                    + index.capacity() * std::mem::size_of::<(HashRef<Value>, IdType)>()
+                    + owned,
+            }
+        }
+    }
+    /// Releases the spare capacity of the index. The arena blocks keep
+    /// theirs, as the values must not move.
+    pub(crate) fn shrink_to_fit(&self) {
+        unsafe {
+            (&mut *self.index.get()).shrink_to_fit();
+            (&mut *self.arena.get()).shrink_to_fit();
+        }
+    }
     pub(crate) fn get_or_insert(&self, item: Value) -> IdType {
         self.get(&HashRef::from(&item))
             .unwrap_or_else(|| self.insert(item))
@@ -248,7 +315,10 @@ where
         let id = self
             .get(value.as_ref())
             .unwrap_or_else(|| self.insert(value.into_boxed()));
-        InternResult { id, val: self[id].as_ref() }
+        InternResult {
+            id,
+            val: self[id].as_ref(),
+        }
     }
 }
 
@@ -282,7 +352,7 @@ impl<IdType, Value> InternResult<IdType, Value> {
     }
 }
 
-pub trait IntoBoxed<Value> 
+pub trait IntoBoxed<Value>
 where
     Value: Hash + Eq + ?Sized,
 {
@@ -319,6 +389,84 @@ impl IntoBoxed<str> for Box<str> {
     }
 }
 
+// The number of shards of an `Interner`
+const SHARDS: usize = 16;
+
+type Shard = Mutex<IdMap<u32, Box<str>>>;
+
+/// A string interner that can be shared between threads, e.g. by the tasks
+/// parsing indexes in parallel, so that they keep a single copy of the
+/// strings they have in common. The strings are spread over shards by hash,
+/// each behind a lock of its own, so that the threads interning different
+/// strings rarely wait for each other.
+pub struct Interner {
+    hasher: RandomState,
+    shards: Box<[Shard]>,
+}
+
+impl Default for Interner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Self {
+            hasher: RandomState::new(),
+            shards: (0..SHARDS).map(|_| Mutex::new(IdMap::new())).collect(),
+        }
+    }
+    fn shard(&self, value: &str) -> &Shard {
+        &self.shards[self.hasher.hash_one(value) as usize % SHARDS]
+    }
+    /// Returns the interned copy of `value`, interning it first if needed.
+    pub fn intern(&self, value: &str) -> &str {
+        let map = self
+            .shard(value)
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let interned: *const str = map.intern(value).as_ref();
+        // SAFETY: the strings are boxed in blocks that never move, and are
+        // only dropped along with the interner.
+        unsafe { &*interned }
+    }
+    /// Returns the interned copy of `value`, if any.
+    pub fn get(&self, value: &str) -> Option<&str> {
+        let map = self
+            .shard(value)
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let interned: *const str = map[map.get(value)?].as_ref();
+        // SAFETY: as in `intern`
+        Some(unsafe { &*interned })
+    }
+    /// Returns the number of strings interned.
+    pub fn len(&self) -> usize {
+        self.lock_all().map(|map| map.len()).sum()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Returns the number of strings and the bytes the interner takes.
+    pub fn stats(&self) -> MapStats {
+        self.lock_all()
+            .map(|map| map.stats(|s| s.len()))
+            .fold(MapStats::default(), |total, stats| total + stats)
+    }
+    /// Releases the spare capacity of the indexes of the shards.
+    pub fn shrink_to_fit(&mut self) {
+        for map in self.lock_all() {
+            map.shrink_to_fit();
+        }
+    }
+    fn lock_all(&self) -> impl Iterator<Item = std::sync::MutexGuard<'_, IdMap<u32, Box<str>>>> {
+        self.shards
+            .iter()
+            .map(|shard| shard.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
 impl<IdType, Value> Index<IdType> for IdMap<IdType, Value>
 where
     Value: Hash + Eq,
@@ -334,3 +482,88 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // These tests do no IO and stay small enough to run under miri, which
+    // checks the pointers the index keeps into the arena:
+    //   cargo +nightly miri test --no-default-features --lib idmap
+    // (without `io`, as miri cannot run the constructor of curl).
+
+    #[test]
+    fn test_intern() {
+        let map: IdMap<u32, Box<str>> = IdMap::new();
+        let a = map.intern("a").as_id();
+        let b = map.intern(String::from("b")).as_id();
+        assert_eq!(map.intern("a").as_id(), a);
+        assert_ne!(a, b);
+        assert_eq!(map.get("b"), Some(b));
+        assert_eq!(map.get("c"), None);
+        assert_eq!(&*map[a], "a");
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn test_references_stay_valid() {
+        let map: IdMap<u32, Box<str>> = IdMap::with_capacity(1);
+        let first = map.intern("0").as_ref();
+        for i in 1..BLOCK_SIZE + 2 {
+            map.intern(i.to_string());
+        }
+        assert_eq!(first, "0");
+        assert_eq!(map.len(), BLOCK_SIZE + 2);
+        let last = (BLOCK_SIZE + 1).to_string();
+        let id = map.get(last.as_str()).unwrap();
+        assert_eq!(id as usize, BLOCK_SIZE + 1);
+        assert_eq!(&*map[id], last);
+        assert_eq!(map.get("0"), Some(0));
+        map.shrink_to_fit();
+        assert_eq!(first, "0");
+        assert_eq!(map.get(last.as_str()), Some(id));
+    }
+
+    #[test]
+    fn test_insert_or_update() {
+        let map: IdMap<u32, (u32, u32)> = IdMap::new();
+        let id = map.get_or_insert((1, 0));
+        assert_eq!(map.get_or_insert((1, 0)), id);
+        let updated = unsafe { map.insert_or_update(&(1, 0), || unreachable!(), |v| v.1 = 0) };
+        assert!(matches!(updated, UpdateResult::Updated(i) if i == id));
+        let inserted = unsafe { map.insert_or_update(&(2, 0), || (2, 0), |_| unreachable!()) };
+        assert!(matches!(inserted, UpdateResult::Inserted(1)));
+        assert_eq!(map[1], (2, 0));
+        assert_eq!(map.stats(|_| 0).count, 2);
+    }
+
+    #[test]
+    fn test_interner() {
+        let interner = Interner::new();
+        let a = interner.intern("a");
+        assert!(std::ptr::eq(interner.intern("a"), a));
+        assert_eq!(interner.get("a"), Some("a"));
+        assert_eq!(interner.get("b"), None);
+        interner.intern("b");
+        assert_eq!(interner.len(), 2);
+        assert_eq!(interner.stats().count, 2);
+    }
+
+    #[test]
+    fn test_interner_threads() {
+        let interner = Interner::new();
+        let names = ["libc6", "perl", "python3", "zlib1g"];
+        let interned: Vec<Vec<&str>> = std::thread::scope(|scope| {
+            let threads: Vec<_> = (0..4)
+                .map(|_| scope.spawn(|| names.map(|name| interner.intern(name)).to_vec()))
+                .collect();
+            threads.into_iter().map(|t| t.join().unwrap()).collect()
+        });
+        for strings in &interned[1..] {
+            for (a, b) in strings.iter().zip(&interned[0]) {
+                assert!(std::ptr::eq(*a, *b));
+            }
+        }
+        assert_eq!(interner.len(), names.len());
+    }
+}
@@ -0,0 +1,83 @@
+//! Installed packages
+//!
+//! [`Installed`] records the versions installed on the target system and
+//! how the solver may change them: held packages keep their version, like
+//! with `apt-mark hold`, and the other ones are never downgraded unless
+//! allowed explicitly.
+//!
+//! ```ignore
+//! universe.set_installed(
+//!     Installed::new()
+//!         .package("libc6", "2.36-9+deb12u4")
+//!         .package("openssl", "3.0.15-1~deb12u1")
+//!         .hold("libc6")
+//!         .allow_downgrade("openssl"),
+//! );
+//! ```
+
+use {
+    crate::phasing::Phasing,
+    std::collections::{HashMap, HashSet},
+};
+
+/// The installed package versions, with the holds and allowed downgrades.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Installed {
+    versions: HashMap<String, String>,
+    held: HashSet<String>,
+    downgradable: HashSet<String>,
+    essential: HashSet<String>,
+    phasing: Phasing,
+}
+
+impl Installed {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Records `version` of the package `name` as installed.
+    pub fn package(mut self, name: impl Into<String>, version: impl Into<String>) -> Self {
+        self.versions.insert(name.into(), version.into());
+        self
+    }
+    /// Keeps the installed version of `name`.
+    pub fn hold(mut self, name: impl Into<String>) -> Self {
+        self.held.insert(name.into());
+        self
+    }
+    /// Lets the solver pick an older version of `name` than installed.
+    pub fn allow_downgrade(mut self, name: impl Into<String>) -> Self {
+        self.downgradable.insert(name.into());
+        self
+    }
+    /// Marks the installed `name` as `Essential: yes` or `Protected: yes`,
+    /// as recorded in the dpkg status, so that plans do not remove it.
+    pub fn essential(mut self, name: impl Into<String>) -> Self {
+        self.essential.insert(name.into());
+        self
+    }
+    /// Sets which phased updates the installed packages get.
+    pub fn phasing(mut self, phasing: Phasing) -> Self {
+        self.phasing = phasing;
+        self
+    }
+    pub fn phasing_policy(&self) -> &Phasing {
+        &self.phasing
+    }
+    /// The installed versions by package name, e.g. for
+    /// [`crate::CandidateOrdering::Installed`].
+    pub fn versions(&self) -> &HashMap<String, String> {
+        &self.versions
+    }
+    pub fn version(&self, name: &str) -> Option<&str> {
+        self.versions.get(name).map(String::as_str)
+    }
+    pub fn is_held(&self, name: &str) -> bool {
+        self.held.contains(name)
+    }
+    pub fn may_downgrade(&self, name: &str) -> bool {
+        self.downgradable.contains(name)
+    }
+    pub fn is_essential(&self, name: &str) -> bool {
+        self.essential.contains(name)
+    }
+}
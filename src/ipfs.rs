@@ -0,0 +1,107 @@
+//! IPFS repositories
+//!
+//! A repository published to IPFS, e.g. `ipfs://bafy.../debian/` or
+//! `ipns://deb.example.org/`, is fetched through public or local HTTP
+//! gateways, tried in order. Gateways are not trusted: as with any mirror,
+//! [`crate::DebRepo`] checks the files against the signed Release file.
+//!
+//! ```ignore
+//! let repo: DebRepo = ipfs_transport("ipns://deb.example.org/", &["http://127.0.0.1:8080/"])
+//!     .await?
+//!     .into();
+//! ```
+//!
+//! There is no BitTorrent transport: fetching the archive's published
+//! torrents needs a peer wire protocol implementation, which is out of
+//! scope for this crate. IPFS is the only peer-to-peer backend.
+
+use {
+    crate::{failover::Failover, httprepo::HttpDebRepo},
+    std::io,
+};
+
+const IPFS_GATEWAYS: &[&str] = &["https://ipfs.io/", "https://dweb.link/"];
+
+// Splits `ipfs://cid/path`, `ipns://name/path` or the gateway path forms
+// `/ipfs/cid/path` and `/ipns/name/path` into the namespace, the root and
+// the path within it.
+fn parse_ipfs(uri: &str) -> io::Result<(&str, &str, &str)> {
+    let parsed = ["ipfs", "ipns"].into_iter().find_map(|namespace| {
+        let rest = uri
+            .strip_prefix(namespace)
+            .and_then(|rest| rest.strip_prefix("://"))
+            .or_else(|| {
+                uri.strip_prefix('/')
+                    .and_then(|rest| rest.strip_prefix(namespace))
+                    .and_then(|rest| rest.strip_prefix('/'))
+            })?;
+        let (root, path) = rest.split_once('/').unwrap_or((rest, ""));
+        let valid = !root.is_empty()
+            && !root.starts_with('.')
+            && path.split('/').all(|part| part != "..")
+            && root
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_'));
+        valid.then_some((namespace, root, path.trim_matches('/')))
+    });
+    parsed.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid IPFS URI {}", uri),
+        )
+    })
+}
+
+/// The URL of the repository `uri` on `gateway`, e.g.
+/// `https://ipfs.io/ipfs/bafy.../debian/` for `ipfs://bafy.../debian`.
+pub fn ipfs_url(gateway: &str, uri: &str) -> io::Result<String> {
+    let (namespace, root, path) = parse_ipfs(uri)?;
+    let mut url = format!("{}/{}/{}/", gateway.trim_end_matches('/'), namespace, root);
+    if !path.is_empty() {
+        url.push_str(path);
+        url.push('/');
+    }
+    Ok(url)
+}
+
+/// Returns a transport fetching the repository `uri` through `gateways`,
+/// or through public gateways if none is given.
+pub async fn ipfs_transport(uri: &str, gateways: &[&str]) -> io::Result<Failover> {
+    let gateways = if gateways.is_empty() {
+        IPFS_GATEWAYS
+    } else {
+        gateways
+    };
+    let mut failover = Failover::new();
+    for gateway in gateways {
+        failover = failover.mirror(HttpDebRepo::new(&ipfs_url(gateway, uri)?).await?);
+    }
+    Ok(failover)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ipfs_url() {
+        let cid = "bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi";
+        assert_eq!(
+            ipfs_url("https://ipfs.io", &format!("ipfs://{}/debian", cid)).unwrap(),
+            format!("https://ipfs.io/ipfs/{}/debian/", cid)
+        );
+        assert_eq!(
+            ipfs_url("http://127.0.0.1:8080/", "/ipns/deb.example.org/").unwrap(),
+            "http://127.0.0.1:8080/ipns/deb.example.org/"
+        );
+        for uri in [
+            "ipfs://",
+            "https://ipfs.io/ipfs/x",
+            "/ipfs/../etc",
+            "ipns:/name",
+            "ipfs://cid/../other",
+        ] {
+            assert!(ipfs_url("https://ipfs.io/", uri).is_err(), "{}", uri);
+        }
+    }
+}
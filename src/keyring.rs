@@ -0,0 +1,250 @@
+//! Trusted keys
+//!
+//! apt trusts the keys of `/etc/apt/trusted.gpg` and of the files in
+//! `/etc/apt/trusted.gpg.d`: binary keyrings (`.gpg`), either OpenPGP packets
+//! or GnuPG keyboxes, and ascii-armored keys (`.asc`). A source can restrict
+//! the keys to its own with `signed-by`, see [`Keyring::signed_by`].
+//!
+//! ```ignore
+//! let keyring = Keyring::load_system().await?.signed_by(&entry.options.signed_by).await?;
+//! let release = repo.fetch_verify_release_with_keys(&entry.suite, keyring.keys()).await?;
+//! ```
+
+use {
+    crate::{
+        error::{Error, Result},
+        repo::keyring_context,
+    },
+    async_std::{fs, io, path::Path, stream::StreamExt},
+};
+
+/// A set of OpenPGP public keys.
+#[derive(Clone, Debug, Default)]
+pub struct Keyring {
+    // key blocks, armored or binary
+    keys: Vec<Vec<u8>>,
+}
+
+const ARMOR: &[u8] = b"-----BEGIN PGP PUBLIC KEY BLOCK-----";
+
+// Extracts the OpenPGP key blocks of a GnuPG keybox, or returns None if
+// `data` is not one.
+fn keybox_blocks(data: &[u8]) -> Option<io::Result<Vec<Vec<u8>>>> {
+    if data.len() < 12 || data[4] != 1 || &data[8..12] != b"KBXf" {
+        return None;
+    }
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "malformed keybox");
+    let u32_at = |blob: &[u8], at: usize| {
+        blob.get(at..at + 4)
+            .map(|b| u32::from_be_bytes(b.try_into().unwrap()) as usize)
+    };
+    let mut blocks = vec![];
+    let mut rest = data;
+    while !rest.is_empty() {
+        let len = match u32_at(rest, 0) {
+            Some(len) if len >= 6 && len <= rest.len() => len,
+            _ => return Some(Err(invalid())),
+        };
+        let blob = &rest[..len];
+        // blob type 2 is an OpenPGP key block
+        if blob[4] == 2 {
+            let block = u32_at(blob, 8)
+                .zip(u32_at(blob, 12))
+                .and_then(|(offset, size)| blob.get(offset..offset.checked_add(size)?));
+            match block {
+                Some(block) => blocks.push(block.to_vec()),
+                None => return Some(Err(invalid())),
+            }
+        }
+        rest = &rest[len..];
+    }
+    Some(Ok(blocks))
+}
+
+impl Keyring {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// The key blocks, as accepted by
+    /// [`crate::DebRepo::fetch_verify_release_with_keys`].
+    pub fn keys(&self) -> impl Iterator<Item = &[u8]> {
+        self.keys.iter().map(|key| key.as_slice())
+    }
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+    /// Adds the keys of an ascii-armored file, a binary keyring or a keybox.
+    pub fn add(&mut self, data: impl Into<Vec<u8>>) -> io::Result<()> {
+        let data = data.into();
+        match keybox_blocks(&data) {
+            Some(blocks) => self.keys.extend(blocks?),
+            None => self.keys.push(data),
+        }
+        Ok(())
+    }
+    /// Adds the keys of the file at `path`.
+    pub async fn load_file(&mut self, path: impl AsRef<Path>) -> io::Result<()> {
+        let data = fs::read(path.as_ref()).await?;
+        self.add(data).map_err(|err| {
+            io::Error::new(err.kind(), format!("{}: {}", path.as_ref().display(), err))
+        })
+    }
+    /// Adds the keys of the `.gpg` and `.asc` files of the directory `dir`,
+    /// laid out as `trusted.gpg.d`. A missing directory is ignored.
+    pub async fn load_dir(&mut self, dir: impl AsRef<Path>) -> io::Result<()> {
+        let mut files = vec![];
+        match fs::read_dir(dir.as_ref()).await {
+            Ok(mut dir) => {
+                while let Some(entry) = dir.next().await {
+                    let path = entry?.path();
+                    if path
+                        .extension()
+                        .is_some_and(|ext| ext == "gpg" || ext == "asc")
+                    {
+                        files.push(path);
+                    }
+                }
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+        files.sort();
+        for file in files {
+            self.load_file(file).await?;
+        }
+        Ok(())
+    }
+    /// Loads the keys trusted by apt for all the sources,
+    /// `/etc/apt/trusted.gpg` and `/etc/apt/trusted.gpg.d`.
+    pub async fn load_system() -> io::Result<Self> {
+        let mut keyring = Self::new();
+        match keyring.load_file("/etc/apt/trusted.gpg").await {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+            _ => {}
+        }
+        keyring.load_dir("/etc/apt/trusted.gpg.d").await?;
+        Ok(keyring)
+    }
+    /// The fingerprints of the primary keys.
+    pub fn fingerprints(&self) -> Result<Vec<String>> {
+        let (mut ctx, _home) = keyring_context(self.keys())?;
+        let mut fingerprints = vec![];
+        for key in ctx.keys()? {
+            if let Ok(fingerprint) = key?.fingerprint() {
+                fingerprints.push(fingerprint.to_string());
+            }
+        }
+        Ok(fingerprints)
+    }
+    /// The keys with one of the `fingerprints`, of the key or of one of its
+    /// subkeys. Fingerprints are matched regardless of case and spaces.
+    pub fn select<F: AsRef<str>>(&self, fingerprints: &[F]) -> Result<Keyring> {
+        let wanted: Vec<String> = fingerprints
+            .iter()
+            .map(|f| {
+                f.as_ref()
+                    .chars()
+                    .filter(|c| !c.is_whitespace() && *c != '!')
+                    .collect::<String>()
+                    .to_ascii_uppercase()
+            })
+            .collect();
+        let (mut ctx, _home) = keyring_context(self.keys())?;
+        let keys = ctx
+            .keys()?
+            .filter_map(|key| key.ok())
+            .filter(|key| {
+                key.subkeys().any(|subkey| {
+                    subkey
+                        .fingerprint()
+                        .is_ok_and(|f| wanted.iter().any(|w| w.eq_ignore_ascii_case(f)))
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut selected = Self::new();
+        if !keys.is_empty() {
+            let mut data = vec![];
+            ctx.export_keys(&keys, gpgme::ExportMode::empty(), &mut data)?;
+            selected.keys.push(data);
+        }
+        Ok(selected)
+    }
+    /// Scopes the keys to the `signed-by` option of a source, whose values
+    /// are keyring files, ascii-armored keys or fingerprints of keys of this
+    /// keyring. Without `signed-by`, all the keys are kept.
+    pub async fn signed_by<S: AsRef<str>>(&self, signed_by: &[S]) -> Result<Keyring> {
+        if signed_by.is_empty() {
+            return Ok(self.clone());
+        }
+        let mut keyring = Self::new();
+        let mut fingerprints = vec![];
+        for value in signed_by.iter().map(|v| v.as_ref().trim()) {
+            if value.as_bytes().starts_with(ARMOR) {
+                keyring.add(value)?;
+            } else if value.starts_with('/') {
+                keyring.load_file(value).await?;
+            } else {
+                fingerprints.push(value);
+            }
+        }
+        if !fingerprints.is_empty() {
+            let selected = self.select(&fingerprints)?;
+            if selected.is_empty() {
+                return Err(Error::Signature(format!(
+                    "no trusted key with fingerprint {}",
+                    fingerprints.join(", ")
+                )));
+            }
+            keyring.keys.extend(selected.keys);
+        }
+        Ok(keyring)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keybox(block: &[u8]) -> Vec<u8> {
+        let mut kbx = vec![0, 0, 0, 32, 1, 1, 0, 0];
+        kbx.extend(b"KBXf");
+        kbx.resize(32, 0);
+        let len = 16 + block.len() as u32;
+        kbx.extend(len.to_be_bytes());
+        kbx.extend([2, 1, 0, 0]);
+        kbx.extend(16u32.to_be_bytes());
+        kbx.extend((block.len() as u32).to_be_bytes());
+        kbx.extend(block);
+        kbx
+    }
+
+    #[test]
+    fn test_keybox() {
+        let mut keyring = Keyring::new();
+        keyring.add(keybox(b"key block")).unwrap();
+        assert_eq!(keyring.keys().collect::<Vec<_>>(), [b"key block"]);
+        let mut truncated = keybox(b"key block");
+        truncated.pop();
+        assert!(keyring.add(truncated).is_err());
+    }
+
+    #[async_std::test]
+    async fn test_signed_by() {
+        let dir = tempfile::tempdir().unwrap();
+        let armored = "-----BEGIN PGP PUBLIC KEY BLOCK-----\n\nmQINBF...\n";
+        let file = dir.path().join("example.gpg");
+        std::fs::write(&file, keybox(b"key block")).unwrap();
+        let mut keyring = Keyring::new();
+        keyring.load_dir(dir.path()).await.unwrap();
+        keyring.load_dir(dir.path().join("missing")).await.unwrap();
+        assert_eq!(keyring.keys().count(), 1);
+        let signed_by = [file.to_str().unwrap(), armored];
+        let scoped = Keyring::new().signed_by(&signed_by).await.unwrap();
+        assert_eq!(
+            scoped.keys().collect::<Vec<_>>(),
+            [&b"key block"[..], armored.trim().as_bytes()]
+        );
+        let all = keyring.signed_by::<&str>(&[]).await.unwrap();
+        assert_eq!(all.keys().count(), 1);
+    }
+}
@@ -1,55 +1,197 @@
 //! A Debian repository client library
 
+mod alternatives;
+pub mod arch;
+#[cfg(feature = "io")]
+mod auth;
+#[cfg(feature = "bench")]
+pub mod bench;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+mod buildinfo;
+#[cfg(feature = "io")]
+mod cache;
+#[cfg(feature = "io")]
+pub mod cas;
+mod changelog;
+#[cfg(feature = "io")]
+mod chunked;
+mod conffiles;
 mod control;
+#[cfg(feature = "io")]
+mod customize;
+mod dbgsym;
+#[cfg(feature = "io")]
 mod deb;
+#[cfg(feature = "io")]
+mod delta;
+mod dep11;
+#[cfg(feature = "io")]
+mod deployfs;
+mod diagnostic;
+mod diff;
 pub mod digest;
+mod diversions;
+mod dsc;
+mod error;
+#[cfg(feature = "executor")]
+pub mod executor;
+#[cfg(feature = "io")]
+mod failover;
+mod filter;
+#[cfg(feature = "io")]
+mod foreign;
+#[cfg(feature = "io")]
 mod fsrepo;
+#[cfg(feature = "io")]
 mod httprepo;
 mod idmap;
+mod installed;
+#[cfg(feature = "io")]
+mod ipfs;
+#[cfg(feature = "io")]
+mod keyring;
+mod linkgroups;
+pub mod metrics;
+mod migration;
+#[cfg(feature = "io")]
+mod mirror;
+#[cfg(feature = "mirrorlist")]
+pub mod mirrorlist;
+mod ordering;
 mod packages;
+mod phasing;
+mod plan;
+mod policy;
+pub mod pool;
+#[cfg(feature = "io")]
+mod ppa;
+mod progress;
+#[cfg(feature = "io")]
+mod publish;
+#[cfg(feature = "io")]
+mod ratelimit;
+mod relations;
+#[cfg(feature = "io")]
 mod release;
+#[cfg(feature = "io")]
 mod repo;
+#[cfg(feature = "io")]
+mod scan;
+#[cfg(feature = "io")]
+mod scripts;
+mod search;
+mod select;
+mod sources;
+mod srcindex;
+#[cfg(feature = "io")]
+mod tarball;
+mod tasks;
+#[cfg(test)]
+mod testutil;
+#[cfg(feature = "io")]
+mod transport;
+mod triggers;
 mod universe;
+mod variant;
+#[cfg(feature = "io")]
+mod verify;
 mod version;
-mod deployfs;
 
 pub use {
+    alternatives::{AlternativeChoice, ChoiceReason},
+    buildinfo::Buildinfo,
+    changelog::{Changelog, ChangelogEntry, ChangelogItem},
+    conffiles::{Conffile, ConffileAction, ConffilePolicy},
     control::{
         ControlField, ControlFile, ControlParser, ControlStanza, MutableControlField,
         MutableControlFile, MutableControlStanza, ParseError,
     },
-    deb::{DebEntry, DebReader, Tarball, TarballEntry, TarballEntryType},
-    fsrepo::FSDebRepo,
-    httprepo::HttpDebRepo,
-    packages::{Package, Packages},
-    release::Release,
-    repo::{null_provider, DebRepo, DebRepoProvider, DEBIAN_KEYRING},
+    dbgsym::DebugSymbols,
+    dep11::{Component, Components, Icon, IconKind, Localized},
+    diagnostic::Diagnostic,
+    diff::{diff, IndexDiff, PackageChange},
+    diversions::{Diversion, Diversions},
+    dsc::{Dsc, DscFile},
+    error::{Error, Result},
+    filter::{PackageFilter, Section, Tag},
+    idmap::{Interner, MapStats},
+    installed::Installed,
+    linkgroups::{LinkAlternative, LinkGroup, SlaveLink},
+    migration::Migration,
+    ordering::{CandidateOrdering, SolveObjective},
+    packages::{MultiArch, Package, PackageEditor, PackageKind, Packages, ParseMode, Priority},
+    phasing::Phasing,
+    plan::{Plan, PlanAction, PlanItem, PlanPhase},
+    policy::{IndexRelease, Pin, PinTarget, Policy},
+    progress::{NoProgress, Progress},
+    relations::{Relation, Relations},
     resolvo::{NameId, SolvableId, StringId},
-    universe::Universe,
-    version::{Constraint, Dependency, Version},
+    search::{SearchHit, SearchIndex, SearchQuery, SearchRank, Translations},
+    sources::{ListOption, SourceEntry, SourceKind, SourceOptions, SourcesList},
+    srcindex::{BinarySource, SourceGroup, SourceGroups, SourcePackage, SourcesIndex},
+    tasks::{Task, TaskPackages},
+    triggers::{Trigger, TriggerStep, Triggers},
+    universe::{
+        CandidateCacheStats, MemoryStats, SolveOptions, SolveStats, Universe, UniverseBuilder,
+        UniverseSnapshot,
+    },
+    variant::Variant,
+    version::{
+        compare_versions, Constraint, Dependency, Restrictions, Version, VersionRange, VersionSet,
+    },
+};
+
+#[cfg(feature = "io")]
+pub use {
+    auth::{AuthConf, Credentials},
+    cache::{Cached, OfflineMode},
+    chunked::{Chunk, ChunkManifest, Chunked, CHUNK_MANIFEST_SUFFIX},
+    customize::{Customization, PathFilter, UnpackHook},
+    deb::{
+        scan_deb, DebEntry, DebReader, PoolLayout, Tarball, TarballEntry, TarballEntryType,
+        VerifyExtract,
+    },
+    delta::{DeltaFetcher, Fetched},
     deployfs::{DeploymentFileSystem, LocalFileSystem},
+    failover::{is_transient, Attempt, Failover, FailoverError, RetryPolicy},
+    foreign::{Emulator, QemuUser},
+    fsrepo::FSDebRepo,
+    httprepo::{HttpDebRepo, HttpStatusError, Proxy, Tls},
+    ipfs::{ipfs_transport, ipfs_url},
+    keyring::Keyring,
+    mirror::{Mirror, MirrorFilter, MirrorReport},
+    ppa::{ppa_signing_key, ppa_url},
+    publish::{Publisher, Retention},
+    ratelimit::{RateLimited, RateLimitedReader, RateLimiter},
+    release::{FreshnessPolicy, Release, StaleRelease},
+    repo::{null_provider, DebRepo, DebRepoProvider, DEBIAN_KEYRING},
+    scan::{ScanCache, Scanner},
+    scripts::{ChrootScripts, MaintainerScripts, ScriptHook, ScriptKind, SkipScripts},
+    tarball::{write_layer, write_tarball, Layer, TarOptions},
+    transport::{Transport, TransportResponse},
+    verify::VerifiedFile,
 };
 
-pub(crate) fn parse_size(str: &[u8]) -> async_std::io::Result<usize> {
+pub(crate) fn parse_size(str: &[u8]) -> std::io::Result<usize> {
     let mut result: usize = 0;
     for &byte in str {
         if byte == b' ' {
             break;
         }
         if byte < b'0' || byte > b'9' {
-            return Err(async_std::io::Error::new(
-                async_std::io::ErrorKind::InvalidData,
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
                 "not a digit",
             ));
         }
         result = result
             .checked_mul(10)
             .and_then(|res| res.checked_add((byte - b'0') as usize))
-            .ok_or(async_std::io::Error::new(
-                async_std::io::ErrorKind::InvalidData,
+            .ok_or(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
                 "size overflow",
             ))?;
     }
     Ok(result)
 }
-
@@ -0,0 +1,191 @@
+//! update-alternatives state
+//!
+//! A link group of update-alternatives, e.g. `editor`, makes a generic path
+//! such as `/usr/bin/editor` point through `/etc/alternatives/editor` to one
+//! of several alternatives, along with its slave links such as the manual
+//! page. The state of the group `name` lives in
+//! `/var/lib/dpkg/alternatives/name`:
+//!
+//! ```text
+//! auto
+//! /usr/bin/editor
+//! editor.1.gz
+//! /usr/share/man/man1/editor.1.gz
+//!
+//! /bin/nano
+//! 40
+//! /usr/share/man/man1/nano.1.gz
+//!
+//! ```
+
+use {crate::control::ParseError, std::fmt};
+
+/// A secondary link of a link group, following the selected alternative.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SlaveLink {
+    pub name: String,
+    pub link: String,
+}
+
+/// An alternative of a link group.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LinkAlternative {
+    pub path: String,
+    pub priority: i32,
+    /// The targets of the slave links of the group, in the same order,
+    /// None where the alternative has none
+    pub slaves: Vec<Option<String>>,
+}
+
+/// A link group of update-alternatives.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LinkGroup {
+    pub name: String,
+    /// False if the alternative was selected manually
+    pub auto: bool,
+    /// The generic path, e.g. `/usr/bin/editor`
+    pub link: String,
+    pub slaves: Vec<SlaveLink>,
+    pub alternatives: Vec<LinkAlternative>,
+}
+
+impl LinkGroup {
+    pub fn new(name: impl Into<String>, link: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            auto: true,
+            link: link.into(),
+            slaves: vec![],
+            alternatives: vec![],
+        }
+    }
+    /// Parses the state file of the group `name`.
+    pub fn parse(name: &str, src: &str) -> Result<Self, ParseError> {
+        let mut lines = src.lines();
+        let mut next = |what: &str| {
+            lines
+                .next()
+                .ok_or_else(|| ParseError::from(format!("alternatives {}: no {}", name, what)))
+        };
+        let auto = match next("mode")? {
+            "auto" => true,
+            "manual" => false,
+            mode => return Err(format!("alternatives {}: invalid mode {}", name, mode).into()),
+        };
+        let mut group = Self::new(name, next("link")?);
+        group.auto = auto;
+        loop {
+            match next("slave")? {
+                "" => break,
+                slave => group.slaves.push(SlaveLink {
+                    name: slave.to_string(),
+                    link: next("slave link")?.to_string(),
+                }),
+            }
+        }
+        loop {
+            let path = match next("alternative")? {
+                "" => break,
+                path => path.to_string(),
+            };
+            let priority = next("priority")?.parse().map_err(|err| {
+                ParseError::from(format!("alternatives {}: invalid priority: {}", name, err))
+            })?;
+            let slaves = (0..group.slaves.len())
+                .map(|_| next("slave path").map(|s| Some(s.to_string()).filter(|s| !s.is_empty())))
+                .collect::<Result<_, _>>()?;
+            group.alternatives.push(LinkAlternative {
+                path,
+                priority,
+                slaves,
+            });
+        }
+        Ok(group)
+    }
+    /// The alternative selected in auto mode, with the highest priority.
+    pub fn best(&self) -> Option<&LinkAlternative> {
+        self.alternatives
+            .iter()
+            .rev()
+            .max_by_key(|alt| alt.priority)
+    }
+    /// The links set up for the alternative `path`: pairs of the generic
+    /// path, linked to `/etc/alternatives/<name>`, and the target of that
+    /// link, for the master and the slaves the alternative provides.
+    pub fn links(&self, path: &str) -> Vec<(&str, &str)> {
+        let Some(alt) = self.alternatives.iter().find(|alt| alt.path == path) else {
+            return vec![];
+        };
+        std::iter::once((self.link.as_str(), alt.path.as_str()))
+            .chain(
+                self.slaves
+                    .iter()
+                    .zip(&alt.slaves)
+                    .filter_map(|(slave, target)| Some((slave.link.as_str(), target.as_deref()?))),
+            )
+            .collect()
+    }
+    /// Returns true if `path` is one of the links of the group, which
+    /// packages may share through update-alternatives.
+    pub fn owns_link(&self, path: &str) -> bool {
+        self.link == path || self.slaves.iter().any(|slave| slave.link == path)
+    }
+}
+
+impl fmt::Display for LinkGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", if self.auto { "auto" } else { "manual" })?;
+        writeln!(f, "{}", self.link)?;
+        for slave in &self.slaves {
+            writeln!(f, "{}\n{}", slave.name, slave.link)?;
+        }
+        writeln!(f)?;
+        for alt in &self.alternatives {
+            writeln!(f, "{}\n{}", alt.path, alt.priority)?;
+            for slave in &alt.slaves {
+                writeln!(f, "{}", slave.as_deref().unwrap_or_default())?;
+            }
+        }
+        writeln!(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_link_group() {
+        let src = "\
+auto
+/usr/bin/editor
+editor.1.gz
+/usr/share/man/man1/editor.1.gz
+
+/bin/nano
+40
+/usr/share/man/man1/nano.1.gz
+/usr/bin/vim.basic
+30
+
+
+";
+        let group = LinkGroup::parse("editor", src).unwrap();
+        assert_eq!(group.alternatives.len(), 2);
+        assert_eq!(group.best().unwrap().path, "/bin/nano");
+        assert_eq!(
+            group.links("/bin/nano"),
+            [
+                ("/usr/bin/editor", "/bin/nano"),
+                (
+                    "/usr/share/man/man1/editor.1.gz",
+                    "/usr/share/man/man1/nano.1.gz"
+                )
+            ]
+        );
+        assert_eq!(group.links("/usr/bin/vim.basic").len(), 1);
+        assert!(group.owns_link("/usr/share/man/man1/editor.1.gz"));
+        assert_eq!(group.to_string(), src);
+        assert!(LinkGroup::parse("editor", "auto\n/usr/bin/editor\n").is_err());
+    }
+}
@@ -0,0 +1,106 @@
+//! Metrics hooks
+//!
+//! The library counts notable events through the [`Metrics`] recorder
+//! installed with [`set_metrics`], e.g. an adapter to the metrics system of
+//! the service embedding it. Without a recorder, counting is a no-op.
+//!
+//! Timings and sizes of individual operations are reported as fields of
+//! the `solve`, `universe`, `parse` and `fetch` tracing spans instead.
+
+use std::sync::OnceLock;
+
+/// The events counted by the library.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Counter {
+    /// Files requested from a repository
+    Downloads,
+    /// Bytes read from a repository
+    DownloadedBytes,
+    /// Files already present and verified, so not downloaded again
+    CacheHits,
+    /// Files not matching their expected digest
+    HashFailures,
+}
+
+impl Counter {
+    pub const ALL: [Counter; 4] = [
+        Counter::Downloads,
+        Counter::DownloadedBytes,
+        Counter::CacheHits,
+        Counter::HashFailures,
+    ];
+    /// The conventional metric name, e.g. `debrepo_downloads_total`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Counter::Downloads => "debrepo_downloads_total",
+            Counter::DownloadedBytes => "debrepo_downloaded_bytes_total",
+            Counter::CacheHits => "debrepo_cache_hits_total",
+            Counter::HashFailures => "debrepo_hash_failures_total",
+        }
+    }
+}
+
+impl std::fmt::Display for Counter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A recorder of the library counters.
+pub trait Metrics: Send + Sync {
+    /// Adds `value` to `counter`.
+    fn increment(&self, counter: Counter, value: u64);
+}
+
+static METRICS: OnceLock<Box<dyn Metrics>> = OnceLock::new();
+
+/// Installs the process-wide recorder. It can only be installed once; the
+/// recorder is handed back if one already is.
+pub fn set_metrics<M: Metrics + 'static>(metrics: M) -> Result<(), M> {
+    let mut metrics = Some(metrics);
+    METRICS.get_or_init(|| Box::new(metrics.take().unwrap()));
+    match metrics {
+        Some(metrics) => Err(metrics),
+        None => Ok(()),
+    }
+}
+
+pub(crate) fn increment(counter: Counter, value: u64) {
+    if let Some(metrics) = METRICS.get() {
+        metrics.increment(counter, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        std::sync::atomic::{AtomicU64, Ordering},
+    };
+
+    #[derive(Default)]
+    struct Recorder([AtomicU64; 4]);
+
+    impl Metrics for &'static Recorder {
+        fn increment(&self, counter: Counter, value: u64) {
+            let index = Counter::ALL.iter().position(|c| *c == counter).unwrap();
+            self.0[index].fetch_add(value, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn test_metrics() {
+        let recorder: &'static Recorder = Box::leak(Box::default());
+        // the recorder is process-wide, other tests may count as well
+        let hits = || recorder.0[2].load(Ordering::Relaxed);
+        set_metrics(recorder).ok().unwrap();
+        assert!(set_metrics(recorder).is_err());
+        let before = hits();
+        increment(Counter::CacheHits, 2);
+        assert!(hits() >= before + 2);
+        assert_eq!(
+            Counter::HashFailures.to_string(),
+            "debrepo_hash_failures_total"
+        );
+    }
+}
@@ -0,0 +1,207 @@
+//! Suite migrations
+//!
+//! [`Universe::migrate`] plans the dist-upgrade of a system from one suite
+//! to the next, e.g. from bookworm to trixie. The two suites are separate
+//! universes, so packages are matched across them by name: the installed
+//! versions are looked up in the universe of the old suite, their upgrades
+//! in the universe of the new one.
+//!
+//! ```ignore
+//! let mut trixie = Universe::new("amd64", trixie_packages)?;
+//! trixie.set_installed(installed);
+//! let migration = trixie.migrate(&bookworm)?;
+//! print!("{}", migration.plan.render_apt_style());
+//! ```
+
+use {
+    crate::{
+        error::Error,
+        plan::{Plan, PlanAction, PlanItem},
+        universe::Universe,
+        version::{Dependency, Version},
+    },
+    resolvo::SolvableId,
+    std::collections::HashSet,
+};
+
+/// A dist-upgrade of the installed packages to another suite.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Migration {
+    pub plan: Plan,
+    /// The installed packages missing from the new suite, left installed
+    /// unless the plan removes them
+    pub obsolete: Vec<String>,
+    /// The installed packages left at their version because their new
+    /// version cannot be installed along with the rest
+    pub held_back: Vec<String>,
+}
+
+impl<S: AsRef<str> + 'static> Universe<S> {
+    // Solves for the latest versions of the packages `names`.
+    fn solve_names(&mut self, names: &[&str]) -> Result<Vec<SolvableId>, Error> {
+        let requirements = names
+            .iter()
+            .map(|name| Dependency::try_from(*name))
+            .collect::<Result<Vec<_>, _>>()?;
+        let problem = self.problem(requirements, vec![], vec![]);
+        self.solve(problem).map_err(|err| self.solver_error(err))
+    }
+    /// Plans the upgrade of the installed packages, set with
+    /// [`Universe::set_installed`], to the packages of this universe.
+    /// `from` is the universe of the installed suite. The installed
+    /// packages the new versions conflict with or break are removed. When
+    /// the packages cannot all be upgraded together, they are kept one at
+    /// a time, in name order, as long as the rest remains installable.
+    pub fn migrate<T: AsRef<str> + 'static>(
+        &mut self,
+        from: &Universe<T>,
+    ) -> Result<Migration, Error> {
+        let mut names: Vec<String> = self.installed().versions().keys().cloned().collect();
+        names.sort();
+        let (available, obsolete): (Vec<String>, Vec<String>) = names
+            .into_iter()
+            .partition(|name| self.packages().any(|p| p.name() == name));
+        let mut kept: Vec<&str> = available.iter().map(String::as_str).collect();
+        let mut held_back = vec![];
+        let solution = match self.solve_names(&kept) {
+            Ok(solution) => solution,
+            Err(_) => {
+                kept.clear();
+                let mut solution = self.solve_names(&kept)?;
+                for name in &available {
+                    kept.push(name);
+                    match self.solve_names(&kept) {
+                        Ok(found) => solution = found,
+                        Err(_) => {
+                            kept.pop();
+                            held_back.push(name.clone());
+                        }
+                    }
+                }
+                solution
+            }
+        };
+        let mut plan = self.plan(&solution, &[])?;
+        let chosen: HashSet<&str> = solution.iter().map(|&id| self.package(id).name()).collect();
+        for (name, version) in self.installed().versions() {
+            if chosen.contains(name.as_str()) || plan.removed().contains(&name.as_str()) {
+                continue;
+            }
+            let installed = Version::from(version.as_str());
+            let culprit = solution.iter().map(|&id| self.package(id)).find(|p| {
+                p.conflicts_with(name, &installed)
+                    || p.breaks()
+                        .flatten()
+                        .any(|b| *b.name() == name.as_str() && !b.range().contains(&installed))
+            });
+            if let Some(culprit) = culprit {
+                plan.items.push(PlanItem {
+                    name: name.clone(),
+                    version: version.clone(),
+                    action: PlanAction::Remove(culprit.name().to_string()),
+                    requested: false,
+                    size: 0,
+                    installed_size: 0,
+                    replaced_size: 0,
+                });
+            }
+        }
+        // the installed versions are only known to the old suite
+        for item in plan.items.iter_mut() {
+            let installed = match &item.action {
+                PlanAction::Install => continue,
+                PlanAction::Upgrade(version) | PlanAction::Downgrade(version) => version,
+                PlanAction::Keep | PlanAction::Remove(_) => &item.version,
+            };
+            let installed = Version::from(installed.as_str());
+            let name = item.name.split(':').next().unwrap_or_default();
+            if let Some(package) = from
+                .packages()
+                .find(|p| p.name() == name && p.version() == installed)
+            {
+                item.replaced_size = package.installed_size().unwrap_or(0) as usize * 1024;
+            }
+        }
+        plan.items.sort_by(|a, b| a.name.cmp(&b.name));
+        self.sequence(&mut plan);
+        self.check_removals(&plan)?;
+        let removed = plan.removed();
+        held_back.retain(|name| !removed.contains(&name.as_str()));
+        Ok(Migration {
+            plan,
+            obsolete,
+            held_back,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{installed::Installed, packages::Packages},
+    };
+
+    #[test]
+    fn test_migrate() {
+        let bookworm = "\
+Package: libssl3
+Version: 3.0.15-1
+Architecture: amd64
+Installed-Size: 6000
+
+Package: openssl
+Version: 3.0.15-1
+Architecture: amd64
+Depends: libssl3
+Installed-Size: 2000
+
+Package: oldtool
+Version: 1.0
+Architecture: amd64
+Installed-Size: 10
+
+Package: legacy
+Version: 1.0
+Architecture: amd64
+Installed-Size: 20
+";
+        let trixie = "\
+Package: libssl3t64
+Version: 3.5.1-1
+Architecture: amd64
+Conflicts: libssl3
+Replaces: libssl3
+Installed-Size: 6100
+
+Package: openssl
+Version: 3.5.1-1
+Architecture: amd64
+Depends: libssl3t64
+Breaks: legacy (<< 2)
+Installed-Size: 2100
+
+Package: legacy
+Version: 1.0
+Architecture: amd64
+Depends: libssl3
+";
+        let from = Universe::new("amd64", vec![Packages::new_test(bookworm).unwrap()]).unwrap();
+        let mut to = Universe::new("amd64", vec![Packages::new_test(trixie).unwrap()]).unwrap();
+        to.set_installed(
+            Installed::new()
+                .package("libssl3", "3.0.15-1")
+                .package("openssl", "3.0.15-1")
+                .package("oldtool", "1.0")
+                .package("legacy", "1.0"),
+        );
+        let migration = to.migrate(&from).unwrap();
+        assert_eq!(migration.obsolete, ["libssl3", "oldtool"]);
+        assert_eq!(migration.plan.upgraded(), ["openssl"]);
+        assert_eq!(migration.plan.installed(), ["libssl3t64"]);
+        assert_eq!(migration.plan.removed(), ["legacy", "libssl3"]);
+        assert!(migration.held_back.is_empty());
+        // 6100 + 2100 - 6000 - 2000 - 20 KiB
+        assert_eq!(migration.plan.disk_space_change(), 180 * 1024);
+    }
+}
@@ -0,0 +1,369 @@
+//! Partial repository mirroring
+//!
+//! Downloads the indexes of a suite together with the .deb files selected by a
+//! [`MirrorFilter`] and lays them out as a regular repository, so the result
+//! can be used as an offline source (e.g. with [`crate::FSDebRepo`] or apt's
+//! `file:` method).
+//!
+//! The InRelease file of the source is verified before its hashes are
+//! trusted. When the filter does not restrict packages, components or
+//! architectures, every index it lists is mirrored verbatim, along with the
+//! InRelease file and the Release file it signs. Otherwise the indexes are
+//! rebuilt from the mirrored packages with [`Publisher`], and the Release
+//! file is signed with the [`MirrorFilter::signing_key`] if set.
+
+use {
+    crate::{
+        digest::{Digests, MultiVerifyingReader},
+        error::{Error, Result},
+        metrics::{self, Counter},
+        packages::Package,
+        pool,
+        publish::Publisher,
+        release::Release,
+        repo::DebRepo,
+        universe::Universe,
+    },
+    async_std::{
+        fs,
+        io::{self, prelude::*},
+        path::{Path, PathBuf},
+    },
+    resolvo::SolvableId,
+    std::collections::HashSet,
+};
+
+/// Selects what to mirror.
+#[derive(Clone, Debug)]
+pub struct MirrorFilter {
+    distr: String,
+    components: Vec<String>,
+    arches: Vec<String>,
+    packages: Option<HashSet<String>>,
+    files: Option<HashSet<String>>,
+    keys: Vec<Vec<u8>>,
+    insecure: bool,
+    signing_key: Option<Vec<u8>>,
+}
+
+impl MirrorFilter {
+    /// Mirrors all components and architectures of the distribution `distr`.
+    pub fn new(distr: impl Into<String>) -> Self {
+        Self {
+            distr: distr.into(),
+            components: vec![],
+            arches: vec![],
+            packages: None,
+            files: None,
+            keys: vec![],
+            insecure: false,
+            signing_key: None,
+        }
+    }
+    /// Verifies the InRelease file of the source with the listed keys
+    /// instead of the default GPG keyring.
+    pub fn keys<I: IntoIterator<Item = impl Into<Vec<u8>>>>(mut self, keys: I) -> Self {
+        self.keys = keys.into_iter().map(|k| k.into()).collect();
+        self
+    }
+    /// Mirrors the unsigned Release file of the source, skipping the
+    /// verification of its signature.
+    pub fn insecure(mut self) -> Self {
+        self.insecure = true;
+        self
+    }
+    /// Sets the OpenPGP secret key used to sign the rebuilt Release file.
+    pub fn signing_key(mut self, key: impl Into<Vec<u8>>) -> Self {
+        self.signing_key.replace(key.into());
+        self
+    }
+    /// Restricts the mirror to the listed components.
+    pub fn components<I: IntoIterator<Item = impl Into<String>>>(mut self, comps: I) -> Self {
+        self.components = comps.into_iter().map(|c| c.into()).collect();
+        self
+    }
+    /// Restricts the mirror to the listed architectures.
+    pub fn arches<I: IntoIterator<Item = impl Into<String>>>(mut self, arches: I) -> Self {
+        self.arches = arches.into_iter().map(|a| a.into()).collect();
+        self
+    }
+    /// Restricts the mirror to the packages with the listed names.
+    pub fn packages<I: IntoIterator<Item = impl Into<String>>>(mut self, names: I) -> Self {
+        self.packages
+            .get_or_insert_with(HashSet::new)
+            .extend(names.into_iter().map(|n| n.into()));
+        self
+    }
+    /// Restricts the mirror to the exact packages of a solution.
+    pub fn solution<S: AsRef<str> + 'static>(
+        mut self,
+        universe: &Universe<S>,
+        solution: &[SolvableId],
+    ) -> io::Result<Self> {
+        let files = self.files.get_or_insert_with(HashSet::new);
+        for id in solution {
+            let (path, _, _) = universe.package(*id).repo_file()?;
+            files.insert(path.to_owned());
+        }
+        Ok(self)
+    }
+    fn is_restricted(&self) -> bool {
+        self.packages.is_some()
+            || self.files.is_some()
+            || !self.components.is_empty()
+            || !self.arches.is_empty()
+    }
+    fn matches(&self, pkg: &Package<'_>) -> bool {
+        self.packages
+            .as_ref()
+            .is_none_or(|names| names.contains(pkg.name()))
+            && self.files.as_ref().is_none_or(|files| {
+                pkg.field("Filename")
+                    .is_some_and(|path| files.contains(path.trim()))
+            })
+    }
+}
+
+/// Summary of a mirror synchronization.
+#[derive(Default, Clone, Debug)]
+pub struct MirrorReport {
+    /// Number of mirrored packages
+    pub packages: usize,
+    /// Number of packages downloaded during this run
+    pub downloaded: usize,
+    /// Number of bytes downloaded during this run
+    pub bytes: u64,
+}
+
+pub struct Mirror;
+
+impl Mirror {
+    /// Synchronizes the local mirror at `dst` with `src` according to `filter`.
+    /// Every downloaded file is verified against the index hashes; already
+    /// present .deb files are re-hashed and downloaded again on mismatch.
+    pub async fn sync(
+        src: &DebRepo,
+        dst: impl AsRef<Path>,
+        filter: &MirrorFilter,
+    ) -> Result<MirrorReport> {
+        let dst = dst.as_ref();
+        let distr = &filter.distr;
+        let base = format!("dists/{}", distr);
+        // the signed files, as verified
+        let mut signed = vec![];
+        let release = if filter.insecure {
+            let data = src.fetch(&format!("{}/Release", &base)).await?;
+            let text = String::from_utf8(data)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            signed.push(("Release", text.clone().into_bytes()));
+            Release::new(src.clone(), distr, text.into_boxed_str())?
+        } else {
+            let data = src.fetch(&format!("{}/InRelease", &base)).await?;
+            let (release, text) = src.verify_inrelease(distr, data.clone(), &filter.keys)?;
+            signed.push(("InRelease", data));
+            signed.push(("Release", text.into_bytes()));
+            release
+        };
+        let components: Vec<&str> = if filter.components.is_empty() {
+            release.components().collect()
+        } else {
+            filter.components.iter().map(|c| c.as_str()).collect()
+        };
+        let arches: Vec<&str> = if filter.arches.is_empty() {
+            release.architectures().collect()
+        } else {
+            filter.arches.iter().map(|a| a.as_str()).collect()
+        };
+        let mut report = MirrorReport::default();
+        let mut publisher = Publisher::new(dst, distr.clone());
+        if let Some(codename) = release.codename() {
+            publisher.codename(codename);
+        }
+        if let Some(origin) = release.origin() {
+            publisher.origin(origin);
+        }
+        if let Some(label) = release.label() {
+            publisher.label(label);
+        }
+        publisher.architectures(arches.iter().copied());
+        if let Some(key) = &filter.signing_key {
+            publisher.signing_key(key.clone());
+        }
+        let mut seen = HashSet::new();
+        for comp in components.iter() {
+            for arch in arches.iter() {
+                if release.packages_file(comp, arch).is_none() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("no Packages index for {}/{} in {}", comp, arch, distr),
+                    )
+                    .into());
+                }
+                let packages = release.fetch_packages(comp, arch).await?;
+                for pkg in packages.packages().filter(|pkg| filter.matches(pkg)) {
+                    let (path, size, hash) = pkg.repo_file()?;
+                    if seen.insert(path.to_owned()) {
+                        report.packages += 1;
+                        if let Some(bytes) = mirror_file(src, dst, path, size, hash).await? {
+                            report.downloaded += 1;
+                            report.bytes += bytes;
+                        }
+                        if filter.is_restricted() {
+                            publisher.add_package(comp, pkg.into())?;
+                        }
+                    }
+                }
+            }
+        }
+        if filter.is_restricted() {
+            publisher.publish().await?;
+        } else {
+            // every index listed, so that the Release file holds for the
+            // mirror; the variants missing from the source, usually the
+            // uncompressed ones, are left out
+            for (path, size, hash) in release.repo_files() {
+                match mirror_file(src, dst, &path, size, hash).await {
+                    Ok(_) | Err(Error::NotFound(_)) => {}
+                    Err(err) => return Err(err),
+                }
+            }
+            for (name, data) in signed {
+                write_file(&target_path(dst, &format!("{}/{}", &base, name))?, &data).await?;
+            }
+        }
+        Ok(report)
+    }
+}
+
+fn target_path(dst: &Path, path: &str) -> io::Result<PathBuf> {
+    pool::validate_filename(path)?;
+    Ok(dst.join(path))
+}
+
+async fn write_file(path: &Path, data: &[u8]) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).await?;
+    }
+    fs::write(path, data).await
+}
+
+// Copies the file unless a verified copy is already present. Returns the
+// number of downloaded bytes.
+async fn mirror_file(
+    src: &DebRepo,
+    dst: &Path,
+    path: &str,
+    size: usize,
+    hash: Digests,
+) -> Result<Option<u64>> {
+    let target = target_path(dst, path)?;
+    if let Ok(file) = fs::File::open(&target).await {
+        let mut sink = io::sink();
+        if io::copy(
+            MultiVerifyingReader::new(file, size, hash.clone()),
+            &mut sink,
+        )
+        .await
+        .is_ok()
+        {
+            metrics::increment(Counter::CacheHits, 1);
+            return Ok(None);
+        }
+    }
+    if let Some(dir) = target.parent() {
+        fs::create_dir_all(dir).await?;
+    }
+    let mut tmp = target.clone().into_os_string();
+    tmp.push(".partial");
+    let tmp = PathBuf::from(tmp);
+    let mut out = fs::File::create(&tmp).await?;
+    let copied = src.copy_verify(&mut out, path, size, hash).await;
+    out.flush().await?;
+    match copied {
+        Ok(bytes) => {
+            fs::rename(&tmp, &target).await?;
+            Ok(Some(bytes))
+        }
+        Err(err) => {
+            fs::remove_file(&tmp).await.ok();
+            Err(err)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{control::MutableControlStanza, fsrepo::FSDebRepo};
+    use digest::Digest;
+    use futures::StreamExt;
+
+    async fn source_repo(dir: &Path) {
+        let mut publisher = Publisher::new(dir, "stable");
+        for name in ["alpha", "beta"] {
+            let path = format!("pool/main/{}_1.0_amd64.deb", name);
+            let data = format!("{} contents", name);
+            write_file(&dir.join(&path), data.as_bytes()).await.unwrap();
+            let mut stanza = MutableControlStanza::new();
+            stanza
+                .set("Package", name)
+                .set("Version", "1.0")
+                .set("Architecture", "amd64")
+                .set("Filename", path)
+                .set("Size", data.len().to_string())
+                .set("SHA256", hex::encode(sha2::Sha256::digest(data.as_bytes())));
+            publisher.add_package("main", stanza).unwrap();
+        }
+        publisher.publish().await.unwrap();
+    }
+
+    #[async_std::test]
+    async fn test_partial_mirror() {
+        let src_dir = tempfile::tempdir().unwrap();
+        let dst_dir = tempfile::tempdir().unwrap();
+        source_repo(src_dir.path().into()).await;
+        let src: DebRepo = FSDebRepo::new(src_dir.path()).await.unwrap().into();
+        let filter = MirrorFilter::new("stable").insecure().packages(["beta"]);
+        let report = Mirror::sync(&src, dst_dir.path(), &filter).await.unwrap();
+        assert_eq!(report.packages, 1);
+        assert_eq!(report.downloaded, 1);
+
+        let report = Mirror::sync(&src, dst_dir.path(), &filter).await.unwrap();
+        assert_eq!(report.downloaded, 0);
+
+        let dst: DebRepo = FSDebRepo::new(dst_dir.path()).await.unwrap().into();
+        let release = dst.fetch_release("stable").await.unwrap();
+        let packages = release.fetch_packages("main", "amd64").await.unwrap();
+        let names = packages.packages().map(|p| p.name()).collect::<Vec<_>>();
+        assert_eq!(names, ["beta"]);
+        assert!(!dst_dir
+            .path()
+            .join("pool/main/alpha_1.0_amd64.deb")
+            .exists());
+    }
+
+    #[async_std::test]
+    async fn test_full_mirror() {
+        let src_dir = tempfile::tempdir().unwrap();
+        let dst_dir = tempfile::tempdir().unwrap();
+        source_repo(src_dir.path().into()).await;
+        let src: DebRepo = FSDebRepo::new(src_dir.path()).await.unwrap().into();
+        let filter = MirrorFilter::new("stable").insecure();
+        let report = Mirror::sync(&src, dst_dir.path(), &filter).await.unwrap();
+        assert_eq!(report.packages, 2);
+
+        // the Release file of the source holds for every index of the mirror
+        let read = |dir: &Path, path: &str| std::fs::read(dir.join(path)).unwrap();
+        assert_eq!(
+            read(dst_dir.path().into(), "dists/stable/Release"),
+            read(src_dir.path().into(), "dists/stable/Release")
+        );
+        let dst: DebRepo = FSDebRepo::new(dst_dir.path()).await.unwrap().into();
+        let release = dst.fetch_release("stable").await.unwrap();
+        let files: Vec<_> = dst.verify(&release, true).collect().await;
+        assert!(files.iter().all(|file| file.is_ok()));
+        assert!(files
+            .iter()
+            .any(|file| file.path == "dists/stable/main/binary-amd64/Packages.gz"));
+    }
+}
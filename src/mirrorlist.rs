@@ -0,0 +1,192 @@
+//! Debian mirror selection
+//!
+//! The Debian mirrors are listed in `Mirrors.masterlist` of the mirror team,
+//! a control file with a stanza per site. [`MirrorList`] loads it, falling
+//! back to the `deb.debian.org` CDN when the list cannot be fetched, and ranks
+//! the mirrors by the time to the first byte of a small file, so that the
+//! nearest ones come first in the [`Failover`] list:
+//!
+//! ```ignore
+//! let mirrors = MirrorList::load().await.country(["DE"]);
+//! let probe = "dists/bookworm/Release";
+//! let failover = mirrors.failover(probe, 3, Duration::from_secs(2)).await?;
+//! let repo: DebRepo = failover.into();
+//! ```
+
+use {
+    crate::{
+        control::ControlFile, error::Result, failover::Failover, httprepo::HttpDebRepo,
+        repo::DebRepo, transport::Transport,
+    },
+    async_std::{future::timeout, io::prelude::*},
+    futures::future::join_all,
+    std::time::{Duration, Instant},
+};
+
+/// The `deb.debian.org` CDN, redirecting to a nearby mirror.
+pub const DEBIAN_CDN: &str = "https://deb.debian.org/debian/";
+
+const MASTERLIST_URL: &str = "https://salsa.debian.org/mirror-team/masterlist/-/raw/master/";
+
+/// A Debian archive mirror.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MirrorSite {
+    /// Host name of the site
+    pub site: String,
+    /// Two-letter country code, if listed
+    pub country: Option<String>,
+    /// URL of the archive
+    pub url: String,
+}
+
+/// A list of Debian archive mirrors.
+#[derive(Clone, Debug, Default)]
+pub struct MirrorList {
+    pub mirrors: Vec<MirrorSite>,
+}
+
+impl MirrorList {
+    /// Parses `Mirrors.masterlist`. Sites without an HTTP archive are
+    /// skipped; the ones serving https are listed with an https URL.
+    pub fn parse(data: &str) -> Result<Self> {
+        let mirrors = ControlFile::parse(data)?
+            .stanzas()
+            .filter_map(|stanza| {
+                let site = stanza.field("Site")?;
+                let (scheme, path) = match stanza.field("Archive-https") {
+                    Some(path) => ("https", path),
+                    None => ("http", stanza.field("Archive-http")?),
+                };
+                let path = match path.trim_matches('/') {
+                    "" => String::new(),
+                    path => format!("{}/", path),
+                };
+                Some(MirrorSite {
+                    site: site.to_string(),
+                    country: stanza
+                        .field("Country")
+                        .and_then(|country| country.split_whitespace().next())
+                        .map(str::to_ascii_uppercase),
+                    url: format!("{}://{}/{}", scheme, site, path),
+                })
+            })
+            .collect();
+        Ok(Self { mirrors })
+    }
+    /// The list with the CDN only.
+    pub fn cdn() -> Self {
+        Self {
+            mirrors: vec![MirrorSite {
+                site: "deb.debian.org".to_string(),
+                country: None,
+                url: DEBIAN_CDN.to_string(),
+            }],
+        }
+    }
+    /// Fetches the mirror list.
+    pub async fn fetch() -> Result<Self> {
+        let repo: DebRepo = HttpDebRepo::new(MASTERLIST_URL).await?.into();
+        let data = repo.fetch("Mirrors.masterlist").await?;
+        Self::parse(&String::from_utf8_lossy(&data))
+    }
+    /// Fetches the mirror list, or falls back to the CDN if it cannot be
+    /// fetched.
+    pub async fn load() -> Self {
+        match Self::fetch().await {
+            Ok(list) if !list.mirrors.is_empty() => list,
+            Ok(_) => Self::cdn(),
+            Err(err) => {
+                tracing::warn!(
+                    "failed to fetch the mirror list, using {}: {}",
+                    DEBIAN_CDN,
+                    err
+                );
+                Self::cdn()
+            }
+        }
+    }
+    /// Keeps the mirrors of the listed countries, by two-letter code.
+    pub fn country<S: AsRef<str>>(mut self, countries: impl IntoIterator<Item = S>) -> Self {
+        let countries: Vec<String> = countries
+            .into_iter()
+            .map(|c| c.as_ref().to_ascii_uppercase())
+            .collect();
+        self.mirrors
+            .retain(|m| m.country.as_ref().is_some_and(|c| countries.contains(c)));
+        self
+    }
+    /// Measures the time to the first byte of `probe`, a path relative to
+    /// the archive root, on every mirror concurrently. Returns the mirrors
+    /// that answered within `limit`, fastest first.
+    pub async fn rank(&self, probe: &str, limit: Duration) -> Vec<(MirrorSite, Duration)> {
+        let probes = self.mirrors.iter().map(|mirror| async move {
+            let repo = HttpDebRepo::new(&mirror.url).await.ok()?;
+            let start = Instant::now();
+            let mut byte = [0u8];
+            let first_byte = async {
+                let mut rsp = repo.get(probe, Some(0..1)).await?;
+                rsp.reader.read_exact(&mut byte).await
+            };
+            match timeout(limit, first_byte).await {
+                Ok(Ok(())) => Some((mirror.clone(), start.elapsed())),
+                Ok(Err(err)) => {
+                    tracing::debug!("mirror {} failed: {}", mirror.url, err);
+                    None
+                }
+                Err(_) => None,
+            }
+        });
+        let mut ranked: Vec<_> = join_all(probes).await.into_iter().flatten().collect();
+        ranked.sort_by_key(|(_, elapsed)| *elapsed);
+        ranked
+    }
+    /// A [`Failover`] over the `count` fastest mirrors, see
+    /// [`MirrorList::rank`], followed by the CDN.
+    pub async fn failover(&self, probe: &str, count: usize, limit: Duration) -> Result<Failover> {
+        let mut failover = Failover::new();
+        for (mirror, _) in self.rank(probe, limit).await.into_iter().take(count) {
+            if mirror.url != DEBIAN_CDN {
+                failover = failover.mirror(HttpDebRepo::new(&mirror.url).await?);
+            }
+        }
+        Ok(failover.mirror(HttpDebRepo::new(DEBIAN_CDN).await?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_masterlist() {
+        let data = "\
+Site: ftp.de.debian.org
+Type: Push-Primary
+Archive-architecture: amd64 arm64 i386
+Archive-http: /debian/
+Country: DE Germany
+
+Site: mirror.example.org
+Type: leaf
+Archive-https: /pub/debian/
+Country: fr France
+
+Site: ftp.example.net
+Type: leaf
+CDImage-http: /cdimage/
+";
+        let list = MirrorList::parse(data).unwrap();
+        let urls: Vec<_> = list.mirrors.iter().map(|m| m.url.as_str()).collect();
+        assert_eq!(
+            urls,
+            [
+                "http://ftp.de.debian.org/debian/",
+                "https://mirror.example.org/pub/debian/"
+            ]
+        );
+        let list = list.country(["FR"]);
+        assert_eq!(list.mirrors.len(), 1);
+        assert_eq!(list.mirrors[0].country.as_deref(), Some("FR"));
+        assert_eq!(MirrorList::cdn().mirrors[0].url, DEBIAN_CDN);
+    }
+}
@@ -0,0 +1,110 @@
+//! Solver preferences
+//!
+//! When several packages satisfy a requirement, the solver tries them in the
+//! order given by a [`CandidateOrdering`]. Orderings are chained to break
+//! ties, e.g. to keep the installed versions and otherwise upgrade:
+//!
+//! ```ignore
+//! universe.set_candidate_ordering(CandidateOrdering::Chain(vec![
+//!     CandidateOrdering::Installed(installed),
+//!     CandidateOrdering::Newest,
+//! ]));
+//! ```
+//!
+//! A [`SolveObjective`] takes precedence over the ordering, across the
+//! alternatives of a requirement too.
+
+use {
+    crate::{packages::Package, policy::Policy},
+    std::{cmp::Ordering, collections::HashMap},
+};
+
+// The value of a size field, packages without one sort last.
+fn size(package: &Package<'_>, field: &str) -> usize {
+    package
+        .field(field)
+        .and_then(|size| crate::parse_size(size.trim().as_bytes()).ok())
+        .unwrap_or(usize::MAX)
+}
+
+/// The size the solver prefers small candidates by. The preference is
+/// greedy: the smallest candidate of each requirement, e.g. of the
+/// alternatives in `a | b`, is tried first, regardless of the dependencies
+/// it brings in. The total size of the solution is not minimized.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SolveObjective {
+    /// Only the candidate ordering applies
+    #[default]
+    None,
+    /// Prefer the candidates with the smaller `Installed-Size`
+    MinimizeInstalledSize,
+    /// Prefer the candidates with the smaller download `Size`
+    MinimizeDownloadSize,
+}
+
+impl SolveObjective {
+    // The size preferred small, the smaller the better.
+    pub(crate) fn cost(&self, package: &Package<'_>) -> usize {
+        match self {
+            Self::None => 0,
+            Self::MinimizeInstalledSize => size(package, "Installed-Size"),
+            Self::MinimizeDownloadSize => size(package, "Size"),
+        }
+    }
+}
+
+/// The order in which the solver tries the packages satisfying a
+/// requirement. Candidates left equal are tried by name, then oldest
+/// version first.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum CandidateOrdering {
+    /// The oldest version first
+    #[default]
+    Oldest,
+    /// The newest version first
+    Newest,
+    /// The installed versions, given by package name, first
+    Installed(HashMap<String, String>),
+    /// The smallest download first, by the `Size` field
+    SmallestDownload,
+    /// The packages from the index at this position in the universe first
+    Origin(usize),
+    /// The candidates of the apt policy first, by pin priority
+    Policy(Policy),
+    /// The first ordering, with ties broken by the following ones
+    Chain(Vec<CandidateOrdering>),
+}
+
+impl CandidateOrdering {
+    // `origin` is the position of the package index in the universe.
+    pub(crate) fn compare(
+        &self,
+        (this, this_origin): (&Package<'_>, usize),
+        (that, that_origin): (&Package<'_>, usize),
+    ) -> Ordering {
+        match self {
+            Self::Oldest => this
+                .name()
+                .cmp(that.name())
+                .then_with(|| this.version().cmp(&that.version())),
+            Self::Newest => this
+                .name()
+                .cmp(that.name())
+                .then_with(|| that.version().cmp(&this.version())),
+            Self::Installed(installed) => {
+                let is_installed = |p: &Package<'_>| {
+                    installed
+                        .get(p.name())
+                        .is_some_and(|v| p.version() == v.as_str())
+                };
+                is_installed(that).cmp(&is_installed(this))
+            }
+            Self::SmallestDownload => size(this, "Size").cmp(&size(that, "Size")),
+            Self::Origin(origin) => (that_origin == *origin).cmp(&(this_origin == *origin)),
+            Self::Policy(policy) => policy.compare((this, this_origin), (that, that_origin)),
+            Self::Chain(orderings) => orderings.iter().fold(Ordering::Equal, |ord, o| {
+                ord.then_with(|| o.compare((this, this_origin), (that, that_origin)))
+            }),
+        }
+    }
+}
@@ -1,15 +1,28 @@
 use {
     crate::{
-        control::{ControlField, ControlParser, ControlStanza, ParseError, MutableControlStanza},
-        digest::{Digest, Sha256},
-        repo::{DebRepo, VerifyingDebReader},
+        control::{
+            ControlField, ControlParser, ControlStanza, MutableControlField, MutableControlStanza,
+            ParseError,
+        },
+        diagnostic::Diagnostic,
+        digest::{Digests, HashAlgorithm},
+        filter::PackageFilter,
         version::{
             Constraint, Dependency, ParsedConstraintIterator, ParsedDependencyIterator,
-            ParsedProvidedNameIterator, ProvidedName, Version,
+            ParsedProvidedNameIterator, ProvidedName, Restrictions, Version, UNRESTRICTED,
         },
     },
-    async_std::io::{self, Read},
     ouroboros::self_referencing,
+    std::{borrow::Cow, io},
+};
+
+#[cfg(feature = "io")]
+use {
+    crate::{
+        error::Error,
+        repo::{DebRepo, VerifyingDebReader},
+    },
+    async_std::io::Read,
 };
 
 #[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd)]
@@ -38,6 +51,27 @@ impl From<&str> for Priority {
     }
 }
 
+/// The kind of packages listed by an index.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PackageKind {
+    #[default]
+    Deb,
+    /// debian-installer packages, indexed in the `debian-installer`
+    /// subdirectory of a component
+    Udeb,
+}
+
+impl PackageKind {
+    /// Returns the directory of the Packages index relative to the
+    /// distribution, e.g. `main/debian-installer/binary-amd64`.
+    pub fn index_dir(&self, component: &str, arch: &str) -> String {
+        match self {
+            Self::Deb => format!("{}/binary-{}", component, arch),
+            Self::Udeb => format!("{}/debian-installer/binary-{}", component, arch),
+        }
+    }
+}
+
 #[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MultiArch {
     #[default]
@@ -70,7 +104,15 @@ pub struct Package<'a> {
     pre_depends: Option<&'a str>,
     conflicts: Option<&'a str>,
     breaks: Option<&'a str>,
+    replaces: Option<&'a str>,
+    enhances: Option<&'a str>,
+    built_using: Option<&'a str>,
+    static_built_using: Option<&'a str>,
+    section: Option<&'a str>,
+    tags: Option<&'a str>,
+    tasks: Option<&'a str>,
     essential: bool,
+    protected: bool,
     priority: Priority,
     multi_arch: MultiArch,
 }
@@ -82,21 +124,29 @@ impl<'a> std::fmt::Display for Package<'a> {
 }
 
 impl<'a> Package<'a> {
-    pub fn repo_file(&self) -> io::Result<(&'a str, usize, Sha256)> {
-        let (path, size, sha256) = self
+    pub fn repo_file(&self) -> io::Result<(&'a str, usize, Digests)> {
+        let (path, size) = self
             .fields()
-            .find_fields(("Filename", "Size", "SHA256"))
+            .find_fields(("Filename", "Size"))
             .map_err(|err| {
                 io::Error::new(
                     io::ErrorKind::InvalidData,
                     format!("package {} lacks field {}", self, err),
                 )
             })?;
-        Ok((
-            path,
-            crate::parse_size(size.as_bytes())?,
-            Digest::<sha2::Sha256>::try_from(sha256)?,
-        ))
+        let mut digests = Digests::new();
+        for algorithm in HashAlgorithm::ALL {
+            if let Some(digest) = self.field(algorithm.packages_field()) {
+                digests.insert(algorithm, digest.trim())?;
+            }
+        }
+        if digests.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("package {} lacks a checksum field", self),
+            ));
+        }
+        Ok((path, crate::parse_size(size.as_bytes())?, digests))
     }
     pub fn src(&self) -> &'a str {
         self.src
@@ -128,12 +178,41 @@ impl<'a> Package<'a> {
     pub fn essential(&self) -> bool {
         self.essential
     }
+    /// `Protected: yes`, or the former `Important: yes`: the package is
+    /// not removed unless forced, like the essential ones.
+    pub fn protected(&self) -> bool {
+        self.protected
+    }
     pub fn priority(&self) -> Priority {
         self.priority
     }
     pub fn required(&self) -> bool {
         self.priority == Priority::Required
     }
+    /// The section, including the archive area prefix if any, e.g.
+    /// `contrib/libs`.
+    pub fn section(&self) -> Option<&'a str> {
+        self.section
+    }
+    /// The debtags from the `Tag` field, e.g. `role::program`.
+    pub fn tags(&self) -> impl Iterator<Item = &'a str> {
+        self.tags
+            .unwrap_or("")
+            .split(',')
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+    }
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags().any(|t| t == tag)
+    }
+    /// The tasksel tasks from the `Task` field, e.g. `ssh-server`.
+    pub fn tasks(&self) -> impl Iterator<Item = &'a str> {
+        self.tasks
+            .unwrap_or("")
+            .split(',')
+            .map(str::trim)
+            .filter(|task| !task.is_empty())
+    }
     pub fn multi_arch(&self) -> MultiArch {
         self.multi_arch
     }
@@ -143,6 +222,56 @@ impl<'a> Package<'a> {
     pub fn version(&self) -> Version<&'a str> {
         Version::from(self.version)
     }
+    /// The source package name, from the `Source` field if any.
+    pub fn source(&self) -> &'a str {
+        self.field("Source")
+            .and_then(|src| src.split_whitespace().next())
+            .unwrap_or(self.name)
+    }
+    /// The source package version. It differs from the package version for
+    /// binNMUs, e.g. `Source: hello (2.10-3)`.
+    pub fn source_version(&self) -> Version<&'a str> {
+        self.field("Source")
+            .and_then(|src| src.split_once('('))
+            .and_then(|(_, version)| version.split_once(')'))
+            .map_or(self.version(), |(version, _)| Version::from(version.trim()))
+    }
+    pub fn homepage(&self) -> Option<&'a str> {
+        self.field("Homepage").map(str::trim)
+    }
+    pub fn maintainer(&self) -> Option<&'a str> {
+        self.field("Maintainer").map(str::trim)
+    }
+    /// The `Installed-Size`, in KiB.
+    pub fn installed_size(&self) -> Option<u64> {
+        self.field("Installed-Size")?.trim().parse().ok()
+    }
+    /// The md5 of the long description, for the translated descriptions
+    /// of the `Translation-*` indexes.
+    pub fn description_md5(&self) -> Option<&'a str> {
+        self.field("Description-md5").map(str::trim)
+    }
+    pub fn origin(&self) -> Option<&'a str> {
+        self.field("Origin").map(str::trim)
+    }
+    /// The bug tracking system, e.g. `debbugs://bugs.debian.org`.
+    pub fn bugs(&self) -> Option<&'a str> {
+        self.field("Bugs").map(str::trim)
+    }
+    /// The share of machines an Ubuntu update is rolled out to for now,
+    /// from the `Phased-Update-Percentage` field.
+    pub fn phased_update_percentage(&self) -> Option<u8> {
+        self.field("Phased-Update-Percentage")?.trim().parse().ok()
+    }
+    /// The user-defined fields, named with an `X-` prefix.
+    pub fn extra_fields(&self) -> impl Iterator<Item = ControlField<'a>> {
+        self.fields().filter(|field| {
+            field
+                .name()
+                .get(..2)
+                .is_some_and(|prefix| prefix.eq_ignore_ascii_case("x-"))
+        })
+    }
     pub fn depends(
         &self,
     ) -> impl Iterator<
@@ -151,7 +280,23 @@ impl<'a> Package<'a> {
             ParseError,
         >,
     > {
-        ParsedDependencyIterator::new(self.depends.unwrap_or(""))
+        self.depends_in(&UNRESTRICTED)
+    }
+    /// Like [`Package::depends`], skipping the relations that do not apply
+    /// under `restrictions`.
+    pub fn depends_in<'r>(
+        &self,
+        restrictions: &'r Restrictions,
+    ) -> impl Iterator<
+        Item = std::result::Result<
+            Dependency<Option<&'a str>, &'a str, Version<&'a str>>,
+            ParseError,
+        >,
+    > + 'r
+    where
+        'a: 'r,
+    {
+        ParsedDependencyIterator::new(self.depends.unwrap_or(""), restrictions)
     }
     pub fn pre_depends(
         &self,
@@ -161,7 +306,23 @@ impl<'a> Package<'a> {
             ParseError,
         >,
     > {
-        ParsedDependencyIterator::new(self.pre_depends.unwrap_or(""))
+        self.pre_depends_in(&UNRESTRICTED)
+    }
+    /// Like [`Package::pre_depends`], skipping the relations that do not apply
+    /// under `restrictions`.
+    pub fn pre_depends_in<'r>(
+        &self,
+        restrictions: &'r Restrictions,
+    ) -> impl Iterator<
+        Item = std::result::Result<
+            Dependency<Option<&'a str>, &'a str, Version<&'a str>>,
+            ParseError,
+        >,
+    > + 'r
+    where
+        'a: 'r,
+    {
+        ParsedDependencyIterator::new(self.pre_depends.unwrap_or(""), restrictions)
     }
     pub fn breaks(
         &self,
@@ -171,7 +332,23 @@ impl<'a> Package<'a> {
             ParseError,
         >,
     > {
-        ParsedConstraintIterator::new(self.breaks.unwrap_or(""), false)
+        self.breaks_in(&UNRESTRICTED)
+    }
+    /// Like [`Package::breaks`], skipping the relations that do not apply
+    /// under `restrictions`.
+    pub fn breaks_in<'r>(
+        &self,
+        restrictions: &'r Restrictions,
+    ) -> impl Iterator<
+        Item = std::result::Result<
+            Constraint<Option<&'a str>, &'a str, Version<&'a str>>,
+            ParseError,
+        >,
+    > + 'r
+    where
+        'a: 'r,
+    {
+        ParsedConstraintIterator::new(self.breaks.unwrap_or(""), false, restrictions)
     }
     pub fn conflicts(
         &self,
@@ -181,11 +358,111 @@ impl<'a> Package<'a> {
             ParseError,
         >,
     > {
-        ParsedConstraintIterator::new(self.conflicts.unwrap_or(""), false)
+        self.conflicts_in(&UNRESTRICTED)
+    }
+    /// Like [`Package::conflicts`], skipping the relations that do not apply
+    /// under `restrictions`.
+    pub fn conflicts_in<'r>(
+        &self,
+        restrictions: &'r Restrictions,
+    ) -> impl Iterator<
+        Item = std::result::Result<
+            Constraint<Option<&'a str>, &'a str, Version<&'a str>>,
+            ParseError,
+        >,
+    > + 'r
+    where
+        'a: 'r,
+    {
+        ParsedConstraintIterator::new(self.conflicts.unwrap_or(""), false, restrictions)
+    }
+    /// The packages whose files this one may overwrite; with a matching
+    /// `Conflicts`, the packages it takes over.
+    pub fn replaces(
+        &self,
+    ) -> impl Iterator<
+        Item = std::result::Result<
+            Constraint<Option<&'a str>, &'a str, Version<&'a str>>,
+            ParseError,
+        >,
+    > {
+        self.replaces_in(&UNRESTRICTED)
+    }
+    /// Like [`Package::replaces`], skipping the relations that do not apply
+    /// under `restrictions`.
+    pub fn replaces_in<'r>(
+        &self,
+        restrictions: &'r Restrictions,
+    ) -> impl Iterator<
+        Item = std::result::Result<
+            Constraint<Option<&'a str>, &'a str, Version<&'a str>>,
+            ParseError,
+        >,
+    > + 'r
+    where
+        'a: 'r,
+    {
+        ParsedConstraintIterator::new(self.replaces.unwrap_or(""), true, restrictions)
+    }
+    /// Returns true if the package declares a conflict with the version
+    /// `version` of `name`.
+    pub fn conflicts_with(&self, name: &str, version: &Version<&str>) -> bool {
+        // the ranges of conflicts are the versions allowed
+        self.conflicts()
+            .flatten()
+            .any(|c| *c.name() == name && !c.range().contains(version))
+    }
+    /// Returns true if the package may overwrite the files of the version
+    /// `version` of `name`.
+    pub fn replaces_version(&self, name: &str, version: &Version<&str>) -> bool {
+        self.replaces()
+            .flatten()
+            .any(|r| *r.name() == name && r.range().contains(version))
+    }
+    /// The packages this one enhances, the reverse of `Suggests`.
+    pub fn enhances(
+        &self,
+    ) -> impl Iterator<
+        Item = std::result::Result<
+            Dependency<Option<&'a str>, &'a str, Version<&'a str>>,
+            ParseError,
+        >,
+    > {
+        ParsedDependencyIterator::new(self.enhances.unwrap_or(""), &UNRESTRICTED)
+    }
+    /// The source packages, with their exact versions, whose contents were
+    /// incorporated into this one at build time.
+    pub fn built_using(
+        &self,
+    ) -> impl Iterator<
+        Item = std::result::Result<
+            Constraint<Option<&'a str>, &'a str, Version<&'a str>>,
+            ParseError,
+        >,
+    > {
+        ParsedConstraintIterator::new(self.built_using.unwrap_or(""), true, &UNRESTRICTED)
+    }
+    /// Like [`Package::built_using`], for the sources statically linked
+    /// into this one, e.g. Go and Rust libraries.
+    pub fn static_built_using(
+        &self,
+    ) -> impl Iterator<
+        Item = std::result::Result<
+            Constraint<Option<&'a str>, &'a str, Version<&'a str>>,
+            ParseError,
+        >,
+    > {
+        ParsedConstraintIterator::new(self.static_built_using.unwrap_or(""), true, &UNRESTRICTED)
     }
     pub fn control(&self) -> Result<ControlStanza<'a>, ParseError> {
         ControlStanza::parse(self.src)
     }
+    /// Returns an editor of a copy of the package stanza.
+    pub fn edit(&self) -> PackageEditor {
+        PackageEditor {
+            stanza: self.into(),
+        }
+    }
     pub fn field(&self, name: &str) -> Option<&'a str> {
         ControlParser::new(self.src)
             .map(|f| f.unwrap())
@@ -197,7 +474,12 @@ impl<'a> Package<'a> {
             .map(|f| f.unwrap())
             .find(|f| f.is_a(name))
             .map(|f| f.value())
-            .ok_or_else(|| ParseError::from(format!("Package {} description lacks field {}", &self, name)))
+            .ok_or_else(|| {
+                ParseError::from(format!(
+                    "Package {} description lacks field {}",
+                    &self, name
+                ))
+            })
     }
     pub fn fields(&self) -> impl Iterator<Item = ControlField<'a>> {
         ControlParser::new(self.src).map(|f| f.unwrap())
@@ -234,10 +516,28 @@ impl<'a> Package<'a> {
                     pkg.conflicts.replace(field.value());
                 } else if field.is_a("Breaks") {
                     pkg.breaks.replace(field.value());
+                } else if field.is_a("Replaces") {
+                    pkg.replaces.replace(field.value());
+                } else if field.is_a("Enhances") {
+                    pkg.enhances.replace(field.value());
+                } else if field.is_a("Built-Using") {
+                    pkg.built_using.replace(field.value());
+                } else if field.is_a("Static-Built-Using") {
+                    pkg.static_built_using.replace(field.value());
                 } else if field.is_a("Essential") {
                     if field.value().eq_ignore_ascii_case("yes") {
                         pkg.essential = true;
                     }
+                } else if field.is_a("Protected") || field.is_a("Important") {
+                    if field.value().eq_ignore_ascii_case("yes") {
+                        pkg.protected = true;
+                    }
+                } else if field.is_a("Section") {
+                    pkg.section.replace(field.value().trim());
+                } else if field.is_a("Tag") {
+                    pkg.tags.replace(field.value());
+                } else if field.is_a("Task") {
+                    pkg.tasks.replace(field.value());
                 } else if field.is_a("Priority") {
                     pkg.priority = Priority::from(field.value());
                 } else if field.is_a("Multi-Arch") {
@@ -269,11 +569,164 @@ impl<'a> From<&Package<'a>> for MutableControlStanza {
     }
 }
 
+/// Edits the fields of a package stanza. The fields not touched, including
+/// the ones unknown to [`Package`], are kept as they are and in their order.
+///
+/// ```ignore
+/// let stanza = package
+///     .edit()
+///     .set("Phased-Update-Percentage", "10")
+///     .remove("Tag")
+///     .build()?;
+/// println!("{}", stanza);
+/// ```
+pub struct PackageEditor {
+    stanza: MutableControlStanza,
+}
+
+impl PackageEditor {
+    /// Returns the value of the field `name`.
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.stanza.field(name)
+    }
+    /// Sets the value of the field `name`, appending the field if missing.
+    pub fn set<N, V>(mut self, name: N, value: V) -> Self
+    where
+        N: Into<Cow<'static, str>> + AsRef<str>,
+        V: Into<Cow<'static, str>>,
+    {
+        self.stanza.set(name, value);
+        self
+    }
+    /// Removes the field `name`.
+    pub fn remove<S: AsRef<str>>(mut self, name: S) -> Self {
+        self.stanza.remove(name);
+        self
+    }
+    /// Keeps only the fields matching `f`.
+    pub fn retain<F: FnMut(&MutableControlField) -> bool>(mut self, f: F) -> Self {
+        self.stanza.retain(f);
+        self
+    }
+    /// Returns the edited stanza, failing if it is no longer a valid package.
+    pub fn build(self) -> Result<MutableControlStanza, ParseError> {
+        let src = self.stanza.to_string();
+        Package::try_parse_from(&mut ControlParser::new(&src))?
+            .ok_or_else(|| ParseError::from("Empty control stanza"))?;
+        Ok(self.stanza)
+    }
+}
+
+/// How malformed stanzas of a Packages index are handled.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseMode {
+    /// Fail on the first malformed stanza
+    #[default]
+    Strict,
+    /// Skip malformed stanzas, reporting each one
+    Skip,
+    /// Like `Skip`, but keep stanzas with duplicate fields or invalid
+    /// versions and accept whitespace-only lines as stanza separators
+    Permissive,
+}
+
+// Scrapes the package name off a stanza that failed to parse.
+fn stanza_name(stanza: &str) -> Option<&str> {
+    stanza
+        .lines()
+        .take_while(|line| !line.trim().is_empty())
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.eq_ignore_ascii_case("Package"))
+        .map(|(_, value)| value.trim())
+}
+
+// Checks what the strict parser lets through.
+fn validate(package: &Package<'_>) -> Result<(), (Option<&'static str>, String)> {
+    let mut seen: Vec<&str> = vec![];
+    for field in package.fields() {
+        let name = field.name();
+        if seen.iter().any(|n| n.eq_ignore_ascii_case(name)) {
+            return Err((None, format!("duplicate field {}", name)));
+        }
+        seen.push(name);
+    }
+    if !package.version().is_valid() {
+        let err = format!("invalid version {}", package.version());
+        return Err((Some("Version"), err));
+    }
+    Ok(())
+}
+
+// Splits the index into stanzas by lines, so that a malformed stanza can be
+// skipped, and parses them one by one.
+fn parse_lenient<'a>(
+    src: &'a str,
+    mode: ParseMode,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Vec<Package<'a>> {
+    let mut stanzas = vec![];
+    let (mut start, mut offset) = (None, 0);
+    for (n, line) in src.split_inclusive('\n').enumerate() {
+        let blank = line.bytes().all(|b| b == b' ' || b == b'\t' || b == b'\n');
+        if line == "\n" || (blank && mode == ParseMode::Permissive) {
+            if line != "\n" {
+                let message = "whitespace-only line taken as a stanza separator";
+                diagnostics.push(Diagnostic::new(message).line(n + 1));
+            }
+            if let Some((start, line)) = start.take() {
+                stanzas.push((&src[start..offset], line));
+            }
+        } else if start.is_none() {
+            start = Some((offset, n + 1));
+        }
+        offset += line.len();
+    }
+    if let Some((start, line)) = start {
+        stanzas.push((&src[start..], line));
+    }
+    let mut packages = vec![];
+    for (stanza, line) in stanzas {
+        let diagnostic = |package: Option<&str>, message: String| {
+            let diagnostic = Diagnostic::new(message).line(line);
+            match package {
+                Some(package) => diagnostic.package(package),
+                None => diagnostic,
+            }
+        };
+        match Package::try_parse_from(&mut ControlParser::new(stanza)) {
+            Ok(None) => {}
+            Ok(Some(package)) => match validate(&package) {
+                Ok(()) => packages.push(package),
+                Err((field, message)) => {
+                    let mut d = diagnostic(Some(package.name()), message);
+                    if let Some(field) = field {
+                        d = d.field(field);
+                    }
+                    if mode == ParseMode::Permissive {
+                        packages.push(package);
+                    } else {
+                        d.message.push_str(", stanza skipped");
+                    }
+                    diagnostics.push(d);
+                }
+            },
+            Err(err) => {
+                let message = format!("{}, stanza skipped", err);
+                diagnostics.push(diagnostic(stanza_name(stanza), message));
+            }
+        }
+    }
+    diagnostics.sort_by_key(|d| d.line);
+    packages
+}
+
 pub struct Packages<S>
 where
     S: AsRef<str> + 'static,
 {
+    #[cfg(feature = "io")]
     pub(crate) repo: DebRepo,
+    kind: PackageKind,
     inner: PackagesInner<S>,
 }
 
@@ -281,7 +734,16 @@ impl<S: AsRef<str> + 'static> Packages<S> {
     pub fn get(&self, index: usize) -> Option<&Package<'_>> {
         self.inner.with_packages(|packages| packages.get(index))
     }
-    pub async fn get_deb_reader(&self, index: usize) -> io::Result<VerifyingDebReader> {
+    pub fn kind(&self) -> PackageKind {
+        self.kind
+    }
+    /// Marks the index as listing packages of the given kind.
+    pub fn with_kind(mut self, kind: PackageKind) -> Self {
+        self.kind = kind;
+        self
+    }
+    #[cfg(feature = "io")]
+    pub async fn get_deb_reader(&self, index: usize) -> Result<VerifyingDebReader<'_>, Error> {
         let (path, size, hash) = self
             .get(index)
             .ok_or_else(|| {
@@ -293,6 +755,13 @@ impl<S: AsRef<str> + 'static> Packages<S> {
             .and_then(|p| p.repo_file())?;
         self.repo.verifying_deb_reader(path, size, hash).await
     }
+    /// Returns the number of packages in the index.
+    pub fn len(&self) -> usize {
+        self.inner.with_packages(|packages| packages.len())
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
     pub fn package_by_name(&self, name: &str) -> Option<&Package<'_>> {
         self.inner
             .with_packages(|packages| packages.iter().find(|package| package.name() == name))
@@ -300,12 +769,40 @@ impl<S: AsRef<str> + 'static> Packages<S> {
     pub fn packages(&self) -> impl Iterator<Item = &Package<'_>> {
         self.inner.with_packages(|packages| packages.iter())
     }
-    pub fn new(repo: DebRepo, data: S) -> Result<Self, ParseError> {
-        Ok(Packages {
-            repo,
+    /// Returns the packages matching `filter`.
+    pub fn filter(&self, filter: impl Into<PackageFilter>) -> impl Iterator<Item = &Package<'_>> {
+        let filter = filter.into();
+        self.packages().filter(move |p| filter.matches(p))
+    }
+    /// Parses an index not backed by a repository, so its packages cannot
+    /// be fetched.
+    pub fn parse(data: S) -> Result<Self, ParseError> {
+        Self::parse_with_mode(data, ParseMode::Strict).map(|(packages, _)| packages)
+    }
+    /// Parses the index in the given mode. Returns the index along with the
+    /// problems skipped over or fixed up, which is always empty in strict
+    /// mode.
+    pub fn parse_with_mode(
+        data: S,
+        mode: ParseMode,
+    ) -> Result<(Self, Vec<Diagnostic>), ParseError> {
+        let span = tracing::debug_span!(
+            "parse",
+            bytes = data.as_ref().len(),
+            packages = tracing::field::Empty
+        )
+        .entered();
+        let mut diagnostics = vec![];
+        let packages = Packages {
+            #[cfg(feature = "io")]
+            repo: crate::repo::null_provider(),
+            kind: PackageKind::Deb,
             inner: PackagesInnerTryBuilder {
                 data,
                 packages_builder: |data: &'_ S| -> Result<Vec<Package<'_>>, ParseError> {
+                    if mode != ParseMode::Strict {
+                        return Ok(parse_lenient(data.as_ref(), mode, &mut diagnostics));
+                    }
                     let mut parser = ControlParser::new(data.as_ref());
                     let mut packages: Vec<Package<'_>> = vec![];
                     while let Some(package) = Package::try_parse_from(&mut parser)? {
@@ -315,13 +812,32 @@ impl<S: AsRef<str> + 'static> Packages<S> {
                 },
             }
             .try_build()?,
-        })
+        };
+        span.record("packages", packages.inner.borrow_packages().len());
+        Ok((packages, diagnostics))
+    }
+    #[cfg(feature = "io")]
+    pub fn new(repo: DebRepo, data: S) -> Result<Self, ParseError> {
+        Self::new_with_mode(repo, data, ParseMode::Strict).map(|(packages, _)| packages)
+    }
+    /// Parses the index of `repo` in the given mode, see
+    /// [`Packages::parse_with_mode`].
+    #[cfg(feature = "io")]
+    pub fn new_with_mode(
+        repo: DebRepo,
+        data: S,
+        mode: ParseMode,
+    ) -> Result<(Self, Vec<Diagnostic>), ParseError> {
+        let (mut packages, diagnostics) = Self::parse_with_mode(data, mode)?;
+        packages.repo = repo;
+        Ok((packages, diagnostics))
     }
     pub(crate) fn new_test(data: S) -> Result<Self, ParseError> {
-        Self::new(crate::repo::null_provider(), data)
+        Self::parse(data)
     }
 }
 
+#[cfg(feature = "io")]
 impl Packages<Box<str>> {
     pub async fn read<R: Read + Unpin>(r: &mut R) -> io::Result<Self> {
         use async_std::io::ReadExt;
@@ -433,3 +949,134 @@ where
         Ok((r.0.ok_or(m.0)?, r.1.ok_or(m.1)?, r.2.ok_or(m.2)?))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INDEX: &str = "\
+Package: good
+Architecture: amd64
+Version: 1.0
+
+Package: noversion
+Architecture: amd64
+
+Package: dup
+Architecture: amd64
+Version: 1.0
+Version: 1.1
+ \t
+Package: badversion
+Architecture: amd64
+Version: v2
+
+Package: broken
+Bad Field: x
+
+Package: last
+Architecture: amd64
+Version: 2.0
+";
+
+    fn parse(mode: ParseMode) -> Result<(Vec<String>, Vec<Diagnostic>), ParseError> {
+        let (packages, diagnostics) = Packages::parse_with_mode(INDEX, mode)?;
+        let names = packages.packages().map(|p| p.name().to_string()).collect();
+        Ok((names, diagnostics))
+    }
+
+    #[test]
+    fn test_parse_modes() {
+        assert!(parse(ParseMode::Strict).is_err());
+
+        let (names, diagnostics) = parse(ParseMode::Skip).unwrap();
+        assert_eq!(names, ["good", "last"]);
+        let lines: Vec<_> = diagnostics.iter().map(|d| d.line.unwrap()).collect();
+        // Without the fixup, a whitespace-only line continues the field.
+        assert_eq!(lines, [5, 8, 17]);
+        assert_eq!(diagnostics[0].package.as_deref(), Some("noversion"));
+        assert_eq!(diagnostics[2].package.as_deref(), Some("broken"));
+
+        let (names, diagnostics) = parse(ParseMode::Permissive).unwrap();
+        assert_eq!(names, ["good", "dup", "badversion", "last"]);
+        assert_eq!(diagnostics[1].package.as_deref(), Some("dup"));
+        assert_eq!(diagnostics[2].line, Some(12));
+        assert_eq!(diagnostics[3].field.as_deref(), Some("Version"));
+        assert_eq!(diagnostics.len(), 5);
+    }
+
+    #[test]
+    fn test_fields() {
+        let packages = Packages::parse(
+            "\
+Package: hello
+Architecture: amd64
+Version: 2.10-3+b1
+Source: hello (2.10-3)
+Maintainer: Santiago Vila <sanvila@debian.org>
+Installed-Size: 280
+Homepage: https://www.gnu.org/software/hello/
+Description-md5: 27be4f3c5a6d3b5e8f4b2a3e1d0c9b8a
+X-Cargo-Built-Using: rust-foo (= 1.0)
+",
+        )
+        .unwrap();
+        let hello = packages.package_by_name("hello").unwrap();
+        assert_eq!(hello.source(), "hello");
+        assert_eq!(hello.source_version(), Version::from("2.10-3"));
+        assert_eq!(
+            hello.maintainer(),
+            Some("Santiago Vila <sanvila@debian.org>")
+        );
+        assert_eq!(hello.installed_size(), Some(280));
+        assert_eq!(
+            hello.homepage(),
+            Some("https://www.gnu.org/software/hello/")
+        );
+        assert_eq!(hello.description_md5().map(str::len), Some(32));
+        assert_eq!(hello.origin(), None);
+        let extra: Vec<_> = hello.extra_fields().map(|f| f.name()).collect();
+        assert_eq!(extra, ["X-Cargo-Built-Using"]);
+    }
+
+    #[test]
+    fn test_edit() {
+        let packages = Packages::parse(
+            "\
+Package: hello
+Version: 2.10-3
+Architecture: amd64
+X-Custom: kept
+Tag: devel::lang:c
+Description: greeting
+ tool
+",
+        )
+        .unwrap();
+        let hello = packages.package_by_name("hello").unwrap();
+        let stanza = hello
+            .edit()
+            .set("Version", "2.10-4")
+            .set("Phased-Update-Percentage", "10")
+            .remove("Tag")
+            .build()
+            .unwrap();
+        assert_eq!(
+            stanza.to_string(),
+            "\
+Package: hello
+Version: 2.10-4
+Architecture: amd64
+X-Custom: kept
+Description: greeting
+ tool
+Phased-Update-Percentage: 10
+"
+        );
+        let edited = Packages::parse(stanza.to_string()).unwrap();
+        let edited = edited.package_by_name("hello").unwrap();
+        assert_eq!(edited.phased_update_percentage(), Some(10));
+        assert_eq!(hello.edit().field("X-Custom"), Some("kept"));
+        assert!(hello.edit().remove("Version").build().is_err());
+    }
+}
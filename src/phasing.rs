@@ -0,0 +1,137 @@
+//! Phased updates
+//!
+//! Ubuntu rolls updates out gradually: the `Phased-Update-Percentage` field
+//! of a package tells the share of machines that get it for now. apt decides
+//! for each machine by drawing a number from 0 to 100 seeded with the source
+//! package, its version and the machine id, and holds the update back if the
+//! number exceeds the percentage. [`Phasing`] makes the same decision, so
+//! that the upgrades planned for a machine match what apt would do.
+//!
+//! ```ignore
+//! let machine_id = std::fs::read_to_string("/etc/machine-id")?;
+//! universe.set_installed(installed.phasing(Phasing::machine_id(&machine_id)));
+//! ```
+
+use crate::packages::Package;
+
+/// Which phased updates to install, like apt's
+/// `APT::Get::Always-Include-Phased-Updates` and
+/// `APT::Get::Never-Include-Phased-Updates`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum Phasing {
+    /// Install all the updates, as apt does without a machine id
+    #[default]
+    Always,
+    /// Hold back all the updates being phased
+    Never,
+    /// Install the updates phased in for the machine with this id
+    MachineId(String),
+}
+
+impl Phasing {
+    /// Phases updates for the machine id, e.g. the contents of
+    /// `/etc/machine-id`.
+    pub fn machine_id(id: &str) -> Self {
+        Phasing::MachineId(id.trim().to_string())
+    }
+    /// Returns true if the update to `package` is to be installed.
+    pub fn includes(&self, package: &Package<'_>) -> bool {
+        match package.phased_update_percentage() {
+            None | Some(100..) => true,
+            Some(percentage) => self.includes_phase(
+                package.source(),
+                &package.source_version().to_string(),
+                percentage,
+            ),
+        }
+    }
+    /// Returns true if the update of the source package `source` to
+    /// `version`, phased to `percentage` of the machines, is to be
+    /// installed.
+    pub fn includes_phase(&self, source: &str, version: &str, percentage: u8) -> bool {
+        match self {
+            Phasing::Always => true,
+            Phasing::Never => percentage >= 100,
+            Phasing::MachineId(id) => {
+                draw(&format!("{}-{}-{}", source, version, id)) <= percentage as u32
+            }
+        }
+    }
+}
+
+// Draws a number from 0 to 100 like apt does with libstdc++: a
+// `std::minstd_rand` seeded through a `std::seed_seq` of the bytes of `seed`,
+// fed to a `std::uniform_int_distribution<unsigned>(0, 100)`.
+fn draw(seed: &str) -> u32 {
+    // std::seed_seq::generate of the 4 words minstd_rand seeds from
+    let v: Vec<u32> = seed.bytes().map(|b| b as i8 as u32).collect();
+    let (n, s) = (4usize, v.len());
+    let mut words = [0x8b8b8b8bu32; 4];
+    let (t, p) = (1, 1);
+    let q = p + t;
+    let m = (s + 1).max(n);
+    let mix = |x: u32| x ^ (x >> 27);
+    for k in 0..m {
+        let r1 = 1664525u32.wrapping_mul(mix(words[k % n]
+            ^ words[(k + p) % n]
+            ^ words[(k + n - 1) % n]));
+        let r2 = r1.wrapping_add(match k {
+            0 => s as u32,
+            k if k <= s => ((k % n) as u32).wrapping_add(v[k - 1]),
+            k => (k % n) as u32,
+        });
+        words[(k + p) % n] = words[(k + p) % n].wrapping_add(r1);
+        words[(k + q) % n] = words[(k + q) % n].wrapping_add(r2);
+        words[k % n] = r2;
+    }
+    for k in m..m + n {
+        let r3 = 1566083941u32.wrapping_mul(mix(words[k % n]
+            .wrapping_add(words[(k + p) % n])
+            .wrapping_add(words[(k + n - 1) % n])));
+        let r4 = r3.wrapping_sub((k % n) as u32);
+        words[(k + p) % n] ^= r3;
+        words[(k + q) % n] ^= r4;
+        words[k % n] = r4;
+    }
+    // std::minstd_rand
+    const M: u64 = 2147483647;
+    let mut x = words[3] as u64 % M;
+    if x == 0 {
+        x = 1;
+    }
+    // the downscaling of std::uniform_int_distribution, rejecting the
+    // values past the last full bucket
+    let scaling = (M - 2) / 101;
+    loop {
+        x = x * 48271 % M;
+        let value = x - 1;
+        if value < 101 * scaling {
+            return (value / scaling) as u32;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_draw() {
+        // as drawn by libstdc++
+        assert_eq!(draw("hello-2.10-3-0123456789abcdef0123456789abcdef"), 71);
+        assert_eq!(
+            draw("openssl-3.0.2-0ubuntu1.15-d41d8cd98f00b204e9800998ecf8427e"),
+            7
+        );
+        assert_eq!(draw("a-1-x"), 53);
+    }
+
+    #[test]
+    fn test_includes_phase() {
+        let phasing = Phasing::machine_id("0123456789abcdef0123456789abcdef\n");
+        assert!(phasing.includes_phase("hello", "2.10-3", 71));
+        assert!(!phasing.includes_phase("hello", "2.10-3", 70));
+        assert!(Phasing::Always.includes_phase("hello", "2.10-3", 0));
+        assert!(!Phasing::Never.includes_phase("hello", "2.10-3", 99));
+    }
+}
@@ -0,0 +1,285 @@
+//! Installation plans
+//!
+//! A [`Plan`] tells what installing a solution does to the target system:
+//! the packages installed, upgraded, downgraded and removed, from
+//! [`crate::Universe::plan`]. [`Plan::render_apt_style`] prints it the way
+//! `apt-get install --simulate` does:
+//!
+//! ```text
+//! The following additional packages will be installed:
+//!   libfoo1
+//! The following NEW packages will be installed:
+//!   hello libfoo1
+//! 0 upgraded, 2 newly installed, 0 to remove and 0 not upgraded.
+//! Need to get 53.1 kB of archives.
+//! After this operation, 280 kB of additional disk space will be used.
+//! ```
+
+use std::fmt::Write;
+
+/// What happens to a package of a plan.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PlanAction {
+    /// Newly installed
+    Install,
+    /// Upgraded from the installed version
+    Upgrade(String),
+    /// Downgraded from the installed version
+    Downgrade(String),
+    /// Already installed
+    Keep,
+    /// Removed, taken over by the package that conflicts with and replaces
+    /// it
+    Remove(String),
+}
+
+/// A package of a plan.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlanItem {
+    /// The package name, qualified with the architecture for foreign
+    /// packages, e.g. `libc6:i386`
+    pub name: String,
+    pub version: String,
+    pub action: PlanAction,
+    /// False if installed only to satisfy dependencies
+    pub requested: bool,
+    /// The download size, from the `Size` field
+    pub size: usize,
+    /// The `Installed-Size`, in bytes
+    pub installed_size: usize,
+    /// The `Installed-Size` of the installed version, in bytes, if known
+    pub replaced_size: usize,
+}
+
+/// A dpkg run of a plan. The Pre-Depends of the unpacked packages are
+/// configured by the earlier runs; their other dependencies are unpacked
+/// by then, and configured by the same `Configure` run at the latest.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PlanPhase {
+    /// `dpkg --remove`
+    Remove(Vec<String>),
+    /// `dpkg --unpack`
+    Unpack(Vec<String>),
+    /// `dpkg --configure`
+    Configure(Vec<String>),
+}
+
+/// The changes a solution makes to the target system.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Plan {
+    /// The packages, sorted by name
+    pub items: Vec<PlanItem>,
+    /// The installed packages with a newer version left as they are
+    pub not_upgraded: Vec<String>,
+    /// The packages installed, upgraded or downgraded, each after the
+    /// packages it depends on
+    pub install_order: Vec<String>,
+    /// The packages removed, each before the packages it depends on
+    pub removal_order: Vec<String>,
+    /// The dpkg runs carrying out the plan, in order
+    pub phases: Vec<PlanPhase>,
+}
+
+impl Plan {
+    fn names(&self, matches: impl Fn(&PlanItem) -> bool) -> Vec<&str> {
+        self.items
+            .iter()
+            .filter(|item| matches(item))
+            .map(|item| item.name.as_str())
+            .collect()
+    }
+    pub fn installed(&self) -> Vec<&str> {
+        self.names(|item| item.action == PlanAction::Install)
+    }
+    pub fn upgraded(&self) -> Vec<&str> {
+        self.names(|item| matches!(item.action, PlanAction::Upgrade(_)))
+    }
+    pub fn downgraded(&self) -> Vec<&str> {
+        self.names(|item| matches!(item.action, PlanAction::Downgrade(_)))
+    }
+    pub fn removed(&self) -> Vec<&str> {
+        self.names(|item| matches!(item.action, PlanAction::Remove(_)))
+    }
+    /// The total size of the packages to fetch.
+    pub fn download_size(&self) -> usize {
+        self.items
+            .iter()
+            .filter(|item| item.action != PlanAction::Keep)
+            .map(|item| item.size)
+            .sum()
+    }
+    /// The change of the disk space used, in bytes.
+    pub fn disk_space_change(&self) -> i64 {
+        self.items
+            .iter()
+            .filter(|item| item.action != PlanAction::Keep)
+            .map(|item| item.installed_size as i64 - item.replaced_size as i64)
+            .sum()
+    }
+    /// Renders the plan like `apt-get install --simulate` does, before the
+    /// `Inst` lines.
+    pub fn render_apt_style(&self) -> String {
+        let mut out = String::new();
+        let additional = self.names(|item| item.action == PlanAction::Install && !item.requested);
+        let sections = [
+            (
+                "The following additional packages will be installed:",
+                additional,
+            ),
+            ("The following packages will be REMOVED:", self.removed()),
+            (
+                "The following NEW packages will be installed:",
+                self.installed(),
+            ),
+            ("The following packages will be upgraded:", self.upgraded()),
+            (
+                "The following packages will be DOWNGRADED:",
+                self.downgraded(),
+            ),
+        ];
+        for (title, names) in sections {
+            if !names.is_empty() {
+                out.push_str(title);
+                out.push('\n');
+                show_list(&mut out, &names);
+            }
+        }
+        let (upgraded, installed, downgraded, removed) = (
+            self.upgraded().len(),
+            self.installed().len(),
+            self.downgraded().len(),
+            self.removed().len(),
+        );
+        let _ = write!(
+            out,
+            "{} upgraded, {} newly installed, ",
+            upgraded, installed
+        );
+        if downgraded > 0 {
+            let _ = write!(out, "{} downgraded, ", downgraded);
+        }
+        let _ = writeln!(
+            out,
+            "{} to remove and {} not upgraded.",
+            removed,
+            self.not_upgraded.len()
+        );
+        if upgraded + installed + downgraded + removed > 0 {
+            if self.download_size() > 0 {
+                let _ = writeln!(
+                    out,
+                    "Need to get {}B of archives.",
+                    size_to_str(self.download_size() as f64)
+                );
+            }
+            let change = self.disk_space_change();
+            let _ = match change >= 0 {
+                true => writeln!(
+                    out,
+                    "After this operation, {}B of additional disk space will be used.",
+                    size_to_str(change as f64)
+                ),
+                false => writeln!(
+                    out,
+                    "After this operation, {}B disk space will be freed.",
+                    size_to_str(-change as f64)
+                ),
+            };
+        }
+        out
+    }
+}
+
+// Lists the names indented, wrapped at 80 columns.
+fn show_list(out: &mut String, names: &[&str]) {
+    let mut line = String::new();
+    for name in names {
+        if !line.is_empty() && line.len() + 1 + name.len() > 80 {
+            out.push_str(&line);
+            out.push('\n');
+            line.clear();
+        }
+        line.push_str(if line.is_empty() { "  " } else { " " });
+        line.push_str(name);
+    }
+    out.push_str(&line);
+    out.push('\n');
+}
+
+// Formats a size with a decimal prefix, as apt's SizeToStr: 3 significant
+// digits at most, up to 4 digits without a prefix or above 100.
+fn size_to_str(mut size: f64) -> String {
+    for prefix in ["", "k", "M", "G", "T", "P", "E", "Z"] {
+        if size < 100.0 && !prefix.is_empty() {
+            return format!("{:.1} {}", size, prefix);
+        }
+        if size < 10000.0 {
+            return format!("{:.0} {}", size, prefix);
+        }
+        size /= 1000.0;
+    }
+    format!("{:.0} Y", size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(name: &str, action: PlanAction, requested: bool, size: usize) -> PlanItem {
+        PlanItem {
+            name: name.to_string(),
+            version: "1.0".to_string(),
+            action,
+            requested,
+            size,
+            installed_size: size * 4,
+            replaced_size: 0,
+        }
+    }
+
+    #[test]
+    fn test_render_apt_style() {
+        let plan = Plan {
+            items: vec![
+                item("hello", PlanAction::Install, true, 53_100),
+                item(
+                    "libc6",
+                    PlanAction::Upgrade("2.36-8".to_string()),
+                    false,
+                    2_800_000,
+                ),
+                item("libfoo1", PlanAction::Install, false, 1_000),
+                item("perl", PlanAction::Keep, true, 300_000),
+            ],
+            not_upgraded: vec!["bash".to_string()],
+            ..Plan::default()
+        };
+        assert_eq!(
+            plan.render_apt_style(),
+            "\
+The following additional packages will be installed:
+  libfoo1
+The following NEW packages will be installed:
+  hello libfoo1
+The following packages will be upgraded:
+  libc6
+1 upgraded, 2 newly installed, 0 to remove and 1 not upgraded.
+Need to get 2854 kB of archives.
+After this operation, 11.4 MB of additional disk space will be used.
+"
+        );
+        assert_eq!(
+            Plan::default().render_apt_style(),
+            "0 upgraded, 0 newly installed, 0 to remove and 0 not upgraded.\n"
+        );
+    }
+
+    #[test]
+    fn test_size_to_str() {
+        assert_eq!(size_to_str(0.0), "0 ");
+        assert_eq!(size_to_str(9999.0), "9999 ");
+        assert_eq!(size_to_str(10_000.0), "10.0 k");
+        assert_eq!(size_to_str(123_456.0), "123 k");
+        assert_eq!(size_to_str(12_345_678.0), "12.3 M");
+    }
+}
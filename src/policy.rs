@@ -0,0 +1,512 @@
+//! Candidate version policy
+//!
+//! apt picks the version of a package to install, its candidate, by pin
+//! priority: each index gets a priority from its release, 500 by default,
+//! 990 for the default release (`apt-get -t bookworm-backports`), 1 for a
+//! `NotAutomatic` release such as experimental and 100 if it also has
+//! `ButAutomaticUpgrades`, as backports do. The installed version gets 100
+//! at least. Pins from `/etc/apt/preferences` override these. The version
+//! with the highest priority wins, the newest one among equals, and the
+//! versions older than installed only win with a priority of 1000 or more.
+//!
+//! ```ignore
+//! let releases = vec![bookworm.index_release("main"), backports.index_release("main")];
+//! let policy = Policy::new(releases)
+//!     .default_release("bookworm-backports")
+//!     .pins(Pin::parse_preferences(&preferences)?)
+//!     .installed(installed.versions().clone());
+//! let (candidate, priority) = universe.candidate(&policy, "hello").unwrap();
+//! universe.set_candidate_ordering(CandidateOrdering::Policy(policy));
+//! ```
+
+use {
+    crate::{
+        control::{ControlFile, ParseError},
+        packages::Package,
+        universe::Universe,
+        version::Version,
+    },
+    std::{cmp::Ordering, collections::HashMap, fmt::Write},
+};
+
+/// The release an index of a universe comes from, as pins match it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IndexRelease {
+    /// The suite, e.g. `bookworm-backports`, matched by `a=`
+    pub archive: Option<String>,
+    /// Matched by `n=`
+    pub codename: Option<String>,
+    /// Matched by `o=`
+    pub origin: Option<String>,
+    /// Matched by `l=`
+    pub label: Option<String>,
+    /// Matched by `c=`
+    pub component: Option<String>,
+    /// The host of the repository, matched by `Pin: origin`
+    pub site: Option<String>,
+    pub not_automatic: bool,
+    pub but_automatic_upgrades: bool,
+}
+
+impl IndexRelease {
+    // Matches a `key=value` term of a release pin.
+    fn matches_term(&self, key: &str, value: &str) -> bool {
+        let field = match key {
+            "a" => &self.archive,
+            "n" => &self.codename,
+            "o" => &self.origin,
+            "l" => &self.label,
+            "c" => &self.component,
+            _ => return false,
+        };
+        field.as_deref().is_some_and(|field| glob(value, field))
+    }
+}
+
+/// What a pin applies to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PinTarget {
+    /// The indexes of the releases matching all the `key=value` terms
+    Release(Vec<(String, String)>),
+    /// The versions matching the pattern, e.g. `2.10*`
+    Version(String),
+    /// The indexes from the host
+    Origin(String),
+}
+
+/// A pin of the apt preferences.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pin {
+    /// The package names or patterns, `*` for all the packages
+    pub packages: Vec<String>,
+    pub target: PinTarget,
+    pub priority: i32,
+}
+
+impl Pin {
+    /// Parses the stanzas of an apt preferences file.
+    pub fn parse_preferences(src: &str) -> Result<Vec<Pin>, ParseError> {
+        ControlFile::parse(src)?
+            .stanzas()
+            .map(|stanza| {
+                let field = |name| {
+                    stanza
+                        .field(name)
+                        .map(str::trim)
+                        .ok_or_else(|| ParseError::from(format!("preferences: no {} field", name)))
+                };
+                let packages = field("Package")?
+                    .split_whitespace()
+                    .map(str::to_string)
+                    .collect();
+                let priority = field("Pin-Priority")?.parse().map_err(|err| {
+                    ParseError::from(format!("preferences: invalid Pin-Priority: {}", err))
+                })?;
+                let target = match field("Pin")?.split_once(char::is_whitespace) {
+                    Some(("release", terms)) => PinTarget::Release(
+                        terms
+                            .split(',')
+                            .map(|term| match term.trim().split_once('=') {
+                                Some((key, value)) => (key.to_string(), value.to_string()),
+                                None => ("a".to_string(), term.trim().to_string()),
+                            })
+                            .collect(),
+                    ),
+                    Some(("version", version)) => PinTarget::Version(version.trim().to_string()),
+                    Some(("origin", host)) => {
+                        PinTarget::Origin(host.trim().trim_matches('"').to_string())
+                    }
+                    _ => return Err(format!("preferences: invalid Pin {}", field("Pin")?).into()),
+                };
+                Ok(Pin {
+                    packages,
+                    target,
+                    priority,
+                })
+            })
+            .collect()
+    }
+    // Pins for all the packages apply to the indexes, the others to the
+    // versions of their packages.
+    fn is_generic(&self) -> bool {
+        self.packages.iter().all(|p| p == "*")
+    }
+    fn matches(&self, package: &Package<'_>, release: Option<&IndexRelease>) -> bool {
+        self.packages.iter().any(|p| glob(p, package.name()))
+            && match &self.target {
+                PinTarget::Release(terms) => release.is_some_and(|release| {
+                    terms
+                        .iter()
+                        .all(|(key, value)| release.matches_term(key, value))
+                }),
+                PinTarget::Version(pattern) => glob(pattern, &package.version().to_string()),
+                PinTarget::Origin(host) => {
+                    release.and_then(|r| r.site.as_deref()).unwrap_or_default() == host
+                }
+            }
+    }
+}
+
+// Matches `text` against a pattern with the `*` and `?` wildcards.
+pub(crate) fn glob(pattern: &str, text: &str) -> bool {
+    match pattern.split_once('*') {
+        None => {
+            pattern.len() == text.len()
+                && pattern
+                    .chars()
+                    .zip(text.chars())
+                    .all(|(p, t)| p == '?' || p == t)
+        }
+        Some((head, tail)) => {
+            text.len() >= head.len()
+                && text.is_char_boundary(head.len())
+                && glob(head, &text[..head.len()])
+                && text[head.len()..]
+                    .char_indices()
+                    .map(|(i, _)| i)
+                    .chain([text.len() - head.len()])
+                    .any(|i| glob(tail, &text[head.len() + i..]))
+        }
+    }
+}
+
+/// The pin priorities of the packages of a universe.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Policy {
+    releases: Vec<IndexRelease>,
+    pins: Vec<Pin>,
+    default_release: Option<String>,
+    installed: HashMap<String, String>,
+}
+
+impl Policy {
+    /// A policy for the universe of indexes from `releases`, in the order
+    /// of the indexes.
+    pub fn new(releases: Vec<IndexRelease>) -> Self {
+        Self {
+            releases,
+            ..Self::default()
+        }
+    }
+    /// Adds the pins, e.g. from [`Pin::parse_preferences`]. The first
+    /// matching pin applies.
+    pub fn pins(mut self, pins: impl IntoIterator<Item = Pin>) -> Self {
+        self.pins.extend(pins);
+        self
+    }
+    /// Gives the priority 990 to the release with the suite or codename
+    /// `release`, like `apt-get -t`.
+    pub fn default_release(mut self, release: impl Into<String>) -> Self {
+        self.default_release = Some(release.into());
+        self
+    }
+    /// Sets the installed versions, by package name.
+    pub fn installed(mut self, installed: HashMap<String, String>) -> Self {
+        self.installed = installed;
+        self
+    }
+    /// The release of the index at `origin`.
+    pub fn release(&self, origin: usize) -> Option<&IndexRelease> {
+        self.releases.get(origin)
+    }
+    /// The pin priority of `package` from the index at `origin`.
+    pub fn priority(&self, package: &Package<'_>, origin: usize) -> i32 {
+        let release = self.release(origin);
+        let (specific, generic): (Vec<&Pin>, Vec<&Pin>) =
+            self.pins.iter().partition(|pin| !pin.is_generic());
+        let priority = match specific.iter().find(|pin| pin.matches(package, release)) {
+            Some(pin) => pin.priority,
+            None => match generic.iter().find(|pin| pin.matches(package, release)) {
+                Some(pin) => pin.priority,
+                None => self.release_priority(release),
+            },
+        };
+        match self.installed.get(package.name()) {
+            Some(version) if package.version() == version.as_str() => priority.max(100),
+            _ => priority,
+        }
+    }
+    // The default priority of the indexes of `release`.
+    fn release_priority(&self, release: Option<&IndexRelease>) -> i32 {
+        let Some(release) = release else {
+            return 500;
+        };
+        let is_default = self.default_release.as_ref().is_some_and(|default| {
+            [&release.archive, &release.codename]
+                .iter()
+                .any(|name| name.as_ref() == Some(default))
+        });
+        match (
+            is_default,
+            release.not_automatic,
+            release.but_automatic_upgrades,
+        ) {
+            (true, _, _) => 990,
+            (false, true, true) => 100,
+            (false, true, false) => 1,
+            (false, false, _) => 500,
+        }
+    }
+    /// The priority of `package` as a candidate: versions older than the
+    /// installed one only compete with a priority of 1000 or more.
+    pub fn rank(&self, package: &Package<'_>, origin: usize) -> i32 {
+        let priority = self.priority(package, origin);
+        let installed = self
+            .installed
+            .get(package.name())
+            .map(|v| Version::from(v.as_str()));
+        match installed {
+            Some(installed) if package.version() < installed && priority < 1000 => 0,
+            _ => priority,
+        }
+    }
+    /// Picks the candidate among the `versions` of a package, given with
+    /// the position of their index, and returns it with its priority. The
+    /// installed version competes too, with the priority 100 if missing
+    /// from the indexes; None is returned if it wins or nothing does.
+    pub fn candidate<'p>(
+        &self,
+        versions: impl IntoIterator<Item = (&'p Package<'p>, usize)>,
+    ) -> Option<(&'p Package<'p>, i32)> {
+        let mut best: Option<(&'p Package<'p>, i32)> = None;
+        let mut installed: Option<Version<&str>> = None;
+        for (package, origin) in versions {
+            installed = self
+                .installed
+                .get(package.name())
+                .map(|v| Version::from(v.as_str()));
+            let rank = self.rank(package, origin);
+            let better = match best {
+                None => rank > 0,
+                Some((that, priority)) => match rank.cmp(&priority) {
+                    Ordering::Greater => true,
+                    Ordering::Equal => package.version() > that.version(),
+                    Ordering::Less => false,
+                },
+            };
+            if better {
+                best = Some((package, rank));
+            }
+        }
+        match (best, installed) {
+            (Some((package, priority)), Some(installed))
+                if priority <= 100 && package.version() < installed =>
+            {
+                None
+            }
+            (best, _) => best,
+        }
+    }
+    /// Compares the packages by name, then by priority and version, the
+    /// candidates first.
+    pub(crate) fn compare(
+        &self,
+        (this, this_origin): (&Package<'_>, usize),
+        (that, that_origin): (&Package<'_>, usize),
+    ) -> Ordering {
+        this.name()
+            .cmp(that.name())
+            .then_with(|| {
+                self.rank(that, that_origin)
+                    .cmp(&self.rank(this, this_origin))
+            })
+            .then_with(|| that.version().cmp(&this.version()))
+    }
+}
+
+impl<S: AsRef<str> + 'static> Universe<S> {
+    /// Returns the candidate version of the package `name` under `policy`
+    /// with its priority, None if the installed version stays.
+    pub fn candidate(&self, policy: &Policy, name: &str) -> Option<(&Package<'_>, i32)> {
+        policy.candidate(self.versions_of(name))
+    }
+    /// Renders the versions of the package `name` and their priorities
+    /// like `apt-cache policy`:
+    ///
+    /// ```text
+    /// hello:
+    ///   Installed: 2.10-2
+    ///   Candidate: 2.10-3~bpo12+1
+    ///   Version table:
+    ///      2.10-3~bpo12+1 600
+    ///         100 bookworm-backports/main
+    ///  *** 2.10-2 500
+    ///         500 bookworm/main
+    ///         100 /var/lib/dpkg/status
+    /// ```
+    pub fn render_policy(&self, policy: &Policy, name: &str) -> String {
+        let installed = policy.installed.get(name);
+        let candidate = match self.candidate(policy, name) {
+            Some((package, _)) => package.version().to_string(),
+            None => installed.cloned().unwrap_or_else(|| "(none)".to_string()),
+        };
+        let mut versions: Vec<(Version<&str>, Vec<_>)> = vec![];
+        for (package, origin) in self.versions_of(name) {
+            let priority = policy.priority(package, origin);
+            match versions.iter_mut().find(|(v, _)| *v == package.version()) {
+                Some((_, origins)) => origins.push((origin, priority)),
+                None => versions.push((package.version(), vec![(origin, priority)])),
+            }
+        }
+        if let Some(installed) = installed {
+            let version = Version::from(installed.as_str());
+            if !versions.iter().any(|(v, _)| *v == version) {
+                versions.push((version, vec![]));
+            }
+        }
+        versions.sort_by(|(a, _), (b, _)| b.cmp(a));
+        let mut out = String::new();
+        let _ = writeln!(out, "{}:", name);
+        let _ = writeln!(
+            out,
+            "  Installed: {}",
+            installed.map_or("(none)", String::as_str)
+        );
+        let _ = writeln!(out, "  Candidate: {}", candidate);
+        let _ = writeln!(out, "  Version table:");
+        for (version, origins) in versions {
+            let is_installed = installed.is_some_and(|v| version == v.as_str());
+            let priority = origins
+                .iter()
+                .map(|(_, priority)| *priority)
+                .chain(is_installed.then_some(100))
+                .max()
+                .unwrap_or_default();
+            let mark = if is_installed { "***" } else { "   " };
+            let _ = writeln!(out, " {} {} {}", mark, version, priority);
+            for (origin, priority) in origins {
+                let release = policy.release(origin);
+                let suite = release
+                    .and_then(|r| r.archive.as_ref().or(r.codename.as_ref()))
+                    .map_or("?", String::as_str);
+                match release.and_then(|r| r.component.as_ref()) {
+                    Some(component) => {
+                        let _ = writeln!(out, "        {} {}/{}", priority, suite, component);
+                    }
+                    None => {
+                        let _ = writeln!(out, "        {} {}", priority, suite);
+                    }
+                }
+            }
+            if is_installed {
+                let _ = writeln!(out, "        100 /var/lib/dpkg/status");
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{ordering::CandidateOrdering, packages::Packages, version::Dependency},
+    };
+
+    #[test]
+    fn test_glob() {
+        assert!(glob("*", "hello"));
+        assert!(glob("lib*-dev", "libfoo-dev"));
+        assert!(!glob("lib*-dev", "libfoo"));
+        assert!(glob("2.10?3*", "2.10-3+b1"));
+        assert!(!glob("hello", "hello2"));
+    }
+
+    #[test]
+    fn test_candidate() {
+        let packages = |src: &'static str| Packages::new_test(src).unwrap();
+        let main = packages("Package: hello\nVersion: 2.10-2\nArchitecture: amd64\n");
+        let backports = packages("Package: hello\nVersion: 2.10-3~bpo12+1\nArchitecture: amd64\n");
+        let experimental = packages("Package: hello\nVersion: 3.0-1\nArchitecture: amd64\n");
+        let versions = || {
+            [&main, &backports, &experimental]
+                .into_iter()
+                .enumerate()
+                .map(|(origin, index)| (index.packages().next().unwrap(), origin))
+        };
+        let release = |archive: &str, not_automatic, but_automatic_upgrades| IndexRelease {
+            archive: Some(archive.to_string()),
+            not_automatic,
+            but_automatic_upgrades,
+            ..IndexRelease::default()
+        };
+        let policy = Policy::new(vec![
+            release("bookworm", false, false),
+            release("bookworm-backports", true, true),
+            release("experimental", true, false),
+        ]);
+        let candidate = |policy: &Policy| {
+            policy
+                .candidate(versions())
+                .map(|(p, priority)| (p.version().to_string(), priority))
+        };
+        assert_eq!(candidate(&policy), Some(("2.10-2".to_string(), 500)));
+        let installed = HashMap::from([("hello".to_string(), "2.10-3~bpo12+1".to_string())]);
+        assert_eq!(
+            candidate(&policy.clone().installed(installed)),
+            Some(("2.10-3~bpo12+1".to_string(), 100))
+        );
+        assert_eq!(
+            candidate(&policy.clone().default_release("experimental")),
+            Some(("3.0-1".to_string(), 990))
+        );
+        let pins = Pin::parse_preferences(
+            "Package: hello\nPin: release a=bookworm-backports\nPin-Priority: 600\n",
+        )
+        .unwrap();
+        assert_eq!(
+            candidate(&policy.clone().pins(pins)),
+            Some(("2.10-3~bpo12+1".to_string(), 600))
+        );
+        let installed = HashMap::from([("hello".to_string(), "4.0-1".to_string())]);
+        assert_eq!(candidate(&policy.installed(installed)), None);
+    }
+
+    #[test]
+    fn test_render_policy() {
+        let index = |version| {
+            let src = format!(
+                "Package: hello\nVersion: {}\nArchitecture: amd64\n",
+                version
+            );
+            Packages::new_test(src).unwrap()
+        };
+        let mut uni =
+            Universe::new("amd64", vec![index("2.10-2"), index("2.10-3~bpo12+1")]).unwrap();
+        let release = |archive: &str, not_automatic| IndexRelease {
+            archive: Some(archive.to_string()),
+            component: Some("main".to_string()),
+            not_automatic,
+            but_automatic_upgrades: not_automatic,
+            ..IndexRelease::default()
+        };
+        let installed = HashMap::from([("hello".to_string(), "2.10-2".to_string())]);
+        let policy = Policy::new(vec![
+            release("bookworm", false),
+            release("bookworm-backports", true),
+        ])
+        .pins(
+            Pin::parse_preferences("Package: hello\nPin: version 2.10-3*\nPin-Priority: 600\n")
+                .unwrap(),
+        )
+        .installed(installed);
+        assert_eq!(
+            uni.render_policy(&policy, "hello"),
+            "\
+hello:
+  Installed: 2.10-2
+  Candidate: 2.10-3~bpo12+1
+  Version table:
+     2.10-3~bpo12+1 600
+        600 bookworm-backports/main
+ *** 2.10-2 500
+        500 bookworm/main
+        100 /var/lib/dpkg/status
+"
+        );
+        uni.set_candidate_ordering(CandidateOrdering::Policy(policy));
+        let problem = uni.problem(vec![Dependency::try_from("hello").unwrap()], vec![], vec![]);
+        let solution = uni.solve(problem).unwrap();
+        assert_eq!(uni.package(solution[0]).version(), "2.10-3~bpo12+1");
+    }
+}
@@ -0,0 +1,432 @@
+//! Repository pool maintenance
+//!
+//! The pool of a long-lived repository accumulates files no index refers to
+//! any more: superseded versions, removed packages, aborted uploads. [`gc`]
+//! finds them and deletes them, or only lists them in dry-run mode:
+//!
+//! ```ignore
+//! let indexes = PoolIndexes::new().packages(&packages).sources(&sources);
+//! let report = pool::gc("/srv/repo", &indexes, Duration::from_secs(86400), true).await?;
+//! for path in &report.orphans {
+//!     println!("{}", path);
+//! }
+//! ```
+//!
+//! The pool itself is laid out by source package: [`deb_path`] gives the
+//! canonical `Filename` of a package, [`validate_filename`] checks the ones
+//! coming from indexes before they are used as local paths.
+
+use {
+    crate::{
+        packages::{PackageKind, Packages},
+        srcindex::SourcesIndex,
+    },
+    std::{collections::HashSet, io},
+};
+#[cfg(feature = "io")]
+use {
+    async_std::{
+        fs,
+        path::{Path, PathBuf},
+        stream::StreamExt,
+    },
+    std::time::{Duration, SystemTime},
+};
+
+/// Returns the pool directory of the source package `source` in
+/// `component`, e.g. `pool/main/h/hello`. Source packages are grouped by
+/// their first letter, the `lib` ones by their first four, e.g.
+/// `pool/main/libf/libfoo`.
+pub fn source_dir(component: &str, source: &str) -> String {
+    let prefix = match source.get(..4) {
+        Some(prefix) if source.starts_with("lib") => prefix,
+        _ => source.get(..1).unwrap_or_default(),
+    };
+    format!("pool/{}/{}/{}", component, prefix, source)
+}
+
+/// Returns the canonical `Filename` of the binary package `name` built from
+/// `source`, e.g. `pool/main/libf/libfoo/libfoo1_1.2-3_amd64.deb`. The epoch
+/// of the version is left out of the file name.
+pub fn deb_path(
+    component: &str,
+    source: &str,
+    name: &str,
+    version: &str,
+    arch: &str,
+    kind: PackageKind,
+) -> String {
+    let version = match version.split_once(':') {
+        Some((epoch, version)) if epoch.bytes().all(|b| b.is_ascii_digit()) => version,
+        _ => version,
+    };
+    let ext = match kind {
+        PackageKind::Deb => "deb",
+        PackageKind::Udeb => "udeb",
+    };
+    format!(
+        "{}/{}_{}_{}.{}",
+        source_dir(component, source),
+        name,
+        version,
+        arch,
+        ext
+    )
+}
+
+/// Checks that a `Filename` from an index is a plain relative path that
+/// stays within the repository: no root, no `..` or `.` component but a
+/// leading `./`, no empty component, backslash or control character.
+pub fn validate_filename(path: &str) -> io::Result<()> {
+    let rel = path.strip_prefix("./").unwrap_or(path);
+    let valid = !rel.is_empty()
+        && !rel.chars().any(|c| c == '\\' || c.is_control())
+        && rel
+            .split('/')
+            .all(|part| !part.is_empty() && part != "." && part != "..");
+    if valid {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid repository path {:?}", path),
+        ))
+    }
+}
+
+// Returns `path` without a leading `./`, empty or `.` components, if it
+// is then valid for `validate_filename`.
+fn normalize_filename(path: &str) -> Option<String> {
+    let path = path.trim();
+    let rel = path
+        .split('/')
+        .filter(|part| !part.is_empty() && *part != ".")
+        .collect::<Vec<_>>()
+        .join("/");
+    (!path.starts_with('/') && validate_filename(&rel).is_ok()).then_some(rel)
+}
+
+// The binary packages are referred to by Packages indexes, the other pool
+// files by Sources indexes.
+fn is_binary(path: &str) -> bool {
+    [".deb", ".udeb", ".ddeb"]
+        .iter()
+        .any(|ext| path.ends_with(ext))
+}
+
+/// The pool files referred to by the indexes of a repository. The file
+/// names are compared in the normal form of [`validate_filename`], e.g.
+/// `./pool//main/h/hello.dsc` refers to `pool/main/h/hello.dsc`.
+#[derive(Clone, Debug, Default)]
+pub struct PoolIndexes {
+    files: HashSet<String>,
+    // the names no normal form is valid for
+    invalid: Vec<String>,
+    packages: bool,
+    sources: bool,
+}
+
+impl PoolIndexes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Adds the files of the packages of a Packages index.
+    pub fn packages<S: AsRef<str>>(mut self, index: &Packages<S>) -> Self {
+        for path in index.packages().filter_map(|p| p.field("Filename")) {
+            self.add(path);
+        }
+        self.packages = true;
+        self
+    }
+    /// Adds the files of the source packages of a Sources index.
+    pub fn sources(mut self, index: &SourcesIndex) -> Self {
+        for package in index.packages() {
+            let dir = package
+                .directory()
+                .unwrap_or_default()
+                .trim_end_matches('/');
+            for file in package.files() {
+                self.add(&format!("{}/{}", dir, file.name));
+            }
+        }
+        self.sources = true;
+        self
+    }
+    fn add(&mut self, path: &str) {
+        match normalize_filename(path) {
+            Some(path) => {
+                self.files.insert(path);
+            }
+            None => self.invalid.push(path.to_string()),
+        }
+    }
+    /// Returns true if the file at `path`, relative to the repository
+    /// root, is referred to.
+    pub fn contains(&self, path: &str) -> bool {
+        normalize_filename(path).is_some_and(|path| self.files.contains(&path))
+    }
+    // Returns true if an index of the kind referring to `path` was added,
+    // so that `path` is known to be unreferenced unless contained.
+    fn covers(&self, path: &str) -> bool {
+        match is_binary(path) {
+            true => self.packages,
+            false => self.sources,
+        }
+    }
+}
+
+/// The outcome of a pool garbage collection.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GcReport {
+    /// The unreferenced files, relative to the repository root, sorted
+    pub orphans: Vec<String>,
+    /// The total size of the orphans
+    pub bytes: u64,
+    /// The unreferenced files left alone as younger than the grace period
+    pub recent: Vec<String>,
+    /// False in dry-run mode
+    pub deleted: bool,
+}
+
+/// Looks for the files under `pool/` in the repository at `dir` that
+/// `indexes` do not refer to, and deletes them, along with the directories
+/// left empty, unless `dry_run` is set. Files modified within
+/// `grace_period` are kept, as they may belong to an upload whose indexes
+/// are not published yet.
+///
+/// Only the files of the kinds of the indexes added are considered: the
+/// `.deb`, `.udeb` and `.ddeb` files with Packages indexes, the others with
+/// Sources indexes. Fails before deleting anything if an index refers to an
+/// invalid file name, as [`validate_filename`] checks them.
+#[cfg(feature = "io")]
+pub async fn gc(
+    dir: impl AsRef<Path>,
+    indexes: &PoolIndexes,
+    grace_period: Duration,
+    dry_run: bool,
+) -> io::Result<GcReport> {
+    if let Some(path) = indexes.invalid.first() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid repository path {:?}", path),
+        ));
+    }
+    let root = dir.as_ref();
+    let now = SystemTime::now();
+    let mut report = GcReport {
+        deleted: !dry_run,
+        ..GcReport::default()
+    };
+    let mut pending = vec![PathBuf::from("pool")];
+    let mut dirs = vec![];
+    while let Some(dir) = pending.pop() {
+        let mut entries = match fs::read_dir(root.join(&dir)).await {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err),
+        };
+        while let Some(entry) = entries.next().await {
+            let entry = entry?;
+            let path = dir.join(entry.file_name());
+            let meta = entry.metadata().await?;
+            if meta.is_dir() {
+                pending.push(path.clone());
+                dirs.push(path);
+                continue;
+            }
+            let name = path.to_str().map(|p| p.replace('\\', "/")).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("pool file name {:?} is not a valid UTF-8", path),
+                )
+            })?;
+            if indexes.contains(&name) || !indexes.covers(&name) {
+                continue;
+            }
+            let age = meta
+                .modified()
+                .ok()
+                .and_then(|modified| now.duration_since(modified).ok())
+                .unwrap_or_default();
+            if age < grace_period {
+                report.recent.push(name);
+                continue;
+            }
+            if !dry_run {
+                fs::remove_file(root.join(&path)).await?;
+            }
+            report.bytes += meta.len();
+            report.orphans.push(name);
+        }
+    }
+    if !dry_run {
+        // the deepest directories first
+        dirs.sort_by_key(|dir| std::cmp::Reverse(dir.components().count()));
+        for dir in dirs {
+            // fails unless empty
+            fs::remove_dir(root.join(&dir)).await.ok();
+        }
+    }
+    report.orphans.sort();
+    report.recent.sort();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deb_path() {
+        assert_eq!(source_dir("main", "hello"), "pool/main/h/hello");
+        assert_eq!(source_dir("main", "lib"), "pool/main/l/lib");
+        assert_eq!(
+            deb_path(
+                "main",
+                "libfoo",
+                "libfoo1",
+                "1:1.2-3",
+                "amd64",
+                PackageKind::Deb
+            ),
+            "pool/main/libf/libfoo/libfoo1_1.2-3_amd64.deb"
+        );
+        assert_eq!(
+            deb_path(
+                "main",
+                "glibc",
+                "libc6-udeb",
+                "2.36-9",
+                "amd64",
+                PackageKind::Udeb
+            ),
+            "pool/main/g/glibc/libc6-udeb_2.36-9_amd64.udeb"
+        );
+    }
+
+    #[test]
+    fn test_validate_filename() {
+        for path in [
+            "pool/main/h/hello/hello_2.10-3_amd64.deb",
+            "./hello_1.0_all.deb",
+        ] {
+            assert!(validate_filename(path).is_ok(), "{}", path);
+        }
+        for path in [
+            "",
+            "/etc/passwd",
+            "../secret",
+            "pool/../../secret",
+            "pool//main",
+            "pool/./main",
+            "pool\\..\\secret",
+            "pool/main\n",
+        ] {
+            assert!(validate_filename(path).is_err(), "{:?}", path);
+        }
+    }
+
+    #[test]
+    fn test_pool_indexes() {
+        let packages = Packages::new_test(
+            "Package: hello\nVersion: 2.10-3\nArchitecture: amd64\n\
+             Filename: ./pool//main/h/hello/hello_2.10-3_amd64.deb\n",
+        )
+        .unwrap();
+        let indexes = PoolIndexes::new().packages(&packages);
+        assert!(indexes.contains("pool/main/h/hello/hello_2.10-3_amd64.deb"));
+        assert!(indexes.contains("./pool/main/h/hello/hello_2.10-3_amd64.deb"));
+        assert!(indexes.covers("pool/main/h/hello/hello_2.10-2_amd64.deb"));
+        // no Sources index, so the source files are not known to be orphans
+        assert!(!indexes.covers("pool/main/h/hello/hello_2.10-3.dsc"));
+    }
+
+    #[cfg(feature = "io")]
+    #[async_std::test]
+    async fn test_gc_partial_indexes() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for path in [
+            "pool/main/h/hello/hello_2.10-3_amd64.deb",
+            "pool/main/h/hello/hello_2.10-3.dsc",
+        ] {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, "data").unwrap();
+        }
+        let packages = Packages::new_test(
+            "Package: hello\nVersion: 2.10-3\nArchitecture: amd64\n\
+             Filename: ./pool/main/h/hello/hello_2.10-3_amd64.deb\n",
+        )
+        .unwrap();
+        let indexes = PoolIndexes::new().packages(&packages);
+        let report = gc(root, &indexes, Duration::ZERO, false).await.unwrap();
+        assert!(report.orphans.is_empty());
+        assert!(root.join("pool/main/h/hello/hello_2.10-3.dsc").exists());
+
+        let invalid = Packages::new_test(
+            "Package: hello\nVersion: 2.10-3\nArchitecture: amd64\n\
+             Filename: pool/../hello_2.10-3_amd64.deb\n",
+        )
+        .unwrap();
+        let indexes = PoolIndexes::new().packages(&invalid);
+        assert!(gc(root, &indexes, Duration::ZERO, false).await.is_err());
+        assert!(root
+            .join("pool/main/h/hello/hello_2.10-3_amd64.deb")
+            .exists());
+    }
+
+    #[cfg(feature = "io")]
+    #[async_std::test]
+    async fn test_gc() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let day_ago =
+            filetime::FileTime::from_system_time(SystemTime::now() - Duration::from_secs(86400));
+        for path in [
+            "pool/main/h/hello/hello_2.10-3_amd64.deb",
+            "pool/main/h/hello/hello_2.10-2_amd64.deb",
+            "pool/main/h/hello/hello_2.10-3.dsc",
+            "pool/main/o/old/old_1.0_all.deb",
+            "pool/main/n/new/new_1.0_all.deb",
+        ] {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, "data").unwrap();
+            if !path.ends_with("new_1.0_all.deb") {
+                filetime::set_file_mtime(&path, day_ago).unwrap();
+            }
+        }
+        let packages = Packages::new_test(
+            "Package: hello\nVersion: 2.10-3\nArchitecture: amd64\n\
+             Filename: pool/main/h/hello/hello_2.10-3_amd64.deb\n",
+        )
+        .unwrap();
+        let sources = SourcesIndex::parse(
+            "Package: hello\nVersion: 2.10-3\nDirectory: pool/main/h/hello\nFiles:\n \
+             5a4d2b1a2f6c0e1b7d9e8f3a4b5c6d7e 1183 hello_2.10-3.dsc\n",
+        )
+        .unwrap();
+        let indexes = PoolIndexes::new().packages(&packages).sources(&sources);
+        let hour = Duration::from_secs(3600);
+        let report = gc(root, &indexes, hour, true).await.unwrap();
+        assert_eq!(
+            report.orphans,
+            [
+                "pool/main/h/hello/hello_2.10-2_amd64.deb",
+                "pool/main/o/old/old_1.0_all.deb"
+            ]
+        );
+        assert_eq!(report.recent, ["pool/main/n/new/new_1.0_all.deb"]);
+        assert_eq!(report.bytes, 8);
+        assert!(root.join("pool/main/o/old/old_1.0_all.deb").exists());
+        let report = gc(root, &indexes, hour, false).await.unwrap();
+        assert!(report.deleted);
+        assert!(!root.join("pool/main/o").exists());
+        assert!(!root
+            .join("pool/main/h/hello/hello_2.10-2_amd64.deb")
+            .exists());
+        assert!(root.join("pool/main/h/hello/hello_2.10-3.dsc").exists());
+        assert!(root.join("pool/main/n/new/new_1.0_all.deb").exists());
+    }
+}
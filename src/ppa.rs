@@ -0,0 +1,128 @@
+//! Ubuntu PPAs
+//!
+//! A Personal Package Archive `ppa:user/name` is served from
+//! `https://ppa.launchpadcontent.net/user/name/ubuntu/` and signed with a key
+//! of its own, whose fingerprint is published by the Launchpad API.
+
+use {
+    crate::{
+        error::{Error, Result},
+        httprepo::HttpDebRepo,
+        keyring::Keyring,
+        repo::DebRepo,
+    },
+    std::io,
+};
+
+const PPA_URL: &str = "https://ppa.launchpadcontent.net/";
+const LAUNCHPAD_API_URL: &str = "https://api.launchpad.net/1.0/";
+const KEYSERVER_URL: &str = "https://keyserver.ubuntu.com/";
+
+// Splits `ppa:user/name`, `user/name` or `ppa:user` (the `ppa` archive of
+// the user) into the user and the archive name.
+fn parse_ppa(ppa: &str) -> io::Result<(&str, &str)> {
+    let ppa = ppa.strip_prefix("ppa:").unwrap_or(ppa);
+    let (user, name) = ppa.split_once('/').unwrap_or((ppa, "ppa"));
+    let valid = |s: &str| {
+        !s.is_empty()
+            && s.bytes()
+                .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'+'))
+    };
+    if valid(user) && valid(name) {
+        Ok((user, name))
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid PPA {}", ppa),
+        ))
+    }
+}
+
+// The string value of `field` in the JSON object `json`.
+fn json_string<'a>(json: &'a str, field: &str) -> Option<&'a str> {
+    let key = format!("\"{}\"", field);
+    let rest = json[json.find(&key)? + key.len()..].trim_start();
+    let rest = rest.strip_prefix(':')?.trim_start().strip_prefix('"')?;
+    rest.find('"').map(|end| &rest[..end])
+}
+
+/// The repository URL of the PPA, e.g.
+/// `https://ppa.launchpadcontent.net/deadsnakes/ppa/ubuntu/` for
+/// `ppa:deadsnakes/ppa`.
+pub fn ppa_url(ppa: &str) -> io::Result<String> {
+    let (user, name) = parse_ppa(ppa)?;
+    Ok(format!("{}{}/{}/ubuntu/", PPA_URL, user, name))
+}
+
+/// Fetches the signing key of the PPA: its fingerprint from the Launchpad
+/// API, the key from the Ubuntu keyserver. Only the key with that
+/// fingerprint is kept from the keyserver response, which fails if there is
+/// none.
+pub async fn ppa_signing_key(ppa: &str) -> Result<Vec<u8>> {
+    let (user, name) = parse_ppa(ppa)?;
+    let api: DebRepo = HttpDebRepo::new(LAUNCHPAD_API_URL).await?.into();
+    let archive = api
+        .fetch(&format!("~{}/+archive/ubuntu/{}", user, name))
+        .await?;
+    let fingerprint = json_string(
+        &String::from_utf8_lossy(&archive),
+        "signing_key_fingerprint",
+    )
+    .filter(|fingerprint| !fingerprint.is_empty())
+    .map(str::to_owned)
+    .ok_or_else(|| Error::Signature(format!("PPA {}/{} has no signing key", user, name)))?;
+    let keyserver: DebRepo = HttpDebRepo::new(KEYSERVER_URL).await?.into();
+    let key = keyserver
+        .fetch(&format!(
+            "pks/lookup?op=get&options=mr&exact=on&search=0x{}",
+            fingerprint
+        ))
+        .await?;
+    let mut keyring = Keyring::new();
+    keyring.add(key)?;
+    let selected = keyring.select(&[&fingerprint])?;
+    let key = selected.keys().next().map(<[u8]>::to_vec);
+    key.ok_or_else(|| {
+        Error::Signature(format!(
+            "the keyserver has no key {} for PPA {}/{}",
+            fingerprint, user, name
+        ))
+    })
+}
+
+impl DebRepo {
+    /// Opens the PPA, e.g. `ppa:user/name` or `user/name`, configured to
+    /// verify its Release files with the signing key of the PPA, e.g. with
+    /// `repo.fetch_verify_release("noble")`.
+    pub async fn ppa(ppa: &str) -> Result<DebRepo> {
+        let key = ppa_signing_key(ppa).await?;
+        let repo: DebRepo = HttpDebRepo::new(&ppa_url(ppa)?).await?.into();
+        Ok(repo.with_keys([key]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ppa() {
+        assert_eq!(
+            ppa_url("ppa:deadsnakes/ppa").unwrap(),
+            "https://ppa.launchpadcontent.net/deadsnakes/ppa/ubuntu/"
+        );
+        assert_eq!(parse_ppa("user/name").unwrap(), ("user", "name"));
+        assert_eq!(parse_ppa("ppa:user").unwrap(), ("user", "ppa"));
+        assert!(parse_ppa("ppa:user/../x").is_err());
+        assert!(parse_ppa("/name").is_err());
+        let json = r#"{"name": "ppa",
+            "signing_key_fingerprint" : "F23C5A6CF475977595C89F51BA6932366A755776",
+            "x": 1}"#;
+        assert_eq!(
+            json_string(json, "signing_key_fingerprint"),
+            Some("F23C5A6CF475977595C89F51BA6932366A755776")
+        );
+        assert_eq!(json_string(json, "x"), None);
+        assert_eq!(json_string(json, "missing"), None);
+    }
+}
@@ -0,0 +1,33 @@
+//! Progress reporting
+//!
+//! Long operations report their progress to a [`Progress`] set with
+//! [`crate::DebRepo::with_progress`] for downloads and
+//! [`crate::Universe::new_with_progress`] for indexing and solving. All the
+//! methods do nothing by default, so an implementation only overrides the
+//! ones it displays.
+//!
+//! No progress bar comes with the crate: there is no indicatif-based
+//! implementation, to keep indicatif out of the dependencies. The
+//! `--progress` option of the deb-repo tool prints apt-style `Get:` lines,
+//! and an application wanting bars implements [`Progress`] over its own.
+
+/// A receiver of progress notifications.
+pub trait Progress: Send + Sync {
+    /// An index file of `size` bytes is about to be fetched from `path`.
+    fn on_index_fetch(&self, _path: &str, _size: u64) {}
+    /// The package file of `package` of `size` bytes is about to be fetched
+    /// from `path`.
+    fn on_package_fetch(&self, _package: &str, _path: &str, _size: u64) {}
+    /// `count` more bytes of a file were received.
+    fn on_bytes(&self, _count: u64) {}
+    /// A step of loading a universe or of solving is done: the `step`-th
+    /// index out of `total` loaded, or the `step`-th package examined by the
+    /// solver, when `total` is unknown.
+    fn on_solve_step(&self, _step: usize, _total: Option<usize>) {}
+}
+
+/// The [`Progress`] reporting nothing.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoProgress;
+
+impl Progress for NoProgress {}
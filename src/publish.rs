@@ -0,0 +1,899 @@
+//! Debian repository publishing
+//!
+//! Builds the `dists/` tree for a suite out of a pool of .deb files or ready
+//! package stanzas: the Packages indexes for every component and architecture,
+//! the Release file listing all of them and, optionally, the signed InRelease
+//! and Release.gpg files.
+//!
+//! # Example
+//!
+//! ```no_run
+//! # async fn example() -> std::io::Result<()> {
+//! # use debrepo::Publisher;
+//! let mut publisher = Publisher::new("/srv/repo", "bookworm");
+//! publisher.origin("Example").architectures(["amd64", "arm64"]);
+//! publisher.scan_pool("main", "pool/main").await?;
+//! publisher.signing_key(std::fs::read("secret.asc")?);
+//! publisher.publish().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use {
+    crate::{
+        control::{ControlFile, MutableControlStanza},
+        deb::{scan_deb, PoolLayout},
+        pool,
+        version::Version,
+    },
+    async_compression::futures::bufread::{GzipEncoder, XzEncoder},
+    async_std::{
+        fs,
+        io::{self, prelude::*},
+        path::{Path, PathBuf},
+        stream::StreamExt,
+    },
+    chrono::{DateTime, Utc},
+    digest::Digest,
+    std::collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+};
+
+/// Which versions of each package a [`Publisher`] keeps in the indexes, e.g.
+/// of a repository of nightly builds. The latest version of a package is
+/// always kept; the others go if any of the limits drops them.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Retention {
+    /// Keep at most this many versions of each package and architecture
+    pub keep_latest: Option<usize>,
+    /// Keep the versions whose pool files were modified after this date
+    pub newer_than: Option<DateTime<Utc>>,
+    /// Move the pool files of the versions dropped to this directory,
+    /// relative to the repository root, keeping their paths, once no
+    /// published index refers to them any more. Otherwise they are left
+    /// in the pool, see [`crate::pool::gc`].
+    pub attic: Option<String>,
+}
+
+/// Publishes a Debian repository suite.
+pub struct Publisher {
+    root: PathBuf,
+    suite: String,
+    codename: Option<String>,
+    origin: Option<String>,
+    label: Option<String>,
+    description: Option<String>,
+    architectures: BTreeSet<String>,
+    signing_key: Option<Vec<u8>>,
+    retention: Retention,
+    components: BTreeMap<String, Vec<MutableControlStanza>>,
+}
+
+impl Publisher {
+    /// Creates a publisher for the suite `suite` of the repository rooted at `root`.
+    pub fn new(root: impl AsRef<Path>, suite: impl Into<String>) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+            suite: suite.into(),
+            codename: None,
+            origin: None,
+            label: None,
+            description: None,
+            architectures: BTreeSet::new(),
+            signing_key: None,
+            retention: Retention::default(),
+            components: BTreeMap::new(),
+        }
+    }
+    pub fn codename(&mut self, codename: impl Into<String>) -> &mut Self {
+        self.codename.replace(codename.into());
+        self
+    }
+    pub fn origin(&mut self, origin: impl Into<String>) -> &mut Self {
+        self.origin.replace(origin.into());
+        self
+    }
+    pub fn label(&mut self, label: impl Into<String>) -> &mut Self {
+        self.label.replace(label.into());
+        self
+    }
+    pub fn description(&mut self, description: impl Into<String>) -> &mut Self {
+        self.description.replace(description.into());
+        self
+    }
+    /// Sets the list of architectures to publish. If not set, the list
+    /// is derived from the added packages.
+    pub fn architectures<I: IntoIterator<Item = impl Into<String>>>(
+        &mut self,
+        arches: I,
+    ) -> &mut Self {
+        self.architectures = arches.into_iter().map(|a| a.into()).collect();
+        self
+    }
+    /// Sets the OpenPGP secret key used to sign the Release file. The key is
+    /// imported into a temporary keyring.
+    pub fn signing_key(&mut self, key: impl Into<Vec<u8>>) -> &mut Self {
+        self.signing_key.replace(key.into());
+        self
+    }
+    /// Sets which versions of the packages to publish.
+    pub fn retention(&mut self, retention: Retention) -> &mut Self {
+        self.retention = retention;
+        self
+    }
+    /// Adds a package stanza to the component. The stanza must already contain
+    /// the `Filename`, `Size` and checksum fields.
+    pub fn add_package(&mut self, component: &str, stanza: MutableControlStanza) -> io::Result<()> {
+        for field in ["Package", "Version", "Architecture", "Filename", "Size"] {
+            if stanza.field(field).is_none() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("package stanza lacks field {}", field),
+                ));
+            }
+        }
+        pool::validate_filename(stanza.field("Filename").unwrap_or_default().trim())?;
+        self.components
+            .entry(component.to_owned())
+            .or_default()
+            .push(stanza);
+        Ok(())
+    }
+    /// Reads a .deb file located at `path` relative to the repository root and
+    /// adds it to the component.
+    pub async fn add_deb(&mut self, component: &str, path: &str) -> io::Result<()> {
+        let stanza = scan_deb(self.root.join(path), &PoolLayout::Path(path.to_owned())).await?;
+        self.add_package(component, stanza)
+    }
+    /// Copies the .deb file at `file` into the pool of the component, at
+    /// its canonical path (see [`pool::deb_path`]), and adds it. Returns the
+    /// path of the copy relative to the repository root.
+    pub async fn import_deb(
+        &mut self,
+        component: &str,
+        file: impl AsRef<Path>,
+    ) -> io::Result<String> {
+        let layout = PoolLayout::Component(component.to_owned());
+        let stanza = scan_deb(file.as_ref(), &layout).await?;
+        let path = stanza.field("Filename").unwrap_or_default().to_owned();
+        let target = self.root.join(&path);
+        if let Some(dir) = target.parent() {
+            fs::create_dir_all(dir).await?;
+        }
+        fs::copy(file.as_ref(), &target).await?;
+        self.add_package(component, stanza)?;
+        Ok(path)
+    }
+    /// Recursively scans `dir` (relative to the repository root) for .deb files
+    /// and adds them to the component.
+    pub async fn scan_pool(&mut self, component: &str, dir: &str) -> io::Result<usize> {
+        let mut pending = vec![PathBuf::from(dir)];
+        let mut found = vec![];
+        while let Some(dir) = pending.pop() {
+            let mut entries = fs::read_dir(self.root.join(&dir)).await?;
+            while let Some(entry) = entries.next().await {
+                let entry = entry?;
+                let path = dir.join(entry.file_name());
+                if entry.file_type().await?.is_dir() {
+                    pending.push(path);
+                } else if path.extension().is_some_and(|ext| ext == "deb") {
+                    found.push(path.to_str().map(|p| p.to_owned()).ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("pool file name {:?} is not a valid UTF-8", path),
+                        )
+                    })?);
+                }
+            }
+        }
+        found.sort();
+        for path in found.iter() {
+            self.add_deb(component, path).await?;
+        }
+        Ok(found.len())
+    }
+    fn arches(&self) -> BTreeSet<String> {
+        if !self.architectures.is_empty() {
+            return self.architectures.clone();
+        }
+        let mut arches: BTreeSet<String> = self
+            .components
+            .values()
+            .flat_map(|pkgs| pkgs.iter())
+            .filter_map(|pkg| pkg.field("Architecture"))
+            .filter(|arch| *arch != "all")
+            .map(|arch| arch.to_owned())
+            .collect();
+        if arches.is_empty() {
+            arches.insert("all".to_owned());
+        }
+        arches
+    }
+    /// Builds the Packages index for the component and architecture.
+    /// Architecture-independent packages are included in every index.
+    pub fn packages_index(&self, component: &str, arch: &str) -> String {
+        self.index(component, arch, &HashSet::new())
+    }
+    // Builds the Packages index without the packages at the paths `dropped`.
+    fn index(&self, component: &str, arch: &str, dropped: &HashSet<String>) -> String {
+        let mut packages: Vec<&MutableControlStanza> = self
+            .components
+            .get(component)
+            .into_iter()
+            .flat_map(|pkgs| pkgs.iter())
+            .filter(|pkg| {
+                pkg.field("Architecture")
+                    .is_some_and(|a| a == arch || a == "all")
+            })
+            .filter(|pkg| !dropped.contains(pkg.field("Filename").unwrap_or_default()))
+            .collect();
+        packages.sort_by(
+            |this, that| match this.field("Package").cmp(&that.field("Package")) {
+                std::cmp::Ordering::Equal => Version::from(this.field("Version").unwrap_or(""))
+                    .cmp(&Version::from(that.field("Version").unwrap_or(""))),
+                ord => ord,
+            },
+        );
+        let mut out = String::new();
+        for pkg in packages {
+            out.push_str(&pkg.to_string());
+            out.push('\n');
+        }
+        out
+    }
+    // Returns the directory of the `dists/<suite>` symlink, its name and the
+    // name of a new directory for it to point to.
+    fn new_generation(&self) -> io::Result<(PathBuf, String, String)> {
+        let link = self.root.join("dists").join(&self.suite);
+        let (Some(parent), Some(name)) = (link.parent(), link.file_name()) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid suite {:?}", self.suite),
+            ));
+        };
+        let name = name.to_string_lossy().into_owned();
+        let stamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos());
+        let generation = format!(".{}.{}", name, stamp);
+        Ok((parent.to_path_buf(), name, generation))
+    }
+    // Returns the directory of the snapshot `name`.
+    fn snapshot_dir(&self, name: &str) -> io::Result<PathBuf> {
+        if name.is_empty() || name.starts_with('.') || name.contains('/') {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid snapshot name {:?}", name),
+            ));
+        }
+        Ok(self.root.join("snapshots").join(name))
+    }
+    /// Freezes the published suite into the snapshot `name`, a repository
+    /// at `snapshots/<name>` with copies of the indexes and hard links to
+    /// the pool files they list, which can be served as is and published
+    /// again with [`Publisher::switch`]. Snapshots are never overwritten.
+    pub async fn snapshot(&self, name: &str) -> io::Result<()> {
+        let snapshot = self.snapshot_dir(name)?;
+        if snapshot.exists().await {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("snapshot {} already exists", name),
+            ));
+        }
+        let dists = self.root.join("dists").join(&self.suite);
+        copy_dir(&dists, &snapshot.join("dists").join(&self.suite)).await?;
+        for path in pool_files(&dists).await? {
+            link_file(&self.root.join(&path), &snapshot.join(&path)).await?;
+        }
+        Ok(())
+    }
+    /// Publishes the suite as frozen in the snapshot `name`, e.g. to roll
+    /// back a bad update. The pool files of the snapshot removed since are
+    /// linked back into the pool, and the packages of the publisher are
+    /// replaced with the ones of the snapshot, so that the next publication
+    /// starts from it.
+    pub async fn switch(&mut self, name: &str) -> io::Result<()> {
+        let snapshot = self.snapshot_dir(name)?;
+        let dists = snapshot.join("dists").join(&self.suite);
+        if !dists.is_dir().await {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("snapshot {} of {} not found", name, self.suite),
+            ));
+        }
+        let components = read_components(&dists).await?;
+        for path in pool_files(&dists).await? {
+            let target = self.root.join(&path);
+            if !target.exists().await {
+                link_file(&snapshot.join(&path), &target).await?;
+            }
+        }
+        let (parent, suite, generation) = self.new_generation()?;
+        copy_dir(&dists, &parent.join(&generation)).await?;
+        switch_generation(&parent, &suite, &generation).await?;
+        self.components = components;
+        Ok(())
+    }
+    // Returns the paths of the package files the retention drops.
+    async fn dropped(&self) -> io::Result<HashSet<String>> {
+        let mut dropped = HashSet::new();
+        let retention = &self.retention;
+        if retention.keep_latest.is_none() && retention.newer_than.is_none() {
+            return Ok(dropped);
+        }
+        let mut versions = HashMap::<_, Vec<(Version<&str>, &str)>>::new();
+        for (component, pkgs) in &self.components {
+            for pkg in pkgs {
+                let field = |name| pkg.field(name).unwrap_or_default();
+                versions
+                    .entry((component, field("Package"), field("Architecture")))
+                    .or_default()
+                    .push((Version::from(field("Version")), field("Filename")));
+            }
+        }
+        for mut versions in versions.into_values() {
+            versions.sort_by(|(this, _), (that, _)| that.cmp(this));
+            for (i, (_, path)) in versions.into_iter().enumerate().skip(1) {
+                let too_many = retention.keep_latest.is_some_and(|n| i >= n);
+                let too_old = match retention.newer_than {
+                    Some(date) => match fs::metadata(self.root.join(path)).await {
+                        Ok(meta) => DateTime::<Utc>::from(meta.modified()?) <= date,
+                        Err(err) if err.kind() == io::ErrorKind::NotFound => false,
+                        Err(err) => return Err(err),
+                    },
+                    None => false,
+                };
+                if too_many || too_old {
+                    dropped.insert(path.to_owned());
+                }
+            }
+        }
+        Ok(dropped)
+    }
+    /// Writes the indexes and the Release file(s) of the suite.
+    ///
+    /// The files are written to a new directory next to `dists/<suite>`,
+    /// which then becomes a symlink to it, replaced at once, so that
+    /// clients never see a Release file listing indexes not written yet.
+    /// The previous directory is kept, so that the pool files its indexes
+    /// list stay out of the attic until the next publication, while the
+    /// clients that fetched them download the packages; the older ones are
+    /// removed. A `dists/<suite>` directory is moved aside as the previous
+    /// one, exchanged with the symlink at once on Linux. Elsewhere, the
+    /// suite is missing for a moment during this one-time migration.
+    /// Symlinks are only supported on Unix.
+    ///
+    /// The versions dropped by the [`Retention`] are left out of the
+    /// indexes. Their files are moved to the attic once no index of the
+    /// repository refers to them, i.e. usually by the next publication,
+    /// which removes the previous directory still listing them. The files
+    /// other suites or components still refer to stay in the pool.
+    pub async fn publish(&self) -> io::Result<()> {
+        let (parent, name, generation) = self.new_generation()?;
+        let dists = parent.join(&generation);
+        let dropped = self.dropped().await?;
+        let arches = self.arches();
+        let mut files: Vec<(String, Vec<u8>)> = vec![];
+        for component in self.components.keys() {
+            for arch in arches.iter() {
+                let base = format!("{}/binary-{}", component, arch);
+                let packages = self.index(component, arch, &dropped).into_bytes();
+                files.push((
+                    format!("{}/Packages.xz", &base),
+                    compress_xz(&packages).await?,
+                ));
+                files.push((
+                    format!("{}/Packages.gz", &base),
+                    compress_gz(&packages).await?,
+                ));
+                files.push((format!("{}/Packages", &base), packages));
+            }
+        }
+        for (path, data) in files.iter() {
+            let path = dists.join(path);
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir).await?;
+            }
+            write_file(&path, data).await?;
+        }
+        let release = self.release(&arches, &files).to_string();
+        write_file(&dists.join("Release"), release.as_bytes()).await?;
+        if let Some(key) = &self.signing_key {
+            let (inrelease, detached) = sign(key, release.as_bytes())?;
+            write_file(&dists.join("InRelease"), &inrelease).await?;
+            write_file(&dists.join("Release.gpg"), &detached).await?;
+        }
+        switch_generation(&parent, &name, &generation).await?;
+        if let Some(attic) = &self.retention.attic {
+            // the indexes of every suite, including the generations kept
+            let referenced = pool_files(&self.root.join("dists")).await?;
+            for path in &dropped {
+                pool::validate_filename(path)?;
+                if referenced.contains(path.trim()) {
+                    continue;
+                }
+                let target = self.root.join(attic).join(path);
+                if let Some(dir) = target.parent() {
+                    fs::create_dir_all(dir).await?;
+                }
+                match fs::rename(self.root.join(path), &target).await {
+                    Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+    fn release(
+        &self,
+        arches: &BTreeSet<String>,
+        files: &[(String, Vec<u8>)],
+    ) -> MutableControlStanza {
+        let mut release = MutableControlStanza::new();
+        if let Some(origin) = &self.origin {
+            release.set("Origin", origin.clone());
+        }
+        if let Some(label) = &self.label {
+            release.set("Label", label.clone());
+        }
+        release.set("Suite", self.suite.clone());
+        release.set(
+            "Codename",
+            self.codename.clone().unwrap_or_else(|| self.suite.clone()),
+        );
+        release.set(
+            "Date",
+            chrono::Utc::now()
+                .format("%a, %d %b %Y %H:%M:%S UTC")
+                .to_string(),
+        );
+        release.set(
+            "Architectures",
+            arches.iter().cloned().collect::<Vec<_>>().join(" "),
+        );
+        release.set(
+            "Components",
+            self.components
+                .keys()
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(" "),
+        );
+        if let Some(description) = &self.description {
+            release.set("Description", description.clone());
+        }
+        release.set("MD5Sum", checksums::<md5::Md5>(files));
+        release.set("SHA1", checksums::<sha1::Sha1>(files));
+        release.set("SHA256", checksums::<sha2::Sha256>(files));
+        release.set("SHA512", checksums::<sha2::Sha512>(files));
+        release
+    }
+}
+
+// Points the symlink `name` in `dir` to the directory `generation`, keeping
+// the directory it pointed to and removing the older ones.
+async fn switch_generation(dir: &Path, name: &str, generation: &str) -> io::Result<()> {
+    let link = dir.join(name);
+    let prefix = format!(".{}.", name);
+    let tmp = dir.join(format!("{}link", prefix));
+    fs::remove_file(&tmp).await.ok();
+    symlink(generation, &tmp).await?;
+    let previous = match fs::read_link(&link).await {
+        Ok(target) => {
+            fs::rename(&tmp, &link).await?;
+            target.to_str().map(str::to_owned)
+        }
+        Err(_) if link.is_dir().await => {
+            // A symlink cannot be renamed over a directory: the two are
+            // exchanged where supported, otherwise the suite is missing
+            // between moving the directory aside and renaming the symlink.
+            let previous = format!("{}0", prefix);
+            if exchange(&tmp, &link).await.is_ok() {
+                fs::rename(&tmp, dir.join(&previous)).await?;
+            } else {
+                fs::rename(&link, dir.join(&previous)).await?;
+                fs::rename(&tmp, &link).await?;
+            }
+            Some(previous)
+        }
+        Err(_) => {
+            fs::rename(&tmp, &link).await?;
+            None
+        }
+    };
+    let mut entries = fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next().await {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        let Some(stamp) = file_name.to_str().and_then(|n| n.strip_prefix(&prefix)) else {
+            continue;
+        };
+        let current = [Some(generation), previous.as_deref()];
+        if stamp.bytes().all(|b| b.is_ascii_digit()) && !current.contains(&file_name.to_str()) {
+            fs::remove_dir_all(entry.path()).await?;
+        }
+    }
+    Ok(())
+}
+
+// Exchanges the entries `a` and `b` at once.
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+async fn exchange(a: &Path, b: &Path) -> io::Result<()> {
+    use nix::fcntl::{renameat2, RenameFlags};
+    let a: std::path::PathBuf = a.to_path_buf().into();
+    let b: std::path::PathBuf = b.to_path_buf().into();
+    async_std::task::spawn_blocking(move || {
+        renameat2(None, &a, None, &b, RenameFlags::RENAME_EXCHANGE).map_err(io::Error::from)
+    })
+    .await
+}
+
+#[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+async fn exchange(_a: &Path, _b: &Path) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(unix)]
+async fn symlink(target: &str, link: &Path) -> io::Result<()> {
+    async_std::os::unix::fs::symlink(target, link).await
+}
+
+#[cfg(not(unix))]
+async fn symlink(_target: &str, _link: &Path) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+// Copies the files of the directory `src` into `dst`.
+async fn copy_dir(src: &Path, dst: &Path) -> io::Result<()> {
+    let mut pending = vec![PathBuf::new()];
+    while let Some(dir) = pending.pop() {
+        fs::create_dir_all(dst.join(&dir)).await?;
+        let mut entries = fs::read_dir(src.join(&dir)).await?;
+        while let Some(entry) = entries.next().await {
+            let entry = entry?;
+            let path = dir.join(entry.file_name());
+            if entry.file_type().await?.is_dir() {
+                pending.push(path);
+            } else {
+                fs::copy(src.join(&path), dst.join(&path)).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// Hard links the file `src` at `dst`.
+async fn link_file(src: &Path, dst: &Path) -> io::Result<()> {
+    if let Some(dir) = dst.parent() {
+        fs::create_dir_all(dir).await?;
+    }
+    fs::hard_link(src, dst).await
+}
+
+// Returns the pool files listed in the Packages indexes under `dists`.
+async fn pool_files(dists: &Path) -> io::Result<BTreeSet<String>> {
+    let mut files = BTreeSet::new();
+    let mut pending = vec![dists.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let mut entries = fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next().await {
+            let entry = entry?;
+            if entry.file_type().await?.is_dir() {
+                pending.push(entry.path());
+            } else if entry.file_name() == "Packages" {
+                let index = fs::read_to_string(entry.path()).await?;
+                for line in index.lines() {
+                    if let Some(path) = line.strip_prefix("Filename:") {
+                        pool::validate_filename(path.trim())?;
+                        files.insert(path.trim().to_owned());
+                    }
+                }
+            }
+        }
+    }
+    Ok(files)
+}
+
+// Returns the package stanzas of the Packages indexes of the components
+// under `dists`.
+async fn read_components(dists: &Path) -> io::Result<BTreeMap<String, Vec<MutableControlStanza>>> {
+    let mut components = BTreeMap::new();
+    let mut entries = fs::read_dir(dists).await?;
+    while let Some(entry) = entries.next().await {
+        let entry = entry?;
+        if !entry.file_type().await?.is_dir() {
+            continue;
+        }
+        let component = entry.file_name().to_string_lossy().into_owned();
+        let mut packages: Vec<MutableControlStanza> = vec![];
+        // architecture-independent packages are listed in every index
+        let mut seen = HashSet::new();
+        let mut indexes = fs::read_dir(entry.path()).await?;
+        while let Some(index) = indexes.next().await {
+            let path = index?.path().join("Packages");
+            if !path.is_file().await {
+                continue;
+            }
+            let text = fs::read_to_string(&path).await?;
+            let control = ControlFile::parse(&text).map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}: {}", path.display(), err),
+                )
+            })?;
+            for stanza in control.stanzas() {
+                if stanza
+                    .field("Filename")
+                    .is_none_or(|path| seen.insert(path.to_owned()))
+                {
+                    packages.push(MutableControlStanza::from(stanza));
+                }
+            }
+        }
+        components.insert(component, packages);
+    }
+    Ok(components)
+}
+
+fn checksums<D: Digest>(files: &[(String, Vec<u8>)]) -> String {
+    let mut sorted: Vec<&(String, Vec<u8>)> = files.iter().collect();
+    sorted.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut out = String::new();
+    for (path, data) in sorted {
+        out.push_str(&format!(
+            "\n {} {:>16} {}",
+            hex::encode(D::digest(data)),
+            data.len(),
+            path
+        ));
+    }
+    out
+}
+
+async fn compress_xz(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut out = vec![];
+    XzEncoder::new(data).read_to_end(&mut out).await?;
+    Ok(out)
+}
+
+async fn compress_gz(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut out = vec![];
+    GzipEncoder::new(data).read_to_end(&mut out).await?;
+    Ok(out)
+}
+
+async fn write_file(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut file = fs::File::create(path).await?;
+    file.write_all(data).await?;
+    file.sync_all().await
+}
+
+// Returns clear-signed and detached signatures of the `data`.
+fn sign(key: &[u8], data: &[u8]) -> io::Result<(Vec<u8>, Vec<u8>)> {
+    let mut ctx = gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp)?;
+    let tempdir = tempfile::tempdir()?;
+    ctx.set_engine_home_dir(tempdir.path().as_os_str().as_encoded_bytes())?;
+    ctx.set_armor(true);
+    ctx.import(key)?;
+    let signer = ctx
+        .secret_keys()?
+        .find_map(|key| key.ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no secret key to sign with"))?;
+    ctx.add_signer(&signer)?;
+    let mut clear = vec![];
+    ctx.sign_clear(data, &mut clear)?;
+    let mut detached = vec![];
+    ctx.sign_detached(data, &mut detached)?;
+    Ok((clear, detached))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fsrepo::FSDebRepo, repo::DebRepo};
+
+    fn stanza(name: &str, version: &str, arch: &str) -> MutableControlStanza {
+        let mut stanza = MutableControlStanza::new();
+        stanza
+            .set("Package", name.to_owned())
+            .set("Version", version.to_owned())
+            .set("Architecture", arch.to_owned())
+            .set(
+                "Filename",
+                format!("pool/main/{}_{}_{}.deb", name, version, arch),
+            )
+            .set("Size", "10")
+            .set("SHA256", hex::encode(sha2::Sha256::digest(b"0123456789")));
+        stanza
+    }
+
+    #[test]
+    fn test_invalid_filename() {
+        let mut publisher = Publisher::new("/srv/repo", "stable");
+        let mut alpha = stanza("alpha", "1.0", "all");
+        alpha.set("Filename", "pool/../../etc/passwd");
+        let err = publisher.add_package("main", alpha).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[async_std::test]
+    async fn test_publish() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut publisher = Publisher::new(dir.path(), "stable");
+        publisher.origin("Test");
+        publisher
+            .add_package("main", stanza("beta", "1.0", "amd64"))
+            .unwrap();
+        publisher
+            .add_package("main", stanza("alpha", "2.0", "all"))
+            .unwrap();
+        publisher
+            .add_package("main", stanza("alpha", "1:1.0", "all"))
+            .unwrap();
+        publisher.publish().await.unwrap();
+
+        let repo: DebRepo = FSDebRepo::new(dir.path()).await.unwrap().into();
+        let release = repo.fetch_release("stable").await.unwrap();
+        assert_eq!(release.origin(), Some("Test"));
+        assert_eq!(release.architectures().collect::<Vec<_>>(), ["amd64"]);
+        let packages = release.fetch_packages("main", "amd64").await.unwrap();
+        let names = packages
+            .packages()
+            .map(|p| format!("{}={}", p.name(), p.version()))
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["alpha=2.0", "alpha=1:1.0", "beta=1.0"]);
+    }
+
+    #[async_std::test]
+    async fn test_retention() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut publisher = Publisher::new(dir.path(), "nightly");
+        std::fs::create_dir_all(dir.path().join("pool/main")).unwrap();
+        for version in ["1.0", "1.1", "1.2"] {
+            let stanza = stanza("alpha", version, "amd64");
+            let path = dir.path().join(stanza.field("Filename").unwrap());
+            std::fs::write(path, "0123456789").unwrap();
+            publisher.add_package("main", stanza).unwrap();
+        }
+        publisher
+            .add_package("main", stanza("beta", "1.0", "amd64"))
+            .unwrap();
+        publisher.retention(Retention {
+            keep_latest: Some(2),
+            attic: Some("attic".to_string()),
+            ..Retention::default()
+        });
+        publisher.publish().await.unwrap();
+        let index =
+            std::fs::read_to_string(dir.path().join("dists/nightly/main/binary-amd64/Packages"))
+                .unwrap();
+        let versions: Vec<&str> = index
+            .lines()
+            .filter_map(|line| line.strip_prefix("Version: "))
+            .collect();
+        assert_eq!(versions, ["1.1", "1.2", "1.0"]);
+        assert!(dir
+            .path()
+            .join("attic/pool/main/alpha_1.0_amd64.deb")
+            .exists());
+        assert!(!dir.path().join("pool/main/alpha_1.0_amd64.deb").exists());
+
+        // all but the latest version are older than now
+        publisher.retention(Retention {
+            newer_than: Some(Utc::now()),
+            ..Retention::default()
+        });
+        assert_eq!(
+            publisher.dropped().await.unwrap(),
+            HashSet::from(["pool/main/alpha_1.1_amd64.deb".to_string()])
+        );
+    }
+
+    #[async_std::test]
+    async fn test_retention_shared() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("pool/main")).unwrap();
+        let mut stable = Publisher::new(dir.path(), "stable");
+        let mut nightly = Publisher::new(dir.path(), "nightly");
+        for version in ["1.0", "1.1"] {
+            let stanza = stanza("alpha", version, "amd64");
+            let path = dir.path().join(stanza.field("Filename").unwrap());
+            std::fs::write(path, "0123456789").unwrap();
+            nightly.add_package("main", stanza).unwrap();
+        }
+        stable
+            .add_package("main", stanza("alpha", "1.0", "amd64"))
+            .unwrap();
+        stable.publish().await.unwrap();
+        nightly.retention(Retention {
+            keep_latest: Some(1),
+            attic: Some("attic".to_string()),
+            ..Retention::default()
+        });
+        nightly.publish().await.unwrap();
+        nightly.publish().await.unwrap();
+        // stable still refers to it
+        assert!(dir.path().join("pool/main/alpha_1.0_amd64.deb").exists());
+        assert!(!dir
+            .path()
+            .join("attic/pool/main/alpha_1.0_amd64.deb")
+            .exists());
+    }
+
+    #[async_std::test]
+    async fn test_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("pool/main")).unwrap();
+        let mut publisher = Publisher::new(root, "stable");
+        let add = |publisher: &mut Publisher, version| {
+            let stanza = stanza("alpha", version, "all");
+            std::fs::write(root.join(stanza.field("Filename").unwrap()), "0123456789").unwrap();
+            publisher.add_package("main", stanza).unwrap();
+        };
+        add(&mut publisher, "1.0");
+        publisher.publish().await.unwrap();
+        publisher.snapshot("before").await.unwrap();
+        let err = publisher.snapshot("before").await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert!(root
+            .join("snapshots/before/pool/main/alpha_1.0_all.deb")
+            .exists());
+
+        add(&mut publisher, "2.0");
+        publisher.retention(Retention {
+            keep_latest: Some(1),
+            attic: Some("attic".to_string()),
+            ..Retention::default()
+        });
+        publisher.publish().await.unwrap();
+        let index =
+            || std::fs::read_to_string(root.join("dists/stable/main/binary-all/Packages")).unwrap();
+        assert!(!index().contains("Version: 1.0"));
+        // the previous generation still lists it
+        assert!(root.join("pool/main/alpha_1.0_all.deb").exists());
+        publisher.publish().await.unwrap();
+        assert!(!root.join("pool/main/alpha_1.0_all.deb").exists());
+        assert!(root.join("attic/pool/main/alpha_1.0_all.deb").exists());
+
+        publisher.switch("before").await.unwrap();
+        assert!(index().contains("Version: 1.0"));
+        assert!(!index().contains("Version: 2.0"));
+        assert!(root.join("pool/main/alpha_1.0_all.deb").exists());
+        assert!(publisher.switch("missing").await.is_err());
+        // the next publication starts from the snapshot
+        publisher.publish().await.unwrap();
+        assert!(index().contains("Version: 1.0"));
+        assert!(!index().contains("Version: 2.0"));
+    }
+
+    #[async_std::test]
+    async fn test_publish_generations() {
+        let dir = tempfile::tempdir().unwrap();
+        let dists = dir.path().join("dists");
+        std::fs::create_dir_all(dists.join("stable")).unwrap();
+        let mut publisher = Publisher::new(dir.path(), "stable");
+        publisher
+            .add_package("main", stanza("alpha", "1.0", "all"))
+            .unwrap();
+        let generations = || {
+            let mut names: Vec<String> = std::fs::read_dir(&dists)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+                .filter(|name| name != "stable")
+                .collect();
+            names.sort();
+            names
+        };
+        publisher.publish().await.unwrap();
+        // the directory left by an earlier layout is kept as the previous
+        // generation
+        assert_eq!(generations().len(), 2);
+        assert_eq!(generations()[0], ".stable.0");
+        publisher.publish().await.unwrap();
+        publisher.publish().await.unwrap();
+        let current = std::fs::read_link(dists.join("stable")).unwrap();
+        assert_eq!(generations().len(), 2);
+        assert!(generations().contains(&current.to_str().unwrap().to_string()));
+        assert!(dists.join("stable/main/binary-all/Packages").exists());
+    }
+}
@@ -0,0 +1,213 @@
+//! Download rate limiting
+//!
+//! A [`RateLimiter`] holds token buckets for bytes and requests per second.
+//! Clones share the buckets, so a single limiter wrapped around several
+//! transports caps their combined bandwidth, while a limiter per transport
+//! caps each repository separately. Limits can be changed at any time and
+//! apply to the streams already in flight.
+
+use {
+    crate::transport::{Transport, TransportResponse},
+    async_std::{
+        io::{self, Read},
+        task::{self, ready, Context, Poll},
+    },
+    async_trait::async_trait,
+    std::{
+        future::Future,
+        ops::Range,
+        pin::Pin,
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    },
+};
+
+struct Bucket {
+    rate: Option<u64>,
+    tokens: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    fn new(rate: Option<u64>) -> Self {
+        Self {
+            rate,
+            tokens: rate.unwrap_or(0) as f64,
+            last: Instant::now(),
+        }
+    }
+    fn set_rate(&mut self, rate: Option<u64>) {
+        self.refill();
+        self.rate = rate;
+        self.tokens = self.tokens.min(rate.unwrap_or(0) as f64);
+    }
+    fn refill(&mut self) {
+        let now = Instant::now();
+        if let Some(rate) = self.rate {
+            let elapsed = now.duration_since(self.last).as_secs_f64();
+            // The bucket holds at most one second worth of tokens.
+            self.tokens = (self.tokens + elapsed * rate as f64).min(rate as f64);
+        }
+        self.last = now;
+    }
+    // Takes `n` tokens, going into debt if needed, and returns how long the
+    // caller has to wait for the debt to be repaid.
+    fn take(&mut self, n: u64) -> Duration {
+        self.refill();
+        match self.rate {
+            None | Some(0) => Duration::ZERO,
+            Some(rate) => {
+                self.tokens -= n as f64;
+                if self.tokens >= 0.0 {
+                    Duration::ZERO
+                } else {
+                    Duration::from_secs_f64(-self.tokens / rate as f64)
+                }
+            }
+        }
+    }
+}
+
+struct Buckets {
+    bytes: Bucket,
+    requests: Bucket,
+}
+
+/// A shared bandwidth and request rate limit.
+#[derive(Clone)]
+pub struct RateLimiter {
+    inner: Arc<Mutex<Buckets>>,
+}
+
+impl RateLimiter {
+    /// Creates a limiter. `None` means no limit.
+    pub fn new(bytes_per_sec: Option<u64>, requests_per_sec: Option<u64>) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Buckets {
+                bytes: Bucket::new(bytes_per_sec),
+                requests: Bucket::new(requests_per_sec),
+            })),
+        }
+    }
+    pub fn unlimited() -> Self {
+        Self::new(None, None)
+    }
+    /// Changes the bandwidth limit.
+    pub fn set_bytes_per_sec(&self, rate: Option<u64>) {
+        self.inner.lock().unwrap().bytes.set_rate(rate)
+    }
+    /// Changes the request rate limit.
+    pub fn set_requests_per_sec(&self, rate: Option<u64>) {
+        self.inner.lock().unwrap().requests.set_rate(rate)
+    }
+    pub fn bytes_per_sec(&self) -> Option<u64> {
+        self.inner.lock().unwrap().bytes.rate
+    }
+    pub fn requests_per_sec(&self) -> Option<u64> {
+        self.inner.lock().unwrap().requests.rate
+    }
+    fn take_bytes(&self, n: u64) -> Duration {
+        self.inner.lock().unwrap().bytes.take(n)
+    }
+    fn take_request(&self) -> Duration {
+        self.inner.lock().unwrap().requests.take(1)
+    }
+    /// Wraps `reader` so that reading from it is throttled by this limiter.
+    pub fn reader<R: Read + Send>(&self, reader: R) -> RateLimitedReader<R> {
+        RateLimitedReader {
+            inner: reader,
+            limiter: self.clone(),
+            delay: None,
+        }
+    }
+}
+
+/// A reader throttled by a [`RateLimiter`].
+#[pin_project::pin_project]
+pub struct RateLimitedReader<R: Read + Send> {
+    #[pin]
+    inner: R,
+    limiter: RateLimiter,
+    delay: Option<Pin<Box<dyn Future<Output = ()> + Send>>>,
+}
+
+impl<R: Read + Send> Read for RateLimitedReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.project();
+        if let Some(delay) = this.delay.as_mut() {
+            ready!(delay.as_mut().poll(cx));
+            *this.delay = None;
+        }
+        let n = ready!(this.inner.poll_read(cx, buf))?;
+        let wait = this.limiter.take_bytes(n as u64);
+        if !wait.is_zero() {
+            *this.delay = Some(Box::pin(task::sleep(wait)));
+        }
+        Poll::Ready(Ok(n))
+    }
+}
+
+/// A transport throttled by a [`RateLimiter`].
+pub struct RateLimited<T: Transport> {
+    inner: T,
+    limiter: RateLimiter,
+}
+
+impl<T: Transport> RateLimited<T> {
+    pub fn new(inner: T, limiter: RateLimiter) -> Self {
+        Self { inner, limiter }
+    }
+    /// Returns the limiter, e.g. to adjust the limits at runtime.
+    pub fn limiter(&self) -> &RateLimiter {
+        &self.limiter
+    }
+}
+
+#[async_trait]
+impl<T: Transport> Transport for RateLimited<T> {
+    async fn get(&self, path: &str, range: Option<Range<u64>>) -> io::Result<TransportResponse> {
+        let wait = self.limiter.take_request();
+        if !wait.is_zero() {
+            task::sleep(wait).await;
+        }
+        let rsp = self.inner.get(path, range).await?;
+        Ok(TransportResponse::new(
+            Box::pin(self.limiter.reader(rsp.reader)),
+            rsp.length,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_std::io::{prelude::*, Cursor};
+
+    #[async_std::test]
+    async fn test_rate_limit() {
+        let limiter = RateLimiter::new(Some(1000), None);
+        let start = Instant::now();
+        let mut buf = vec![];
+        limiter
+            .reader(Cursor::new(vec![0u8; 1500]))
+            .read_to_end(&mut buf)
+            .await
+            .unwrap();
+        assert_eq!(buf.len(), 1500);
+        assert!(start.elapsed() >= Duration::from_millis(400));
+
+        limiter.set_bytes_per_sec(None);
+        let start = Instant::now();
+        buf.clear();
+        limiter
+            .reader(Cursor::new(vec![0u8; 1 << 20]))
+            .read_to_end(&mut buf)
+            .await
+            .unwrap();
+        assert!(start.elapsed() < Duration::from_millis(400));
+    }
+}
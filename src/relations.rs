@@ -0,0 +1,228 @@
+//! Editable relationship fields
+//!
+//! [`Relations`] holds a `Depends`-style field with every relation kept, the
+//! architecture lists and build profiles included, so that it can be edited
+//! and written back:
+//!
+//! ```ignore
+//! let mut depends = Relations::parse("libc6 (>= 2.34), debconf | debconf-2.0")?;
+//! depends.remove("debconf");
+//! depends.restrict("libc6", &VersionSet::LaterOrEqualThan("2.36".to_string().into()))?;
+//! assert_eq!(depends.to_string(), "libc6 (>= 2.36), debconf-2.0");
+//! ```
+
+use {
+    crate::{
+        control::ParseError,
+        version::{Constraint, RawRelationIterator, Version, VersionRange, VersionSet},
+    },
+    std::fmt,
+};
+
+/// A single relation with its restrictions.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Relation {
+    pub constraint: Constraint<Option<String>, String, Version<String>>,
+    /// The architecture list, e.g. `amd64 !i386`
+    pub archs: Option<String>,
+    /// The build profile formulas, one per `<...>` group
+    pub profiles: Vec<String>,
+}
+
+impl Relation {
+    pub fn new(constraint: Constraint<Option<String>, String, Version<String>>) -> Self {
+        Self {
+            constraint,
+            archs: None,
+            profiles: vec![],
+        }
+    }
+    pub fn name(&self) -> &str {
+        self.constraint.name()
+    }
+}
+
+// Collapses the whitespace in a restriction list.
+fn words(s: &str) -> String {
+    s.split_ascii_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+impl fmt::Display for Relation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.constraint.fmt(f)?;
+        if let Some(archs) = &self.archs {
+            write!(f, " [{}]", archs)?;
+        }
+        for profile in &self.profiles {
+            write!(f, " <{}>", profile)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Relation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Relation({})", self)
+    }
+}
+
+/// A parsed relationship field: comma separated groups of alternatives.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Relations {
+    pub groups: Vec<Vec<Relation>>,
+}
+
+impl Relations {
+    pub fn parse(src: &str) -> Result<Self, ParseError> {
+        let groups = RawRelationIterator::new(src)
+            .map(|group| {
+                Ok(group?
+                    .into_iter()
+                    .map(|raw| Relation {
+                        constraint: raw.constraint.translate(
+                            |arch| arch.map(String::from),
+                            |name| name.to_string(),
+                            |version| version.translate(|v| v.to_string()),
+                        ),
+                        archs: raw.archs.map(words),
+                        profiles: raw.profiles.into_iter().map(words).collect(),
+                    })
+                    .collect())
+            })
+            .collect::<Result<_, ParseError>>()?;
+        Ok(Self { groups })
+    }
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+    /// Returns the relations on the package `name`.
+    pub fn find<'s>(&'s self, name: &'s str) -> impl Iterator<Item = &'s Relation> {
+        self.groups
+            .iter()
+            .flatten()
+            .filter(move |r| r.name() == name)
+    }
+    /// Drops the alternatives on the package `name`, and the groups left
+    /// empty. Returns true if anything was dropped.
+    pub fn remove(&mut self, name: &str) -> bool {
+        let count = self.groups.iter().map(Vec::len).sum::<usize>();
+        for group in &mut self.groups {
+            group.retain(|r| r.name() != name);
+        }
+        self.groups.retain(|group| !group.is_empty());
+        count != self.groups.iter().map(Vec::len).sum::<usize>()
+    }
+    /// Narrows the version range of every relation on the package `name` to
+    /// `set`, e.g. raises `(>= 1.0)` to `(>= 1.2)`. A relation standing
+    /// alone is split in two when both bounds are needed. Fails, leaving the
+    /// field unchanged, if no version is left or an alternative would need
+    /// two bounds.
+    pub fn restrict(
+        &mut self,
+        name: &str,
+        set: &VersionSet<Version<String>>,
+    ) -> Result<(), ParseError> {
+        let mut groups = Vec::with_capacity(self.groups.len());
+        for group in &self.groups {
+            let alone = group.len() == 1;
+            let mut split = None;
+            let mut restricted = group.clone();
+            for relation in restricted.iter_mut().filter(|r| r.name() == name) {
+                let range = VersionRange::from(relation.constraint.range())
+                    .intersect(&VersionRange::from(set));
+                let sets = match range.to_version_sets() {
+                    Some(sets) if !range.is_empty() => sets,
+                    _ => return Err(format!("no version of {} satisfies {}", name, set).into()),
+                };
+                let constraint = |set| {
+                    Constraint::new(relation.constraint.arch().clone(), name.to_string(), set)
+                };
+                let mut sets = sets.into_iter();
+                match (sets.next(), sets.next(), sets.next()) {
+                    (Some(first), None, None) => relation.constraint = constraint(first),
+                    (Some(first), Some(second), None) if alone => {
+                        let second = Relation {
+                            constraint: constraint(second),
+                            ..relation.clone()
+                        };
+                        relation.constraint = constraint(first);
+                        split = Some(second);
+                    }
+                    _ => {
+                        return Err(format!(
+                            "{} restricted to {} does not fit in one relation",
+                            name, set
+                        )
+                        .into())
+                    }
+                }
+            }
+            groups.push(restricted);
+            groups.extend(split.map(|relation| vec![relation]));
+        }
+        self.groups = groups;
+        Ok(())
+    }
+}
+
+impl fmt::Display for Relations {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, group) in self.groups.iter().enumerate() {
+            if i != 0 {
+                f.write_str(", ")?;
+            }
+            for (j, relation) in group.iter().enumerate() {
+                if j != 0 {
+                    f.write_str(" | ")?;
+                }
+                relation.fmt(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let src =
+            "debhelper-compat (= 13),\n libcap-dev [linux-any]  | libc-dev [ !linux-any ],\n \
+                   python3:any (>=3.9) <!nocheck> <cross>, foo:amd64";
+        let relations = Relations::parse(src).unwrap();
+        let canonical = "debhelper-compat (= 13), libcap-dev [linux-any] | libc-dev [!linux-any], \
+                         python3:any (>= 3.9) <!nocheck> <cross>, foo:amd64";
+        assert_eq!(relations.to_string(), canonical);
+        assert_eq!(Relations::parse(canonical).unwrap(), relations);
+        assert!(Relations::parse("foo (>= 1.0").is_err());
+        assert!(Relations::parse("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_edit() {
+        let later = |v: &str| VersionSet::LaterOrEqualThan(Version::from(v.to_string()));
+        let earlier = |v: &str| VersionSet::StrictlyEarlierThan(Version::from(v.to_string()));
+        let mut relations =
+            Relations::parse("libc6 (>= 2.34), debconf (>= 0.5) | debconf-2.0, perl").unwrap();
+        assert!(relations.remove("debconf-2.0"));
+        assert!(!relations.remove("python3"));
+        relations.restrict("libc6", &later("2.36")).unwrap();
+        relations.restrict("libc6", &later("2.30")).unwrap();
+        assert_eq!(
+            relations.to_string(),
+            "libc6 (>= 2.36), debconf (>= 0.5), perl"
+        );
+        relations.restrict("libc6", &earlier("2.37")).unwrap();
+        assert_eq!(
+            relations.to_string(),
+            "libc6 (>= 2.36), libc6 (<< 2.37), debconf (>= 0.5), perl"
+        );
+        assert!(relations.restrict("debconf", &earlier("0.4")).is_err());
+        let mut union = Relations::parse("gawk (>= 1) | mawk").unwrap();
+        assert!(union.restrict("gawk", &earlier("2")).is_err());
+        assert_eq!(union.to_string(), "gawk (>= 1) | mawk");
+        assert_eq!(relations.find("libc6").count(), 2);
+    }
+}
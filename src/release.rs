@@ -7,7 +7,7 @@
 /// println!("{}", release.codename().unwrap());
 ///
 /// let contents_all = release.file("main/Contents-all.gz").unwrap();
-/// println!("size: {} digest: {}", contents_all.size, contents_all.digest);
+/// println!("size: {} digests: {:?}", contents_all.size, contents_all.digests);
 ///
 /// let package_size = release.packages_file_for("contrib", "amd64", Some(".xz"));
 /// println!("size: {}", package_size);
@@ -15,16 +15,104 @@
 use {
     crate::{
         control::{ControlStanza, ParseError},
-        digest::{Digest, Sha256},
-        packages::Packages,
+        dep11::Components,
+        digest::{Digests, HashAlgorithm},
+        error::Error,
+        packages::{PackageKind, Packages},
         parse_size,
+        policy::IndexRelease,
         repo::DebRepo,
+        srcindex::SourcesIndex,
     },
-    chrono::{DateTime, Utc},
+    chrono::{DateTime, TimeDelta, Utc},
     ouroboros::self_referencing,
-    std::{borrow::Cow, io, sync::Arc},
+    std::{borrow::Cow, fmt, io, sync::Arc},
 };
 
+/// Freshness checks applied to Release files, like apt's
+/// `check-valid-until` and `check-date` options.
+#[derive(Clone, Debug)]
+pub struct FreshnessPolicy {
+    /// Reject releases past their Valid-Until date
+    pub check_valid_until: bool,
+    /// Reject releases whose Date is in the future
+    pub check_date: bool,
+    /// Tolerated clock difference between the archive and the local host
+    pub max_clock_skew: TimeDelta,
+}
+
+impl Default for FreshnessPolicy {
+    fn default() -> Self {
+        Self {
+            check_valid_until: true,
+            check_date: true,
+            max_clock_skew: TimeDelta::minutes(5),
+        }
+    }
+}
+
+impl FreshnessPolicy {
+    /// Disables all checks, e.g. for snapshot and archived suites
+    /// (`check-valid-until=false`).
+    pub fn disabled() -> Self {
+        Self {
+            check_valid_until: false,
+            check_date: false,
+            ..Self::default()
+        }
+    }
+}
+
+/// The error returned for a Release file failing the freshness checks.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StaleRelease {
+    /// The release is past its Valid-Until date
+    Expired {
+        distr: String,
+        valid_until: DateTime<Utc>,
+        now: DateTime<Utc>,
+    },
+    /// The release Date is in the future
+    FromFuture {
+        distr: String,
+        date: DateTime<Utc>,
+        now: DateTime<Utc>,
+    },
+}
+
+impl fmt::Display for StaleRelease {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Expired {
+                distr,
+                valid_until,
+                now,
+            } => write!(
+                f,
+                "release {} expired at {} (now {})",
+                distr,
+                valid_until.to_rfc2822(),
+                now.to_rfc2822()
+            ),
+            Self::FromFuture { distr, date, now } => write!(
+                f,
+                "release {} is dated {}, in the future (now {})",
+                distr,
+                date.to_rfc2822(),
+                now.to_rfc2822()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for StaleRelease {}
+
+impl From<StaleRelease> for io::Error {
+    fn from(err: StaleRelease) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, err)
+    }
+}
+
 pub struct Release {
     name: Arc<str>,
     repo: DebRepo,
@@ -46,7 +134,7 @@ macro_rules! matches {
 #[derive(Clone)]
 pub struct ReleaseFile<'a> {
     pub path: Cow<'a, str>,
-    pub digest: Digest<sha2::Sha256>,
+    pub digests: Digests,
     pub size: usize,
 }
 
@@ -74,28 +162,55 @@ impl Release {
         self.inner
             .with_files(|files| files.iter().find(|file| file.path == path))
     }
-    pub fn packages_file(&self, component: &str, arch: &str) -> Option<(String, usize, Sha256)> {
+    /// Returns all the files listed in the release, with their paths from
+    /// the repository root.
+    pub fn repo_files(&self) -> Vec<(String, usize, Digests)> {
+        self.inner.with_files(|files| {
+            files
+                .iter()
+                .map(|file| {
+                    (
+                        format!("dists/{}/{}", &self.name, &file.path),
+                        file.size,
+                        file.digests.clone(),
+                    )
+                })
+                .collect()
+        })
+    }
+    pub fn packages_file(&self, component: &str, arch: &str) -> Option<(String, usize, Digests)> {
+        self.index_file(PackageKind::Deb, component, arch)
+    }
+    /// Returns the Packages index of the given kind, preferring the
+    /// compressed variants.
+    pub fn index_file(
+        &self,
+        kind: PackageKind,
+        component: &str,
+        arch: &str,
+    ) -> Option<(String, usize, Digests)> {
+        let dir = &kind.index_dir(component, arch);
         self.inner
             .with_files(|files| {
                 files
                     .iter()
-                    .find(|file| matches!(file.path, [ component "/binary-" arch "/Packages.xz" ]))
+                    .find(|file| matches!(file.path, [ dir "/Packages.xz" ]))
                     .or_else(|| {
-                        files.iter().find(
-                        |file| matches!(file.path, [ component "/binary-" arch "/Packages.gz" ]),
-                    )
+                        files
+                            .iter()
+                            .find(|file| matches!(file.path, [ dir "/Packages.gz" ]))
                     })
                     .or_else(|| {
-                        files.iter().find(
-                            |file| matches!(file.path, [ component "/binary-" arch "/Packages" ]),
-                        )
+                        files
+                            .iter()
+                            .find(|file| matches!(file.path, [ dir "/Packages" ]))
                     })
             })
             .map(|file| {
                 (
                     format!("dists/{}/{}", &self.name, &file.path).into(),
                     file.size,
-                    file.digest.clone(),
+                    file.digests.clone(),
                 )
             })
     }
@@ -103,16 +218,26 @@ impl Release {
         &self,
         component: &str,
         arch: &str,
-    ) -> io::Result<Packages<Box<str>>> {
-        let (path, size, hash) = self.packages_file(component, arch).ok_or_else(|| {
+    ) -> Result<Packages<Box<str>>, Error> {
+        self.fetch_index(PackageKind::Deb, component, arch).await
+    }
+    /// Fetches and verifies the Packages index of the given kind.
+    pub async fn fetch_index(
+        &self,
+        kind: PackageKind,
+        component: &str,
+        arch: &str,
+    ) -> Result<Packages<Box<str>>, Error> {
+        let (path, size, hash) = self.index_file(kind, component, arch).ok_or_else(|| {
             io::Error::new(
                 io::ErrorKind::InvalidData,
                 format!(
-                    "File {}/binary-{}/Packages(.xz|.gz)? not found in release",
-                    component, arch
+                    "File {}/Packages(.xz|.gz)? not found in release",
+                    kind.index_dir(component, arch)
                 ),
             )
         })?;
+        self.repo.progress().on_index_fetch(&path, size as u64);
         let release = String::from_utf8(self.repo.fetch_verify_unpack(&path, size, hash).await?)
             .map_err(|err| {
                 io::Error::new(
@@ -120,17 +245,118 @@ impl Release {
                     format!("Invalid release file: {}", err),
                 )
             })?;
-        Ok(
-            Packages::new(self.repo.clone(), release.into_boxed_str()).map_err(|err| {
+        Ok(Packages::new(self.repo.clone(), release.into_boxed_str())
+            .map_err(|err| {
                 io::Error::new(
                     io::ErrorKind::InvalidData,
                     format!("Invalid release file: {}", err),
                 )
-            })?,
-        )
+            })?
+            .with_kind(kind))
+    }
+    /// Returns the AppStream components file of the component, preferring
+    /// the compressed variants.
+    pub fn components_file(&self, component: &str, arch: &str) -> Option<(String, usize, Digests)> {
+        let name = &format!("{}/dep11/Components-{}", component, arch);
+        self.inner
+            .with_files(|files| {
+                files
+                    .iter()
+                    .find(|file| matches!(file.path, [ name ".yml.xz" ]))
+                    .or_else(|| {
+                        files
+                            .iter()
+                            .find(|file| matches!(file.path, [ name ".yml.gz" ]))
+                    })
+                    .or_else(|| {
+                        files
+                            .iter()
+                            .find(|file| matches!(file.path, [ name ".yml" ]))
+                    })
+            })
+            .map(|file| {
+                (
+                    format!("dists/{}/{}", &self.name, &file.path),
+                    file.size,
+                    file.digests.clone(),
+                )
+            })
+    }
+    /// Fetches and verifies the AppStream components of the component.
+    pub async fn fetch_components(&self, component: &str, arch: &str) -> Result<Components, Error> {
+        let (path, size, hash) = self.components_file(component, arch).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "File {}/dep11/Components-{}.yml(.xz|.gz)? not found in release",
+                    component, arch
+                ),
+            )
+        })?;
+        let invalid = |err: String| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid components file {}: {}", path, err),
+            )
+        };
+        self.repo.progress().on_index_fetch(&path, size as u64);
+        let text = String::from_utf8(self.repo.fetch_verify_unpack(&path, size, hash).await?)
+            .map_err(|err| invalid(err.to_string()))?;
+        Ok(Components::parse(&text).map_err(|err| invalid(err.to_string()))?)
+    }
+    /// Returns the Sources index of the component, preferring the
+    /// compressed variants.
+    pub fn sources_file(&self, component: &str) -> Option<(String, usize, Digests)> {
+        let dir = &format!("{}/source", component);
+        self.inner
+            .with_files(|files| {
+                files
+                    .iter()
+                    .find(|file| matches!(file.path, [ dir "/Sources.xz" ]))
+                    .or_else(|| {
+                        files
+                            .iter()
+                            .find(|file| matches!(file.path, [ dir "/Sources.gz" ]))
+                    })
+                    .or_else(|| {
+                        files
+                            .iter()
+                            .find(|file| matches!(file.path, [ dir "/Sources" ]))
+                    })
+            })
+            .map(|file| {
+                (
+                    format!("dists/{}/{}", &self.name, &file.path),
+                    file.size,
+                    file.digests.clone(),
+                )
+            })
+    }
+    /// Fetches and verifies the Sources index of the component.
+    pub async fn fetch_sources(&self, component: &str) -> Result<SourcesIndex, Error> {
+        let (path, size, hash) = self.sources_file(component).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "File {}/source/Sources(.xz|.gz)? not found in release",
+                    component
+                ),
+            )
+        })?;
+        let invalid = |err: String| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid Sources index {}: {}", path, err),
+            )
+        };
+        self.repo.progress().on_index_fetch(&path, size as u64);
+        let text = String::from_utf8(self.repo.fetch_verify_unpack(&path, size, hash).await?)
+            .map_err(|err| invalid(err.to_string()))?;
+        Ok(SourcesIndex::parse(&text).map_err(|err| invalid(err.to_string()))?)
     }
     fn field(&self, name: &str) -> Option<&str> {
-        self.inner.with_control(|ctrl| ctrl.field(name).map(|s| s.as_ref()))
+        self.inner
+            .with_control(|ctrl| ctrl.field(name).map(|s| s.as_ref()))
     }
     pub fn codename(&self) -> Option<&str> {
         self.field("Codename")
@@ -141,6 +367,37 @@ impl Release {
     pub fn label(&self) -> Option<&str> {
         self.field("Label")
     }
+    pub fn suite(&self) -> Option<&str> {
+        self.field("Suite")
+    }
+    /// Returns true if the packages of the release are not to be installed
+    /// unless asked for, as in experimental.
+    pub fn not_automatic(&self) -> bool {
+        self.field("NotAutomatic")
+            .is_some_and(|v| v.trim() == "yes")
+    }
+    /// Returns true if the installed packages from a `NotAutomatic`
+    /// release still get upgrades from it, as backports do.
+    pub fn but_automatic_upgrades(&self) -> bool {
+        self.field("ButAutomaticUpgrades")
+            .is_some_and(|v| v.trim() == "yes")
+    }
+    /// Describes the index of `component` for a [`crate::Policy`], which gives it
+    /// the priority 1 if the release is `NotAutomatic`, 100 if it also has
+    /// `ButAutomaticUpgrades`, like apt.
+    pub fn index_release(&self, component: &str) -> IndexRelease {
+        let field = |name| self.field(name).map(|v| v.trim().to_string());
+        IndexRelease {
+            archive: field("Suite").or_else(|| Some(self.name.to_string())),
+            codename: field("Codename"),
+            origin: field("Origin"),
+            label: field("Label"),
+            component: Some(component.to_string()),
+            site: None,
+            not_automatic: self.not_automatic(),
+            but_automatic_upgrades: self.but_automatic_upgrades(),
+        }
+    }
     pub fn components(&self) -> impl Iterator<Item = &str> {
         self.field("Components").map_or_else(
             || "".split_ascii_whitespace(),
@@ -157,12 +414,51 @@ impl Release {
         self.field("Description").unwrap_or("")
     }
     pub fn date(&self) -> Option<DateTime<Utc>> {
-        self.field("Date")
-            .and_then(|date| DateTime::parse_from_rfc2822(date).map(|t| t.to_utc()).ok())
+        self.field("Date").and_then(|date| parse_date(date).ok())
     }
     pub fn valid_until(&self) -> Option<DateTime<Utc>> {
         self.field("Valid-Until")
-            .and_then(|date| DateTime::parse_from_rfc2822(date).map(|t| t.to_utc()).ok())
+            .and_then(|date| parse_date(date).ok())
+    }
+    /// Checks the Date and Valid-Until fields against `now`.
+    pub fn check_freshness(&self, policy: &FreshnessPolicy, now: DateTime<Utc>) -> io::Result<()> {
+        let parse = |name: &str| {
+            self.field(name)
+                .map(|date| {
+                    parse_date(date).map_err(|err| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("Invalid {} in release {}: {}", name, &self.name, err),
+                        )
+                    })
+                })
+                .transpose()
+        };
+        if policy.check_valid_until {
+            if let Some(valid_until) = parse("Valid-Until")? {
+                if valid_until + policy.max_clock_skew < now {
+                    return Err(StaleRelease::Expired {
+                        distr: self.name.to_string(),
+                        valid_until,
+                        now,
+                    }
+                    .into());
+                }
+            }
+        }
+        if policy.check_date {
+            if let Some(date) = parse("Date")? {
+                if date > now + policy.max_clock_skew {
+                    return Err(StaleRelease::FromFuture {
+                        distr: self.name.to_string(),
+                        date,
+                        now,
+                    }
+                    .into());
+                }
+            }
+        }
+        Ok(())
     }
     pub(crate) fn new(repo: DebRepo, distr: &str, data: Box<str>) -> Result<Release, ParseError> {
         Ok(Release {
@@ -172,36 +468,54 @@ impl Release {
                 data,
                 control_builder: |data: &'_ Box<str>| ControlStanza::parse(data.as_ref()),
                 files_builder: |control: &'_ ControlStanza| {
-                    control
-                        .field("SHA256")
-                        .ok_or_else(|| {
-                            ParseError::from("Field SHA256 not found in the release file")
-                        })?
-                        .lines()
-                        .map(|l| l.trim())
-                        .filter(|l| l != &"")
-                        .map(|line| {
+                    let mut files: Vec<ReleaseFile<'_>> = vec![];
+                    let mut index = std::collections::HashMap::new();
+                    for algorithm in HashAlgorithm::ALL {
+                        let Some(field) = control.field(algorithm.release_field()) else {
+                            continue;
+                        };
+                        for line in field.lines().map(|l| l.trim()).filter(|l| l != &"") {
                             let parts: Vec<&'_ str> = line.split_ascii_whitespace().collect();
-                            if let [digest, size, path] = parts[..] {
-                                let digest: Sha256 = digest.try_into().map_err(|err| {
+                            let [digest, size, path] = parts[..] else {
+                                return Err(ParseError::from(format!(
+                                    "Invalid release line: {}",
+                                    line
+                                )));
+                            };
+                            let size = parse_size(size.as_bytes()).map_err(|err| {
+                                ParseError::from(format!("Invalid size: {:?} {}", size, err))
+                            })?;
+                            let file = *index.entry(path).or_insert_with(|| {
+                                files.push(ReleaseFile {
+                                    digests: Digests::new(),
+                                    size,
+                                    path: path.into(),
+                                });
+                                files.len() - 1
+                            });
+                            if files[file].size != size {
+                                return Err(ParseError::from(format!(
+                                    "Conflicting sizes for {} in the release file",
+                                    path
+                                )));
+                            }
+                            files[file]
+                                .digests
+                                .insert(algorithm, digest)
+                                .map_err(|err| {
                                     ParseError::from(format!(
                                         "Invalid digest: {:?} {}",
                                         digest, err
                                     ))
                                 })?;
-                                let size = parse_size(size.as_bytes()).map_err(|err| {
-                                    ParseError::from(format!("Invalid size: {:?} {}", size, err))
-                                })?;
-                                Ok(ReleaseFile {
-                                    digest,
-                                    size,
-                                    path: path.into(),
-                                })
-                            } else {
-                                Err(ParseError::from(format!("Invalid release line: {}", line)))
-                            }
-                        })
-                        .collect::<Result<Vec<ReleaseFile<'_>>, ParseError>>()
+                        }
+                    }
+                    if files.is_empty() {
+                        return Err(ParseError::from(
+                            "No checksum fields found in the release file",
+                        ));
+                    }
+                    Ok(files)
                 },
             }
             .try_build()?,
@@ -209,6 +523,17 @@ impl Release {
     }
 }
 
+// Release dates are RFC 2822, but archives commonly use the `UTC` zone name
+// which chrono does not accept.
+fn parse_date(date: &str) -> chrono::ParseResult<DateTime<Utc>> {
+    let date = date.trim();
+    match date.strip_suffix(" UTC") {
+        Some(date) => DateTime::parse_from_rfc2822(&format!("{} +0000", date)),
+        None => DateTime::parse_from_rfc2822(date),
+    }
+    .map(|t| t.to_utc())
+}
+
 #[self_referencing]
 struct ReleaseInner {
     data: Box<str>,
@@ -225,6 +550,10 @@ mod tests {
     use super::*;
     use crate::digest::Sha256;
 
+    fn sha256(hex: &str) -> Digests {
+        Sha256::try_from(hex).unwrap().into()
+    }
+
     #[test]
     fn test_find_release_entry() {
         let data = "\
@@ -245,18 +574,123 @@ SHA256:
         let (path, size, hash) = release.packages_file("contrib", "all").unwrap();
         assert_eq!(
             hash,
-            Sha256::try_from("9b6ce8e2bcccc2a0e9d3e5f7864d89ac1dc2ec6335419dd6cc0e6bdd96697325")
-                .unwrap()
+            sha256("9b6ce8e2bcccc2a0e9d3e5f7864d89ac1dc2ec6335419dd6cc0e6bdd96697325")
         );
         assert_eq!(size, 24088);
-        assert_eq!(path, "sid/contrib/binary-all/Packages.xz");
+        assert_eq!(path, "dists/sid/contrib/binary-all/Packages.xz");
         let (path, size, hash) = release.packages_file("contrib", "arm64").unwrap();
         assert_eq!(
             hash,
-            Sha256::try_from("0601d762ab26a93dcf066d78b4d34f789ca34155929a5dd069a5c50ac58a627e")
-                .unwrap()
+            sha256("0601d762ab26a93dcf066d78b4d34f789ca34155929a5dd069a5c50ac58a627e")
         );
         assert_eq!(size, 45652);
-        assert_eq!(path, "sid/contrib/binary-arm64/Packages.xz");
+        assert_eq!(path, "dists/sid/contrib/binary-arm64/Packages.xz");
+    }
+
+    #[test]
+    fn test_sha512_only_release() {
+        let data = "\
+SHA512:
+ 309ecc489c12d6eb4cc40f50c902f2b4d0ed77ee511a7c7a9bcd3ca86d4cd86f989dd35bc5ff499670da34255b45b0cfd830e81f605dcf7dc5542e93ae9cd76f      11 main/binary-amd64/Packages
+MD5Sum:
+ 5eb63bbbe01eeed093cb22bb8f5acdc3      11 main/binary-amd64/Packages
+".to_string().into_boxed_str();
+        let release = Release::new(crate::repo::null_provider(), "sid", data).unwrap();
+        let (_, size, digests) = release.packages_file("main", "amd64").unwrap();
+        assert_eq!(size, 11);
+        assert_eq!(digests.strongest().unwrap().0, HashAlgorithm::Sha512);
+        assert!(digests.get(HashAlgorithm::Md5).is_some());
+        assert!(digests.sha256().is_none());
+    }
+
+    #[test]
+    fn test_udeb_index() {
+        let data = "\
+SHA256:
+ 4f0e1d2bd8e5e2d3b3a7a1e1d0b0c7f95f4d2e3c3e0b5a1f6e1e8b2c6a4d9f01   210112 main/binary-amd64/Packages.xz
+ 1b2ea5c0d6d4c9e0b7f1a2f4e5a6b7c8d9e0f1a2b3c4d5e6f7a8b9c0d1e2f3a4    61924 main/debian-installer/binary-amd64/Packages.gz
+".to_string().into_boxed_str();
+        let release = Release::new(crate::repo::null_provider(), "bookworm", data).unwrap();
+        let (path, size, _) = release
+            .index_file(PackageKind::Udeb, "main", "amd64")
+            .unwrap();
+        assert_eq!(
+            path,
+            "dists/bookworm/main/debian-installer/binary-amd64/Packages.gz"
+        );
+        assert_eq!(size, 61924);
+        let (path, _, _) = release.packages_file("main", "amd64").unwrap();
+        assert_eq!(path, "dists/bookworm/main/binary-amd64/Packages.xz");
+        assert!(release
+            .index_file(PackageKind::Udeb, "contrib", "amd64")
+            .is_none());
+    }
+
+    #[test]
+    fn test_freshness() {
+        let data = "\
+Date: Sat, 01 Jun 2024 08:00:00 UTC
+Valid-Until: Sat, 08 Jun 2024 08:00:00 UTC
+SHA256:
+ b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9      11 main/binary-amd64/Packages
+"
+        .to_string()
+        .into_boxed_str();
+        let release = Release::new(crate::repo::null_provider(), "sid", data).unwrap();
+        let at = |s: &str| DateTime::parse_from_rfc3339(s).unwrap().to_utc();
+        let policy = FreshnessPolicy::default();
+        release
+            .check_freshness(&policy, at("2024-06-05T00:00:00Z"))
+            .unwrap();
+        let err = release
+            .check_freshness(&policy, at("2024-06-09T00:00:00Z"))
+            .unwrap_err();
+        let Some(StaleRelease::Expired { .. }) = err.get_ref().unwrap().downcast_ref() else {
+            panic!("unexpected error {}", err);
+        };
+        let err = release
+            .check_freshness(&policy, at("2024-05-31T00:00:00Z"))
+            .unwrap_err();
+        let Some(StaleRelease::FromFuture { .. }) = err.get_ref().unwrap().downcast_ref() else {
+            panic!("unexpected error {}", err);
+        };
+        release
+            .check_freshness(&FreshnessPolicy::disabled(), at("2030-01-01T00:00:00Z"))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_not_automatic() {
+        let data = "\
+Origin: Debian Backports
+Suite: bookworm-backports
+Codename: bookworm-backports
+NotAutomatic: yes
+ButAutomaticUpgrades: yes
+SHA256:
+ b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9      11 main/binary-amd64/Packages
+"
+        .to_string()
+        .into_boxed_str();
+        let release =
+            Release::new(crate::repo::null_provider(), "bookworm-backports", data).unwrap();
+        assert!(release.not_automatic() && release.but_automatic_upgrades());
+        let index = release.index_release("main");
+        assert_eq!(index.archive.as_deref(), Some("bookworm-backports"));
+        assert_eq!(index.origin.as_deref(), Some("Debian Backports"));
+        assert!(index.not_automatic && index.but_automatic_upgrades);
+        let data = "\
+Suite: unstable
+SHA256:
+ b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9      11 main/binary-amd64/Packages
+"
+        .to_string()
+        .into_boxed_str();
+        let release = Release::new(crate::repo::null_provider(), "sid", data).unwrap();
+        assert!(!release.not_automatic());
+        assert_eq!(
+            release.index_release("main").archive.as_deref(),
+            Some("unstable")
+        );
     }
 }
@@ -1,16 +1,34 @@
 //! Debian repository client
 
 use {
-    crate::{deb::DebReader, digest::Sha256, release::Release},
+    crate::{
+        changelog::Changelog,
+        deb::DebReader,
+        digest::Digests,
+        dsc::Dsc,
+        error::{Error, Result},
+        metrics::{self, Counter},
+        packages::Package,
+        progress::{NoProgress, Progress},
+        release::{FreshnessPolicy, Release},
+        transport::{Transport, TransportResponse},
+    },
     async_compression::futures::bufread::{
         BzDecoder, GzipDecoder, LzmaDecoder, XzDecoder, ZstdDecoder,
     },
-    async_std::io::{self, prelude::*, BufReader},
+    async_std::{
+        fs,
+        io::{self, prelude::*, BufReader},
+        path::{Path, PathBuf},
+    },
     async_trait::async_trait,
     std::{
+        ops::Range,
         pin::{pin, Pin},
         sync::Arc,
+        task::{Context, Poll},
     },
+    tracing::Instrument,
 };
 
 /// A test Provider returning `Not Found` to any request.
@@ -26,23 +44,61 @@ impl DebRepoProvider for NullProvider {
 }
 pub fn null_provider() -> DebRepo {
     DebRepo {
-        inner: Arc::new(NullProvider {}) as Arc<dyn DebRepoProvider>,
+        inner: Arc::new(NullProvider {}) as Arc<dyn Transport>,
+        freshness: FreshnessPolicy::default(),
+        progress: Arc::new(NoProgress),
+        keys: Arc::new([]),
     }
 }
 
-pub type VerifyingReader = crate::digest::VerifyingReader<sha2::Sha256, Pin<Box<dyn Read + Send>>>;
+struct CountingReader {
+    inner: Pin<Box<dyn Read + Send>>,
+    progress: Arc<dyn Progress>,
+    span: tracing::Span,
+    bytes: u64,
+}
+
+impl Read for CountingReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let read = self.inner.as_mut().poll_read(cx, buf);
+        match read {
+            Poll::Ready(Ok(0)) if !buf.is_empty() => {
+                self.span.record("bytes", self.bytes);
+                tracing::debug!(parent: &self.span, bytes = self.bytes, "fetched");
+            }
+            Poll::Ready(Ok(size)) => {
+                self.bytes += size as u64;
+                self.progress.on_bytes(size as u64);
+                metrics::increment(Counter::DownloadedBytes, size as u64);
+            }
+            _ => {}
+        }
+        read
+    }
+}
+
+pub type VerifyingReader = crate::digest::MultiVerifyingReader<Pin<Box<dyn Read + Send>>>;
 pub type VerifyingDebReader<'a> = DebReader<'a, VerifyingReader>;
 
 /// Represents interface for a Debian Repository
 pub struct DebRepo {
-    inner: Arc<dyn DebRepoProvider>,
+    inner: Arc<dyn Transport>,
+    freshness: FreshnessPolicy,
+    progress: Arc<dyn Progress>,
+    keys: Arc<[Vec<u8>]>,
 }
-unsafe impl Sync for DebRepo {} // DebRepoProvider is Sync
 
 impl Clone for DebRepo {
     fn clone(&self) -> Self {
         Self {
             inner: Arc::clone(&self.inner),
+            freshness: self.freshness.clone(),
+            progress: Arc::clone(&self.progress),
+            keys: Arc::clone(&self.keys),
         }
     }
 }
@@ -50,8 +106,55 @@ impl Clone for DebRepo {
 pub const DEBIAN_KEYRING: &[u8] = include_bytes!("../keyring/debian-keys.bin");
 
 impl DebRepo {
-    /// Fetches, verifies and parses the InRelease file. Uses the default GPG keyring, that
-    /// can be set with GNUPGHOME environment variable.
+    /// Sets the freshness checks applied to fetched Release files. Use
+    /// [`FreshnessPolicy::disabled`] for snapshot and archived suites.
+    pub fn with_freshness(mut self, policy: FreshnessPolicy) -> Self {
+        self.freshness = policy;
+        self
+    }
+    /// Verifies the Release files of the repository with `keys` instead of
+    /// the default GPG keyring, see [`DebRepo::fetch_verify_release`].
+    pub fn with_keys<K: IntoIterator<Item = impl Into<Vec<u8>>>>(mut self, keys: K) -> Self {
+        self.keys = keys.into_iter().map(|key| key.into()).collect();
+        self
+    }
+    /// Reports the downloads from the repository to `progress`.
+    pub fn with_progress(mut self, progress: Arc<dyn Progress>) -> Self {
+        self.progress = progress;
+        self
+    }
+    pub fn progress(&self) -> &dyn Progress {
+        self.progress.as_ref()
+    }
+    /// Fetches the `range` of the file at `path` from the underlying transport
+    /// as is, without any verification.
+    pub async fn get(&self, path: &str, range: Option<Range<u64>>) -> Result<TransportResponse> {
+        Ok(self.counted_get(path, range).await?)
+    }
+    async fn reader(&self, path: &str) -> io::Result<Pin<Box<dyn Read + Send>>> {
+        Ok(self.counted_get(path, None).await?.reader)
+    }
+    // Gets the file within a `fetch` span lasting until its stream is
+    // dropped, counting the download and its bytes.
+    async fn counted_get(
+        &self,
+        path: &str,
+        range: Option<Range<u64>>,
+    ) -> io::Result<TransportResponse> {
+        let span = tracing::debug_span!("fetch", path, bytes = tracing::field::Empty);
+        let mut response = self.inner.get(path, range).instrument(span.clone()).await?;
+        metrics::increment(Counter::Downloads, 1);
+        response.reader = Box::pin(CountingReader {
+            inner: response.reader,
+            progress: Arc::clone(&self.progress),
+            span,
+            bytes: 0,
+        });
+        Ok(response)
+    }
+    /// Fetches, verifies and parses the InRelease file. Uses the keys set with
+    /// [`DebRepo::with_keys`], or else the default GPG keyring, that can be set
+    /// with GNUPGHOME environment variable.
     ///
     /// Example:
     /// ```
@@ -59,11 +162,12 @@ impl DebRepo {
     ///    let repo: DebRepo = HttpDebRepo::new("https://archive.ubuntu.com/ubuntu/").await?.into();
     ///    let release = repo.fetch_verify_release("bionic", None::<&[u8]>).await?;
     /// ```
-    pub async fn fetch_verify_release(
-        &self,
-        distr: &str,
-    ) -> io::Result<Release> {
+    pub async fn fetch_verify_release(&self, distr: &str) -> Result<Release> {
         let data = self.fetch(&format!("dists/{}/InRelease", distr)).await?;
+        if !self.keys.is_empty() {
+            let (ctx, _keyring) = keyring_context(self.keys.iter())?;
+            return self.verify_release(distr, data, ctx).await;
+        }
         let ctx = gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp)?;
         self.verify_release(distr, data, ctx).await
     }
@@ -79,15 +183,9 @@ impl DebRepo {
         &self,
         distr: &str,
         keys: K,
-    ) -> io::Result<Release> {
+    ) -> Result<Release> {
         let data = self.fetch(&format!("dists/{}/InRelease", distr)).await?;
-        let mut ctx = gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp)?;
-        let tempdir = tempfile::tempdir()?;
-        ctx.set_engine_home_dir(tempdir.path().as_os_str().as_encoded_bytes())?;
-        ctx.set_flag("auto-key-retrieve", "0")?;
-        for key in keys {
-            ctx.import(key.as_ref())?;
-        }
+        let (ctx, _keyring) = keyring_context(keys)?;
         self.verify_release(distr, data, ctx).await
     }
     async fn verify_release(
@@ -95,33 +193,109 @@ impl DebRepo {
         distr: &str,
         release: Vec<u8>,
         mut ctx: gpgme::Context,
-    ) -> io::Result<Release> {
-        let mut plaintext = Vec::new();
-        let verify_result = ctx.verify_opaque(release, &mut plaintext)?;
-        if let Some(signature) = verify_result.signatures().next() {
-            println!("Signature: {:?}", &signature);
-            if let Err(err) = signature.status() {
-                return Err(err.into());
-            }
+    ) -> Result<Release> {
+        let file = verify_clearsigned(&mut ctx, release, &format!("dists/{}/InRelease", distr))?;
+        self.parse_release(distr, file)
+    }
+    // Verifies the InRelease file `data` of `distr` with `keys`, or the keys
+    // of the repository, or the default GPG keyring if there are none.
+    // Returns the Release file along with its signed text.
+    pub(crate) fn verify_inrelease(
+        &self,
+        distr: &str,
+        data: Vec<u8>,
+        keys: &[Vec<u8>],
+    ) -> Result<(Release, String)> {
+        let keys = if keys.is_empty() {
+            &self.keys[..]
         } else {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "no signature found in InRelease",
-            ));
-        }
-        let file = String::from_utf8(plaintext)
+            keys
+        };
+        let (mut ctx, _keyring) = match keys.is_empty() {
+            true => (
+                gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp)?,
+                None,
+            ),
+            false => {
+                let (ctx, keyring) = keyring_context(keys)?;
+                (ctx, Some(keyring))
+            }
+        };
+        let text = verify_clearsigned(&mut ctx, data, &format!("dists/{}/InRelease", distr))?;
+        Ok((self.parse_release(distr, text.clone())?, text))
+    }
+    fn parse_release(&self, distr: &str, text: String) -> Result<Release> {
+        let release = Release::new(self.clone(), distr, text.into_boxed_str())?;
+        release.check_freshness(&self.freshness, chrono::Utc::now())?;
+        Ok(release)
+    }
+    /// Fetches and parses a .dsc file, skipping signature verification.
+    pub async fn fetch_dsc(&self, path: &str) -> Result<Dsc> {
+        let data = String::from_utf8(self.fetch(path).await?)
             .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("{}", err)))?;
-        Release::new(self.clone(), distr, file.into_boxed_str()).map_err(|err| err.into())
+        Ok(Dsc::parse(&data)?)
+    }
+    /// Fetches a .dsc file and verifies its signature with the supplied keys,
+    /// e.g. the Debian maintainers keyring.
+    pub async fn fetch_verify_dsc_with_keys<K: IntoIterator<Item = impl AsRef<[u8]>>>(
+        &self,
+        path: &str,
+        keys: K,
+    ) -> Result<Dsc> {
+        let data = self.fetch(path).await?;
+        let (mut ctx, _keyring) = keyring_context(keys)?;
+        Ok(Dsc::parse(&verify_clearsigned(&mut ctx, data, path)?)?)
+    }
+    /// Downloads the files of the source package `dsc` from the pool
+    /// directory `dir` (the `Directory` field of the Sources index) into
+    /// `dst`, verifying their sizes and digests. A file is only put in
+    /// place once verified. Returns the paths of the downloaded files.
+    pub async fn fetch_source(
+        &self,
+        dir: &str,
+        dsc: &Dsc,
+        dst: impl AsRef<Path>,
+    ) -> Result<Vec<PathBuf>> {
+        let dst = dst.as_ref();
+        let mut paths = vec![];
+        for file in dsc.files() {
+            if file.name.contains('/') || file.name.starts_with('.') {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid source file name {:?}", file.name),
+                )
+                .into());
+            }
+            let path = dst.join(&file.name);
+            let partial = dst.join(format!("{}.partial", file.name));
+            let mut out = fs::File::create(&partial).await?;
+            let src = format!("{}/{}", dir.trim_end_matches('/'), file.name);
+            let res = match self
+                .copy_verify(&mut out, &src, file.size, file.digests.clone())
+                .await
+            {
+                Ok(_) => out.flush().await.map_err(Error::from),
+                Err(err) => Err(err),
+            };
+            drop(out);
+            if let Err(err) = res {
+                fs::remove_file(&partial).await.ok();
+                return Err(err);
+            }
+            fs::rename(&partial, &path).await?;
+            paths.push(path);
+        }
+        Ok(paths)
     }
     /// Fetch the Release file, skip verification.
-    pub async fn fetch_release(&self, distr: &str) -> io::Result<Release> {
+    pub async fn fetch_release(&self, distr: &str) -> Result<Release> {
         let data = String::from_utf8(self.fetch(&format!("dists/{}/Release", distr)).await?)
             .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("{}", err)))?;
-        Release::new(self.clone(), distr, data.into_boxed_str()).map_err(|err| err.into())
+        self.parse_release(distr, data)
     }
     /// Returns a debian package reader.
-    pub async fn deb_reader(&self, path: &str) -> io::Result<DebReader<Pin<Box<dyn Read + Send>>>> {
-        DebReader::new(self.inner.reader(path).await?).await
+    pub async fn deb_reader(&self, path: &str) -> Result<DebReader<'_, Pin<Box<dyn Read + Send>>>> {
+        Ok(DebReader::new(self.reader(path).await?).await?)
     }
     /// Returns a verifying Debian package reader that generates an error
     /// if the supplied size or hash does not match.
@@ -129,53 +303,98 @@ impl DebRepo {
         &self,
         path: &str,
         size: usize,
-        digest: Sha256,
-    ) -> io::Result<VerifyingDebReader<'_>> {
-        DebReader::new(VerifyingReader::new(
-            self.inner.reader(path).await?,
-            size,
-            digest,
-        ))
-        .await
+        digest: impl Into<Digests>,
+    ) -> Result<VerifyingDebReader<'_>> {
+        Ok(DebReader::new(VerifyingReader::new(self.reader(path).await?, size, digest)).await?)
     }
     pub async fn verifying_reader(
         &self,
         path: &str,
         size: usize,
-        digest: Sha256,
-    ) -> io::Result<VerifyingReader> {
-        Ok(VerifyingReader::new(
-            self.inner.reader(path).await?,
-            size,
-            digest,
-        ))
+        digest: impl Into<Digests>,
+    ) -> Result<VerifyingReader> {
+        Ok(VerifyingReader::new(self.reader(path).await?, size, digest))
     }
-    pub async fn unpacking_reader(&self, path: &str) -> io::Result<Pin<Box<dyn Read + Send>>> {
-        Ok(unpacker(path, self.inner.reader(path).await?))
+    pub async fn unpacking_reader(&self, path: &str) -> Result<Pin<Box<dyn Read + Send>>> {
+        Ok(unpacker(path, self.reader(path).await?))
     }
     pub async fn verifying_unpacking_reader(
         &self,
         path: &str,
         size: usize,
-        digest: Sha256,
-    ) -> io::Result<Pin<Box<dyn Read + Send>>> {
+        digest: impl Into<Digests>,
+    ) -> Result<Pin<Box<dyn Read + Send>>> {
         Ok(unpacker(
             path,
-            VerifyingReader::new(self.inner.reader(path).await?, size, digest),
+            VerifyingReader::new(self.reader(path).await?, size, digest),
         ))
     }
-    pub async fn fetch(&self, path: &str) -> io::Result<Vec<u8>> {
-        let mut buffer = vec![0u8; 0];
-        self.inner
-            .reader(path)
-            .await?
-            .read_to_end(&mut buffer)
+    /// Fetches the Debian changelog of `package`. It is taken from the
+    /// changelogs service `metadata` if any, e.g. an [`crate::HttpDebRepo`]
+    /// for `https://metadata.ftp-master.debian.org/changelogs/`, and from
+    /// the package itself otherwise or if not found there.
+    pub async fn changelog(
+        &self,
+        package: &Package<'_>,
+        metadata: Option<&DebRepo>,
+    ) -> Result<Changelog> {
+        let files = ["changelog.Debian.gz", "changelog.gz"];
+        let text = self
+            .package_doc(package, metadata, "changelog", &files)
             .await?;
+        Ok(Changelog::parse(&text)?)
+    }
+    /// Fetches the copyright file of `package`, like
+    /// [`DebRepo::changelog`].
+    pub async fn copyright(
+        &self,
+        package: &Package<'_>,
+        metadata: Option<&DebRepo>,
+    ) -> Result<String> {
+        self.package_doc(package, metadata, "copyright", &["copyright"])
+            .await
+    }
+    // Fetches `kind` of the package from the metadata service, or the first
+    // of `files` found in its doc directory.
+    async fn package_doc(
+        &self,
+        package: &Package<'_>,
+        metadata: Option<&DebRepo>,
+        kind: &str,
+        files: &[&str],
+    ) -> Result<String> {
+        if let Some(metadata) = metadata {
+            match metadata.fetch(&metadata_path(package, kind)?).await {
+                Ok(data) => return utf8(data),
+                Err(Error::NotFound(_)) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        let (path, size, digests) = package.repo_file()?;
+        let docs: Vec<String> = files
+            .iter()
+            .map(|file| format!("usr/share/doc/{}/{}", package.name(), file))
+            .collect();
+        let docs: Vec<&str> = docs.iter().map(String::as_str).collect();
+        let reader = self.verifying_deb_reader(path, size, digests).await?;
+        match reader.data_file(&docs).await? {
+            Some((pos, data)) if files[pos].ends_with(".gz") => {
+                let mut buf = vec![];
+                GzipDecoder::new(&data[..]).read_to_end(&mut buf).await?;
+                utf8(buf)
+            }
+            Some((_, data)) => utf8(data),
+            None => Err(Error::NotFound(format!("{} of {}", kind, package))),
+        }
+    }
+    pub async fn fetch(&self, path: &str) -> Result<Vec<u8>> {
+        let mut buffer = vec![0u8; 0];
+        self.reader(path).await?.read_to_end(&mut buffer).await?;
         Ok(buffer)
     }
-    pub async fn fetch_unpack(&self, path: &str) -> io::Result<Vec<u8>> {
+    pub async fn fetch_unpack(&self, path: &str) -> Result<Vec<u8>> {
         let mut buffer = vec![0u8; 0];
-        unpacker(path, self.inner.reader(path).await?)
+        unpacker(path, self.reader(path).await?)
             .read_to_end(&mut buffer)
             .await?;
         Ok(buffer)
@@ -184,10 +403,10 @@ impl DebRepo {
         &self,
         path: &str,
         size: usize,
-        digest: Sha256,
-    ) -> io::Result<Vec<u8>> {
+        digest: impl Into<Digests>,
+    ) -> Result<Vec<u8>> {
         let mut buffer = Vec::<u8>::with_capacity(size);
-        VerifyingReader::new(self.inner.reader(path).await?, size, digest)
+        VerifyingReader::new(self.reader(path).await?, size, digest)
             .read_to_end(&mut buffer)
             .await?;
         Ok(buffer)
@@ -196,57 +415,125 @@ impl DebRepo {
         &self,
         path: &str,
         size: usize,
-        digest: Sha256,
-    ) -> io::Result<Vec<u8>> {
+        digest: impl Into<Digests>,
+    ) -> Result<Vec<u8>> {
         let mut buffer = Vec::<u8>::with_capacity(size);
         unpacker(
             path,
-            VerifyingReader::new(self.inner.reader(path).await?, size, digest),
+            VerifyingReader::new(self.reader(path).await?, size, digest),
         )
         .read_to_end(&mut buffer)
         .await?;
         Ok(buffer)
     }
-    pub async fn copy<W: Write + Send>(&self, path: &str, w: W) -> io::Result<u64> {
-        io::copy(&mut self.inner.reader(path).await?, pin!(w)).await
+    pub async fn copy<W: Write + Send>(&self, path: &str, w: W) -> Result<u64> {
+        Ok(io::copy(&mut self.reader(path).await?, pin!(w)).await?)
     }
-    pub async fn copy_unpack<W: Write + Send>(&self, path: &str, w: W) -> io::Result<u64> {
-        io::copy(&mut unpacker(path, self.inner.reader(path).await?), pin!(w)).await
+    pub async fn copy_unpack<W: Write + Send>(&self, path: &str, w: W) -> Result<u64> {
+        Ok(io::copy(&mut unpacker(path, self.reader(path).await?), pin!(w)).await?)
     }
     pub async fn copy_verify<W: Write + Send>(
         &self,
         w: W,
         path: &str,
         size: usize,
-        digest: Sha256,
-    ) -> io::Result<u64> {
-        let mut reader = VerifyingReader::new(self.inner.reader(path).await?, size, digest);
-        io::copy(&mut reader, pin!(w)).await
+        digest: impl Into<Digests>,
+    ) -> Result<u64> {
+        let mut reader = VerifyingReader::new(self.reader(path).await?, size, digest);
+        // async_std's copy wraps the errors, hiding a digest mismatch
+        Ok(futures::io::copy(&mut reader, &mut pin!(w)).await?)
     }
     pub async fn copy_verify_unpack<W: Write + Send>(
         &self,
         w: W,
         path: &str,
         size: usize,
-        digest: Sha256,
-    ) -> io::Result<u64> {
+        digest: impl Into<Digests>,
+    ) -> Result<u64> {
         let mut reader = unpacker(
             path,
-            VerifyingReader::new(self.inner.reader(path).await?, size, digest),
+            VerifyingReader::new(self.reader(path).await?, size, digest),
         );
-        io::copy(&mut reader, pin!(w)).await
+        Ok(futures::io::copy(&mut reader, &mut pin!(w)).await?)
+    }
+}
+
+// The path of `kind` of the package on the changelogs service, e.g.
+// `main/h/hello/hello_2.10-3_changelog`.
+fn metadata_path(package: &Package<'_>, kind: &str) -> Result<String> {
+    let component = package
+        .section()
+        .and_then(|section| section.split_once('/'))
+        .map_or("main", |(component, _)| component);
+    let source = package.source();
+    let prefix = match source.strip_prefix("lib") {
+        Some(rest) if !rest.is_empty() => source.get(..4),
+        _ => source.get(..1),
     }
+    .ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid source package name {:?}", source),
+        )
+    })?;
+    let version = package.source_version();
+    let version = match version.debian_revision() {
+        Some(revision) => format!("{}-{}", version.upstream(), revision),
+        None => version.upstream().to_string(),
+    };
+    Ok(format!(
+        "{}/{}/{}/{}_{}_{}",
+        component, prefix, source, source, version, kind
+    ))
 }
 
-impl<P: DebRepoProvider + 'static> From<P> for DebRepo {
-    fn from(provider: P) -> Self {
+fn utf8(data: Vec<u8>) -> Result<String> {
+    Ok(String::from_utf8(data)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("{}", err)))?)
+}
+
+// A context with a temporary keyring holding only `keys`. The keyring is
+// removed when the returned directory is dropped.
+pub(crate) fn keyring_context<K: IntoIterator<Item = impl AsRef<[u8]>>>(
+    keys: K,
+) -> Result<(gpgme::Context, tempfile::TempDir)> {
+    let mut ctx = gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp)?;
+    let tempdir = tempfile::tempdir()?;
+    ctx.set_engine_home_dir(tempdir.path().as_os_str().as_encoded_bytes())?;
+    ctx.set_flag("auto-key-retrieve", "0")?;
+    for key in keys {
+        ctx.import(key.as_ref())?;
+    }
+    Ok((ctx, tempdir))
+}
+
+// Verifies a clearsigned file and returns the signed text.
+fn verify_clearsigned(ctx: &mut gpgme::Context, data: Vec<u8>, path: &str) -> Result<String> {
+    let mut plaintext = Vec::new();
+    let verify_result = ctx.verify_opaque(data, &mut plaintext)?;
+    if let Some(signature) = verify_result.signatures().next() {
+        tracing::debug!("signature of {}: {:?}", path, signature);
+        signature.status()?;
+    } else {
+        return Err(Error::Signature(format!("no signature found in {}", path)));
+    }
+    Ok(String::from_utf8(plaintext)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("{}", err)))?)
+}
+
+impl<T: Transport + 'static> From<T> for DebRepo {
+    fn from(transport: T) -> Self {
         Self {
-            inner: Arc::new(provider) as Arc<dyn DebRepoProvider>,
+            inner: Arc::new(transport) as Arc<dyn Transport>,
+            freshness: FreshnessPolicy::default(),
+            progress: Arc::new(NoProgress),
+            keys: Arc::new([]),
         }
     }
 }
 
-/// Defines a Debian Repository Provider.
+/// Defines a Debian Repository Provider. Every provider is also a
+/// [`Transport`]; implement [`Transport`] directly to support ranged fetches.
 #[async_trait]
 pub trait DebRepoProvider: Sync {
     /// Provides a reader for accessing the specified path within the repository.
@@ -267,3 +554,91 @@ fn unpacker<'a, R: Read + Send + 'a>(u: &str, r: R) -> Pin<Box<dyn Read + Send +
         _ => Box::pin(r),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{fsrepo::FSDebRepo, packages::Packages, testutil::deb},
+        async_compression::futures::bufread::GzipEncoder,
+        sha2::{Digest, Sha256},
+    };
+
+    #[async_std::test]
+    async fn test_package_docs() {
+        let changelog = "\
+hello (2.10-3) unstable; urgency=medium
+
+  * Fix the build with GCC 14.
+
+ -- Jane Doe <jane@example.org>  Sat, 27 Jul 2024 12:00:00 +0200
+";
+        let mut gzipped = vec![];
+        GzipEncoder::new(changelog.as_bytes())
+            .read_to_end(&mut gzipped)
+            .await
+            .unwrap();
+        let deb = deb(
+            b"Package: hello\n",
+            &[
+                ("./usr/share/doc/hello/changelog.gz", Some(b"not gzipped")),
+                ("./usr/share/doc/hello/changelog.Debian.gz", Some(&gzipped)),
+                ("./usr/share/doc/hello/copyright", Some(b"Format: dep5\n")),
+            ],
+        )
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+        let path = "pool/main/h/hello/hello_2.10-3+b1_amd64.deb";
+        std::fs::create_dir_all(dir.path().join("pool/main/h/hello")).unwrap();
+        std::fs::write(dir.path().join(path), &deb).unwrap();
+        let repo: DebRepo = FSDebRepo::new(dir.path()).await.unwrap().into();
+        let index = format!(
+            "Package: hello\nSource: hello (2.10-3)\nVersion: 2.10-3+b1\nArchitecture: amd64\n\
+             Section: devel\nFilename: {}\nSize: {}\nSHA256: {}\n",
+            path,
+            deb.len(),
+            hex::encode(Sha256::digest(&deb))
+        );
+        let packages = Packages::new(repo.clone(), index).unwrap();
+        let package = packages.package_by_name("hello").unwrap();
+        assert_eq!(
+            metadata_path(package, "changelog").unwrap(),
+            "main/h/hello/hello_2.10-3_changelog"
+        );
+        for source in ["libé", "é"] {
+            let index = format!(
+                "Package: x\nSource: {}\nVersion: 1.0\nArchitecture: all\n",
+                source
+            );
+            let packages = Packages::new_test(index).unwrap();
+            let package = packages.package_by_name("x").unwrap();
+            assert!(metadata_path(package, "changelog").is_err(), "{}", source);
+        }
+        let entries = repo.changelog(package, None).await.unwrap().entries;
+        assert_eq!(entries[0].version, "2.10-3");
+        assert_eq!(
+            repo.copyright(package, None).await.unwrap(),
+            "Format: dep5\n"
+        );
+
+        let metadata_dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(metadata_dir.path().join("main/h/hello")).unwrap();
+        std::fs::write(
+            metadata_dir
+                .path()
+                .join("main/h/hello/hello_2.10-3_changelog"),
+            changelog.replace("2.10-3", "2.10-4"),
+        )
+        .unwrap();
+        let metadata: DebRepo = FSDebRepo::new(metadata_dir.path()).await.unwrap().into();
+        let entries = repo
+            .changelog(package, Some(&metadata))
+            .await
+            .unwrap()
+            .entries;
+        assert_eq!(entries[0].version, "2.10-4");
+        // not on the service, taken from the package
+        let copyright = repo.copyright(package, Some(&metadata)).await.unwrap();
+        assert_eq!(copyright, "Format: dep5\n");
+    }
+}
@@ -0,0 +1,359 @@
+//! Pool scanning
+//!
+//! [`Scanner`] does the work of `dpkg-scanpackages` and `dpkg-scansources`:
+//! it walks a directory of a repository for .deb, .udeb and .dsc files and
+//! builds the stanzas of their Packages and Sources indexes. The files are
+//! hashed in parallel, and the stanzas of a previous scan can be reused for
+//! the files whose modification time and size did not change:
+//!
+//! ```ignore
+//! let cache = ScanCache::parse(&fs::read_to_string("scan.cache").await?)?;
+//! let mut scanner = Scanner::new("/srv/repo").cache(cache);
+//! for stanza in scanner.scan_packages("pool/main").await? {
+//!     publisher.add_package("main", stanza)?;
+//! }
+//! fs::write("scan.cache", scanner.take_cache().to_string()).await?;
+//! ```
+
+use {
+    crate::{
+        control::{MutableControlStanza, ParseError},
+        deb::{scan_deb, PoolLayout},
+        digest::HashAlgorithm,
+        dsc::strip_signature,
+        version::Version,
+    },
+    async_std::{
+        fs, io,
+        path::{Path, PathBuf},
+        task,
+    },
+    digest::Digest,
+    futures::{
+        future::{self, Either},
+        stream::{self, StreamExt},
+    },
+    std::{collections::HashMap, fmt, time::UNIX_EPOCH},
+};
+
+// The stanza of a file, with the modification time and size it had.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct CachedStanza {
+    mtime: u64,
+    size: u64,
+    stanza: String,
+}
+
+/// The stanzas of the files of a previous scan, by path relative to the
+/// repository root. Displays as a control file, each stanza with the
+/// `X-Scan-Path`, `X-Scan-Mtime` and `X-Scan-Size` fields added.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ScanCache {
+    entries: HashMap<String, CachedStanza>,
+}
+
+impl ScanCache {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut entries = HashMap::new();
+        for stanza in crate::control::ControlFile::parse(text)?.stanzas() {
+            let mut stanza = MutableControlStanza::from(stanza);
+            let field = |name| {
+                stanza
+                    .field(name)
+                    .map(|value| value.trim().to_string())
+                    .ok_or_else(|| ParseError::from(format!("scan cache: no {} field", name)))
+            };
+            let path = field("X-Scan-Path")?;
+            let number = |name| {
+                field(name)?
+                    .parse()
+                    .map_err(|err| ParseError::from(format!("scan cache: {}: {}", name, err)))
+            };
+            let (mtime, size) = (number("X-Scan-Mtime")?, number("X-Scan-Size")?);
+            stanza
+                .remove("X-Scan-Path")
+                .remove("X-Scan-Mtime")
+                .remove("X-Scan-Size");
+            let stanza = stanza.to_string();
+            entries.insert(
+                path,
+                CachedStanza {
+                    mtime,
+                    size,
+                    stanza,
+                },
+            );
+        }
+        Ok(Self { entries })
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl fmt::Display for ScanCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut paths: Vec<&String> = self.entries.keys().collect();
+        paths.sort();
+        for path in paths {
+            let entry = &self.entries[path];
+            write!(f, "{}", entry.stanza)?;
+            writeln!(f, "X-Scan-Path: {}", path)?;
+            writeln!(f, "X-Scan-Mtime: {}", entry.mtime)?;
+            writeln!(f, "X-Scan-Size: {}\n", entry.size)?;
+        }
+        Ok(())
+    }
+}
+
+/// Builds the index stanzas of the package files of a repository.
+pub struct Scanner {
+    root: PathBuf,
+    jobs: usize,
+    cache: ScanCache,
+}
+
+impl Scanner {
+    /// A scanner of the repository rooted at `root`, hashing as many files
+    /// at a time as there are CPUs.
+    pub fn new(root: impl AsRef<Path>) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+            jobs: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            cache: ScanCache::default(),
+        }
+    }
+    /// Sets the number of files scanned at a time.
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs.max(1);
+        self
+    }
+    /// Reuses the stanzas of a previous scan.
+    pub fn cache(mut self, cache: ScanCache) -> Self {
+        self.cache = cache;
+        self
+    }
+    /// Returns the stanzas of the files scanned so far, to be passed to the
+    /// next scan.
+    pub fn take_cache(&mut self) -> ScanCache {
+        std::mem::take(&mut self.cache)
+    }
+    /// Scans `dir`, relative to the repository root, for .deb and .udeb
+    /// files and returns their Packages stanzas, sorted by package name,
+    /// version and architecture.
+    pub async fn scan_packages(&mut self, dir: &str) -> io::Result<Vec<MutableControlStanza>> {
+        let mut stanzas = self
+            .scan(dir, &["deb", "udeb"], |root, path| async move {
+                let stanza = scan_deb(root.join(&path), &PoolLayout::Path(path)).await?;
+                Ok(stanza.to_string())
+            })
+            .await?;
+        sort(&mut stanzas, "Package", "Architecture");
+        Ok(stanzas)
+    }
+    /// Scans `dir`, relative to the repository root, for .dsc files and
+    /// returns their Sources stanzas, sorted by package name and version.
+    pub async fn scan_sources(&mut self, dir: &str) -> io::Result<Vec<MutableControlStanza>> {
+        let mut stanzas = self
+            .scan(dir, &["dsc"], |root, path| async move {
+                Ok(scan_dsc(&root, &path).await?.to_string())
+            })
+            .await?;
+        sort(&mut stanzas, "Package", "Directory");
+        Ok(stanzas)
+    }
+    async fn scan<F, Fut>(
+        &mut self,
+        dir: &str,
+        extensions: &[&str],
+        scan: F,
+    ) -> io::Result<Vec<MutableControlStanza>>
+    where
+        F: Fn(PathBuf, String) -> Fut,
+        Fut: std::future::Future<Output = io::Result<String>> + Send + 'static,
+    {
+        let mut files = vec![];
+        let mut pending = vec![PathBuf::from(dir)];
+        while let Some(dir) = pending.pop() {
+            let mut entries = fs::read_dir(self.root.join(&dir)).await?;
+            while let Some(entry) = entries.next().await {
+                let entry = entry?;
+                let path = dir.join(entry.file_name());
+                let meta = entry.metadata().await?;
+                if meta.is_dir() {
+                    pending.push(path);
+                } else if path
+                    .extension()
+                    .is_some_and(|ext| extensions.iter().any(|e| ext == *e))
+                {
+                    let path = path.to_str().map(|p| p.to_owned()).ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("pool file name {:?} is not a valid UTF-8", path),
+                        )
+                    })?;
+                    let mtime = meta
+                        .modified()?
+                        .duration_since(UNIX_EPOCH)
+                        .map_or(0, |d| d.as_secs());
+                    files.push((path, mtime, meta.len()));
+                }
+            }
+        }
+        let cache = &self.cache;
+        let scanned: Vec<(String, u64, u64, String)> = stream::iter(files)
+            .map(|(path, mtime, size)| {
+                let stanza = match cache
+                    .entries
+                    .get(&path)
+                    .filter(|e| e.mtime == mtime && e.size == size)
+                {
+                    Some(cached) => Either::Left(future::ready(Ok(cached.stanza.clone()))),
+                    None => Either::Right(task::spawn(scan(self.root.clone(), path.clone()))),
+                };
+                async move { Ok::<_, io::Error>((path, mtime, size, stanza.await?)) }
+            })
+            .buffer_unordered(self.jobs)
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<io::Result<_>>()?;
+        let mut stanzas = vec![];
+        let mut entries = HashMap::new();
+        for (path, mtime, size, stanza) in scanned {
+            stanzas.push(MutableControlStanza::parse(stanza.as_str()).map_err(|err| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, err))
+            })?);
+            entries.insert(
+                path,
+                CachedStanza {
+                    mtime,
+                    size,
+                    stanza,
+                },
+            );
+        }
+        // keeps the stanzas of the other directories
+        self.cache.entries.extend(entries);
+        Ok(stanzas)
+    }
+}
+
+// Sorts the stanzas by name, version and `key`.
+fn sort(stanzas: &mut [MutableControlStanza], name: &str, key: &str) {
+    stanzas.sort_by(|this, that| {
+        let field = |stanza: &MutableControlStanza, name| {
+            stanza
+                .field(name)
+                .map(str::trim)
+                .unwrap_or_default()
+                .to_string()
+        };
+        field(this, name)
+            .cmp(&field(that, name))
+            .then_with(|| {
+                let (this, that) = (field(this, "Version"), field(that, "Version"));
+                Version::from(this.as_str()).cmp(&Version::from(that.as_str()))
+            })
+            .then_with(|| field(this, key).cmp(&field(that, key)))
+    });
+}
+
+// Builds the Sources stanza of the .dsc file at `path`: the `Source` field
+// becomes `Package`, the pool directory goes into `Directory` and the .dsc
+// file joins the checksum lists.
+async fn scan_dsc(root: &Path, path: &str) -> io::Result<MutableControlStanza> {
+    let invalid =
+        |err: ParseError| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, err));
+    let data = fs::read(root.join(path)).await?;
+    let text = String::from_utf8_lossy(&data);
+    let dsc = MutableControlStanza::parse(strip_signature(&text).map_err(invalid)?.into_owned())
+        .map_err(invalid)?;
+    let source = dsc
+        .field("Source")
+        .ok_or_else(|| invalid("no Source field".into()))?;
+    let (dir, name) = path.rsplit_once('/').unwrap_or((".", path));
+    let mut stanza = MutableControlStanza::new();
+    stanza.set("Package", source.trim().to_string());
+    for field in dsc.fields() {
+        if !field.is_a("Source") {
+            stanza.set(field.name().to_string(), field.value().to_string());
+        }
+    }
+    stanza.set("Directory", dir.to_string());
+    for algorithm in [
+        HashAlgorithm::Md5,
+        HashAlgorithm::Sha1,
+        HashAlgorithm::Sha256,
+    ] {
+        let digest = match algorithm {
+            HashAlgorithm::Md5 => hex::encode(md5::Md5::digest(&data)),
+            HashAlgorithm::Sha1 => hex::encode(sha1::Sha1::digest(&data)),
+            _ => hex::encode(sha2::Sha256::digest(&data)),
+        };
+        let field = algorithm.sources_field();
+        if let Some(list) = dsc.field(field) {
+            let list = format!("{}\n {} {} {}", list.trim_end(), digest, data.len(), name);
+            stanza.set(field, list);
+        }
+    }
+    Ok(stanza)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_scan_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = Path::new(dir.path().to_str().unwrap());
+        let dsc = "\
+-----BEGIN PGP SIGNED MESSAGE-----
+Hash: SHA512
+
+Format: 3.0 (quilt)
+Source: hello
+Binary: hello
+Version: 2.10-3
+Files:
+ 6cd0ffea3884a4e79330338dcc2987d6 725946 hello_2.10.orig.tar.gz
+Checksums-Sha256:
+ 31e066137a962676e89f69d1b65382de95a7ef7d914b8cb956f41ea72e0f516b 725946 hello_2.10.orig.tar.gz
+-----BEGIN PGP SIGNATURE-----
+
+iQ==
+-----END PGP SIGNATURE-----
+";
+        fs::create_dir_all(root.join("pool/main/h/hello"))
+            .await
+            .unwrap();
+        fs::write(root.join("pool/main/h/hello/hello_2.10-3.dsc"), dsc)
+            .await
+            .unwrap();
+        let mut scanner = Scanner::new(root).jobs(2);
+        let stanzas = scanner.scan_sources("pool").await.unwrap();
+        assert_eq!(stanzas.len(), 1);
+        let hello = &stanzas[0];
+        assert_eq!(hello.field("Package"), Some("hello"));
+        assert_eq!(hello.field("Directory"), Some("pool/main/h/hello"));
+        let index = crate::srcindex::SourcesIndex::parse(&hello.to_string()).unwrap();
+        let package = index.packages().next().unwrap();
+        assert_eq!(package.files().len(), 2);
+        assert_eq!(package.files()[1].name, "hello_2.10-3.dsc");
+        assert_eq!(package.files()[1].size, dsc.len());
+
+        let cache = ScanCache::parse(&scanner.take_cache().to_string()).unwrap();
+        assert_eq!(cache.len(), 1);
+        // the cached stanza is reused for the unchanged file
+        let key = "pool/main/h/hello/hello_2.10-3.dsc".to_string();
+        let mut cache = cache;
+        cache.entries.get_mut(&key).unwrap().stanza = "Package: cached\n".to_string();
+        let mut scanner = Scanner::new(root).cache(cache);
+        let stanzas = scanner.scan_sources("pool").await.unwrap();
+        assert_eq!(stanzas[0].field("Package"), Some("cached"));
+    }
+}
@@ -0,0 +1,252 @@
+//! Maintainer scripts
+//!
+//! The preinst, postinst, prerm and postrm scripts and the triggers of a
+//! package come with its control.tar. The library never runs them: an
+//! installer reads them with [`DebReader::maintainer_scripts`] and hands them
+//! to a [`ScriptHook`], which decides whether and how to run them, e.g. in a
+//! chroot, under fakeroot, or not at all.
+
+use {
+    crate::{
+        deb::{DebEntry, DebReader, TarballEntryType},
+        triggers::Trigger,
+    },
+    async_std::{
+        io::{self, prelude::*, Read},
+        path::PathBuf,
+        stream::StreamExt,
+        task,
+    },
+    async_trait::async_trait,
+    std::fmt,
+};
+
+/// A maintainer script.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ScriptKind {
+    Preinst,
+    Postinst,
+    Prerm,
+    Postrm,
+}
+
+impl ScriptKind {
+    pub const ALL: [ScriptKind; 4] = [
+        ScriptKind::Preinst,
+        ScriptKind::Postinst,
+        ScriptKind::Prerm,
+        ScriptKind::Postrm,
+    ];
+    /// The name of the script in control.tar.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScriptKind::Preinst => "preinst",
+            ScriptKind::Postinst => "postinst",
+            ScriptKind::Prerm => "prerm",
+            ScriptKind::Postrm => "postrm",
+        }
+    }
+}
+
+impl fmt::Display for ScriptKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The maintainer scripts and triggers of a package.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MaintainerScripts {
+    pub preinst: Option<Vec<u8>>,
+    pub postinst: Option<Vec<u8>>,
+    pub prerm: Option<Vec<u8>>,
+    pub postrm: Option<Vec<u8>>,
+    pub triggers: Vec<Trigger>,
+}
+
+impl MaintainerScripts {
+    pub fn get(&self, kind: ScriptKind) -> Option<&[u8]> {
+        match kind {
+            ScriptKind::Preinst => self.preinst.as_deref(),
+            ScriptKind::Postinst => self.postinst.as_deref(),
+            ScriptKind::Prerm => self.prerm.as_deref(),
+            ScriptKind::Postrm => self.postrm.as_deref(),
+        }
+    }
+    fn slot(&mut self, name: &str) -> Option<&mut Option<Vec<u8>>> {
+        match name {
+            "preinst" => Some(&mut self.preinst),
+            "postinst" => Some(&mut self.postinst),
+            "prerm" => Some(&mut self.prerm),
+            "postrm" => Some(&mut self.postrm),
+            _ => None,
+        }
+    }
+    /// Returns true if there are neither scripts nor triggers.
+    pub fn is_empty(&self) -> bool {
+        ScriptKind::ALL.iter().all(|kind| self.get(*kind).is_none()) && self.triggers.is_empty()
+    }
+    /// Passes the `kind` script of `package`, if any, to `hook` with the
+    /// arguments `args`, e.g. `["configure"]` for postinst.
+    pub async fn run<H: ScriptHook + ?Sized>(
+        &self,
+        hook: &H,
+        package: &str,
+        kind: ScriptKind,
+        args: &[&str],
+    ) -> io::Result<()> {
+        match self.get(kind) {
+            Some(script) => hook.run(package, kind, script, args).await,
+            None => Ok(()),
+        }
+    }
+}
+
+impl<'a, R: Read + Unpin + Send + 'a> DebReader<'a, R> {
+    /// Reads the maintainer scripts and triggers from the control.tar
+    /// member, skipping the rest.
+    pub async fn maintainer_scripts(mut self) -> io::Result<MaintainerScripts> {
+        let mut entries = match self.next().await {
+            Some(Ok(DebEntry::Control(tarball))) => tarball.entries()?,
+            Some(Err(err)) => return Err(err),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "no control.tar entry",
+                ))
+            }
+        };
+        let mut scripts = MaintainerScripts::default();
+        while let Some(entry) = entries.next().await {
+            let mut entry = entry?;
+            if entry.header().entry_type() != TarballEntryType::Regular {
+                continue;
+            }
+            let path = entry.header().path()?;
+            let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            let name = name.to_string();
+            let mut data = Vec::with_capacity(entry.header().size()? as usize);
+            if name == "triggers" {
+                entry.read_to_end(&mut data).await?;
+                scripts.triggers = Trigger::parse_list(&String::from_utf8_lossy(&data))?;
+            } else if let Some(slot) = scripts.slot(&name) {
+                entry.read_to_end(&mut data).await?;
+                *slot = Some(data);
+            }
+        }
+        Ok(scripts)
+    }
+}
+
+/// Decides whether and how the maintainer scripts of the packages being
+/// installed are run.
+#[async_trait]
+pub trait ScriptHook: Send + Sync {
+    /// Runs, or skips, the `kind` script of `package` with the contents
+    /// `script` and the arguments `args`.
+    async fn run(
+        &self,
+        package: &str,
+        kind: ScriptKind,
+        script: &[u8],
+        args: &[&str],
+    ) -> io::Result<()>;
+}
+
+/// A [`ScriptHook`] skipping all the scripts, e.g. for a rootfs whose
+/// packages are configured on first boot.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SkipScripts;
+
+#[async_trait]
+impl ScriptHook for SkipScripts {
+    async fn run(&self, package: &str, kind: ScriptKind, _: &[u8], _: &[&str]) -> io::Result<()> {
+        tracing::debug!("skipping {} of {}", kind, package);
+        Ok(())
+    }
+}
+
+/// A [`ScriptHook`] running the scripts chrooted into the target root,
+/// optionally through a wrapper such as `fakechroot fakeroot`.
+#[derive(Clone, Debug)]
+pub struct ChrootScripts {
+    root: PathBuf,
+    wrapper: Vec<String>,
+}
+
+impl ChrootScripts {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            wrapper: vec![],
+        }
+    }
+    /// Runs `chroot` through the command `wrapper`, e.g.
+    /// `["fakechroot", "fakeroot"]`.
+    pub fn wrapper<I: IntoIterator<Item = impl Into<String>>>(mut self, wrapper: I) -> Self {
+        self.wrapper = wrapper.into_iter().map(|w| w.into()).collect();
+        self
+    }
+}
+
+#[async_trait]
+impl ScriptHook for ChrootScripts {
+    async fn run(
+        &self,
+        package: &str,
+        kind: ScriptKind,
+        script: &[u8],
+        args: &[&str],
+    ) -> io::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = PathBuf::from("/var/lib/dpkg/tmp.ci");
+        let path = dir.join(format!("{}.{}", package, kind));
+        let host_dir = self.root.join(dir.strip_prefix("/").unwrap());
+        let host_path = host_dir.join(path.file_name().unwrap());
+        async_std::fs::create_dir_all(&host_dir).await?;
+        async_std::fs::write(&host_path, script).await?;
+        async_std::fs::set_permissions(&host_path, std::fs::Permissions::from_mode(0o755)).await?;
+        let mut argv = self.wrapper.clone();
+        argv.push("chroot".to_string());
+        argv.push(self.root.to_string_lossy().into_owned());
+        argv.push(path.to_string_lossy().into_owned());
+        argv.extend(args.iter().map(|arg| arg.to_string()));
+        let (env_package, name) = (package.to_string(), kind.name());
+        let status = task::spawn_blocking(move || {
+            std::process::Command::new(&argv[0])
+                .args(&argv[1..])
+                .env("DPKG_MAINTSCRIPT_PACKAGE", &env_package)
+                .env("DPKG_MAINTSCRIPT_NAME", name)
+                .status()
+        })
+        .await;
+        async_std::fs::remove_file(&host_path).await.ok();
+        match status? {
+            status if status.success() => Ok(()),
+            status => Err(io::Error::other(format!(
+                "{} of {} failed: {}",
+                kind, package, status
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_scripts() {
+        let mut scripts = MaintainerScripts::default();
+        assert!(scripts.is_empty());
+        *scripts.slot("postinst").unwrap() = Some(b"#!/bin/sh\n".to_vec());
+        assert!(scripts.slot("config").is_none());
+        assert_eq!(scripts.get(ScriptKind::Postinst), Some(&b"#!/bin/sh\n"[..]));
+        scripts
+            .run(&SkipScripts, "hello", ScriptKind::Postinst, &["configure"])
+            .await
+            .unwrap();
+    }
+}
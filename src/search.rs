@@ -0,0 +1,368 @@
+//! Package search
+//!
+//! [`Universe::search`] finds packages by their name and description, as
+//! `apt-cache search` does. The descriptions include those of the
+//! `Translation-*` indexes set with [`Universe::set_translations`], which
+//! hold the long descriptions of the Debian packages:
+//!
+//! ```ignore
+//! universe.set_translations([Translations::parse(&translation_en)?]);
+//! for hit in universe.search(&SearchQuery::words("http server")) {
+//!     println!("{}", universe.package(hit.solvable));
+//! }
+//! ```
+//!
+//! A [`SearchIndex`] built once speeds up the word searches of large
+//! universes; it returns the same hits.
+
+use {
+    crate::{
+        control::{ControlFile, ParseError},
+        idmap::{IntoId, ToIndex},
+        packages::Package,
+        universe::Universe,
+    },
+    resolvo::SolvableId,
+    std::collections::{BTreeMap, HashMap},
+};
+
+/// The translated descriptions of a `Translation-*` index, keyed by the
+/// `Description-md5` of the packages.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Translations {
+    descriptions: HashMap<String, String>,
+}
+
+impl Translations {
+    /// Parses a `Translation-*` index, e.g. `main/i18n/Translation-en`.
+    pub fn parse(src: &str) -> Result<Self, ParseError> {
+        let mut descriptions = HashMap::new();
+        for stanza in ControlFile::parse(src)?.stanzas() {
+            let md5 = stanza
+                .field("Description-md5")
+                .ok_or_else(|| ParseError::from("Field Description-md5 not found"))?;
+            let description = stanza.fields().find(|field| {
+                let name = field.name();
+                name.len() > 12
+                    && name
+                        .get(..12)
+                        .is_some_and(|prefix| prefix.eq_ignore_ascii_case("Description-"))
+                    && !field.is_a("Description-md5")
+            });
+            if let Some(description) = description {
+                descriptions.insert(
+                    md5.trim().to_string(),
+                    description.value().trim().to_string(),
+                );
+            }
+        }
+        Ok(Self { descriptions })
+    }
+    /// Returns the description with the md5 `md5`.
+    pub fn get(&self, md5: &str) -> Option<&str> {
+        self.descriptions.get(md5).map(String::as_str)
+    }
+    pub fn len(&self) -> usize {
+        self.descriptions.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.descriptions.is_empty()
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Pattern {
+    // lowercase words
+    Words(Vec<String>),
+    #[cfg(feature = "regex")]
+    Regex(regex::Regex),
+}
+
+/// What [`Universe::search`] looks for.
+#[derive(Clone, Debug)]
+pub struct SearchQuery {
+    pattern: Pattern,
+    names_only: bool,
+}
+
+impl SearchQuery {
+    /// Packages whose name or description contain each of the
+    /// whitespace-separated words of `query`, ignoring case.
+    pub fn words(query: &str) -> Self {
+        Self {
+            pattern: Pattern::Words(query.split_whitespace().map(str::to_lowercase).collect()),
+            names_only: false,
+        }
+    }
+    /// Packages whose name or description match the regular expression
+    /// `pattern`, ignoring case.
+    #[cfg(feature = "regex")]
+    pub fn regex(pattern: &str) -> Result<Self, regex::Error> {
+        Ok(Self {
+            pattern: Pattern::Regex(
+                regex::RegexBuilder::new(pattern)
+                    .case_insensitive(true)
+                    .build()?,
+            ),
+            names_only: false,
+        })
+    }
+    /// Matches the package names only, as `apt-cache search --names-only`.
+    pub fn names_only(mut self) -> Self {
+        self.names_only = true;
+        self
+    }
+    // Returns the rank of a package named `name` described by
+    // `descriptions`, None if it does not match.
+    fn rank<'d>(
+        &self,
+        name: &str,
+        descriptions: impl Iterator<Item = &'d str> + Clone,
+    ) -> Option<SearchRank> {
+        let in_description =
+            |matches: &dyn Fn(&str) -> bool| !self.names_only && descriptions.clone().any(matches);
+        let (exact, in_name, in_both) = match &self.pattern {
+            Pattern::Words(words) => {
+                let name = name.to_lowercase();
+                let lower = |text: &str| text.to_lowercase();
+                let mut in_name = 0;
+                for word in words {
+                    if name.contains(word.as_str()) {
+                        in_name += 1;
+                    } else if !in_description(&|text| lower(text).contains(word.as_str())) {
+                        return None;
+                    }
+                }
+                let exact = words.len() == 1 && words[0] == name;
+                (exact, in_name == words.len(), in_name > 0)
+            }
+            #[cfg(feature = "regex")]
+            Pattern::Regex(regex) => match regex.find(name) {
+                Some(found) => (found.len() == name.len(), true, true),
+                None if in_description(&|text| regex.is_match(text)) => (false, false, false),
+                None => return None,
+            },
+        };
+        Some(match (exact, in_name, in_both) {
+            (true, _, _) => SearchRank::ExactName,
+            (_, true, _) => SearchRank::Name,
+            (_, _, true) => SearchRank::NameAndDescription,
+            _ => SearchRank::Description,
+        })
+    }
+}
+
+/// How well a package matches a [`SearchQuery`], the best first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SearchRank {
+    /// The name is the query itself
+    ExactName,
+    /// The name matches the whole query
+    Name,
+    /// The name matches some words, the description the others
+    NameAndDescription,
+    /// Only the description matches
+    Description,
+}
+
+/// A package found by [`Universe::search`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SearchHit {
+    pub solvable: SolvableId,
+    pub rank: SearchRank,
+}
+
+/// An inverted index of the words of the package names and descriptions
+/// of a universe, for [`SearchIndex::search`].
+///
+/// The index refers to the packages by their ids, so it is only valid for
+/// the universe it was built from, with the same translations.
+#[derive(Clone, Debug, Default)]
+pub struct SearchIndex {
+    words: BTreeMap<String, Vec<SolvableId>>,
+    count: usize,
+}
+
+// Splits `text` into its lowercase alphanumeric words.
+fn tokens(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
+
+impl SearchIndex {
+    pub fn new<S: AsRef<str> + 'static>(universe: &Universe<S>) -> Self {
+        let mut words = BTreeMap::<String, Vec<SolvableId>>::new();
+        let mut count = 0;
+        for (id, package) in universe.packages().enumerate() {
+            let id: SolvableId = id.into_id();
+            let text = std::iter::once(package.name()).chain(universe.descriptions(package));
+            for word in text.flat_map(tokens) {
+                let ids = words.entry(word).or_default();
+                // the packages are indexed in order
+                if ids.last() != Some(&id) {
+                    ids.push(id);
+                }
+            }
+            count += 1;
+        }
+        Self { words, count }
+    }
+    // The packages containing the words `word` contains, a superset of the
+    // packages containing `word`.
+    fn candidates(&self, word: &str) -> Vec<bool> {
+        let mut candidates = vec![true; self.count];
+        for part in tokens(word) {
+            let mut found = vec![false; self.count];
+            for (_, ids) in self.words.iter().filter(|(token, _)| token.contains(&part)) {
+                ids.iter().for_each(|id| found[id.to_index()] = true);
+            }
+            candidates
+                .iter_mut()
+                .zip(found)
+                .for_each(|(candidate, found)| *candidate &= found);
+        }
+        candidates
+    }
+    /// Same as [`Universe::search`], looking the words of `query` up in
+    /// the index first. Regular expressions are matched against every
+    /// package.
+    pub fn search<S: AsRef<str> + 'static>(
+        &self,
+        universe: &Universe<S>,
+        query: &SearchQuery,
+    ) -> Vec<SearchHit> {
+        let candidates = match &query.pattern {
+            Pattern::Words(words) => words.iter().fold(vec![true; self.count], |all, word| {
+                all.into_iter()
+                    .zip(self.candidates(word))
+                    .map(|(a, b)| a && b)
+                    .collect()
+            }),
+            #[cfg(feature = "regex")]
+            Pattern::Regex(_) => vec![true; self.count],
+        };
+        universe.search_in(query, |id| candidates.get(id).copied().unwrap_or(false))
+    }
+}
+
+impl<S: AsRef<str> + 'static> Universe<S> {
+    // The descriptions of `package`: its own and its translations.
+    fn descriptions<'a>(
+        &'a self,
+        package: &'a Package<'a>,
+    ) -> impl Iterator<Item = &'a str> + Clone + 'a {
+        let md5 = package.description_md5();
+        package.field("Description").into_iter().chain(
+            self.translations()
+                .iter()
+                .filter_map(move |translations| translations.get(md5?)),
+        )
+    }
+    /// Returns the packages matching `query`, the best ranked first, then
+    /// by name and id.
+    pub fn search(&self, query: &SearchQuery) -> Vec<SearchHit> {
+        self.search_in(query, |_| true)
+    }
+    fn search_in(&self, query: &SearchQuery, candidate: impl Fn(usize) -> bool) -> Vec<SearchHit> {
+        let mut hits: Vec<(SearchHit, &str)> = self
+            .packages()
+            .enumerate()
+            .filter(|(id, _)| candidate(*id))
+            .filter_map(|(id, package)| {
+                let rank = query.rank(package.name(), self.descriptions(package))?;
+                let hit = SearchHit {
+                    solvable: id.into_id(),
+                    rank,
+                };
+                Some((hit, package.name()))
+            })
+            .collect();
+        hits.sort_by(|(this, this_name), (that, that_name)| {
+            (this.rank, this_name, this.solvable).cmp(&(that.rank, that_name, that.solvable))
+        });
+        hits.into_iter().map(|(hit, _)| hit).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{packages::Packages, universe::Universe},
+    };
+
+    #[test]
+    fn test_search() {
+        let src = "\
+Package: nginx
+Version: 1.22
+Architecture: amd64
+Description: small, powerful, scalable web/proxy server
+Description-md5: 0001
+
+Package: apache2
+Version: 2.4
+Architecture: amd64
+Description: Apache HTTP Server
+Description-md5: 0002
+
+Package: nginx-doc
+Version: 1.22
+Architecture: all
+Description: documentation for nginx
+
+Package: lighttpd
+Version: 1.4
+Architecture: amd64
+Description-md5: 0003
+";
+        let translations = Translations::parse(
+            "Package: lighttpd\nDescription-md5: 0003\nDescription-en: fast webserver\n \
+             Lighttpd is a secure, fast and flexible HTTP server.\n",
+        )
+        .unwrap();
+        assert_eq!(translations.len(), 1);
+        let mut uni = Universe::new("amd64", [Packages::new_test(src).unwrap()]).unwrap();
+        uni.set_translations([translations]);
+        let index = SearchIndex::new(&uni);
+        let search = |query: &SearchQuery| {
+            let hits = uni.search(query);
+            assert_eq!(index.search(&uni, query), hits);
+            hits.into_iter()
+                .map(|hit| (uni.package(hit.solvable).name(), hit.rank))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            search(&SearchQuery::words("NGINX")),
+            [
+                ("nginx", SearchRank::ExactName),
+                ("nginx-doc", SearchRank::Name)
+            ]
+        );
+        assert_eq!(
+            search(&SearchQuery::words("http server")),
+            [
+                ("lighttpd", SearchRank::NameAndDescription),
+                ("apache2", SearchRank::Description)
+            ]
+        );
+        assert_eq!(
+            search(&SearchQuery::words("nginx documentation")),
+            [("nginx-doc", SearchRank::NameAndDescription)]
+        );
+        assert_eq!(
+            search(&SearchQuery::words("web/proxy")),
+            [("nginx", SearchRank::Description)]
+        );
+        assert!(search(&SearchQuery::words("server").names_only()).is_empty());
+        #[cfg(feature = "regex")]
+        assert_eq!(
+            search(&SearchQuery::regex("^(apache|lighttpd)").unwrap()),
+            [
+                ("lighttpd", SearchRank::ExactName),
+                ("apache2", SearchRank::Name)
+            ]
+        );
+    }
+}
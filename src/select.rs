@@ -0,0 +1,182 @@
+//! Package selection by name patterns
+//!
+//! [`Universe::pattern_problem`] accepts requirements naming packages by a
+//! glob, e.g. `libfoo-*`, or by a regular expression between slashes, e.g.
+//! `/-dbgsym$/`, and requires every package whose name matches:
+//!
+//! ```ignore
+//! let problem = universe.pattern_problem(&["nginx", "libnginx-mod-*", "/^lua-.*-dev$/"])?;
+//! let solution = universe.solve(problem)?;
+//! ```
+//!
+//! Regular expressions need the `regex` feature.
+
+use {
+    crate::{
+        control::ParseError, error::Error, policy::glob, universe::Universe, version::Dependency,
+    },
+    resolvo::SolvableId,
+};
+
+// The name pattern of a requirement.
+enum NamePattern<'a> {
+    Glob(&'a str),
+    #[cfg(feature = "regex")]
+    Regex(regex::Regex),
+}
+
+impl NamePattern<'_> {
+    fn matches(&self, name: &str) -> bool {
+        match self {
+            NamePattern::Glob(pattern) => glob(pattern, name),
+            #[cfg(feature = "regex")]
+            NamePattern::Regex(regex) => regex.is_match(name),
+        }
+    }
+}
+
+#[cfg(feature = "regex")]
+fn regex_pattern<'a>(regex: &str, _: &str) -> Result<NamePattern<'a>, ParseError> {
+    regex::Regex::new(regex)
+        .map(NamePattern::Regex)
+        .map_err(|err| ParseError::from(err.to_string()))
+}
+
+#[cfg(not(feature = "regex"))]
+fn regex_pattern<'a>(_: &str, relation: &str) -> Result<NamePattern<'a>, ParseError> {
+    Err(ParseError::from(format!(
+        "{}: regular expressions need the regex feature",
+        relation
+    )))
+}
+
+// Splits `relation` into its name pattern and the rest, e.g. `:amd64` or
+// `(>= 1.0)`. Returns None if the name is not a pattern.
+fn split_pattern(relation: &str) -> Result<Option<(NamePattern<'_>, &str)>, ParseError> {
+    let (pattern, rest) = match relation.trim().strip_prefix('/') {
+        Some(regex) => {
+            let (regex, rest) = regex.split_once('/').ok_or_else(|| {
+                ParseError::from(format!("unterminated regular expression {}", relation))
+            })?;
+            (regex_pattern(regex, relation)?, rest)
+        }
+        None => {
+            let relation = relation.trim();
+            let (name, rest) =
+                relation.split_at(relation.find([' ', '(', ':']).unwrap_or(relation.len()));
+            if !name.contains(['*', '?']) {
+                return Ok(None);
+            }
+            (NamePattern::Glob(name), rest)
+        }
+    };
+    if rest.contains('|') {
+        return Err(ParseError::from(format!(
+            "{}: a pattern cannot be an alternative",
+            relation
+        )));
+    }
+    Ok(Some((pattern, rest)))
+}
+
+impl<S: AsRef<str> + 'static> Universe<S> {
+    /// Builds a problem installing `requirements`, as
+    /// [`Universe::problem`] does, expanding the requirements whose package
+    /// name is a pattern to one requirement for each package matching it,
+    /// with the same architecture and version, e.g. `libfoo-* (>= 1.2)`.
+    /// Globs match the whole name, regular expressions any part of it.
+    /// Fails if no package matches a pattern.
+    pub fn pattern_problem(
+        &self,
+        requirements: &[&str],
+    ) -> Result<resolvo::Problem<std::iter::Empty<SolvableId>>, Error> {
+        let mut relations = vec![];
+        for requirement in requirements {
+            match split_pattern(requirement)? {
+                None => relations.push(requirement.to_string()),
+                Some((pattern, rest)) => {
+                    let mut names: Vec<&str> = self
+                        .packages()
+                        .map(|p| p.name())
+                        .filter(|name| pattern.matches(name))
+                        .collect();
+                    if names.is_empty() {
+                        return Err(Error::NotFound(format!("package matching {}", requirement)));
+                    }
+                    names.sort_unstable();
+                    names.dedup();
+                    relations.extend(names.into_iter().map(|name| format!("{}{}", name, rest)));
+                }
+            }
+        }
+        let requirements = relations
+            .iter()
+            .map(|relation| Dependency::try_from(relation.as_str()))
+            .collect::<Result<Vec<_>, ParseError>>()?;
+        Ok(self.problem(requirements, vec![], vec![]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{packages::Packages, universe::Universe},
+    };
+
+    #[test]
+    fn test_pattern_problem() {
+        let src = "\
+Package: libfoo1
+Version: 1.0
+Architecture: amd64
+
+Package: libfoo-dev
+Version: 1.0
+Architecture: amd64
+Depends: libfoo1
+
+Package: libfoo-doc
+Version: 0.9
+Architecture: all
+
+Package: libbar-dev
+Version: 2.0
+Architecture: amd64
+";
+        let mut uni = Universe::new("amd64", [Packages::new_test(src).unwrap()]).unwrap();
+        let mut solve = |requirements: &[&str]| {
+            let problem = uni.pattern_problem(requirements)?;
+            let solution = uni.solve(problem).map_err(|err| uni.solver_error(err))?;
+            let mut names: Vec<_> = solution
+                .into_iter()
+                .map(|id| uni.package(id).name().to_string())
+                .collect();
+            names.sort();
+            Ok::<_, Error>(names)
+        };
+        assert_eq!(
+            solve(&["libfoo-* (>= 0.9)"]).unwrap(),
+            ["libfoo-dev", "libfoo-doc", "libfoo1"]
+        );
+        assert_eq!(
+            solve(&["libfoo?", "libbar-dev"]).unwrap(),
+            ["libbar-dev", "libfoo1"]
+        );
+        #[cfg(feature = "regex")]
+        assert_eq!(
+            solve(&["/-dev$/:amd64"]).unwrap(),
+            ["libbar-dev", "libfoo-dev", "libfoo1"]
+        );
+        assert!(matches!(solve(&["libbaz-*"]), Err(Error::NotFound(_))));
+        assert!(matches!(
+            solve(&["libfoo-* (>= 1.0)"]),
+            Err(Error::Solver(_))
+        ));
+        assert!(matches!(
+            solve(&["libfoo-* | libbar-dev"]),
+            Err(Error::Parse(_))
+        ));
+        assert!(matches!(solve(&["/-dev$"]), Err(Error::Parse(_))));
+    }
+}
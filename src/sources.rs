@@ -0,0 +1,504 @@
+//! apt sources
+//!
+//! Parses the one-line `sources.list` format, options included:
+//!
+//! ```text
+//! deb [arch=amd64,arm64 signed-by=/etc/apt/keyrings/ex.gpg] https://example.org/debian stable main
+//! deb-src [trusted=yes] file:/srv/repo ./
+//! ```
+//!
+//! List options can be set (`arch=amd64`), extended (`arch+=i386`) or
+//! reduced (`arch-=i386`) relative to the defaults, as with apt.
+
+use {
+    crate::control::ParseError,
+    std::{fmt, str::FromStr},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SourceKind {
+    Deb,
+    DebSrc,
+}
+
+/// A list valued option, like `arch` or `lang`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ListOption {
+    /// The values replacing the defaults, if set
+    pub set: Option<Vec<String>>,
+    /// The values added to the defaults or to the set values
+    pub add: Vec<String>,
+    /// The values removed
+    pub remove: Vec<String>,
+}
+
+impl ListOption {
+    pub fn is_default(&self) -> bool {
+        self.set.is_none() && self.add.is_empty() && self.remove.is_empty()
+    }
+    /// The values of the option, given the defaults.
+    pub fn apply<'a>(&self, defaults: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+        let mut values: Vec<String> = match &self.set {
+            Some(set) => set.clone(),
+            None => defaults.into_iter().map(String::from).collect(),
+        };
+        for value in &self.add {
+            if !values.contains(value) {
+                values.push(value.clone());
+            }
+        }
+        values.retain(|value| !self.remove.contains(value));
+        values
+    }
+}
+
+/// The options of a source, between brackets after `deb`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SourceOptions {
+    /// `arch`: the architectures to fetch the indexes of
+    pub arch: ListOption,
+    /// `lang`: the languages of the translations
+    pub lang: ListOption,
+    /// `target`: the index targets, e.g. `Packages` or `Contents-deb`
+    pub target: ListOption,
+    /// `trusted`: skip (yes) or enforce (no) the signature verification
+    pub trusted: Option<bool>,
+    /// `signed-by`: the keyring files or fingerprints the Release must be
+    /// signed with
+    pub signed_by: Vec<String>,
+    /// `check-valid-until`
+    pub check_valid_until: Option<bool>,
+    /// `check-date`
+    pub check_date: Option<bool>,
+    /// The other options, in order
+    pub other: Vec<(String, String)>,
+}
+
+// apt's StringToBool
+fn parse_bool(name: &str, value: &str) -> Result<bool, ParseError> {
+    match value.to_ascii_lowercase().as_str() {
+        "yes" | "true" | "with" | "on" | "enable" | "1" => Ok(true),
+        "no" | "false" | "without" | "off" | "disable" | "0" => Ok(false),
+        _ => Err(format!("invalid value {} of option {}", value, name).into()),
+    }
+}
+
+impl SourceOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Parses the options, e.g. `arch=amd64,arm64 trusted=yes`.
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut options = Self::new();
+        for option in text.split_ascii_whitespace() {
+            options.set(option)?;
+        }
+        Ok(options)
+    }
+    /// Sets an option from its `name=value`, `name+=value` or `name-=value`
+    /// form.
+    pub fn set(&mut self, option: &str) -> Result<(), ParseError> {
+        let (name, value) = option
+            .split_once('=')
+            .ok_or_else(|| ParseError::from(format!("option {} has no value", option)))?;
+        let (name, op) = match name.as_bytes().last() {
+            Some(op @ (b'+' | b'-')) => (&name[..name.len() - 1], Some(*op)),
+            _ => (name, None),
+        };
+        let list = match name {
+            "arch" => Some(&mut self.arch),
+            "lang" => Some(&mut self.lang),
+            "target" => Some(&mut self.target),
+            _ => None,
+        };
+        let values = || value.split(',').filter(|v| !v.is_empty()).map(String::from);
+        match (list, op) {
+            (Some(list), None) => list.set = Some(values().collect()),
+            (Some(list), Some(b'+')) => list.add.extend(values()),
+            (Some(list), Some(_)) => list.remove.extend(values()),
+            (None, Some(_)) => {
+                return Err(format!("option {} is not a list", name).into());
+            }
+            (None, None) => match name {
+                "trusted" => self.trusted = Some(parse_bool(name, value)?),
+                "signed-by" => self.signed_by = values().collect(),
+                "check-valid-until" => self.check_valid_until = Some(parse_bool(name, value)?),
+                "check-date" => self.check_date = Some(parse_bool(name, value)?),
+                _ => self.other.push((name.to_string(), value.to_string())),
+            },
+        }
+        Ok(())
+    }
+    /// The value of an option kept as is, e.g. `by-hash`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.other
+            .iter()
+            .rev()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+    /// Whether the index target `name` is fetched, `Packages` and `Sources`
+    /// by default.
+    pub fn wants_target(&self, name: &str) -> bool {
+        self.target
+            .apply(["Packages", "Sources"])
+            .iter()
+            .any(|t| t == name)
+    }
+}
+
+impl fmt::Display for SourceOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut sep = "";
+        let mut opt = |f: &mut fmt::Formatter<'_>, name: &str, op: &str, value: &str| {
+            let r = write!(f, "{}{}{}={}", sep, name, op, value);
+            sep = " ";
+            r
+        };
+        for (name, list) in [
+            ("arch", &self.arch),
+            ("lang", &self.lang),
+            ("target", &self.target),
+        ] {
+            if let Some(set) = &list.set {
+                opt(f, name, "", &set.join(","))?;
+            }
+            if !list.add.is_empty() {
+                opt(f, name, "+", &list.add.join(","))?;
+            }
+            if !list.remove.is_empty() {
+                opt(f, name, "-", &list.remove.join(","))?;
+            }
+        }
+        let yes_no = |b: bool| if b { "yes" } else { "no" };
+        if let Some(trusted) = self.trusted {
+            opt(f, "trusted", "", yes_no(trusted))?;
+        }
+        if !self.signed_by.is_empty() {
+            opt(f, "signed-by", "", &self.signed_by.join(","))?;
+        }
+        if let Some(check) = self.check_valid_until {
+            opt(f, "check-valid-until", "", yes_no(check))?;
+        }
+        if let Some(check) = self.check_date {
+            opt(f, "check-date", "", yes_no(check))?;
+        }
+        for (name, value) in &self.other {
+            opt(f, name, "", value)?;
+        }
+        Ok(())
+    }
+}
+
+/// A `deb` or `deb-src` line of a sources.list file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceEntry {
+    pub kind: SourceKind,
+    pub options: SourceOptions,
+    pub uri: String,
+    pub suite: String,
+    /// Empty for a flat repository, whose suite is a path ending with `/`
+    pub components: Vec<String>,
+}
+
+impl SourceEntry {
+    /// Parses a sources.list line. Returns `None` for blank and comment
+    /// lines.
+    pub fn parse(line: &str) -> Result<Option<Self>, ParseError> {
+        let line = line.split_once('#').map_or(line, |(line, _)| line).trim();
+        if line.is_empty() {
+            return Ok(None);
+        }
+        let (kind, rest) = line
+            .split_once(|c: char| c.is_ascii_whitespace())
+            .ok_or_else(|| ParseError::from(format!("malformed source {}", line)))?;
+        let kind = match kind {
+            "deb" => SourceKind::Deb,
+            "deb-src" => SourceKind::DebSrc,
+            _ => return Err(format!("unknown source type {}", kind).into()),
+        };
+        let rest = rest.trim_start();
+        let (options, rest) = match rest.strip_prefix('[') {
+            Some(rest) => {
+                let (options, rest) = rest
+                    .split_once(']')
+                    .ok_or_else(|| ParseError::from("unterminated source options"))?;
+                (SourceOptions::parse(options)?, rest)
+            }
+            None => (SourceOptions::new(), rest),
+        };
+        let mut words = rest.split_ascii_whitespace();
+        let uri = words
+            .next()
+            .ok_or_else(|| ParseError::from("source has no URI"))?;
+        let suite = words
+            .next()
+            .ok_or_else(|| ParseError::from(format!("source {} has no suite", uri)))?;
+        let components: Vec<String> = words.map(String::from).collect();
+        if suite.ends_with('/') != components.is_empty() {
+            return Err(match components.is_empty() {
+                true => format!("source {} {} has no components", uri, suite).into(),
+                false => format!("flat source {} {} has components", uri, suite).into(),
+            });
+        }
+        Ok(Some(Self {
+            kind,
+            options,
+            uri: uri.to_string(),
+            suite: suite.to_string(),
+            components,
+        }))
+    }
+    pub fn is_flat(&self) -> bool {
+        self.components.is_empty()
+    }
+    /// The architectures to fetch the Packages indexes of: the `arch`
+    /// option applied to `defaults` (the native and foreign architectures),
+    /// restricted to the ones of the Release file if it lists any.
+    pub fn architectures<'a, 'b>(
+        &self,
+        defaults: impl IntoIterator<Item = &'a str>,
+        release: impl IntoIterator<Item = &'b str>,
+    ) -> Vec<String> {
+        let mut arches = self.options.arch.apply(defaults);
+        let release: Vec<&str> = release.into_iter().collect();
+        if !release.is_empty() {
+            arches.retain(|arch| release.contains(&arch.as_str()));
+        }
+        arches
+    }
+}
+
+impl fmt::Display for SourceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self.kind {
+            SourceKind::Deb => "deb",
+            SourceKind::DebSrc => "deb-src",
+        })?;
+        if !self.options.is_empty() {
+            write!(f, " [{}]", self.options)?;
+        }
+        write!(f, " {} {}", self.uri, self.suite)?;
+        for component in &self.components {
+            write!(f, " {}", component)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for SourceEntry {
+    type Err = ParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)?.ok_or_else(|| ParseError::from("empty source"))
+    }
+}
+
+/// The entries of sources.list files.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SourcesList {
+    pub entries: Vec<SourceEntry>,
+}
+
+impl SourcesList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Appends the entries of a sources.list file.
+    pub fn parse(&mut self, text: &str) -> Result<(), ParseError> {
+        for (num, line) in text.lines().enumerate() {
+            let entry = SourceEntry::parse(line)
+                .map_err(|err| ParseError::from(format!("line {}: {}", num + 1, err)))?;
+            self.entries.extend(entry);
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for SourcesList {
+    type Err = ParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut list = Self::new();
+        list.parse(s)?;
+        Ok(list)
+    }
+}
+
+#[cfg(feature = "io")]
+mod io {
+    use {
+        super::*,
+        crate::{
+            error::Result,
+            fsrepo::FSDebRepo,
+            httprepo::HttpDebRepo,
+            ipfs::ipfs_transport,
+            keyring::Keyring,
+            packages::Packages,
+            release::{FreshnessPolicy, Release},
+            repo::DebRepo,
+        },
+        async_std::{fs, path::Path, stream::StreamExt},
+    };
+
+    impl SourceOptions {
+        /// The freshness checks of Release files, per `check-valid-until`
+        /// and `check-date`.
+        pub fn freshness(&self) -> FreshnessPolicy {
+            let default = FreshnessPolicy::default();
+            FreshnessPolicy {
+                check_valid_until: self.check_valid_until.unwrap_or(default.check_valid_until),
+                check_date: self.check_date.unwrap_or(default.check_date),
+                ..default
+            }
+        }
+    }
+
+    impl SourceEntry {
+        /// Opens the repository, an http(s), `ipfs:`, `ipns:` or `file:` URI.
+        pub async fn open(&self) -> Result<DebRepo> {
+            let uri = self.uri.as_str();
+            let repo: DebRepo = if uri.starts_with("http:") || uri.starts_with("https:") {
+                HttpDebRepo::new(uri).await?.into()
+            } else if uri.starts_with("ipfs:") || uri.starts_with("ipns:") {
+                ipfs_transport(uri, &[]).await?.into()
+            } else {
+                FSDebRepo::from_url(uri).await?.into()
+            };
+            Ok(repo.with_freshness(self.options.freshness()))
+        }
+        /// Fetches the Release file of the suite: unverified with
+        /// `trusted=yes`, verified with the `signed-by` keys if any (see
+        /// [`Keyring::signed_by`]), or else with the default keyring.
+        pub async fn fetch_release(&self, repo: &DebRepo) -> Result<Release> {
+            if self.options.trusted == Some(true) {
+                return repo.fetch_release(&self.suite).await;
+            }
+            if self.options.signed_by.is_empty() {
+                return repo.fetch_verify_release(&self.suite).await;
+            }
+            let keyring = Keyring::load_system()
+                .await?
+                .signed_by(&self.options.signed_by)
+                .await?;
+            repo.fetch_verify_release_with_keys(&self.suite, keyring.keys())
+                .await
+        }
+        /// Fetches the Packages indexes of the components for the
+        /// [`SourceEntry::architectures`], unless the `target` option
+        /// excludes them.
+        pub async fn fetch_packages<'a>(
+            &self,
+            release: &Release,
+            arches: impl IntoIterator<Item = &'a str>,
+        ) -> Result<Vec<Packages<Box<str>>>> {
+            if self.kind != SourceKind::Deb || !self.options.wants_target("Packages") {
+                return Ok(vec![]);
+            }
+            let mut indexes = vec![];
+            let arches = self.architectures(arches, release.architectures());
+            for component in &self.components {
+                for arch in &arches {
+                    indexes.push(release.fetch_packages(component, arch).await?);
+                }
+            }
+            Ok(indexes)
+        }
+    }
+
+    impl SourcesList {
+        /// Loads `/etc/apt/sources.list` and `/etc/apt/sources.list.d/*.list`.
+        pub async fn load_system() -> Result<Self> {
+            Self::load_dir("/etc/apt").await
+        }
+        /// Loads `sources.list` and `sources.list.d/*.list` from the apt
+        /// configuration directory `etc`. Missing files are ignored.
+        pub async fn load_dir(etc: impl AsRef<Path>) -> Result<Self> {
+            let etc = etc.as_ref();
+            let mut list = Self::new();
+            let mut files = vec![etc.join("sources.list")];
+            match fs::read_dir(etc.join("sources.list.d")).await {
+                Ok(mut dir) => {
+                    let mut parts = vec![];
+                    while let Some(entry) = dir.next().await {
+                        let path = entry?.path();
+                        if path.extension().is_some_and(|ext| ext == "list") {
+                            parts.push(path);
+                        }
+                    }
+                    parts.sort();
+                    files.extend(parts);
+                }
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(err) => return Err(err.into()),
+            }
+            for file in files {
+                match fs::read_to_string(&file).await {
+                    Ok(text) => list
+                        .parse(&text)
+                        .map_err(|err| ParseError::from(format!("{}: {}", file.display(), err)))?,
+                    Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(err) => return Err(err.into()),
+                }
+            }
+            Ok(list)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sources() {
+        let list: SourcesList = "\
+# comment
+deb [arch=amd64,arm64 signed-by=/etc/apt/keyrings/ex.gpg] https://example.org/ stable main contrib
+deb-src [ trusted=yes lang=en,de target-=Sources by-hash=force ] file:/srv/repo ./ # flat
+
+deb [arch+=i386 check-valid-until=no] http://deb.debian.org/debian bookworm main
+"
+        .parse()
+        .unwrap();
+        assert_eq!(list.entries.len(), 3);
+        let deb = &list.entries[0];
+        assert_eq!(deb.kind, SourceKind::Deb);
+        assert_eq!(deb.uri, "https://example.org/");
+        assert_eq!(deb.components, ["main", "contrib"]);
+        assert_eq!(deb.options.signed_by, ["/etc/apt/keyrings/ex.gpg"]);
+        assert_eq!(
+            deb.architectures(["amd64"], ["amd64", "i386", "arm64"]),
+            ["amd64", "arm64"]
+        );
+        assert_eq!(deb.architectures(["amd64"], ["amd64"]), ["amd64"]);
+        let src = &list.entries[1];
+        assert!(src.is_flat());
+        assert_eq!(src.options.trusted, Some(true));
+        assert_eq!(src.options.lang.apply([]), ["en", "de"]);
+        assert_eq!(src.options.get("by-hash"), Some("force"));
+        assert!(src.options.wants_target("Packages"));
+        assert!(!src.options.wants_target("Sources"));
+        assert_eq!(
+            src.to_string(),
+            "deb-src [lang=en,de target-=Sources trusted=yes by-hash=force] file:/srv/repo ./"
+        );
+        let multi = &list.entries[2];
+        assert_eq!(multi.architectures(["amd64"], []), ["amd64", "i386"]);
+        assert_eq!(multi.options.check_valid_until, Some(false));
+        assert_eq!(multi.to_string().parse::<SourceEntry>().unwrap(), *multi);
+
+        for bad in [
+            "rpm http://example.org/ stable main",
+            "deb [arch=amd64 http://example.org/ stable main",
+            "deb http://example.org/ stable",
+            "deb http://example.org/ ./ main",
+            "deb [trusted=maybe] http://example.org/ stable main",
+            "deb [trusted+=yes] http://example.org/ stable main",
+        ] {
+            assert!(SourceEntry::parse(bad).is_err(), "{}", bad);
+        }
+    }
+}
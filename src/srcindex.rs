@@ -0,0 +1,175 @@
+//! Sources indexes
+//!
+//! The `Sources` index of a component lists its source packages, one stanza
+//! per version, much like a `.dsc` file with the `Source` field named
+//! `Package` and the pool directory in `Directory`. Binary packages name
+//! their source package in the `Source` field; [`crate::Universe::source_of`]
+//! resolves it against the indexes set with
+//! [`crate::Universe::set_sources`], and [`crate::Universe::by_source`]
+//! groups the packages of a solution by it.
+
+use {
+    crate::{
+        control::{ControlFile, MutableControlStanza, ParseError},
+        dsc::{parse_checksums, DscFile},
+        version::Version,
+    },
+    std::fmt,
+};
+
+/// A source package of a Sources index.
+pub struct SourcePackage {
+    control: MutableControlStanza,
+    files: Vec<DscFile>,
+}
+
+impl SourcePackage {
+    /// Parses a stanza of a Sources index.
+    pub fn parse(control: MutableControlStanza) -> Result<Self, ParseError> {
+        for field in ["Package", "Version"] {
+            if control.field(field).is_none() {
+                return Err(format!("Field {} not found", field).into());
+            }
+        }
+        let files = parse_checksums(&control)?;
+        Ok(Self { control, files })
+    }
+    pub fn name(&self) -> &str {
+        self.field("Package").unwrap_or_default()
+    }
+    pub fn version(&self) -> Version<&str> {
+        Version::from(self.field("Version").unwrap_or_default())
+    }
+    /// The pool directory of the files, e.g. `pool/main/h/hello`.
+    pub fn directory(&self) -> Option<&str> {
+        self.field("Directory")
+    }
+    /// The binary packages built from the source package.
+    pub fn binaries(&self) -> impl Iterator<Item = &str> {
+        self.field("Binary")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+    }
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.control.field(name).map(str::trim)
+    }
+    /// The files making up the source package, with their sizes and
+    /// digests.
+    pub fn files(&self) -> &[DscFile] {
+        &self.files
+    }
+}
+
+/// A parsed Sources index.
+#[derive(Default)]
+pub struct SourcesIndex {
+    packages: Vec<SourcePackage>,
+}
+
+impl SourcesIndex {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let packages = ControlFile::parse(text)?
+            .stanzas()
+            .map(|stanza| SourcePackage::parse(stanza.into()))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { packages })
+    }
+    pub fn packages(&self) -> impl Iterator<Item = &SourcePackage> {
+        self.packages.iter()
+    }
+    /// Returns the source package `name` at `version`.
+    pub fn get(&self, name: &str, version: &Version<&str>) -> Option<&SourcePackage> {
+        self.packages
+            .iter()
+            .find(|p| p.name() == name && p.version() == *version)
+    }
+}
+
+/// The source package a binary package was built from.
+pub struct BinarySource<'a> {
+    pub name: &'a str,
+    pub version: Version<&'a str>,
+    /// The source package from the Sources indexes, if found there
+    pub package: Option<&'a SourcePackage>,
+}
+
+/// The binary packages of a solution built from one source package.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceGroup {
+    pub name: String,
+    pub version: String,
+    /// The binary package names, sorted
+    pub binaries: Vec<String>,
+}
+
+/// The binary packages of a solution grouped by source package, from
+/// [`crate::Universe::by_source`]. Displays like the listing of
+/// `apt-get source --simulate`, one source package per line:
+///
+/// ```text
+/// Fetch source hello=2.10-3 (hello)
+/// Fetch source glibc=2.36-9 (libc-bin libc6)
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SourceGroups {
+    /// The groups, sorted by source package name and version
+    pub groups: Vec<SourceGroup>,
+}
+
+impl SourceGroups {
+    /// Returns the group of the source package `name`, if any.
+    pub fn get(&self, name: &str) -> Option<&SourceGroup> {
+        self.groups.iter().find(|g| g.name == name)
+    }
+}
+
+impl fmt::Display for SourceGroups {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for group in &self.groups {
+            writeln!(
+                f,
+                "Fetch source {}={} ({})",
+                group.name,
+                group.version,
+                group.binaries.join(" ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let index = SourcesIndex::parse(
+            "\
+Package: hello
+Binary: hello, hello-dbgsym
+Version: 2.10-3
+Directory: pool/main/h/hello
+Files:
+ 5a4d2b1a2f6c0e1b7d9e8f3a4b5c6d7e 1183 hello_2.10-3.dsc
+ 6cd0ffea3884a4e79330338dcc2987d6 725946 hello_2.10.orig.tar.gz
+
+Package: hello
+Binary: hello
+Version: 2.10-2
+",
+        )
+        .unwrap();
+        let hello = index.get("hello", &Version::from("2.10-3")).unwrap();
+        assert_eq!(
+            hello.binaries().collect::<Vec<_>>(),
+            ["hello", "hello-dbgsym"]
+        );
+        assert_eq!(hello.directory(), Some("pool/main/h/hello"));
+        assert_eq!(hello.files().len(), 2);
+        assert!(index.get("hello", &Version::from("2.10-1")).is_none());
+        assert!(SourcesIndex::parse("Package: hello\n").is_err());
+    }
+}
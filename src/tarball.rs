@@ -0,0 +1,337 @@
+//! Tarballs of extracted trees
+//!
+//! [`write_tarball`] packs a tree, e.g. a root file system extracted with
+//! [`crate::LocalFileSystem`], into a tar archive. With
+//! [`TarOptions::reproducible`] the same tree gives a bit-identical
+//! archive wherever and whenever it is extracted and packed:
+//!
+//! ```ignore
+//! let out = fs::File::create("rootfs.tar").await?;
+//! write_tarball("rootfs", out, &TarOptions::reproducible()).await?;
+//! ```
+//!
+//! [`write_layer`] packs it as a gzip compressed OCI image layer instead,
+//! returning the digests the image manifest and configuration refer to it
+//! by. A mirror laid out by [`crate::mirror`] is a tree like any other, but
+//! the Release file rebuilt for a filtered mirror is dated when it is
+//! written, so only mirrors of unfiltered suites archive reproducibly.
+
+use {
+    crate::digest::DigestingWriter,
+    async_compression::futures::write::GzipEncoder,
+    async_std::{
+        fs,
+        io::{self, prelude::*},
+        path::{Path, PathBuf},
+        stream::StreamExt,
+    },
+    async_tar::{Builder, EntryType, Header},
+    std::{
+        collections::HashMap,
+        os::unix::fs::{MetadataExt, PermissionsExt},
+    },
+};
+
+/// How [`write_tarball`] records the metadata of the files.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TarOptions {
+    /// The latest modification time, later ones are clamped to it
+    pub source_date_epoch: Option<u64>,
+    /// The owner `(uid, gid)` of every entry, instead of their own
+    pub owner: Option<(u32, u32)>,
+    /// The permission bits cleared from every entry, e.g. `0o022`
+    pub umask: u32,
+}
+
+impl TarOptions {
+    /// Options giving reproducible archives: the modification times are
+    /// clamped to `SOURCE_DATE_EPOCH`, 0 if unset, and every entry is
+    /// owned by root. The entries are always sorted by name, and the user
+    /// and group names are never recorded.
+    pub fn reproducible() -> Self {
+        let epoch = std::env::var("SOURCE_DATE_EPOCH")
+            .ok()
+            .and_then(|epoch| epoch.trim().parse().ok())
+            .unwrap_or(0);
+        Self {
+            source_date_epoch: Some(epoch),
+            owner: Some((0, 0)),
+            umask: 0,
+        }
+    }
+    pub fn source_date_epoch(mut self, epoch: u64) -> Self {
+        self.source_date_epoch = Some(epoch);
+        self
+    }
+    pub fn owner(mut self, uid: u32, gid: u32) -> Self {
+        self.owner = Some((uid, gid));
+        self
+    }
+    pub fn umask(mut self, umask: u32) -> Self {
+        self.umask = umask;
+        self
+    }
+    // The header of an entry of type `kind` with the metadata `meta`.
+    fn header(&self, kind: EntryType, meta: &fs::Metadata) -> Header {
+        let mut header = Header::new_gnu();
+        header.set_entry_type(kind);
+        header.set_size(0);
+        header.set_mode(meta.permissions().mode() & 0o7777 & !self.umask);
+        let (uid, gid) = self.owner.unwrap_or((meta.uid(), meta.gid()));
+        header.set_uid(uid as u64);
+        header.set_gid(gid as u64);
+        let mtime = meta.mtime().max(0) as u64;
+        header.set_mtime(match self.source_date_epoch {
+            Some(epoch) => mtime.min(epoch),
+            None => mtime,
+        });
+        header
+    }
+}
+
+// The paths in `root`, relative to it, sorted by name within each
+// directory, each directory followed by its contents.
+async fn walk(root: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut paths = vec![];
+    let mut pending = vec![(PathBuf::new(), true)];
+    while let Some((path, is_dir)) = pending.pop() {
+        if !path.as_os_str().is_empty() {
+            paths.push(path.clone());
+        }
+        if !is_dir {
+            continue;
+        }
+        let mut entries = fs::read_dir(root.join(&path)).await?;
+        let mut names = vec![];
+        while let Some(entry) = entries.next().await {
+            let entry = entry?;
+            names.push((entry.file_name(), entry.file_type().await?.is_dir()));
+        }
+        names.sort();
+        pending.extend(
+            names
+                .into_iter()
+                .rev()
+                .map(|(name, is_dir)| (path.join(name), is_dir)),
+        );
+    }
+    Ok(paths)
+}
+
+/// Writes the tree `root` to `out` as a tar archive, with the paths
+/// relative to `root`, e.g. `usr/bin/hello`, sorted by name within each
+/// directory, each directory followed by its contents. Files
+/// linked more than once are archived once, then as hard links to the
+/// first path. Returns `out`.
+pub async fn write_tarball<W: Write + Unpin + Send + Sync>(
+    root: impl AsRef<Path>,
+    out: W,
+    options: &TarOptions,
+) -> io::Result<W> {
+    let root = root.as_ref();
+    let mut builder = Builder::new(out);
+    let meta = fs::metadata(root).await?;
+    let mut header = options.header(EntryType::Directory, &meta);
+    header.set_cksum();
+    builder.append_data(&mut header, "./", io::empty()).await?;
+    let mut linked: HashMap<(u64, u64), PathBuf> = HashMap::new();
+    for path in walk(root).await? {
+        let source = root.join(&path);
+        let meta = fs::symlink_metadata(&source).await?;
+        let kind = meta.file_type();
+        if kind.is_dir() {
+            let mut header = options.header(EntryType::Directory, &meta);
+            header.set_cksum();
+            builder.append_data(&mut header, &path, io::empty()).await?;
+        } else if kind.is_symlink() {
+            let mut header = options.header(EntryType::Symlink, &meta);
+            header.set_link_name(fs::read_link(&source).await?)?;
+            header.set_cksum();
+            builder.append_data(&mut header, &path, io::empty()).await?;
+        } else if kind.is_file() {
+            if meta.nlink() > 1 {
+                if let Some(first) = linked.get(&(meta.dev(), meta.ino())) {
+                    let mut header = options.header(EntryType::Link, &meta);
+                    header.set_link_name(first)?;
+                    header.set_cksum();
+                    builder.append_data(&mut header, &path, io::empty()).await?;
+                    continue;
+                }
+                linked.insert((meta.dev(), meta.ino()), path.clone());
+            }
+            let mut header = options.header(EntryType::Regular, &meta);
+            header.set_size(meta.len());
+            header.set_cksum();
+            let file = fs::File::open(&source).await?;
+            builder.append_data(&mut header, &path, file).await?;
+        } else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("cannot archive special file {}", source.display()),
+            ));
+        }
+    }
+    builder.into_inner().await
+}
+
+/// The digests of a layer written by [`write_layer`], as `sha256:<hex>`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Layer {
+    /// The digest of the uncompressed archive, for the `diff_ids` of the
+    /// image configuration
+    pub diff_id: String,
+    /// The digest of the compressed layer, for the image manifest
+    pub digest: String,
+    /// The size of the compressed layer
+    pub size: u64,
+}
+
+/// Writes the tree `root` to `out` as a gzip compressed OCI image layer,
+/// archived like [`write_tarball`] does. With the same options, e.g.
+/// [`TarOptions::reproducible`], the same tree gives the same layer.
+/// Closes `out` once the layer is written and returns it.
+pub async fn write_layer<W: Write + Unpin + Send + Sync>(
+    root: impl AsRef<Path>,
+    out: W,
+    options: &TarOptions,
+) -> io::Result<(Layer, W)> {
+    let compressed = DigestingWriter::<sha2::Sha256, _>::new(out);
+    let tar = DigestingWriter::<sha2::Sha256, _>::new(GzipEncoder::new(compressed));
+    let (diff_id, mut gzip) = write_tarball(root, tar, options).await?.into_parts();
+    futures::AsyncWriteExt::close(&mut gzip).await?;
+    let compressed = gzip.into_inner();
+    let size = compressed.size();
+    let (digest, out) = compressed.into_parts();
+    let layer = Layer {
+        diff_id: format!("sha256:{:x}", diff_id),
+        digest: format!("sha256:{:x}", digest),
+        size,
+    };
+    Ok((layer, out))
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::deb::Tarball, std::time::Duration};
+
+    async fn tree(dir: &Path, mtime: u64) {
+        fs::create_dir_all(dir.join("usr/bin")).await.unwrap();
+        fs::create_dir_all(dir.join("etc")).await.unwrap();
+        fs::write(dir.join("usr/bin/hello"), "#!/bin/sh\n")
+            .await
+            .unwrap();
+        fs::set_permissions(
+            dir.join("usr/bin/hello"),
+            std::fs::Permissions::from_mode(0o775),
+        )
+        .await
+        .unwrap();
+        fs::hard_link(dir.join("usr/bin/hello"), dir.join("usr/bin/hi"))
+            .await
+            .unwrap();
+        fs::write(dir.join("etc/hello.conf"), "greeting=hi\n")
+            .await
+            .unwrap();
+        async_std::os::unix::fs::symlink("hello", dir.join("usr/bin/hey"))
+            .await
+            .unwrap();
+        let mtime = std::time::UNIX_EPOCH + Duration::from_secs(mtime);
+        for path in [
+            "usr/bin/hello",
+            "etc/hello.conf",
+            "etc",
+            "usr/bin",
+            "usr",
+            "",
+        ] {
+            filetime::set_file_mtime(dir.join(path), mtime.into()).unwrap();
+        }
+    }
+
+    #[async_std::test]
+    async fn test_write_tarball() {
+        let dir = tempfile::tempdir().unwrap();
+        let (one, two) = (dir.path().join("one"), dir.path().join("two"));
+        tree(Path::new(&one), 1_700_000_000).await;
+        tree(Path::new(&two), 1_800_000_000).await;
+        let options = TarOptions::reproducible()
+            .source_date_epoch(1_600_000_000)
+            .umask(0o022);
+        let tar = write_tarball(&one, vec![], &options).await.unwrap();
+        assert_eq!(tar, write_tarball(&two, vec![], &options).await.unwrap());
+
+        let mut entries = Tarball::new(&tar[..]).entries().unwrap();
+        let mut listed = vec![];
+        while let Some(entry) = entries.next().await {
+            let entry = entry.unwrap();
+            let header = entry.header();
+            assert_eq!(header.mtime().unwrap(), 1_600_000_000);
+            assert_eq!((header.uid().unwrap(), header.gid().unwrap()), (0, 0));
+            listed.push((
+                header.path().unwrap().to_string_lossy().into_owned(),
+                header.entry_type(),
+                header.mode().unwrap(),
+            ));
+        }
+        let names: Vec<_> = listed.iter().map(|(name, _, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "./",
+                "etc",
+                "etc/hello.conf",
+                "usr",
+                "usr/bin",
+                "usr/bin/hello",
+                "usr/bin/hey",
+                "usr/bin/hi",
+            ]
+        );
+        assert_eq!(listed[5].1, EntryType::Regular);
+        assert_eq!(listed[5].2, 0o755);
+        assert_eq!(listed[6].1, EntryType::Symlink);
+        assert_eq!(listed[7].1, EntryType::Link);
+
+        // without the options the own metadata is kept
+        let tar = write_tarball(&one, vec![], &TarOptions::default())
+            .await
+            .unwrap();
+        let mut entries = Tarball::new(&tar[..]).entries().unwrap();
+        let root = entries.next().await.unwrap().unwrap();
+        assert_eq!(root.header().mtime().unwrap(), 1_700_000_000);
+    }
+
+    #[async_std::test]
+    async fn test_walk() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("b")).await.unwrap();
+        for path in ["a", "b/x", "c"] {
+            fs::write(dir.path().join(path), path).await.unwrap();
+        }
+        let paths = walk(Path::new(dir.path())).await.unwrap();
+        let names: Vec<_> = paths.iter().map(|path| path.to_str().unwrap()).collect();
+        assert_eq!(names, ["a", "b", "b/x", "c"]);
+    }
+
+    #[async_std::test]
+    async fn test_write_layer() {
+        use {async_compression::futures::bufread::GzipDecoder, sha2::Digest as _};
+        let dir = tempfile::tempdir().unwrap();
+        let (one, two) = (dir.path().join("one"), dir.path().join("two"));
+        tree(Path::new(&one), 1_700_000_000).await;
+        tree(Path::new(&two), 1_800_000_000).await;
+        let options = TarOptions::reproducible().source_date_epoch(1_600_000_000);
+        let (layer, gz) = write_layer(&one, vec![], &options).await.unwrap();
+        assert_eq!(layer, write_layer(&two, vec![], &options).await.unwrap().0);
+        assert_eq!(layer.size, gz.len() as u64);
+        let hex = |data: &[u8]| format!("sha256:{}", hex::encode(sha2::Sha256::digest(data)));
+        assert_eq!(layer.digest, hex(&gz));
+        let mut tar = vec![];
+        GzipDecoder::new(&gz[..])
+            .read_to_end(&mut tar)
+            .await
+            .unwrap();
+        assert_eq!(tar, write_tarball(&one, vec![], &options).await.unwrap());
+        assert_eq!(layer.diff_id, hex(&tar));
+    }
+}
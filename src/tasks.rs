@@ -0,0 +1,124 @@
+//! tasksel tasks
+//!
+//! A task is a named group of packages, such as `ssh-server` or `standard`.
+//! Packages declare membership with the `Task` field of the Packages index;
+//! tasksel's description files (`debian-tasks.desc`) add the key packages of
+//! each task and tell how the rest of the members are selected:
+//!
+//! ```text
+//! Task: ssh-server
+//! Relevance: 2
+//! Key:
+//!  task-ssh-server
+//! Packages: task-fields
+//! ```
+//!
+//! Requirements such as `task:ssh-server` passed to
+//! [`crate::Universe::problem`] are expanded to the task members.
+
+use crate::control::{ControlFile, ParseError};
+
+/// How the members of a task besides its key packages are selected.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum TaskPackages {
+    /// Packages listing the task in their `Task` field
+    #[default]
+    TaskFields,
+    /// Packages with `Priority: standard`
+    Standard,
+    /// An explicit list of packages
+    List(Vec<String>),
+    /// No packages besides the key ones
+    Manual,
+}
+
+/// A task from a tasksel description file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Task {
+    pub name: String,
+    /// Packages that must be installable for the task to be offered
+    pub key: Vec<String>,
+    pub packages: TaskPackages,
+}
+
+impl Task {
+    /// A task whose members are the packages listing it in their `Task`
+    /// field.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            key: vec![],
+            packages: TaskPackages::TaskFields,
+        }
+    }
+    /// Parses the stanzas of a tasksel description file.
+    pub fn parse_descs(src: &str) -> Result<Vec<Task>, ParseError> {
+        ControlFile::parse(src)?
+            .stanzas()
+            .map(|stanza| {
+                let name = stanza
+                    .field("Task")
+                    .ok_or_else(|| ParseError::from("Field Task not found"))?;
+                let key = stanza.field("Key").map_or(vec![], |key| {
+                    key.split_ascii_whitespace().map(String::from).collect()
+                });
+                let packages = match stanza.field("Packages").map(|p| p.trim_start()) {
+                    None => TaskPackages::TaskFields,
+                    Some(p) => {
+                        let mut list = p.split_ascii_whitespace();
+                        match list.next() {
+                            Some("task-fields") => TaskPackages::TaskFields,
+                            Some("standard") => TaskPackages::Standard,
+                            Some("manual") => TaskPackages::Manual,
+                            Some("list") => TaskPackages::List(list.map(String::from).collect()),
+                            _ => {
+                                return Err(ParseError::from(format!(
+                                    "task {}: unsupported Packages method {:?}",
+                                    name.trim(),
+                                    p
+                                )))
+                            }
+                        }
+                    }
+                };
+                Ok(Task {
+                    name: name.trim().to_string(),
+                    key,
+                    packages,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_descs() {
+        let tasks = Task::parse_descs(
+            "Task: standard\n\
+             Relevance: 5\n\
+             Section: user\n\
+             Description: standard system utilities\n\
+             Packages: standard\n\
+             \n\
+             Task: ssh-server\n\
+             Relevance: 2\n\
+             Key:\n task-ssh-server\n\
+             Packages: task-fields\n\
+             \n\
+             Task: laptop\n\
+             Packages: list\n wpasupplicant\n powertop\n",
+        )
+        .unwrap();
+        assert_eq!(tasks[0].packages, TaskPackages::Standard);
+        assert_eq!(tasks[1].key, ["task-ssh-server"]);
+        assert_eq!(tasks[1].packages, TaskPackages::TaskFields);
+        assert_eq!(
+            tasks[2].packages,
+            TaskPackages::List(vec!["wpasupplicant".into(), "powertop".into()])
+        );
+    }
+}
@@ -0,0 +1,87 @@
+//! Fixtures shared by the tests
+
+#[cfg(feature = "io")]
+use async_tar::EntryType;
+
+/// Returns a tar archive of `files`, the entries without contents being
+/// directories.
+#[cfg(feature = "io")]
+pub(crate) async fn tarball(files: &[(&str, Option<&[u8]>)]) -> Vec<u8> {
+    let mut builder = async_tar::Builder::new(vec![]);
+    for (path, data) in files {
+        let mut header = async_tar::Header::new_gnu();
+        let data = data.unwrap_or_default();
+        header.set_entry_type(match data.is_empty() {
+            true => EntryType::Directory,
+            false => EntryType::Regular,
+        });
+        header.set_size(data.len() as u64);
+        header.set_mode(0o755);
+        header.set_uid(0);
+        header.set_gid(0);
+        header.set_mtime(0);
+        header.set_cksum();
+        builder.append_data(&mut header, path, data).await.unwrap();
+    }
+    builder.into_inner().await.unwrap()
+}
+
+/// Returns an ar archive of `members`, as laid out in .deb files.
+#[cfg(feature = "io")]
+pub(crate) fn ar(members: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut ar = b"!<arch>\n".to_vec();
+    for (name, data) in members {
+        let header = format!(
+            "{:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n",
+            name,
+            0,
+            0,
+            0,
+            100644,
+            data.len()
+        );
+        ar.extend(header.as_bytes());
+        ar.extend(*data);
+        if data.len() % 2 == 1 {
+            ar.push(b'\n');
+        }
+    }
+    ar
+}
+
+/// Returns a .deb with the control file `control`, the conffile
+/// /etc/hello.conf and the data.tar entries `files`, see [`tarball`].
+#[cfg(feature = "io")]
+pub(crate) async fn deb(control: &[u8], files: &[(&str, Option<&[u8]>)]) -> Vec<u8> {
+    let control = tarball(&[
+        ("./control", Some(control)),
+        ("./conffiles", Some(b"/etc/hello.conf\n")),
+    ])
+    .await;
+    let data = tarball(files).await;
+    ar(&[
+        ("debian-binary", b"2.0\n"),
+        ("control.tar", &control),
+        ("data.tar", &data),
+    ])
+}
+
+/// A seeded xorshift generator, for the tests checking random cases
+/// without proptest, which is not a dependency of the crate. Every run
+/// checks the same cases, so a failure reproduces as is, but the failing
+/// case is not shrunk: the assertions show it whole.
+pub(crate) struct XorShift(u64);
+
+impl XorShift {
+    /// Returns a generator starting from `seed`, which must not be 0.
+    pub(crate) fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+    /// Returns a number below `n`.
+    pub(crate) fn below(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % n as u64) as usize
+    }
+}
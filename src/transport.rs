@@ -0,0 +1,110 @@
+//! Repository transports
+//!
+//! A [`Transport`] fetches raw bytes from a repository location. It knows
+//! nothing about Debian indexes or checksums: [`crate::DebRepo`] wraps any
+//! transport and does the verification, so custom stores (S3 buckets,
+//! artifact managers, authenticated internal mirrors) only have to implement
+//! [`Transport::get`].
+
+use {
+    crate::repo::DebRepoProvider,
+    async_std::io::{self, prelude::*, Read},
+    async_trait::async_trait,
+    std::{ops::Range, pin::Pin},
+};
+
+/// A stream returned by a [`Transport`] along with its length, if known.
+pub struct TransportResponse {
+    pub reader: Pin<Box<dyn Read + Send>>,
+    pub length: Option<u64>,
+}
+
+impl TransportResponse {
+    pub fn new(reader: Pin<Box<dyn Read + Send>>, length: Option<u64>) -> Self {
+        Self { reader, length }
+    }
+}
+
+/// Defines a way to fetch files from a repository.
+#[async_trait]
+pub trait Transport: Sync + Send {
+    /// Returns a stream of the file at `path`, relative to the repository root.
+    /// If `range` is set, only that byte range of the file is returned.
+    async fn get(&self, path: &str, range: Option<Range<u64>>) -> io::Result<TransportResponse>;
+}
+
+/// Every [`DebRepoProvider`] is a transport. Ranges are served by skipping
+/// the leading bytes of the stream.
+#[async_trait]
+impl<P: DebRepoProvider + Send> Transport for P {
+    async fn get(&self, path: &str, range: Option<Range<u64>>) -> io::Result<TransportResponse> {
+        if let Some(range) = range.as_ref().filter(|range| range.end < range.start) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid range {:?} of {}", range, path),
+            ));
+        }
+        let reader = self.reader(path).await?;
+        match range {
+            None => Ok(TransportResponse::new(reader, None)),
+            Some(range) => skip_to_range(path, reader, None, range).await,
+        }
+    }
+}
+
+// Serves `range` of the whole file `path` read from `reader`, which is
+// `length` bytes long if known, by skipping its leading bytes.
+pub(crate) async fn skip_to_range(
+    path: &str,
+    mut reader: Pin<Box<dyn Read + Send>>,
+    length: Option<u64>,
+    range: Range<u64>,
+) -> io::Result<TransportResponse> {
+    let skipped = io::copy(&mut (&mut reader).take(range.start), &mut io::sink()).await?;
+    if skipped < range.start {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("range {:?} is out of bounds of {}", range, path),
+        ));
+    }
+    let length = length.map(|length| {
+        length
+            .saturating_sub(range.start)
+            .min(range.end - range.start)
+    });
+    Ok(TransportResponse::new(
+        Box::pin(reader.take(range.end - range.start)),
+        length,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Static(&'static [u8]);
+
+    #[async_trait]
+    impl DebRepoProvider for Static {
+        async fn reader(&self, _path: &str) -> io::Result<Pin<Box<dyn Read + Send>>> {
+            Ok(Box::pin(self.0))
+        }
+    }
+
+    #[async_std::test]
+    async fn test_provider_range() {
+        let provider = Static(b"data");
+        let mut buf = vec![];
+        let mut rsp = provider.get("file", Some(1..3)).await.unwrap();
+        rsp.reader.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(buf, b"at");
+        let err = provider
+            .get("file", Some(Range { start: 3, end: 1 }))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let err = provider.get("file", Some(5..6)).await.err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,235 @@
+//! dpkg triggers
+//!
+//! A package declares in its `triggers` control file the triggers it is
+//! interested in, named triggers or file paths, and the ones it activates.
+//! dpkg defers the processing of activated triggers: [`Triggers::schedule`]
+//! places it in an installation order after the packages activating them,
+//! at the end for `-noawait` activations, so that e.g. man-db and ldconfig
+//! triggers run once after the packages shipping manual pages or libraries.
+//!
+//! ```ignore
+//! let triggers = Triggers::new()
+//!     .package("man-db", scripts.triggers)
+//!     .files("hello", ["/usr/share/man/man1/hello.1.gz"]);
+//! let order: Vec<_> = universe.sort_solution(&mut solution).collect();
+//! let names: Vec<_> = order.iter().map(|id| universe.package(*id).name()).collect();
+//! for step in triggers.schedule(names) { ... }
+//! ```
+
+use {
+    crate::control::ParseError,
+    std::collections::{BTreeMap, HashMap},
+};
+
+/// A directive of the triggers control file, e.g. `interest /usr/share/icons`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Trigger {
+    /// `interest`, `activate` or one of their `-await`/`-noawait` variants
+    pub directive: String,
+    /// The trigger name or file path
+    pub name: String,
+}
+
+impl Trigger {
+    /// Parses the triggers control file, skipping comments and blank lines.
+    pub fn parse_list(src: &str) -> Result<Vec<Trigger>, ParseError> {
+        src.lines()
+            .map(|line| line.split('#').next().unwrap_or_default().trim())
+            .filter(|line| !line.is_empty())
+            .map(|line| match line.split_once(char::is_whitespace) {
+                Some((directive, name)) => Ok(Trigger {
+                    directive: directive.to_string(),
+                    name: name.trim().to_string(),
+                }),
+                None => Err(format!("invalid trigger directive {}", line).into()),
+            })
+            .collect()
+    }
+    pub fn is_interest(&self) -> bool {
+        self.directive.starts_with("interest")
+    }
+    pub fn is_activate(&self) -> bool {
+        self.directive.starts_with("activate")
+    }
+    /// Returns false for the `-noawait` directives, whose activations do not
+    /// wait for the trigger to be processed.
+    pub fn awaits(&self) -> bool {
+        !self.directive.ends_with("-noawait")
+    }
+    /// Returns true for file triggers, activated by the packages shipping
+    /// files under the path.
+    pub fn is_file(&self) -> bool {
+        self.name.starts_with('/')
+    }
+}
+
+/// A step of an installation order with trigger processing.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TriggerStep<'a> {
+    /// A package of the order, installed and configured
+    Package(&'a str),
+    /// The processing of `triggers` by the interested `package`
+    Process {
+        package: &'a str,
+        triggers: Vec<String>,
+    },
+}
+
+/// The triggers of a set of packages.
+#[derive(Clone, Debug, Default)]
+pub struct Triggers {
+    triggers: HashMap<String, Vec<Trigger>>,
+    files: HashMap<String, Vec<String>>,
+}
+
+impl Triggers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Records the triggers directives of the package `name`.
+    pub fn package(mut self, name: impl Into<String>, triggers: Vec<Trigger>) -> Self {
+        self.triggers
+            .entry(name.into())
+            .or_default()
+            .extend(triggers);
+        self
+    }
+    /// Records the files shipped by the package `name`, which activate the
+    /// file triggers they fall under.
+    pub fn files<I: IntoIterator<Item = impl Into<String>>>(
+        mut self,
+        name: impl Into<String>,
+        files: I,
+    ) -> Self {
+        let list = self.files.entry(name.into()).or_default();
+        list.extend(files.into_iter().map(|file| file.into()));
+        self
+    }
+    // Returns Some(awaits) if `package` activates `interest`.
+    fn activates(&self, package: &str, interest: &Trigger) -> Option<bool> {
+        let explicit = self
+            .triggers
+            .get(package)
+            .into_iter()
+            .flatten()
+            .filter(|t| t.is_activate() && t.name == interest.name)
+            .map(|t| t.awaits());
+        let file = interest.is_file()
+            && self.files.get(package).into_iter().flatten().any(|file| {
+                file.strip_prefix(interest.name.trim_end_matches('/'))
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            });
+        explicit
+            .chain(file.then_some(true))
+            .reduce(|a, b| a || b)
+            .map(|awaits| awaits && interest.awaits())
+    }
+    /// Interleaves the processing of the activated triggers with the
+    /// installation `order`. An awaited trigger is processed as soon as
+    /// the interested package and all the packages awaiting it are
+    /// installed; the `-noawait` ones are deferred to the end.
+    pub fn schedule<'a, I: IntoIterator<Item = &'a str>>(&self, order: I) -> Vec<TriggerStep<'a>> {
+        let order: Vec<&'a str> = order.into_iter().collect();
+        let end = order.len().saturating_sub(1);
+        // the triggers to process after the package at a position, by
+        // interested package
+        let mut after: BTreeMap<(usize, usize), Vec<String>> = BTreeMap::new();
+        for (at, package) in order.iter().enumerate() {
+            let interests = self.triggers.get(*package).into_iter().flatten();
+            for interest in interests.filter(|t| t.is_interest()) {
+                let activations: Vec<_> = order
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(pos, p)| Some((pos, self.activates(p, interest)?)))
+                    .collect();
+                if activations.is_empty() {
+                    continue;
+                }
+                let last = activations
+                    .iter()
+                    .map(|&(pos, awaits)| if awaits { pos } else { end })
+                    .max()
+                    .unwrap_or(end)
+                    .max(at);
+                after
+                    .entry((last, at))
+                    .or_default()
+                    .push(interest.name.clone());
+            }
+        }
+        let mut steps = Vec::with_capacity(order.len() + after.len());
+        let mut after = after.into_iter().peekable();
+        for (pos, package) in order.iter().enumerate() {
+            steps.push(TriggerStep::Package(package));
+            while let Some(((_, at), triggers)) = after.next_if(|((last, _), _)| *last == pos) {
+                steps.push(TriggerStep::Process {
+                    package: order[at],
+                    triggers,
+                });
+            }
+        }
+        steps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let triggers = "# icon cache\ninterest /usr/share/icons\n\nactivate-noawait ldconfig\n";
+        let triggers = Trigger::parse_list(triggers).unwrap();
+        assert_eq!(
+            triggers,
+            [
+                Trigger {
+                    directive: "interest".to_string(),
+                    name: "/usr/share/icons".to_string()
+                },
+                Trigger {
+                    directive: "activate-noawait".to_string(),
+                    name: "ldconfig".to_string()
+                },
+            ]
+        );
+        assert!(triggers[0].is_interest() && triggers[0].is_file());
+        assert!(triggers[1].is_activate() && !triggers[1].awaits());
+        assert!(Trigger::parse_list("interest").is_err());
+    }
+
+    #[test]
+    fn test_schedule() {
+        let parse = |src: &str| Trigger::parse_list(src).unwrap();
+        let triggers = Triggers::new()
+            .package("man-db", parse("interest-noawait /usr/share/man"))
+            .package("libc-bin", parse("interest ldconfig"))
+            .package("libfoo1", parse("activate ldconfig"))
+            .files("hello", ["/usr/share/man/man1/hello.1.gz"])
+            .files("other", ["/usr/share/manual"]);
+        let steps = triggers.schedule(["libc-bin", "libfoo1", "man-db", "hello", "other"]);
+        let process = |package, trigger: &str| TriggerStep::Process {
+            package,
+            triggers: vec![trigger.to_string()],
+        };
+        assert_eq!(
+            steps,
+            [
+                TriggerStep::Package("libc-bin"),
+                TriggerStep::Package("libfoo1"),
+                process("libc-bin", "ldconfig"),
+                TriggerStep::Package("man-db"),
+                TriggerStep::Package("hello"),
+                TriggerStep::Package("other"),
+                process("man-db", "/usr/share/man"),
+            ]
+        );
+        assert_eq!(
+            triggers.schedule(["man-db", "libc-bin"]),
+            [
+                TriggerStep::Package("man-db"),
+                TriggerStep::Package("libc-bin")
+            ]
+        );
+    }
+}
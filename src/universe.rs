@@ -1,12 +1,25 @@
 use {
     crate::{
+        alternatives::{AlternativeChoice, ChoiceReason},
+        arch,
+        buildinfo::Buildinfo,
         control::ParseError,
-        idmap::{id_type, HashRef, IdMap, IntoId, ToIndex, UpdateResult},
-        packages::{Package, Packages},
-        repo::{VerifyingDebReader, VerifyingReader},
-        version::{self, Constraint, Dependency, ProvidedName, Satisfies, Version},
+        diagnostic::Diagnostic,
+        error::Error,
+        filter::PackageFilter,
+        idmap::{id_type, HashRef, IdMap, IntoId, MapStats, ToIndex, UpdateResult},
+        installed::Installed,
+        ordering::{CandidateOrdering, SolveObjective},
+        packages::{Package, PackageKind, Packages, Priority},
+        phasing::Phasing,
+        plan::{Plan, PlanAction, PlanItem, PlanPhase},
+        progress::{NoProgress, Progress},
+        search::Translations,
+        srcindex::{BinarySource, SourceGroup, SourceGroups, SourcesIndex},
+        tasks::{Task, TaskPackages},
+        variant::Variant,
+        version::{self, Constraint, Dependency, ProvidedName, Restrictions, Satisfies, Version},
     },
-    async_std::io::{self, Write},
     iterator_ext::IteratorExt,
     resolvo::{
         Candidates, Dependencies, DependencyProvider, Interner, KnownDependencies, NameId,
@@ -16,9 +29,23 @@ use {
     smallvec::{smallvec, SmallVec},
     std::{
         borrow::Borrow,
+        cell::{Cell, RefCell},
+        collections::{hash_map::Entry, BTreeMap, HashMap, HashSet},
         hash::{Hash, Hasher},
-        pin::pin,
+        rc::Rc,
+        sync::Arc,
+        time::{Duration, Instant},
+    },
+};
+
+#[cfg(feature = "io")]
+use {
+    crate::{
+        digest::Digests,
+        repo::{DebRepo, VerifyingDebReader, VerifyingReader},
     },
+    async_std::io::{self, Write},
+    std::pin::pin,
 };
 
 #[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -43,12 +70,16 @@ impl ToIndex for ArchId {
         }
     }
 }
-impl Satisfies<ArchId> for ArchId {
-    fn satisfies(&self, target: &ArchId) -> bool {
+impl<'a> Satisfies<(ArchId, &IdMap<ArchId, &'a str>)> for ArchId {
+    // `target` may be a wildcard such as `linux-any`, so distinct ids are
+    // compared by their architecture tuples.
+    fn satisfies(&self, (target, archlist): &(ArchId, &IdMap<ArchId, &'a str>)) -> bool {
         match (self, target) {
             (ArchId::Any, _) => true,
             (_, ArchId::Any) => true,
-            (ArchId::Arch(this), ArchId::Arch(that)) => this == that,
+            (ArchId::Arch(this), ArchId::Arch(that)) => {
+                this == that || arch::matches(archlist[*self], archlist[*target])
+            }
         }
     }
 }
@@ -97,6 +128,8 @@ struct Solvable<'a> {
     name: NameId,
     pkgs: u32,
     package: &'a Package<'a>,
+    // the parsed Provides field
+    provides: Box<[ProvidedName<NameId, Version<&'a str>>]>,
 }
 
 impl<'a> std::fmt::Debug for Solvable<'a> {
@@ -127,6 +160,15 @@ struct UniverseIndex<'a> {
     version_sets: IdMap<VersionSetId, VersionSet<'a>>,
     version_set_unions: IdMap<VersionSetUnionId, SmallVec<[VersionSetId; 2]>>,
     required: Vec<Requirement>,
+    restrictions: RefCell<Restrictions>,
+    diagnostics: RefCell<Vec<Diagnostic>>,
+    ordering: RefCell<CandidateOrdering>,
+    objective: Cell<SolveObjective>,
+    // the unions sorted by the objective, cleared when it changes
+    sorted_unions: RefCell<HashMap<VersionSetUnionId, SmallVec<[VersionSetId; 2]>>>,
+    // the candidates of the names looked up, None for the names without any
+    candidates: RefCell<HashMap<NameId, Option<Candidates>>>,
+    candidate_stats: Cell<CandidateCacheStats>,
 }
 
 #[ouroboros::self_referencing]
@@ -138,10 +180,135 @@ struct InnerUniverse<S: AsRef<str> + 'static> {
     index: UniverseIndex<'this>,
 }
 
+/// The limits and the tunables of [`Universe::solve_with_options`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SolveOptions {
+    /// Gives up after this many unit propagation rounds
+    pub max_propagations: Option<u64>,
+    /// Gives up after this long
+    pub timeout: Option<Duration>,
+    /// The activity bump and decay of the package selection heuristic of
+    /// the solver, `(1.0, 0.95)` by default
+    pub activity: (f32, f32),
+}
+
+impl Default for SolveOptions {
+    fn default() -> Self {
+        Self {
+            max_propagations: None,
+            timeout: None,
+            activity: (1.0, 0.95),
+        }
+    }
+}
+
+/// The statistics of a solve, see [`Universe::solve_with_options`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SolveStats {
+    /// Unit propagation rounds, one after each decision or learned clause
+    pub propagations: u64,
+    /// Packages whose dependencies were looked up
+    pub dependency_queries: u64,
+    /// Names whose candidates were looked up
+    pub candidate_queries: u64,
+    /// Version sets of dependencies first seen while solving
+    pub version_sets_created: usize,
+    pub elapsed: Duration,
+}
+
+/// The hit statistics of the candidate lookups of a [`Universe`]. Lookups
+/// are memoized across problems, including the names no package provides.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CandidateCacheStats {
+    /// Lookups answered from the cache
+    pub hits: u64,
+    /// The hits for names without candidates
+    pub negative_hits: u64,
+    /// Lookups of names not yet in the cache
+    pub misses: u64,
+}
+
+/// The memory taken by the indexes of a [`Universe`], as counts of entries
+/// and estimates of bytes. The package indexes themselves are not included.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemoryStats {
+    pub solvables: MapStats,
+    pub names: MapStats,
+    pub archs: MapStats,
+    pub version_sets: MapStats,
+    pub version_set_unions: MapStats,
+    pub strings: MapStats,
+}
+
+impl MemoryStats {
+    /// Returns the sum of all the indexes.
+    pub fn total(&self) -> MapStats {
+        self.solvables
+            + self.names
+            + self.archs
+            + self.version_sets
+            + self.version_set_unions
+            + self.strings
+    }
+}
+
 impl<'a> UniverseIndex<'a> {
+    fn with_capacity(packages: usize) -> Self {
+        // names include the virtual packages and the packages only referred
+        // to by dependencies
+        Self {
+            solvables: Vec::with_capacity(packages),
+            names: IdMap::with_capacity(packages + packages / 4),
+            ..Default::default()
+        }
+    }
+    fn shrink_to_fit(&mut self) {
+        self.solvables.shrink_to_fit();
+        self.names.shrink_to_fit();
+        self.archlist.shrink_to_fit();
+        self.version_sets.shrink_to_fit();
+        self.version_set_unions.shrink_to_fit();
+        self.required.shrink_to_fit();
+    }
+    fn memory_stats(&self) -> MemoryStats {
+        let spilled = |ids: usize, capacity: usize, size: usize| {
+            if capacity > ids {
+                capacity * size
+            } else {
+                0
+            }
+        };
+        MemoryStats {
+            solvables: MapStats {
+                count: self.solvables.len(),
+                bytes: self.solvables.capacity() * std::mem::size_of::<Solvable>()
+                    + self
+                        .solvables
+                        .iter()
+                        .map(|solvable| solvable.provides.len())
+                        .sum::<usize>()
+                        * std::mem::size_of::<ProvidedName<NameId, Version<&str>>>(),
+            },
+            names: self.names.stats(|name| {
+                spilled(
+                    1,
+                    name.packages.capacity(),
+                    std::mem::size_of::<SolvableId>(),
+                ) + name.required.capacity() * std::mem::size_of::<SolvableId>()
+            }),
+            archs: self.archlist.stats(|_| 0),
+            version_sets: self.version_sets.stats(|_| 0),
+            version_set_unions: self
+                .version_set_unions
+                .stats(|set| spilled(2, set.capacity(), std::mem::size_of::<VersionSetId>())),
+            strings: MapStats::default(),
+        }
+    }
     fn get_arch_id(&self, arch: &'a str) -> ArchId {
-        if arch.eq_ignore_ascii_case("all") {
+        if arch.eq_ignore_ascii_case("all") || arch.eq_ignore_ascii_case("any") {
             ArchId::Any
+        } else if arch.eq_ignore_ascii_case("native") {
+            self.arch
         } else {
             self.archlist.get_or_insert(arch).into()
         }
@@ -236,17 +403,52 @@ impl<'a> UniverseIndex<'a> {
                     id
                 }
             };
+        let provides = package
+            .provides()
+            .map(|pv| {
+                let pv = pv?;
+                let name = self
+                    .insert_or_update_name(pv.name(), Some((solvable_id, false)))
+                    .unwrap();
+                Ok(pv.translate(|_| name, |version| version.clone()))
+            })
+            .collect::<Result<_, ParseError>>()?;
         self.solvables.push(Solvable {
             pkgs,
             arch,
             name,
             package,
+            provides,
         });
-        for pv in package.provides() {
-            self.insert_or_update_name(pv?.name(), Some((solvable_id, false)));
-        }
         Ok(())
     }
+    // Whether `sid` satisfies `vs`, or None if it is never a candidate for it.
+    fn matches(&self, vs: &VersionSet<'a>, sid: SolvableId) -> Option<bool> {
+        let solvable = &self.solvables[sid.to_index()];
+        // always exclude self-referencing dependencies and the dependencies
+        // with not suitable arch
+        if Some(sid) == vs.selfref || !solvable.arch.satisfies(&(vs.arch, &self.archlist)) {
+            None
+        } else {
+            Some(
+                (solvable.name == vs.name && (solvable.package.version().satisfies(&vs.range)))
+                    || solvable
+                        .provides
+                        .iter()
+                        .any(|pv| *pv.name() == vs.name && pv.satisfies(&vs.range)),
+            )
+        }
+    }
+    // Records an error in `field` of `package` that was skipped over.
+    fn report(&self, package: &Package<'_>, field: &str, err: impl std::fmt::Display) {
+        let diagnostic = Diagnostic::new(err.to_string())
+            .package(package.full_name().to_string())
+            .field(field);
+        let mut diagnostics = self.diagnostics.borrow_mut();
+        if !diagnostics.contains(&diagnostic) {
+            diagnostics.push(diagnostic);
+        }
+    }
     fn add_single_package_dependency(
         &self,
         id: SolvableId,
@@ -275,10 +477,11 @@ impl<'a> UniverseIndex<'a> {
         strings: &'a IdMap<StringId, Box<str>>,
     ) -> Dependencies {
         let pkg = &self.solvables[solvable.to_index()];
+        let restrictions = self.restrictions.borrow();
         let requirements = match pkg
             .package
-            .pre_depends()
-            .chain(pkg.package.depends())
+            .pre_depends_in(&restrictions)
+            .chain(pkg.package.depends_in(&restrictions))
             .and_then(|dep| match dep {
                 Dependency::Single(dep) => Ok(Requirement::Single(
                     self.add_single_package_dependency(solvable, dep),
@@ -295,6 +498,16 @@ impl<'a> UniverseIndex<'a> {
         {
             Ok(reqs) => reqs,
             Err(err) => {
+                let pre_depends = pkg
+                    .package
+                    .pre_depends_in(&restrictions)
+                    .any(|d| d.is_err());
+                let field = if pre_depends {
+                    "Pre-Depends"
+                } else {
+                    "Depends"
+                };
+                self.report(pkg.package, field, &err);
                 return Dependencies::Unknown(
                     strings
                         .intern(format!(
@@ -303,18 +516,24 @@ impl<'a> UniverseIndex<'a> {
                             err
                         ))
                         .as_id(),
-                )
+                );
             }
         };
         let constrains = match pkg
             .package
-            .conflicts()
-            .chain(pkg.package.breaks())
+            .conflicts_in(&restrictions)
+            .chain(pkg.package.breaks_in(&restrictions))
             .and_then(|dep| Ok(self.add_single_package_dependency(solvable, dep)))
             .collect::<Result<Vec<_>, ParseError>>()
         {
             Ok(reqs) => reqs,
             Err(err) => {
+                let conflicts = pkg.package.conflicts_in(&restrictions).any(|d| d.is_err());
+                self.report(
+                    pkg.package,
+                    if conflicts { "Conflicts" } else { "Breaks" },
+                    &err,
+                );
                 return Dependencies::Unknown(
                     strings
                         .intern(format!(
@@ -323,7 +542,7 @@ impl<'a> UniverseIndex<'a> {
                             err
                         ))
                         .as_id(),
-                )
+                );
             }
         };
         Dependencies::Known(KnownDependencies {
@@ -333,8 +552,244 @@ impl<'a> UniverseIndex<'a> {
     }
 }
 
+// A resolvo 0.8 solver cannot be reused: `Solver::solve` drops the clauses
+// of the previous problem but keeps the record of the packages it added
+// clauses for, so a second problem misses their dependencies. Each problem
+// thus gets a new solver over the shared universe, and the universe itself
+// is what persists between problems. The solver steps are counted
+// for the progress reporting. The problems of a batch share a cache of the
+// solver queries.
+struct Provider<S: AsRef<str> + 'static>(
+    Rc<InnerUniverse<S>>,
+    Arc<dyn Progress>,
+    SolveState,
+    Option<Rc<BatchCache>>,
+);
+
+// The counters and the limits of the current solve.
+#[derive(Default)]
+struct SolveState {
+    options: SolveOptions,
+    deadline: Option<Instant>,
+    dependencies: Cell<u64>,
+    candidates: Cell<u64>,
+    // resolvo checks for cancellation before each unit propagation round
+    // and each query of the provider
+    checks: Cell<u64>,
+}
+
+impl SolveState {
+    fn propagations(&self) -> u64 {
+        self.checks
+            .get()
+            .saturating_sub(self.dependencies.get() + self.candidates.get())
+    }
+}
+
+// The dependencies and the filtered candidates of the problems solved by
+// `Universe::solve_many`. Both depend on the universe only, as long as the
+// build profiles do not change during the batch.
+#[derive(Default)]
+struct BatchCache {
+    dependencies: RefCell<HashMap<SolvableId, Dependencies>>,
+    filtered: RefCell<HashMap<(VersionSetId, bool), Vec<SolvableId>>>,
+}
+
+impl<S: AsRef<str> + 'static> std::ops::Deref for Provider<S> {
+    type Target = InnerUniverse<S>;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// The packages of a set of indexes, indexed for solving.
+///
+/// A universe can be moved to another thread but not shared between
+/// threads, as even its queries update its indexes. Services solving
+/// concurrent requests over one universe keep it behind a `Mutex`.
 pub struct Universe<S: AsRef<str> + 'static> {
-    inner: resolvo::Solver<InnerUniverse<S>>,
+    inner: resolvo::Solver<Provider<S>>,
+    kind: PackageKind,
+    tasks: Vec<Task>,
+    installed: Installed,
+    sources: Vec<SourcesIndex>,
+    translations: Vec<Translations>,
+    force_remove_essential: bool,
+}
+
+// SAFETY: a universe is not Send automatically because of
+// - the `Rc` shared by the universe and the provider of its solver, and the
+//   `Rc` of the batch cache shared by the providers of a batch,
+// - the `Rc` events resolvo keeps for the requests in flight while solving.
+// All of them point to data the universe owns and none is handed out: the
+// providers are replaced, never cloned out, and the solver drops its events
+// before `solve` returns. So moving the universe to another thread moves
+// every owner of this data along with it.
+//
+// It is not Sync: the lookups and the solving mutate it through `Cell`,
+// `RefCell` and the `UnsafeCell`s of IdMap.
+unsafe impl<S: AsRef<str> + 'static> Send for Universe<S> where Packages<S>: Send {}
+
+// The impl above only vouches for the solver. The other fields must be Send
+// on their own, so that adding one that is not fails to build.
+const _: fn() = || {
+    fn assert_send<T: Send>() {}
+    assert_send::<PackageKind>();
+    assert_send::<Vec<Task>>();
+    assert_send::<Installed>();
+    assert_send::<Vec<SourcesIndex>>();
+    assert_send::<Vec<Translations>>();
+    assert_send::<bool>();
+};
+
+// `task:<name>` requirements look like architecture qualified names; task
+// names never parse as architectures.
+fn task_name<A, N, V>(dep: &Constraint<Option<A>, N, V>) -> Option<&str>
+where
+    A: AsRef<str>,
+    N: AsRef<str>,
+{
+    match dep.arch() {
+        Some(task) if dep.name().as_ref() == "task" => {
+            let task = task.as_ref();
+            let is_arch = ["any", "all", "native"].contains(&task)
+                || arch::ArchTuple::from_wildcard(task).is_some();
+            (!is_arch).then_some(task)
+        }
+        _ => None,
+    }
+}
+
+/// Builds a [`Universe`], allocating its indexes up front for the number
+/// of packages expected.
+///
+/// ```ignore
+/// let universe = UniverseBuilder::new("amd64")
+///     .packages(indexes)
+///     .capacity(70_000)
+///     .build()?;
+/// ```
+pub struct UniverseBuilder<S: AsRef<str> + 'static> {
+    arch: String,
+    packages: Vec<Packages<S>>,
+    progress: Arc<dyn Progress>,
+    capacity: Option<usize>,
+}
+
+impl<S: AsRef<str> + 'static> UniverseBuilder<S> {
+    pub fn new(arch: impl AsRef<str>) -> Self {
+        Self {
+            arch: arch.as_ref().to_string(),
+            packages: vec![],
+            progress: Arc::new(NoProgress),
+            capacity: None,
+        }
+    }
+    /// Adds package indexes to the universe.
+    pub fn packages(mut self, from: impl IntoIterator<Item = Packages<S>>) -> Self {
+        self.packages.extend(from);
+        self
+    }
+    /// Sets the progress reporting of the indexes loaded, and later the
+    /// solver steps.
+    pub fn progress(mut self, progress: Arc<dyn Progress>) -> Self {
+        self.progress = progress;
+        self
+    }
+    /// Sets the number of packages to allocate the indexes for. It is
+    /// counted from the indexes by default; 0 grows the indexes as needed.
+    pub fn capacity(mut self, packages: usize) -> Self {
+        self.capacity = Some(packages);
+        self
+    }
+    pub fn build(self) -> Result<Universe<S>, ParseError> {
+        let packages = self.packages;
+        let progress = self.progress;
+        // the indexes are parsed already, so counting their packages is cheap
+        let capacity = self
+            .capacity
+            .unwrap_or_else(|| packages.iter().map(|p| p.len()).sum());
+        let span = tracing::debug_span!(
+            "universe",
+            indexes = packages.len(),
+            packages = tracing::field::Empty
+        )
+        .entered();
+        // debs and udebs are resolved separately
+        let kind = packages.first().map_or(PackageKind::Deb, |p| p.kind());
+        if packages.iter().any(|p| p.kind() != kind) {
+            return Err(ParseError::from(
+                "cannot mix deb and udeb indexes in a universe",
+            ));
+        }
+        let universe = Universe {
+            kind,
+            inner: resolvo::Solver::new(Provider(
+                Rc::new(
+                    InnerUniverseTryBuilder {
+                        packages,
+                        interned: IdMap::from([self.arch.as_str()]),
+                        index_builder:
+                            |list: &'_ Vec<Packages<S>>,
+                             interned: &'_ IdMap<StringId, Box<str>>|
+                             -> Result<UniverseIndex<'_>, ParseError> {
+                                let mut index = UniverseIndex::with_capacity(capacity);
+                                index.archlist.get_or_insert("any"); // == ArchId::Any
+                                index.arch = index.archlist.get_or_insert(&interned[StringId(0)]);
+                                index.restrictions =
+                                    RefCell::new(Restrictions::new().arch(&*interned[StringId(0)]));
+                                let mut required = Vec::<NameId>::new();
+                                for (num, pkgs) in list.iter().enumerate() {
+                                    for package in pkgs.packages() {
+                                        index.add_package(num as u32, &mut required, package)?;
+                                    }
+                                    progress.on_solve_step(num + 1, Some(list.len()));
+                                }
+                                for name in required {
+                                    let pkgs: SmallVec<[VersionSetId; 2]> = index.names[name]
+                                        .required
+                                        .iter()
+                                        .map(|sid| {
+                                            let solvable = &index.solvables[sid.to_index()];
+                                            index.version_sets.get_or_insert(VersionSet {
+                                                name,
+                                                arch: solvable.arch,
+                                                selfref: None,
+                                                range: index.solvables[sid.to_index()]
+                                                    .full_name()
+                                                    .version()
+                                                    .into(),
+                                            })
+                                        })
+                                        .collect();
+                                    index.required.push(match pkgs.len() {
+                                        1 => Requirement::Single(pkgs[0]),
+                                        _ => Requirement::Union(
+                                            index.version_set_unions.get_or_insert(pkgs),
+                                        ),
+                                    })
+                                }
+                                index.shrink_to_fit();
+                                interned.shrink_to_fit();
+                                Ok(index)
+                            },
+                    }
+                    .try_build()?,
+                ),
+                progress,
+                SolveState::default(),
+                None,
+            )),
+            tasks: vec![],
+            installed: Installed::default(),
+            sources: vec![],
+            translations: vec![],
+            force_remove_essential: false,
+        };
+        let count = universe.inner.provider().with_index(|i| i.solvables.len());
+        span.record("packages", count);
+        Ok(universe)
+    }
 }
 
 impl<S: AsRef<str> + 'static> Universe<S> {
@@ -342,58 +797,170 @@ impl<S: AsRef<str> + 'static> Universe<S> {
         arch: impl AsRef<str>,
         from: impl IntoIterator<Item = Packages<S>>,
     ) -> Result<Self, ParseError> {
-        Ok(Self {
-            inner: resolvo::Solver::new(
-                InnerUniverseTryBuilder {
-                    packages: from.into_iter().collect(),
-                    interned: IdMap::from([arch.as_ref()]),
-                    index_builder: |list: &'_ Vec<Packages<S>>,
-                                    interned: &'_ IdMap<StringId, Box<str>>|
-                     -> Result<UniverseIndex<'_>, ParseError> {
-                        let mut index = UniverseIndex::default();
-                        index.archlist.get_or_insert("any"); // == ArchId::Any
-                        index.arch = index.archlist.get_or_insert(&interned[StringId(0)]);
-                        let mut required = Vec::<NameId>::new();
-                        for (num, pkgs) in list.iter().enumerate() {
-                            for package in pkgs.packages() {
-                                index.add_package(num as u32, &mut required, package)?;
-                            }
-                        }
-                        for name in required {
-                            let pkgs: SmallVec<[VersionSetId; 2]> = index.names[name]
-                                .required
-                                .iter()
-                                .map(|sid| {
-                                    let solvable = &index.solvables[sid.to_index()];
-                                    index.version_sets.get_or_insert(VersionSet {
-                                        name,
-                                        arch: solvable.arch,
-                                        selfref: None,
-                                        range: index.solvables[sid.to_index()]
-                                            .full_name()
-                                            .version()
-                                            .into(),
-                                    })
-                                })
-                                .collect();
-                            index.required.push(match pkgs.len() {
-                                1 => Requirement::Single(pkgs[0]),
-                                _ => {
-                                    Requirement::Union(index.version_set_unions.get_or_insert(pkgs))
-                                }
-                            })
-                        }
-                        Ok(index)
-                    },
-                }
-                .try_build()?,
-            ),
+        Self::new_with_progress(arch, from, Arc::new(NoProgress))
+    }
+    /// Creates the universe reporting the indexes loaded, and later the
+    /// solver steps, to `progress`.
+    pub fn new_with_progress(
+        arch: impl AsRef<str>,
+        from: impl IntoIterator<Item = Packages<S>>,
+        progress: Arc<dyn Progress>,
+    ) -> Result<Self, ParseError> {
+        UniverseBuilder::new(arch)
+            .packages(from)
+            .progress(progress)
+            .build()
+    }
+    /// Sets the active build profiles, e.g. `nocheck`. Dependencies with
+    /// `<profile>` restrictions are included or dropped accordingly. Must be
+    /// called before solving.
+    pub fn set_build_profiles<I, P>(&mut self, profiles: I)
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<String>,
+    {
+        self.inner
+            .provider()
+            .with_index(|i| i.restrictions.borrow_mut().set_profiles(profiles))
+    }
+    /// Sets the order in which the packages satisfying a requirement are
+    /// tried. Must be called before solving.
+    pub fn set_candidate_ordering(&mut self, ordering: CandidateOrdering) {
+        self.inner
+            .provider()
+            .with_index(|i| *i.ordering.borrow_mut() = ordering)
+    }
+    /// Sets the size the solver prefers small candidates by, ahead of the
+    /// candidate ordering.
+    pub fn set_objective(&mut self, objective: SolveObjective) {
+        self.inner.provider().with_index(|i| {
+            i.objective.set(objective);
+            i.sorted_unions.borrow_mut().clear();
         })
     }
-    pub fn problem<A, N, V, Id, Ic>(
+    /// Returns the problems found in package relations while solving, such
+    /// as dependencies that failed to parse, and clears them.
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        self.inner
+            .provider()
+            .with_index(|i| std::mem::take(&mut *i.diagnostics.borrow_mut()))
+    }
+    /// Returns the number of entries and the memory taken by the indexes
+    /// of the universe.
+    pub fn memory_stats(&self) -> MemoryStats {
+        let provider = self.inner.provider();
+        MemoryStats {
+            strings: provider.with_interned(|strings| strings.stats(|s| s.len())),
+            ..provider.with_index(|index| index.memory_stats())
+        }
+    }
+    /// Returns the hit statistics of the candidate lookups so far.
+    pub fn candidate_cache_stats(&self) -> CandidateCacheStats {
+        self.inner
+            .provider()
+            .with_index(|i| i.candidate_stats.get())
+    }
+    /// The kind of the packages in the universe.
+    pub fn kind(&self) -> PackageKind {
+        self.kind
+    }
+    /// Sets the tasksel task descriptions used to expand `task:<name>`
+    /// requirements. Tasks without a description select the packages
+    /// listing them in their `Task` field.
+    pub fn set_tasks(&mut self, tasks: impl IntoIterator<Item = Task>) {
+        self.tasks = tasks.into_iter().collect();
+    }
+    /// Sets the installed packages. Problems built afterwards keep the held
+    /// packages at their installed version and do not downgrade the other
+    /// ones unless allowed.
+    pub fn set_installed(&mut self, installed: Installed) {
+        self.installed = installed;
+    }
+    pub fn installed(&self) -> &Installed {
+        &self.installed
+    }
+    /// Allows [`Universe::plan`] to remove essential and protected
+    /// packages, as `apt-get --allow-remove-essential` does.
+    pub fn set_force_remove_essential(&mut self, force: bool) {
+        self.force_remove_essential = force;
+    }
+    /// Sets the Sources indexes [`Universe::source_of`] looks the source
+    /// packages up in.
+    pub fn set_sources(&mut self, sources: impl IntoIterator<Item = SourcesIndex>) {
+        self.sources = sources.into_iter().collect();
+    }
+    /// Sets the `Translation-*` indexes [`Universe::search`] looks the
+    /// package descriptions up in.
+    pub fn set_translations(&mut self, translations: impl IntoIterator<Item = Translations>) {
+        self.translations = translations.into_iter().collect();
+    }
+    pub(crate) fn translations(&self) -> &[Translations] {
+        &self.translations
+    }
+    /// Returns the source package of `solvable`, from its `Source` field or
+    /// its own name and version, along with the source package itself if
+    /// the Sources indexes list it.
+    pub fn source_of(&self, solvable: SolvableId) -> BinarySource<'_> {
+        let package = self.package(solvable);
+        let (name, version) = (package.source(), package.source_version());
+        BinarySource {
+            name,
+            package: self
+                .sources
+                .iter()
+                .find_map(|index| index.get(name, &version)),
+            version,
+        }
+    }
+    /// Returns the names of the packages making up `task`. A task neither
+    /// described nor named by the `Task` field of a package, or one without
+    /// any package in the universe, is an error.
+    pub fn task_members(&self, task: &str) -> Result<Vec<&str>, Error> {
+        let desc = self.tasks.iter().find(|t| t.name == task);
+        let mut members: Vec<&str> =
+            desc.map_or(vec![], |d| d.key.iter().map(|k| k.as_str()).collect());
+        match desc.map_or(&TaskPackages::TaskFields, |d| &d.packages) {
+            TaskPackages::TaskFields => members.extend(
+                self.packages()
+                    .filter(|p| p.tasks().any(|t| t == task))
+                    .map(|p| p.name()),
+            ),
+            TaskPackages::Standard => members.extend(
+                self.packages()
+                    .filter(|p| p.priority() == Priority::Standard)
+                    .map(|p| p.name()),
+            ),
+            TaskPackages::List(list) => {
+                // Like tasksel, skip the packages missing from the archive
+                let known: HashSet<&str> = self.packages().map(|p| p.name()).collect();
+                members.extend(
+                    list.iter()
+                        .map(|p| p.as_str())
+                        .filter(|p| known.contains(p)),
+                )
+            }
+            TaskPackages::Manual => {}
+        }
+        members.sort_unstable();
+        members.dedup();
+        match (desc, members.is_empty()) {
+            (_, false) => Ok(members),
+            (None, true) => Err(Error::Solver(format!("unknown task {}", task))),
+            (Some(_), true) => Err(Error::Solver(format!("task {} has no packages", task))),
+        }
+    }
+    /// Builds a problem installing `requirements`. `constraints` restrict
+    /// the versions of the packages that get selected; the packages matching
+    /// `forbid` are never selected, e.g. `openssl1.1` or `libfoo (<< 2.0)`.
+    /// Forbidden packages without an architecture qualifier are forbidden
+    /// on all architectures. A `task:<name>` requirement stands for the
+    /// members of the task, see [`Universe::task_members`]; an unknown or
+    /// empty task cannot be satisfied.
+    pub fn problem<A, N, V, Id, Ic, If>(
         &self,
         requirements: Id,
         constraints: Ic,
+        forbid: If,
     ) -> resolvo::Problem<std::iter::Empty<SolvableId>>
     where
         A: AsRef<str>,
@@ -401,39 +968,613 @@ impl<S: AsRef<str> + 'static> Universe<S> {
         V: AsRef<str>,
         Id: IntoIterator<Item = Dependency<Option<A>, N, Version<V>>>,
         Ic: IntoIterator<Item = Constraint<Option<A>, N, Version<V>>>,
+        If: IntoIterator<Item = Constraint<Option<A>, N, Version<V>>>,
     {
-        resolvo::Problem::new()
-            .requirements(
-                requirements
-                    .into_iter()
-                    .map(|d| match d {
-                        Dependency::Single(vs) => {
-                            Requirement::Single(self.inner.provider().intern_single_dependency(vs))
+        let provider = self.inner.provider();
+        let mut reqs = vec![];
+        for dep in requirements {
+            match dep {
+                Dependency::Single(vs) => {
+                    match task_name(&vs).map(|task| self.task_members(task)) {
+                        Some(Ok(members)) => reqs.extend(members.into_iter().map(|name| {
+                            Requirement::Single(provider.intern_single_dependency(Constraint::new(
+                                None::<&str>,
+                                name,
+                                version::VersionSet::<Version<&str>>::Any,
+                            )))
+                        })),
+                        // an unknown or empty task is kept as is, unsatisfiable
+                        Some(Err(err)) => {
+                            tracing::warn!("{}", err);
+                            reqs.push(Requirement::Single(provider.intern_single_dependency(vs)))
                         }
-                        Dependency::Union(vsu) => {
-                            Requirement::Union(self.inner.provider().intern_union_dependency(vsu))
+                        None => {
+                            reqs.push(Requirement::Single(provider.intern_single_dependency(vs)))
                         }
-                    })
-                    .chain(
-                        self.inner
-                            .provider()
-                            .with_index(|i| i.required.iter())
-                            .map(|v: &Requirement| v.clone()),
-                    )
-                    .collect(),
-            )
-            .constraints(
-                constraints
-                    .into_iter()
-                    .map(|dep| self.inner.provider().intern_single_dependency(dep))
-                    .collect(),
-            )
+                    }
+                }
+                Dependency::Union(vsu) => {
+                    reqs.push(Requirement::Union(provider.intern_union_dependency(vsu)))
+                }
+            }
+        }
+        let reqs = self.with_required(reqs);
+        let mut constraints: Vec<_> = constraints
+            .into_iter()
+            .map(|dep| provider.intern_single_dependency(dep))
+            .collect();
+        for dep in forbid {
+            let dep = dep.translate(
+                |a| Some(a.as_ref().map_or("any", |a| a.as_ref()).to_string()),
+                |n| n.as_ref().to_string(),
+                |v| v.translate(|v| v.as_ref().to_string()),
+            );
+            constraints.push(provider.intern_single_dependency(dep.complement()));
+        }
+        let phasing = self.installed.phasing_policy();
+        let phased: Vec<_> = match phasing {
+            Phasing::Always => vec![],
+            _ => self.packages().filter(|p| !phasing.includes(p)).collect(),
+        };
+        for (name, version) in self.installed.versions() {
+            let version = Version::from(version.as_str());
+            // updates not phased in yet are held back below their version
+            let phased_out = phased
+                .iter()
+                .filter(|p| p.name() == name && p.version() > version)
+                .map(|p| p.version())
+                .min();
+            if let Some(phased_out) = phased_out {
+                constraints.push(provider.intern_single_dependency(Constraint::new(
+                    Some("any"),
+                    name.as_str(),
+                    version::VersionSet::StrictlyEarlierThan(phased_out),
+                )));
+            }
+            let range = if self.installed.is_held(name) {
+                version::VersionSet::Exactly(version)
+            } else if !self.installed.may_downgrade(name) {
+                version::VersionSet::LaterOrEqualThan(version)
+            } else {
+                continue;
+            };
+            // all architectures of a Multi-Arch: same package are covered
+            constraints.push(provider.intern_single_dependency(Constraint::new(
+                Some("any"),
+                name.as_str(),
+                range,
+            )));
+        }
+        resolvo::Problem::new()
+            .requirements(reqs)
+            .constraints(constraints)
     }
+    /// Builds a problem requiring the exact package versions recorded in
+    /// the Installed-Build-Depends field of `buildinfo`.
+    pub fn buildinfo_problem(
+        &self,
+        buildinfo: &Buildinfo,
+    ) -> Result<resolvo::Problem<std::iter::Empty<SolvableId>>, ParseError> {
+        let deps = buildinfo
+            .installed_build_depends()
+            .collect::<Result<Vec<_>, ParseError>>()?;
+        Ok(self.problem(deps, vec![], vec![]))
+    }
+    /// Solves `problem`. The solution, and its order, depend on the indexes
+    /// of the universe, their order and the problem only, so that the same
+    /// inputs give the same solution across runs and platforms.
     pub fn solve(
         &mut self,
         problem: resolvo::Problem<std::iter::Empty<SolvableId>>,
     ) -> Result<Vec<SolvableId>, UnsolvableOrCancelled> {
-        self.inner.solve(problem)
+        self.solve_in(problem, SolveState::default(), None)
+    }
+    /// Solves `problem` within the limits of `options`, returning the
+    /// statistics of the solve along with the result. A solve reaching a
+    /// limit is cancelled; [`Universe::solver_error`] names the limit.
+    pub fn solve_with_options(
+        &mut self,
+        problem: resolvo::Problem<std::iter::Empty<SolvableId>>,
+        options: SolveOptions,
+    ) -> (Result<Vec<SolvableId>, UnsolvableOrCancelled>, SolveStats) {
+        let started = Instant::now();
+        let state = SolveState {
+            options,
+            deadline: options.timeout.map(|timeout| started + timeout),
+            ..SolveState::default()
+        };
+        let version_sets = self.inner.provider().with_index(|i| i.version_sets.len());
+        let solution = self.solve_in(problem, state, None);
+        let provider = self.inner.provider();
+        let state = &provider.2;
+        let stats = SolveStats {
+            propagations: state.propagations(),
+            dependency_queries: state.dependencies.get(),
+            candidate_queries: state.candidates.get(),
+            version_sets_created: provider.with_index(|i| i.version_sets.len()) - version_sets,
+            elapsed: started.elapsed(),
+        };
+        (solution, stats)
+    }
+    /// Solves a batch of related problems, e.g. the installability of many
+    /// packages. The dependencies and the candidates the solver computes for
+    /// one problem are reused for the next ones, which makes the batch
+    /// faster than solving the problems one by one. The failures are
+    /// explained as by [`Universe::solver_error`].
+    pub fn solve_many<I>(&mut self, problems: I) -> Vec<Result<Vec<SolvableId>, Error>>
+    where
+        I: IntoIterator<Item = resolvo::Problem<std::iter::Empty<SolvableId>>>,
+    {
+        let cache = Rc::new(BatchCache::default());
+        problems
+            .into_iter()
+            .map(|problem| {
+                let solution =
+                    self.solve_in(problem, SolveState::default(), Some(Rc::clone(&cache)));
+                solution.map_err(|err| self.solver_error(err))
+            })
+            .collect()
+    }
+    fn solve_in(
+        &mut self,
+        problem: resolvo::Problem<std::iter::Empty<SolvableId>>,
+        state: SolveState,
+        cache: Option<Rc<BatchCache>>,
+    ) -> Result<Vec<SolvableId>, UnsolvableOrCancelled> {
+        let provider = self.inner.provider();
+        let (add, decay) = state.options.activity;
+        self.inner = resolvo::Solver::new(Provider(
+            Rc::clone(&provider.0),
+            Arc::clone(&provider.1),
+            state,
+            cache,
+        ))
+        .with_activity_params(add, decay);
+        let span = tracing::debug_span!(
+            "solve",
+            packages = self.inner.provider().with_index(|i| i.solvables.len()),
+            solution = tracing::field::Empty
+        )
+        .entered();
+        let solution = self.inner.solve(problem);
+        match &solution {
+            Ok(solution) => span.record("solution", solution.len()),
+            Err(_) => span.record("solution", "none"),
+        };
+        solution
+    }
+    /// Checks that every package can be installed, along with the essential
+    /// and required packages, as edos-debcheck does. Returns the packages
+    /// that cannot be installed with the [`Error::Solver`] explaining why.
+    /// The installed packages are not taken into account.
+    ///
+    /// The packages of a solution found are not checked again, and the
+    /// problems share the queries of the solver, as [`Universe::solve_many`]
+    /// does. Other sub-solutions are not reused: each remaining package is
+    /// solved from scratch, so a failing dependency shared by many packages
+    /// is explored once for each of them.
+    pub fn check_installable_all(&mut self) -> Vec<(SolvableId, Error)> {
+        let count = self.inner.provider().with_index(|i| i.solvables.len());
+        // every package of a solution is installable, no need to check it
+        let mut installable = vec![false; count];
+        let mut uninstallable = vec![];
+        let cache = Rc::new(BatchCache::default());
+        for id in 0..count {
+            if installable[id] {
+                continue;
+            }
+            let id: SolvableId = id.into_id();
+            let requirements = self.with_required(vec![self.inner.provider().exact(id)]);
+            let problem = resolvo::Problem::new().requirements(requirements);
+            match self.solve_in(problem, SolveState::default(), Some(Rc::clone(&cache))) {
+                Ok(solution) => solution
+                    .into_iter()
+                    .for_each(|id| installable[id.to_index()] = true),
+                Err(err) => uninstallable.push((id, self.solver_error(err))),
+            }
+        }
+        uninstallable
+    }
+    /// Checks that the packages matching `relations`, e.g. `["exim4",
+    /// "postfix (>= 3.7)"]`, can be installed together. Returns a solution,
+    /// or the [`Error::Solver`] explaining the conflict.
+    pub fn check_coinstallable(&mut self, relations: &[&str]) -> Result<Vec<SolvableId>, Error> {
+        let requirements = relations
+            .iter()
+            .map(|rel| Dependency::try_from(*rel))
+            .collect::<Result<Vec<_>, ParseError>>()?;
+        let problem = self.problem(requirements, vec![], vec![]);
+        self.solve(problem).map_err(|err| self.solver_error(err))
+    }
+    /// Reports the alternative selected for each `a | b` dependency of the
+    /// packages in `solution`, and why.
+    pub fn alternatives_report(&self, solution: &[SolvableId]) -> Vec<AlternativeChoice> {
+        let provider = self.inner.provider();
+        let known = |id: SolvableId| match provider.0.get_dependencies(id) {
+            Dependencies::Known(deps) => Some(deps),
+            Dependencies::Unknown(_) => None,
+        };
+        let deps: HashMap<SolvableId, KnownDependencies> = solution
+            .iter()
+            .filter_map(|id| Some((*id, known(*id)?)))
+            .collect();
+        let mut report = vec![];
+        for &package in solution {
+            let unions = deps.get(&package).into_iter().flat_map(|d| &d.requirements);
+            for union in unions.filter_map(|req| match req {
+                Requirement::Union(union) => Some(*union),
+                Requirement::Single(_) => None,
+            }) {
+                let sets: Vec<VersionSetId> = provider.version_sets_in_union(union).collect();
+                let dependency = sets
+                    .iter()
+                    .map(|vs| provider.display_version_set(*vs).to_string())
+                    .collect::<Vec<_>>()
+                    .join(" | ");
+                report.extend(provider.with_index(|i| {
+                    // whether `id` is ruled out by the constraints of `by`
+                    let excludes = |by: &KnownDependencies, id: SolvableId| {
+                        by.constrains.iter().any(|vs| {
+                            let vs = &i.version_sets[*vs];
+                            i.names[vs.name].packages.contains(&id)
+                                && i.matches(vs, id) == Some(false)
+                        })
+                    };
+                    let candidates = |vs: VersionSetId| {
+                        let vs = &i.version_sets[vs];
+                        i.names[vs.name]
+                            .packages
+                            .iter()
+                            .copied()
+                            .filter(move |id| i.matches(vs, *id) == Some(true))
+                    };
+                    let (alternative, selected) = sets.iter().enumerate().find_map(|(n, vs)| {
+                        candidates(*vs)
+                            .find(|id| deps.contains_key(id))
+                            .map(|id| (n, id))
+                    })?;
+                    let passed: Vec<_> = sets[..alternative]
+                        .iter()
+                        .flat_map(|vs| candidates(*vs))
+                        .collect();
+                    let reason = if alternative == 0 {
+                        ChoiceReason::FirstPreference
+                    } else if passed.is_empty() {
+                        ChoiceReason::OnlyInstallable
+                    } else {
+                        let passed: Vec<_> = passed.into_iter().map(|p| (p, known(p))).collect();
+                        let mut by: Vec<_> = solution
+                            .iter()
+                            .copied()
+                            .filter(|s| {
+                                passed.iter().any(|(p, p_deps)| {
+                                    i.solvables[s.to_index()].name == i.solvables[p.to_index()].name
+                                        || deps.get(s).is_some_and(|d| excludes(d, *p))
+                                        || p_deps.as_ref().is_some_and(|d| excludes(d, *s))
+                                })
+                            })
+                            .collect();
+                        by.sort();
+                        ChoiceReason::Constrained(by)
+                    };
+                    Some(AlternativeChoice {
+                        package,
+                        dependency,
+                        alternative,
+                        selected,
+                        reason,
+                    })
+                }));
+            }
+        }
+        report
+    }
+    fn with_required(&self, mut requirements: Vec<Requirement>) -> Vec<Requirement> {
+        requirements.extend(
+            self.inner
+                .provider()
+                .with_index(|i| i.required.iter())
+                .copied(),
+        );
+        requirements
+    }
+    /// Computes the changes installing `solution` makes to the installed
+    /// packages. The new packages not named in `requested` are reported as
+    /// installed to satisfy dependencies. Fails with
+    /// [`Error::EssentialRemoval`] if the plan removes essential or
+    /// protected packages, unless allowed with
+    /// [`Universe::set_force_remove_essential`].
+    pub fn plan(&self, solution: &[SolvableId], requested: &[&str]) -> Result<Plan, Error> {
+        let arch = self
+            .inner
+            .provider()
+            .with_interned(|i| i[StringId(0)].to_string());
+        let size = |p: &Package<'_>, field| {
+            p.field(field)
+                .and_then(|size| crate::parse_size(size.trim().as_bytes()).ok())
+                .unwrap_or(0)
+        };
+        // Installed-Size is in KiB
+        let installed_size = |p: &Package<'_>| p.installed_size().unwrap_or(0) as usize * 1024;
+        let mut by_name: HashMap<&str, Vec<&Package<'_>>> = HashMap::new();
+        for package in self.packages() {
+            by_name.entry(package.name()).or_default().push(package);
+        }
+        let versions = |name: &str| by_name.get(name).into_iter().flatten().copied();
+        let version_of =
+            |name: &str, version: &Version<&str>| versions(name).find(|p| p.version() == *version);
+        let mut chosen = HashMap::new();
+        let mut changed = HashMap::new();
+        let mut items = vec![];
+        for &id in solution {
+            let package = self.package(id);
+            let version = package.version();
+            chosen.insert(package.name(), version.clone());
+            let (action, replaced_size) = match self.installed.version(package.name()) {
+                None => (PlanAction::Install, 0),
+                Some(installed) => {
+                    let installed = Version::from(installed);
+                    let action = match version.cmp(&installed) {
+                        std::cmp::Ordering::Equal => PlanAction::Keep,
+                        std::cmp::Ordering::Greater => PlanAction::Upgrade(installed.to_string()),
+                        std::cmp::Ordering::Less => PlanAction::Downgrade(installed.to_string()),
+                    };
+                    let replaced = version_of(package.name(), &installed).map_or(0, installed_size);
+                    (action, replaced)
+                }
+            };
+            let name = match package.arch() {
+                a if a == arch || a == "all" => package.name().to_string(),
+                a => format!("{}:{}", package.name(), a),
+            };
+            if action != PlanAction::Keep {
+                changed.insert(id, name.clone());
+            }
+            items.push(PlanItem {
+                name,
+                version: version.to_string(),
+                action,
+                requested: requested.contains(&package.name()),
+                size: size(package, "Size"),
+                installed_size: installed_size(package),
+                replaced_size,
+            });
+        }
+        // An installed package left out of the solution is removed when a
+        // package of the solution both conflicts with and replaces it.
+        let mut removed = HashSet::new();
+        for (name, installed) in self.installed.versions() {
+            if chosen.contains_key(name.as_str()) {
+                continue;
+            }
+            let installed = Version::from(installed.as_str());
+            let takes_over = solution.iter().map(|&id| self.package(id)).find(|p| {
+                p.conflicts_with(name, &installed) && p.replaces_version(name, &installed)
+            });
+            if let Some(package) = takes_over {
+                removed.insert(name.as_str());
+                items.push(PlanItem {
+                    name: name.clone(),
+                    version: installed.to_string(),
+                    action: PlanAction::Remove(package.name().to_string()),
+                    requested: false,
+                    size: 0,
+                    installed_size: 0,
+                    replaced_size: version_of(name, &installed).map_or(0, installed_size),
+                });
+            }
+        }
+        items.sort_by(|a, b| a.name.cmp(&b.name));
+        let mut not_upgraded: Vec<String> = self
+            .installed
+            .versions()
+            .iter()
+            .filter(|(name, version)| {
+                let version = Version::from(version.as_str());
+                !removed.contains(name.as_str())
+                    && chosen.get(name.as_str()).is_none_or(|v| *v == version)
+                    && versions(name).any(|p| p.version() > version)
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        not_upgraded.sort();
+        let mut sorted = solution.to_vec();
+        let install_order = self
+            .sort_solution(&mut sorted)
+            .filter_map(|id| changed.remove(&id))
+            .collect();
+        let mut plan = Plan {
+            items,
+            not_upgraded,
+            install_order,
+            ..Plan::default()
+        };
+        self.sequence(&mut plan);
+        self.check_removals(&plan)?;
+        Ok(plan)
+    }
+    // An edge from each package of `items` to the ones of `items` it
+    // depends on, true for Pre-Depends. The dependencies are those of the
+    // versions of the items in the universe.
+    fn plan_graph(&self, items: &[&PlanItem]) -> petgraph::graphmap::DiGraphMap<usize, bool> {
+        fn base_name(item: &PlanItem) -> &str {
+            item.name.split(':').next().unwrap_or_default()
+        }
+        let mut graph = petgraph::graphmap::DiGraphMap::<usize, bool>::new();
+        for (from, item) in items.iter().enumerate() {
+            graph.add_node(from);
+            let version = Version::from(item.version.as_str());
+            let package = self
+                .packages()
+                .find(|p| p.name() == base_name(item) && p.version() == version);
+            let Some(package) = package else { continue };
+            let deps = package
+                .pre_depends()
+                .map(|dep| (dep, true))
+                .chain(package.depends().map(|dep| (dep, false)));
+            for (dep, pre) in deps.filter_map(|(dep, pre)| Some((dep.ok()?, pre))) {
+                for constraint in dep.iter() {
+                    let targets = items
+                        .iter()
+                        .enumerate()
+                        .filter(|(to, item)| *to != from && base_name(item) == *constraint.name());
+                    for (to, _) in targets {
+                        let pre = pre || graph.edge_weight(from, to).is_some_and(|pre| *pre);
+                        graph.add_edge(from, to, pre);
+                    }
+                }
+            }
+        }
+        graph
+    }
+    // Sequences the changes of `plan`. The removals are ordered so that each
+    // package is removed before the packages it depends on; within a
+    // dependency cycle the Pre-Depends are kept if possible, then the names
+    // decide. The installs are split into dpkg phases.
+    pub(crate) fn sequence(&self, plan: &mut Plan) {
+        let removed: Vec<&PlanItem> = plan
+            .items
+            .iter()
+            .filter(|item| matches!(item.action, PlanAction::Remove(_)))
+            .collect();
+        let graph = self.plan_graph(&removed);
+        let mut removal_order = vec![];
+        // the components come with the dependencies first
+        for mut component in petgraph::algo::kosaraju_scc(&graph).into_iter().rev() {
+            component.sort_by_key(|&i| &removed[i].name);
+            while !component.is_empty() {
+                let first = component
+                    .iter()
+                    .position(|&i| {
+                        !component
+                            .iter()
+                            .any(|&j| graph.edge_weight(j, i).is_some_and(|pre| *pre))
+                    })
+                    .unwrap_or(0);
+                removal_order.push(removed[component.remove(first)].name.clone());
+            }
+        }
+        let mut phases = vec![];
+        if !removal_order.is_empty() {
+            phases.push(PlanPhase::Remove(removal_order.clone()));
+        }
+        let installed: Vec<&PlanItem> = plan
+            .install_order
+            .iter()
+            .filter_map(|name| plan.items.iter().find(|item| item.name == *name))
+            .collect();
+        let graph = self.plan_graph(&installed);
+        let mut flush = |batch: &mut Vec<usize>| {
+            if !batch.is_empty() {
+                batch.sort_unstable();
+                let names: Vec<String> = batch.iter().map(|&i| installed[i].name.clone()).collect();
+                phases.push(PlanPhase::Unpack(names.clone()));
+                phases.push(PlanPhase::Configure(names));
+                batch.clear();
+            }
+        };
+        // A batch is unpacked, then configured, so that the dependency
+        // cycles within a batch are configured together; a package
+        // pre-depending on one of the batch starts the next batch.
+        let mut batch = vec![];
+        for component in petgraph::algo::kosaraju_scc(&graph) {
+            let pre_depends = component.iter().any(|&i| {
+                graph
+                    .edges(i)
+                    .any(|(_, j, pre)| *pre && !component.contains(&j) && batch.contains(&j))
+            });
+            if pre_depends {
+                flush(&mut batch);
+            }
+            batch.extend(component);
+        }
+        flush(&mut batch);
+        plan.removal_order = removal_order;
+        plan.phases = phases;
+    }
+    // Fails with the essential and protected packages `plan` removes, unless
+    // forced: those marked so in the installed packages or whose installed
+    // version is marked so in the universe.
+    pub(crate) fn check_removals(&self, plan: &Plan) -> Result<(), Error> {
+        if self.force_remove_essential {
+            return Ok(());
+        }
+        let essential: Vec<String> = plan
+            .items
+            .iter()
+            .filter(|item| matches!(item.action, PlanAction::Remove(_)))
+            .filter(|item| {
+                let name = item.name.split(':').next().unwrap_or_default();
+                let version = Version::from(item.version.as_str());
+                self.installed.is_essential(name)
+                    || self.packages().any(|p| {
+                        p.name() == name
+                            && p.version() == version
+                            && (p.essential() || p.protected())
+                    })
+            })
+            .map(|item| item.name.clone())
+            .collect();
+        match essential.is_empty() {
+            true => Ok(()),
+            false => Err(Error::EssentialRemoval(essential)),
+        }
+    }
+    /// Groups the packages of `solution` by their source package and
+    /// version, e.g. to list the changelogs or the sources to fetch.
+    pub fn by_source(&self, solution: &[SolvableId]) -> SourceGroups {
+        let mut groups: BTreeMap<(&str, Version<&str>), Vec<String>> = BTreeMap::new();
+        for &id in solution {
+            let source = self.source_of(id);
+            groups
+                .entry((source.name, source.version))
+                .or_default()
+                .push(self.package(id).name().to_string());
+        }
+        let groups = groups
+            .into_iter()
+            .map(|((name, version), mut binaries)| {
+                binaries.sort();
+                binaries.dedup();
+                SourceGroup {
+                    name: name.to_string(),
+                    version: version.to_string(),
+                    binaries,
+                }
+            })
+            .collect();
+        SourceGroups { groups }
+    }
+    /// Returns the source packages, with their versions, incorporated into
+    /// the packages of `solution` at build time per their `Built-Using` and
+    /// `Static-Built-Using` fields, sorted and deduplicated. An SBOM lists
+    /// them next to the sources of the packages themselves, as their
+    /// licenses apply to the binaries too.
+    pub fn built_using(&self, solution: &[SolvableId]) -> Vec<(String, String)> {
+        let mut sources: Vec<(String, String)> = solution
+            .iter()
+            .map(|&id| self.package(id))
+            .flat_map(|p| p.built_using().chain(p.static_built_using()))
+            .filter_map(|source| {
+                let source = source.ok()?;
+                let version = source.version().map(|v| v.to_string()).unwrap_or_default();
+                Some((source.name().to_string(), version))
+            })
+            .collect();
+        sources.sort();
+        sources.dedup();
+        sources
+    }
+    /// Returns the seed of the base system: the packages marked
+    /// `Essential: yes` or `Protected: yes`, or with `Priority: required`.
+    pub fn base_packages(&self) -> impl Iterator<Item = SolvableId> + '_ {
+        self.filter(PackageFilter::Essential | PackageFilter::Protected | Priority::Required)
+    }
+    /// Resolves the base system, i.e. the closure of the essential,
+    /// protected and required packages, as debootstrap does for its base
+    /// set. Same as [`Universe::variant_system`] of [`Variant::Minbase`].
+    pub fn base_system(&mut self) -> Result<Vec<SolvableId>, Error> {
+        self.variant_system(Variant::Minbase)
     }
     pub fn dependency_graph(
         &self,
@@ -455,6 +1596,19 @@ impl<S: AsRef<str> + 'static> Universe<S> {
     ) -> impl std::fmt::Display + '_ {
         conflict.display_user_friendly(&self.inner)
     }
+    /// Converts a solver failure into an [`Error::Solver`] explaining the
+    /// conflict.
+    pub fn solver_error(&self, err: UnsolvableOrCancelled) -> Error {
+        match err {
+            UnsolvableOrCancelled::Unsolvable(conflict) => {
+                Error::Solver(self.display_conflict(conflict).to_string())
+            }
+            UnsolvableOrCancelled::Cancelled(reason) => match reason.downcast::<String>() {
+                Ok(reason) => Error::Solver(*reason),
+                Err(_) => Error::Solver("cancelled".to_string()),
+            },
+        }
+    }
     pub fn display_solvable(&self, solvable: SolvableId) -> impl std::fmt::Display + '_ {
         self.inner.provider().display_solvable(solvable)
     }
@@ -463,29 +1617,210 @@ impl<S: AsRef<str> + 'static> Universe<S> {
             .provider()
             .with_index(|i| i.solvables.iter().map(|s| s.package))
     }
-    pub async fn deb_reader<'a>(&'a self, id: SolvableId) -> io::Result<VerifyingDebReader<'a>> {
-        let (repo, path, size, hash) = self.inner.provider().with(|u| {
-            let s = &u.index.solvables[id.to_index()];
-            let (path, size, hash) = s.package.repo_file()?;
-            Ok::<_, io::Error>((&u.packages[s.pkgs as usize].repo, path, size, hash))
-        })?;
-        repo.verifying_deb_reader(path, size, hash).await
-    }
-    pub async fn deb_file_reader(&self, id: SolvableId) -> io::Result<VerifyingReader> {
+    /// Returns the versions of the package `name`, with the position of
+    /// their package index in the universe.
+    pub fn versions_of<'a: 'n, 'n>(
+        &'a self,
+        name: &'n str,
+    ) -> impl Iterator<Item = (&'a Package<'a>, usize)> + 'n {
+        self.inner.provider().with_index(move |i| {
+            i.solvables
+                .iter()
+                .filter(move |s| s.package.name() == name)
+                .map(|s| (s.package, s.pkgs as usize))
+        })
+    }
+    /// Returns the solvables whose packages match `filter`.
+    pub fn filter(
+        &self,
+        filter: impl Into<PackageFilter>,
+    ) -> impl Iterator<Item = SolvableId> + '_ {
+        let filter = filter.into();
+        self.inner.provider().with_index(move |i| {
+            i.solvables
+                .iter()
+                .enumerate()
+                .filter(move |(_, s)| filter.matches(s.package))
+                .map(|(id, _)| id.into_id())
+        })
+    }
+    /// Freezes the universe into a read-only [`UniverseSnapshot`], resolving
+    /// the dependencies of every package up front. The snapshot can be
+    /// shared between threads in an `Arc` and queried without locking,
+    /// while the next universe is built from refreshed indexes.
+    pub fn freeze(self) -> UniverseSnapshot<S> {
+        let inner = Rc::clone(&self.inner.provider().0);
+        let count = inner.with_index(|i| i.solvables.len());
+        let requirements = (0..count)
+            .map(|id| inner.requirement_candidates(id.into_id()))
+            .collect();
+        let providers = inner.with_index(|i| {
+            let mut providers = HashMap::<Box<str>, Vec<SolvableId>>::new();
+            for (id, s) in i.solvables.iter().enumerate() {
+                let names = std::iter::once(s.name).chain(s.provides.iter().map(|pv| *pv.name()));
+                for name in names.collect::<HashSet<_>>() {
+                    providers
+                        .entry(i.names[name].name.into())
+                        .or_default()
+                        .push(id.into_id());
+                }
+            }
+            providers
+                .into_iter()
+                .map(|(name, ids)| (name, ids.into_boxed_slice()))
+                .collect()
+        });
+        let kind = self.kind;
+        // the solver holds the only other reference to the indexes
+        drop(self.inner);
+        let packages = match Rc::try_unwrap(inner) {
+            Ok(inner) => inner.into_heads().packages,
+            Err(_) => unreachable!("the universe indexes are shared"),
+        };
+        let offsets = std::iter::once(0)
+            .chain(packages.iter().scan(0, |total, p| {
+                *total += p.len();
+                Some(*total)
+            }))
+            .collect();
+        UniverseSnapshot {
+            kind,
+            packages,
+            offsets,
+            requirements,
+            providers,
+        }
+    }
+}
+
+#[cfg(feature = "io")]
+impl<S: AsRef<str> + 'static> Universe<S> {
+    // The repository and location of the .deb of the package, reporting its
+    // download to the progress of the repository.
+    fn deb_file(&self, id: SolvableId) -> io::Result<(&DebRepo, &str, usize, Digests)> {
         let (repo, path, size, hash) = self.inner.provider().with(|u| {
             let s = &u.index.solvables[id.to_index()];
             let (path, size, hash) = s.package.repo_file()?;
             Ok::<_, io::Error>((&u.packages[s.pkgs as usize].repo, path, size, hash))
         })?;
+        let package = self.package(id).to_string();
+        repo.progress()
+            .on_package_fetch(&package, path, size as u64);
+        Ok((repo, path, size, hash))
+    }
+    pub async fn deb_reader(&self, id: SolvableId) -> Result<VerifyingDebReader<'_>, Error> {
+        let (repo, path, size, hash) = self.deb_file(id)?;
+        repo.verifying_deb_reader(path, size, hash).await
+    }
+    pub async fn deb_file_reader(&self, id: SolvableId) -> Result<VerifyingReader, Error> {
+        let (repo, path, size, hash) = self.deb_file(id)?;
         repo.verifying_reader(path, size, hash).await
     }
-    pub async fn copy_deb_file<W: Write + Send>(&self, w: W, id: SolvableId) -> io::Result<u64> {
-        let (repo, path, size, hash) = self.inner.provider().with(|u| {
-            let s = &u.index.solvables[id.to_index()];
-            let (path, size, hash) = s.package.repo_file()?;
-            Ok::<_, io::Error>((&u.packages[s.pkgs as usize].repo, path, size, hash))
-        })?;
-        io::copy(repo.verifying_reader(path, size, hash).await?, pin!(w)).await
+    pub async fn copy_deb_file<W: Write + Send>(&self, w: W, id: SolvableId) -> Result<u64, Error> {
+        let (repo, path, size, hash) = self.deb_file(id)?;
+        let reader = repo.verifying_reader(path, size, hash).await?;
+        Ok(futures::io::copy(reader, &mut pin!(w)).await?)
+    }
+}
+
+/// A read-only universe, made by [`Universe::freeze`].
+///
+/// The snapshot owns the package indexes along with the dependencies of
+/// their packages, resolved to the packages matching them. It cannot solve
+/// problems, but unlike a universe it is `Sync`, so any number of threads
+/// can look packages up and walk the dependency graph at once:
+///
+/// ```ignore
+/// let snapshot = Arc::new(universe.freeze());
+/// let order: Vec<_> = snapshot.sort_solution(&mut solution).collect();
+/// ```
+pub struct UniverseSnapshot<S: AsRef<str> + 'static> {
+    kind: PackageKind,
+    packages: Vec<Packages<S>>,
+    // the id of the first package of each index, and the total count
+    offsets: Vec<usize>,
+    // the packages matching each requirement of each package
+    requirements: Vec<Box<[Box<[SolvableId]>]>>,
+    // the packages named or providing a name
+    providers: HashMap<Box<str>, Box<[SolvableId]>>,
+}
+
+impl<S: AsRef<str> + 'static> UniverseSnapshot<S> {
+    pub fn kind(&self) -> PackageKind {
+        self.kind
+    }
+    /// Returns the number of packages.
+    pub fn len(&self) -> usize {
+        self.requirements.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.requirements.is_empty()
+    }
+    /// Returns the package `solvable`, with the same ids as the universe
+    /// the snapshot was made from.
+    pub fn package(&self, solvable: SolvableId) -> &Package<'_> {
+        let id = solvable.to_index();
+        let pkgs = self.offsets.partition_point(|&offset| offset <= id) - 1;
+        self.packages[pkgs]
+            .get(id - self.offsets[pkgs])
+            .expect("solvable out of range")
+    }
+    pub fn packages(&self) -> impl Iterator<Item = &'_ Package<'_>> {
+        self.packages.iter().flat_map(|p| p.packages())
+    }
+    /// Returns the packages named `name` or providing it.
+    pub fn providers(&self, name: &str) -> &[SolvableId] {
+        self.providers.get(name).map_or(&[], |ids| ids)
+    }
+    /// Returns the solvables whose packages match `filter`.
+    pub fn filter(
+        &self,
+        filter: impl Into<PackageFilter>,
+    ) -> impl Iterator<Item = SolvableId> + '_ {
+        let filter = filter.into();
+        self.packages()
+            .enumerate()
+            .filter(move |(_, p)| filter.matches(p))
+            .map(|(id, _)| id.into_id())
+    }
+    /// Returns the packages matching each of the Pre-Depends and Depends of
+    /// `solvable`, the alternatives of `a | b` merged.
+    pub fn requirements(&self, solvable: SolvableId) -> &[Box<[SolvableId]>] {
+        &self.requirements[solvable.to_index()]
+    }
+    /// Returns the packages with a requirement `solvable` matches.
+    pub fn reverse_dependencies(
+        &self,
+        solvable: SolvableId,
+    ) -> impl Iterator<Item = SolvableId> + '_ {
+        self.requirements
+            .iter()
+            .enumerate()
+            .filter(move |(_, reqs)| reqs.iter().any(|req| req.contains(&solvable)))
+            .map(|(id, _)| id.into_id())
+    }
+    /// Same as [`Universe::dependency_graph`].
+    pub fn dependency_graph(
+        &self,
+        solution: &mut [SolvableId],
+    ) -> petgraph::graphmap::DiGraphMap<SolvableId, ()> {
+        solution.sort();
+        let solution = &*solution;
+        petgraph::graphmap::DiGraphMap::<SolvableId, ()>::from_edges(solution.iter().flat_map(
+            |&package| {
+                self.requirements(package)
+                    .iter()
+                    .flat_map(|req| req.iter())
+                    .filter(move |&&sid| sid != package && solution.binary_search(&sid).is_ok())
+                    .map(move |&dependency| (package, dependency))
+            },
+        ))
+    }
+    /// Same as [`Universe::sort_solution`].
+    pub fn sort_solution(&self, solution: &mut [SolvableId]) -> impl Iterator<Item = SolvableId> {
+        petgraph::algo::kosaraju_scc(&self.dependency_graph(solution))
+            .into_iter()
+            .flat_map(|g| g.into_iter())
     }
 }
 
@@ -496,6 +1831,18 @@ impl<S: AsRef<str> + 'static> std::fmt::Debug for Universe<S> {
 }
 
 impl<S: AsRef<str> + 'static> InnerUniverse<S> {
+    // Requires exactly the solvable `id`.
+    fn exact(&self, id: SolvableId) -> Requirement {
+        self.with_index(|i| {
+            let solvable = &i.solvables[id.to_index()];
+            Requirement::Single(i.version_sets.get_or_insert(VersionSet {
+                name: solvable.name,
+                arch: solvable.arch,
+                selfref: None,
+                range: solvable.full_name().version().into(),
+            }))
+        })
+    }
     fn intern_single_dependency<A, N, V>(
         &self,
         dep: Constraint<Option<A>, N, Version<V>>,
@@ -530,20 +1877,68 @@ impl<S: AsRef<str> + 'static> InnerUniverse<S> {
     }
     fn get_candidates(&self, name: NameId) -> Option<Candidates> {
         self.with_index(|i| {
-            let candidates = &i.names[name].packages;
-            match candidates.len() {
-                0 => None,
-                _ => Some(Candidates {
-                    hint_dependencies_available: candidates.to_vec(),
-                    candidates: candidates.to_vec(),
-                    ..Candidates::default()
-                }),
-            }
+            let mut stats = i.candidate_stats.get();
+            let candidates = match i.candidates.borrow_mut().entry(name) {
+                Entry::Occupied(entry) => {
+                    stats.hits += 1;
+                    if entry.get().is_none() {
+                        stats.negative_hits += 1;
+                    }
+                    entry.get().clone()
+                }
+                Entry::Vacant(entry) => {
+                    stats.misses += 1;
+                    let candidates = &i.names[name].packages;
+                    let candidates = match candidates.len() {
+                        0 => None,
+                        _ => Some(Candidates {
+                            hint_dependencies_available: candidates.to_vec(),
+                            candidates: candidates.to_vec(),
+                            ..Candidates::default()
+                        }),
+                    };
+                    entry.insert(candidates).clone()
+                }
+            };
+            i.candidate_stats.set(stats);
+            candidates
         })
     }
     fn get_dependencies(&self, solvable: SolvableId) -> Dependencies {
         self.with(|u| u.index.add_package_dependencies(solvable, &u.interned))
     }
+    // The candidates matching each requirement of `solvable`.
+    fn requirement_candidates(&self, solvable: SolvableId) -> Box<[Box<[SolvableId]>]> {
+        let requirements = match self.get_dependencies(solvable) {
+            Dependencies::Known(deps) => deps.requirements,
+            Dependencies::Unknown(_) => vec![],
+        };
+        self.with_index(|i| {
+            requirements
+                .into_iter()
+                .map(|req| {
+                    let sets = match req {
+                        Requirement::Single(vs) => smallvec![vs],
+                        Requirement::Union(u) => i.version_set_unions[u].clone(),
+                    };
+                    let mut candidates: Vec<SolvableId> = sets
+                        .iter()
+                        .flat_map(|&vs| {
+                            let vs = &i.version_sets[vs];
+                            i.names[vs.name]
+                                .packages
+                                .iter()
+                                .copied()
+                                .filter(move |&sid| i.matches(vs, sid) == Some(true))
+                        })
+                        .collect();
+                    candidates.sort();
+                    candidates.dedup();
+                    candidates.into_boxed_slice()
+                })
+                .collect()
+        })
+    }
     fn dependency_graph(
         &self,
         solution: &mut [SolvableId],
@@ -673,78 +2068,147 @@ impl<S: AsRef<str> + 'static> Interner for InnerUniverse<S> {
     }
 }
 
-impl<S: AsRef<str> + 'static> DependencyProvider for InnerUniverse<S> {
+impl<S: AsRef<str> + 'static> Interner for Provider<S> {
+    fn display_name(&self, name: NameId) -> impl std::fmt::Display + '_ {
+        self.0.display_name(name)
+    }
+    fn solvable_name(&self, solvable: SolvableId) -> NameId {
+        self.0.solvable_name(solvable)
+    }
+    fn display_string(&self, string_id: StringId) -> impl std::fmt::Display + '_ {
+        self.0.display_string(string_id)
+    }
+    fn display_solvable(&self, solvable: SolvableId) -> impl std::fmt::Display + '_ {
+        self.0.display_solvable(solvable)
+    }
+    fn version_set_name(&self, version_set: VersionSetId) -> NameId {
+        self.0.version_set_name(version_set)
+    }
+    fn display_version_set(&self, version_set: VersionSetId) -> impl std::fmt::Display + '_ {
+        self.0.display_version_set(version_set)
+    }
+    fn display_solvable_name(&self, solvable: SolvableId) -> impl std::fmt::Display + '_ {
+        self.0.display_solvable_name(solvable)
+    }
+    fn version_sets_in_union(
+        &self,
+        version_set_union: VersionSetUnionId,
+    ) -> impl Iterator<Item = VersionSetId> {
+        // The solver tries the alternatives in order, the cheapest goes first
+        self.with_index(|i| {
+            let objective = i.objective.get();
+            if objective == SolveObjective::None {
+                return i.version_set_unions[version_set_union].clone();
+            }
+            let mut sorted = i.sorted_unions.borrow_mut();
+            let sets = sorted.entry(version_set_union).or_insert_with(|| {
+                let mut sets = i.version_set_unions[version_set_union].clone();
+                sets.sort_by_cached_key(|vs| {
+                    i.names[i.version_sets[*vs].name]
+                        .packages
+                        .iter()
+                        .map(|sid| objective.cost(i.solvables[sid.to_index()].package))
+                        .min()
+                        .unwrap_or(usize::MAX)
+                });
+                sets
+            });
+            sets.clone()
+        })
+        .into_iter()
+    }
+    fn display_merged_solvables(&self, solvables: &[SolvableId]) -> impl std::fmt::Display + '_ {
+        self.0.display_merged_solvables(solvables)
+    }
+}
+
+impl<S: AsRef<str> + 'static> DependencyProvider for Provider<S> {
     async fn filter_candidates(
         &self,
         candidates: &[SolvableId],
         version_set: VersionSetId,
         inverse: bool,
     ) -> Vec<SolvableId> {
-        let c = self.with(|u| {
+        if let Some(cache) = &self.3 {
+            if let Some(filtered) = cache.filtered.borrow().get(&(version_set, inverse)) {
+                return filtered.clone();
+            }
+        }
+        let c: Vec<SolvableId> = self.with(|u| {
             let vs = &u.index.version_sets[version_set];
             tracing::trace!(
-                "filter candidates {:?} with {}{}{}",
-                candidates
+                candidates = ?candidates
                     .iter()
                     .map(|c| {
                         let c = &u.index.solvables[c.to_index()];
                         format!("{}", c.package.full_name())
                     })
                     .collect::<Vec<_>>(),
-                u.index.version_sets[version_set].selfref.map_or_else(
+                selfref = %u.index.version_sets[version_set].selfref.map_or_else(
                     || "".to_string(),
                     |c| {
                         let c = &u.index.solvables[c.to_index()];
-                        format!("({}={}) ", c.package.name(), c.package.version())
+                        format!("{}={}", c.package.name(), c.package.version())
                     }
                 ),
-                Constraint::new(
+                constraint = %Constraint::new(
                     Some(&u.index.archlist[vs.arch]),
                     &u.index.names[vs.name].name,
                     vs.range.clone(),
                 ),
-                if inverse { " inverse" } else { "" },
+                inverse,
+                "filter candidates"
             );
             candidates
                 .iter()
                 .filter(|&&sid| {
                     let solvable = &u.index.solvables[sid.to_index()];
-                    tracing::trace!("  validating {}", solvable.package.full_name(),);
-                    if Some(sid) == vs.selfref {
-                        false // always exclude self-referencing dependencies
-                    } else if !solvable.arch.satisfies(&vs.arch) {
-                        false // always exclude dependencies with not suitable arch
-                    } else {
-                        let sname = u.index.names[vs.name].name;
-                        ((solvable.name == vs.name
-                            && (solvable.package.version().satisfies(&vs.range)))
-                            || solvable
-                                .package
-                                .provides()
-                                .filter_map(|pv| pv.ok()) // TODO:: report parsing error
-                                .find(|pv| *pv.name() == sname && (pv.satisfies(&vs.range)))
-                                .is_some())
-                            ^ inverse
-                    }
+                    tracing::trace!(package = %solvable.package.full_name(), "validating");
+                    u.index.matches(vs, sid).is_some_and(|m| m ^ inverse)
                 })
                 .map(|s| *s)
                 .collect()
         });
-        tracing::trace!("result is {:?}", &c);
+        tracing::trace!(result = ?c, "filtered candidates");
+        if let Some(cache) = &self.3 {
+            cache
+                .filtered
+                .borrow_mut()
+                .insert((version_set, inverse), c.clone());
+        }
         c
     }
 
     async fn get_candidates(&self, name: NameId) -> Option<Candidates> {
-        self.get_candidates(name)
+        self.2.candidates.set(self.2.candidates.get() + 1);
+        self.0.get_candidates(name)
     }
 
     async fn get_dependencies(&self, solvable: SolvableId) -> Dependencies {
-        let deps = self.get_dependencies(solvable);
+        let cached = self
+            .3
+            .as_ref()
+            .and_then(|cache| cache.dependencies.borrow().get(&solvable).cloned());
+        let deps = match cached {
+            Some(deps) => deps,
+            None => {
+                let deps = self.0.get_dependencies(solvable);
+                if let Some(cache) = &self.3 {
+                    cache
+                        .dependencies
+                        .borrow_mut()
+                        .insert(solvable, deps.clone());
+                }
+                deps
+            }
+        };
+        let steps = self.2.dependencies.get() + 1;
+        self.2.dependencies.set(steps);
+        self.1.on_solve_step(steps as usize, None);
         tracing::trace!(
-            "dependencies for {} {}: {}",
-            solvable.to_index(),
-            self.display_solvable(solvable),
-            match &deps {
+            solvable = solvable.to_index(),
+            package = %self.display_solvable(solvable),
+            dependencies = %match &deps {
                 Dependencies::Known(deps) => {
                     format!(
                         "Requirements({}) Constrains({})",
@@ -771,32 +2235,58 @@ impl<S: AsRef<str> + 'static> DependencyProvider for InnerUniverse<S> {
                 Dependencies::Unknown(s) => {
                     self.display_string(*s).to_string()
                 }
-            }
+            },
+            "dependencies"
         );
         deps
     }
 
     async fn sort_candidates(&self, _solver: &SolverCache<Self>, solvables: &mut [SolvableId]) {
         self.with_index(|i| {
-            solvables.sort_by(|this, that| {
-                let this = &i.solvables[this.to_index()];
-                let that = &i.solvables[that.to_index()];
-                match (this.arch.satisfies(&i.arch), that.arch.satisfies(&i.arch)) {
+            let ordering = i.ordering.borrow();
+            let objective = i.objective.get();
+            // a total order, so that the solution depends on the indexes
+            // only and never on the order the solver lists the candidates in
+            solvables.sort_by(|this_id, that_id| {
+                let this = &i.solvables[this_id.to_index()];
+                let that = &i.solvables[that_id.to_index()];
+                match (
+                    this.arch.satisfies(&(i.arch, &i.archlist)),
+                    that.arch.satisfies(&(i.arch, &i.archlist)),
+                ) {
                     (false, true) => std::cmp::Ordering::Less,
                     (true, false) => std::cmp::Ordering::Greater,
-                    _ => match this.package.name().cmp(that.package.name()) {
-                        std::cmp::Ordering::Equal => {
-                            this.package.version().cmp(&that.package.version())
-                        }
-                        cmp => cmp,
-                    },
+                    _ => objective
+                        .cost(this.package)
+                        .cmp(&objective.cost(that.package))
+                        .then_with(|| {
+                            ordering.compare(
+                                (this.package, this.pkgs as usize),
+                                (that.package, that.pkgs as usize),
+                            )
+                        })
+                        .then_with(|| this.package.name().cmp(that.package.name()))
+                        .then_with(|| this.package.version().cmp(&that.package.version()))
+                        .then_with(|| this_id.cmp(that_id)),
                 }
             })
         })
     }
 
     fn should_cancel_with_value(&self) -> Option<Box<dyn std::any::Any>> {
-        None
+        let state = &self.2;
+        state.checks.set(state.checks.get() + 1);
+        let reason = match (state.options.max_propagations, state.deadline) {
+            (Some(max), _) if state.propagations() > max => {
+                format!("gave up after {} propagation rounds", max)
+            }
+            (_, Some(deadline)) if Instant::now() >= deadline => format!(
+                "gave up after {:?}",
+                state.options.timeout.unwrap_or_default()
+            ),
+            _ => return None,
+        };
+        Some(Box::new(reason))
     }
 }
 
@@ -804,6 +2294,7 @@ impl<S: AsRef<str> + 'static> DependencyProvider for InnerUniverse<S> {
 mod tests {
     use super::*;
     use crate::packages::Packages;
+    use crate::testutil::XorShift;
 
     use std::sync::Once;
 
@@ -831,6 +2322,7 @@ mod tests {
                         .into_iter()
                         .map(|dep| Dependency::try_from(dep).expect("failed to parse dependency")),
                     vec![],
+                    vec![],
                 );
                 let solution = match uni.solve(problem) {
                     Ok(solution) => solution,
@@ -851,6 +2343,1323 @@ mod tests {
         };
     }
 
+    #[test]
+    fn test_base_system() {
+        // Excerpt from the bookworm main/binary-amd64 index
+        let src = "\
+Package: base-files
+Version: 12.4+deb12u8
+Architecture: amd64
+Essential: yes
+Priority: required
+Section: admin
+Pre-Depends: awk
+Breaks: debian-security-support (<< 2019.04.25), initscripts (<< 2.88dsf-13.3)
+
+Package: mawk
+Version: 1.3.4.20200120-3.1
+Architecture: amd64
+Multi-Arch: foreign
+Priority: required
+Section: interpreters
+Depends: libc6 (>= 2.34)
+Provides: awk
+
+Package: libc6
+Version: 2.36-9+deb12u9
+Architecture: amd64
+Multi-Arch: same
+Priority: optional
+Section: libs
+Depends: libgcc-s1
+Breaks: hurd (<< 1:0.9.git20220301-2), nscd (<< 2.36)
+
+Package: libgcc-s1
+Version: 12.2.0-14
+Architecture: amd64
+Multi-Arch: same
+Priority: optional
+Section: libs
+Depends: gcc-12-base (= 12.2.0-14), libc6 (>= 2.35)
+
+Package: gcc-12-base
+Version: 12.2.0-14
+Architecture: amd64
+Multi-Arch: same
+Priority: optional
+Section: libs
+
+Package: dpkg
+Version: 1.21.22
+Architecture: amd64
+Essential: yes
+Priority: required
+Section: admin
+Pre-Depends: libbz2-1.0, libc6 (>= 2.34), liblzma5 (>= 5.4.0), libmd0 (>= 0.0.0), \
+ libselinux1 (>= 3.1~), libzstd1 (>= 1.5.2), zlib1g (>= 1:1.1.4)
+Depends: tar (>= 1.28-1)
+
+Package: tar
+Version: 1.34+dfsg-1.2+deb12u1
+Architecture: amd64
+Essential: yes
+Priority: required
+Section: utils
+Pre-Depends: libacl1 (>= 2.2.23), libc6 (>= 2.34), libselinux1 (>= 3.1~)
+
+Package: libbz2-1.0
+Version: 1.0.8-5+b1
+Architecture: amd64
+Priority: important
+Section: libs
+Depends: libc6 (>= 2.4)
+
+Package: liblzma5
+Version: 5.4.1-0.2
+Architecture: amd64
+Priority: required
+Section: libs
+Depends: libc6 (>= 2.34)
+
+Package: libmd0
+Version: 1.0.4-2
+Architecture: amd64
+Priority: optional
+Section: libs
+Depends: libc6 (>= 2.33)
+
+Package: libselinux1
+Version: 3.4-1+b6
+Architecture: amd64
+Priority: optional
+Section: libs
+Depends: libc6 (>= 2.34), libpcre2-8-0 (>= 10.22)
+
+Package: libpcre2-8-0
+Version: 10.42-1
+Architecture: amd64
+Priority: optional
+Section: libs
+Depends: libc6 (>= 2.34)
+
+Package: libzstd1
+Version: 1.5.4+dfsg2-5
+Architecture: amd64
+Priority: optional
+Section: libs
+Depends: libc6 (>= 2.34)
+
+Package: zlib1g
+Version: 1:1.2.13.dfsg-1
+Architecture: amd64
+Priority: optional
+Section: libs
+Depends: libc6 (>= 2.14)
+
+Package: libacl1
+Version: 2.3.1-3
+Architecture: amd64
+Priority: optional
+Section: libs
+Depends: libc6 (>= 2.33)
+
+Package: vim
+Version: 2:9.0.1378-2
+Architecture: amd64
+Priority: optional
+Section: editors
+Depends: vim-common (= 2:9.0.1378-2), libc6 (>= 2.34)
+";
+        let mut uni = Universe::new("amd64", vec![Packages::new_test(src).unwrap()]).unwrap();
+        let seed: Vec<_> = uni
+            .base_packages()
+            .map(|id| uni.package(id).name().to_string())
+            .collect();
+        assert_eq!(seed, ["base-files", "mawk", "dpkg", "tar", "liblzma5"]);
+        let base = uni.base_system().unwrap();
+        let mut names: Vec<_> = base.iter().map(|id| uni.package(*id).name()).collect();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                "base-files",
+                "dpkg",
+                "gcc-12-base",
+                "libacl1",
+                "libbz2-1.0",
+                "libc6",
+                "libgcc-s1",
+                "liblzma5",
+                "libmd0",
+                "libpcre2-8-0",
+                "libselinux1",
+                "libzstd1",
+                "mawk",
+                "tar",
+                "zlib1g",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_udeb_universe() {
+        let src = "\
+Package: anna
+Version: 1.87
+Architecture: amd64
+Depends: libc6-udeb (>= 2.36), libdebian-installer4-udeb (>= 0.122)
+Filename: pool/main/a/anna/anna_1.87_amd64.udeb
+
+Package: libc6-udeb
+Version: 2.36-9+deb12u9
+Architecture: amd64
+Provides: libc6, libc-udeb
+Filename: pool/main/g/glibc/libc6-udeb_2.36-9+deb12u9_amd64.udeb
+
+Package: libdebian-installer4-udeb
+Version: 0.122
+Architecture: amd64
+Depends: libc6-udeb (>= 2.36)
+Filename: pool/main/libd/libdebian-installer/libdebian-installer4-udeb_0.122_amd64.udeb
+";
+        let udebs = || {
+            Packages::new_test(src)
+                .unwrap()
+                .with_kind(PackageKind::Udeb)
+        };
+        let mut uni = Universe::new("amd64", vec![udebs()]).unwrap();
+        assert_eq!(uni.kind(), PackageKind::Udeb);
+        let problem = uni.problem(vec![Dependency::try_from("anna").unwrap()], vec![], vec![]);
+        assert_eq!(uni.solve(problem).unwrap().len(), 3);
+        let debs = Packages::new_test("Package: a\nVersion: 1\nArchitecture: all\n").unwrap();
+        assert!(Universe::new("amd64", vec![udebs(), debs]).is_err());
+    }
+
+    #[test]
+    fn test_memory_stats() {
+        let src = "\
+Package: aa
+Version: 1
+Architecture: amd64
+Depends: bb (>= 1) | cc
+
+Package: bb
+Version: 1
+Architecture: all
+Provides: cc
+";
+        let mut uni = Universe::new("amd64", vec![Packages::new_test(src).unwrap()]).unwrap();
+        let built = uni.memory_stats();
+        assert_eq!(built.solvables.count, 2);
+        assert_eq!(built.names.count, 3);
+        assert_eq!(built.archs.count, 2);
+        assert_eq!(built.strings.count, 1);
+        assert!(built.strings.bytes >= "amd64".len());
+        assert_eq!(built.total().count, 8);
+        let problem = uni.problem(vec![Dependency::try_from("aa").unwrap()], vec![], vec![]);
+        assert_eq!(uni.solve(problem).unwrap().len(), 2);
+        let solved = uni.memory_stats();
+        assert!(solved.version_sets.count > built.version_sets.count);
+        assert!(solved.total().bytes > built.total().bytes);
+    }
+
+    #[test]
+    fn test_candidate_cache() {
+        let src = "Package: aa\nVersion: 1\nArchitecture: all\nDepends: missing | bb\n\n\
+                   Package: bb\nVersion: 1\nArchitecture: all\n";
+        let mut uni = Universe::new("amd64", vec![Packages::new_test(src).unwrap()]).unwrap();
+        let solve = |uni: &mut Universe<_>| {
+            let problem = uni.problem(vec![Dependency::try_from("aa").unwrap()], vec![], vec![]);
+            uni.solve(problem).unwrap().len()
+        };
+        assert_eq!(solve(&mut uni), 2);
+        let first = uni.candidate_cache_stats();
+        assert_eq!(first.misses, 3);
+        assert_eq!(solve(&mut uni), 2);
+        let second = uni.candidate_cache_stats();
+        assert_eq!(second.misses, first.misses);
+        assert!(second.hits >= first.hits + 3);
+        assert!(second.negative_hits > first.negative_hits);
+    }
+
+    #[test]
+    fn test_versioned_provides() {
+        let src = "Package: aa\nVersion: 1\nArchitecture: all\nProvides: vv (= 2), ww\n";
+        let mut uni = Universe::new("amd64", vec![Packages::new_test(src).unwrap()]).unwrap();
+        for (requirement, solvable) in [
+            ("vv (>= 2)", true),
+            ("vv (>= 3)", false),
+            ("ww", true),
+            ("aa (= 1)", true),
+            ("xx", false),
+        ] {
+            let problem = uni.problem(
+                vec![Dependency::try_from(requirement).unwrap()],
+                vec![],
+                vec![],
+            );
+            assert_eq!(uni.solve(problem).is_ok(), solvable, "{}", requirement);
+        }
+    }
+
+    #[test]
+    fn test_solve_many() {
+        let src = "Package: aa\nVersion: 1\nArchitecture: all\nDepends: bb | cc\n\n\
+                   Package: bb\nVersion: 1\nArchitecture: all\nConflicts: dd\n\n\
+                   Package: dd\nVersion: 1\nArchitecture: all\nDepends: missing\n";
+        let mut uni = Universe::new("amd64", vec![Packages::new_test(src).unwrap()]).unwrap();
+        let requirements = ["aa", "bb", "aa, bb", "dd"];
+        let problem = |uni: &Universe<_>, requirement: &str| {
+            let requirements: Vec<_> = requirement
+                .split(", ")
+                .map(|r| Dependency::try_from(r).unwrap())
+                .collect();
+            uni.problem(requirements, vec![], vec![])
+        };
+        let one_by_one: Vec<_> = requirements
+            .iter()
+            .map(|r| {
+                let problem = problem(&uni, r);
+                uni.solve(problem).ok().map(|mut solution| {
+                    solution.sort();
+                    solution
+                })
+            })
+            .collect();
+        let problems: Vec<_> = requirements.iter().map(|r| problem(&uni, r)).collect();
+        let batch: Vec<_> = uni
+            .solve_many(problems)
+            .into_iter()
+            .map(|solution| {
+                solution.ok().map(|mut solution| {
+                    solution.sort();
+                    solution
+                })
+            })
+            .collect();
+        assert_eq!(batch, one_by_one);
+        assert!(batch[0].is_some() && batch[3].is_none());
+    }
+
+    #[test]
+    fn test_solve_options() {
+        let src = "Package: aa\nVersion: 1\nArchitecture: all\nDepends: bb (>= 1) | cc\n\n\
+                   Package: bb\nVersion: 1\nArchitecture: all\n";
+        let mut uni = Universe::new("amd64", vec![Packages::new_test(src).unwrap()]).unwrap();
+        let problem = |uni: &Universe<_>| {
+            uni.problem(vec![Dependency::try_from("aa").unwrap()], vec![], vec![])
+        };
+        let (solution, stats) = uni.solve_with_options(problem(&uni), SolveOptions::default());
+        assert_eq!(solution.unwrap().len(), 2);
+        assert!(stats.propagations > 0);
+        assert_eq!(stats.dependency_queries, 2);
+        assert!(stats.candidate_queries >= 2);
+        assert_eq!(stats.version_sets_created, 2);
+
+        let options = SolveOptions {
+            max_propagations: Some(0),
+            ..SolveOptions::default()
+        };
+        let (solution, _) = uni.solve_with_options(problem(&uni), options);
+        let err = uni.solver_error(solution.unwrap_err());
+        assert_eq!(
+            err.to_string(),
+            "no solution: gave up after 0 propagation rounds"
+        );
+        let options = SolveOptions {
+            timeout: Some(Duration::ZERO),
+            ..SolveOptions::default()
+        };
+        let (solution, _) = uni.solve_with_options(problem(&uni), options);
+        assert!(matches!(solution, Err(UnsolvableOrCancelled::Cancelled(_))));
+    }
+
+    #[test]
+    fn test_deterministic() {
+        let mut rng = XorShift::new(0x2545_f491_4f6c_dd1d);
+        let mut next = move |n: u64| rng.below(n as usize) as u64;
+        let mut src = String::new();
+        for i in 0..300u64 {
+            for version in 1..=next(3) + 1 {
+                src.push_str(&format!("Package: p{:03}\nVersion: {}\n", i, version));
+                src.push_str("Architecture: all\n");
+                let depends: Vec<_> = (0..next(4))
+                    .map(|_| {
+                        let dep = format!("p{:03} (>= {})", i + 1 + next(300 - i), next(3) + 1);
+                        match next(3) {
+                            0 => format!("{} | v{:02}", dep, next(20)),
+                            _ => dep,
+                        }
+                    })
+                    .filter(|dep| !dep.starts_with("p300"))
+                    .collect();
+                if !depends.is_empty() {
+                    src.push_str(&format!("Depends: {}\n", depends.join(", ")));
+                }
+                if next(4) == 0 {
+                    src.push_str(&format!("Provides: v{:02}\n", next(20)));
+                }
+                if next(8) == 0 {
+                    src.push_str(&format!("Conflicts: p{:03}\n", next(300)));
+                }
+                src.push('\n');
+            }
+        }
+        let solve = || {
+            let packages = Packages::new_test(src.clone()).unwrap();
+            let mut uni = Universe::new("amd64", vec![packages]).unwrap();
+            (0..20)
+                .map(|i| {
+                    let name = format!("p{:03}", i * 7);
+                    let requirement = Dependency::try_from(name.as_str()).unwrap();
+                    let problem = uni.problem(vec![requirement], vec![], vec![]);
+                    uni.solve(problem).ok().map(|mut solution| {
+                        let order: Vec<_> = uni.sort_solution(&mut solution).collect();
+                        let names: Vec<_> = order
+                            .iter()
+                            .map(|id| uni.package(*id).full_name().to_string())
+                            .collect();
+                        (solution, names)
+                    })
+                })
+                .collect::<Vec<_>>()
+        };
+        let first = solve();
+        assert!(first.iter().filter(|solution| solution.is_some()).count() > 5);
+        assert_eq!(first, solve());
+    }
+
+    #[test]
+    fn test_send() {
+        fn send<T: Send>(value: T) -> T {
+            value
+        }
+        let src = "Package: aa\nVersion: 1\nArchitecture: all\nDepends: bb\n\n\
+                   Package: bb\nVersion: 1\nArchitecture: all\n";
+        let packages = Packages::parse(std::sync::Arc::<str>::from(src)).unwrap();
+        let mut uni = send(Universe::new("amd64", [packages]).unwrap());
+        let problem = uni.problem(vec![Dependency::try_from("aa").unwrap()], vec![], vec![]);
+        assert_eq!(uni.solve(problem).unwrap().len(), 2);
+        let uni = std::sync::Mutex::new(uni);
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    let mut uni = uni.lock().unwrap();
+                    let problem =
+                        uni.problem(vec![Dependency::try_from("bb").unwrap()], vec![], vec![]);
+                    assert_eq!(uni.solve(problem).unwrap().len(), 1);
+                });
+            }
+        });
+        let mut uni = std::thread::spawn(move || uni.into_inner().unwrap())
+            .join()
+            .unwrap();
+        let problem = uni.problem(vec![Dependency::try_from("aa").unwrap()], vec![], vec![]);
+        assert_eq!(uni.solve(problem).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_freeze() {
+        fn shared<T: Send + Sync>(value: T) -> std::sync::Arc<T> {
+            std::sync::Arc::new(value)
+        }
+        let src = "Package: aa\nVersion: 1\nArchitecture: all\nDepends: bb | cc, dd (>= 2)\n\n\
+                   Package: bb\nVersion: 1\nArchitecture: all\nProvides: cc\n\n\
+                   Package: dd\nVersion: 1\nArchitecture: all\n\n\
+                   Package: dd\nVersion: 2\nArchitecture: all\nDepends: aa\n";
+        let packages = Packages::parse(std::sync::Arc::<str>::from(src)).unwrap();
+        let mut uni = Universe::new("amd64", [packages]).unwrap();
+        let problem = uni.problem(vec![Dependency::try_from("aa").unwrap()], vec![], vec![]);
+        let mut solution = uni.solve(problem).unwrap();
+        let order: Vec<_> = uni.sort_solution(&mut solution).collect();
+        let snapshot = shared(uni.freeze());
+        assert_eq!(snapshot.len(), 4);
+        assert_eq!(snapshot.package(3.into_id()).version().to_string(), "2");
+        let id = |n: usize| -> SolvableId { n.into_id() };
+        assert_eq!(snapshot.providers("cc"), [id(1)]);
+        assert_eq!(snapshot.providers("dd"), [id(2), id(3)]);
+        assert!(snapshot.providers("ee").is_empty());
+        assert_eq!(
+            snapshot.requirements(id(0)),
+            [
+                vec![id(1)].into_boxed_slice(),
+                vec![id(3)].into_boxed_slice()
+            ]
+        );
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                let snapshot = std::sync::Arc::clone(&snapshot);
+                let mut solution = solution.clone();
+                let order = &order;
+                scope.spawn(move || {
+                    assert_eq!(
+                        &snapshot.sort_solution(&mut solution).collect::<Vec<_>>(),
+                        order
+                    );
+                    let rdeps: Vec<_> = snapshot.reverse_dependencies(id(3)).collect();
+                    assert_eq!(rdeps, [id(0)]);
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn test_builder() {
+        let src = "Package: aa\nVersion: 1\nArchitecture: all\nDepends: bb\n\n\
+                   Package: bb\nVersion: 1\nArchitecture: all\n";
+        for capacity in [None, Some(0), Some(100_000)] {
+            let mut builder =
+                UniverseBuilder::new("amd64").packages([Packages::new_test(src).unwrap()]);
+            if let Some(capacity) = capacity {
+                builder = builder.capacity(capacity);
+            }
+            let mut uni = builder.build().unwrap();
+            assert_eq!(uni.memory_stats().solvables.count, 2);
+            let problem = uni.problem(vec![Dependency::try_from("aa").unwrap()], vec![], vec![]);
+            assert_eq!(uni.solve(problem).unwrap().len(), 2);
+        }
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let src = "\
+Package: alpha
+Version: 1.0
+Architecture: amd64
+Depends: beta (>> 1.0
+
+Package: beta
+Version: 1.0
+Architecture: amd64
+Conflicts: alpha (<= )
+";
+        let mut uni = Universe::new("amd64", vec![Packages::new_test(src).unwrap()]).unwrap();
+        let problem = uni.problem(
+            vec![Dependency::try_from("alpha | beta").unwrap()],
+            vec![],
+            vec![],
+        );
+        assert!(uni.solve(problem).is_err());
+        let mut diagnostics = uni.take_diagnostics();
+        diagnostics.sort_by(|a, b| a.package.cmp(&b.package));
+        let context: Vec<_> = diagnostics
+            .iter()
+            .map(|d| (d.package.as_deref().unwrap(), d.field.as_deref().unwrap()))
+            .collect();
+        assert_eq!(
+            context,
+            [("alpha=1.0", "Depends"), ("beta=1.0", "Conflicts")]
+        );
+        assert!(uni.take_diagnostics().is_empty());
+    }
+
+    #[test]
+    fn test_candidate_ordering() {
+        let index = |versions: &[(&str, &str)]| {
+            let src: String = versions
+                .iter()
+                .map(|(version, size)| {
+                    format!(
+                        "Package: alpha\nArchitecture: amd64\nVersion: {}\nSize: {}\n\n",
+                        version, size
+                    )
+                })
+                .collect();
+            Packages::new_test(src).unwrap()
+        };
+        let solve = |ordering: CandidateOrdering| {
+            let mut uni = Universe::new(
+                "amd64",
+                vec![
+                    index(&[("1.0", "300"), ("2.0", "200")]),
+                    index(&[("1.5", "100")]),
+                ],
+            )
+            .unwrap();
+            uni.set_candidate_ordering(ordering);
+            let problem = uni.problem(vec![Dependency::try_from("alpha").unwrap()], vec![], vec![]);
+            let solution = uni.solve(problem).unwrap();
+            uni.package(solution[0]).version().to_string()
+        };
+        assert_eq!(solve(CandidateOrdering::default()), "1.0");
+        assert_eq!(solve(CandidateOrdering::Newest), "2.0");
+        assert_eq!(solve(CandidateOrdering::SmallestDownload), "1.5");
+        assert_eq!(solve(CandidateOrdering::Origin(1)), "1.5");
+        let installed = [("alpha".to_string(), "1.5".to_string())].into();
+        assert_eq!(solve(CandidateOrdering::Installed(installed)), "1.5");
+        let installed = [("beta".to_string(), "1.5".to_string())].into();
+        assert_eq!(
+            solve(CandidateOrdering::Chain(vec![
+                CandidateOrdering::Installed(installed),
+                CandidateOrdering::Newest
+            ])),
+            "2.0"
+        );
+    }
+
+    #[test]
+    fn test_objective() {
+        let src = "\
+Package: app
+Version: 1.0
+Architecture: amd64
+Depends: large | small, libfoo
+
+Package: large
+Version: 1.0
+Architecture: amd64
+Installed-Size: 5000
+Size: 100
+
+Package: small
+Version: 1.0
+Architecture: amd64
+Installed-Size: 10
+Size: 900
+
+Package: libfoo
+Version: 1.0
+Architecture: amd64
+Installed-Size: 50
+
+Package: libfoo
+Version: 2.0
+Architecture: amd64
+Installed-Size: 40
+";
+        let solve = |objective| {
+            let mut uni = Universe::new("amd64", vec![Packages::new_test(src).unwrap()]).unwrap();
+            uni.set_objective(objective);
+            let problem = uni.problem(vec![Dependency::try_from("app").unwrap()], vec![], vec![]);
+            let mut names: Vec<_> = uni
+                .solve(problem)
+                .unwrap()
+                .iter()
+                .map(|id| uni.package(*id).full_name().to_string())
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(
+            solve(SolveObjective::None),
+            ["app=1.0", "large=1.0", "libfoo=1.0"]
+        );
+        assert_eq!(
+            solve(SolveObjective::MinimizeInstalledSize),
+            ["app=1.0", "libfoo=2.0", "small=1.0"]
+        );
+        assert_eq!(
+            solve(SolveObjective::MinimizeDownloadSize),
+            ["app=1.0", "large=1.0", "libfoo=1.0"]
+        );
+    }
+
+    #[test]
+    fn test_alternatives_report() {
+        let src = "\
+Package: app
+Version: 1.0
+Architecture: amd64
+Depends: gawk | mawk, missing | awk-tools, libold | libnew
+
+Package: gawk
+Version: 1.0
+Architecture: amd64
+Conflicts: blocker
+
+Package: mawk
+Version: 1.0
+Architecture: amd64
+
+Package: awk-tools
+Version: 1.0
+Architecture: amd64
+
+Package: libold
+Version: 1.0
+Architecture: amd64
+Depends: libbase (= 1.0)
+
+Package: libnew
+Version: 1.0
+Architecture: amd64
+
+Package: libbase
+Version: 1.0
+Architecture: amd64
+
+Package: libbase
+Version: 2.0
+Architecture: amd64
+
+Package: blocker
+Version: 1.0
+Architecture: amd64
+";
+        let mut uni = Universe::new("amd64", vec![Packages::new_test(src).unwrap()]).unwrap();
+        let report = |uni: &mut Universe<&str>, reqs: &[&str]| {
+            let problem = uni.problem(
+                reqs.iter().map(|r| Dependency::try_from(*r).unwrap()),
+                vec![],
+                vec![],
+            );
+            let solution = uni.solve(problem).unwrap();
+            uni.alternatives_report(&solution)
+                .into_iter()
+                .map(|choice| {
+                    let reason = match choice.reason {
+                        ChoiceReason::Constrained(by) => by
+                            .iter()
+                            .map(|id| uni.package(*id).full_name().to_string())
+                            .collect::<Vec<_>>()
+                            .join(","),
+                        reason => format!("{:?}", reason),
+                    };
+                    (uni.package(choice.selected).name().to_string(), reason)
+                })
+                .collect::<Vec<_>>()
+        };
+        let mut choices = report(&mut uni, &["app"]);
+        choices.sort();
+        assert_eq!(
+            choices,
+            [
+                ("awk-tools".to_string(), "OnlyInstallable".to_string()),
+                ("gawk".to_string(), "FirstPreference".to_string()),
+                ("libold".to_string(), "FirstPreference".to_string()),
+            ]
+        );
+        let mut choices = report(&mut uni, &["app", "blocker", "libbase (>= 2.0)"]);
+        choices.sort();
+        assert_eq!(
+            choices,
+            [
+                ("awk-tools".to_string(), "OnlyInstallable".to_string()),
+                // ruled out by its dependency on libbase (= 1.0)
+                ("libnew".to_string(), "".to_string()),
+                ("mawk".to_string(), "blocker=1.0".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_installed() {
+        let src = "\
+Package: alpha
+Version: 1.0
+Architecture: amd64
+
+Package: alpha
+Version: 1.5
+Architecture: amd64
+
+Package: alpha
+Version: 2.0
+Architecture: amd64
+";
+        let solve = |installed: Installed, ordering| {
+            let mut uni = Universe::new("amd64", vec![Packages::new_test(src).unwrap()]).unwrap();
+            uni.set_candidate_ordering(ordering);
+            uni.set_installed(installed);
+            let problem = uni.problem(vec![Dependency::try_from("alpha").unwrap()], vec![], vec![]);
+            let solution = uni.solve(problem).unwrap();
+            uni.package(solution[0]).version().to_string()
+        };
+        let installed = Installed::new().package("alpha", "1.5");
+        assert_eq!(solve(Installed::new(), CandidateOrdering::Oldest), "1.0");
+        assert_eq!(solve(installed.clone(), CandidateOrdering::Oldest), "1.5");
+        assert_eq!(solve(installed.clone(), CandidateOrdering::Newest), "2.0");
+        assert_eq!(
+            solve(installed.clone().hold("alpha"), CandidateOrdering::Newest),
+            "1.5"
+        );
+        assert_eq!(
+            solve(
+                installed.allow_downgrade("alpha"),
+                CandidateOrdering::Oldest
+            ),
+            "1.0"
+        );
+    }
+
+    #[test]
+    fn test_plan() {
+        let src = "\
+Package: hello
+Version: 2.10-3
+Architecture: amd64
+Depends: libc6, libfoo1
+Installed-Size: 280
+Size: 53100
+
+Package: libfoo1
+Version: 1.0
+Architecture: amd64
+Installed-Size: 10
+Size: 1000
+
+Package: libc6
+Version: 2.36-8
+Architecture: amd64
+Installed-Size: 12000
+
+Package: libc6
+Version: 2.36-9
+Architecture: amd64
+Installed-Size: 12100
+Size: 2800000
+
+Package: bash
+Version: 5.2-1
+Architecture: amd64
+
+Package: bash
+Version: 5.2-2
+Architecture: amd64
+";
+        let mut uni = Universe::new("amd64", vec![Packages::new_test(src).unwrap()]).unwrap();
+        uni.set_candidate_ordering(CandidateOrdering::Newest);
+        uni.set_installed(
+            Installed::new()
+                .package("libc6", "2.36-8")
+                .package("bash", "5.2-1"),
+        );
+        let problem = uni.problem(vec![Dependency::try_from("hello").unwrap()], vec![], vec![]);
+        let solution = uni.solve(problem).unwrap();
+        let plan = uni.plan(&solution, &["hello"]).unwrap();
+        assert_eq!(plan.installed(), ["hello", "libfoo1"]);
+        assert_eq!(plan.upgraded(), ["libc6"]);
+        assert_eq!(plan.not_upgraded, ["bash"]);
+        assert_eq!(plan.download_size(), 2_854_100);
+        assert_eq!(plan.disk_space_change(), 390 * 1024);
+        assert_eq!(
+            plan.render_apt_style(),
+            "\
+The following additional packages will be installed:
+  libfoo1
+The following NEW packages will be installed:
+  hello libfoo1
+The following packages will be upgraded:
+  libc6
+1 upgraded, 2 newly installed, 0 to remove and 1 not upgraded.
+Need to get 2854 kB of archives.
+After this operation, 399 kB of additional disk space will be used.
+"
+        );
+    }
+
+    #[test]
+    fn test_plan_takeover() {
+        let src = "\
+Package: exim4
+Version: 4.96-1
+Architecture: amd64
+Installed-Size: 100
+
+Package: postfix
+Version: 3.7-1
+Architecture: amd64
+Conflicts: exim4, sendmail
+Replaces: exim4 (<< 5)
+Installed-Size: 300
+Size: 1000
+
+Package: sendmail
+Version: 8.17-1
+Architecture: amd64
+";
+        let mut uni = Universe::new("amd64", vec![Packages::new_test(src).unwrap()]).unwrap();
+        uni.set_installed(
+            Installed::new()
+                .package("exim4", "4.96-1")
+                .package("sendmail", "8.17-1"),
+        );
+        let problem = uni.problem(
+            vec![Dependency::try_from("postfix").unwrap()],
+            vec![],
+            vec![],
+        );
+        let solution = uni.solve(problem).unwrap();
+        let plan = uni.plan(&solution, &["postfix"]).unwrap();
+        assert_eq!(plan.removed(), ["exim4"]);
+        assert_eq!(
+            plan.items[0].action,
+            PlanAction::Remove("postfix".to_string())
+        );
+        assert_eq!(plan.disk_space_change(), 200 * 1024);
+        assert!(plan
+            .render_apt_style()
+            .starts_with("The following packages will be REMOVED:\n  exim4\n"));
+    }
+
+    #[test]
+    fn test_plan_order() {
+        let src = "\
+Package: mta
+Version: 2.0
+Architecture: amd64
+Depends: libmta
+Conflicts: mta-utils, mta-daemon, mta-common, mta-doc
+Replaces: mta-utils, mta-daemon, mta-common, mta-doc
+
+Package: libmta
+Version: 2.0
+Architecture: amd64
+
+Package: mta-utils
+Version: 1.0
+Architecture: amd64
+Depends: mta-daemon
+
+Package: mta-daemon
+Version: 1.0
+Architecture: amd64
+Pre-Depends: mta-common
+
+Package: mta-common
+Version: 1.0
+Architecture: all
+Depends: mta-daemon
+
+Package: mta-doc
+Version: 1.0
+Architecture: all
+";
+        let mut uni = Universe::new("amd64", vec![Packages::new_test(src).unwrap()]).unwrap();
+        uni.set_installed(
+            Installed::new()
+                .package("mta-common", "1.0")
+                .package("mta-daemon", "1.0")
+                .package("mta-doc", "1.0")
+                .package("mta-utils", "1.0"),
+        );
+        let problem = uni.problem(vec![Dependency::try_from("mta").unwrap()], vec![], vec![]);
+        let solution = uni.solve(problem).unwrap();
+        let plan = uni.plan(&solution, &["mta"]).unwrap();
+        assert_eq!(plan.install_order, ["libmta", "mta"]);
+        let mut order = plan.removal_order.clone();
+        let position = |name| order.iter().position(|n| n == name).unwrap();
+        // mta-daemon Pre-Depends on mta-common, which depends on it back
+        assert!(position("mta-utils") < position("mta-daemon"));
+        assert!(position("mta-daemon") < position("mta-common"));
+        order.sort();
+        assert_eq!(order, plan.removed());
+    }
+
+    #[test]
+    fn test_plan_phases() {
+        let src = "\
+Package: app
+Version: 1.0
+Architecture: amd64
+Depends: libapp, perl
+
+Package: libapp
+Version: 1.0
+Architecture: amd64
+Pre-Depends: dpkg
+
+Package: dpkg
+Version: 1.22
+Architecture: amd64
+
+Package: perl
+Version: 5.36
+Architecture: amd64
+Depends: perl-base
+
+Package: perl-base
+Version: 5.36
+Architecture: amd64
+Depends: perl
+";
+        let mut uni = Universe::new("amd64", vec![Packages::new_test(src).unwrap()]).unwrap();
+        uni.set_installed(Installed::new().package("dpkg", "1.21"));
+        let problem = uni.problem(vec![Dependency::try_from("app").unwrap()], vec![], vec![]);
+        let solution = uni.solve(problem).unwrap();
+        let plan = uni.plan(&solution, &["app"]).unwrap();
+        let names = |phase: &PlanPhase| {
+            let mut names = match phase {
+                PlanPhase::Unpack(names) | PlanPhase::Configure(names) => names.clone(),
+                PlanPhase::Remove(names) => names.clone(),
+            };
+            names.sort();
+            names
+        };
+        // libapp is unpacked once dpkg is configured, the perl cycle is
+        // configured together
+        assert_eq!(plan.phases.len(), 4);
+        for batch in plan.phases.chunks(2) {
+            assert!(matches!(batch[0], PlanPhase::Unpack(_)));
+            assert!(matches!(batch[1], PlanPhase::Configure(_)));
+            assert_eq!(names(&batch[0]), names(&batch[1]));
+        }
+        let (first, second) = (names(&plan.phases[0]), names(&plan.phases[2]));
+        assert!(first.contains(&"dpkg".to_string()));
+        assert!(second.contains(&"libapp".to_string()));
+        let mut all = [first, second].concat();
+        all.sort();
+        assert_eq!(all, ["app", "dpkg", "libapp", "perl", "perl-base"]);
+        let perl = names(&plan.phases[1]).contains(&"perl".to_string());
+        assert_eq!(
+            perl,
+            names(&plan.phases[1]).contains(&"perl-base".to_string())
+        );
+    }
+
+    #[test]
+    fn test_plan_essential() {
+        let src = "\
+Package: systemd-sysv
+Version: 252-1
+Architecture: amd64
+Conflicts: sysvinit-core, runit-init
+Replaces: sysvinit-core, runit-init
+
+Package: runit-init
+Version: 2.1-5
+Architecture: amd64
+Protected: yes
+
+Package: runit-init
+Version: 2.1-6
+Architecture: amd64
+Protected: yes
+";
+        let mut uni = Universe::new("amd64", vec![Packages::new_test(src).unwrap()]).unwrap();
+        uni.set_installed(
+            Installed::new()
+                .package("sysvinit-core", "3.06-4")
+                .essential("sysvinit-core")
+                .package("runit-init", "2.1-5"),
+        );
+        let problem = uni.problem(
+            vec![Dependency::try_from("systemd-sysv").unwrap()],
+            vec![],
+            vec![],
+        );
+        let solution = uni.solve(problem).unwrap();
+        match uni.plan(&solution, &["systemd-sysv"]) {
+            Err(Error::EssentialRemoval(names)) => {
+                assert_eq!(names, ["runit-init", "sysvinit-core"])
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        uni.set_force_remove_essential(true);
+        let plan = uni.plan(&solution, &["systemd-sysv"]).unwrap();
+        assert_eq!(plan.removed(), ["runit-init", "sysvinit-core"]);
+    }
+
+    #[test]
+    fn test_base_protected() {
+        let src = "\
+Package: base-files
+Version: 12.4
+Architecture: amd64
+Essential: yes
+
+Package: init
+Version: 1.66
+Architecture: amd64
+Important: yes
+Depends: systemd-sysv
+
+Package: systemd-sysv
+Version: 252-1
+Architecture: amd64
+
+Package: hello
+Version: 2.10-3
+Architecture: amd64
+";
+        let mut uni = Universe::new("amd64", vec![Packages::new_test(src).unwrap()]).unwrap();
+        let names = |uni: &Universe<_>, ids: Vec<SolvableId>| {
+            let mut names: Vec<_> = ids.into_iter().map(|id| uni.package(id).name()).collect();
+            names.sort();
+            names.join(" ")
+        };
+        assert_eq!(
+            names(&uni, uni.base_packages().collect()),
+            "base-files init"
+        );
+        let base = uni.base_system().unwrap();
+        assert_eq!(names(&uni, base), "base-files init systemd-sysv");
+    }
+
+    #[test]
+    fn test_built_using() {
+        let src = "\
+Package: hello-go
+Version: 1.0
+Architecture: amd64
+Enhances: hello
+Built-Using: gcc-12 (= 12.2.0-14)
+Static-Built-Using: golang-x-text (= 0.7.0-1), gcc-12 (= 12.2.0-14)
+";
+        let mut uni = Universe::new("amd64", vec![Packages::new_test(src).unwrap()]).unwrap();
+        let package = uni.packages().next().unwrap();
+        let enhances: Vec<_> = package.enhances().map(|d| d.unwrap().to_string()).collect();
+        assert_eq!(enhances, ["hello"]);
+        let problem = uni.problem(
+            vec![Dependency::try_from("hello-go").unwrap()],
+            vec![],
+            vec![],
+        );
+        let solution = uni.solve(problem).unwrap();
+        assert_eq!(
+            uni.built_using(&solution),
+            [
+                ("gcc-12".to_string(), "12.2.0-14".to_string()),
+                ("golang-x-text".to_string(), "0.7.0-1".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_source_of() {
+        let src = "\
+Package: hello
+Version: 2.10-3+b1
+Architecture: amd64
+Source: hello (2.10-3)
+
+Package: libfoo1
+Version: 1.0-1
+Architecture: amd64
+Source: foo
+";
+        let mut uni = Universe::new("amd64", vec![Packages::new_test(src).unwrap()]).unwrap();
+        let sources = "Package: hello\nBinary: hello\nVersion: 2.10-3\n";
+        uni.set_sources([SourcesIndex::parse(sources).unwrap()]);
+        let requirements = ["hello", "libfoo1"].map(|name| Dependency::try_from(name).unwrap());
+        let problem = uni.problem(requirements, vec![], vec![]);
+        let solution = uni.solve(problem).unwrap();
+        let sources: Vec<_> = solution.iter().map(|&id| uni.source_of(id)).collect();
+        let hello = sources.iter().find(|s| s.name == "hello").unwrap();
+        assert_eq!(hello.version, Version::from("2.10-3"));
+        assert_eq!(
+            hello.package.unwrap().binaries().collect::<Vec<_>>(),
+            ["hello"]
+        );
+        let foo = sources.iter().find(|s| s.name == "foo").unwrap();
+        assert_eq!(foo.version, Version::from("1.0-1"));
+        assert!(foo.package.is_none());
+    }
+
+    #[test]
+    fn test_by_source() {
+        let src = "\
+Package: libc6
+Version: 2.36-9
+Architecture: amd64
+Source: glibc
+
+Package: libc-bin
+Version: 2.36-9
+Architecture: amd64
+Source: glibc
+Depends: libc6
+
+Package: hello
+Version: 2.10-3+b1
+Architecture: amd64
+Source: hello (2.10-3)
+Depends: libc6
+";
+        let mut uni = Universe::new("amd64", vec![Packages::new_test(src).unwrap()]).unwrap();
+        let requirements = ["hello", "libc-bin"].map(|name| Dependency::try_from(name).unwrap());
+        let problem = uni.problem(requirements, vec![], vec![]);
+        let solution = uni.solve(problem).unwrap();
+        let groups = uni.by_source(&solution);
+        assert_eq!(groups.get("glibc").unwrap().binaries, ["libc-bin", "libc6"]);
+        assert_eq!(
+            groups.to_string(),
+            "Fetch source glibc=2.36-9 (libc-bin libc6)\nFetch source hello=2.10-3 (hello)\n"
+        );
+    }
+
+    #[test]
+    fn test_phasing() {
+        let src = "\
+Package: hello
+Version: 2.10-2
+Architecture: amd64
+
+Package: hello
+Version: 2.10-3
+Architecture: amd64
+Phased-Update-Percentage: 70
+";
+        let mut uni = Universe::new("amd64", vec![Packages::new_test(src).unwrap()]).unwrap();
+        uni.set_candidate_ordering(CandidateOrdering::Newest);
+        let mut versions = vec![];
+        for phasing in [
+            Phasing::machine_id("0123456789abcdef0123456789abcdef"),
+            Phasing::Always,
+        ] {
+            uni.set_installed(Installed::new().package("hello", "2.10-2").phasing(phasing));
+            let problem = uni.problem(vec![Dependency::try_from("hello").unwrap()], vec![], vec![]);
+            let solution = uni.solve(problem).unwrap();
+            versions.push(uni.package(solution[0]).version().to_string());
+        }
+        assert_eq!(versions, ["2.10-2", "2.10-3"]);
+    }
+
+    #[test]
+    fn test_forbid() {
+        let src = "\
+Package: alpha
+Version: 1.0
+Architecture: amd64
+Depends: libssl1.1 | libssl3, delta
+
+Package: libssl1.1
+Version: 1.1.1
+Architecture: amd64
+
+Package: libssl3
+Version: 3.0.15
+Architecture: amd64
+
+Package: delta
+Version: 1.0
+Architecture: amd64
+
+Package: delta
+Version: 2.0
+Architecture: amd64
+";
+        let solve = |forbid: Vec<&str>| {
+            let mut uni = Universe::new("amd64", vec![Packages::new_test(src).unwrap()]).unwrap();
+            let problem = uni.problem(
+                vec![Dependency::try_from("alpha").unwrap()],
+                vec![],
+                forbid.into_iter().map(|c| Constraint::try_from(c).unwrap()),
+            );
+            uni.solve(problem).map(|solution| {
+                let mut names: Vec<_> = solution
+                    .iter()
+                    .map(|id| uni.package(*id).full_name().to_string())
+                    .collect();
+                names.sort();
+                names
+            })
+        };
+        assert_eq!(
+            solve(vec![]).unwrap(),
+            ["alpha=1.0", "delta=1.0", "libssl1.1=1.1.1"]
+        );
+        assert_eq!(
+            solve(vec!["libssl1.1", "delta (<< 2.0)"]).unwrap(),
+            ["alpha=1.0", "delta=2.0", "libssl3=3.0.15"]
+        );
+        assert!(solve(vec!["delta:any"]).is_err());
+    }
+
+    #[test]
+    fn test_installability() {
+        let src = "\
+Package: alpha
+Version: 1.0
+Architecture: amd64
+Depends: beta
+
+Package: beta
+Version: 1.0
+Architecture: amd64
+
+Package: beta
+Version: 2.0
+Architecture: amd64
+Depends: missing
+
+Package: gamma
+Version: 1.0
+Architecture: all
+Conflicts: beta
+";
+        let mut uni = Universe::new("amd64", vec![Packages::new_test(src).unwrap()]).unwrap();
+        let uninstallable: Vec<_> = uni
+            .check_installable_all()
+            .into_iter()
+            .map(|(id, err)| (uni.package(id).full_name().to_string(), err))
+            .collect();
+        assert_eq!(uninstallable.len(), 1);
+        assert_eq!(uninstallable[0].0, "beta=2.0");
+        assert!(matches!(&uninstallable[0].1, Error::Solver(msg) if msg.contains("missing")));
+        assert_eq!(
+            uni.check_coinstallable(&["alpha", "beta"]).unwrap().len(),
+            2
+        );
+        assert!(uni.check_coinstallable(&["alpha", "gamma"]).is_err());
+        assert!(uni
+            .check_coinstallable(&["alpha", "beta (>= 2.0)"])
+            .is_err());
+        assert!(matches!(
+            uni.check_coinstallable(&["alpha ("]),
+            Err(Error::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn test_tasks() {
+        let src = "\
+Package: task-ssh-server
+Version: 3.73
+Architecture: all
+Task: ssh-server
+Depends: tasksel (= 3.73)
+
+Package: openssh-server
+Version: 1:9.2p1-2
+Architecture: amd64
+Task: ssh-server
+
+Package: tasksel
+Version: 3.73
+Architecture: all
+
+Package: less
+Version: 590-2
+Architecture: amd64
+Priority: standard
+
+Package: task-laptop
+Version: 3.73
+Architecture: all
+Task: laptop
+";
+        let mut uni = Universe::new("amd64", vec![Packages::new_test(src).unwrap()]).unwrap();
+        uni.set_tasks(
+            Task::parse_descs(
+                "Task: standard\nPackages: standard\n\nTask: laptop\nPackages: list\n powertop\n",
+            )
+            .unwrap(),
+        );
+        assert_eq!(
+            uni.task_members("ssh-server").unwrap(),
+            ["openssh-server", "task-ssh-server"]
+        );
+        assert_eq!(uni.task_members("standard").unwrap(), ["less"]);
+        assert!(uni.task_members("laptop").is_err());
+        assert!(uni.task_members("gaming").is_err());
+        let problem = uni.problem(
+            vec![Dependency::try_from("task:gaming").unwrap()],
+            vec![],
+            vec![],
+        );
+        assert!(uni.solve(problem).is_err());
+        let problem = uni.problem(
+            vec![Dependency::try_from("task:ssh-server").unwrap()],
+            vec![],
+            vec![],
+        );
+        let solution = uni.solve(problem).unwrap();
+        let mut names: Vec<_> = solution.iter().map(|id| uni.package(*id).name()).collect();
+        names.sort();
+        assert_eq!(names, ["openssh-server", "task-ssh-server", "tasksel"]);
+    }
+
     test_solution!(self_dependent
     [ "alpha" ] => [ "alpha:amd64=1.0" ],
 "Package: alpha
@@ -918,4 +3727,35 @@ Package: xkb-data
 Version: 2.35.1-1
 Architecture: all
 ");
+
+    #[test]
+    fn test_progress() {
+        #[derive(Default)]
+        struct Steps(std::sync::Mutex<Vec<(usize, Option<usize>)>>);
+        impl Progress for Steps {
+            fn on_solve_step(&self, step: usize, total: Option<usize>) {
+                self.0.lock().unwrap().push((step, total));
+            }
+        }
+        let index = |src: &'static str| Packages::new_test(src).unwrap();
+        let steps = Arc::new(Steps::default());
+        let mut uni = Universe::new_with_progress(
+            "amd64",
+            [
+                index("Package: alpha\nVersion: 1\nArchitecture: all\nDepends: beta\n"),
+                index("Package: beta\nVersion: 1\nArchitecture: all\n"),
+            ],
+            steps.clone(),
+        )
+        .unwrap();
+        assert_eq!(*steps.0.lock().unwrap(), [(1, Some(2)), (2, Some(2))]);
+        steps.0.lock().unwrap().clear();
+        uni.check_coinstallable(&["alpha"]).unwrap();
+        let solved = steps.0.lock().unwrap().clone();
+        assert!(solved.len() >= 2);
+        assert!(solved
+            .iter()
+            .enumerate()
+            .all(|(n, step)| *step == (n + 1, None)));
+    }
 }
@@ -0,0 +1,190 @@
+//! Bootstrap variants
+//!
+//! A [`Variant`] names the package set of a base system, as the
+//! `--variant` option of debootstrap and mmdebstrap does:
+//!
+//! ```ignore
+//! let variant: Variant = "buildd".parse()?;
+//! let solution = universe.variant_system(variant)?;
+//! ```
+
+use {
+    crate::{
+        control::ParseError,
+        error::Error,
+        filter::PackageFilter,
+        packages::Priority,
+        universe::Universe,
+        version::{Dependency, Version},
+    },
+    resolvo::SolvableId,
+    std::{fmt, str::FromStr},
+};
+
+// A requirement on a package of the universe.
+type Requirement<'a> = Dependency<Option<&'a str>, &'a str, Version<&'a str>>;
+
+/// The package set of a base system. Each variant includes the previous
+/// one, except [`Variant::Buildd`], which only adds to [`Variant::Minbase`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Variant {
+    /// The essential, protected and required packages
+    #[default]
+    Minbase,
+    /// Minbase and `build-essential`, for building packages
+    Buildd,
+    /// Minbase and the packages of priority important, the default of
+    /// debootstrap
+    Important,
+    /// Important and the packages of priority standard
+    Standard,
+}
+
+impl Variant {
+    pub const ALL: [Variant; 4] = [
+        Variant::Minbase,
+        Variant::Buildd,
+        Variant::Important,
+        Variant::Standard,
+    ];
+    pub fn name(&self) -> &'static str {
+        match self {
+            Variant::Minbase => "minbase",
+            Variant::Buildd => "buildd",
+            Variant::Important => "important",
+            Variant::Standard => "standard",
+        }
+    }
+    /// The packages selected by their fields, besides the essential and
+    /// required ones every problem includes.
+    pub fn filter(&self) -> PackageFilter {
+        match self {
+            Variant::Minbase | Variant::Buildd => PackageFilter::Protected,
+            Variant::Important => PackageFilter::Protected | Priority::Important,
+            Variant::Standard => {
+                PackageFilter::Protected | Priority::Important | Priority::Standard
+            }
+        }
+    }
+    /// The packages selected by name.
+    pub fn seeds(&self) -> &'static [&'static str] {
+        match self {
+            Variant::Buildd => &["build-essential"],
+            _ => &[],
+        }
+    }
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Variant {
+    type Err = ParseError;
+    /// Parses a variant name, also accepting `required` for minbase and
+    /// `debootstrap` for important, as mmdebstrap does.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "required" => Ok(Variant::Minbase),
+            "debootstrap" | "-" => Ok(Variant::Important),
+            _ => Variant::ALL
+                .into_iter()
+                .find(|variant| variant.name() == s)
+                .ok_or_else(|| ParseError::from(format!("unknown variant {}", s))),
+        }
+    }
+}
+
+impl<S: AsRef<str> + 'static> Universe<S> {
+    /// Returns the requirements of the base system of `variant`, one for
+    /// each package name it selects, to be combined with others.
+    pub fn variant_requirements(
+        &self,
+        variant: Variant,
+    ) -> Result<Vec<Requirement<'_>>, ParseError> {
+        // Essential and required packages are part of every problem.
+        let filter = variant.filter();
+        let mut names: Vec<&str> = self
+            .packages()
+            .filter(|p| filter.matches(p))
+            .map(|p| p.name())
+            .chain(variant.seeds().iter().copied())
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+        names.into_iter().map(Dependency::try_from).collect()
+    }
+    /// Resolves the base system of `variant`: the closure of the packages
+    /// it selects.
+    pub fn variant_system(&mut self, variant: Variant) -> Result<Vec<SolvableId>, Error> {
+        let requirements = self.variant_requirements(variant)?;
+        let problem = self.problem(requirements, std::iter::empty(), std::iter::empty());
+        self.solve(problem).map_err(|err| self.solver_error(err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::packages::Packages};
+
+    #[test]
+    fn test_variant_system() {
+        let src = "\
+Package: base-files
+Version: 12.4
+Architecture: amd64
+Essential: yes
+Priority: required
+
+Package: init
+Version: 1.65
+Architecture: amd64
+Priority: important
+Important: yes
+
+Package: apt
+Version: 2.6.1
+Architecture: amd64
+Priority: important
+
+Package: less
+Version: 590-2
+Architecture: amd64
+Priority: standard
+
+Package: build-essential
+Version: 12.9
+Architecture: amd64
+Priority: optional
+Depends: gcc
+
+Package: gcc
+Version: 12.2
+Architecture: amd64
+Priority: optional
+";
+        let mut uni = Universe::new("amd64", [Packages::new_test(src).unwrap()]).unwrap();
+        let mut names = |variant: &str| {
+            let variant = variant.parse().unwrap();
+            let mut names: Vec<String> = uni
+                .variant_system(variant)
+                .unwrap()
+                .into_iter()
+                .map(|id| uni.package(id).name().to_string())
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(names("minbase"), ["base-files", "init"]);
+        assert_eq!(
+            names("buildd"),
+            ["base-files", "build-essential", "gcc", "init"]
+        );
+        assert_eq!(names("debootstrap"), ["apt", "base-files", "init"]);
+        assert_eq!(names("standard"), ["apt", "base-files", "init", "less"]);
+        assert!("fat".parse::<Variant>().is_err());
+        assert_eq!(Variant::Buildd.to_string(), "buildd");
+    }
+}
@@ -0,0 +1,240 @@
+//! Repository verification
+//!
+//! [`DebRepo::verify`] audits a mirror: it checks the size and digests of
+//! every file listed in a Release file and, optionally, of every pool file
+//! listed in its Packages indexes. The results come as a stream, one per
+//! file, as the files are checked:
+//!
+//! ```ignore
+//! let release = repo.fetch_verify_release_with_keys("bookworm", [DEBIAN_KEYRING]).await?;
+//! let mut results = pin!(repo.verify(&release, true));
+//! while let Some(file) = results.next().await {
+//!     if let Err(err) = &file.result {
+//!         println!("{}: {}", file.path, err);
+//!     }
+//! }
+//! ```
+//!
+//! Note that Release files list the uncompressed indexes too, which
+//! mirrors usually omit: these come out as [`Error::NotFound`].
+
+use {
+    crate::{
+        digest::Digests,
+        error::{Error, Result},
+        release::Release,
+        repo::DebRepo,
+    },
+    futures::stream::{self, Stream},
+    std::collections::{HashSet, VecDeque},
+};
+
+/// The result of the check of a file of a repository.
+#[derive(Debug)]
+pub struct VerifiedFile {
+    pub path: String,
+    /// The size listed in the index
+    pub size: usize,
+    pub result: Result<()>,
+}
+
+impl VerifiedFile {
+    pub fn is_ok(&self) -> bool {
+        self.result.is_ok()
+    }
+    /// Returns true if the file is missing from the repository.
+    pub fn is_missing(&self) -> bool {
+        matches!(self.result, Err(Error::NotFound(_)))
+    }
+}
+
+enum Check {
+    File(String, usize, Digests),
+    // the pool files of the Packages index of a component and architecture
+    Pool(String, String),
+    // a file that could not be listed for checking
+    Invalid(String, usize, Error),
+}
+
+struct Verifier<'a> {
+    repo: &'a DebRepo,
+    release: &'a Release,
+    queue: VecDeque<Check>,
+    seen: HashSet<String>,
+}
+
+impl Verifier<'_> {
+    async fn next(&mut self) -> Option<VerifiedFile> {
+        loop {
+            match self.queue.pop_front()? {
+                Check::File(path, size, digests) => {
+                    let result = self
+                        .repo
+                        .copy_verify(futures::io::sink(), &path, size, digests)
+                        .await
+                        .map(|_| ());
+                    return Some(VerifiedFile { path, size, result });
+                }
+                Check::Invalid(path, size, err) => {
+                    return Some(VerifiedFile {
+                        path,
+                        size,
+                        result: Err(err),
+                    });
+                }
+                Check::Pool(component, arch) => {
+                    let packages = match self.release.fetch_packages(&component, &arch).await {
+                        Ok(packages) => packages,
+                        Err(err) => {
+                            let (path, size) = match self.release.packages_file(&component, &arch) {
+                                Some((path, size, _)) => (path, size),
+                                None => (format!("{}/binary-{}/Packages", component, arch), 0),
+                            };
+                            self.queue.push_front(Check::Invalid(path, size, err));
+                            continue;
+                        }
+                    };
+                    for package in packages.packages() {
+                        match package.repo_file() {
+                            Ok((path, size, digests)) => {
+                                if self.seen.insert(path.to_string()) {
+                                    self.queue.push_back(Check::File(
+                                        path.to_string(),
+                                        size,
+                                        digests,
+                                    ));
+                                }
+                            }
+                            Err(err) => self.queue.push_back(Check::Invalid(
+                                package.to_string(),
+                                0,
+                                err.into(),
+                            )),
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl DebRepo {
+    /// Checks the files listed in `release` against their sizes and
+    /// digests, then, if `pool` is set, the files listed in its Packages
+    /// indexes. The Release file itself is taken as is, e.g. as verified by
+    /// [`DebRepo::fetch_verify_release`].
+    pub fn verify<'a>(
+        &'a self,
+        release: &'a Release,
+        pool: bool,
+    ) -> impl Stream<Item = VerifiedFile> + 'a {
+        let mut queue: VecDeque<Check> = release
+            .repo_files()
+            .into_iter()
+            .map(|(path, size, digests)| Check::File(path, size, digests))
+            .collect();
+        if pool {
+            for component in release.components() {
+                for arch in release.architectures() {
+                    if release.packages_file(component, arch).is_some() {
+                        queue.push_back(Check::Pool(component.to_string(), arch.to_string()));
+                    }
+                }
+            }
+        }
+        let verifier = Verifier {
+            repo: self,
+            release,
+            queue,
+            seen: HashSet::new(),
+        };
+        stream::unfold(verifier, |mut verifier| async move {
+            let file = verifier.next().await?;
+            Some((file, verifier))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{control::MutableControlStanza, fsrepo::FSDebRepo, publish::Publisher},
+        async_std::fs,
+        digest::Digest,
+        futures::StreamExt,
+    };
+
+    // Writes a pool file for the package `name` and returns its stanza.
+    async fn pool_file(root: &std::path::Path, name: &'static str) -> MutableControlStanza {
+        let path = format!("pool/main/{}_1.0_amd64.deb", name);
+        let data = format!("{} contents", name);
+        fs::create_dir_all(root.join("pool/main")).await.unwrap();
+        fs::write(root.join(&path), &data).await.unwrap();
+        let mut stanza = MutableControlStanza::new();
+        stanza
+            .set("Package", name)
+            .set("Version", "1.0")
+            .set("Architecture", "amd64")
+            .set("Filename", path)
+            .set("Size", data.len().to_string())
+            .set("SHA256", hex::encode(sha2::Sha256::digest(data.as_bytes())));
+        stanza
+    }
+
+    #[async_std::test]
+    async fn test_verify() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut publisher = Publisher::new(dir.path(), "stable");
+        for name in ["alpha", "beta"] {
+            let stanza = pool_file(dir.path(), name).await;
+            publisher.add_package("main", stanza).unwrap();
+        }
+        publisher.publish().await.unwrap();
+        fs::write(
+            dir.path().join("pool/main/beta_1.0_amd64.deb"),
+            "beta changed",
+        )
+        .await
+        .unwrap();
+        let repo: DebRepo = FSDebRepo::new(dir.path()).await.unwrap().into();
+        let release = repo.fetch_release("stable").await.unwrap();
+        let files: Vec<VerifiedFile> = repo.verify(&release, false).collect().await;
+        assert!(!files.is_empty());
+        assert!(files.iter().all(|file| file.is_ok()));
+        let files: Vec<VerifiedFile> = repo.verify(&release, true).collect().await;
+        let failed: Vec<&str> = files
+            .iter()
+            .filter(|file| !file.is_ok())
+            .map(|file| file.path.as_str())
+            .collect();
+        assert_eq!(failed, ["pool/main/beta_1.0_amd64.deb"]);
+        assert!(files
+            .iter()
+            .any(|file| file.path == "pool/main/alpha_1.0_amd64.deb"));
+    }
+
+    #[async_std::test]
+    async fn test_verify_invalid_stanza() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut publisher = Publisher::new(dir.path(), "stable");
+        for name in ["alpha", "beta", "gamma"] {
+            let mut stanza = pool_file(dir.path(), name).await;
+            if name == "beta" {
+                stanza.remove("SHA256");
+            }
+            publisher.add_package("main", stanza).unwrap();
+        }
+        publisher.publish().await.unwrap();
+        let repo: DebRepo = FSDebRepo::new(dir.path()).await.unwrap().into();
+        let release = repo.fetch_release("stable").await.unwrap();
+        let files: Vec<VerifiedFile> = repo.verify(&release, true).collect().await;
+        let failed: Vec<&VerifiedFile> = files.iter().filter(|file| !file.is_ok()).collect();
+        assert_eq!(failed.len(), 1);
+        assert!(failed[0].path.contains("beta"));
+        for name in ["alpha", "gamma"] {
+            let path = format!("pool/main/{}_1.0_amd64.deb", name);
+            assert!(files.iter().any(|file| file.path == path && file.is_ok()));
+        }
+    }
+}
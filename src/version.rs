@@ -1,12 +1,13 @@
 ///
 use {
+    crate::{arch, control::ParseError},
     smallvec::{smallvec, SmallVec},
     std::{
         cmp::Ordering,
         fmt::{self, Debug, Display, Formatter},
         hash::{Hash, Hasher},
+        ops::Bound,
     },
-    crate::control::ParseError,
 };
 
 /// Defines a method to check if a given value satisfies a specific requirement.
@@ -162,6 +163,13 @@ impl<A, N, V> Constraint<A, N, V> {
     pub fn into_range(self) -> VersionSet<V> {
         self.range
     }
+    /// The constraint matching the versions this one does not match.
+    pub fn complement(self) -> Self {
+        Self {
+            range: self.range.complement(),
+            ..self
+        }
+    }
 }
 
 impl<A: Hash + Eq, N: Hash + Eq, V: Hash + Eq> Hash for Constraint<A, N, V> {
@@ -264,9 +272,7 @@ impl<A, N, V> Dependency<A, N, V> {
                 let single: SmallVec<[Constraint<A, N, V>; 2]> = smallvec![dep];
                 single.into_iter()
             }
-            Self::Union(deps) => {
-                deps.into_iter()
-            }
+            Self::Union(deps) => deps.into_iter(),
         }
     }
 }
@@ -395,6 +401,19 @@ impl<V: Display> Debug for VersionSet<V> {
 }
 
 impl<V> VersionSet<V> {
+    /// The set of the versions not in this one.
+    pub fn complement(self) -> Self {
+        match self {
+            Self::Any => Self::None,
+            Self::StrictlyEarlierThan(v) => Self::LaterOrEqualThan(v),
+            Self::EarlierOrEqualThan(v) => Self::StrictlyLaterThan(v),
+            Self::Exactly(v) => Self::Except(v),
+            Self::Except(v) => Self::Exactly(v),
+            Self::LaterOrEqualThan(v) => Self::StrictlyEarlierThan(v),
+            Self::StrictlyLaterThan(v) => Self::EarlierOrEqualThan(v),
+            Self::None => Self::Any,
+        }
+    }
     pub fn version(&self) -> Option<&V> {
         match self {
             Self::Any | Self::None => None,
@@ -430,6 +449,226 @@ impl<V> VersionSet<V> {
     }
 }
 
+/// A set of versions built from [`VersionSet`]s with intersections and
+/// unions, kept as sorted disjoint intervals. Redundant bounds vanish on the
+/// way, so `>= 1.0, >= 1.2, << 2` comes back from
+/// [`VersionRange::to_version_sets`] as `>= 1.2, << 2`.
+#[derive(Clone, Debug)]
+pub struct VersionRange<V> {
+    intervals: Vec<Interval<V>>,
+}
+
+type Interval<V> = (Bound<Version<V>>, Bound<Version<V>>);
+
+// Orders bounds by their version; at the same version an included lower
+// bound comes before an excluded one, and the other way round for upper
+// bounds.
+fn cmp_bounds<V: Eq + AsRef<str>>(
+    a: &Bound<Version<V>>,
+    b: &Bound<Version<V>>,
+    upper: bool,
+) -> Ordering {
+    let unbounded = if upper {
+        Ordering::Greater
+    } else {
+        Ordering::Less
+    };
+    match (a, b) {
+        (Bound::Unbounded, Bound::Unbounded) => Ordering::Equal,
+        (Bound::Unbounded, _) => unbounded,
+        (_, Bound::Unbounded) => unbounded.reverse(),
+        (Bound::Included(x), Bound::Included(y)) | (Bound::Excluded(x), Bound::Excluded(y)) => {
+            x.cmp(y)
+        }
+        (Bound::Included(x), Bound::Excluded(y)) => x.cmp(y).then(unbounded),
+        (Bound::Excluded(x), Bound::Included(y)) => x.cmp(y).then(unbounded.reverse()),
+    }
+}
+
+// Returns true if an interval ending at `upper` and one starting at `lower`
+// overlap or meet, so that they merge into one.
+fn bounds_meet<V: Eq + AsRef<str>>(upper: &Bound<Version<V>>, lower: &Bound<Version<V>>) -> bool {
+    match (upper, lower) {
+        (Bound::Unbounded, _) | (_, Bound::Unbounded) => true,
+        (Bound::Excluded(u), Bound::Excluded(l)) => u > l,
+        (Bound::Included(u) | Bound::Excluded(u), Bound::Included(l) | Bound::Excluded(l)) => {
+            u >= l
+        }
+    }
+}
+
+impl<V: Eq + AsRef<str> + Clone> VersionRange<V> {
+    /// The range of no versions.
+    pub fn empty() -> Self {
+        Self { intervals: vec![] }
+    }
+    /// The range of all versions.
+    pub fn any() -> Self {
+        Self {
+            intervals: vec![(Bound::Unbounded, Bound::Unbounded)],
+        }
+    }
+    /// The versions satisfying all of `sets`, e.g. the constraints on one
+    /// package in a dependency field.
+    pub fn all_of<'s>(sets: impl IntoIterator<Item = &'s VersionSet<Version<V>>>) -> Self
+    where
+        V: 's,
+    {
+        sets.into_iter()
+            .fold(Self::any(), |range, set| range.intersect(&set.into()))
+    }
+    /// The versions satisfying any of `sets`, e.g. the alternatives of one
+    /// package in a dependency field.
+    pub fn any_of<'s>(sets: impl IntoIterator<Item = &'s VersionSet<Version<V>>>) -> Self
+    where
+        V: 's,
+    {
+        sets.into_iter()
+            .fold(Self::empty(), |range, set| range.union(&set.into()))
+    }
+    pub fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+    pub fn contains(&self, version: &Version<V>) -> bool {
+        let point = Bound::Included(version.clone());
+        self.intervals.iter().any(|(lower, upper)| {
+            cmp_bounds(lower, &point, false).is_le() && cmp_bounds(upper, &point, true).is_ge()
+        })
+    }
+    /// The versions in both ranges.
+    pub fn intersect(&self, other: &Self) -> Self {
+        let mut intervals = vec![];
+        for (a_lower, a_upper) in &self.intervals {
+            for (b_lower, b_upper) in &other.intervals {
+                let lower = std::cmp::max_by(a_lower, b_lower, |a, b| cmp_bounds(a, b, false));
+                let upper = std::cmp::min_by(a_upper, b_upper, |a, b| cmp_bounds(a, b, true));
+                intervals.push((lower.clone(), upper.clone()));
+            }
+        }
+        Self::normalized(intervals)
+    }
+    /// The versions in either range.
+    pub fn union(&self, other: &Self) -> Self {
+        Self::normalized(
+            self.intervals
+                .iter()
+                .chain(&other.intervals)
+                .cloned()
+                .collect(),
+        )
+    }
+    /// Returns the range as the shortest list of version sets that must all
+    /// be satisfied, or `None` if it cannot be written this way, e.g. for
+    /// `<< 1 | >> 2`.
+    pub fn to_version_sets(&self) -> Option<Vec<VersionSet<Version<V>>>> {
+        let (Some(first), Some(last)) = (self.intervals.first(), self.intervals.last()) else {
+            return Some(vec![VersionSet::None]);
+        };
+        if let (Bound::Included(l), Bound::Included(u)) = first {
+            if l == u && self.intervals.len() == 1 {
+                return Some(vec![VersionSet::Exactly(l.clone())]);
+            }
+        }
+        let mut sets = vec![];
+        match &first.0 {
+            Bound::Included(v) => sets.push(VersionSet::LaterOrEqualThan(v.clone())),
+            Bound::Excluded(v) => sets.push(VersionSet::StrictlyLaterThan(v.clone())),
+            Bound::Unbounded => {}
+        }
+        // intervals can only be split by excluded single versions
+        for (a, b) in self.intervals.iter().zip(&self.intervals[1..]) {
+            match (&a.1, &b.0) {
+                (Bound::Excluded(u), Bound::Excluded(l)) if u == l => {
+                    sets.push(VersionSet::Except(u.clone()))
+                }
+                _ => return None,
+            }
+        }
+        match &last.1 {
+            Bound::Included(v) => sets.push(VersionSet::EarlierOrEqualThan(v.clone())),
+            Bound::Excluded(v) => sets.push(VersionSet::StrictlyEarlierThan(v.clone())),
+            Bound::Unbounded => {}
+        }
+        if sets.is_empty() {
+            sets.push(VersionSet::Any);
+        }
+        Some(sets)
+    }
+    // Drops empty intervals, sorts the rest and merges the overlapping ones.
+    fn normalized(mut intervals: Vec<Interval<V>>) -> Self {
+        intervals.retain(|(lower, upper)| match (lower, upper) {
+            (Bound::Unbounded, _) | (_, Bound::Unbounded) => true,
+            (Bound::Included(l), Bound::Included(u)) => l <= u,
+            (Bound::Included(l) | Bound::Excluded(l), Bound::Included(u) | Bound::Excluded(u)) => {
+                l < u
+            }
+        });
+        intervals.sort_by(|a, b| cmp_bounds(&a.0, &b.0, false));
+        let mut merged: Vec<Interval<V>> = vec![];
+        for (lower, upper) in intervals {
+            match merged.last_mut() {
+                Some(last) if bounds_meet(&last.1, &lower) => {
+                    if cmp_bounds(&upper, &last.1, true).is_gt() {
+                        last.1 = upper;
+                    }
+                }
+                _ => merged.push((lower, upper)),
+            }
+        }
+        Self { intervals: merged }
+    }
+}
+
+impl<V: Eq + AsRef<str> + Clone> From<&VersionSet<Version<V>>> for VersionRange<V> {
+    fn from(set: &VersionSet<Version<V>>) -> Self {
+        let interval = |lower, upper| Self {
+            intervals: vec![(lower, upper)],
+        };
+        match set {
+            VersionSet::Any => Self::any(),
+            VersionSet::StrictlyEarlierThan(v) => {
+                interval(Bound::Unbounded, Bound::Excluded(v.clone()))
+            }
+            VersionSet::EarlierOrEqualThan(v) => {
+                interval(Bound::Unbounded, Bound::Included(v.clone()))
+            }
+            VersionSet::Exactly(v) => {
+                interval(Bound::Included(v.clone()), Bound::Included(v.clone()))
+            }
+            VersionSet::Except(v) => Self {
+                intervals: vec![
+                    (Bound::Unbounded, Bound::Excluded(v.clone())),
+                    (Bound::Excluded(v.clone()), Bound::Unbounded),
+                ],
+            },
+            VersionSet::LaterOrEqualThan(v) => {
+                interval(Bound::Included(v.clone()), Bound::Unbounded)
+            }
+            VersionSet::StrictlyLaterThan(v) => {
+                interval(Bound::Excluded(v.clone()), Bound::Unbounded)
+            }
+            VersionSet::None => Self::empty(),
+        }
+    }
+}
+
+impl<V: Eq + AsRef<str> + Clone> VersionSet<Version<V>> {
+    /// The versions satisfying both sets.
+    pub fn intersect(&self, other: &Self) -> VersionRange<V> {
+        VersionRange::from(self).intersect(&other.into())
+    }
+    /// The versions satisfying either set.
+    pub fn union(&self, other: &Self) -> VersionRange<V> {
+        VersionRange::from(self).union(&other.into())
+    }
+    pub fn is_empty(&self) -> bool {
+        matches!(self, VersionSet::None)
+    }
+    pub fn contains(&self, version: &Version<V>) -> bool {
+        version.satisfies(self)
+    }
+}
+
 /// Version represents a single version number
 #[derive(Clone, Default)]
 pub struct Version<V> {
@@ -478,6 +717,116 @@ where
     }
 }
 
+impl<V: AsRef<str>> Version<V> {
+    /// Compares the versions exactly as `dpkg --compare-versions` does. The
+    /// epoch, the upstream version and the revision are compared in turn, a
+    /// missing epoch or revision counting as `0`. Malformed versions are
+    /// compared the same way rather than rejected.
+    pub fn cmp_dpkg<W: AsRef<str>>(&self, other: &Version<W>) -> Ordering {
+        comparator::compare(
+            self.inner.as_ref().as_bytes(),
+            other.inner.as_ref().as_bytes(),
+        )
+    }
+    // The epoch, upstream version and revision, split as dpkg does.
+    fn parts(&self) -> (Option<&str>, &str, Option<&str>) {
+        let version = self.inner.as_ref();
+        let (epoch, rest) = match version.split_once(':') {
+            Some((epoch, rest)) => (Some(epoch), rest),
+            None => (None, version),
+        };
+        match rest.rsplit_once('-') {
+            Some((upstream, revision)) => (epoch, upstream, Some(revision)),
+            None => (epoch, rest, None),
+        }
+    }
+    /// The epoch, `0` if missing or malformed.
+    pub fn epoch(&self) -> u32 {
+        self.parts().0.and_then(|e| e.parse().ok()).unwrap_or(0)
+    }
+    /// The upstream version, without the epoch and the revision.
+    pub fn upstream(&self) -> &str {
+        self.parts().1
+    }
+    /// The debian revision, `None` for native packages.
+    pub fn debian_revision(&self) -> Option<&str> {
+        self.parts().2
+    }
+    /// Returns the version with the debian revision replaced.
+    pub fn with_revision(&self, revision: &str) -> Version<String> {
+        let mut version = match self.parts().0 {
+            Some(epoch) => format!("{}:{}", epoch, self.upstream()),
+            None => self.upstream().to_string(),
+        };
+        if !revision.is_empty() {
+            version.push('-');
+            version.push_str(revision);
+        }
+        Version { inner: version }
+    }
+    /// Returns the version of the next binary-only rebuild (binNMU):
+    /// `1.0-2` becomes `1.0-2+b1`, `1.0-2+b1` becomes `1.0-2+b2`. Native
+    /// versions get the suffix appended to the upstream version.
+    pub fn next_revision(&self) -> Version<String> {
+        let version = self.inner.as_ref();
+        let (base, nmu) = match version.rsplit_once("+b") {
+            Some((base, n)) if !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()) => {
+                (base, n.parse::<u64>().map_or(1, |n| n + 1))
+            }
+            _ => (version, 1),
+        };
+        Version {
+            inner: format!("{}+b{}", base, nmu),
+        }
+    }
+    /// Returns true if the version follows the `[epoch:]upstream[-revision]`
+    /// syntax, with a numeric epoch and an upstream version starting with a
+    /// digit.
+    pub fn is_valid(&self) -> bool {
+        let version = self.inner.as_ref();
+        let upstream = match version.split_once(':') {
+            Some((epoch, rest)) => {
+                if epoch.is_empty() || !epoch.bytes().all(|b| b.is_ascii_digit()) {
+                    return false;
+                }
+                rest
+            }
+            None => version,
+        };
+        upstream.bytes().next().is_some_and(|b| b.is_ascii_digit())
+            && upstream.bytes().all(|b| version_char(&b))
+    }
+}
+
+/// Evaluates `a op b` as `dpkg --compare-versions` does. The operators are
+/// `lt`, `le`, `eq`, `ne`, `ge` and `gt`, the obsolete `<<`, `<=`, `=`,
+/// `>=` and `>>`, as well as `<` and `>`, which dpkg takes for `<=` and
+/// `>=`. An empty version is earlier than any other, except for the `-nl`
+/// variants of `lt`, `le`, `ge` and `gt`, where it is later.
+pub fn compare_versions(a: &str, op: &str, b: &str) -> Result<bool, ParseError> {
+    let (op, empty_later) = match op.strip_suffix("-nl") {
+        Some(op @ ("lt" | "le" | "ge" | "gt")) => (op, true),
+        _ => (op, false),
+    };
+    let ordering = match (a.is_empty(), b.is_empty()) {
+        (true, true) => Ordering::Equal,
+        (true, false) if empty_later => Ordering::Greater,
+        (false, true) if empty_later => Ordering::Less,
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+        (false, false) => Version::from(a).cmp_dpkg(&Version::from(b)),
+    };
+    Ok(match op {
+        "lt" | "<<" => ordering.is_lt(),
+        "le" | "<=" | "<" => ordering.is_le(),
+        "eq" | "=" => ordering.is_eq(),
+        "ne" => ordering.is_ne(),
+        "ge" | ">=" | ">" => ordering.is_ge(),
+        "gt" | ">>" => ordering.is_gt(),
+        _ => return Err(ParseError::from(format!("unknown operator {}", op))),
+    })
+}
+
 impl<V: Hash> Hash for Version<V> {
     #[inline]
     fn hash<H: Hasher>(&self, state: &mut H) {
@@ -502,19 +851,12 @@ impl<V: Eq + AsRef<str>> PartialOrd for Version<V> {
 impl<V: Eq + AsRef<str>> Ord for Version<V> {
     #[inline]
     fn cmp(&self, other: &Self) -> Ordering {
-        let k = comparator::compare(
-            self.inner.as_ref().as_bytes(),
-            other.inner.as_ref().as_bytes(),
-        );
+        let k = self.cmp_dpkg(other);
         tracing::trace!(
-            "compare versions {} {} {}",
-            self.inner.as_ref(),
-            match k {
-                Ordering::Equal => "=",
-                Ordering::Less => "<",
-                Ordering::Greater => ">",
-            },
-            other.inner.as_ref(),
+            left = self.inner.as_ref(),
+            right = other.inner.as_ref(),
+            ordering = ?k,
+            "compare versions"
         );
         k
     }
@@ -546,6 +888,12 @@ impl<V: AsRef<str>> From<&Version<V>> for String {
     }
 }
 
+impl From<String> for Version<String> {
+    fn from(value: String) -> Self {
+        Version { inner: value }
+    }
+}
+
 impl<'a> From<&'a str> for Version<&'a str> {
     fn from(value: &'a str) -> Self {
         Version { inner: value }
@@ -745,67 +1093,145 @@ impl<'a> Parser<'a> {
     }
 }
 
-pub struct ParsedConstraintIterator<'a> {
+/// The architecture and the active build profiles used to evaluate the
+/// `[arch ...]` and `<profile ...>` restrictions of dependency fields, as in
+/// `foo [linux-any] <!nocheck>`.
+#[derive(Clone, Debug, Default)]
+pub struct Restrictions {
+    arch: Option<String>,
+    profiles: Vec<String>,
+}
+
+pub(crate) static UNRESTRICTED: Restrictions = Restrictions::new();
+
+impl Restrictions {
+    /// No architecture and no active profiles: architecture lists are
+    /// ignored and only relations not requiring a profile apply.
+    pub const fn new() -> Self {
+        Self {
+            arch: None,
+            profiles: Vec::new(),
+        }
+    }
+    /// Sets the host architecture that architecture lists are matched against.
+    pub fn arch(mut self, arch: impl Into<String>) -> Self {
+        self.arch = Some(arch.into());
+        self
+    }
+    /// Sets the active build profiles, e.g. `nocheck` or `nodoc`.
+    pub fn profiles<I, P>(mut self, profiles: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<String>,
+    {
+        self.set_profiles(profiles);
+        self
+    }
+    pub fn set_profiles<I, P>(&mut self, profiles: I)
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<String>,
+    {
+        self.profiles = profiles.into_iter().map(Into::into).collect();
+    }
+    pub fn is_active(&self, profile: &str) -> bool {
+        self.profiles.iter().any(|p| p == profile)
+    }
+    // `[amd64 arm64]` applies to the listed architectures, `[!hurd-any]` to
+    // all but the listed ones.
+    fn arch_matches(&self, list: &str) -> bool {
+        let Some(host) = self.arch.as_deref() else {
+            return true;
+        };
+        let mut negated = false;
+        let mut found = false;
+        for term in list.split_ascii_whitespace() {
+            match term.strip_prefix('!') {
+                Some(wildcard) if arch::matches(host, wildcard) => return false,
+                Some(_) => negated = true,
+                None => found |= arch::matches(host, term),
+            }
+        }
+        negated || found
+    }
+    // The terms of a single `<...>` group are and-ed.
+    fn profiles_match(&self, formula: &str) -> bool {
+        formula
+            .split_ascii_whitespace()
+            .all(|term| match term.strip_prefix('!') {
+                Some(profile) => !self.is_active(profile),
+                None => self.is_active(term),
+            })
+    }
+}
+
+pub struct ParsedConstraintIterator<'a, 'r> {
     straight: bool,
     parser: Parser<'a>,
+    restrictions: &'r Restrictions,
 }
 
-impl<'a> ParsedConstraintIterator<'a> {
-    pub(crate) fn new(src: &'a str, straight: bool) -> Self {
+impl<'a, 'r> ParsedConstraintIterator<'a, 'r> {
+    pub(crate) fn new(src: &'a str, straight: bool, restrictions: &'r Restrictions) -> Self {
         Self {
             straight,
             parser: Parser {
                 inp: src.as_bytes(),
             },
+            restrictions,
         }
     }
 }
 
-impl<'a> Iterator for ParsedConstraintIterator<'a> {
+impl<'a, 'r> Iterator for ParsedConstraintIterator<'a, 'r> {
     type Item = Result<Constraint<Option<&'a str>, &'a str, Version<&'a str>>, ParseError>;
     fn next(&mut self) -> Option<Self::Item> {
-        if self.parser.is_empty() {
-            None
-        } else {
-            match Constraint::parse_internal(&mut self.parser, self.straight) {
-                Ok(vs) => {
+        while !self.parser.is_empty() {
+            match Constraint::parse_restricted(&mut self.parser, self.straight, self.restrictions) {
+                Ok((vs, applies)) => {
                     self.parser.matches(b',');
-                    Some(Ok(vs))
+                    if applies {
+                        return Some(Ok(vs));
+                    }
                 }
-                err => Some(err),
+                Err(err) => return Some(Err(err)),
             }
         }
+        None
     }
 }
 
-pub(crate) struct ParsedDependencyIterator<'a> {
+pub(crate) struct ParsedDependencyIterator<'a, 'r> {
     parser: Parser<'a>,
+    restrictions: &'r Restrictions,
 }
 
-impl<'a> ParsedDependencyIterator<'a> {
-    pub(crate) fn new(src: &'a str) -> Self {
+impl<'a, 'r> ParsedDependencyIterator<'a, 'r> {
+    pub(crate) fn new(src: &'a str, restrictions: &'r Restrictions) -> Self {
         Self {
             parser: Parser {
                 inp: src.as_bytes(),
             },
+            restrictions,
         }
     }
 }
 
-impl<'a> Iterator for ParsedDependencyIterator<'a> {
+impl<'a, 'r> Iterator for ParsedDependencyIterator<'a, 'r> {
     type Item = Result<Dependency<Option<&'a str>, &'a str, Version<&'a str>>, ParseError>;
     fn next(&mut self) -> Option<Self::Item> {
-        if self.parser.is_empty() {
-            None
-        } else {
-            match Dependency::parse(&mut self.parser) {
+        while !self.parser.is_empty() {
+            match Dependency::parse(&mut self.parser, self.restrictions) {
                 Ok(dep) => {
                     self.parser.matches(b',');
-                    Some(Ok(dep))
+                    if dep.is_some() {
+                        return dep.map(Ok);
+                    }
                 }
-                err => Some(err),
+                Err(err) => return Some(Err(err)),
             }
         }
+        None
     }
 }
 
@@ -815,7 +1241,8 @@ impl<'a> TryFrom<&'a str> for Dependency<Option<&'a str>, &'a str, Version<&'a s
         let mut parser = Parser {
             inp: src.as_bytes(),
         };
-        let dep = Dependency::parse(&mut parser)?;
+        let dep = Dependency::parse(&mut parser, &UNRESTRICTED)?
+            .ok_or_else(|| ParseError::from("restricted dependency"))?;
         if parser.is_empty() {
             Ok(dep)
         } else {
@@ -824,6 +1251,16 @@ impl<'a> TryFrom<&'a str> for Dependency<Option<&'a str>, &'a str, Version<&'a s
     }
 }
 
+impl<'a> TryFrom<&'a str> for Constraint<Option<&'a str>, &'a str, Version<&'a str>> {
+    type Error = ParseError;
+    fn try_from(src: &'a str) -> Result<Self, Self::Error> {
+        match Dependency::try_from(src)? {
+            Dependency::Single(constraint) => Ok(constraint),
+            Dependency::Union(_) => Err("unexpected alternatives".into()),
+        }
+    }
+}
+
 pub struct ParsedProvidedNameIterator<'a> {
     parser: Parser<'a>,
 }
@@ -884,6 +1321,29 @@ impl<'a> Constraint<Option<&'a str>, &'a str, Version<&'a str>> {
         }
     }
     fn parse_internal(inp: &mut Parser<'a>, straight: bool) -> Result<Self, ParseError> {
+        Self::parse_restricted(inp, straight, &UNRESTRICTED).map(|(dep, _)| dep)
+    }
+    // Parses a relation with its optional architecture list and build profile
+    // formula. The flag tells whether the relation applies under
+    // `restrictions`.
+    fn parse_restricted(
+        inp: &mut Parser<'a>,
+        straight: bool,
+        restrictions: &Restrictions,
+    ) -> Result<(Self, bool), ParseError> {
+        let relation = Self::parse_relation(inp, straight)?;
+        let applies = relation
+            .archs
+            .is_none_or(|list| restrictions.arch_matches(list));
+        // Multiple `<...>` groups are or-ed.
+        let profiles = relation.profiles.is_empty()
+            || relation
+                .profiles
+                .iter()
+                .any(|formula| restrictions.profiles_match(formula));
+        Ok((relation.constraint, applies && profiles))
+    }
+    fn parse_relation(inp: &mut Parser<'a>, straight: bool) -> Result<RawRelation<'a>, ParseError> {
         if inp.is_empty() {
             return Err("dependency".into());
         }
@@ -898,7 +1358,65 @@ impl<'a> Constraint<Option<&'a str>, &'a str, Version<&'a str>> {
             None
         };
         let range = VersionSet::<Version<&'a str>>::parse(inp, straight)?;
-        Ok(Constraint { arch, name, range })
+        let mut archs = None;
+        if inp.matches(b'[').is_some() {
+            archs = Some(inp.parse_string_of(1, |&b: &u8| b != b']', "architecture list")?);
+            inp.parse(b']', "closing ']'")?;
+        }
+        let mut profiles = smallvec![];
+        while inp.matches(b'<').is_some() {
+            profiles.push(inp.parse_string_of(1, |&b: &u8| b != b'>', "build profile")?);
+            inp.parse(b'>', "closing '>'")?;
+        }
+        Ok(RawRelation {
+            constraint: Constraint { arch, name, range },
+            archs,
+            profiles,
+        })
+    }
+}
+
+/// A relation as written, with its architecture list and build profile
+/// formulas unevaluated.
+pub(crate) struct RawRelation<'a> {
+    pub constraint: Constraint<Option<&'a str>, &'a str, Version<&'a str>>,
+    pub archs: Option<&'a str>,
+    pub profiles: SmallVec<[&'a str; 1]>,
+}
+
+/// Iterates over the comma separated groups of alternatives of a relation
+/// field, keeping every relation regardless of its restrictions.
+pub(crate) struct RawRelationIterator<'a> {
+    parser: Parser<'a>,
+}
+
+impl<'a> RawRelationIterator<'a> {
+    pub(crate) fn new(src: &'a str) -> Self {
+        Self {
+            parser: Parser {
+                inp: src.as_bytes(),
+            },
+        }
+    }
+}
+
+impl<'a> Iterator for RawRelationIterator<'a> {
+    type Item = Result<SmallVec<[RawRelation<'a>; 2]>, ParseError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.parser.is_empty() {
+            return None;
+        }
+        let mut group = smallvec![];
+        loop {
+            match Constraint::parse_relation(&mut self.parser, true) {
+                Ok(relation) => group.push(relation),
+                Err(err) => return Some(Err(err)),
+            }
+            if self.parser.matches(b'|').is_none() {
+                self.parser.matches(b',');
+                return Some(Ok(group));
+            }
+        }
     }
 }
 
@@ -960,23 +1478,25 @@ impl<'a> VersionSet<Version<&'a str>> {
 }
 
 impl<'a> Dependency<Option<&'a str>, &'a str, Version<&'a str>> {
-    fn parse(inp: &mut Parser<'a>) -> Result<Self, ParseError> {
+    // Alternatives not applying under `restrictions` are dropped; returns
+    // None if none of them is left.
+    fn parse(
+        inp: &mut Parser<'a>,
+        restrictions: &Restrictions,
+    ) -> Result<Option<Self>, ParseError> {
         let mut union: SmallVec<[Constraint<Option<&'a str>, &'a str, Version<&'a str>>; 2]> =
             smallvec![];
         loop {
-            let dep = Constraint::parse_internal(inp, true)?;
-            match inp.matches(b'|') {
-                None => {
-                    if union.is_empty() {
-                        return Ok(Dependency::Single(dep));
-                    } else {
-                        union.push(dep);
-                        return Ok(Dependency::Union(union));
-                    }
-                }
-                Some(_) => {
-                    union.push(dep);
-                }
+            let (dep, applies) = Constraint::parse_restricted(inp, true, restrictions)?;
+            if applies {
+                union.push(dep);
+            }
+            if inp.matches(b'|').is_none() {
+                return Ok(match union.len() {
+                    0 => None,
+                    1 => union.pop().map(Dependency::Single),
+                    _ => Some(Dependency::Union(union)),
+                });
             }
         }
     }
@@ -1014,200 +1534,98 @@ impl<'a> Dependency<Option<&'a str>, &'a str, Version<&'a str>> {
 // These two steps (comparing and removing initial non-digit
 // strings and initial digit strings) are repeated until a difference
 // is found or both strings are exhausted.
+//
+// The epoch, the upstream version (up to the last hyphen) and the debian
+// revision are compared this way in turn.
 
 mod comparator {
     use std::cmp::Ordering;
 
-    type Result<T> = std::result::Result<T, Ordering>;
-
-    fn cmp<T: std::cmp::Ord + ?Sized>(this: &T, that: &T) -> Result<()> {
-        match std::cmp::Ord::cmp(this, that) {
-            Ordering::Equal => Ok(()),
-            other => Err(other),
-        }
-    }
-
-    fn cmp_alpha(this: u8, that: u8) -> Result<()> {
-        if this == that {
-            Ok(())
-        } else if this == b'-' {
-            Err(Ordering::Less)
-        } else if that == b'-' {
-            Err(Ordering::Greater)
-        } else if (this >= b'a' && this <= b'z') || (this >= b'A' && this <= b'Z') {
-            if (that >= b'a' && that <= b'z') || (that >= b'A' && that <= b'Z') {
-                cmp(&this, &that)
-            } else {
-                Err(Ordering::Less)
-            }
-        } else {
-            if (that >= b'a' && that <= b'z') || (that >= b'A' && that <= b'Z') {
-                Err(Ordering::Greater)
-            } else {
-                cmp(&this, &that)
-            }
+    // The weight of a non-digit character, or of the end of the part.
+    #[inline]
+    fn order(c: Option<&u8>) -> i32 {
+        match c {
+            None => 0,
+            Some(b'~') => -1,
+            Some(c) if c.is_ascii_alphabetic() => *c as i32,
+            Some(c) => *c as i32 + 256,
         }
     }
 
-    struct VersionComparator<'a> {
-        data: &'a [u8],
-    }
-
-    impl<'a> std::fmt::Debug for VersionComparator<'a> {
-        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-            f.write_str("<")?;
-            f.write_str(unsafe { std::str::from_utf8_unchecked(self.data) })?;
-            f.write_str(">")
-        }
+    #[inline]
+    fn non_digit(s: &[u8]) -> Option<&u8> {
+        s.first().filter(|c| !c.is_ascii_digit())
     }
 
-    impl<'a> VersionComparator<'a> {
-        #[inline]
-        fn advance(&mut self) {
-            self.data = &self.data[1..];
-        }
-        #[inline]
-        fn peek(&self) -> Option<u8> {
-            if self.data.len() == 0 {
-                None
-            } else {
-                Some(self.data[0])
-            }
-        }
-        #[inline]
-        fn peek_at(&self, pos: usize) -> Option<u8> {
-            if pos < self.data.len() {
-                Some(self.data[pos])
-            } else {
-                None
-            }
-        }
-        #[inline]
-        fn peek_char(&self) -> Option<u8> {
-            match self.peek() {
-                Some(c)
-                    if (c >= b'a' && c <= b'z')
-                        || (c >= b'A' && c <= b'Z')
-                        || c == b'.'
-                        || c == b'-'
-                        || c == b'+'
-                        || c == b'~' =>
-                {
-                    Some(c)
-                }
-                _ => None,
-            }
-        }
-        #[inline]
-        fn peek_number(&self, start: u32) -> (u32, usize) {
-            let mut result = start;
-            let mut pos = 0;
-            while pos < self.data.len() && self.data[pos] >= b'0' && self.data[pos] <= b'9' {
-                result = result * 10 + (self.data[pos] - b'0') as u32;
-                pos += 1;
-            }
-            (result, pos)
-        }
-        fn compare_initial(&mut self, other: &mut Self) -> Result<()> {
-            let (this_maybe_epoch, this_pos) = self.peek_number(0);
-            let (that_maybe_epoch, that_pos) = other.peek_number(0);
-            if self.peek_at(this_pos) == Some(b':') {
-                // this version contains epoch
-                if other.peek_at(that_pos) == Some(b':') {
-                    // that version contains epoch, too
-                    self.data = &self.data[this_pos + 1..];
-                    other.data = &other.data[that_pos + 1..];
-                    cmp(&this_maybe_epoch, &that_maybe_epoch)
-                } else if this_maybe_epoch > 0 {
-                    // that version has no epoch (i.e. epoch 0)
-                    Err(Ordering::Greater)
-                } else {
-                    // a very unlikely epoch 0:
-                    self.data = &self.data[this_pos + 1..];
-                    Ok(())
+    // Takes the leading number off `s`, without leading zeroes.
+    #[inline]
+    fn number<'a>(s: &mut &'a [u8]) -> &'a [u8] {
+        let zeroes = s.iter().take_while(|c| **c == b'0').count();
+        let digits = s[zeroes..]
+            .iter()
+            .take_while(|c| c.is_ascii_digit())
+            .count();
+        let number = &s[zeroes..zeroes + digits];
+        *s = &s[zeroes + digits..];
+        number
+    }
+
+    // Compares two parts of the version, as dpkg's `verrevcmp` does.
+    fn compare_part(mut this: &[u8], mut that: &[u8]) -> Ordering {
+        while !this.is_empty() || !that.is_empty() {
+            loop {
+                let (a, b) = (non_digit(this), non_digit(that));
+                if a.is_none() && b.is_none() {
+                    break;
                 }
-            } else if other.peek_at(that_pos) == Some(b':') {
-                // only that version has epoch
-                if that_maybe_epoch > 0 {
-                    Err(Ordering::Less)
-                } else {
-                    // a very unlikely epoch 0:
-                    other.data = &other.data[that_pos + 1..];
-                    Ok(())
+                match order(a).cmp(&order(b)) {
+                    Ordering::Equal => {}
+                    other => return other,
                 }
-            } else {
-                self.data = &self.data[this_pos..];
-                other.data = &other.data[that_pos..];
-                cmp(&this_maybe_epoch, &that_maybe_epoch)
+                // equal weights imply both are non-digit characters
+                this = &this[1..];
+                that = &that[1..];
             }
-        }
-        fn compare_alpha(&mut self, other: &mut Self) -> Result<()> {
-            loop {
-                let this = self.peek_char();
-                let that = other.peek_char();
-                match this {
-                    None => match that {
-                        None => break Ok(()),
-                        Some(b'~') => break Err(Ordering::Greater),
-                        Some(_) => break Err(Ordering::Less),
-                    },
-                    Some(b'~') => match that {
-                        None => break Err(Ordering::Less),
-                        Some(b'~') => {
-                            self.advance();
-                            other.advance();
-                            continue;
-                        }
-                        Some(_) => break Err(Ordering::Greater),
-                    },
-                    Some(left) => match that {
-                        None => break Err(Ordering::Greater),
-                        Some(b'~') => break Err(Ordering::Greater),
-                        Some(right) => {
-                            cmp_alpha(left, right)?;
-                            self.advance();
-                            other.advance();
-                            continue;
-                        }
-                    },
-                }
+            // Numbers are compared by value, however long they are: longer
+            // runs of digits without leading zeroes are greater.
+            let (a, b) = (number(&mut this), number(&mut that));
+            match a.len().cmp(&b.len()).then_with(|| a.cmp(b)) {
+                Ordering::Equal => {}
+                other => return other,
             }
         }
-        fn compare_num(&mut self, other: &mut Self) -> Result<()> {
-            let (this_num, this_pos) = self.peek_number(0);
-            let (that_num, that_pos) = other.peek_number(0);
-            self.data = &self.data[this_pos..];
-            other.data = &other.data[that_pos..];
-            cmp(&this_num, &that_num)
-        }
-        fn compare(&mut self, other: &mut Self) -> Result<()> {
-            self.compare_initial(other)?;
-            while self.data.len() > 0 || other.data.len() > 0 {
-                self.compare_alpha(other)?;
-                self.compare_num(other)?;
-            }
-            Ok(())
+        Ordering::Equal
+    }
+
+    // Splits the version into the epoch, the upstream version and the
+    // revision, the way dpkg's `parseversion` does.
+    fn split(version: &[u8]) -> (&[u8], &[u8], &[u8]) {
+        let (epoch, rest) = match memchr::memchr(b':', version) {
+            Some(pos) => (&version[..pos], &version[pos + 1..]),
+            None => (&b""[..], version),
+        };
+        match memchr::memrchr(b'-', rest) {
+            Some(pos) => (epoch, &rest[..pos], &rest[pos + 1..]),
+            None => (epoch, rest, &b""[..]),
         }
     }
 
     pub(super) fn compare(this: &[u8], that: &[u8]) -> Ordering {
         // Check for equality first, as it is significantly faster.
         if this == that {
-            Ordering::Equal
-        } else {
-            let mut this = VersionComparator { data: this };
-            let mut that = VersionComparator { data: that };
-            match this.compare(&mut that) {
-                Ok(()) => Ordering::Equal,
-                Err(ord) => ord,
-            }
+            return Ordering::Equal;
         }
+        let (this, that) = (split(this), split(that));
+        compare_part(this.0, that.0)
+            .then_with(|| compare_part(this.1, that.1))
+            .then_with(|| compare_part(this.2, that.2))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testutil::XorShift;
 
     macro_rules! assert {
         ($left:tt $op:tt $right:tt) => {
@@ -1238,6 +1656,52 @@ mod tests {
             .expect_err("invalid predicate, should fail");
     }
 
+    #[test]
+    fn test_restrictions() {
+        let names = |src, r: &Restrictions| -> Vec<String> {
+            ParsedDependencyIterator::new(src, r)
+                .map(|dep| match dep.unwrap() {
+                    Dependency::Single(c) => c.name().to_string(),
+                    Dependency::Union(u) => {
+                        u.iter().map(|c| *c.name()).collect::<Vec<_>>().join("|")
+                    }
+                })
+                .collect()
+        };
+        let src = "debhelper (>= 13), check <!nocheck>, doxygen <!nodoc> <doc>, \
+                   libcap-dev [linux-any] | libc-dev [!linux-any], mingw-w64 <cross !nocheck>";
+        let none = Restrictions::new();
+        assert_eq!(
+            names(src, &none),
+            ["debhelper", "check", "doxygen", "libcap-dev|libc-dev"]
+        );
+        let amd64 = Restrictions::new()
+            .arch("amd64")
+            .profiles(["nocheck", "doc"]);
+        assert_eq!(names(src, &amd64), ["debhelper", "doxygen", "libcap-dev"]);
+        let hurd = Restrictions::new()
+            .arch("hurd-i386")
+            .profiles(["cross", "nodoc"]);
+        assert_eq!(
+            names(src, &hurd),
+            ["debhelper", "check", "libc-dev", "mingw-w64"]
+        );
+        std::assert!(ParsedDependencyIterator::new("foo <nocheck", &none)
+            .next()
+            .unwrap()
+            .is_err());
+    }
+
+    #[test]
+    fn test_valid() {
+        std::assert!(Version::from("1:2.36-9+deb12u4").is_valid());
+        std::assert!(Version::from("0.0.0+2016.01.15.git.29cc9e1b05-2+b8").is_valid());
+        std::assert!(!Version::from("v1.0").is_valid());
+        std::assert!(!Version::from("x:1.0").is_valid());
+        std::assert!(!Version::from("1.0 beta").is_valid());
+        std::assert!(!Version::from("").is_valid());
+    }
+
     #[test]
     fn test_requirements() {
         satisfies!("1.0.1" "(>= 1.0.0)");
@@ -1283,4 +1747,314 @@ mod tests {
         assert!("0.0.0+2016.01.15.git.29cc9e1b05-2+b8" < "0.0.0+2016.02.15.git.29cc9e1b05");
         assert!("6.2.2006+really6.2.1905+dfsg-5.1+b1" == "6.2.2006+really6.2.1905+dfsg-5.1+b1");
     }
+
+    #[test]
+    fn test_dpkg_compat() {
+        let cmp = |a: &str, b: &str| Version::from(a).cmp_dpkg(&Version::from(b));
+        for (a, b, ord) in [
+            // a missing epoch or revision is 0
+            ("0:1.0", "1.0", Ordering::Equal),
+            ("1.0-0", "1.0", Ordering::Equal),
+            ("1.0", "1.0-1~", Ordering::Less),
+            ("1:0.1", "0:9.9", Ordering::Greater),
+            // the revision starts after the last hyphen
+            ("1.0-1", "1.0-0-1", Ordering::Less),
+            ("1.0-2-3", "1.0-10", Ordering::Greater),
+            // letters sort before non-letters, tilde before anything
+            ("1.0a", "1.0", Ordering::Greater),
+            ("1.0", "1.0~", Ordering::Greater),
+            ("1.0+", "1.0a", Ordering::Greater),
+            ("1.0.1", "1.0a", Ordering::Greater),
+            ("1.0~~", "1.0~", Ordering::Less),
+            ("1.0-1", "1.0-a", Ordering::Less),
+            // numbers compare by value
+            ("1.001", "1.1", Ordering::Equal),
+            ("1.0010", "1.9", Ordering::Greater),
+            (
+                "1.20230101000000000000",
+                "1.20230101000000000",
+                Ordering::Greater,
+            ),
+            (
+                "100000000000000000000:1",
+                "99999999999999999999:2",
+                Ordering::Greater,
+            ),
+            // colons are allowed in the upstream version after an epoch
+            ("1:1.0:2", "1:1.0:3", Ordering::Less),
+        ] {
+            assert_eq!(cmp(a, b), ord, "{} {:?} {}", a, ord, b);
+            assert_eq!(cmp(b, a), ord.reverse(), "{} {:?} {}", b, ord.reverse(), a);
+        }
+    }
+
+    // A direct transcription of dpkg's `verrevcmp` and `dpkg_version_compare`
+    fn dpkg_reference(a: &str, b: &str) -> Ordering {
+        fn order(c: u8) -> i32 {
+            if c.is_ascii_digit() || c == 0 {
+                0
+            } else if c.is_ascii_alphabetic() {
+                c as i32
+            } else if c == b'~' {
+                -1
+            } else {
+                c as i32 + 256
+            }
+        }
+        fn verrevcmp(a: &str, b: &str) -> i32 {
+            let (a, b) = (a.as_bytes(), b.as_bytes());
+            let at = |i: usize| a.get(i).copied().unwrap_or(0);
+            let bt = |i: usize| b.get(i).copied().unwrap_or(0);
+            let (mut i, mut j) = (0, 0);
+            while at(i) != 0 || bt(j) != 0 {
+                let mut first_diff = 0;
+                while (at(i) != 0 && !at(i).is_ascii_digit())
+                    || (bt(j) != 0 && !bt(j).is_ascii_digit())
+                {
+                    let (ac, bc) = (order(at(i)), order(bt(j)));
+                    if ac != bc {
+                        return ac - bc;
+                    }
+                    i += 1;
+                    j += 1;
+                }
+                while at(i) == b'0' {
+                    i += 1;
+                }
+                while bt(j) == b'0' {
+                    j += 1;
+                }
+                while at(i).is_ascii_digit() && bt(j).is_ascii_digit() {
+                    if first_diff == 0 {
+                        first_diff = at(i) as i32 - bt(j) as i32;
+                    }
+                    i += 1;
+                    j += 1;
+                }
+                if at(i).is_ascii_digit() {
+                    return 1;
+                }
+                if bt(j).is_ascii_digit() {
+                    return -1;
+                }
+                if first_diff != 0 {
+                    return first_diff;
+                }
+            }
+            0
+        }
+        fn parse(v: &str) -> (u64, &str, &str) {
+            let (epoch, rest) = match v.split_once(':') {
+                Some((epoch, rest)) => (epoch.parse().unwrap_or(0), rest),
+                None => (0, v),
+            };
+            let (upstream, revision) = rest.rsplit_once('-').unwrap_or((rest, ""));
+            (epoch, upstream, revision)
+        }
+        let (a, b) = (parse(a), parse(b));
+        a.0.cmp(&b.0)
+            .then_with(|| verrevcmp(a.1, b.1).cmp(&0))
+            .then_with(|| verrevcmp(a.2, b.2).cmp(&0))
+    }
+
+    #[test]
+    fn test_dpkg_random() {
+        let mut rng = XorShift::new(0x2545f4914f6cdd1d);
+        let mut next = move |n: usize| rng.below(n);
+        const CHARS: &[u8] = b"0001123456789aAbz.+-~~";
+        let mut version = || {
+            let mut v = String::new();
+            if next(4) == 0 {
+                v += &format!("{}:", next(3));
+            }
+            for _ in 0..next(10) {
+                v.push(CHARS[next(CHARS.len())] as char);
+            }
+            v
+        };
+        let versions: Vec<String> = (0..300).map(|_| version()).collect();
+        for a in &versions {
+            let va = Version::from(a.as_str());
+            std::assert!(va.cmp_dpkg(&va) == Ordering::Equal);
+            for b in &versions {
+                let vb = Version::from(b.as_str());
+                let ord = va.cmp_dpkg(&vb);
+                assert_eq!(ord, dpkg_reference(a, b), "{:?} vs {:?}", a, b);
+                assert_eq!(vb.cmp_dpkg(&va), ord.reverse(), "{:?} vs {:?}", a, b);
+            }
+        }
+        for _ in 0..20000 {
+            let w = [0, 1, 2].map(|_| &versions[next(versions.len())]);
+            let v = w.map(|v| Version::from(v.as_str()));
+            let (ab, bc) = (v[0].cmp_dpkg(&v[1]), v[1].cmp_dpkg(&v[2]));
+            if ab == bc || bc == Ordering::Equal {
+                assert_eq!(v[0].cmp_dpkg(&v[2]), ab, "{:?}", w);
+            } else if ab == Ordering::Equal {
+                assert_eq!(v[0].cmp_dpkg(&v[2]), bc, "{:?}", w);
+            }
+        }
+    }
+
+    #[test]
+    fn test_dpkg_vectors() {
+        // The results of `dpkg --compare-versions a op b`
+        let vectors = [
+            ("1.0", "eq", "1.0-0"),
+            ("1.0-0", "eq", "1.00"),
+            ("0:1.0", "eq", "1.0"),
+            ("1:0.9", "gt", "2.0"),
+            ("1.0~rc1", "lt", "1.0"),
+            ("1.0~~", "lt", "1.0~"),
+            ("1.0~", "lt", "1.0"),
+            ("1.0", "lt", "1.0+"),
+            ("1.0+", "gt", "1.0a"),
+            ("1.0a", "lt", "1.0."),
+            ("1.0.", "eq", "1.0.0"),
+            ("1.0~~a", "gt", "1.0~~"),
+            ("1.0-1", "lt", "1.0-1.1"),
+            ("1.0-1", "lt", "1.0-1ubuntu1"),
+            ("1.0-1~bpo12+1", "lt", "1.0-1"),
+            ("1.0-1+deb12u1", "gt", "1.0-1+b1"),
+            ("2.36-9", "lt", "2.36-9+deb12u4"),
+            ("9", "lt", "10"),
+            ("1.0+dfsg-1", "gt", "1.0dfsg-1"),
+            ("1.2.3-4-5", "gt", "1.2.3-4"),
+            ("1.2.3-4-5", "gt", "1.2.3-45"),
+            ("7.0.0~beta-1", "lt", "7.0.0-1"),
+            ("1.0-A", "lt", "1.0-a"),
+            ("1.0-z", "lt", "1.0-+"),
+            ("0.0001", "eq", "0.1"),
+            ("1.0~a", "gt", "1.0~A"),
+            ("1:1.0~", "lt", "1:1.0"),
+            ("2:0", "gt", "1:9999"),
+            ("1.0-0.1", "gt", "1.0"),
+            ("1.0-1a", "lt", "1.0-1+"),
+            ("3.0.0+git20240101", "gt", "3.0.0"),
+            ("1.18.0ubuntu1", "lt", "1.18.0+dfsg"),
+            ("0~", "lt", "0"),
+            ("1.0a~", "lt", "1.0a"),
+            ("1.01", "eq", "1.1"),
+            ("1.0.9", "lt", "1.0.10"),
+        ];
+        for (a, op, b) in vectors {
+            let expected = match op {
+                "lt" => Ordering::Less,
+                "eq" => Ordering::Equal,
+                _ => Ordering::Greater,
+            };
+            let (va, vb) = (Version::from(a), Version::from(b));
+            assert_eq!(va.cmp_dpkg(&vb), expected, "{} {} {}", a, op, b);
+            assert_eq!(vb.cmp_dpkg(&va), expected.reverse(), "{} {} {}", a, op, b);
+            assert_eq!(dpkg_reference(a, b), expected, "{} {} {}", a, op, b);
+        }
+    }
+
+    #[test]
+    fn test_parts() {
+        let v = Version::from("1:2.36-9+deb12u4");
+        assert_eq!((v.epoch(), v.upstream()), (1, "2.36"));
+        assert_eq!(v.debian_revision(), Some("9+deb12u4"));
+        let native = Version::from("0.9-rc1-ish");
+        assert_eq!((native.epoch(), native.upstream()), (0, "0.9-rc1"));
+        assert_eq!(Version::from("1.0").debian_revision(), None);
+
+        assert_eq!(v.with_revision("10"), "1:2.36-10");
+        assert_eq!(Version::from("2.36").with_revision("1"), "2.36-1");
+        assert_eq!(Version::from("2.36-1").with_revision(""), "2.36");
+
+        assert_eq!(Version::from("1.0-2").next_revision(), "1.0-2+b1");
+        assert_eq!(Version::from("1:1.0-2+b9").next_revision(), "1:1.0-2+b10");
+        assert_eq!(Version::from("2024.1").next_revision(), "2024.1+b1");
+        let v = Version::from("1.0-2");
+        assert_eq!(v.next_revision().cmp_dpkg(&v), Ordering::Greater);
+    }
+
+    #[test]
+    fn test_compare_versions() {
+        for (a, op, b, expected) in [
+            ("1.0", "lt", "1.0-1", true),
+            ("1:1.0", "gt", "2.0", true),
+            ("1.0", "eq", "0:1.00", true),
+            ("1.0", "ne", "1.0", false),
+            ("1.0~rc1", "<<", "1.0", true),
+            ("1.0", "<", "1.0", true),
+            ("1.0", ">", "1.0", true),
+            ("1.0", ">>", "1.0", false),
+            ("", "lt", "1.0", true),
+            ("", "lt-nl", "1.0", false),
+            ("1.0", "gt-nl", "", false),
+            ("", "eq", "", true),
+            ("", "ge-nl", "", true),
+        ] {
+            assert_eq!(
+                compare_versions(a, op, b).unwrap(),
+                expected,
+                "{} {} {}",
+                a,
+                op,
+                b
+            );
+        }
+        std::assert!(compare_versions("1.0", "eq-nl", "1.0").is_err());
+        std::assert!(compare_versions("1.0", "=>", "1.0").is_err());
+    }
+
+    #[test]
+    fn test_version_range() {
+        let set =
+            |s: &'static str| VersionSet::parse(&mut Parser { inp: s.as_bytes() }, true).unwrap();
+        let v = Version::from;
+
+        let range = VersionRange::all_of(&[set("(>= 1.0)"), set("(>= 1.2)"), set("(<< 2)")]);
+        std::assert!(range.contains(&v("1.2")) && !range.contains(&v("1.1")));
+        std::assert!(!range.contains(&v("2")) && range.contains(&v("2~rc1")));
+        assert_eq!(
+            range.to_version_sets().unwrap(),
+            [set("(>= 1.2)"), set("(<< 2)")]
+        );
+
+        let glibc = set("(>= 2.35.1~)").intersect(&set("(<< 2.35.1A)"));
+        std::assert!(glibc.contains(&v("2.35.1-1")));
+        assert_eq!(glibc.to_version_sets().unwrap().len(), 2);
+        std::assert!(set("(>= 2)").intersect(&set("(<< 2)")).is_empty());
+        let except = VersionSet::Except(v("1.0"));
+        std::assert!(VersionRange::all_of(&[set("(= 1.0)"), except]).is_empty());
+        assert_eq!(
+            set("(>= 2)")
+                .intersect(&set("(<= 2)"))
+                .to_version_sets()
+                .unwrap(),
+            [set("(= 2)")]
+        );
+
+        let (one, two) = (VersionSet::Except(v("1")), VersionSet::Except(v("2")));
+        let holes = VersionRange::all_of(&[one.clone(), two.clone(), set("(>> 0.5)")]);
+        assert_eq!(
+            holes.to_version_sets().unwrap(),
+            [set("(>> 0.5)"), one, two]
+        );
+        let split = set("(<< 1)").union(&set("(>> 2)"));
+        std::assert!(split.to_version_sets().is_none());
+        assert_eq!(
+            split
+                .union(&(&set("(>= 1)")).into())
+                .to_version_sets()
+                .unwrap(),
+            [VersionSet::Any]
+        );
+        assert_eq!(
+            set("(<= 1)")
+                .union(&set("(>> 1)"))
+                .to_version_sets()
+                .unwrap(),
+            [VersionSet::Any]
+        );
+        assert_eq!(
+            set("(<< 1)")
+                .union(&set("(>> 1)"))
+                .to_version_sets()
+                .unwrap(),
+            [VersionSet::Except(v("1"))]
+        );
+    }
 }